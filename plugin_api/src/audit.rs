@@ -0,0 +1,177 @@
+// An append-only audit log of privileged actions: forwards into clusters, exec sessions
+// and exec-based transports, HTTPS interception and generated commands being run. Every
+// process appends one JSON line per action to `audit/audit.log` in the state directory,
+// or to $PROXY_AUDIT_LOG, with the time, user, host, pid, plugin and the action's details.
+// Actions against a cluster also carry the kubeconfig context and its cluster.
+// `proxy audit` exports the log.
+use chrono::{SecondsFormat, Utc};
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Where the audit log is kept
+pub fn log_path() -> Option<PathBuf> {
+    match std::env::var_os("PROXY_AUDIT_LOG") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => crate::plugin_state_dir("audit").map(|dir| dir.join("audit.log")),
+    }
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut name = [0u8; 256];
+    if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } != 0 {
+        return String::new();
+    }
+    let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..end]).to_string()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+/// Stands in for the user name when the environment doesn't give one
+#[cfg(unix)]
+fn user_id() -> String {
+    format!("uid {}", unsafe { libc::getuid() })
+}
+
+#[cfg(not(unix))]
+fn user_id() -> String {
+    String::new()
+}
+
+fn append(line: &str) -> io::Result<()> {
+    let path =
+        log_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // One write per entry, so entries of concurrent processes don't interleave
+    options
+        .open(&path)?
+        .write_all(format!("{}\n", line).as_bytes())
+}
+
+/// Records a privileged action, e.g. `record("k8s_exec", "pod_exec", &[("pod", "api-0")])`
+pub fn record(plugin: &str, action: &str, details: &[(&str, &str)]) {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| user_id());
+    let mut line = format!(
+        "{{\"time\":{},\"user\":{},\"host\":{},\"pid\":{},\"plugin\":{},\"action\":{}",
+        json!(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+        json!(user),
+        json!(hostname()),
+        std::process::id(),
+        json!(plugin),
+        json!(action)
+    );
+    for (key, value) in details {
+        line.push_str(&format!(",{}:{}", json!(key), json!(value)));
+    }
+    line.push('}');
+    if let Err(e) = append(&line) {
+        // Once per process, so a broken log doesn't drown the plugin's output
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            eprintln!("⚠️  Could not write the audit log: {}", e);
+        }
+    }
+}
+
+/// The kubeconfig files in use, the first one setting a value winning
+fn kubeconfigs() -> Vec<PathBuf> {
+    match std::env::var_os("KUBECONFIG") {
+        Some(paths) => std::env::split_paths(&paths).collect(),
+        None => dirs::home_dir()
+            .map(|home| home.join(".kube").join("config"))
+            .into_iter()
+            .collect(),
+    }
+}
+
+fn yaml_value(line: &str, key: &str) -> Option<String> {
+    let value = line
+        .trim_start()
+        .trim_start_matches("- ")
+        .strip_prefix(key)?;
+    let value = value.strip_prefix(':')?.trim().trim_matches(['"', '\'']);
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// The kubeconfig's current context
+pub fn kube_context() -> Option<String> {
+    kubeconfigs().into_iter().find_map(|path| {
+        fs::read_to_string(path)
+            .ok()?
+            .lines()
+            .filter(|line| line.starts_with("current-context"))
+            .find_map(|line| yaml_value(line, "current-context"))
+    })
+}
+
+/// The cluster a kubeconfig context points at
+fn kube_cluster(context: &str) -> Option<String> {
+    for path in kubeconfigs() {
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        // Entries of the top-level contexts list, the cluster sitting in each entry's
+        // context; a blank line at the end closes the last entry
+        let (mut in_contexts, mut name, mut cluster) = (false, None, None);
+        for line in content.lines().chain(std::iter::once("")) {
+            let top_level = !line.starts_with([' ', '-']);
+            let indent = line.len() - line.trim_start().len();
+            let entry_start = line.trim_start().starts_with('-') && indent <= 2;
+            if in_contexts && (top_level || entry_start) {
+                if name.as_deref() == Some(context) && cluster.is_some() {
+                    return cluster;
+                }
+                (name, cluster) = (None, None);
+            }
+            if top_level {
+                in_contexts = line.starts_with("contexts:");
+                continue;
+            }
+            if !in_contexts {
+                continue;
+            }
+            if let Some(value) = yaml_value(line, "name") {
+                name = Some(value);
+            } else if let Some(value) = yaml_value(line, "cluster") {
+                cluster = Some(value);
+            }
+        }
+    }
+    None
+}
+
+/// `record` for an action against a cluster, adding the context (the kubeconfig's current
+/// one unless the details name it) and its cluster
+pub fn record_k8s(plugin: &str, action: &str, details: &[(&str, &str)]) {
+    let given = details
+        .iter()
+        .find(|(key, _)| *key == "context")
+        .map(|(_, context)| context.to_string());
+    let context = given.clone().or_else(kube_context).unwrap_or_default();
+    let cluster = kube_cluster(&context).unwrap_or_default();
+    let mut details = details.to_vec();
+    if given.is_none() {
+        details.push(("context", &context));
+    }
+    details.push(("cluster", &cluster));
+    record(plugin, action, &details);
+}
@@ -0,0 +1,408 @@
+// Checking a plugin's config against what the plugin reads from it. Plugins deserialize
+// their TOML config with serde, which skips keys it doesn't know and stops at the first
+// value of the wrong type; `validate_toml` deserializes it the same way, through a
+// deserializer that notes each key a struct has no field for and where an error happened,
+// so a misspelled setting is reported instead of quietly left at its default.
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess,
+    Visitor,
+};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+
+/// A problem in a plugin's config, see `Plugin::validate_config`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigError {
+    /// Where in the config, e.g. `forward[1].local_port` or `line 3`; empty for all of it
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Checks `content` as the TOML config a plugin reads into `T`: whether it parses, and
+/// reports keys `T` has no field for, values of the wrong type and missing required fields
+pub fn validate_toml<T: DeserializeOwned>(content: &str) -> Result<(), Vec<ConfigError>> {
+    let table: toml::Table = match toml::from_str(content) {
+        Ok(table) => table,
+        Err(e) => {
+            let path = e
+                .span()
+                .map(|span| format!("line {}", content[..span.start].matches('\n').count() + 1))
+                .unwrap_or_default();
+            return Err(vec![ConfigError {
+                path,
+                message: e.message().trim().replace('\n', ", "),
+            }]);
+        }
+    };
+    let unknown = RefCell::new(Vec::new());
+    let result = T::deserialize(Checked {
+        value: toml::Value::Table(table),
+        path: String::new(),
+        unknown: &unknown,
+    });
+    let mut errors: Vec<ConfigError> = unknown
+        .into_inner()
+        .into_iter()
+        .map(|(path, fields): (String, &[&str])| ConfigError {
+            path,
+            message: format!(
+                "unknown key, expected one of {}",
+                fields
+                    .iter()
+                    .map(|field| format!("`{}`", field))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        })
+        .collect();
+    if let Err(e) = result {
+        errors.push(ConfigError {
+            path: e.path.unwrap_or_default(),
+            message: e.message,
+        });
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Keys structs had no field for, with the fields they have
+type Unknown<'a> = &'a RefCell<Vec<(String, &'static [&'static str])>>;
+
+#[derive(Debug)]
+struct Error {
+    /// The value it happened at, set by the innermost one
+    path: Option<String>,
+    message: String,
+}
+
+impl Error {
+    fn at(mut self, path: &str) -> Self {
+        self.path.get_or_insert_with(|| path.to_string());
+        self
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error {
+            path: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// A value of the config and where it is
+struct Checked<'a> {
+    value: toml::Value,
+    path: String,
+    unknown: Unknown<'a>,
+}
+
+impl<'de> Deserializer<'de> for Checked<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let result = match self.value {
+            toml::Value::Table(table) => visitor.visit_map(Table {
+                entries: table.into_iter(),
+                value: None,
+                path: self.path.clone(),
+                unknown: self.unknown,
+            }),
+            toml::Value::Array(array) => visitor.visit_seq(Array {
+                entries: array.into_iter().enumerate(),
+                path: self.path.clone(),
+                unknown: self.unknown,
+            }),
+            value => value
+                .deserialize_any(visitor)
+                .map_err(|e| de::Error::custom(e.message())),
+        };
+        result.map_err(|e| e.at(&self.path))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if let toml::Value::Table(table) = &self.value {
+            let mut unknown = self.unknown.borrow_mut();
+            for key in table.keys() {
+                if !fields.contains(&key.as_str()) {
+                    unknown.push((join(&self.path, key), fields));
+                }
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // TOML has no null: a value that is there is Some
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let path = self.path;
+        self.value
+            .deserialize_enum(name, variants, visitor)
+            .map_err(|e| de::Error::custom(e.message()))
+            .map_err(|e: Error| e.at(&path))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        byte_buf unit unit_struct seq tuple tuple_struct map identifier ignored_any
+    }
+}
+
+struct Table<'a> {
+    entries: toml::map::IntoIter,
+    value: Option<(String, toml::Value)>,
+    path: String,
+    unknown: Unknown<'a>,
+}
+
+impl<'de> MapAccess<'de> for Table<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some((key.clone(), value));
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| <Error as de::Error>::custom("value without a key"))?;
+        seed.deserialize(Checked {
+            value,
+            path: join(&self.path, &key),
+            unknown: self.unknown,
+        })
+    }
+}
+
+struct Array<'a> {
+    entries: std::iter::Enumerate<std::vec::IntoIter<toml::Value>>,
+    path: String,
+    unknown: Unknown<'a>,
+}
+
+impl<'de> SeqAccess<'de> for Array<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let Some((index, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        seed.deserialize(Checked {
+            value,
+            path: format!("{}[{}]", self.path, index),
+            unknown: self.unknown,
+        })
+        .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Config {
+        listen: String,
+        #[serde(default)]
+        verbose: bool,
+        timeout: Option<u64>,
+        upstream: Option<Upstream>,
+        #[serde(default)]
+        forward: Vec<Forward>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Upstream {
+        host: String,
+        port: Option<u16>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Forward {
+        name: String,
+        local_port: u16,
+        #[serde(default)]
+        kind: Kind,
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Kind {
+        #[default]
+        Pod,
+        Service,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Flattened {
+        name: String,
+        #[serde(flatten)]
+        upstream: Upstream,
+        #[serde(flatten)]
+        rest: BTreeMap<String, toml::Value>,
+    }
+
+    fn paths(errors: &[ConfigError]) -> Vec<&str> {
+        errors.iter().map(|error| error.path.as_str()).collect()
+    }
+
+    #[test]
+    fn a_valid_config_passes() {
+        let config = r#"
+            listen = "127.0.0.1:1080"
+            timeout = 5
+            [upstream]
+            host = "bastion"
+            [[forward]]
+            name = "web"
+            local_port = 8080
+            kind = "service"
+        "#;
+        assert_eq!(validate_toml::<Config>(config), Ok(()));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_in_nested_tables_and_arrays_of_tables() {
+        let config = r#"
+            listen = "127.0.0.1:1080"
+            verbos = true
+            [upstream]
+            host = "bastion"
+            prot = 22
+            [[forward]]
+            name = "web"
+            local_port = 8080
+            [[forward]]
+            name = "db"
+            local_port = 5432
+            remote = 5432
+        "#;
+        let errors = validate_toml::<Config>(config).unwrap_err();
+        // In the order of the keys, which TOML tables keep sorted
+        assert_eq!(
+            paths(&errors),
+            ["verbos", "forward[1].remote", "upstream.prot"]
+        );
+        assert!(errors[2].message.contains("`host`, `port`"));
+    }
+
+    #[test]
+    fn wrong_value_types_are_reported_where_they_are() {
+        let errors = validate_toml::<Config>(
+            "listen = \"x\"\n[[forward]]\nname = \"web\"\nlocal_port = \"8080\"\n",
+        )
+        .unwrap_err();
+        assert_eq!(paths(&errors), ["forward[0].local_port"]);
+        assert!(errors[0].message.contains("u16"));
+
+        let errors = validate_toml::<Config>("listen = \"x\"\n[upstream]\nhost = 1\n").unwrap_err();
+        assert_eq!(paths(&errors), ["upstream.host"]);
+
+        let errors = validate_toml::<Config>(
+            "listen = \"x\"\n[[forward]]\nname = \"web\"\nlocal_port = 1\nkind = \"node\"\n",
+        )
+        .unwrap_err();
+        assert_eq!(paths(&errors), ["forward[0].kind"]);
+        assert!(errors[0].message.contains("node"));
+    }
+
+    #[test]
+    fn missing_required_fields_are_reported_at_their_table() {
+        let errors = validate_toml::<Config>("timeout = 5\n").unwrap_err();
+        assert_eq!(paths(&errors), [""]);
+        assert!(errors[0].message.contains("`listen`"));
+
+        let errors =
+            validate_toml::<Config>("listen = \"x\"\n[[forward]]\nname = \"web\"\n").unwrap_err();
+        assert_eq!(paths(&errors), ["forward[0]"]);
+        assert!(errors[0].message.contains("`local_port`"));
+    }
+
+    #[test]
+    fn default_and_flattened_fields_are_not_unknown() {
+        assert_eq!(
+            validate_toml::<Config>("listen = \"x\"\nverbose = true\nforward = []\n"),
+            Ok(())
+        );
+        assert_eq!(
+            validate_toml::<Flattened>("name = \"a\"\nhost = \"b\"\nport = 22\nextra = 1\n"),
+            Ok(())
+        );
+        let errors = validate_toml::<Flattened>("name = \"a\"\nport = 22\n").unwrap_err();
+        assert!(errors[0].message.contains("`host`"));
+    }
+
+    #[test]
+    fn toml_syntax_errors_name_their_line() {
+        let errors = validate_toml::<Config>("listen = \"x\"\ntimeout = \n").unwrap_err();
+        assert_eq!(paths(&errors), ["line 2"]);
+    }
+}
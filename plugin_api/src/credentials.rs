@@ -0,0 +1,390 @@
+// Cloud credentials watched for the lifetime of a long-running plugin. AWS SSO logins and
+// Google credentials expire while forwards are up; a background thread checks every
+// watched source each minute and, when it is about to expire or has stopped working, runs
+// the configured refresh command, or the provider's login flow when there is a terminal to
+// complete it in. Plugins subscribe to be told when credentials were renewed, to rebuild
+// what was started with the old ones.
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::periodic::Periodic;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Credentials expiring within this are refreshed ahead of time
+const REFRESH_AHEAD: chrono::Duration = chrono::Duration::minutes(10);
+/// A refresh that didn't help isn't tried again before this
+const RETRY_AFTER: Duration = Duration::from_secs(300);
+/// `aws` exits with this on a command it doesn't know, e.g. export-credentials before 2.9
+const AWS_USAGE_ERROR: i32 = 252;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// An AWS profile, the CLI's default credentials without one
+    Aws { profile: Option<String> },
+    /// Application Default Credentials when `adc`, the gcloud login otherwise
+    Gcp { adc: bool },
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Aws {
+                profile: Some(profile),
+            } => write!(f, "AWS profile {}", profile),
+            Source::Aws { profile: None } => write!(f, "AWS default credentials"),
+            Source::Gcp { adc: true } => write!(f, "Application Default Credentials"),
+            Source::Gcp { adc: false } => write!(f, "gcloud login"),
+        }
+    }
+}
+
+enum Status {
+    /// Working, until the given time when it is known
+    Valid(Option<DateTime<Utc>>),
+    Invalid(String),
+    /// The tools can't tell, so the source isn't watched
+    Unsupported(String),
+}
+
+impl Source {
+    fn check(&self) -> Status {
+        let mut command = match self {
+            Source::Aws { profile } => {
+                let mut command = Command::new("aws");
+                if let Some(profile) = profile {
+                    command.arg("--profile").arg(profile);
+                }
+                command.args(["configure", "export-credentials", "--format", "process"]);
+                command
+            }
+            Source::Gcp { adc } => {
+                let mut command = Command::new("gcloud");
+                command.arg("auth");
+                if *adc {
+                    command.arg("application-default");
+                }
+                command.arg("print-access-token");
+                command
+            }
+        };
+        let output = match command.stdin(Stdio::null()).output() {
+            Ok(output) => output,
+            Err(e) => return Status::Unsupported(e.to_string()),
+        };
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().last().unwrap_or_default().trim().to_string();
+        if matches!(self, Source::Aws { .. }) && output.status.code() == Some(AWS_USAGE_ERROR) {
+            return Status::Unsupported(format!("aws configure export-credentials: {}", reason));
+        }
+        if !output.status.success() {
+            return Status::Invalid(reason);
+        }
+        // Only SSO logins need renewing ahead of time: the CLI renews role credentials by
+        // itself and gcloud its access tokens
+        match self {
+            Source::Aws { profile } => Status::Valid(sso_expiry(profile.as_deref())),
+            Source::Gcp { .. } => Status::Valid(None),
+        }
+    }
+
+    /// What renews the credentials when no refresh command is configured
+    fn login_command(&self) -> Vec<String> {
+        let words: &[&str] = match self {
+            Source::Aws { .. } => &["aws", "sso", "login"],
+            Source::Gcp { adc: true } => &["gcloud", "auth", "application-default", "login"],
+            Source::Gcp { adc: false } => &["gcloud", "auth", "login"],
+        };
+        let mut command: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+        if let Source::Aws {
+            profile: Some(profile),
+        } = self
+        {
+            command.extend(["--profile".to_string(), profile.clone()]);
+        }
+        command
+    }
+}
+
+/// The string value of `key` in a JSON document, without a JSON parser
+fn json_value<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let quoted = format!("\"{}\"", key);
+    let rest = &json[json.find(&quoted)? + quoted.len()..];
+    let rest = rest
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start()
+        .strip_prefix('"')?;
+    Some(&rest[..rest.find('"')?])
+}
+
+/// The sections of an AWS config file, by header, e.g. "profile prod" or "sso-session corp"
+fn aws_config_sections() -> HashMap<String, HashMap<String, String>> {
+    let path = match std::env::var_os("AWS_CONFIG_FILE") {
+        Some(path) => PathBuf::from(path),
+        None => match dirs::home_dir() {
+            Some(home) => home.join(".aws").join("config"),
+            None => return HashMap::new(),
+        },
+    };
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = String::new();
+    for line in fs::read_to_string(path).unwrap_or_default().lines() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = header.trim().to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            sections
+                .entry(current.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
+/// When the SSO login behind an AWS profile expires, for profiles using SSO
+fn sso_expiry(profile: Option<&str>) -> Option<DateTime<Utc>> {
+    let profile = profile
+        .map(str::to_string)
+        .or_else(|| std::env::var("AWS_PROFILE").ok())
+        .unwrap_or_else(|| "default".to_string());
+    let sections = aws_config_sections();
+    let header = match profile.as_str() {
+        "default" => "default".to_string(),
+        profile => format!("profile {}", profile),
+    };
+    let section = sections.get(&header)?;
+    let start_url = match section.get("sso_session") {
+        Some(session) => sections
+            .get(&format!("sso-session {}", session))?
+            .get("sso_start_url")?,
+        None => section.get("sso_start_url")?,
+    };
+
+    // The CLI caches the login's token under a hashed name, so look for it by URL
+    let cache = dirs::home_dir()?.join(".aws").join("sso").join("cache");
+    fs::read_dir(cache)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .filter(|token| {
+            json_value(token, "startUrl") == Some(start_url.as_str())
+                && json_value(token, "accessToken").is_some()
+        })
+        .filter_map(|token| {
+            DateTime::parse_from_rfc3339(json_value(&token, "expiresAt")?)
+                .ok()
+                .map(|time| time.with_timezone(&Utc))
+        })
+        .max()
+}
+
+#[derive(Default)]
+struct State {
+    valid: bool,
+    unsupported: bool,
+    last_refresh: Option<Instant>,
+    /// The expiry was reported; done once until the credentials are good for a while
+    reported: bool,
+}
+
+/// Credentials being watched, shared by everything using the same source
+pub struct Credentials {
+    source: Source,
+    refresh_command: Option<String>,
+    state: Mutex<State>,
+    /// Held while checking, so a check after a failure and the periodic one don't both
+    /// start a login
+    checking: Mutex<()>,
+    renewed: watch::Sender<u64>,
+}
+
+static WATCHED: Mutex<Vec<Arc<Credentials>>> = Mutex::new(Vec::new());
+static CHECKING: Periodic = Periodic::new();
+
+extern "C" fn stop_checking() {
+    CHECKING.stop();
+}
+
+/// Starts watching `source`, renewing it with `refresh_command` (run by the shell) when
+/// given. Watching the same source again returns the same credentials.
+pub fn watch(source: Source, refresh_command: Option<String>) -> Arc<Credentials> {
+    let mut watched = WATCHED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(credentials) = watched.iter().find(|c| c.source == source) {
+        return credentials.clone();
+    }
+    let credentials = Arc::new(Credentials {
+        source,
+        refresh_command,
+        state: Mutex::new(State {
+            valid: true,
+            ..State::default()
+        }),
+        checking: Mutex::new(()),
+        renewed: watch::Sender::new(0),
+    });
+    if watched.is_empty() {
+        CHECKING.start(CHECK_INTERVAL, check_all);
+        unsafe {
+            libc::atexit(stop_checking);
+        }
+    }
+    watched.push(credentials.clone());
+    credentials
+}
+
+fn check_all() {
+    let watched = WATCHED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    for credentials in watched {
+        credentials.check();
+    }
+}
+
+impl Credentials {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    /// Whether the credentials worked when last checked
+    pub fn is_valid(&self) -> bool {
+        self.state().valid
+    }
+
+    /// Changes every time the credentials were renewed
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.renewed.subscribe()
+    }
+
+    fn renewed(&self) {
+        println!("🔑 {} renewed", self.source);
+        self.renewed.send_modify(|generation| *generation += 1);
+    }
+
+    /// Checks the credentials now, e.g. after a failure, renewing them when needed. Blocks
+    /// while a login runs.
+    pub fn check(&self) {
+        let _checking = self.checking.lock().unwrap_or_else(|e| e.into_inner());
+        if self.state().unsupported {
+            return;
+        }
+        let reason = match self.source.check() {
+            Status::Valid(expires) => {
+                let was_valid = std::mem::replace(&mut self.state().valid, true);
+                if !was_valid {
+                    // Renewed outside, e.g. a login in another terminal
+                    self.renewed();
+                }
+                match expires {
+                    Some(expires) if expires - Utc::now() < REFRESH_AHEAD => {
+                        let minutes = (expires - Utc::now()).num_minutes().max(0);
+                        format!("expires in {} min", minutes)
+                    }
+                    _ => {
+                        self.state().reported = false;
+                        return;
+                    }
+                }
+            }
+            Status::Invalid(reason) => {
+                self.state().valid = false;
+                format!("stopped working ({})", reason)
+            }
+            Status::Unsupported(reason) => {
+                eprintln!("⚠️  Not watching {} for expiry: {}", self.source, reason);
+                self.state().unsupported = true;
+                return;
+            }
+        };
+        self.refresh(&reason);
+    }
+
+    fn refresh(&self, reason: &str) {
+        let command = match &self.refresh_command {
+            Some(command) => command.clone(),
+            None => self.source.login_command().join(" "),
+        };
+        {
+            let mut state = self.state();
+            if state
+                .last_refresh
+                .is_some_and(|last| last.elapsed() < RETRY_AFTER)
+            {
+                return;
+            }
+            // A login flow needs someone to complete it
+            if self.refresh_command.is_none() && !std::io::stdin().is_terminal() {
+                if !std::mem::replace(&mut state.reported, true) {
+                    eprintln!("⚠️  {} {}", self.source, reason);
+                    eprintln!("💡 Renew with: {}", command);
+                }
+                return;
+            }
+            state.last_refresh = Some(Instant::now());
+        }
+
+        println!("🔑 {} {}, renewing with: {}", self.source, reason, command);
+        let status = match &self.refresh_command {
+            Some(command) => {
+                let mut shell = shell(command);
+                if let Source::Aws {
+                    profile: Some(profile),
+                } = &self.source
+                {
+                    shell.env("AWS_PROFILE", profile);
+                }
+                shell.status()
+            }
+            None => {
+                let login = self.source.login_command();
+                Command::new(&login[0]).args(&login[1..]).status()
+            }
+        };
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                eprintln!("❌ Renewing {} failed ({})", self.source, status);
+                return;
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to run {}: {}", command, e);
+                return;
+            }
+        }
+        match self.source.check() {
+            Status::Valid(_) => {
+                self.state().valid = true;
+                self.renewed();
+            }
+            Status::Invalid(reason) | Status::Unsupported(reason) => {
+                eprintln!("❌ {} still not working: {}", self.source, reason);
+            }
+        }
+    }
+}
+
+/// `command` run by the shell
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
@@ -0,0 +1,279 @@
+// Reaching ports of Docker and Podman containers through the Engine API (bollard),
+// shared by the container plugins; built with the `docker` feature. Podman serves the
+// same API, so its socket works as well: pass a socket (or set DOCKER_HOST), or leave
+// both unset to try Docker's default socket and then Podman's rootless and rootful ones.
+use anyhow::{anyhow, Context, Result};
+use bollard::container::{ListContainersOptions, LogOutput};
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::models::ContainerSummary;
+use bollard::API_DEFAULT_VERSION;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_util::io::StreamReader;
+
+pub use bollard::Docker;
+
+const TIMEOUT_SECS: u64 = 120;
+/// How long a container IP gets to answer before falling back to exec
+const IP_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How connections reach the container port
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Via {
+    /// Published port, then container IP, then exec
+    #[default]
+    Auto,
+    Published,
+    Ip,
+    Exec,
+}
+
+/// Which running container to use
+#[derive(Debug, Clone)]
+pub enum Selector {
+    Name(String),
+    /// "key=value" labels that must all match
+    Labels(Vec<String>),
+}
+
+impl std::fmt::Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Selector::Name(name) => write!(f, "{}", name),
+            Selector::Labels(labels) => write!(f, "label:{}", labels.join(",")),
+        }
+    }
+}
+
+/// A way into the container port; `how` is printed with the connection
+pub struct Connection {
+    pub reader: Box<dyn AsyncRead + Unpin + Send>,
+    pub writer: Box<dyn AsyncWrite + Unpin + Send>,
+    pub how: String,
+}
+
+fn podman_sockets() -> Vec<PathBuf> {
+    let mut sockets = Vec::new();
+    if let Some(runtime) = std::env::var_os("XDG_RUNTIME_DIR") {
+        sockets.push(PathBuf::from(runtime).join("podman/podman.sock"));
+    }
+    sockets.push(PathBuf::from("/run/podman/podman.sock"));
+    sockets
+}
+
+pub fn connect(socket: Option<&str>) -> Result<Docker> {
+    if let Some(socket) = socket {
+        return Docker::connect_with_socket(socket, TIMEOUT_SECS, API_DEFAULT_VERSION)
+            .with_context(|| format!("Failed to connect to {}", socket));
+    }
+    if cfg!(unix)
+        && std::env::var_os("DOCKER_HOST").is_none()
+        && !std::path::Path::new("/var/run/docker.sock").exists()
+    {
+        if let Some(podman) = podman_sockets().into_iter().find(|path| path.exists()) {
+            return Docker::connect_with_socket(
+                &podman.to_string_lossy(),
+                TIMEOUT_SECS,
+                API_DEFAULT_VERSION,
+            )
+            .with_context(|| format!("Failed to connect to {}", podman.display()));
+        }
+    }
+    Docker::connect_with_local_defaults().context("Failed to connect to the Docker socket")
+}
+
+fn display_name(container: &ContainerSummary) -> String {
+    container
+        .names
+        .as_ref()
+        .and_then(|names| names.first())
+        .map(|name| name.trim_start_matches('/').to_string())
+        .or_else(|| container.id.clone())
+        .unwrap_or_default()
+}
+
+/// The running container to forward to. The name filter of the API matches substrings,
+/// so an exact name wins over the other matches.
+async fn find_container(docker: &Docker, selector: &Selector) -> Result<ContainerSummary> {
+    let filters = match selector {
+        Selector::Name(name) => HashMap::from([("name".to_string(), vec![name.clone()])]),
+        Selector::Labels(labels) => HashMap::from([("label".to_string(), labels.clone())]),
+    };
+    let containers = docker
+        .list_containers(Some(ListContainersOptions {
+            filters,
+            ..Default::default()
+        }))
+        .await?;
+    let exact = match selector {
+        Selector::Name(name) => containers
+            .iter()
+            .find(|container| display_name(container) == *name),
+        Selector::Labels(_) => None,
+    };
+    exact
+        .or_else(|| containers.first())
+        .cloned()
+        .ok_or_else(|| anyhow!("No running container matches {}", selector))
+}
+
+fn published_port(container: &ContainerSummary, port: u16) -> Option<u16> {
+    container.ports.as_ref()?.iter().find_map(|binding| {
+        (binding.private_port == port)
+            .then_some(binding.public_port)
+            .flatten()
+    })
+}
+
+/// The container's IPv4 address, its IPv6 one on IPv6-only networks
+fn container_ip(container: &ContainerSummary) -> Option<String> {
+    let networks = container.network_settings.as_ref()?.networks.as_ref()?;
+    let ipv4 = networks
+        .values()
+        .filter_map(|network| network.ip_address.clone())
+        .find(|ip| !ip.is_empty());
+    ipv4.or_else(|| {
+        networks
+            .values()
+            .filter_map(|network| network.global_ipv6_address.clone())
+            .find(|ip| !ip.is_empty())
+    })
+}
+
+fn tcp(stream: TcpStream, how: String) -> Connection {
+    let (reader, writer) = stream.into_split();
+    Connection {
+        reader: Box::new(reader),
+        writer: Box::new(writer),
+        how,
+    }
+}
+
+/// The port from inside the container: nc when the image has it, bash /dev/tcp otherwise
+fn port_command(port: u16) -> Vec<String> {
+    let script = format!(
+        "if command -v nc >/dev/null 2>&1; then exec nc 127.0.0.1 {port}; fi; \
+         exec bash -c 'exec 3<>/dev/tcp/127.0.0.1/{port}; cat <&3 & pid=$!; cat >&3; kill $pid 2>/dev/null; exec 3>&-'"
+    );
+    vec!["sh".to_string(), "-c".to_string(), script]
+}
+
+/// A Unix socket from inside the container, with socat or else nc -U; the path is $1
+fn socket_command(path: &str) -> Vec<String> {
+    let script = "if command -v socat >/dev/null 2>&1; then exec socat - UNIX-CONNECT:\"$1\"; fi; \
+                  exec nc -U \"$1\"";
+    vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        script.to_string(),
+        "sh".to_string(),
+        path.to_string(),
+    ]
+}
+
+/// Runs `cmd` in the container, its stdin and stdout being the connection
+async fn exec(docker: &Docker, id: &str, cmd: Vec<String>) -> Result<Connection> {
+    let created = docker
+        .create_exec(
+            id,
+            CreateExecOptions {
+                attach_stdin: Some(true),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                cmd: Some(cmd),
+                ..Default::default()
+            },
+        )
+        .await?;
+    match docker.start_exec(&created.id, None).await? {
+        StartExecResults::Attached { output, input } => {
+            let stdout = output.filter_map(|chunk| async move {
+                match chunk {
+                    Ok(LogOutput::StdOut { message }) => Some(Ok(message)),
+                    Ok(LogOutput::StdErr { message }) => {
+                        eprintln!("⚠️  {}", String::from_utf8_lossy(&message).trim_end());
+                        None
+                    }
+                    Ok(_) => None,
+                    Err(e) => Some(Err(std::io::Error::other(e))),
+                }
+            });
+            Ok(Connection {
+                reader: Box::new(StreamReader::new(Box::pin(stdout))),
+                writer: Box::new(input),
+                how: "exec".to_string(),
+            })
+        }
+        StartExecResults::Detached => Err(anyhow!("Exec did not attach")),
+    }
+}
+
+/// The running container, with its name and id
+async fn container_id(
+    docker: &Docker,
+    selector: &Selector,
+) -> Result<(ContainerSummary, String, String)> {
+    let container = find_container(docker, selector).await?;
+    let name = display_name(&container);
+    let id = container
+        .id
+        .clone()
+        .ok_or_else(|| anyhow!("Container {} has no id", name))?;
+    Ok((container, name, id))
+}
+
+/// Looks the container up again for every connection, so restarted and recreated
+/// containers are picked up without restarting the forward. Returns the container name
+/// along with the connection.
+pub async fn open(
+    docker: &Docker,
+    selector: &Selector,
+    port: u16,
+    via: Via,
+) -> Result<(String, Connection)> {
+    let (container, name, id) = container_id(docker, selector).await?;
+
+    if matches!(via, Via::Auto | Via::Published) {
+        if let Some(published) = published_port(&container, port) {
+            let stream = TcpStream::connect(("127.0.0.1", published)).await?;
+            return Ok((name, tcp(stream, format!("published port {}", published))));
+        }
+        if via == Via::Published {
+            return Err(anyhow!("{} does not publish port {}", name, port));
+        }
+    }
+    if matches!(via, Via::Auto | Via::Ip) {
+        match container_ip(&container) {
+            Some(ip) => {
+                let connect = TcpStream::connect((ip.as_str(), port));
+                match tokio::time::timeout(IP_CONNECT_TIMEOUT, connect).await {
+                    Ok(Ok(stream)) => return Ok((name, tcp(stream, ip))),
+                    Ok(Err(e)) if via == Via::Ip => return Err(e.into()),
+                    Err(_) if via == Via::Ip => return Err(anyhow!("{} did not answer", ip)),
+                    _ => {}
+                }
+            }
+            None if via == Via::Ip => return Err(anyhow!("{} has no IP address", name)),
+            None => {}
+        }
+    }
+    let connection = exec(docker, &id, port_command(port)).await?;
+    Ok((name, connection))
+}
+
+/// Like `open`, for a Unix socket in the container; sockets are only reachable with exec
+pub async fn open_socket(
+    docker: &Docker,
+    selector: &Selector,
+    path: &str,
+) -> Result<(String, Connection)> {
+    let (_, name, id) = container_id(docker, selector).await?;
+    let connection = exec(docker, &id, socket_command(path)).await?;
+    Ok((name, connection))
+}
@@ -0,0 +1,108 @@
+// Config files encrypted with sops or age, so configs holding connection strings and
+// passwords can be committed. sops encrypts a .conf file as a binary document, kept as a
+// JSON envelope with a "sops" section; age files start with their header line. Both are
+// decrypted by the tools themselves: sops with whatever key the file was encrypted for
+// (age, AWS KMS, GCP KMS, ...), age with the identity in the age key file.
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const AGE_HEADER: &[u8] = b"age-encryption.org/v1";
+const AGE_ARMOR_HEADER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Envelope {
+    Sops,
+    Age,
+}
+
+impl Envelope {
+    /// How `content` was encrypted, None for plain text
+    pub(crate) fn detect(content: &[u8]) -> Option<Envelope> {
+        let start = content
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .unwrap_or(content.len());
+        let content = &content[start..];
+        if content.starts_with(AGE_HEADER) || content.starts_with(AGE_ARMOR_HEADER) {
+            return Some(Envelope::Age);
+        }
+        // TOML can't start with a brace, so this is no plain config
+        let text = String::from_utf8_lossy(content);
+        (text.starts_with('{') && text.contains("\"sops\"") && text.contains("ENC["))
+            .then_some(Envelope::Sops)
+    }
+}
+
+fn run(mut command: Command, tool: &str, path: &Path) -> io::Result<String> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::other(format!(
+                "{} is encrypted, install {} to decrypt it",
+                path.display(),
+                tool
+            )),
+            _ => e,
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("no reason given");
+        return Err(io::Error::other(format!(
+            "Failed to decrypt {} with {}: {}",
+            path.display(),
+            tool,
+            reason.trim()
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} does not decrypt to text", path.display()),
+        )
+    })
+}
+
+pub(crate) fn decrypt(path: &Path, envelope: Envelope) -> io::Result<String> {
+    let key = crate::age_key_path().filter(|key| key.exists());
+    match envelope {
+        Envelope::Sops => {
+            let mut command = Command::new("sops");
+            command.args([
+                "--decrypt",
+                "--input-type",
+                "binary",
+                "--output-type",
+                "binary",
+            ]);
+            if let Some(key) = key {
+                if std::env::var_os("SOPS_AGE_KEY_FILE").is_none() {
+                    command.env("SOPS_AGE_KEY_FILE", key);
+                }
+            }
+            command.arg(path);
+            run(command, "sops", path)
+        }
+        Envelope::Age => {
+            let key = key.ok_or_else(|| {
+                io::Error::other(format!(
+                    "{} is age-encrypted but there is no age key at {}",
+                    path.display(),
+                    crate::age_key_path().unwrap_or_default().display()
+                ))
+            })?;
+            let mut command = Command::new("age");
+            command
+                .arg("--decrypt")
+                .arg("--identity")
+                .arg(key)
+                .arg(path);
+            run(command, "age", path)
+        }
+    }
+}
@@ -0,0 +1,629 @@
+// The inter-plugin event bus. Plugin processes, `proxy daemon` and the dashboards tell each
+// other when plugin processes start and exit, and ask for plugins to be started and
+// stopped. Plugins run in processes of their own, so the bus is a file: every process
+// appends one line per event to `events/bus.log` in the state directory, and a `Subscriber`
+// reads what was appended since it last looked. A line is the time, the publishing pid,
+// the event's kind and its fields, separated by tabs.
+//
+// Requests (`Start`, `Stop`) are handled by one process at a time, the one holding the
+// `Handler` role: `proxy daemon` takes it over to run requested plugins under its
+// supervisor, and a dashboard takes it while nobody else has it. The handler answers a
+// request with `Started`, `Stopped` or `Failed`, carrying the request's id.
+use chrono::{SecondsFormat, Utc};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Past this size the bus is moved to `bus.log.1`, replacing the one before
+const MAX_BUS_SIZE: u64 = 1024 * 1024;
+/// How often `request` looks for the answer
+const ANSWER_POLL: Duration = Duration::from_millis(100);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Asks for `proxy <args>` to be started in the background
+    Start { id: String, args: Vec<String> },
+    /// Asks for a plugin process to be stopped as Ctrl-C would
+    Stop { id: String, pid: u32 },
+    /// A plugin process started; `id` is the request's, empty when none asked for it
+    Started {
+        id: String,
+        plugin: String,
+        pid: u32,
+    },
+    /// A plugin process was interrupted; `id` as for `Started`
+    Stopped {
+        id: String,
+        plugin: String,
+        pid: u32,
+    },
+    /// A request could not be handled
+    Failed { id: String, message: String },
+    /// A plugin process exited
+    Exited { plugin: String, pid: u32 },
+}
+
+impl Event {
+    /// The id of the request it asks or answers, if any
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Event::Start { id, .. }
+            | Event::Stop { id, .. }
+            | Event::Started { id, .. }
+            | Event::Stopped { id, .. }
+            | Event::Failed { id, .. } => Some(id).filter(|id| !id.is_empty()).map(String::as_str),
+            Event::Exited { .. } => None,
+        }
+    }
+
+    /// Whether it asks for something, for the handler to answer
+    pub fn is_request(&self) -> bool {
+        matches!(self, Event::Start { .. } | Event::Stop { .. })
+    }
+
+    fn fields(&self) -> Vec<String> {
+        match self {
+            Event::Start { id, args } => [vec!["start".into(), id.clone()], args.clone()].concat(),
+            Event::Stop { id, pid } => vec!["stop".into(), id.clone(), pid.to_string()],
+            Event::Started { id, plugin, pid } => {
+                vec![
+                    "started".into(),
+                    id.clone(),
+                    plugin.clone(),
+                    pid.to_string(),
+                ]
+            }
+            Event::Stopped { id, plugin, pid } => {
+                vec![
+                    "stopped".into(),
+                    id.clone(),
+                    plugin.clone(),
+                    pid.to_string(),
+                ]
+            }
+            Event::Failed { id, message } => vec!["failed".into(), id.clone(), message.clone()],
+            Event::Exited { plugin, pid } => {
+                vec!["exited".into(), plugin.clone(), pid.to_string()]
+            }
+        }
+    }
+
+    fn from_fields(fields: &[String]) -> Option<Event> {
+        let pid = |field: Option<&String>| field.and_then(|pid| pid.parse().ok());
+        let field = |index: usize| fields.get(index).cloned();
+        let event = match fields.first()?.as_str() {
+            "start" => Event::Start {
+                id: field(1)?,
+                args: fields[2..].to_vec(),
+            },
+            "stop" => Event::Stop {
+                id: field(1)?,
+                pid: pid(fields.get(2))?,
+            },
+            "started" => Event::Started {
+                id: field(1)?,
+                plugin: field(2)?,
+                pid: pid(fields.get(3))?,
+            },
+            "stopped" => Event::Stopped {
+                id: field(1)?,
+                plugin: field(2)?,
+                pid: pid(fields.get(3))?,
+            },
+            "failed" => Event::Failed {
+                id: field(1)?,
+                message: field(2)?,
+            },
+            "exited" => Event::Exited {
+                plugin: field(1)?,
+                pid: pid(fields.get(2))?,
+            },
+            _ => return None,
+        };
+        Some(event)
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Start { args, .. } => write!(f, "asked to start `{}`", args.join(" ")),
+            Event::Stop { pid, .. } => write!(f, "asked to stop pid {}", pid),
+            Event::Started { plugin, pid, .. } => write!(f, "started {} (pid {})", plugin, pid),
+            Event::Stopped { plugin, pid, .. } => write!(f, "stopped {} (pid {})", plugin, pid),
+            Event::Failed { message, .. } => write!(f, "failed: {}", message),
+            Event::Exited { plugin, pid } => write!(f, "{} (pid {}) exited", plugin, pid),
+        }
+    }
+}
+
+/// An event as read from the bus
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub time: String,
+    /// The process that published it
+    pub pid: u32,
+    pub event: Event,
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut text = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some(c) => text.push(c),
+                None => {}
+            },
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+fn parse_line(line: &str) -> Option<Received> {
+    let mut fields = line.split('\t').map(unescape);
+    let time = fields.next()?;
+    let pid = fields.next()?.parse().ok()?;
+    let event = Event::from_fields(&fields.collect::<Vec<_>>())?;
+    Some(Received { time, pid, event })
+}
+
+fn events_dir() -> io::Result<PathBuf> {
+    crate::plugin_state_dir("events").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not determine the state directory",
+        )
+    })
+}
+
+fn bus_path() -> io::Result<PathBuf> {
+    events_dir().map(|dir| dir.join("bus.log"))
+}
+
+/// Announces `event` to every subscriber
+pub fn publish(event: &Event) -> io::Result<()> {
+    let path = bus_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if fs::metadata(&path).is_ok_and(|metadata| metadata.len() > MAX_BUS_SIZE) {
+        let _ = fs::rename(&path, path.with_extension("log.1"));
+    }
+    let mut fields = vec![
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        std::process::id().to_string(),
+    ];
+    fields.extend(event.fields());
+    let line: Vec<String> = fields.iter().map(|field| escape(field)).collect();
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // One write per event, so events of concurrent processes don't interleave
+    options
+        .open(&path)?
+        .write_all(format!("{}\n", line.join("\t")).as_bytes())
+}
+
+/// Publishes `event`, warning instead of failing: for announcements nothing waits on
+pub fn announce(event: &Event) {
+    if let Err(e) = publish(event) {
+        eprintln!("⚠️  Could not publish to the event bus: {}", e);
+    }
+}
+
+/// The latest `limit` events on the bus, oldest first
+pub fn recent(limit: usize) -> Vec<Received> {
+    let Ok(content) = bus_path().and_then(fs::read_to_string) else {
+        return Vec::new();
+    };
+    let events: Vec<Received> = content.lines().filter_map(parse_line).collect();
+    events[events.len().saturating_sub(limit)..].to_vec()
+}
+
+/// Reads the events published after it was created
+pub struct Subscriber {
+    offset: u64,
+    /// Which file `offset` is in, to tell when the bus was moved aside between polls even
+    /// if the new one already grew past it
+    identity: Option<u128>,
+    /// The start of a line still being written
+    partial: String,
+}
+
+/// What tells one bus file from the next: its inode, or where there is none its creation
+/// time; `None` when the platform can't tell
+fn identity(metadata: &fs::Metadata) -> Option<u128> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(((metadata.dev() as u128) << 64) | metadata.ino() as u128)
+    }
+    #[cfg(not(unix))]
+    {
+        metadata
+            .created()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|since| since.as_nanos())
+    }
+}
+
+impl Subscriber {
+    pub fn new() -> Self {
+        let metadata = bus_path().and_then(fs::metadata).ok();
+        Subscriber {
+            offset: metadata.as_ref().map_or(0, |metadata| metadata.len()),
+            identity: metadata.as_ref().and_then(identity),
+            partial: String::new(),
+        }
+    }
+
+    /// The events published since the last call, oldest first
+    pub fn poll(&mut self) -> Vec<Received> {
+        let Ok(path) = bus_path() else {
+            return Vec::new();
+        };
+        let Ok(mut file) = File::open(&path) else {
+            return Vec::new();
+        };
+        let Ok(metadata) = file.metadata() else {
+            return Vec::new();
+        };
+        let current = identity(&metadata);
+        let rotated = match (self.identity, current) {
+            (Some(read), Some(current)) => read != current,
+            _ => metadata.len() < self.offset,
+        };
+        if rotated {
+            // Moved aside for a new one: first the rest of the file it was reading
+            let rest = self.identity.and_then(|reading| {
+                let mut old = File::open(path.with_extension("log.1")).ok()?;
+                let same = old
+                    .metadata()
+                    .is_ok_and(|metadata| identity(&metadata) == Some(reading));
+                let mut rest = String::new();
+                let read = same
+                    && old.seek(SeekFrom::Start(self.offset)).is_ok()
+                    && old.read_to_string(&mut rest).is_ok();
+                read.then_some(rest)
+            });
+            match rest {
+                Some(rest) => self.partial.push_str(&rest),
+                // Moved aside again since, or it can't tell
+                None => self.partial.clear(),
+            }
+            self.offset = 0;
+        }
+        self.identity = current;
+        let mut appended = String::new();
+        if file.seek(SeekFrom::Start(self.offset)).is_err()
+            || file.read_to_string(&mut appended).is_err()
+        {
+            return Vec::new();
+        }
+        self.offset += appended.len() as u64;
+        self.partial.push_str(&appended);
+        let Some(end) = self.partial.rfind('\n') else {
+            return Vec::new();
+        };
+        let complete: String = self.partial.drain(..=end).collect();
+        complete.lines().filter_map(parse_line).collect()
+    }
+}
+
+impl Default for Subscriber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A new request id, unique across processes
+pub fn new_id() -> String {
+    format!(
+        "{}-{}",
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Publishes the request `ask` makes of a new id and waits up to `timeout` for its answer
+pub fn request(ask: impl FnOnce(String) -> Event, timeout: Duration) -> io::Result<Event> {
+    let id = new_id();
+    let mut subscriber = Subscriber::new();
+    publish(&ask(id.clone()))?;
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        for received in subscriber.poll() {
+            if !received.event.is_request() && received.event.id() == Some(id.as_str()) {
+                return Ok(received.event);
+            }
+        }
+        std::thread::sleep(ANSWER_POLL);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "Nothing handled the request on the event bus",
+    ))
+}
+
+fn handler_path() -> io::Result<PathBuf> {
+    events_dir().map(|dir| dir.join("handler.pid"))
+}
+
+/// The process handling requests, if one is running
+pub fn handler() -> Option<u32> {
+    let pid = fs::read_to_string(handler_path().ok()?)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    crate::processes::alive(pid).then_some(pid)
+}
+
+/// The role of handling requests, held until dropped or taken over
+pub struct Handler {
+    path: PathBuf,
+}
+
+impl Handler {
+    /// A file naming this process, to be moved or linked into place as the handler's
+    fn record(path: &Path) -> Option<PathBuf> {
+        fs::create_dir_all(path.parent()?).ok()?;
+        let record = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&record, std::process::id().to_string()).ok()?;
+        Some(record)
+    }
+
+    /// Takes the role, unless a running process holds it
+    pub fn take() -> Option<Handler> {
+        let path = handler_path().ok()?;
+        let record = Self::record(&path)?;
+        // Linked, so the role is taken with the pid in it or not at all; once more after
+        // removing the record of a process that is gone
+        let mut taken = false;
+        for _ in 0..2 {
+            match fs::hard_link(&record, &path) {
+                Ok(()) => {
+                    taken = true;
+                    break;
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && handler().is_none() => {
+                    let _ = fs::remove_file(&path);
+                }
+                Err(_) => break,
+            }
+        }
+        let _ = fs::remove_file(&record);
+        taken.then_some(Handler { path })
+    }
+
+    /// Takes the role from whoever holds it
+    pub fn take_over() -> Option<Handler> {
+        let path = handler_path().ok()?;
+        let record = Self::record(&path)?;
+        match fs::rename(&record, &path) {
+            Ok(()) => Some(Handler { path }),
+            Err(_) => {
+                let _ = fs::remove_file(&record);
+                None
+            }
+        }
+    }
+
+    /// Whether this process still holds the role, which another may have taken over
+    pub fn held(&self) -> bool {
+        fs::read_to_string(&self.path).is_ok_and(|pid| pid.trim() == std::process::id().to_string())
+    }
+}
+
+impl Drop for Handler {
+    fn drop(&mut self) {
+        if self.held() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard, OnceLock};
+
+    /// Points the state directory at a fresh one for this test process, and keeps the tests
+    /// touching the bus from running at the same time
+    fn state_dir() -> MutexGuard<'static, ()> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        static DIR: OnceLock<PathBuf> = OnceLock::new();
+        let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = DIR.get_or_init(|| {
+            let dir = std::env::temp_dir().join(format!("proxy-events-{}", std::process::id()));
+            std::env::set_var("PROXY_STATE_DIR", &dir);
+            dir
+        });
+        let _ = fs::remove_dir_all(dir);
+        guard
+    }
+
+    /// A line as `publish` writes it
+    fn line(event: &Event) -> String {
+        let mut fields = vec!["2026-10-16T12:00:00.000Z".to_string(), "42".to_string()];
+        fields.extend(event.fields());
+        let escaped: Vec<String> = fields.iter().map(|field| escape(field)).collect();
+        escaped.join("\t")
+    }
+
+    #[test]
+    fn fields_with_tabs_newlines_and_backslashes_round_trip() {
+        let events = [
+            Event::Start {
+                id: "7-1".into(),
+                args: vec![
+                    "http_proxy".into(),
+                    "--header".into(),
+                    "X-A:\tb\nc".into(),
+                    "C:\\logs\\new".into(),
+                ],
+            },
+            Event::Failed {
+                id: "7-2".into(),
+                message: "literal \\t and \\n, then a real\ttab".into(),
+            },
+            Event::Exited {
+                plugin: "k8s_port_forward".into(),
+                pid: 4242,
+            },
+        ];
+        for event in events {
+            let line = line(&event);
+            assert!(!line.contains('\n'), "{:?}", line);
+            let received = parse_line(&line).unwrap();
+            assert_eq!(received.time, "2026-10-16T12:00:00.000Z");
+            assert_eq!(received.pid, 42);
+            assert_eq!(received.event, event);
+        }
+    }
+
+    #[test]
+    fn unknown_and_incomplete_lines_are_skipped() {
+        assert!(parse_line("2026-10-16T12:00:00.000Z\t42\treloaded\tx").is_none());
+        assert!(parse_line("2026-10-16T12:00:00.000Z\t42\tstop\t7-1").is_none());
+        assert!(parse_line("2026-10-16T12:00:00.000Z\tnot-a-pid\texited\tdns_proxy\t1").is_none());
+    }
+
+    #[test]
+    fn bus_is_moved_aside_past_its_size_limit() {
+        let _dir = state_dir();
+        let path = bus_path().unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let filler = line(&Event::Exited {
+            plugin: "socks5_proxy".into(),
+            pid: 1,
+        }) + "\n";
+        let old = filler.repeat(MAX_BUS_SIZE as usize / filler.len() + 1);
+        fs::write(&path, &old).unwrap();
+
+        let event = Event::Exited {
+            plugin: "dns_proxy".into(),
+            pid: 2,
+        };
+        publish(&event).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(path.with_extension("log.1")).unwrap(),
+            old
+        );
+        let recent: Vec<Event> = recent(10).into_iter().map(|r| r.event).collect();
+        assert_eq!(recent, vec![event]);
+    }
+
+    #[test]
+    fn bus_under_its_size_limit_is_appended_to() {
+        let _dir = state_dir();
+        let first = Event::Exited {
+            plugin: "dns_proxy".into(),
+            pid: 1,
+        };
+        let second = Event::Exited {
+            plugin: "dns_proxy".into(),
+            pid: 2,
+        };
+        let mut subscriber = Subscriber::new();
+        publish(&first).unwrap();
+        publish(&second).unwrap();
+
+        assert!(!bus_path().unwrap().with_extension("log.1").exists());
+        let polled: Vec<Event> = subscriber.poll().into_iter().map(|r| r.event).collect();
+        assert_eq!(polled, vec![first, second]);
+        assert!(subscriber.poll().is_empty());
+    }
+
+    #[test]
+    fn subscriber_reads_across_a_rotation_the_new_bus_outgrew() {
+        let _dir = state_dir();
+        let event = |pid| Event::Exited {
+            plugin: "dns_proxy".into(),
+            pid,
+        };
+        let mut subscriber = Subscriber::new();
+        publish(&event(1)).unwrap();
+        assert_eq!(subscriber.poll().len(), 1);
+
+        // Appended to the old bus after the last poll, then moved aside, and the new bus
+        // longer than the old one by the next poll
+        publish(&event(2)).unwrap();
+        let path = bus_path().unwrap();
+        fs::rename(&path, path.with_extension("log.1")).unwrap();
+        for pid in 3..6 {
+            publish(&event(pid)).unwrap();
+        }
+
+        let polled: Vec<Event> = subscriber.poll().into_iter().map(|r| r.event).collect();
+        assert_eq!(polled, (2..6).map(event).collect::<Vec<_>>());
+        assert!(subscriber.poll().is_empty());
+    }
+
+    #[test]
+    fn handler_role_is_held_by_one_process_at_a_time() {
+        let _dir = state_dir();
+        let me = std::process::id();
+        assert_eq!(handler(), None);
+
+        let taken = Handler::take().unwrap();
+        assert!(taken.held());
+        assert_eq!(handler(), Some(me));
+        assert!(
+            Handler::take().is_none(),
+            "taken while held by a live process"
+        );
+
+        drop(taken);
+        assert_eq!(handler(), None);
+        assert!(!handler_path().unwrap().exists());
+    }
+
+    #[test]
+    fn handler_role_of_an_exited_process_is_taken() {
+        let _dir = state_dir();
+        let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+            .arg("--list")
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let gone = child.id();
+        child.wait().unwrap();
+        let path = handler_path().unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, gone.to_string()).unwrap();
+        assert_eq!(handler(), None);
+
+        let taken = Handler::take().unwrap();
+        assert_eq!(handler(), Some(std::process::id()));
+
+        // Taken over, so dropping the old holder leaves the new one's record alone
+        fs::write(&path, gone.to_string()).unwrap();
+        assert!(!taken.held());
+        drop(taken);
+        assert_eq!(fs::read_to_string(&path).unwrap(), gone.to_string());
+    }
+}
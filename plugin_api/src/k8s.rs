@@ -0,0 +1,49 @@
+// Finding the pods to reach through the Kubernetes API (kube), shared by the plugins that
+// exec into or forward to pods; built with the `k8s` feature.
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, ListParams};
+
+/// Whether the pod is in the Running phase
+pub fn is_running(pod: &Pod) -> bool {
+    pod.status
+        .as_ref()
+        .and_then(|status| status.phase.as_deref())
+        == Some("Running")
+}
+
+/// The running pods matching a label selector
+pub async fn running_pods(pods: &Api<Pod>, selector: &str) -> Result<Vec<Pod>> {
+    let list = pods.list(&ListParams::default().labels(selector)).await?;
+    Ok(list.items.into_iter().filter(is_running).collect())
+}
+
+/// The first running pod matching a label selector
+pub async fn running_pod(pods: &Api<Pod>, selector: &str) -> Result<Pod> {
+    running_pods(pods, selector)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No running pods match selector: {}", selector))
+}
+
+/// The pod by name, or the first running one matching the selector, saying so when
+/// there was more than one
+pub async fn find_pod(pods: &Api<Pod>, name: Option<&str>, selector: Option<&str>) -> Result<Pod> {
+    if let Some(name) = name {
+        return Ok(pods.get(name).await?);
+    }
+    let selector = selector.ok_or_else(|| anyhow!("Must specify either a pod or a selector"))?;
+    let running = running_pods(pods, selector).await?;
+    if running.len() > 1 {
+        eprintln!(
+            "🏷️  {} running pods match '{}', using the first",
+            running.len(),
+            selector
+        );
+    }
+    running
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No running pods match selector: {}", selector))
+}
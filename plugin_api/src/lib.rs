@@ -1,21 +1,264 @@
+pub mod audit;
+pub mod config;
+pub mod credentials;
+#[cfg(feature = "docker")]
+pub mod docker;
+mod encrypted;
+pub mod events;
+#[cfg(feature = "k8s")]
+pub mod k8s;
+pub mod metrics;
+pub mod net;
+pub mod notify;
+mod periodic;
+pub mod processes;
+pub mod project;
+pub mod runtime;
+#[cfg(feature = "subprocess")]
+pub mod subprocess;
+pub mod telemetry;
+pub mod traffic;
+pub mod usage;
+
 use std::path::PathBuf;
+/// Returns the directory of the plugins' config files, e.g. ~/.cohandv/proxy/config/plugins.d
+pub fn plugin_config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("PROXY_PLUGINS_CONFIG_DIR") {
+        Some(PathBuf::from(dir))
+    } else {
+        dirs::home_dir().map(|h| h.join(".cohandv/proxy/config/plugins.d"))
+    }
+}
 /// Returns the config path for a given plugin name, e.g. ~/.cohandv/proxy/config/plugins.d/{plugin_name}.conf
 pub fn plugin_config_path(plugin_name: &str) -> Option<PathBuf> {
-    if let Some(dir) = std::env::var_os("PROXY_PLUGINS_CONFIG_DIR") {
-        Some(PathBuf::from(dir).join(format!("{plugin_name}.conf")))
+    plugin_config_dir().map(|dir| dir.join(format!("{plugin_name}.conf")))
+}
+/// Returns the fragments directory for a given plugin name, e.g. ~/.cohandv/proxy/config/plugins.d/{plugin_name}.conf.d
+/// Plugins that support split configs merge every file in it into the main config.
+pub fn plugin_config_fragments_dir(plugin_name: &str) -> Option<PathBuf> {
+    plugin_config_path(plugin_name).map(|path| path.with_extension("conf.d"))
+}
+/// Returns the state directory for a given plugin name, e.g. ~/.cohandv/proxy/state/{plugin_name}
+pub fn plugin_state_dir(plugin_name: &str) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("PROXY_STATE_DIR") {
+        Some(PathBuf::from(dir).join(plugin_name))
     } else {
-        dirs::home_dir().map(|h| {
-            h.join(".cohandv/proxy/config/plugins.d")
-                .join(format!("{plugin_name}.conf"))
-        })
+        dirs::home_dir().map(|h| h.join(".cohandv/proxy/state").join(plugin_name))
     }
 }
+/// Returns the directory named secrets are read from, e.g. ~/.cohandv/proxy/secrets
+pub fn secrets_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("PROXY_SECRETS_DIR") {
+        Some(PathBuf::from(dir))
+    } else {
+        dirs::home_dir().map(|h| h.join(".cohandv/proxy/secrets"))
+    }
+}
+/// Returns the age identity file encrypted configs are decrypted with, e.g. ~/.cohandv/proxy/config/age.key
+pub fn age_key_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("PROXY_AGE_KEY_FILE") {
+        Some(PathBuf::from(path))
+    } else {
+        dirs::home_dir().map(|h| h.join(".cohandv/proxy/config/age.key"))
+    }
+}
+/// Reads a config file, decrypting it first when it was encrypted with sops or age. A
+/// plugin's config gets the settings of the project config (.proxy.toml) merged over it,
+/// and is read from there alone when the home directory has none.
+pub fn read_config(path: &std::path::Path) -> std::io::Result<String> {
+    let Some(plugin_name) = config_plugin(path) else {
+        return read_file(path);
+    };
+    let content = match read_file(path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    project::merged(plugin_name, content)?.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} not found", path.display()),
+        )
+    })
+}
+
+/// Whether a config file exists, or for a plugin's config, the project config has settings
+/// for the plugin
+pub fn config_exists(path: &std::path::Path) -> bool {
+    path.exists()
+        || config_plugin(path).is_some_and(|name| matches!(project::section(name), Ok(Some(_))))
+}
+
+/// The plugin `path` is the config file of
+fn config_plugin(path: &std::path::Path) -> Option<&str> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|name| plugin_config_path(name).as_deref() == Some(path))
+}
+
+fn read_file(path: &std::path::Path) -> std::io::Result<String> {
+    let content = std::fs::read(path)?;
+    match encrypted::Envelope::detect(&content) {
+        Some(envelope) => encrypted::decrypt(path, envelope),
+        None => String::from_utf8(content)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+    }
+}
+
+/// Prefix of the line `proxy vault get --encoded` prints the hex-encoded secret on
+pub const ENCODED_SECRET_PREFIX: &str = "secret-hex:";
+
+/// Set by `proxy` to its own path for the plugin binaries it runs
+pub const PROXY_EXE_ENV: &str = "PROXY_EXE";
+
+/// The `proxy` executable, to run proxy commands with: in a plugin binary the one that
+/// started it, as the binary's own executable is the plugin
+pub fn proxy_exe() -> std::io::Result<PathBuf> {
+    match std::env::var_os(PROXY_EXE_ENV) {
+        Some(exe) => Ok(PathBuf::from(exe)),
+        None => std::env::current_exe(),
+    }
+}
+
+/// Reads a Vault secret through the vault plugin, which holds the auth config and token
+fn resolve_vault(reference: &str) -> Result<String, String> {
+    let exe = proxy_exe().map_err(|e| format!("Failed to locate proxy: {e}"))?;
+    let output = std::process::Command::new(exe)
+        .args(["vault", "get", "--encoded", reference])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run the vault plugin: {e}"))?;
+    let encoded = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix(ENCODED_SECRET_PREFIX).map(str::to_string));
+    match encoded {
+        Some(encoded) if output.status.success() => hex::decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(|| format!("Vault secret {reference} is not valid text")),
+        _ => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .rev()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or("is the vault plugin installed?");
+            Err(format!("Vault secret {reference}: {}", reason.trim()))
+        }
+    }
+}
+
+/// Resolves a config value that may reference a secret instead of containing it:
+/// "secret:env:VAR" reads an environment variable, "secret:file:/path" a file, and
+/// "secret:NAME" the file NAME in the secrets directory. "vault:PATH#FIELD" reads a
+/// field of a Vault secret with the vault plugin. Other values are returned as is.
+pub fn resolve_secret(value: &str) -> Result<String, String> {
+    if let Some(reference) = value.strip_prefix("vault:") {
+        return resolve_vault(reference);
+    }
+    let Some(reference) = value.strip_prefix("secret:") else {
+        return Ok(value.to_string());
+    };
+    let secret = if let Some(var) = reference.strip_prefix("env:") {
+        std::env::var(var).map_err(|_| format!("Environment variable {var} is not set"))?
+    } else {
+        let path = match reference.strip_prefix("file:") {
+            Some(path) => PathBuf::from(path),
+            None => secrets_dir()
+                .ok_or("Could not determine the secrets directory")?
+                .join(reference),
+        };
+        std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read secret {}: {e}", path.display()))?
+    };
+    let secret = secret.trim().to_string();
+    if secret.is_empty() {
+        return Err(format!("Secret '{value}' is empty"));
+    }
+    Ok(secret)
+}
 use clap::{ArgMatches, Command};
 
+/// Version of the interface between the host and its plugins: the `Plugin` trait and what
+/// it passes. Raise it with every change to them; the host refuses plugins built against
+/// another version. Plugins export it next to `create_plugin`:
+///
+/// ```ignore
+/// #[no_mangle]
+/// pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
+/// ```
+pub const PLUGIN_API_VERSION: u32 = 3;
+
 pub trait Plugin {
     fn name(&self) -> &'static str;
     fn version(&self) -> &'static str;
     fn description(&self) -> &'static str;
     fn subcommand(&self) -> Command;
     fn run(&self, matches: &ArgMatches);
+    /// A commented config file to start from, which `proxy config init` writes
+    fn sample_config(&self) -> Option<&'static str> {
+        None
+    }
+    /// Checks the content of a config file for the plugin, for `proxy config validate`.
+    /// Plugins reading their config into a serde type check it against that type with
+    /// `config::validate_toml`; by default it only has to be TOML.
+    fn validate_config(&self, config: &str) -> Result<(), Vec<config::ConfigError>> {
+        config::validate_toml::<toml::Table>(config)
+    }
+}
+
+/// A plugin whose command is async. It runs on the shared multi-threaded runtime
+/// (`runtime::runtime()`) rather than one the plugin builds, and as its future is `Send`
+/// it can be spawned next to others. Every `AsyncPlugin` is a `Plugin`, so `create_plugin`
+/// returns it as usual; import only this trait in the plugin, as both have `name()`:
+///
+/// ```ignore
+/// #[no_mangle]
+/// #[allow(improper_ctypes_definitions)]
+/// pub extern "C" fn create_plugin() -> Box<dyn plugin_api::Plugin> {
+///     Box::new(MyPlugin)
+/// }
+/// ```
+pub trait AsyncPlugin {
+    fn name(&self) -> &'static str;
+    fn version(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn subcommand(&self) -> Command;
+    fn run(&self, matches: &ArgMatches) -> impl std::future::Future<Output = ()> + Send;
+    fn sample_config(&self) -> Option<&'static str> {
+        None
+    }
+    fn validate_config(&self, config: &str) -> Result<(), Vec<config::ConfigError>> {
+        config::validate_toml::<toml::Table>(config)
+    }
+}
+
+impl<P: AsyncPlugin> Plugin for P {
+    fn name(&self) -> &'static str {
+        AsyncPlugin::name(self)
+    }
+
+    fn version(&self) -> &'static str {
+        AsyncPlugin::version(self)
+    }
+
+    fn description(&self) -> &'static str {
+        AsyncPlugin::description(self)
+    }
+
+    fn subcommand(&self) -> Command {
+        AsyncPlugin::subcommand(self)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        runtime::block_on(AsyncPlugin::run(self, matches))
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        AsyncPlugin::sample_config(self)
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<config::ConfigError>> {
+        AsyncPlugin::validate_config(self, config)
+    }
 }
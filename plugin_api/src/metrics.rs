@@ -0,0 +1,243 @@
+// Metrics shared across plugin processes. A plugin records counters and gauges in its
+// process-wide registry; once `init` names the plugin, a background thread writes them in
+// the Prometheus text format to `metrics/<plugin>-<pid>.prom` in the state directory.
+// `proxy serve-metrics` merges the files of every running plugin into one endpoint, with
+// `plugin` and `pid` labels telling the processes apart.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::periodic::Periodic;
+
+/// How often a plugin process rewrites its metrics file
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(5);
+/// Files not rewritten for this long were left by processes that were killed
+const STALE_AFTER: Duration = Duration::from_secs(30);
+
+struct Family {
+    kind: &'static str,
+    help: String,
+    /// Values by rendered label set
+    samples: BTreeMap<String, f64>,
+}
+
+static REGISTRY: Mutex<BTreeMap<String, Family>> = Mutex::new(BTreeMap::new());
+/// This process' metrics file, once publishing started
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+static PUBLISHER: Periodic = Periodic::new();
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn record(
+    name: &str,
+    kind: &'static str,
+    help: &str,
+    labels: &[(&str, &str)],
+    update: impl FnOnce(&mut f64),
+) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let family = registry.entry(name.to_string()).or_insert_with(|| Family {
+        kind,
+        help: help.to_string(),
+        samples: BTreeMap::new(),
+    });
+    update(family.samples.entry(render_labels(labels)).or_insert(0.0));
+}
+
+/// Adds to a counter; names follow Prometheus conventions, e.g. `proxy_relay_bytes_total`
+pub fn counter_add(name: &str, help: &str, labels: &[(&str, &str)], value: u64) {
+    record(name, "counter", help, labels, |total| {
+        *total += value as f64
+    });
+}
+
+pub fn gauge_set(name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+    record(name, "gauge", help, labels, |gauge| *gauge = value);
+}
+
+/// Moves a gauge up or down, e.g. by 1 and -1 around a connection
+pub fn gauge_add(name: &str, help: &str, labels: &[(&str, &str)], delta: f64) {
+    record(name, "gauge", help, labels, |gauge| *gauge += delta);
+}
+
+/// The directory every plugin process publishes its metrics file in
+pub fn metrics_dir() -> Option<PathBuf> {
+    crate::plugin_state_dir("metrics")
+}
+
+fn render() -> String {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let mut text = String::new();
+    for (name, family) in registry.iter() {
+        text.push_str(&format!("# HELP {} {}\n", name, family.help));
+        text.push_str(&format!("# TYPE {} {}\n", name, family.kind));
+        for (labels, value) in &family.samples {
+            if labels.is_empty() {
+                text.push_str(&format!("{} {}\n", name, value));
+            } else {
+                text.push_str(&format!("{}{{{}}} {}\n", name, labels, value));
+            }
+        }
+    }
+    text
+}
+
+fn publish(path: &Path) {
+    // Written aside and renamed, so a reader never sees half a file
+    let partial = path.with_extension("tmp");
+    if fs::write(&partial, render()).is_ok() {
+        let _ = fs::rename(&partial, path);
+    }
+}
+
+/// Starts publishing this process' metrics under the plugin's name. Metrics recorded
+/// before or without it are kept in memory only. The file is removed when the process
+/// exits. Usage accounting (see `usage`) starts along.
+pub fn init(plugin_name: &str) {
+    crate::usage::init(plugin_name);
+    let Some(dir) = metrics_dir() else {
+        return;
+    };
+    if fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+    let path = dir.join(format!("{}-{}.prom", plugin_name, std::process::id()));
+    if PATH.set(path.clone()).is_ok() {
+        PUBLISHER.start(PUBLISH_INTERVAL, move || publish(&path));
+        unsafe {
+            libc::atexit(unpublish);
+        }
+    }
+}
+
+extern "C" fn unpublish() {
+    PUBLISHER.stop();
+    if let Some(path) = PATH.get() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+struct Merged {
+    help: String,
+    kind: String,
+    samples: Vec<String>,
+}
+
+/// The files of the plugin processes still publishing, with their plugin and pid. Files of
+/// processes that have exited are removed.
+fn live_files() -> Vec<(PathBuf, String, u32)> {
+    let mut live = Vec::new();
+    let entries = metrics_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .flatten();
+    for entry in entries {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("prom") {
+            continue;
+        }
+        let Some((plugin, pid)) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.rsplit_once('-'))
+            .and_then(|(plugin, pid)| Some((plugin.to_string(), pid.parse().ok()?)))
+        else {
+            continue;
+        };
+        let stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_none_or(|age| age > STALE_AFTER);
+        if stale {
+            let _ = fs::remove_file(&path);
+            continue;
+        }
+        live.push((path, plugin, pid));
+    }
+    live.sort_by_key(|(_, _, pid)| *pid);
+    live
+}
+
+/// The plugin and pid of every process publishing metrics
+pub fn publishers() -> Vec<(String, u32)> {
+    live_files()
+        .into_iter()
+        .map(|(_, plugin, pid)| (plugin, pid))
+        .collect()
+}
+
+/// The metrics of every running plugin process in one exposition, each sample labelled
+/// with its plugin and pid. Files of processes that have exited are removed.
+pub fn gather() -> String {
+    let mut families: BTreeMap<String, Merged> = BTreeMap::new();
+    let mut up = Vec::new();
+    for (path, plugin, pid) in live_files() {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let extra = render_labels(&[("plugin", &plugin), ("pid", &pid.to_string())]);
+        up.push(format!("proxy_plugin_up{{{}}} 1", extra));
+        for line in content.lines() {
+            if let Some(rest) = line.strip_prefix("# HELP ") {
+                let (name, help) = rest.split_once(' ').unwrap_or((rest, ""));
+                families.entry(name.to_string()).or_insert_with(|| Merged {
+                    help: help.to_string(),
+                    kind: "untyped".to_string(),
+                    samples: Vec::new(),
+                });
+            } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+                if let Some((name, kind)) = rest.split_once(' ') {
+                    if let Some(family) = families.get_mut(name) {
+                        family.kind = kind.to_string();
+                    }
+                }
+            } else if !line.is_empty() && !line.starts_with('#') {
+                let end = line.find(['{', ' ']).unwrap_or(line.len());
+                let name = &line[..end];
+                let sample = match line[end..].strip_prefix('{') {
+                    Some(rest) => format!("{}{{{},{}", name, extra, rest),
+                    None => format!("{}{{{}}}{}", name, extra, &line[end..]),
+                };
+                if let Some(family) = families.get_mut(name) {
+                    family.samples.push(sample);
+                }
+            }
+        }
+    }
+
+    let mut text = String::new();
+    text.push_str("# HELP proxy_plugin_up Plugin processes publishing metrics\n");
+    text.push_str("# TYPE proxy_plugin_up gauge\n");
+    for line in up {
+        text.push_str(&line);
+        text.push('\n');
+    }
+    for (name, family) in families {
+        text.push_str(&format!("# HELP {} {}\n", name, family.help));
+        text.push_str(&format!("# TYPE {} {}\n", name, family.kind));
+        for sample in family.samples {
+            text.push_str(&sample);
+            text.push('\n');
+        }
+    }
+    text
+}
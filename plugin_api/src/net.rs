@@ -0,0 +1,292 @@
+// Addresses that work the same for IPv4 and IPv6. An IPv6 address is written in brackets
+// when a port follows ([::1]:8080) and without them to bind or connect; `listen` binds a
+// configured address, `::` taking IPv4 connections as well (dual-stack).
+//
+// An address can also be a Unix socket, written as a path (/run/proxy/db.sock) or with a
+// "unix:" prefix; `bind` and `connect` take either kind. A socket file left behind by a
+// process that died is replaced, and the file is removed again when the listener is
+// dropped or the process exits.
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+#[cfg(unix)]
+use {
+    std::fs,
+    std::path::{Path, PathBuf},
+    std::sync::{Mutex, Once},
+    tokio::net::{UnixListener, UnixStream},
+};
+
+/// Where listeners bind unless configured otherwise
+pub const LOOPBACK: &str = "127.0.0.1";
+
+/// The host without the brackets an IPv6 address may be written in
+pub fn unbracket(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// `host:port`, with an IPv6 address in brackets
+pub fn host_port(host: &str, port: u16) -> String {
+    let host = unbracket(host);
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// Splits "host:port", "[::1]:port", "host", "[::1]" or "::1" into the host, without
+/// brackets, and the port when there is one
+pub fn split_host_port(authority: &str) -> (&str, Option<&str>) {
+    if let Some(rest) = authority.strip_prefix('[') {
+        return match rest.split_once(']') {
+            Some((host, after)) => (host, after.strip_prefix(':')),
+            None => (authority, None),
+        };
+    }
+    match authority.rsplit_once(':') {
+        // More than one colon is an IPv6 address without a port
+        Some((host, port)) if !host.contains(':') => (host, Some(port)),
+        _ => (authority, None),
+    }
+}
+
+/// Binds a listener on `host` (a name, or an IPv4 or IPv6 address, bracketed or not) and
+/// `port`. The IPv6 wildcard `::` also accepts IPv4 connections.
+pub async fn listen(host: &str, port: u16) -> io::Result<TcpListener> {
+    let host = unbracket(host);
+    if host.parse::<Ipv6Addr>().is_ok_and(|ip| ip.is_unspecified()) {
+        let socket = TcpSocket::new_v6()?;
+        socket.set_reuseaddr(true)?;
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+            let off: libc::c_int = 0;
+            let result = unsafe {
+                libc::setsockopt(
+                    socket.as_raw_fd(),
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_V6ONLY,
+                    (&off as *const libc::c_int).cast(),
+                    std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            if result != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        socket.bind(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port))?;
+        return socket.listen(1024);
+    }
+    TcpListener::bind((host, port)).await
+}
+
+/// The socket path when `address` is a Unix socket: "unix:" and a path, or an absolute path
+pub fn unix_path(address: &str) -> Option<&str> {
+    address
+        .strip_prefix("unix:")
+        .or_else(|| address.starts_with('/').then_some(address))
+}
+
+/// How a listener on `address` and `port` is printed: `host:port`, or the socket path
+pub fn listen_address(address: &str, port: u16) -> String {
+    match unix_path(address) {
+        Some(path) => path.to_string(),
+        None => host_port(address, port),
+    }
+}
+
+/// A listener on a TCP port or a Unix socket
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+/// A connection accepted by a `Listener` or opened with `connect`
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Listener {
+    /// The next connection, with who it came from: the peer address, or the pid of the
+    /// process on the other end of a Unix socket
+    pub async fn accept(&self) -> io::Result<(Stream, String)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((Stream::Tcp(stream), addr.to_string()))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept().await?;
+                let peer = match stream.peer_cred().ok().and_then(|cred| cred.pid()) {
+                    Some(pid) => format!("pid {}", pid),
+                    None => "a local process".to_string(),
+                };
+                Ok((Stream::Unix(stream), peer))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = self {
+            forget_socket(path);
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Socket files of this process' listeners, removed at exit
+#[cfg(unix)]
+static SOCKETS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[cfg(unix)]
+fn forget_socket(path: &Path) {
+    let mut sockets = SOCKETS.lock().unwrap_or_else(|e| e.into_inner());
+    sockets.retain(|socket| socket != path);
+}
+
+#[cfg(unix)]
+extern "C" fn remove_sockets() {
+    let sockets = SOCKETS.lock().unwrap_or_else(|e| e.into_inner());
+    for path in sockets.iter() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Clears the way for a socket at `path`: a socket nothing answers on is what a dead
+/// process left behind and is removed, anything else there is an error
+#[cfg(unix)]
+fn remove_stale(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another process", path.display()),
+        )),
+        Err(_) => fs::remove_file(path),
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &str, mode: Option<u32>) -> io::Result<Listener> {
+    use std::os::unix::fs::PermissionsExt;
+    static CLEANUP: Once = Once::new();
+    let path = PathBuf::from(path);
+    remove_stale(&path)?;
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    if let Some(mode) = mode {
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+    }
+    SOCKETS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(path.clone());
+    CLEANUP.call_once(|| unsafe {
+        libc::atexit(remove_sockets);
+    });
+    Ok(Listener::Unix(listener, path))
+}
+
+/// Binds `address` like `listen`, or a Unix socket when the address is one (see
+/// `unix_path`); `port` is unused then. `mode` sets the socket file's permission bits,
+/// e.g. 0o660 to let the group connect.
+pub async fn bind(address: &str, port: u16, mode: Option<u32>) -> io::Result<Listener> {
+    match unix_path(address) {
+        #[cfg(unix)]
+        Some(path) => bind_unix(path, mode),
+        #[cfg(not(unix))]
+        Some(_) => {
+            let _ = mode;
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets are not supported on this platform",
+            ))
+        }
+        None => listen(address, port).await.map(Listener::Tcp),
+    }
+}
+
+/// Connects to `target`, a `host:port` or a Unix socket
+pub async fn connect(target: &str) -> io::Result<Stream> {
+    match unix_path(target) {
+        #[cfg(unix)]
+        Some(path) => UnixStream::connect(path).await.map(Stream::Unix),
+        #[cfg(not(unix))]
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix sockets are not supported on this platform",
+        )),
+        None => TcpStream::connect(target).await.map(Stream::Tcp),
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+// Notifications to the user, configured once for every plugin in notifications.conf:
+// desktop notifications, Slack, a generic webhook and a command hook. The host sends
+// them, `proxy notify`, so plugins don't each carry the clients; `send` starts it in the
+// background and returns right away.
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Where the notification channels are configured, e.g. ~/.cohandv/proxy/config/notifications.conf
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("PROXY_NOTIFICATIONS_CONFIG") {
+        Some(PathBuf::from(path))
+    } else {
+        dirs::home_dir().map(|h| h.join(".cohandv/proxy/config/notifications.conf"))
+    }
+}
+
+/// Notifies the user of `event` (e.g. "forward_dropped") through every configured channel.
+/// Does nothing when no channel is configured.
+pub fn send(plugin: &str, event: &str, title: &str, body: &str) {
+    if !config_path().is_some_and(|path| path.exists()) {
+        return;
+    }
+    let Ok(exe) = crate::proxy_exe() else {
+        return;
+    };
+    // Values given with `=` so a body starting with a dash isn't taken for a flag
+    let spawned = Command::new(exe)
+        .arg("notify")
+        .arg(format!("--plugin={}", plugin))
+        .arg(format!("--event={}", event))
+        .arg(format!("--title={}", title))
+        .arg(format!("--body={}", body))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn();
+    match spawned {
+        // Reaped when it is done
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => eprintln!("⚠️  Could not send a notification: {}", e),
+    }
+}
@@ -0,0 +1,48 @@
+// A background thread running a task at an interval. The modules using it stop and join
+// it from an exit handler, which also runs when the host unloads the plugin library, or
+// from telemetry::shutdown, so the thread never wakes up in code that is no longer mapped.
+use std::sync::{Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub(crate) struct Periodic {
+    stopped: Mutex<bool>,
+    wake: Condvar,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Periodic {
+    pub(crate) const fn new() -> Self {
+        Periodic {
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+            thread: Mutex::new(None),
+        }
+    }
+
+    /// Runs `task` every `interval`, the first time right away
+    pub(crate) fn start(&'static self, interval: Duration, task: impl Fn() + Send + 'static) {
+        let thread = std::thread::spawn(move || loop {
+            task();
+            let stopped = self.stopped.lock().unwrap_or_else(|e| e.into_inner());
+            let (stopped, _) = self
+                .wake
+                .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                .unwrap_or_else(|e| e.into_inner());
+            if *stopped {
+                return;
+            }
+        });
+        *self.thread.lock().unwrap_or_else(|e| e.into_inner()) = Some(thread);
+    }
+
+    /// Stops the thread and waits for a running task to finish
+    pub(crate) fn stop(&self) {
+        *self.stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.wake.notify_all();
+        let thread = self.thread.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+}
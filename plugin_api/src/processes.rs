@@ -0,0 +1,418 @@
+// Plugin processes run in the background on behalf of other tools, such as the web_ui
+// dashboard, `proxy serve-control` and `proxy daemon`. Each one started here leaves a pid
+// record, `processes/<plugin>-<pid>.pid` in the state directory, and counts as running
+// while a process with that pid and the start time in the record is alive, so a pid the
+// OS has since reused is never taken for it; a plugin started any other way counts while
+// it publishes metrics. The output of a started process goes to `processes/<plugin>-<time>.log`.
+// Starting, stopping and exits are announced on the event bus (`events`), whose start and
+// stop requests `handle` carries out.
+//
+// A started process leads a process group of its own, with a plugin binary's process in
+// it. Windows can't send Ctrl-C to another group, so it gets Ctrl-Break there instead,
+// and only from a process on the same console: one that can't be interrupted is ended.
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::events::{self, Event};
+use crate::metrics;
+
+/// How long a process on Windows gets to exit after Ctrl-Break before it is ended
+#[cfg(windows)]
+const STOP_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct Running {
+    pub plugin: String,
+    pub pid: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Started {
+    pub pid: u32,
+    pub log: PathBuf,
+}
+
+/// Whether the process with `pid` is running
+#[cfg(unix)]
+pub fn alive(pid: u32) -> bool {
+    // EPERM is a process of another user; only ESRCH means there is none
+    let signalled = unsafe { libc::kill(pid as i32, 0) } == 0;
+    signalled || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Whether the process with `pid` is running
+#[cfg(windows)]
+pub fn alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return false;
+        }
+        let mut exit_code = 0u32;
+        let ok = GetExitCodeProcess(process, &mut exit_code);
+        CloseHandle(process);
+        ok != 0 && exit_code == STILL_ACTIVE as u32
+    }
+}
+
+/// When the process with `pid` started, in a unit of the platform's own; `None` when
+/// there's no such process or the platform doesn't tell
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn start_time(pid: u32) -> Option<u64> {
+    // The 22nd field of /proc/<pid>/stat, counted after the parenthesized command name
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// When the process with `pid` started, in a unit of the platform's own; `None` when
+/// there's no such process or the platform doesn't tell
+#[cfg(target_os = "macos")]
+fn start_time(pid: u32) -> Option<u64> {
+    let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+    let read = unsafe {
+        libc::proc_pidinfo(
+            pid as libc::c_int,
+            libc::PROC_PIDTBSDINFO,
+            0,
+            &mut info as *mut _ as *mut libc::c_void,
+            size,
+        )
+    };
+    (read == size).then(|| info.pbi_start_tvsec * 1_000_000 + info.pbi_start_tvusec)
+}
+
+/// When the process with `pid` started, in a unit of the platform's own; `None` when
+/// there's no such process or the platform doesn't tell
+#[cfg(windows)]
+fn start_time(pid: u32) -> Option<u64> {
+    use windows_sys::Win32::Foundation::{CloseHandle, FILETIME};
+    use windows_sys::Win32::System::Threading::{
+        GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+        let zero = FILETIME {
+            dwLowDateTime: 0,
+            dwHighDateTime: 0,
+        };
+        let (mut created, mut exited, mut kernel, mut user) = (zero, zero, zero, zero);
+        let ok = GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user);
+        CloseHandle(process);
+        (ok != 0).then(|| ((created.dwHighDateTime as u64) << 32) | created.dwLowDateTime as u64)
+    }
+}
+
+/// When the process with `pid` started, in a unit of the platform's own; `None` when
+/// there's no such process or the platform doesn't tell
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    windows
+)))]
+fn start_time(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// Whether the process a pid record names still runs: a record holding a start time only
+/// matches the process that started then, not a later one given the same pid
+fn recorded_alive(pid: u32, record: &str) -> bool {
+    let started = record
+        .lines()
+        .find_map(|line| line.strip_prefix("started "))
+        .and_then(|started| started.trim().parse::<u64>().ok());
+    match started {
+        Some(started) => start_time(pid) == Some(started),
+        None => alive(pid),
+    }
+}
+
+/// Interrupts the process group `pid` leads, as Ctrl-C would
+pub fn interrupt(pid: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    if unsafe { libc::kill(-(pid as i32), libc::SIGINT) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+
+        if unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Ends the process group `pid` leads at once, for one that didn't exit when interrupted
+pub fn kill(pid: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    if unsafe { libc::kill(-(pid as i32), libc::SIGKILL) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[cfg(windows)]
+    {
+        let status = Command::new("taskkill")
+            .arg("/PID")
+            .arg(pid.to_string())
+            .arg("/T")
+            .arg("/F")
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("taskkill failed for pid {}", pid)));
+        }
+    }
+    Ok(())
+}
+
+/// The plugin a command line runs, its first argument
+fn plugin(args: &[String]) -> io::Result<&str> {
+    match args.first() {
+        Some(plugin) if !plugin.starts_with('-') => Ok(plugin),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The command line has to start with a plugin name",
+        )),
+    }
+}
+
+fn processes_dir() -> io::Result<PathBuf> {
+    crate::plugin_state_dir("processes").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not determine the state directory",
+        )
+    })
+}
+
+/// The processes started here, from their pid records; records of processes that have
+/// exited, or whose pid now belongs to another process, are removed
+fn recorded() -> Vec<Running> {
+    let mut recorded = Vec::new();
+    let entries = processes_dir()
+        .ok()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .flatten();
+    for entry in entries {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("pid") {
+            continue;
+        }
+        let Some((plugin, pid)) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.rsplit_once('-'))
+            .and_then(|(plugin, pid)| Some((plugin.to_string(), pid.parse().ok()?)))
+        else {
+            continue;
+        };
+        let record = fs::read_to_string(&path).unwrap_or_default();
+        if recorded_alive(pid, &record) {
+            recorded.push(Running { plugin, pid });
+        } else {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    recorded
+}
+
+/// Every running plugin process: those started here and those publishing metrics, by pid
+pub fn running() -> Vec<Running> {
+    let mut running = recorded();
+    for (plugin, pid) in metrics::publishers() {
+        if !running.iter().any(|process| process.pid == pid) {
+            running.push(Running { plugin, pid });
+        }
+    }
+    running.sort_by_key(|process| process.pid);
+    running
+}
+
+/// Runs `proxy <args>` in a process group of its own, with its output going to `log`,
+/// and records it as running; `exited` removes the record once it has been waited for
+pub fn spawn(args: &[String], log: File) -> io::Result<Child> {
+    let plugin = plugin(args)?;
+    let dir = processes_dir()?;
+    fs::create_dir_all(&dir)?;
+
+    // Started through proxy, which runs library plugins itself and plugin binaries as
+    // children
+    let mut cmd = Command::new(crate::proxy_exe()?);
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // Out of the caller's process group, so a Ctrl-C in its terminal doesn't reach it
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        use windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP;
+        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+    let child = cmd.spawn()?;
+    let record = dir.join(format!("{}-{}.pid", plugin, child.id()));
+    let mut content = format!("{}\n", args.join(" "));
+    if let Some(started) = start_time(child.id()) {
+        content.push_str(&format!("started {}\n", started));
+    }
+    if let Err(e) = fs::write(&record, content) {
+        eprintln!("⚠️  Failed to write {}: {}", record.display(), e);
+    }
+    Ok(child)
+}
+
+/// Removes the pid record of a process `spawn` started, after it exited
+pub fn exited(pid: u32) {
+    let entries = processes_dir()
+        .ok()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .flatten();
+    let suffix = format!("-{}.pid", pid);
+    for entry in entries {
+        if entry.file_name().to_string_lossy().ends_with(&suffix) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Runs `proxy <args>` in the background, the first argument naming the plugin. It keeps
+/// running when the caller exits or its terminal gets a Ctrl-C.
+pub fn start(args: &[String]) -> io::Result<Started> {
+    start_for("", args)
+}
+
+/// `start`, announced as answering the request `id`
+fn start_for(id: &str, args: &[String]) -> io::Result<Started> {
+    let plugin = plugin(args)?.to_string();
+    let logs = processes_dir()?;
+    fs::create_dir_all(&logs)?;
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let log_path = logs.join(format!("{}-{}.log", plugin, started));
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)?;
+
+    let mut child = spawn(args, log)?;
+    let pid = child.id();
+    events::announce(&Event::Started {
+        id: id.to_string(),
+        plugin: plugin.clone(),
+        pid,
+    });
+    // Reaped when it exits, however it is stopped; when the caller exits first, `running`
+    // removes the record once it finds the process gone
+    std::thread::spawn(move || {
+        let _ = child.wait();
+        exited(pid);
+        events::announce(&Event::Exited { plugin, pid });
+    });
+    Ok(Started { pid, log: log_path })
+}
+
+/// Interrupts a running plugin process as Ctrl-C would, so it cleans up. Returns the
+/// plugin's name.
+pub fn stop(pid: u32) -> io::Result<String> {
+    stop_for("", pid)
+}
+
+/// `stop`, announced as answering the request `id`
+fn stop_for(id: &str, pid: u32) -> io::Result<String> {
+    let Some(process) = running().into_iter().find(|process| process.pid == pid) else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No plugin process with pid {}", pid),
+        ));
+    };
+    // One started here leads its own process group, which also holds a plugin binary's
+    // process: interrupting the group reaches that too
+    let started_here = recorded().iter().any(|process| process.pid == pid);
+    #[cfg(unix)]
+    if started_here {
+        interrupt(pid)?;
+    } else if unsafe { libc::kill(pid as i32, libc::SIGINT) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[cfg(windows)]
+    if started_here && interrupt(pid).is_ok() {
+        let started = start_time(pid);
+        std::thread::spawn(move || {
+            std::thread::sleep(STOP_GRACE);
+            if alive(pid) && start_time(pid) == started {
+                let _ = kill(pid);
+            }
+        });
+    } else {
+        kill(pid)?;
+    }
+    events::announce(&Event::Stopped {
+        id: id.to_string(),
+        plugin: process.plugin.clone(),
+        pid,
+    });
+    Ok(process.plugin)
+}
+
+/// Carries out a start or stop request from the event bus, answering it there; other
+/// events are left alone
+pub fn handle(event: &Event) {
+    let result = match event {
+        Event::Start { id, args } => start_for(id, args).map(|_| ()),
+        Event::Stop { id, pid } => stop_for(id, *pid).map(|_| ()),
+        _ => return,
+    };
+    if let (Err(e), Some(id)) = (result, event.id()) {
+        events::announce(&Event::Failed {
+            id: id.to_string(),
+            message: e.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    fn records_match_only_the_process_that_started_then() {
+        let pid = std::process::id();
+        let started = start_time(pid).unwrap();
+        assert!(recorded_alive(
+            pid,
+            &format!("web_ui\nstarted {}\n", started)
+        ));
+        assert!(!recorded_alive(
+            pid,
+            &format!("web_ui\nstarted {}\n", started + 1)
+        ));
+        // Records written before start times were kept go by the pid alone
+        assert!(recorded_alive(pid, "web_ui\n"));
+    }
+}
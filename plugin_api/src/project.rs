@@ -0,0 +1,222 @@
+// Project-local configuration: a .proxy.toml in the current directory or one of its
+// parents, found the way git finds its repository. Each table named after a plugin is
+// merged over that plugin's config in the home directory: tables key by key, any other
+// value (forwards, lists) replaced whole. `up` lists the plugins `proxy up` starts.
+//
+// A project config can start processes and point forwards anywhere, and one is picked up
+// from any directory above, so a checked-out repository could bring its own. It is only
+// used once the user trusted it with `proxy project allow`, which records its path and a
+// sha256 of its content in `trusted-projects` in the configuration directory; one that
+// changed since has to be allowed again. A config named by $PROXY_PROJECT_CONFIG is
+// trusted as given.
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const FILE_NAME: &str = ".proxy.toml";
+
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// The project config that applies here: $PROXY_PROJECT_CONFIG, or the closest .proxy.toml
+/// walking up from the current directory
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("PROXY_PROJECT_CONFIG") {
+        return Some(PathBuf::from(path)).filter(|path| !path.as_os_str().is_empty());
+    }
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Where the trusted project configs are recorded, next to plugins.d
+fn trust_path() -> io::Result<PathBuf> {
+    crate::plugin_config_dir()
+        .and_then(|dir| dir.parent().map(|dir| dir.join("trusted-projects")))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not determine the configuration directory",
+            )
+        })
+}
+
+/// The trusted project configs: the sha256 of the content trusted, by canonical path
+fn trusted_list() -> BTreeMap<PathBuf, String> {
+    let Ok(content) = trust_path().and_then(fs::read_to_string) else {
+        return BTreeMap::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(hash, path)| (PathBuf::from(path), hash.to_string()))
+        .collect()
+}
+
+/// Written like sha256sum's output: the hash, two spaces and the path
+fn write_trusted_list(list: &BTreeMap<PathBuf, String>) -> io::Result<()> {
+    let path = trust_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content: String = list
+        .iter()
+        .map(|(path, hash)| format!("{}  {}\n", hash, path.display()))
+        .collect();
+    fs::write(path, content)
+}
+
+/// The canonical path of the project config at `path` and the sha256 of its content
+fn fingerprint(path: &Path) -> io::Result<(PathBuf, String)> {
+    let path = fs::canonicalize(path)?;
+    let hash = hex::encode(Sha256::digest(fs::read(&path)?));
+    Ok((path, hash))
+}
+
+/// Whether the project config at `path` may be used: it was allowed as it is now, or was
+/// named by $PROXY_PROJECT_CONFIG
+pub fn trusted(path: &Path) -> bool {
+    if std::env::var_os("PROXY_PROJECT_CONFIG").is_some_and(|given| Path::new(&given) == path) {
+        return true;
+    }
+    fingerprint(path).is_ok_and(|(path, hash)| trusted_list().get(&path) == Some(&hash))
+}
+
+/// Trusts the project config at `path` as it is now; returns its canonical path
+pub fn allow(path: &Path) -> io::Result<PathBuf> {
+    let (path, hash) = fingerprint(path)?;
+    let mut list = trusted_list();
+    list.insert(path.clone(), hash);
+    write_trusted_list(&list)?;
+    Ok(path)
+}
+
+/// Stops trusting the project config at `path`; returns whether it was trusted
+pub fn deny(path: &Path) -> io::Result<bool> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut list = trusted_list();
+    if list.remove(&path).is_none() {
+        return Ok(false);
+    }
+    write_trusted_list(&list)?;
+    Ok(true)
+}
+
+fn untrusted(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "{} is not trusted, or changed since it was; check it and run: proxy project allow",
+            path.display()
+        ),
+    )
+}
+
+/// The parsed project config, None outside a project or when it isn't trusted
+pub fn load() -> io::Result<Option<(PathBuf, toml::Table)>> {
+    let Some(path) = path() else {
+        return Ok(None);
+    };
+    if !trusted(&path) {
+        // Once per process, plugins reading several configs
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            eprintln!("⚠️  Ignoring {}", untrusted(&path));
+        }
+        return Ok(None);
+    }
+    let content = crate::read_file(&path)?;
+    let table = toml::from_str(&content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid {}: {}", path.display(), e),
+        )
+    })?;
+    Ok(Some((path, table)))
+}
+
+/// The settings the project config defines for a plugin
+pub fn section(plugin_name: &str) -> io::Result<Option<toml::Table>> {
+    let Some((_, mut table)) = load()? else {
+        return Ok(None);
+    };
+    match table.remove(plugin_name) {
+        Some(toml::Value::Table(section)) => Ok(Some(section)),
+        _ => Ok(None),
+    }
+}
+
+/// Whether the project config sets `key` for a plugin, e.g. its own forwards
+pub fn defines(plugin_name: &str, key: &str) -> bool {
+    section(plugin_name)
+        .ok()
+        .flatten()
+        .is_some_and(|section| section.contains_key(key))
+}
+
+/// The command lines `proxy up` starts, each a plugin name and its arguments
+pub fn up() -> io::Result<Option<(PathBuf, Vec<Vec<String>>)>> {
+    if let Some(path) = path().filter(|path| !trusted(path)) {
+        return Err(untrusted(&path));
+    }
+    let Some((path, table)) = load()? else {
+        return Ok(None);
+    };
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "`up` in {} has to be a list of plugin command lines",
+                path.display()
+            ),
+        )
+    };
+    let commands = match table.get("up") {
+        None => Vec::new(),
+        Some(toml::Value::Array(entries)) => {
+            let mut commands = Vec::new();
+            for entry in entries {
+                let line = entry.as_str().ok_or_else(invalid)?;
+                let args: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+                if !args.is_empty() {
+                    commands.push(args);
+                }
+            }
+            commands
+        }
+        Some(_) => return Err(invalid()),
+    };
+    Ok(Some((path, commands)))
+}
+
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// `content` of a plugin's home config (None when it has none) with the project's
+/// settings merged over it. Content that doesn't parse is left to the plugin to report.
+pub(crate) fn merged(plugin_name: &str, content: Option<String>) -> io::Result<Option<String>> {
+    let Some(section) = section(plugin_name)? else {
+        return Ok(content);
+    };
+    let mut base = match &content {
+        Some(content) => match toml::from_str::<toml::Table>(content) {
+            Ok(base) => base,
+            Err(_) => return Ok(Some(content.clone())),
+        },
+        None => toml::Table::new(),
+    };
+    merge(&mut base, section);
+    toml::to_string(&base)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,48 @@
+// The multi-threaded Tokio runtime async plugins run on (see `AsyncPlugin`), started on
+// first use and shared by everything in the plugin library, instead of every command
+// building its own. Each plugin library links its own copy of plugin_api and Tokio, so
+// the runtime can't be handed over by the host; one per library is what can be shared.
+//
+// Its worker threads are shut down from an exit handler, which also runs when the host
+// unloads the library, so none is left running in code that is no longer mapped.
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+
+static RUNTIME: Mutex<Option<Arc<Runtime>>> = Mutex::new(None);
+
+/// How long tasks still running when the library is unloaded get to finish
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+extern "C" fn stop_runtime() {
+    let runtime = RUNTIME.lock().unwrap_or_else(|e| e.into_inner()).take();
+    // Still in use when the process exits from inside a plugin command; its threads end
+    // with the process then
+    if let Some(runtime) = runtime.and_then(Arc::into_inner) {
+        runtime.shutdown_timeout(SHUTDOWN_TIMEOUT);
+    }
+}
+
+/// The shared runtime, started on the first call
+pub fn runtime() -> Arc<Runtime> {
+    static CLEANUP: Once = Once::new();
+    let mut runtime = RUNTIME.lock().unwrap_or_else(|e| e.into_inner());
+    runtime
+        .get_or_insert_with(|| {
+            CLEANUP.call_once(|| unsafe {
+                libc::atexit(stop_runtime);
+            });
+            Arc::new(
+                Builder::new_multi_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Failed to create Tokio runtime"),
+            )
+        })
+        .clone()
+}
+
+/// Runs `future` to completion on the shared runtime, blocking the calling thread
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    runtime().block_on(future)
+}
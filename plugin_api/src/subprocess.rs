@@ -0,0 +1,461 @@
+// Plugins built as programs of their own, run in a child process so a plugin that
+// panics, aborts or segfaults can't take `proxy` down with it; built with the
+// `subprocess` feature. The binary's main hands its plugin to `serve`, and is installed
+// in the plugin directory as proxy-plugin-<name> (proxy-plugin-<name>.exe on Windows).
+//
+// When the host starts, it asks each binary what it is (`describe`) in JSON-RPC 2.0, one
+// message per line over the child's stdin and stdout, and adds the command line it gets
+// back as a subcommand; `proxy config validate` asks it to `validate_config` the same
+// way. To run it, the host starts the binary again with the command's arguments and the
+// terminal handed through: without PROXY_PLUGIN_PROTOCOL set the binary runs its command
+// like any program, so it can also be run on its own.
+use crate::config::ConfigError;
+use crate::{Plugin, PLUGIN_API_VERSION, PROXY_EXE_ENV};
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgAction, Command};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{self, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Set by the host when it talks to a plugin binary, to the plugin API version it uses
+pub const PROTOCOL_ENV: &str = "PROXY_PLUGIN_PROTOCOL";
+/// What the file names of plugin binaries start with
+pub const BINARY_PREFIX: &str = "proxy-plugin-";
+
+const JSONRPC: &str = "2.0";
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Response {
+    jsonrpc: String,
+    id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// What a plugin binary answers to `describe`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Description {
+    /// The plugin API the binary was built against
+    pub api_version: u32,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub command: CommandSpec,
+    pub sample_config: Option<String>,
+}
+
+/// A clap command as it travels between the processes: what help, man pages and
+/// parsing in the host need. Values are checked by the plugin, which parses them again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandSpec {
+    pub name: String,
+    #[serde(default)]
+    pub about: Option<String>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub hide: bool,
+    #[serde(default)]
+    pub subcommand_required: bool,
+    #[serde(default)]
+    pub args: Vec<ArgSpec>,
+    #[serde(default)]
+    pub subcommands: Vec<CommandSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArgSpec {
+    pub id: String,
+    /// Neither long nor short makes it positional
+    #[serde(default)]
+    pub long: Option<String>,
+    #[serde(default)]
+    pub short: Option<char>,
+    #[serde(default)]
+    pub help: Option<String>,
+    #[serde(default)]
+    pub value_name: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    /// "set", "append", "set_true", "set_false" or "count"
+    pub action: String,
+    /// Takes more than one value at a time
+    #[serde(default)]
+    pub multiple: bool,
+    #[serde(default)]
+    pub default_values: Vec<String>,
+    #[serde(default)]
+    pub possible_values: Vec<String>,
+    #[serde(default)]
+    pub hide: bool,
+    #[serde(default)]
+    pub global: bool,
+    /// Takes the rest of the command line, e.g. a command to run
+    #[serde(default)]
+    pub trailing: bool,
+    #[serde(default)]
+    pub allow_hyphen_values: bool,
+}
+
+/// Commands built from a spec live as long as the process, which clap's names need
+fn leak(text: &str) -> &'static str {
+    Box::leak(text.to_string().into_boxed_str())
+}
+
+impl CommandSpec {
+    pub fn from_command(cmd: &Command) -> Self {
+        CommandSpec {
+            name: cmd.get_name().to_string(),
+            about: cmd.get_about().map(|about| about.to_string()),
+            aliases: cmd.get_visible_aliases().map(str::to_string).collect(),
+            hide: cmd.is_hide_set(),
+            subcommand_required: cmd.is_subcommand_required_set(),
+            args: cmd.get_arguments().filter_map(ArgSpec::from_arg).collect(),
+            subcommands: cmd.get_subcommands().map(Self::from_command).collect(),
+        }
+    }
+
+    pub fn to_command(&self) -> Command {
+        let mut cmd = Command::new(leak(&self.name))
+            .hide(self.hide)
+            .subcommand_required(self.subcommand_required);
+        if let Some(about) = &self.about {
+            cmd = cmd.about(about.clone());
+        }
+        for alias in &self.aliases {
+            cmd = cmd.visible_alias(leak(alias));
+        }
+        for arg in &self.args {
+            cmd = cmd.arg(arg.to_arg());
+        }
+        for sub in &self.subcommands {
+            cmd = cmd.subcommand(sub.to_command());
+        }
+        cmd
+    }
+}
+
+impl ArgSpec {
+    /// None for the arguments clap adds itself, --help and --version
+    pub fn from_arg(arg: &Arg) -> Option<Self> {
+        let action = match arg.get_action() {
+            ArgAction::Set => "set",
+            ArgAction::Append => "append",
+            ArgAction::SetTrue => "set_true",
+            ArgAction::SetFalse => "set_false",
+            ArgAction::Count => "count",
+            _ => return None,
+        };
+        Some(ArgSpec {
+            id: arg.get_id().to_string(),
+            long: arg.get_long().map(str::to_string),
+            short: arg.get_short(),
+            help: arg.get_help().map(|help| help.to_string()),
+            value_name: arg
+                .get_value_names()
+                .and_then(|names| names.first())
+                .map(|name| name.to_string()),
+            required: arg.is_required_set(),
+            action: action.to_string(),
+            multiple: arg
+                .get_num_args()
+                .is_some_and(|range| range.max_values() > 1),
+            default_values: arg
+                .get_default_values()
+                .iter()
+                .map(|value| value.to_string_lossy().into_owned())
+                .collect(),
+            possible_values: arg
+                .get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect(),
+            hide: arg.is_hide_set(),
+            global: arg.is_global_set(),
+            trailing: arg.is_trailing_var_arg_set() || arg.is_last_set(),
+            allow_hyphen_values: arg.is_allow_hyphen_values_set(),
+        })
+    }
+
+    pub fn to_arg(&self) -> Arg {
+        let action = match self.action.as_str() {
+            "append" => ArgAction::Append,
+            "set_true" => ArgAction::SetTrue,
+            "set_false" => ArgAction::SetFalse,
+            "count" => ArgAction::Count,
+            _ => ArgAction::Set,
+        };
+        let mut arg = Arg::new(leak(&self.id))
+            .action(action)
+            .required(self.required)
+            .hide(self.hide)
+            .global(self.global)
+            .allow_hyphen_values(self.allow_hyphen_values);
+        if let Some(long) = &self.long {
+            arg = arg.long(leak(long));
+        }
+        if let Some(short) = self.short {
+            arg = arg.short(short);
+        }
+        if let Some(help) = &self.help {
+            arg = arg.help(help.clone());
+        }
+        if let Some(value_name) = &self.value_name {
+            arg = arg.value_name(leak(value_name));
+        }
+        if self.multiple {
+            arg = arg.num_args(1..);
+        }
+        if self.trailing {
+            arg = arg.num_args(1..).trailing_var_arg(true);
+        }
+        if !self.default_values.is_empty() {
+            arg = arg.default_values(self.default_values.iter().map(|value| leak(value)));
+        }
+        if !self.possible_values.is_empty() {
+            let values: Vec<&'static str> = self
+                .possible_values
+                .iter()
+                .map(|value| leak(value))
+                .collect();
+            arg = arg.value_parser(PossibleValuesParser::new(values));
+        }
+        arg
+    }
+}
+
+impl Response {
+    fn result(id: Value, result: Value) -> Self {
+        Response {
+            jsonrpc: JSONRPC.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: String) -> Self {
+        Response {
+            jsonrpc: JSONRPC.to_string(),
+            id,
+            result: None,
+            error: Some(RpcError { code, message }),
+        }
+    }
+}
+
+fn respond(plugin: &dyn Plugin, request: Request) -> Response {
+    match request.method.as_str() {
+        "describe" => {
+            let description = Description {
+                api_version: PLUGIN_API_VERSION,
+                name: plugin.name().to_string(),
+                version: plugin.version().to_string(),
+                description: plugin.description().to_string(),
+                command: CommandSpec::from_command(&plugin.subcommand()),
+                sample_config: plugin.sample_config().map(str::to_string),
+            };
+            match serde_json::to_value(description) {
+                Ok(result) => Response::result(request.id, result),
+                Err(e) => Response::error(request.id, INTERNAL_ERROR, e.to_string()),
+            }
+        }
+        "validate_config" => {
+            let Some(config) = request.params.get("config").and_then(Value::as_str) else {
+                return Response::error(
+                    request.id,
+                    INVALID_PARAMS,
+                    "validate_config needs the config".to_string(),
+                );
+            };
+            let errors = plugin.validate_config(config).err().unwrap_or_default();
+            match serde_json::to_value(errors) {
+                Ok(result) => Response::result(request.id, result),
+                Err(e) => Response::error(request.id, INTERNAL_ERROR, e.to_string()),
+            }
+        }
+        method => Response::error(
+            request.id,
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+        ),
+    }
+}
+
+/// The main of a plugin binary: answers the host's requests when it talks the protocol,
+/// and otherwise runs the plugin's command with the process' arguments
+pub fn serve<P: Plugin>(plugin: P) {
+    if std::env::var_os(PROTOCOL_ENV).is_none() {
+        let matches = plugin.subcommand().get_matches();
+        plugin.run(&matches);
+        crate::telemetry::shutdown();
+        return;
+    }
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => respond(&plugin, request),
+            Err(e) => Response::error(Value::Null, PARSE_ERROR, e.to_string()),
+        };
+        let Ok(response) = serde_json::to_string(&response) else {
+            break;
+        };
+        if writeln!(stdout, "{}", response)
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+    crate::telemetry::shutdown();
+}
+
+/// Whether `path` is named like a plugin binary
+pub fn is_plugin_binary(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let name = if cfg!(windows) {
+        match name.strip_suffix(".exe") {
+            Some(name) => name,
+            None => return false,
+        }
+    } else {
+        name
+    };
+    name.strip_prefix(BINARY_PREFIX)
+        .is_some_and(|plugin| !plugin.is_empty())
+}
+
+/// Sends the plugin binary at `path` one request, giving it `timeout` to answer
+fn call(path: &Path, method: &str, params: Value, timeout: Duration) -> io::Result<Value> {
+    let mut child = process::Command::new(path)
+        .env(PROTOCOL_ENV, PLUGIN_API_VERSION.to_string())
+        .env(PROXY_EXE_ENV, crate::proxy_exe()?)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let request = Request {
+        jsonrpc: JSONRPC.to_string(),
+        id: json!(1),
+        method: method.to_string(),
+        params,
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{}", serde_json::to_string(&request)?)?;
+    }
+
+    // Read on a thread, so a binary that never answers can be killed
+    let stdout = child.stdout.take();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        let result = match stdout {
+            Some(stdout) => BufReader::new(stdout).read_line(&mut line).map(|_| line),
+            None => Ok(line),
+        };
+        let _ = tx.send(result);
+    });
+    let line = match rx.recv_timeout(timeout) {
+        Ok(line) => line,
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no answer within {}s", timeout.as_secs()),
+            ));
+        }
+    };
+    let status = child.wait()?;
+    let line = line?;
+    if line.trim().is_empty() {
+        return Err(io::Error::other(format!(
+            "exited without answering {} ({})",
+            method, status
+        )));
+    }
+
+    let response: Response = serde_json::from_str(&line).map_err(invalid)?;
+    if let Some(error) = response.error {
+        return Err(io::Error::other(error.message));
+    }
+    Ok(response.result.unwrap_or_default())
+}
+
+fn invalid(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid answer: {}", e))
+}
+
+/// Asks the plugin binary at `path` what it is, giving it `timeout` to answer. A binary
+/// built against another plugin API is an error.
+pub fn describe(path: &Path, timeout: Duration) -> io::Result<Description> {
+    let result = call(
+        path,
+        "describe",
+        json!({ "api_version": PLUGIN_API_VERSION }),
+        timeout,
+    )?;
+    let description: Description = serde_json::from_value(result).map_err(invalid)?;
+    if description.api_version != PLUGIN_API_VERSION {
+        return Err(io::Error::other(format!(
+            "built against plugin API {}, this proxy uses {}; rebuild the plugin",
+            description.api_version, PLUGIN_API_VERSION
+        )));
+    }
+    Ok(description)
+}
+
+/// Has the plugin binary at `path` check a config file's content, see
+/// `Plugin::validate_config`
+pub fn validate_config(
+    path: &Path,
+    config: &str,
+    timeout: Duration,
+) -> io::Result<Result<(), Vec<ConfigError>>> {
+    let result = call(
+        path,
+        "validate_config",
+        json!({ "config": config }),
+        timeout,
+    )?;
+    let errors: Vec<ConfigError> = serde_json::from_value(result).map_err(invalid)?;
+    if errors.is_empty() {
+        Ok(Ok(()))
+    } else {
+        Ok(Err(errors))
+    }
+}
@@ -0,0 +1,271 @@
+// Tracing for the host and plugins, exported over OTLP/HTTP with JSON encoding. It is
+// configured once, for every process, by the standard environment variables:
+// OTEL_EXPORTER_OTLP_ENDPOINT (e.g. http://localhost:4318, spans go to /v1/traces) or
+// OTEL_EXPORTER_OTLP_TRACES_ENDPOINT, and OTEL_SERVICE_NAME. Without an endpoint spans
+// are inert. The host hands the span of the plugin it runs over in TRACEPARENT, so the
+// plugin's spans land in the same trace. Spans still open at `shutdown`, which the host
+// and plugin binaries call before they exit, are ended and sent then.
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use crate::periodic::Periodic;
+
+/// Environment variable carrying the parent span, in the W3C traceparent format
+pub const TRACEPARENT: &str = "TRACEPARENT";
+const EXPORT_INTERVAL: Duration = Duration::from_secs(2);
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Ended spans kept while the collector is unreachable; the oldest are dropped first
+const MAX_QUEUED: usize = 2048;
+
+struct Exporter {
+    /// Where spans are posted, e.g. http://localhost:4318/v1/traces
+    endpoint: String,
+    service: String,
+}
+
+/// A span that hasn't ended yet
+struct Open {
+    trace_id: String,
+    parent_id: Option<String>,
+    name: String,
+    start: u128,
+    /// OTLP key/value objects
+    attributes: Vec<Value>,
+    error: Option<String>,
+}
+
+static EXPORTER: OnceLock<Option<Exporter>> = OnceLock::new();
+/// Made on first use by `flush`, outside any async runtime, which the blocking client
+/// must not be created or used in
+static CLIENT: OnceLock<reqwest::blocking::Client> = OnceLock::new();
+static OPEN: Mutex<Option<HashMap<String, Open>>> = Mutex::new(None);
+static ENDED: Mutex<Vec<Value>> = Mutex::new(Vec::new());
+static WARNED: AtomicBool = AtomicBool::new(false);
+static EXPORTING: Periodic = Periodic::new();
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+/// `bytes` random bytes from the OS as lowercase hex
+fn random_id(bytes: usize) -> String {
+    let mut id = vec![0u8; bytes];
+    // Only fails without an OS source of randomness; the id then stays all zeros, which
+    // collectors reject as invalid
+    let _ = getrandom::getrandom(&mut id);
+    hex::encode(id)
+}
+
+/// Ends the spans still open and sends every ended one. The host calls it before it
+/// exits, and `subprocess::serve` when a plugin binary's command returns.
+pub fn shutdown() {
+    if EXPORTER.get().is_none_or(Option::is_none) {
+        return;
+    }
+    EXPORTING.stop();
+    let open = OPEN.lock().ok().and_then(|mut open| open.take());
+    for (span_id, span) in open.into_iter().flatten() {
+        finish(&span_id, span);
+    }
+    flush();
+}
+
+fn exporter() -> Option<&'static Exporter> {
+    EXPORTER
+        .get_or_init(|| {
+            let endpoint = match std::env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT") {
+                Ok(endpoint) => endpoint,
+                Err(_) => {
+                    let base = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+                    format!("{}/v1/traces", base.trim_end_matches('/'))
+                }
+            };
+            let service =
+                std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "proxy".to_string());
+            EXPORTING.start(EXPORT_INTERVAL, flush);
+            Some(Exporter { endpoint, service })
+        })
+        .as_ref()
+}
+
+/// The trace and span id handed over by the parent process, if any
+fn inherited() -> Option<(String, String)> {
+    let value = std::env::var(TRACEPARENT).ok()?;
+    let mut parts = value.split('-');
+    let (_, trace_id, span_id) = (parts.next()?, parts.next()?, parts.next()?);
+    (trace_id.len() == 32 && span_id.len() == 16)
+        .then(|| (trace_id.to_lowercase(), span_id.to_lowercase()))
+}
+
+fn finish(span_id: &str, span: Open) {
+    let mut rendered = json!({
+        "traceId": span.trace_id,
+        "spanId": span_id,
+        "name": span.name,
+        "kind": 1,
+        "startTimeUnixNano": span.start.to_string(),
+        "endTimeUnixNano": now().to_string(),
+        "attributes": span.attributes,
+    });
+    if let Some(parent_id) = span.parent_id {
+        rendered["parentSpanId"] = json!(parent_id);
+    }
+    if let Some(error) = span.error {
+        rendered["status"] = json!({ "code": 2, "message": error });
+    }
+
+    let mut ended = ENDED.lock().unwrap_or_else(|e| e.into_inner());
+    if ended.len() >= MAX_QUEUED {
+        ended.remove(0);
+    }
+    ended.push(rendered);
+}
+
+fn post(exporter: &Exporter, body: &Value) -> reqwest::Result<()> {
+    let client = match CLIENT.get() {
+        Some(client) => client,
+        None => {
+            let client = reqwest::blocking::Client::builder()
+                .timeout(EXPORT_TIMEOUT)
+                .build()?;
+            CLIENT.get_or_init(|| client)
+        }
+    };
+    client
+        .post(&exporter.endpoint)
+        .json(body)
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Sends the spans that have ended; done every few seconds and at `shutdown`
+pub fn flush() {
+    let Some(exporter) = exporter() else {
+        return;
+    };
+    let spans = std::mem::take(&mut *ENDED.lock().unwrap_or_else(|e| e.into_inner()));
+    if spans.is_empty() {
+        return;
+    }
+    let body = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": exporter.service } },
+                ],
+            },
+            "scopeSpans": [{ "scope": { "name": "proxy" }, "spans": spans }],
+        }],
+    });
+    if let Err(e) = post(exporter, &body) {
+        // Once per process, the collector may simply not be running
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            eprintln!("⚠️  Could not send traces to {}: {}", exporter.endpoint, e);
+        }
+    }
+}
+
+/// A timed operation in a trace, ended when dropped
+pub struct Span {
+    /// Trace and span id, None when tracing is off
+    ids: Option<(String, String)>,
+}
+
+impl Span {
+    /// A span under the one the parent process handed over, or the root of a new trace
+    pub fn start(name: &str) -> Span {
+        Self::open(name, inherited())
+    }
+
+    /// A span under this one
+    pub fn child(&self, name: &str) -> Span {
+        match &self.ids {
+            Some(ids) => Self::open(name, Some(ids.clone())),
+            None => Span { ids: None },
+        }
+    }
+
+    fn open(name: &str, parent: Option<(String, String)>) -> Span {
+        if exporter().is_none() {
+            return Span { ids: None };
+        }
+        let (trace_id, parent_id) = match parent {
+            Some((trace_id, span_id)) => (trace_id, Some(span_id)),
+            None => (random_id(16), None),
+        };
+        let span_id = random_id(8);
+        let mut open = OPEN.lock().unwrap_or_else(|e| e.into_inner());
+        open.get_or_insert_with(HashMap::new).insert(
+            span_id.clone(),
+            Open {
+                trace_id: trace_id.clone(),
+                parent_id,
+                name: name.to_string(),
+                start: now(),
+                attributes: Vec::new(),
+                error: None,
+            },
+        );
+        Span {
+            ids: Some((trace_id, span_id)),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Open)) {
+        let Some((_, span_id)) = &self.ids else {
+            return;
+        };
+        let mut open = OPEN.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(span) = open.as_mut().and_then(|open| open.get_mut(span_id)) {
+            f(span);
+        }
+    }
+
+    pub fn set_attribute(&self, key: &str, value: impl Display) {
+        let rendered = json!({ "key": key, "value": { "stringValue": value.to_string() } });
+        self.update(|span| span.attributes.push(rendered));
+    }
+
+    pub fn set_int(&self, key: &str, value: i64) {
+        // OTLP/JSON carries 64-bit integers as strings
+        let rendered = json!({ "key": key, "value": { "intValue": value.to_string() } });
+        self.update(|span| span.attributes.push(rendered));
+    }
+
+    /// Marks the span failed
+    pub fn set_error(&self, message: impl Display) {
+        let message = message.to_string();
+        self.update(|span| span.error = Some(message));
+    }
+
+    /// This span in the traceparent format, for a process started under it
+    pub fn traceparent(&self) -> Option<String> {
+        let (trace_id, span_id) = self.ids.as_ref()?;
+        Some(format!("00-{}-{}-01", trace_id, span_id))
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some((_, span_id)) = self.ids.take() else {
+            return;
+        };
+        let span = OPEN
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+            .and_then(|open| open.remove(&span_id));
+        if let Some(span) = span {
+            finish(&span_id, span);
+        }
+    }
+}
@@ -0,0 +1,543 @@
+// Protocol-aware logging of forwarded traffic, shared by the plugins that carry bytes
+// through the proxy process: every chunk read from either side is printed with a
+// timestamp, decoded as HTTP, PostgreSQL or MySQL messages when the protocol is known.
+// `relay` does the copying for plugins that hand a client over to an upstream, counting
+// connections and bytes in `metrics` (and in `usage`, given a target) and tracing each
+// connection as a span. A one-line summary of the latest messages is also published to
+// `traffic/<pid>.log` in the state directory, for `recent_messages` to read from other
+// processes.
+use crate::metrics;
+use crate::periodic::Periodic;
+use crate::telemetry::Span;
+use crate::usage;
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const ACTIVE_CONNECTIONS: &str = "proxy_relay_connections_active";
+const ACTIVE_CONNECTIONS_HELP: &str = "Client connections being relayed";
+/// Summaries a process keeps and publishes
+const RECENT_KEPT: usize = 100;
+const FEED_INTERVAL: Duration = Duration::from_secs(2);
+/// Feeds not rewritten for this long were left by processes that were killed
+const FEED_STALE_AFTER: Duration = Duration::from_secs(30);
+
+/// Tab-separated summary lines: timestamp, direction, protocol, summary
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static FEED_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+static FEED: Periodic = Periodic::new();
+
+#[derive(Debug, Clone)]
+pub enum Protocol {
+    Tcp,
+    Http,
+    Postgres,
+    Mysql,
+}
+
+impl From<&str> for Protocol {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "http" => Protocol::Http,
+            "postgres" | "postgresql" => Protocol::Postgres,
+            "mysql" | "mariadb" => Protocol::Mysql,
+            _ => Protocol::Tcp,
+        }
+    }
+}
+
+/// A decoded message summarized by another plugin process
+#[derive(Debug, Clone)]
+pub struct RecentMessage {
+    pub pid: u32,
+    pub timestamp: String,
+    pub direction: String,
+    pub protocol: String,
+    pub summary: String,
+}
+
+/// The directory every plugin process publishes its recent messages in
+pub fn traffic_dir() -> Option<PathBuf> {
+    crate::plugin_state_dir("traffic")
+}
+
+fn publish_feed() {
+    let Some(Some(path)) = FEED_PATH.get() else {
+        return;
+    };
+    let recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    let mut text = String::new();
+    for line in recent.iter() {
+        text.push_str(line);
+        text.push('\n');
+    }
+    drop(recent);
+    // Written aside and renamed, so a reader never sees half a file
+    let partial = path.with_extension("tmp");
+    if fs::write(&partial, text).is_ok() {
+        let _ = fs::rename(&partial, path);
+    }
+}
+
+extern "C" fn remove_feed() {
+    FEED.stop();
+    if let Some(Some(path)) = FEED_PATH.get() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn start_feed() -> Option<PathBuf> {
+    let dir = traffic_dir()?;
+    fs::create_dir_all(&dir).ok()?;
+    FEED.start(FEED_INTERVAL, publish_feed);
+    unsafe {
+        libc::atexit(remove_feed);
+    }
+    Some(dir.join(format!("{}.log", std::process::id())))
+}
+
+/// A one-line description of a message, e.g. its HTTP request line or SQL query
+fn summarize(direction: &str, protocol: &Protocol, data: &[u8]) -> String {
+    let text = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .trim_end_matches('\0')
+            .to_string()
+    };
+    let summary = match protocol {
+        Protocol::Http => {
+            // The request or status line, for the same messages log_http_message decodes
+            let line = data.split(|&b| b == b'\n').next().unwrap_or_default();
+            let line = text(line).trim().to_string();
+            ["GET ", "POST ", "PUT ", "DELETE ", "HTTP/"]
+                .iter()
+                .any(|start| line.starts_with(start))
+                .then_some(line)
+        }
+        Protocol::Postgres if data.len() >= 5 => match data[0] {
+            b'Q' => Some(format!("Query: {}", text(&data[5..]))),
+            b'C' => Some(format!("Command Complete: {}", text(&data[5..]))),
+            _ => None,
+        },
+        Protocol::Mysql if data.len() >= 5 && direction.contains('→') && data[4] == 0x03 => {
+            Some(format!("Query: {}", text(&data[5..])))
+        }
+        _ => None,
+    };
+    let summary = summary.unwrap_or_else(|| {
+        let preview = &data[..std::cmp::min(60, data.len())];
+        match std::str::from_utf8(preview) {
+            Ok(preview)
+                if preview
+                    .chars()
+                    .all(|c| c.is_ascii_graphic() || c.is_ascii_whitespace()) =>
+            {
+                format!("{} bytes: {}", data.len(), preview)
+            }
+            _ => format!("{} bytes", data.len()),
+        }
+    });
+    summary
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .take(200)
+        .collect()
+}
+
+fn remember(direction: &str, protocol: &Protocol, data: &[u8], timestamp: &str) {
+    if FEED_PATH.get_or_init(start_feed).is_none() {
+        return;
+    }
+    let line = format!(
+        "{}\t{}\t{:?}\t{}",
+        timestamp,
+        direction.replace('\t', " "),
+        protocol,
+        summarize(direction, protocol, data)
+    );
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() >= RECENT_KEPT {
+        recent.pop_front();
+    }
+    recent.push_back(line);
+}
+
+/// The latest messages decoded by every running plugin process, oldest first
+pub fn recent_messages(limit: usize) -> Vec<RecentMessage> {
+    let mut messages = Vec::new();
+    let entries = traffic_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .flatten();
+    for entry in entries {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("log") {
+            continue;
+        }
+        let Some(pid) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok())
+        else {
+            continue;
+        };
+        let stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_none_or(|age| age > FEED_STALE_AFTER);
+        if stale {
+            let _ = fs::remove_file(&path);
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        for line in content.lines() {
+            let mut fields = line.splitn(4, '\t');
+            if let (Some(timestamp), Some(direction), Some(protocol), Some(summary)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            {
+                messages.push(RecentMessage {
+                    pid,
+                    timestamp: timestamp.to_string(),
+                    direction: direction.to_string(),
+                    protocol: protocol.to_string(),
+                    summary: summary.to_string(),
+                });
+            }
+        }
+    }
+    // Timestamps sort chronologically as text
+    messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    let skip = messages.len().saturating_sub(limit);
+    messages.split_off(skip)
+}
+
+/// Prints one chunk of forwarded data, `direction` being e.g. "→ REQUEST"
+pub fn log_message(direction: &str, protocol: &Protocol, data: &[u8]) {
+    let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string();
+    remember(direction, protocol, data, &timestamp);
+
+    match protocol {
+        Protocol::Http => log_http_message(direction, data, &timestamp),
+        Protocol::Postgres => log_postgres_message(direction, data, &timestamp),
+        Protocol::Mysql => log_mysql_message(direction, data, &timestamp),
+        Protocol::Tcp => log_tcp_message(direction, data, &timestamp),
+    }
+}
+
+fn log_http_message(direction: &str, data: &[u8], timestamp: &str) {
+    if let Ok(text) = std::str::from_utf8(data) {
+        // Try to parse as HTTP
+        if text.starts_with("GET ")
+            || text.starts_with("POST ")
+            || text.starts_with("PUT ")
+            || text.starts_with("DELETE ")
+            || text.starts_with("HTTP/")
+        {
+            println!("🌐 [{}] {} HTTP Message:", timestamp, direction);
+
+            // Split headers and body
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let headers = &text[..header_end];
+                let body = &text[header_end + 4..];
+
+                println!("   Headers:");
+                for line in headers.lines() {
+                    println!("     {}", line);
+                }
+
+                if !body.is_empty() {
+                    println!("   Body:");
+                    println!("     {}", body);
+                }
+            } else {
+                println!("   {}", text);
+            }
+        } else {
+            log_tcp_message(direction, data, timestamp);
+        }
+    } else {
+        log_tcp_message(direction, data, timestamp);
+    }
+}
+
+fn log_postgres_message(direction: &str, data: &[u8], timestamp: &str) {
+    if data.is_empty() {
+        return;
+    }
+
+    println!("🐘 [{}] {} PostgreSQL Message:", timestamp, direction);
+
+    // Basic PostgreSQL protocol parsing
+    if data.len() >= 5 {
+        let msg_type = data[0] as char;
+        let length = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+
+        match msg_type {
+            'Q' => {
+                if let Ok(query) = std::str::from_utf8(&data[5..]) {
+                    println!("   Query: {}", query.trim_end_matches('\0'));
+                }
+            }
+            'P' => println!("   Parse message (length: {})", length),
+            'B' => println!("   Bind message (length: {})", length),
+            'E' => println!("   Execute message (length: {})", length),
+            'S' => println!("   Sync message"),
+            'X' => println!("   Terminate message"),
+            'T' => println!("   Row Description (length: {})", length),
+            'D' => println!("   Data Row (length: {})", length),
+            'C' => {
+                if let Ok(command) = std::str::from_utf8(&data[5..]) {
+                    println!("   Command Complete: {}", command.trim_end_matches('\0'));
+                }
+            }
+            'Z' => println!("   Ready for Query"),
+            'R' => println!("   Authentication Response (length: {})", length),
+            _ => {
+                println!(
+                    "   Unknown message type '{}' (length: {})",
+                    msg_type, length
+                );
+                println!(
+                    "   Raw data: {}",
+                    hex::encode(&data[..std::cmp::min(50, data.len())])
+                );
+            }
+        }
+    } else {
+        log_tcp_message(direction, data, timestamp);
+    }
+}
+
+fn log_mysql_message(direction: &str, data: &[u8], timestamp: &str) {
+    // Every packet starts with a 3-byte little-endian length and a sequence number
+    if data.len() < 5 {
+        log_tcp_message(direction, data, timestamp);
+        return;
+    }
+
+    println!("🐬 [{}] {} MySQL Message:", timestamp, direction);
+
+    let length = u32::from_le_bytes([data[0], data[1], data[2], 0]) as usize;
+    let sequence = data[3];
+    let payload = &data[4..std::cmp::min(4 + length, data.len())];
+    let text = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .trim_end_matches('\0')
+            .to_string()
+    };
+    let from_client = direction.contains('→');
+
+    if from_client {
+        match payload[0] {
+            _ if sequence != 0 => println!("   Handshake Response (length: {})", length),
+            0x01 => println!("   Quit"),
+            0x02 => println!("   Use Database: {}", text(&payload[1..])),
+            0x03 => println!("   Query: {}", text(&payload[1..])),
+            0x0e => println!("   Ping"),
+            0x16 => println!("   Prepare: {}", text(&payload[1..])),
+            0x17 => println!("   Execute Statement (length: {})", length),
+            0x19 => println!("   Close Statement"),
+            command => println!("   Command 0x{:02x} (length: {})", command, length),
+        }
+    } else {
+        match payload[0] {
+            0x0a if sequence == 0 => {
+                let version = payload[1..].split(|&b| b == 0).next().unwrap_or_default();
+                println!("   Server Handshake: version {}", text(version));
+            }
+            0x00 if length >= 7 => println!("   OK"),
+            0xfe if length < 9 => println!("   EOF"),
+            0xff if payload.len() >= 3 => {
+                let code = u16::from_le_bytes([payload[1], payload[2]]);
+                // The SQL state ("#" and 5 characters) comes before the message
+                let message = match payload.get(3) {
+                    Some(b'#') if payload.len() >= 9 => &payload[9..],
+                    _ => &payload[3..],
+                };
+                println!("   Error {}: {}", code, text(message));
+            }
+            _ => println!("   Result Data (length: {})", data.len()),
+        }
+    }
+}
+
+fn log_tcp_message(direction: &str, data: &[u8], timestamp: &str) {
+    println!(
+        "🔌 [{}] {} TCP Message ({} bytes):",
+        timestamp,
+        direction,
+        data.len()
+    );
+
+    // Show first 100 bytes as hex and try to show as text if printable
+    let preview_len = std::cmp::min(100, data.len());
+    let preview = &data[..preview_len];
+
+    println!("   Hex: {}", hex::encode(preview));
+
+    if let Ok(text) = std::str::from_utf8(preview) {
+        if text
+            .chars()
+            .all(|c| c.is_ascii() && (c.is_ascii_graphic() || c.is_ascii_whitespace()))
+        {
+            println!(
+                "   Text: {}",
+                text.replace('\n', "\\n").replace('\r', "\\r")
+            );
+        }
+    }
+
+    if data.len() > preview_len {
+        println!("   ... ({} more bytes)", data.len() - preview_len);
+    }
+}
+
+async fn pipe<R, W>(
+    mut from: R,
+    mut to: W,
+    direction: &str,
+    metric: &str,
+    protocol: Option<&Protocol>,
+    session: Option<&usage::Session>,
+) -> u64
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0u8; 8192];
+    let mut total = 0;
+    loop {
+        match from.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                total += n as u64;
+                let data = &buffer[..n];
+                metrics::counter_add(
+                    "proxy_relay_bytes_total",
+                    "Bytes relayed between clients and upstreams",
+                    &[("direction", metric)],
+                    n as u64,
+                );
+                match session {
+                    Some(session) if metric == "request" => session.add_bytes(n as u64, 0),
+                    Some(session) => session.add_bytes(0, n as u64),
+                    None => {}
+                }
+                if let Some(protocol) = protocol {
+                    log_message(direction, protocol, data);
+                }
+                if to.write_all(data).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    let _ = to.shutdown().await;
+    total
+}
+
+/// Copies both directions between a client and its upstream until each side has
+/// closed, logging every chunk when a protocol is given
+pub async fn relay<C, R, W>(client: C, reader: R, writer: W, protocol: Option<&Protocol>)
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    relay_tagged("", client, reader, writer, protocol).await
+}
+
+/// `relay` accounting the connection's usage to `target`, e.g. the forward's name
+pub async fn relay_to<C, R, W>(
+    target: &str,
+    client: C,
+    reader: R,
+    writer: W,
+    protocol: Option<&Protocol>,
+) where
+    C: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    relay_accounted("", target, client, reader, writer, protocol).await
+}
+
+/// `relay` with `tag` (e.g. a service name) in front of every logged direction, for
+/// plugins that interleave the traffic of several forwards. The usage is accounted to it.
+pub async fn relay_tagged<C, R, W>(
+    tag: &str,
+    client: C,
+    reader: R,
+    writer: W,
+    protocol: Option<&Protocol>,
+) where
+    C: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    relay_accounted(tag, tag, client, reader, writer, protocol).await
+}
+
+async fn relay_accounted<C, R, W>(
+    tag: &str,
+    target: &str,
+    client: C,
+    reader: R,
+    writer: W,
+    protocol: Option<&Protocol>,
+) where
+    C: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let (request, response) = if tag.is_empty() {
+        ("→ REQUEST".to_string(), "← RESPONSE".to_string())
+    } else {
+        (
+            format!("[{}] → REQUEST", tag),
+            format!("[{}] ← RESPONSE", tag),
+        )
+    };
+    metrics::counter_add(
+        "proxy_relay_connections_total",
+        "Client connections relayed to an upstream",
+        &[],
+        1,
+    );
+    metrics::gauge_add(ACTIVE_CONNECTIONS, ACTIVE_CONNECTIONS_HELP, &[], 1.0);
+    let session = (!target.is_empty()).then(|| usage::session(target, None));
+    let span = Span::start("relay");
+    if !tag.is_empty() {
+        span.set_attribute("proxy.tag", tag);
+    }
+    let (client_read, client_write) = tokio::io::split(client);
+    let (sent, received) = tokio::join!(
+        pipe(
+            client_read,
+            writer,
+            &request,
+            "request",
+            protocol,
+            session.as_ref()
+        ),
+        pipe(
+            reader,
+            client_write,
+            &response,
+            "response",
+            protocol,
+            session.as_ref()
+        ),
+    );
+    span.set_int("proxy.request_bytes", sent as i64);
+    span.set_int("proxy.response_bytes", received as i64);
+    metrics::gauge_add(ACTIVE_CONNECTIONS, ACTIVE_CONNECTIONS_HELP, &[], -1.0);
+}
@@ -0,0 +1,222 @@
+// Usage accounting across sessions. A plugin process counts, per target (a service, host
+// or cluster resource it tunnels to), the sessions it opened, the bytes moved each way and
+// how long the target was in use. Once `init` names the plugin, the counts are written to
+// `usage/<plugin>-<pid>-<start>.json` in the state directory every few seconds and when
+// the process exits. Unlike metrics the files are kept: `proxy usage` sums them up.
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::periodic::Periodic;
+
+/// How often a plugin process rewrites its usage file
+const WRITE_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Entry {
+    first_used: DateTime<Utc>,
+    last_used: DateTime<Utc>,
+    sessions: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    /// Sessions currently open
+    open: u32,
+    /// Since when the target has had an open session
+    open_since: Option<DateTime<Utc>>,
+    /// Time the target had an open session, the current period excluded
+    active_secs: f64,
+}
+
+struct Usage {
+    plugin: String,
+    started: DateTime<Utc>,
+    /// Entries by target and kubeconfig context (empty outside clusters)
+    entries: BTreeMap<(String, String), Entry>,
+}
+
+static USAGE: Mutex<Option<Usage>> = Mutex::new(None);
+/// This process' usage file, once writing started
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+static WRITER: Periodic = Periodic::new();
+
+/// The directory every plugin process writes its usage file in
+pub fn usage_dir() -> Option<PathBuf> {
+    crate::plugin_state_dir("usage")
+}
+
+fn current(usage: &mut Option<Usage>) -> &mut Usage {
+    usage.get_or_insert_with(|| Usage {
+        plugin: String::new(),
+        started: Utc::now(),
+        entries: BTreeMap::new(),
+    })
+}
+
+fn update(target: &str, context: &str, change: impl FnOnce(&mut Entry, DateTime<Utc>)) {
+    let mut usage = USAGE.lock().unwrap_or_else(|e| e.into_inner());
+    let usage = current(&mut usage);
+    let now = Utc::now();
+    let entry = usage
+        .entries
+        .entry((target.to_string(), context.to_string()))
+        .or_insert_with(|| Entry {
+            first_used: now,
+            last_used: now,
+            sessions: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            open: 0,
+            open_since: None,
+            active_secs: 0.0,
+        });
+    entry.last_used = now;
+    change(entry, now);
+}
+
+/// An open session with a target, in use until dropped
+pub struct Session {
+    target: String,
+    context: String,
+}
+
+impl Session {
+    /// Adds bytes moved within the session, sent to and received from the target
+    pub fn add_bytes(&self, sent: u64, received: u64) {
+        update(&self.target, &self.context, |entry, _| {
+            entry.bytes_sent += sent;
+            entry.bytes_received += received;
+        });
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        update(&self.target, &self.context, |entry, now| {
+            entry.open = entry.open.saturating_sub(1);
+            if entry.open == 0 {
+                if let Some(since) = entry.open_since.take() {
+                    entry.active_secs += seconds_between(since, now);
+                }
+            }
+        });
+    }
+}
+
+fn seconds_between(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_milliseconds().max(0) as f64 / 1000.0
+}
+
+/// Opens a session with `target`, e.g. one relayed connection or one running forward;
+/// `context` is the kubeconfig context for targets in a cluster
+pub fn session(target: &str, context: Option<&str>) -> Session {
+    let context = context.unwrap_or_default();
+    update(target, context, |entry, now| {
+        entry.sessions += 1;
+        entry.open += 1;
+        entry.open_since.get_or_insert(now);
+    });
+    Session {
+        target: target.to_string(),
+        context: context.to_string(),
+    }
+}
+
+/// `session` in the kubeconfig's current context
+pub fn session_k8s(target: &str) -> Session {
+    let context = crate::audit::kube_context();
+    session(target, context.as_deref())
+}
+
+fn time(time: DateTime<Utc>) -> String {
+    json!(time.to_rfc3339_opts(SecondsFormat::Secs, true)).to_string()
+}
+
+fn render() -> Option<String> {
+    let usage = USAGE.lock().unwrap_or_else(|e| e.into_inner());
+    let usage = usage.as_ref().filter(|usage| !usage.entries.is_empty())?;
+    let now = Utc::now();
+    let targets: Vec<String> = usage
+        .entries
+        .iter()
+        .map(|((target, context), entry)| {
+            // Open sessions count up to now
+            let (last_used, active_secs) = match entry.open_since {
+                Some(since) => (now, entry.active_secs + seconds_between(since, now)),
+                None => (entry.last_used, entry.active_secs),
+            };
+            format!(
+                concat!(
+                    "{{\"target\":{},\"context\":{},\"first_used\":{},\"last_used\":{},",
+                    "\"sessions\":{},\"active_secs\":{:.1},\"bytes_sent\":{},",
+                    "\"bytes_received\":{}}}"
+                ),
+                json!(target),
+                json!(context),
+                time(entry.first_used),
+                time(last_used),
+                entry.sessions,
+                active_secs,
+                entry.bytes_sent,
+                entry.bytes_received
+            )
+        })
+        .collect();
+    Some(format!(
+        "{{\"plugin\":{},\"pid\":{},\"started\":{},\"updated\":{},\"targets\":[{}]}}\n",
+        json!(usage.plugin),
+        std::process::id(),
+        time(usage.started),
+        time(now),
+        targets.join(",")
+    ))
+}
+
+fn write(path: &Path) {
+    let Some(text) = render() else {
+        return;
+    };
+    // Written aside and renamed, so a reader never sees half a file
+    let partial = path.with_extension("tmp");
+    if fs::write(&partial, text).is_ok() {
+        let _ = fs::rename(&partial, path);
+    }
+}
+
+/// Starts writing this process' usage under the plugin's name. Nothing is written until
+/// a session was opened.
+pub fn init(plugin_name: &str) {
+    let Some(dir) = usage_dir() else {
+        return;
+    };
+    if fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+    let started = {
+        let mut usage = USAGE.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = current(&mut usage);
+        usage.plugin = plugin_name.to_string();
+        usage.started
+    };
+    let path = dir.join(format!(
+        "{}-{}-{}.json",
+        plugin_name,
+        std::process::id(),
+        started.timestamp()
+    ));
+    if PATH.set(path.clone()).is_ok() {
+        WRITER.start(WRITE_INTERVAL, move || write(&path));
+        unsafe {
+            libc::atexit(finish);
+        }
+    }
+}
+
+extern "C" fn finish() {
+    WRITER.stop();
+    if let Some(path) = PATH.get() {
+        write(path);
+    }
+}
@@ -0,0 +1,108 @@
+// AWS CLI calls. Credentials come from the standard AWS chain the CLI implements
+// (environment, profiles, SSO, instance roles); the forwarding itself is done by the
+// session-manager-plugin that `aws ssm start-session` hands the session to.
+use anyhow::{anyhow, Context, Result};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Profile and region for the calls; unset means the CLI's own defaults
+#[derive(Debug, Clone, Default)]
+pub struct Account {
+    pub profile: Option<String>,
+    pub region: Option<String>,
+}
+
+fn aws(account: &Account) -> Command {
+    let mut command = Command::new("aws");
+    if let Some(profile) = &account.profile {
+        command.arg("--profile").arg(profile);
+    }
+    if let Some(region) = &account.region {
+        command.arg("--region").arg(region);
+    }
+    command
+}
+
+/// Turns "Name=bastion,env=prod" into describe-instances filters
+fn tag_filters(tags: &str) -> Result<Vec<String>> {
+    tags.split(',')
+        .map(|pair| {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid tag '{}', expected Key=Value", pair))?;
+            Ok(format!("Name=tag:{},Values={}", key.trim(), value.trim()))
+        })
+        .collect()
+}
+
+/// Running instances with all the given tags, oldest launch first
+pub async fn find_instances(account: &Account, tags: &str) -> Result<Vec<String>> {
+    let output = aws(account)
+        .arg("ec2")
+        .arg("describe-instances")
+        .arg("--filters")
+        .args(tag_filters(tags)?)
+        .arg("Name=instance-state-name,Values=running")
+        .arg("--query")
+        .arg("sort_by(Reservations[].Instances[], &LaunchTime)[].InstanceId")
+        .arg("--output")
+        .arg("json")
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("Failed to run the aws CLI")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "aws ec2 describe-instances failed ({})",
+            output.status
+        ));
+    }
+    serde_json::from_slice(&output.stdout).context("Unexpected describe-instances output")
+}
+
+/// Returns false when session-manager-plugin, which does the actual forwarding, is missing
+pub async fn has_session_manager_plugin() -> bool {
+    Command::new("session-manager-plugin")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
+
+/// `aws ssm start-session` forwarding local_port to remote_port on the instance, or to
+/// remote_host:remote_port as seen from the instance
+pub fn start_session(
+    account: &Account,
+    instance: &str,
+    remote_host: Option<&str>,
+    local_port: u16,
+    remote_port: u16,
+) -> Command {
+    let (document, parameters) = match remote_host {
+        Some(host) => (
+            "AWS-StartPortForwardingSessionToRemoteHost",
+            format!(
+                "host={},portNumber={},localPortNumber={}",
+                host, remote_port, local_port
+            ),
+        ),
+        None => (
+            "AWS-StartPortForwardingSession",
+            format!("portNumber={},localPortNumber={}", remote_port, local_port),
+        ),
+    };
+    let mut command = aws(account);
+    command
+        .arg("ssm")
+        .arg("start-session")
+        .arg("--target")
+        .arg(instance)
+        .arg("--document-name")
+        .arg(document)
+        .arg("--parameters")
+        .arg(parameters)
+        .kill_on_drop(true);
+    command
+}
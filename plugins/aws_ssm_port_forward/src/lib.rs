@@ -0,0 +1,357 @@
+// Port forwards through AWS Systems Manager Session Manager, configured like the
+// k8s_port_forward forwards: each [[forward]] names an instance (by id or tags) and
+// the ports, optionally a host reached through the instance such as an RDS endpoint.
+// No bastion or open inbound port is needed, only SSM access to the instance. A session
+// only needs credentials to start, so when they expire the running sessions carry on and
+// the next one waits for them to be renewed.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::credentials::{self, Credentials};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::watch;
+
+mod aws;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A session that lasted this long starts the backoff over
+const STABLE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SsmConfig {
+    /// Default AWS profile for all forwards
+    pub profile: Option<String>,
+    /// Default AWS region for all forwards
+    pub region: Option<String>,
+    /// Renews expired credentials, with AWS_PROFILE set to the profile; without it
+    /// `aws sso login` runs when there is a terminal
+    pub refresh_command: Option<String>,
+    #[serde(default)]
+    pub forward: Vec<SsmForward>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SsmForward {
+    pub name: String,
+    pub instance_id: Option<String>,
+    /// Tag selector, e.g. "Name=bastion,env=prod"; the oldest running match is used
+    pub tags: Option<String>,
+    /// Host to reach through the instance (RDS, ElastiCache, internal ALBs, ...)
+    pub remote_host: Option<String>,
+    pub local_port: u16,
+    pub remote_port: u16,
+    pub profile: Option<String>,
+    pub region: Option<String>,
+    /// Start a new session when one ends, e.g. after the idle timeout (default true)
+    pub restart: Option<bool>,
+}
+
+impl SsmForward {
+    fn target_desc(&self) -> String {
+        let instance = match (&self.instance_id, &self.tags) {
+            (Some(id), _) => id.clone(),
+            (None, Some(tags)) => format!("tags:{}", tags),
+            (None, None) => "invalid-config".to_string(),
+        };
+        match &self.remote_host {
+            Some(host) => format!("{}:{} via {}", host, self.remote_port, instance),
+            None => format!("{}:{}", instance, self.remote_port),
+        }
+    }
+}
+
+pub struct AwsSsmPortForwardPlugin;
+
+impl AwsSsmPortForwardPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# AWS SSM Port Forward Configuration
+# Credentials come from the standard AWS chain (env, profiles, SSO, roles)
+# profile = "prod"
+# region = "eu-west-1"
+# refresh_command = "aws sso login --no-browser"  # when the credentials expire
+
+[[forward]]
+name = "bastion-ssh"
+instance_id = "i-0123456789abcdef0"
+local_port = 2222
+remote_port = 22
+
+# Reach a database through any running instance tagged Name=bastion, env=prod
+[[forward]]
+name = "orders-db"
+tags = "Name=bastion,env=prod"
+remote_host = "orders.cluster-abc123.eu-west-1.rds.amazonaws.com"
+local_port = 5432
+remote_port = 5432
+# profile = "prod-readonly"  # per-forward override
+# restart = false            # don't reconnect after the session ends
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<SsmConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: SsmConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!(
+                    "📝 Sample config:\n{}",
+                    AwsSsmPortForwardPlugin::sample_config()
+                );
+                Ok(SsmConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(SsmConfig::default())
+        }
+    }
+}
+
+/// The instance to connect to; tags are looked up again on every (re)start since
+/// instances behind an auto scaling group come and go
+async fn resolve_instance(account: &aws::Account, fwd: &SsmForward) -> Result<String> {
+    if let Some(id) = &fwd.instance_id {
+        return Ok(id.clone());
+    }
+    let tags = fwd
+        .tags
+        .as_deref()
+        .ok_or_else(|| anyhow!("Must specify either instance_id or tags"))?;
+    let instances = aws::find_instances(account, tags).await?;
+    match instances.as_slice() {
+        [] => Err(anyhow!("No running instance has tags {}", tags)),
+        [only] => Ok(only.clone()),
+        [first, ..] => {
+            println!(
+                "Found {} instances tagged {}: {}",
+                instances.len(),
+                tags,
+                instances.join(", ")
+            );
+            println!("Using the first one: {}", first);
+            Ok(first.clone())
+        }
+    }
+}
+
+/// Checks the credentials after a failure. When they were renewed since the attempt, or
+/// stopped working and then got renewed, returns true to try again right away.
+async fn wait_for_credentials(
+    name: &str,
+    watched: &Arc<Credentials>,
+    renewed: &mut watch::Receiver<u64>,
+) -> bool {
+    let checking = watched.clone();
+    let _ = tokio::task::spawn_blocking(move || checking.check()).await;
+    if renewed.has_changed().unwrap_or(false) {
+        return true;
+    }
+    if watched.is_valid() {
+        return false;
+    }
+    println!(
+        "🔑 [{}] Waiting for {} to be renewed",
+        name,
+        watched.source()
+    );
+    renewed.changed().await.is_ok()
+}
+
+/// Runs one forward, starting a new session whenever the previous one ends
+async fn run_forward(
+    fwd: SsmForward,
+    account: aws::Account,
+    watched: Arc<Credentials>,
+) -> Result<()> {
+    let mut renewed = watched.subscribe();
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        renewed.mark_unchanged();
+        let instance = match resolve_instance(&account, &fwd).await {
+            Ok(instance) => instance,
+            Err(_) if wait_for_credentials(&fwd.name, &watched, &mut renewed).await => continue,
+            Err(e) => return Err(e),
+        };
+        println!(
+            "🚀 [{}] localhost:{} → {}",
+            fwd.name,
+            fwd.local_port,
+            match &fwd.remote_host {
+                Some(host) => format!("{}:{} via {}", host, fwd.remote_port, instance),
+                None => format!("{}:{}", instance, fwd.remote_port),
+            }
+        );
+        let started = Instant::now();
+        let status = aws::start_session(
+            &account,
+            &instance,
+            fwd.remote_host.as_deref(),
+            fwd.local_port,
+            fwd.remote_port,
+        )
+        .status()
+        .await
+        .map_err(|e| anyhow!("Failed to run the aws CLI: {}", e))?;
+        println!("⚠️  [{}] Session ended ({})", fwd.name, status);
+
+        if !fwd.restart.unwrap_or(true) {
+            return Ok(());
+        }
+        if !status.success() && wait_for_credentials(&fwd.name, &watched, &mut renewed).await {
+            backoff = MIN_BACKOFF;
+            continue;
+        }
+        if started.elapsed() >= STABLE {
+            backoff = MIN_BACKOFF;
+        }
+        println!("🔄 [{}] Reconnecting in {}s", fwd.name, backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+impl Plugin for AwsSsmPortForwardPlugin {
+    fn name(&self) -> &'static str {
+        "aws_ssm_port_forward"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Port forwarding to EC2 instances through AWS SSM Session Manager"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Port-forward through AWS SSM as defined in config file")
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .value_name("NAME")
+                    .help("Only start the forward with this name"),
+            )
+            .arg(
+                Arg::new("profile")
+                    .long("profile")
+                    .value_name("PROFILE")
+                    .help("Override the AWS profile for all forwards"),
+            )
+            .arg(
+                Arg::new("region")
+                    .long("region")
+                    .value_name("REGION")
+                    .help("Override the AWS region for all forwards"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(AwsSsmPortForwardPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<SsmConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let forwards: Vec<SsmForward> = match matches.get_one::<String>("name") {
+                Some(name) => config
+                    .forward
+                    .into_iter()
+                    .filter(|fwd| &fwd.name == name)
+                    .collect(),
+                None => config.forward,
+            };
+            if forwards.is_empty() {
+                match matches.get_one::<String>("name") {
+                    Some(name) => eprintln!("❌ No forward found with name: {}", name),
+                    None => eprintln!("❌ No forwards found in config file"),
+                }
+                std::process::exit(1);
+            }
+
+            if !aws::has_session_manager_plugin().await {
+                eprintln!("❌ session-manager-plugin was not found on PATH");
+                eprintln!("💡 Install it: https://docs.aws.amazon.com/systems-manager/latest/userguide/session-manager-working-with-install-plugin.html");
+                std::process::exit(1);
+            }
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("Starting {} SSM port-forward(s):", forwards.len());
+            for fwd in &forwards {
+                println!("  {} -> localhost:{}", fwd.target_desc(), fwd.local_port);
+            }
+
+            let handles: Vec<_> = forwards
+                .into_iter()
+                .map(|fwd| {
+                    let account = aws::Account {
+                        profile: matches
+                            .get_one::<String>("profile")
+                            .or(fwd.profile.as_ref())
+                            .or(config.profile.as_ref())
+                            .cloned(),
+                        region: matches
+                            .get_one::<String>("region")
+                            .or(fwd.region.as_ref())
+                            .or(config.region.as_ref())
+                            .cloned(),
+                    };
+                    let watched = credentials::watch(
+                        credentials::Source::Aws {
+                            profile: account.profile.clone(),
+                        },
+                        config.refresh_command.clone(),
+                    );
+                    tokio::spawn(async move {
+                        let name = fwd.name.clone();
+                        if let Err(e) = run_forward(fwd, account, watched).await {
+                            eprintln!("❌ [{}] {}", name, e);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(AwsSsmPortForwardPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
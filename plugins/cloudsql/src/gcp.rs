@@ -0,0 +1,42 @@
+// Cloud SQL through the Cloud SQL Auth Proxy (v2). It handles the IAM-authorized
+// connection and its certificates; it listens on a private loopback port, where the
+// plugin connects, so the local side stays plaintext and can be decoded.
+use crate::Instance;
+use std::process::Stdio;
+use tokio::process::Command;
+
+pub const BINARY: &str = "cloud-sql-proxy";
+
+/// Returns false when the Cloud SQL Auth Proxy isn't installed
+pub async fn is_installed() -> bool {
+    Command::new(BINARY)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
+
+/// The proxy for `instance`, listening on 127.0.0.1:`listen_port`
+pub fn start_proxy(instance: &Instance, connection_name: &str, listen_port: u16) -> Command {
+    let mut command = Command::new(BINARY);
+    command
+        .arg(connection_name)
+        .arg("--address=127.0.0.1")
+        .arg(format!("--port={}", listen_port));
+    if instance.iam_auth {
+        command.arg("--auto-iam-authn");
+    }
+    if instance.private_ip {
+        command.arg("--private-ip");
+    }
+    if let Some(credentials) = &instance.credentials_file {
+        command.arg(format!("--credentials-file={}", credentials));
+    }
+    if let Some(account) = &instance.impersonate {
+        command.arg(format!("--impersonate-service-account={}", account));
+    }
+    command.stdin(Stdio::null()).kill_on_drop(true);
+    command
+}
@@ -0,0 +1,402 @@
+// Managed databases with the same workflow as in-cluster ones: each configured Cloud SQL
+// or RDS instance gets a local port whose traffic is logged with the PostgreSQL or MySQL
+// decoder. Cloud SQL goes through the Cloud SQL Auth Proxy, which is started on a
+// private port and kept running; RDS is reached directly and logs in with IAM auth
+// tokens from `--token`.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::net;
+use plugin_api::traffic::{relay_to, Protocol};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+
+mod gcp;
+mod rds;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A proxy that lasted this long starts the backoff over
+const STABLE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CloudSqlConfig {
+    #[serde(default)]
+    pub instance: Vec<Instance>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    CloudSql,
+    Rds,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Instance {
+    pub name: String,
+    /// cloudsql (default) or rds
+    #[serde(default)]
+    pub kind: Kind,
+    /// Cloud SQL: "project:region:instance"
+    pub connection_name: Option<String>,
+    /// Cloud SQL: log in as the IAM principal instead of a database password
+    #[serde(default)]
+    pub iam_auth: bool,
+    /// Cloud SQL: connect to the private IP, e.g. from inside the VPC
+    #[serde(default)]
+    pub private_ip: bool,
+    /// Cloud SQL: service account key; Application Default Credentials otherwise
+    pub credentials_file: Option<String>,
+    /// Cloud SQL: service account to impersonate
+    pub impersonate: Option<String>,
+    /// RDS: instance endpoint
+    pub host: Option<String>,
+    /// RDS: instance port (default 5432, 3306 for mysql)
+    pub port: Option<u16>,
+    /// RDS: database user that IAM auth tokens are made for
+    pub user: Option<String>,
+    pub region: Option<String>,
+    /// AWS profile for the tokens
+    pub profile: Option<String>,
+    pub local_port: u16,
+    /// Address to listen on: 127.0.0.1 (default), ::1, or :: for IPv4 and IPv6
+    pub bind: Option<String>,
+    /// Message decoding for the traffic log: tcp (default), postgres, mysql
+    pub protocol: Option<String>,
+    /// Print the forwarded traffic (default true)
+    pub log_traffic: Option<bool>,
+}
+
+impl Instance {
+    fn validate(&self) -> Result<()> {
+        match self.kind {
+            Kind::CloudSql if self.connection_name.is_none() => Err(anyhow!(
+                "Instance '{}' needs a connection_name (project:region:instance)",
+                self.name
+            )),
+            Kind::Rds if self.host.is_none() => {
+                Err(anyhow!("Instance '{}' needs the RDS host", self.name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn rds_port(&self) -> u16 {
+        self.port.unwrap_or(match self.protocol.as_deref() {
+            Some("mysql") => 3306,
+            _ => 5432,
+        })
+    }
+
+    fn target_desc(&self) -> String {
+        match self.kind {
+            Kind::CloudSql => format!(
+                "{} (Cloud SQL{})",
+                self.connection_name.as_deref().unwrap_or("?"),
+                if self.iam_auth { ", IAM auth" } else { "" }
+            ),
+            Kind::Rds => format!(
+                "{} (RDS{})",
+                net::host_port(self.host.as_deref().unwrap_or("?"), self.rds_port()),
+                if self.user.is_some() {
+                    ", IAM auth"
+                } else {
+                    ""
+                }
+            ),
+        }
+    }
+}
+
+pub struct CloudSqlPlugin;
+
+impl CloudSqlPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Cloud SQL / RDS Configuration
+[[instance]]
+name = "orders-db"
+connection_name = "my-project:europe-west1:orders"
+iam_auth = true  # log in as your Google identity
+# private_ip = true
+local_port = 5432
+# bind = "::1"  # default 127.0.0.1; "::" listens on every interface, IPv4 and IPv6
+protocol = "postgres"  # Options: tcp, postgres, mysql
+
+[[instance]]
+name = "billing-db"
+kind = "rds"
+host = "billing.abc123xyz.eu-west-1.rds.amazonaws.com"
+port = 3306
+user = "app_iam"  # IAM-enabled database user; get a password with --token
+region = "eu-west-1"
+# profile = "prod"
+local_port = 13306
+protocol = "mysql"
+# log_traffic = false
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<CloudSqlConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: CloudSqlConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", CloudSqlPlugin::sample_config());
+                Ok(CloudSqlConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(CloudSqlConfig::default())
+        }
+    }
+}
+
+/// A free loopback port for the Cloud SQL Auth Proxy to listen on
+fn private_port() -> Result<u16> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// Keeps the Cloud SQL Auth Proxy running on `proxy_port`
+async fn supervise(instance: Instance, connection_name: String, proxy_port: u16) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        let status = gcp::start_proxy(&instance, &connection_name, proxy_port)
+            .status()
+            .await;
+        match status {
+            Ok(status) => println!("⚠️  [{}] Cloud SQL proxy ended ({})", instance.name, status),
+            Err(e) => eprintln!(
+                "❌ [{}] Failed to run {}: {}",
+                instance.name,
+                gcp::BINARY,
+                e
+            ),
+        }
+        if started.elapsed() >= STABLE {
+            backoff = MIN_BACKOFF;
+        }
+        println!(
+            "🔄 [{}] Reconnecting in {}s",
+            instance.name,
+            backoff.as_secs()
+        );
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn start_instance(instance: Instance, protocol_override: Option<&str>) -> Result<()> {
+    instance.validate()?;
+    let bind = instance.bind.as_deref().unwrap_or(net::LOOPBACK);
+    let listener = net::listen(bind, instance.local_port).await?;
+    let protocol = instance.log_traffic.unwrap_or(true).then(|| {
+        Protocol::from(
+            protocol_override
+                .or(instance.protocol.as_deref())
+                .unwrap_or("tcp"),
+        )
+    });
+    let protocol = Arc::new(protocol);
+    println!(
+        "🎧 [{}] {} → {}",
+        instance.name,
+        net::host_port(bind, instance.local_port),
+        instance.target_desc()
+    );
+
+    let upstream = match (instance.kind, &instance.connection_name, &instance.host) {
+        (Kind::CloudSql, Some(connection_name), _) => {
+            let proxy_port = private_port()?;
+            tokio::spawn(supervise(
+                instance.clone(),
+                connection_name.clone(),
+                proxy_port,
+            ));
+            ("127.0.0.1".to_string(), proxy_port)
+        }
+        (_, _, Some(host)) => {
+            if let Some(user) = &instance.user {
+                println!(
+                    "🔑 [{}] Log in as {} with TLS; the password is `proxy cloudsql --token {}`",
+                    instance.name, user, instance.name
+                );
+            }
+            (net::unbracket(host).to_string(), instance.rds_port())
+        }
+        _ => unreachable!("validated above"),
+    };
+
+    let name = instance.name.clone();
+    let upstream = Arc::new(upstream);
+    loop {
+        let (client, addr) = listener.accept().await?;
+        println!("📞 [{}] New connection from {}", name, addr);
+        let name = name.clone();
+        let protocol = protocol.clone();
+        let upstream = upstream.clone();
+        tokio::spawn(async move {
+            match TcpStream::connect((upstream.0.as_str(), upstream.1)).await {
+                Ok(upstream) => {
+                    let (reader, writer) = upstream.into_split();
+                    relay_to(&name, client, reader, writer, protocol.as_ref().as_ref()).await;
+                    println!("🔌 [{}] Connection from {} closed", name, addr);
+                }
+                Err(e) => eprintln!("❌ [{}] Database is not reachable yet: {}", name, e),
+            }
+        });
+    }
+}
+
+impl Plugin for CloudSqlPlugin {
+    fn name(&self) -> &'static str {
+        "cloudsql"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Cloud SQL and RDS tunnels with IAM auth and database traffic logging"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Authenticated local ports to Cloud SQL and RDS instances in config file")
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .short('n')
+                    .value_name("NAME")
+                    .help("Only start the instance with this name"),
+            )
+            .arg(
+                Arg::new("token")
+                    .long("token")
+                    .value_name("NAME")
+                    .conflicts_with("name")
+                    .help("Print an IAM auth token (the password) for an RDS instance"),
+            )
+            .arg(
+                Arg::new("protocol")
+                    .long("protocol")
+                    .value_name("PROTOCOL")
+                    .help("Protocol for message decoding: tcp, postgres, mysql")
+                    .value_parser(["tcp", "postgres", "mysql"]),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(CloudSqlPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<CloudSqlConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        plugin_api::metrics::init(self.name());
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Some(name) = matches.get_one::<String>("token") {
+                let token = match config.instance.iter().find(|i| &i.name == name) {
+                    Some(instance) if instance.kind == Kind::Rds => {
+                        let host = instance.host.as_deref().unwrap_or_default();
+                        rds::auth_token(instance, host, instance.rds_port()).await
+                    }
+                    Some(_) => Err(anyhow!(
+                        "'{}' is a Cloud SQL instance; with iam_auth it needs no password",
+                        name
+                    )),
+                    None => Err(anyhow!("No instance named '{}'", name)),
+                };
+                match token {
+                    // Alone on the last line, for `| tail -n1`
+                    Ok(token) => println!("{}", token),
+                    Err(e) => {
+                        eprintln!("❌ {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+
+            let instances: Vec<Instance> = match matches.get_one::<String>("name") {
+                Some(name) => config
+                    .instance
+                    .into_iter()
+                    .filter(|instance| &instance.name == name)
+                    .collect(),
+                None => config.instance,
+            };
+            if instances.is_empty() {
+                eprintln!("❌ No instances to start");
+                eprintln!("💡 Add [[instance]] entries to the config file");
+                std::process::exit(1);
+            }
+            if instances.iter().any(|i| i.kind == Kind::CloudSql) && !gcp::is_installed().await {
+                eprintln!("❌ {} not found on PATH", gcp::BINARY);
+                eprintln!("💡 Install it: https://cloud.google.com/sql/docs/postgres/sql-proxy");
+                std::process::exit(1);
+            }
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🚀 Starting {} database tunnel(s)", instances.len());
+            let protocol = matches.get_one::<String>("protocol").cloned();
+            let handles: Vec<_> = instances
+                .into_iter()
+                .map(|instance| {
+                    let protocol = protocol.clone();
+                    tokio::spawn(async move {
+                        let name = instance.name.clone();
+                        if let Err(e) = start_instance(instance, protocol.as_deref()).await {
+                            eprintln!("❌ [{}] {}", name, e);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(CloudSqlPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
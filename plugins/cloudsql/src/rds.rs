@@ -0,0 +1,41 @@
+// RDS IAM database authentication. The connection itself goes straight to the instance
+// endpoint; what IAM changes is the password, a token from `aws rds
+// generate-db-auth-token` that is valid for 15 minutes and only checked at login.
+use crate::Instance;
+use anyhow::{anyhow, Context, Result};
+use std::process::Stdio;
+use tokio::process::Command;
+
+pub async fn auth_token(instance: &Instance, host: &str, port: u16) -> Result<String> {
+    let user = instance
+        .user
+        .as_deref()
+        .ok_or_else(|| anyhow!("Instance '{}' needs a user for IAM auth", instance.name))?;
+    let mut command = Command::new("aws");
+    if let Some(profile) = &instance.profile {
+        command.arg("--profile").arg(profile);
+    }
+    if let Some(region) = &instance.region {
+        command.arg("--region").arg(region);
+    }
+    let output = command
+        .arg("rds")
+        .arg("generate-db-auth-token")
+        .arg("--hostname")
+        .arg(host)
+        .arg("--port")
+        .arg(port.to_string())
+        .arg("--username")
+        .arg(user)
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("Failed to run the aws CLI")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "aws rds generate-db-auth-token failed ({})",
+            output.status
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
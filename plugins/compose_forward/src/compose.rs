@@ -0,0 +1,132 @@
+// Reading the compose file: the project name and, per service, the container ports
+// listed under `ports` (short and long syntax) and `expose`. Only what's needed to find
+// the containers is read; everything else in the file is ignored.
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file names `docker compose` looks for, in its order
+const DEFAULT_FILES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+#[derive(Debug, Deserialize)]
+struct ComposeFile {
+    name: Option<String>,
+    #[serde(default)]
+    services: BTreeMap<String, ServiceDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ServiceDef {
+    #[serde(default)]
+    ports: Vec<Value>,
+    #[serde(default)]
+    expose: Vec<Value>,
+}
+
+#[derive(Debug)]
+pub struct Stack {
+    pub project: String,
+    /// Container ports by service name
+    pub services: BTreeMap<String, BTreeSet<u16>>,
+}
+
+pub fn find_file(file: Option<&str>) -> Result<PathBuf> {
+    if let Some(file) = file {
+        return Ok(PathBuf::from(file));
+    }
+    DEFAULT_FILES
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+        .ok_or_else(|| {
+            anyhow!(
+                "No compose file in the current directory (looked for {})",
+                DEFAULT_FILES.join(", ")
+            )
+        })
+}
+
+/// Compose's default project name: the directory name, lowercased, with anything but
+/// letters, digits, '-' and '_' dropped
+fn directory_project(path: &Path) -> Option<String> {
+    let dir = fs::canonicalize(path)
+        .ok()?
+        .parent()?
+        .file_name()?
+        .to_owned();
+    let name: String = dir
+        .to_string_lossy()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// "80", "8080:80", "127.0.0.1:8080:80/tcp", "3000-3005", or a bare number
+fn container_ports(entry: &Value) -> Result<Vec<u16>> {
+    let spec = match entry {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Mapping(long) => {
+            if long.get("protocol").and_then(Value::as_str) == Some("udp") {
+                return Ok(Vec::new());
+            }
+            let target = long
+                .get("target")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| anyhow!("Port entry without target: {:?}", entry))?;
+            return Ok(vec![u16::try_from(target)?]);
+        }
+        _ => return Err(anyhow!("Unsupported port entry: {:?}", entry)),
+    };
+    let (spec, proto) = spec.split_once('/').unwrap_or((&spec, "tcp"));
+    if proto != "tcp" {
+        return Ok(Vec::new());
+    }
+    let container = spec.rsplit(':').next().unwrap_or(spec);
+    let parse = |port: &str| {
+        port.trim()
+            .parse::<u16>()
+            .map_err(|_| anyhow!("Invalid port '{}'", spec))
+    };
+    match container.split_once('-') {
+        Some((start, end)) => Ok((parse(start)?..=parse(end)?).collect()),
+        None => Ok(vec![parse(container)?]),
+    }
+}
+
+pub fn load(path: &Path, project: Option<&str>) -> Result<Stack> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let file: ComposeFile = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let project = project
+        .map(str::to_string)
+        .or_else(|| std::env::var("COMPOSE_PROJECT_NAME").ok())
+        .or(file.name)
+        .or_else(|| directory_project(path))
+        .ok_or_else(|| anyhow!("Could not determine the compose project name"))?;
+
+    let mut services = BTreeMap::new();
+    for (name, service) in file.services {
+        let mut ports = BTreeSet::new();
+        for entry in service.ports.iter().chain(&service.expose) {
+            match container_ports(entry) {
+                Ok(entry_ports) => ports.extend(entry_ports),
+                Err(e) => eprintln!("⚠️  [{}] {}, skipped", name, e),
+            }
+        }
+        services.insert(name, ports);
+    }
+    Ok(Stack { project, services })
+}
@@ -0,0 +1,390 @@
+// The k8s_port_forward workflow for docker-compose stacks: the compose file is read for
+// its services and their ports, and every container port gets a local forward at
+// container port + port_offset, so the same stack always ends up on the same ports.
+// Connections go through plugin_api::docker like docker_forward's, and the traffic log
+// lines carry the service name.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::docker::{self, Docker, Selector, Via};
+use plugin_api::net;
+use plugin_api::traffic::{relay_tagged, Protocol};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+mod compose;
+
+const DEFAULT_PORT_OFFSET: u16 = 10000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ComposeConfig {
+    /// Compose file; compose.yaml / docker-compose.yml in the current directory otherwise
+    pub file: Option<String>,
+    /// Project name; taken from the file or the directory name like compose does otherwise
+    pub project: Option<String>,
+    /// Docker or Podman socket
+    pub socket: Option<String>,
+    /// Added to the container port to get the local port (default 10000)
+    pub port_offset: Option<u16>,
+    /// Address to listen on: 127.0.0.1 (default), ::1, or :: for IPv4 and IPv6
+    pub bind: Option<String>,
+    pub via: Option<Via>,
+    /// Print the forwarded traffic (default true)
+    pub log_traffic: Option<bool>,
+    #[serde(default)]
+    pub service: BTreeMap<String, ServiceConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ServiceConfig {
+    /// Message decoding for the traffic log: tcp (default), http, postgres
+    pub protocol: Option<String>,
+    /// Local port per container port, e.g. { 5432 = 5432 }
+    #[serde(default)]
+    pub ports: BTreeMap<String, u16>,
+    /// Leave the service out
+    #[serde(default)]
+    pub skip: bool,
+}
+
+/// One local port → service port
+#[derive(Debug)]
+struct Forward {
+    service: String,
+    container_port: u16,
+    bind: String,
+    local_port: u16,
+    /// Shown in front of the log lines: the service, plus the port if it has several
+    tag: String,
+    protocol: Option<Protocol>,
+}
+
+pub struct ComposeForwardPlugin;
+
+impl ComposeForwardPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Compose Forward Configuration
+# file = "/home/me/src/shop/docker-compose.yml"  # default: compose file in the current directory
+# project = "shop"                         # default: `name:` in the file, or the directory name
+port_offset = 10000  # localhost:(container port + offset), e.g. db:5432 → localhost:15432
+# bind = "::1"       # default 127.0.0.1; "::" listens on every interface, IPv4 and IPv6
+# via = "exec"       # Options: auto, published, ip, exec
+# log_traffic = false
+
+[service.db]
+protocol = "postgres"
+ports = { 5432 = 5432 }  # explicit local port
+
+[service.api]
+protocol = "http"
+
+[service.worker]
+skip = true
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<ComposeConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: ComposeConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!(
+                    "📝 Sample config:\n{}",
+                    ComposeForwardPlugin::sample_config()
+                );
+                Ok(ComposeConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(ComposeConfig::default())
+        }
+    }
+}
+
+/// The forwards for a stack, in service order
+fn plan(
+    stack: &compose::Stack,
+    config: &ComposeConfig,
+    only: Option<&str>,
+    offset: u16,
+    protocol_override: Option<&str>,
+) -> Result<Vec<Forward>> {
+    let no_settings = ServiceConfig::default();
+    let mut forwards = Vec::new();
+    for (service, ports) in &stack.services {
+        let settings = config.service.get(service).unwrap_or(&no_settings);
+        if settings.skip || only.is_some_and(|only| only != service) {
+            continue;
+        }
+        for &container_port in ports {
+            let local_port = match settings.ports.get(&container_port.to_string()) {
+                Some(&port) => port,
+                None => container_port.checked_add(offset).ok_or_else(|| {
+                    anyhow!(
+                        "{}:{} + offset {} is past 65535, set a port in [service.{}] ports",
+                        service,
+                        container_port,
+                        offset,
+                        service
+                    )
+                })?,
+            };
+            let tag = if ports.len() > 1 {
+                format!("{}:{}", service, container_port)
+            } else {
+                service.clone()
+            };
+            let protocol = config.log_traffic.unwrap_or(true).then(|| {
+                Protocol::from(
+                    protocol_override
+                        .or(settings.protocol.as_deref())
+                        .unwrap_or("tcp"),
+                )
+            });
+            forwards.push(Forward {
+                service: service.clone(),
+                container_port,
+                bind: config
+                    .bind
+                    .clone()
+                    .unwrap_or_else(|| net::LOOPBACK.to_string()),
+                local_port,
+                tag,
+                protocol,
+            });
+        }
+    }
+    Ok(forwards)
+}
+
+async fn run_forward(docker: Docker, project: String, fwd: Forward, via: Via) -> Result<()> {
+    let listener = net::listen(&fwd.bind, fwd.local_port).await?;
+    let selector = Selector::Labels(vec![
+        format!("com.docker.compose.project={}", project),
+        format!("com.docker.compose.service={}", fwd.service),
+    ]);
+    let shared = Arc::new((fwd, selector));
+
+    loop {
+        let (client, addr) = listener.accept().await?;
+        let docker = docker.clone();
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            let (fwd, selector) = shared.as_ref();
+            match docker::open(&docker, selector, fwd.container_port, via).await {
+                Ok((container, connection)) => {
+                    println!(
+                        "📞 [{}] New connection from {} → {} ({})",
+                        fwd.tag, addr, container, connection.how
+                    );
+                    relay_tagged(
+                        &fwd.tag,
+                        client,
+                        connection.reader,
+                        connection.writer,
+                        fwd.protocol.as_ref(),
+                    )
+                    .await;
+                    println!("🔌 [{}] Connection from {} closed", fwd.tag, addr);
+                }
+                Err(e) => eprintln!("❌ [{}] {}", fwd.tag, e),
+            }
+        });
+    }
+}
+
+impl Plugin for ComposeForwardPlugin {
+    fn name(&self) -> &'static str {
+        "compose_forward"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Local forwards to every service port of a docker-compose stack"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Forward the ports of a docker-compose stack to localhost")
+            .arg(
+                Arg::new("file")
+                    .long("file")
+                    .short('f')
+                    .value_name("FILE")
+                    .help("Compose file (default: compose file in the current directory)"),
+            )
+            .arg(
+                Arg::new("project")
+                    .long("project")
+                    .short('p')
+                    .value_name("PROJECT")
+                    .help("Compose project name"),
+            )
+            .arg(
+                Arg::new("service")
+                    .long("service")
+                    .short('s')
+                    .value_name("SERVICE")
+                    .help("Only forward this service"),
+            )
+            .arg(
+                Arg::new("offset")
+                    .long("offset")
+                    .value_name("OFFSET")
+                    .help("Added to container ports to get local ports")
+                    .value_parser(clap::value_parser!(u16)),
+            )
+            .arg(
+                Arg::new("socket")
+                    .long("socket")
+                    .value_name("SOCKET")
+                    .help("Docker or Podman socket to use"),
+            )
+            .arg(
+                Arg::new("protocol")
+                    .long("protocol")
+                    .value_name("PROTOCOL")
+                    .help("Protocol for message decoding: tcp, http, postgres")
+                    .value_parser(["tcp", "http", "postgres"]),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(ComposeForwardPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<ComposeConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        plugin_api::metrics::init(self.name());
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let file = matches.get_one::<String>("file").or(config.file.as_ref());
+            let project = matches
+                .get_one::<String>("project")
+                .or(config.project.as_ref());
+            let stack = match compose::find_file(file.map(String::as_str))
+                .and_then(|path| compose::load(&path, project.map(String::as_str)))
+            {
+                Ok(stack) => stack,
+                Err(e) => {
+                    eprintln!("❌ {:#}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let offset = matches
+                .get_one::<u16>("offset")
+                .copied()
+                .or(config.port_offset)
+                .unwrap_or(DEFAULT_PORT_OFFSET);
+            let forwards = match plan(
+                &stack,
+                &config,
+                matches.get_one::<String>("service").map(String::as_str),
+                offset,
+                matches.get_one::<String>("protocol").map(String::as_str),
+            ) {
+                Ok(forwards) => forwards,
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if forwards.is_empty() {
+                eprintln!(
+                    "❌ No service ports to forward in project {}",
+                    stack.project
+                );
+                eprintln!("💡 Services need `ports` or `expose` entries in the compose file");
+                std::process::exit(1);
+            }
+
+            let socket = matches
+                .get_one::<String>("socket")
+                .or(config.socket.as_ref());
+            let docker = match docker::connect(socket.map(String::as_str)) {
+                Ok(docker) => docker,
+                Err(e) => {
+                    eprintln!("❌ {:#}", e);
+                    eprintln!(
+                        "💡 Is Docker or Podman running? Set `socket` in the config to pick one"
+                    );
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!(
+                "🚀 Forwarding {} port(s) of compose project {}:",
+                forwards.len(),
+                stack.project
+            );
+            for fwd in &forwards {
+                println!(
+                    "  {}:{} → {}",
+                    fwd.service,
+                    fwd.container_port,
+                    net::host_port(&fwd.bind, fwd.local_port)
+                );
+            }
+
+            let via = config.via.unwrap_or_default();
+            let handles: Vec<_> = forwards
+                .into_iter()
+                .map(|fwd| {
+                    let docker = docker.clone();
+                    let project = stack.project.clone();
+                    tokio::spawn(async move {
+                        let tag = fwd.tag.clone();
+                        if let Err(e) = run_forward(docker, project, fwd, via).await {
+                            eprintln!("❌ [{}] {}", tag, e);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(ComposeForwardPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,97 @@
+// The database clients, pointed at the tunnel. Passwords go through the environment
+// variables each client reads (PGPASSWORD, MYSQL_PWD, REDISCLI_AUTH), so they don't show
+// up in the process list.
+use crate::{Engine, Target};
+use tokio::process::Command;
+
+/// What the client logs in with
+pub struct Login {
+    pub host: String,
+    pub port: u16,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub database: Option<String>,
+    /// Require TLS, which RDS IAM logins need
+    pub tls: bool,
+}
+
+pub fn default_program(engine: Engine) -> &'static str {
+    match engine {
+        Engine::Postgres => "psql",
+        Engine::Mysql => "mysql",
+        Engine::Redis => "redis-cli",
+    }
+}
+
+/// The client command; `extra` comes after the target's client_args
+pub fn command(target: &Target, login: &Login, extra: &[String]) -> Command {
+    let program = target
+        .client
+        .as_deref()
+        .unwrap_or(default_program(target.engine));
+    let mut command = Command::new(program);
+    let args = target.client_args.iter().chain(extra);
+    match target.engine {
+        Engine::Postgres => {
+            command
+                .env("PGHOST", &login.host)
+                .env("PGPORT", login.port.to_string());
+            if let Some(user) = &login.user {
+                command.env("PGUSER", user);
+            }
+            if let Some(database) = &login.database {
+                command.env("PGDATABASE", database);
+            }
+            if let Some(password) = &login.password {
+                command.env("PGPASSWORD", password);
+            }
+            if login.tls {
+                command.env("PGSSLMODE", "require");
+            }
+            command.args(args);
+        }
+        Engine::Mysql => {
+            command
+                .arg("-h")
+                .arg(&login.host)
+                .arg("-P")
+                .arg(login.port.to_string());
+            if let Some(user) = &login.user {
+                command.arg("-u").arg(user);
+            }
+            if let Some(password) = &login.password {
+                command.env("MYSQL_PWD", password);
+            }
+            if login.tls {
+                command
+                    .arg("--ssl-mode=REQUIRED")
+                    .arg("--enable-cleartext-plugin");
+            }
+            command.args(args);
+            if let Some(database) = &login.database {
+                command.arg(database);
+            }
+        }
+        Engine::Redis => {
+            command
+                .arg("-h")
+                .arg(&login.host)
+                .arg("-p")
+                .arg(login.port.to_string());
+            if let Some(user) = &login.user {
+                command.arg("--user").arg(user);
+            }
+            if let Some(database) = &login.database {
+                command.arg("-n").arg(database);
+            }
+            if let Some(password) = &login.password {
+                command.env("REDISCLI_AUTH", password);
+            }
+            if login.tls {
+                command.arg("--tls");
+            }
+            command.args(args);
+        }
+    }
+    command
+}
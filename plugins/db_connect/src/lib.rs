@@ -0,0 +1,407 @@
+// One command from "I need to query staging" to a prompt: a named target says where the
+// database is and how to reach it, the plugin opens that tunnel, resolves the password
+// through the secret references of plugin_api (or an RDS IAM token), and runs psql, mysql
+// or redis-cli against it. The tunnel closes when the client exits.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::net;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use tokio::runtime::Runtime;
+
+mod client;
+mod tunnel;
+
+use client::Login;
+
+const PLUGIN_NAME: &str = "db_connect";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DbConnectConfig {
+    #[serde(default)]
+    pub target: Vec<Target>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    Postgres,
+    Mysql,
+    Redis,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelKind {
+    #[default]
+    Direct,
+    K8s,
+    Ssh,
+    CloudSql,
+    Rds,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Target {
+    pub name: String,
+    /// postgres, mysql or redis
+    pub engine: Engine,
+    /// direct (default), k8s, ssh, cloudsql or rds
+    #[serde(default)]
+    pub tunnel: TunnelKind,
+    /// Database host; with ssh as seen from the jump host (default localhost)
+    pub host: Option<String>,
+    /// Database port, the service's or pod's with k8s (default: the engine's)
+    pub port: Option<u16>,
+    /// Database name, the database number with redis
+    pub database: Option<String>,
+    pub user: Option<String>,
+    /// Secret reference such as "secret:NAME" or "vault:PATH#FIELD"; RDS targets
+    /// without one log in with an IAM auth token
+    pub password: Option<String>,
+    /// k8s: namespace (default: the kubeconfig context's)
+    pub namespace: Option<String>,
+    /// k8s: the service to reach, or a pod by name or selector
+    pub service: Option<String>,
+    pub pod_name: Option<String>,
+    pub pod_selector: Option<String>,
+    /// ssh: jump host, or a Host alias from ~/.ssh/config
+    pub ssh_host: Option<String>,
+    pub ssh_user: Option<String>,
+    /// cloudsql: "project:region:instance"
+    pub connection_name: Option<String>,
+    /// cloudsql: log in as the IAM principal
+    #[serde(default)]
+    pub iam_auth: bool,
+    #[serde(default)]
+    pub private_ip: bool,
+    /// rds: AWS region and profile for the IAM token
+    pub region: Option<String>,
+    pub profile: Option<String>,
+    /// Fixed local port of the tunnel (default: a free one)
+    pub local_port: Option<u16>,
+    /// Client program instead of psql, mysql or redis-cli, e.g. "pgcli"
+    pub client: Option<String>,
+    #[serde(default)]
+    pub client_args: Vec<String>,
+}
+
+impl Target {
+    /// The host, an IPv6 address without brackets
+    pub fn host(&self) -> String {
+        self.host
+            .as_deref()
+            .map_or("localhost", net::unbracket)
+            .to_string()
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.engine {
+            Engine::Postgres => 5432,
+            Engine::Mysql => 3306,
+            Engine::Redis => 6379,
+        })
+    }
+
+    fn describe(&self) -> String {
+        let place = match self.tunnel {
+            TunnelKind::Direct => net::host_port(&self.host(), self.port()),
+            TunnelKind::K8s => {
+                let what = match (&self.service, &self.pod_name, &self.pod_selector) {
+                    (Some(service), _, _) => format!("svc/{}", service),
+                    (_, Some(pod), _) => format!("pod/{}", pod),
+                    (_, _, selector) => selector.clone().unwrap_or_default(),
+                };
+                format!("k8s {}:{}", what, self.port())
+            }
+            TunnelKind::Ssh => format!(
+                "{} via ssh {}",
+                net::host_port(&self.host(), self.port()),
+                self.ssh_host.as_deref().unwrap_or("?")
+            ),
+            TunnelKind::CloudSql => format!(
+                "Cloud SQL {}",
+                self.connection_name.as_deref().unwrap_or("?")
+            ),
+            TunnelKind::Rds => format!("RDS {}", net::host_port(&self.host(), self.port())),
+        };
+        format!("{} → {}", client::default_program(self.engine), place)
+    }
+}
+
+pub struct DbConnectPlugin;
+
+impl DbConnectPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Database Connect Configuration
+[[target]]
+name = "staging"
+engine = "postgres"  # Options: postgres, mysql, redis
+tunnel = "k8s"  # Options: direct, k8s, ssh, cloudsql, rds
+namespace = "staging"
+service = "postgres"
+database = "app"
+user = "app"
+password = "secret:staging-db"  # or "vault:kv/data/staging/db#password"
+
+[[target]]
+name = "prod-orders"
+engine = "postgres"
+tunnel = "cloudsql"
+connection_name = "my-project:europe-west1:orders"
+iam_auth = true
+user = "me@example.com"
+database = "orders"
+
+[[target]]
+name = "prod-billing"
+engine = "mysql"
+tunnel = "rds"  # no password: an IAM auth token is used
+host = "billing.abc123xyz.eu-west-1.rds.amazonaws.com"
+region = "eu-west-1"
+user = "app_iam"
+database = "billing"
+
+[[target]]
+name = "sessions"
+engine = "redis"
+tunnel = "ssh"
+ssh_host = "bastion.example.com"
+host = "sessions.internal"
+password = "secret:env:REDIS_PASSWORD"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<DbConnectConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: DbConnectConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", DbConnectPlugin::sample_config());
+                Ok(DbConnectConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(DbConnectConfig::default())
+        }
+    }
+}
+
+fn print_targets(targets: &[Target]) {
+    let width = targets
+        .iter()
+        .map(|target| target.name.len())
+        .max()
+        .unwrap_or(0);
+    for target in targets {
+        println!("  {:<width$}  {}", target.name, target.describe());
+    }
+}
+
+/// The password from the target's secret reference, or an IAM token for RDS
+async fn password(target: &Target) -> Result<Option<String>> {
+    match (&target.password, target.tunnel) {
+        (Some(reference), _) => Ok(Some(
+            plugin_api::resolve_secret(reference).map_err(|e| anyhow!(e))?,
+        )),
+        (None, TunnelKind::Rds) => {
+            let user = target
+                .user
+                .as_deref()
+                .ok_or_else(|| anyhow!("Target '{}' needs a user for IAM auth", target.name))?;
+            Ok(Some(tunnel::rds_token(target, user).await?))
+        }
+        (None, _) => Ok(None),
+    }
+}
+
+/// A connection URL without the password, for GUI clients and scripts
+fn url(target: &Target, login: &Login) -> String {
+    let scheme = match target.engine {
+        Engine::Postgres => "postgres",
+        Engine::Mysql => "mysql",
+        Engine::Redis => "redis",
+    };
+    let user = login
+        .user
+        .as_ref()
+        .map(|user| format!("{}@", user))
+        .unwrap_or_default();
+    let database = login
+        .database
+        .as_ref()
+        .map(|database| format!("/{}", database))
+        .unwrap_or_default();
+    format!(
+        "{}://{}{}{}",
+        scheme,
+        user,
+        net::host_port(&login.host, login.port),
+        database
+    )
+}
+
+async fn connect(target: &Target, extra: &[String], tunnel_only: bool) -> Result<i32> {
+    let password = password(target).await?;
+    let tunnel = tunnel::open(target).await?;
+    // The target counts as in use for as long as the tunnel or client runs
+    plugin_api::usage::init(PLUGIN_NAME);
+    let _session = if target.tunnel == TunnelKind::K8s {
+        plugin_api::usage::session_k8s(&target.name)
+    } else {
+        plugin_api::usage::session(&target.name, None)
+    };
+    let login = Login {
+        host: tunnel.host.clone(),
+        port: tunnel.port,
+        user: target.user.clone(),
+        password,
+        database: target.database.clone(),
+        tls: target.tunnel == TunnelKind::Rds && target.password.is_none(),
+    };
+
+    if tunnel_only {
+        println!("✅ {} is reachable at {}", target.name, url(target, &login));
+        match (&target.password, &login.password) {
+            (Some(reference), _) => println!("🔑 Password: {}", reference),
+            // Nothing else hands out the token
+            (None, Some(token)) => println!("🔑 IAM auth token (valid 15 minutes): {}", token),
+            (None, None) => {}
+        }
+        ctrlc::set_handler(move || {
+            println!("\n👋 Shutting down...");
+            std::process::exit(0);
+        })?;
+        std::future::pending::<()>().await;
+    }
+
+    // Ctrl+C is for the client (e.g. psql cancels the running query), not for us
+    ctrlc::set_handler(|| {})?;
+    let program = target
+        .client
+        .as_deref()
+        .unwrap_or(client::default_program(target.engine));
+    eprintln!("🚀 {} → {}", program, url(target, &login));
+    let status = client::command(target, &login, extra)
+        .status()
+        .await
+        .map_err(|e| anyhow!("Failed to run {}: {}", program, e))?;
+    drop(tunnel);
+    Ok(status.code().unwrap_or(1))
+}
+
+impl Plugin for DbConnectPlugin {
+    fn name(&self) -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Open the tunnel to a named database and start psql, mysql or redis-cli on it"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Connect a database client to a named target through its tunnel")
+            .arg(
+                Arg::new("target")
+                    .value_name("TARGET")
+                    .help("Name of the target in the config file"),
+            )
+            .arg(
+                Arg::new("list")
+                    .long("list")
+                    .short('l')
+                    .action(ArgAction::SetTrue)
+                    .help("List the configured targets"),
+            )
+            .arg(
+                Arg::new("tunnel-only")
+                    .long("tunnel-only")
+                    .action(ArgAction::SetTrue)
+                    .requires("target")
+                    .help("Keep the tunnel open and print where to connect, without a client"),
+            )
+            .arg(
+                Arg::new("args")
+                    .value_name("CLIENT_ARGS")
+                    .num_args(1..)
+                    .last(true)
+                    .help("Arguments for the client, after --"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(DbConnectPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<DbConnectConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let name = match matches.get_one::<String>("target") {
+            Some(name) if !matches.get_flag("list") => name,
+            _ => {
+                if config.target.is_empty() {
+                    println!("📭 No targets configured");
+                } else {
+                    println!("🗄️  Targets:");
+                    print_targets(&config.target);
+                }
+                return;
+            }
+        };
+        let Some(target) = config.target.iter().find(|target| &target.name == name) else {
+            eprintln!("❌ No target named '{}'", name);
+            if !config.target.is_empty() {
+                eprintln!("💡 Configured targets:");
+                print_targets(&config.target);
+            }
+            std::process::exit(1);
+        };
+        let extra: Vec<String> = matches
+            .get_many::<String>("args")
+            .map(|args| args.cloned().collect())
+            .unwrap_or_default();
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        let code = rt.block_on(async {
+            match connect(target, &extra, matches.get_flag("tunnel-only")).await {
+                Ok(code) => code,
+                Err(e) => {
+                    eprintln!("❌ {}: {}", target.name, e);
+                    1
+                }
+            }
+        });
+        drop(rt);
+        std::process::exit(code);
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(DbConnectPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,300 @@
+// The way to a target's database, opened for the length of one client session. Direct
+// targets need none. Kubernetes and SSH targets get a listener on a free loopback port
+// whose connections are carried over the port-forward API or `ssh -W`. Cloud SQL runs
+// the Cloud SQL Auth Proxy on a free port; RDS is reached directly.
+use crate::{Target, TunnelKind, PLUGIN_NAME};
+use anyhow::{anyhow, Context, Result};
+use k8s_openapi::api::core::v1::{Pod, Service};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::api::Api;
+use kube::Client;
+use plugin_api::k8s::running_pod;
+use plugin_api::net;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::{Child, Command};
+
+const LOOPBACK: &str = "127.0.0.1";
+/// How long the Cloud SQL Auth Proxy gets to start listening
+const PROXY_STARTUP: Duration = Duration::from_secs(30);
+
+/// Where the client connects; the tunnel stays open while this is alive
+pub struct Tunnel {
+    pub host: String,
+    pub port: u16,
+    /// The Cloud SQL Auth Proxy, killed when the tunnel is dropped
+    _proxy: Option<Child>,
+}
+
+/// The pod and its port for a target given by service, pod name or selector
+async fn k8s_backend(
+    target: &Target,
+    pods: &Api<Pod>,
+    services: &Api<Service>,
+) -> Result<(String, u16)> {
+    let port = target.port();
+    if let Some(name) = &target.service {
+        let service = services.get(name).await?;
+        let spec = service
+            .spec
+            .ok_or_else(|| anyhow!("Service {} has no spec", name))?;
+        let selector = spec
+            .selector
+            .unwrap_or_default()
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(",");
+        if selector.is_empty() {
+            return Err(anyhow!("Service {} has no selector", name));
+        }
+        let service_port = spec
+            .ports
+            .unwrap_or_default()
+            .into_iter()
+            .find(|p| p.port == port as i32)
+            .ok_or_else(|| anyhow!("Service {} has no port {}", name, port))?;
+        let pod = running_pod(pods, &selector).await?;
+        let pod_port = match service_port.target_port {
+            Some(IntOrString::Int(number)) => number as u16,
+            Some(IntOrString::String(port_name)) => pod
+                .spec
+                .iter()
+                .flat_map(|spec| &spec.containers)
+                .flat_map(|container| container.ports.iter().flatten())
+                .find(|p| p.name.as_deref() == Some(port_name.as_str()))
+                .map(|p| p.container_port as u16)
+                .ok_or_else(|| anyhow!("No container port named {}", port_name))?,
+            None => port,
+        };
+        let pod_name = pod.metadata.name.unwrap_or_default();
+        return Ok((pod_name, pod_port));
+    }
+    if let Some(name) = &target.pod_name {
+        return Ok((name.clone(), port));
+    }
+    let selector = target.pod_selector.as_deref().ok_or_else(|| {
+        anyhow!(
+            "Target '{}' needs a service, pod_name or pod_selector",
+            target.name
+        )
+    })?;
+    let pod = running_pod(pods, selector).await?;
+    Ok((pod.metadata.name.unwrap_or_default(), port))
+}
+
+/// A listener for the client, on the target's local_port or a free one
+async fn listen(target: &Target) -> Result<(TcpListener, u16)> {
+    let listener = TcpListener::bind((LOOPBACK, target.local_port.unwrap_or(0))).await?;
+    let port = listener.local_addr()?.port();
+    Ok((listener, port))
+}
+
+async fn open_k8s(target: &Target) -> Result<Tunnel> {
+    let client = Client::try_default().await?;
+    let namespace = target
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+    let pods: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    let services: Api<Service> = Api::namespaced(client, &namespace);
+    let (pod, pod_port) = k8s_backend(target, &pods, &services).await?;
+    eprintln!("🔗 Forwarding to pod {}:{} in {}", pod, pod_port, namespace);
+
+    let (listener, port) = listen(target).await?;
+    plugin_api::audit::record_k8s(
+        crate::PLUGIN_NAME,
+        "port_forward",
+        &[
+            ("namespace", &namespace),
+            ("pod", &pod),
+            ("local_port", &port.to_string()),
+            ("remote_port", &pod_port.to_string()),
+        ],
+    );
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let pods = pods.clone();
+            let pod = pod.clone();
+            tokio::spawn(async move {
+                let stream = match pods.portforward(&pod, &[pod_port]).await {
+                    Ok(mut forwarder) => forwarder.take_stream(pod_port),
+                    Err(e) => {
+                        eprintln!("❌ Port forward to {} failed: {}", pod, e);
+                        return;
+                    }
+                };
+                if let Some(mut stream) = stream {
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut stream).await;
+                }
+            });
+        }
+    });
+    Ok(Tunnel {
+        host: LOOPBACK.to_string(),
+        port,
+        _proxy: None,
+    })
+}
+
+async fn open_ssh(target: &Target) -> Result<Tunnel> {
+    let jump = target
+        .ssh_host
+        .clone()
+        .ok_or_else(|| anyhow!("Target '{}' needs an ssh_host", target.name))?;
+    let destination = net::host_port(&target.host(), target.port());
+    eprintln!("🔗 Forwarding to {} via ssh {}", destination, jump);
+    let user = target.ssh_user.clone();
+    let (listener, port) = listen(target).await?;
+    let spec = Arc::new((jump, user, destination));
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let spec = spec.clone();
+            tokio::spawn(async move {
+                let (jump, user, destination) = spec.as_ref();
+                let mut command = Command::new("ssh");
+                command.arg("-o").arg("BatchMode=yes");
+                if let Some(user) = user {
+                    command.arg("-l").arg(user);
+                }
+                let child = command
+                    .arg("-W")
+                    .arg(destination)
+                    .arg(jump)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn();
+                let mut child = match child {
+                    Ok(child) => child,
+                    Err(e) => {
+                        eprintln!("❌ Failed to start ssh: {}", e);
+                        return;
+                    }
+                };
+                if let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) {
+                    let mut upstream = tokio::io::join(stdout, stdin);
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+                }
+            });
+        }
+    });
+    Ok(Tunnel {
+        host: LOOPBACK.to_string(),
+        port,
+        _proxy: None,
+    })
+}
+
+async fn open_cloudsql(target: &Target) -> Result<Tunnel> {
+    let connection_name = target
+        .connection_name
+        .as_deref()
+        .ok_or_else(|| anyhow!("Target '{}' needs a connection_name", target.name))?;
+    // The proxy binds the port itself, so take a free one and let it go
+    let port = match target.local_port {
+        Some(port) => port,
+        None => std::net::TcpListener::bind((LOOPBACK, 0))?
+            .local_addr()?
+            .port(),
+    };
+    let log_path = plugin_api::plugin_state_dir(PLUGIN_NAME)
+        .map(|dir| {
+            let _ = std::fs::create_dir_all(&dir);
+            dir.join("cloud-sql-proxy.log")
+        })
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+    let log = std::fs::File::create(&log_path)?;
+
+    let mut command = Command::new("cloud-sql-proxy");
+    command
+        .arg(connection_name)
+        .arg(format!("--address={}", LOOPBACK))
+        .arg(format!("--port={}", port));
+    if target.iam_auth {
+        command.arg("--auto-iam-authn");
+    }
+    if target.private_ip {
+        command.arg("--private-ip");
+    }
+    eprintln!(
+        "🔗 Starting the Cloud SQL Auth Proxy for {}",
+        connection_name
+    );
+    let mut proxy = command
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to run cloud-sql-proxy; is it installed?")?;
+
+    let started = Instant::now();
+    while TcpStream::connect((LOOPBACK, port)).await.is_err() {
+        if let Some(status) = proxy.try_wait()? {
+            return Err(anyhow!(
+                "cloud-sql-proxy exited ({}), see {}",
+                status,
+                log_path.display()
+            ));
+        }
+        if started.elapsed() > PROXY_STARTUP {
+            return Err(anyhow!(
+                "cloud-sql-proxy didn't start listening, see {}",
+                log_path.display()
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    Ok(Tunnel {
+        host: LOOPBACK.to_string(),
+        port,
+        _proxy: Some(proxy),
+    })
+}
+
+pub async fn open(target: &Target) -> Result<Tunnel> {
+    match target.tunnel {
+        TunnelKind::Direct | TunnelKind::Rds => Ok(Tunnel {
+            host: target.host(),
+            port: target.port(),
+            _proxy: None,
+        }),
+        TunnelKind::K8s => open_k8s(target).await,
+        TunnelKind::Ssh => open_ssh(target).await,
+        TunnelKind::CloudSql => open_cloudsql(target).await,
+    }
+}
+
+/// An RDS IAM auth token for the target's user, valid for 15 minutes
+pub async fn rds_token(target: &Target, user: &str) -> Result<String> {
+    let mut command = Command::new("aws");
+    if let Some(profile) = &target.profile {
+        command.arg("--profile").arg(profile);
+    }
+    if let Some(region) = &target.region {
+        command.arg("--region").arg(region);
+    }
+    let output = command
+        .arg("rds")
+        .arg("generate-db-auth-token")
+        .arg("--hostname")
+        .arg(target.host())
+        .arg("--port")
+        .arg(target.port().to_string())
+        .arg("--username")
+        .arg(user)
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("Failed to run the aws CLI")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "aws rds generate-db-auth-token failed ({})",
+            output.status
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,225 @@
+// Just enough of the DNS wire format (RFC 1035) for the proxy: reading the question of a
+// query and building answers for overridden names. Everything else is forwarded as-is.
+use std::fmt;
+use std::net::IpAddr;
+
+const HEADER_LEN: usize = 12;
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const FLAG_RECURSION_AVAILABLE: u16 = 0x0080;
+/// Pointer to the name at offset 12, i.e. the question's
+const QUESTION_NAME_POINTER: u16 = 0xC00C;
+
+#[derive(Debug)]
+pub struct Query {
+    pub id: u16,
+    flags: u16,
+    pub name: String,
+    pub qtype: u16,
+    /// Offset just past the question, which answers repeat verbatim
+    question_end: usize,
+}
+
+pub struct TypeName(pub u16);
+
+impl fmt::Display for TypeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            TYPE_A => write!(f, "A"),
+            TYPE_AAAA => write!(f, "AAAA"),
+            5 => write!(f, "CNAME"),
+            12 => write!(f, "PTR"),
+            15 => write!(f, "MX"),
+            16 => write!(f, "TXT"),
+            33 => write!(f, "SRV"),
+            65 => write!(f, "HTTPS"),
+            other => write!(f, "TYPE{}", other),
+        }
+    }
+}
+
+fn read_u16(packet: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *packet.get(offset)?,
+        *packet.get(offset + 1)?,
+    ]))
+}
+
+/// The first question of a query; None for anything that isn't a plain query
+pub fn parse_query(packet: &[u8]) -> Option<Query> {
+    let id = read_u16(packet, 0)?;
+    let flags = read_u16(packet, 2)?;
+    let questions = read_u16(packet, 4)?;
+    if flags & FLAG_RESPONSE != 0 || questions == 0 {
+        return None;
+    }
+
+    let mut labels = Vec::new();
+    let mut offset = HEADER_LEN;
+    loop {
+        let len = *packet.get(offset)? as usize;
+        offset += 1;
+        if len == 0 {
+            break;
+        }
+        // Queries don't compress the question name
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        let label = packet.get(offset..offset + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        offset += len;
+    }
+    let qtype = read_u16(packet, offset)?;
+    // The class isn't looked at, but answers repeat it, so it has to be there
+    read_u16(packet, offset + 2)?;
+    Some(Query {
+        id,
+        flags,
+        name: labels.join("."),
+        qtype,
+        question_end: offset + 4,
+    })
+}
+
+/// An authoritative answer with the addresses that fit the question's type; no matching
+/// address gives an empty answer, so the client doesn't try other servers
+pub fn answer(query: &Query, packet: &[u8], addresses: &[IpAddr], ttl: u32) -> Vec<u8> {
+    let records: Vec<Vec<u8>> = addresses
+        .iter()
+        .filter_map(|address| match (address, query.qtype) {
+            (IpAddr::V4(v4), TYPE_A) => Some(v4.octets().to_vec()),
+            (IpAddr::V6(v6), TYPE_AAAA) => Some(v6.octets().to_vec()),
+            _ => None,
+        })
+        .collect();
+
+    let flags = FLAG_RESPONSE
+        | FLAG_AUTHORITATIVE
+        | FLAG_RECURSION_AVAILABLE
+        | (query.flags & FLAG_RECURSION_DESIRED);
+    let mut response = Vec::with_capacity(query.question_end + records.len() * 28);
+    response.extend_from_slice(&query.id.to_be_bytes());
+    response.extend_from_slice(&flags.to_be_bytes());
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&(records.len() as u16).to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0]);
+    response.extend_from_slice(&packet[HEADER_LEN..query.question_end]);
+    for rdata in records {
+        response.extend_from_slice(&QUESTION_NAME_POINTER.to_be_bytes());
+        response.extend_from_slice(&query.qtype.to_be_bytes());
+        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&ttl.to_be_bytes());
+        response.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        response.extend_from_slice(&rdata);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A query for `name` as a stub resolver sends it, with recursion desired
+    fn query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+        let mut packet = Vec::new();
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+        packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.split('.') {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.push(0);
+        packet.extend_from_slice(&qtype.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn parses_the_question_of_a_query() {
+        let packet = query(0x1234, "db.dev.internal", TYPE_AAAA);
+        let parsed = parse_query(&packet).unwrap();
+        assert_eq!(parsed.id, 0x1234);
+        assert_eq!(parsed.name, "db.dev.internal");
+        assert_eq!(parsed.qtype, TYPE_AAAA);
+        assert_eq!(parsed.question_end, packet.len());
+    }
+
+    #[test]
+    fn truncated_queries_are_not_parsed() {
+        let packet = query(1, "db.dev.internal", TYPE_A);
+        // Cut inside the class, the type, the name and the header
+        for len in [packet.len() - 1, packet.len() - 3, 20, HEADER_LEN, 5] {
+            assert!(
+                parse_query(&packet[..len]).is_none(),
+                "parsed {} bytes",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn responses_and_compressed_names_are_not_parsed() {
+        let mut response = query(1, "example.com", TYPE_A);
+        response[2] |= (FLAG_RESPONSE >> 8) as u8;
+        assert!(parse_query(&response).is_none());
+
+        let mut compressed = query(1, "example.com", TYPE_A);
+        compressed[HEADER_LEN] = 0xC0;
+        assert!(parse_query(&compressed).is_none());
+
+        let mut no_question = query(1, "example.com", TYPE_A);
+        no_question[5] = 0;
+        assert!(parse_query(&no_question).is_none());
+    }
+
+    #[test]
+    fn answers_with_the_addresses_of_the_queried_type() {
+        let packet = query(0xBEEF, "db.dev.internal", TYPE_A);
+        let parsed = parse_query(&packet).unwrap();
+        let addresses: Vec<IpAddr> = vec![
+            "10.0.0.7".parse().unwrap(),
+            "fd00::7".parse().unwrap(),
+            "10.0.0.8".parse().unwrap(),
+        ];
+        let response = answer(&parsed, &packet, &addresses, 60);
+
+        assert_eq!(read_u16(&response, 0), Some(0xBEEF));
+        let flags = read_u16(&response, 2).unwrap();
+        assert_ne!(flags & FLAG_RESPONSE, 0);
+        assert_ne!(flags & FLAG_AUTHORITATIVE, 0);
+        assert_ne!(flags & FLAG_RECURSION_DESIRED, 0);
+        assert_eq!(read_u16(&response, 6), Some(2));
+        assert_eq!(&response[HEADER_LEN..packet.len()], &packet[HEADER_LEN..]);
+
+        let first = packet.len();
+        assert_eq!(read_u16(&response, first), Some(QUESTION_NAME_POINTER));
+        assert_eq!(read_u16(&response, first + 2), Some(TYPE_A));
+        assert_eq!(read_u16(&response, first + 10), Some(4));
+        assert_eq!(&response[first + 12..first + 16], &[10, 0, 0, 7]);
+        assert_eq!(&response[first + 28..first + 32], &[10, 0, 0, 8]);
+        assert_eq!(response.len(), first + 32);
+    }
+
+    #[test]
+    fn answers_empty_when_no_address_fits_the_type() {
+        let packet = query(7, "db.dev.internal", TYPE_AAAA);
+        let parsed = parse_query(&packet).unwrap();
+        let response = answer(&parsed, &packet, &["10.0.0.7".parse().unwrap()], 60);
+        assert_eq!(read_u16(&response, 6), Some(0));
+        assert_eq!(response.len(), packet.len());
+    }
+
+    #[test]
+    fn names_record_types() {
+        assert_eq!(TypeName(TYPE_A).to_string(), "A");
+        assert_eq!(TypeName(TYPE_AAAA).to_string(), "AAAA");
+        assert_eq!(TypeName(65).to_string(), "HTTPS");
+        assert_eq!(TypeName(99).to_string(), "TYPE99");
+    }
+}
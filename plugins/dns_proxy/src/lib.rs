@@ -0,0 +1,337 @@
+// A local DNS resolver for development: names matching a [[record]] pattern (e.g.
+// "*.svc.cluster.local") are answered locally, typically with 127.0.0.1 where the
+// port forwards listen, and every other query is forwarded to the upstream resolver.
+// Apps resolve cluster host names without /etc/hosts edits. UDP only.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::runtime::Runtime;
+
+mod dns;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:5353";
+const FALLBACK_UPSTREAM: &str = "1.1.1.1:53";
+const DEFAULT_TTL: u32 = 5;
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest UDP answer accepted from upstream (EDNS sizes included)
+const MAX_PACKET: usize = 4096;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DnsConfig {
+    /// Address to answer on (default 127.0.0.1:5353)
+    pub listen: Option<String>,
+    /// Resolver for everything else (default: first nameserver in /etc/resolv.conf)
+    pub upstream: Option<String>,
+    /// TTL of local answers in seconds (default 5)
+    pub ttl: Option<u32>,
+    /// Print every query (default true)
+    pub log_queries: Option<bool>,
+    #[serde(default)]
+    pub record: Vec<Record>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Record {
+    /// "*", "*.svc.cluster.local" or an exact name
+    pub name: String,
+    /// IPv4 or IPv6 address returned for A or AAAA queries
+    pub address: IpAddr,
+}
+
+impl Record {
+    fn matches(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.');
+        match self.name.strip_prefix('*') {
+            Some("") => true,
+            Some(suffix) => name
+                .to_ascii_lowercase()
+                .ends_with(&suffix.to_ascii_lowercase()),
+            None => name.eq_ignore_ascii_case(self.name.trim_end_matches('.')),
+        }
+    }
+}
+
+pub struct DnsProxyPlugin;
+
+impl DnsProxyPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# DNS Proxy Configuration
+listen = "127.0.0.1:5353"
+# upstream = "1.1.1.1:53"  # default: first nameserver in /etc/resolv.conf
+ttl = 5
+
+[[record]]
+name = "*.svc.cluster.local"
+address = "127.0.0.1"
+
+[[record]]
+name = "*.svc.cluster.local"
+address = "::1"
+
+[[record]]
+name = "api.dev.internal"
+address = "127.0.0.1"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<DnsConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: DnsConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", DnsProxyPlugin::sample_config());
+                Ok(DnsConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(DnsConfig::default())
+        }
+    }
+}
+
+/// The system's first nameserver, unless it is this proxy
+fn system_upstream(listen: &SocketAddr) -> Option<SocketAddr> {
+    let resolv = fs::read_to_string("/etc/resolv.conf").ok()?;
+    resolv
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|address| address.trim().parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, 53))
+        .find(|upstream| upstream != listen)
+}
+
+struct Resolver {
+    socket: UdpSocket,
+    upstream: SocketAddr,
+    records: Vec<Record>,
+    ttl: u32,
+    log_queries: bool,
+}
+
+impl Resolver {
+    fn local_addresses(&self, name: &str) -> Vec<IpAddr> {
+        self.records
+            .iter()
+            .filter(|record| record.matches(name))
+            .map(|record| record.address)
+            .collect()
+    }
+
+    async fn forward(&self, packet: &[u8], client: SocketAddr) -> Result<()> {
+        let bind = if self.upstream.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.connect(self.upstream).await?;
+        socket.send(packet).await?;
+        let mut buffer = vec![0u8; MAX_PACKET];
+        let n = tokio::time::timeout(UPSTREAM_TIMEOUT, socket.recv(&mut buffer))
+            .await
+            .map_err(|_| anyhow!("{} did not answer", self.upstream))??;
+        self.socket.send_to(&buffer[..n], client).await?;
+        Ok(())
+    }
+
+    async fn handle(self: Arc<Self>, packet: Vec<u8>, client: SocketAddr) {
+        let query = dns::parse_query(&packet);
+        if let Some(query) = &query {
+            let addresses = self.local_addresses(&query.name);
+            if !addresses.is_empty() {
+                let response = dns::answer(query, &packet, &addresses, self.ttl);
+                if self.log_queries {
+                    let matching: Vec<String> = addresses
+                        .iter()
+                        .filter(|ip| match query.qtype {
+                            dns::TYPE_A => ip.is_ipv4(),
+                            dns::TYPE_AAAA => ip.is_ipv6(),
+                            _ => false,
+                        })
+                        .map(IpAddr::to_string)
+                        .collect();
+                    println!(
+                        "📌 {} {} → {}",
+                        dns::TypeName(query.qtype),
+                        query.name,
+                        if matching.is_empty() {
+                            "(no record)".to_string()
+                        } else {
+                            matching.join(", ")
+                        }
+                    );
+                }
+                if let Err(e) = self.socket.send_to(&response, client).await {
+                    eprintln!("❌ Failed to answer {}: {}", client, e);
+                }
+                return;
+            }
+            if self.log_queries {
+                println!(
+                    "↗️  {} {} → {}",
+                    dns::TypeName(query.qtype),
+                    query.name,
+                    self.upstream
+                );
+            }
+        }
+        if let Err(e) = self.forward(&packet, client).await {
+            let name = query.map(|query| query.name).unwrap_or_default();
+            eprintln!("❌ {} {}", name, e);
+        }
+    }
+}
+
+impl Plugin for DnsProxyPlugin {
+    fn name(&self) -> &'static str {
+        "dns_proxy"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Local DNS resolver answering configured names and forwarding the rest"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Run a local DNS resolver with overrides from the config file")
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .short('l')
+                    .value_name("ADDR")
+                    .help("Address to answer on (default 127.0.0.1:5353)"),
+            )
+            .arg(
+                Arg::new("upstream")
+                    .long("upstream")
+                    .value_name("ADDR")
+                    .help("Resolver for names without a record, e.g. 1.1.1.1:53"),
+            )
+            .arg(
+                Arg::new("quiet")
+                    .long("quiet")
+                    .short('q')
+                    .action(ArgAction::SetTrue)
+                    .help("Don't print queries"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(DnsProxyPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<DnsConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let listen = matches
+                .get_one::<String>("listen")
+                .or(config.listen.as_ref())
+                .map(String::as_str)
+                .unwrap_or(DEFAULT_LISTEN);
+            let listen: SocketAddr = match listen.parse() {
+                Ok(listen) => listen,
+                Err(e) => {
+                    eprintln!("❌ Invalid listen address {}: {}", listen, e);
+                    std::process::exit(1);
+                }
+            };
+            let upstream = match matches
+                .get_one::<String>("upstream")
+                .or(config.upstream.as_ref())
+            {
+                Some(upstream) => upstream.parse(),
+                None => Ok(system_upstream(&listen)
+                    .unwrap_or_else(|| FALLBACK_UPSTREAM.parse().expect("valid address"))),
+            };
+            let upstream: SocketAddr = match upstream {
+                Ok(upstream) => upstream,
+                Err(e) => {
+                    eprintln!("❌ Invalid upstream address: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let socket = match UdpSocket::bind(listen).await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    eprintln!("❌ Failed to listen on {}: {}", listen, e);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🚀 DNS proxy on {} (upstream {})", listen, upstream);
+            for record in &config.record {
+                println!("  {} → {}", record.name, record.address);
+            }
+            if config.record.is_empty() {
+                println!("💡 No [[record]] entries, every query goes upstream");
+            }
+
+            let resolver = Arc::new(Resolver {
+                socket,
+                upstream,
+                records: config.record,
+                ttl: config.ttl.unwrap_or(DEFAULT_TTL),
+                log_queries: !matches.get_flag("quiet") && config.log_queries.unwrap_or(true),
+            });
+            let mut buffer = vec![0u8; MAX_PACKET];
+            loop {
+                let (n, client) = match resolver.socket.recv_from(&mut buffer).await {
+                    Ok(received) => received,
+                    Err(e) => {
+                        eprintln!("❌ Receive failed: {}", e);
+                        continue;
+                    }
+                };
+                tokio::spawn(resolver.clone().handle(buffer[..n].to_vec(), client));
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(DnsProxyPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
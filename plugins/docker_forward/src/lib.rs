@@ -0,0 +1,317 @@
+// Local port forwards to Docker and Podman containers, found by name or label. Ports
+// don't have to be published: without a published port the container IP is used, and
+// where that isn't routable (Docker Desktop) the port is reached from inside the
+// container with exec. The traffic is logged with the shared protocol decoders.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::docker::{self, Docker, Selector, Via};
+use plugin_api::net;
+use plugin_api::traffic::{relay_to, Protocol};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DockerConfig {
+    /// Engine socket, e.g. "unix:///run/user/1000/podman/podman.sock"
+    pub socket: Option<String>,
+    #[serde(default)]
+    pub forward: Vec<DockerForward>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DockerForward {
+    pub name: String,
+    pub container: Option<String>,
+    /// Label selector, e.g. "com.docker.compose.service=api"
+    pub label: Option<String>,
+    #[serde(default)]
+    pub container_port: u16,
+    /// Unix socket in the container to forward to instead of container_port, reached
+    /// with exec (needs socat or nc in the image)
+    pub container_socket: Option<String>,
+    #[serde(default)]
+    pub local_port: u16,
+    /// Address to listen on: 127.0.0.1 (default), ::1, :: for IPv4 and IPv6, or the
+    /// path of a Unix socket instead of local_port
+    pub bind: Option<String>,
+    /// Permissions of the Unix socket listened on, e.g. 0o660 (default: umask)
+    pub socket_mode: Option<u32>,
+    pub via: Option<Via>,
+    /// Message decoding for the traffic log: tcp (default), http, postgres
+    pub protocol: Option<String>,
+    /// Print the forwarded traffic (default true)
+    pub log_traffic: Option<bool>,
+}
+
+impl DockerForward {
+    fn selector(&self) -> Result<Selector> {
+        match (&self.container, &self.label) {
+            (Some(name), _) => Ok(Selector::Name(name.clone())),
+            (None, Some(label)) => Ok(Selector::Labels(vec![label.clone()])),
+            (None, None) => Err(anyhow!("Must specify either container or label")),
+        }
+    }
+}
+
+pub struct DockerForwardPlugin;
+
+impl DockerForwardPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Docker Forward Configuration
+# socket = "unix:///run/user/1000/podman/podman.sock"  # default: DOCKER_HOST, Docker, Podman
+
+[[forward]]
+name = "api"
+container = "shop-api-1"
+container_port = 8080
+local_port = 8080
+protocol = "http"  # Options: tcp, http, postgres
+
+[[forward]]
+name = "db"
+label = "com.docker.compose.service=db"
+container_port = 5432
+local_port = 15432
+protocol = "postgres"
+# bind = "::1"  # default 127.0.0.1; "::" listens on every interface, IPv4 and IPv6
+# via = "exec"  # Options: auto, published, ip, exec
+# log_traffic = false
+
+[[forward]]
+name = "builder-docker"
+container = "ci-builder"
+container_socket = "/var/run/docker.sock"
+bind = "/tmp/ci-builder-docker.sock"  # a Unix socket instead of local_port
+# socket_mode = 0o600
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<DockerConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: DockerConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!(
+                    "📝 Sample config:\n{}",
+                    DockerForwardPlugin::sample_config()
+                );
+                Ok(DockerConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(DockerConfig::default())
+        }
+    }
+}
+
+async fn run_forward(
+    docker: Docker,
+    fwd: DockerForward,
+    protocol_override: Option<&str>,
+) -> Result<()> {
+    let selector = Arc::new(fwd.selector()?);
+    let bind = fwd.bind.as_deref().unwrap_or(net::LOOPBACK);
+    if net::unix_path(bind).is_none() && fwd.local_port == 0 {
+        return Err(anyhow!(
+            "Must specify local_port unless bind is a Unix socket"
+        ));
+    }
+    if fwd.container_socket.is_none() && fwd.container_port == 0 {
+        return Err(anyhow!(
+            "Must specify either container_port or container_socket"
+        ));
+    }
+    let listener = net::bind(bind, fwd.local_port, fwd.socket_mode).await?;
+    let protocol = fwd.log_traffic.unwrap_or(true).then(|| {
+        Protocol::from(
+            protocol_override
+                .or(fwd.protocol.as_deref())
+                .unwrap_or("tcp"),
+        )
+    });
+    let protocol = Arc::new(protocol);
+    let remote = match &fwd.container_socket {
+        Some(path) => path.clone(),
+        None => fwd.container_port.to_string(),
+    };
+    println!(
+        "🎧 [{}] {} → {}:{}",
+        fwd.name,
+        net::listen_address(bind, fwd.local_port),
+        selector,
+        remote
+    );
+    let fwd = Arc::new(fwd);
+
+    loop {
+        let (client, addr) = listener.accept().await?;
+        let docker = docker.clone();
+        let fwd = fwd.clone();
+        let selector = selector.clone();
+        let protocol = protocol.clone();
+        tokio::spawn(async move {
+            let opened = match &fwd.container_socket {
+                Some(path) => docker::open_socket(&docker, &selector, path).await,
+                None => {
+                    let via = fwd.via.unwrap_or_default();
+                    docker::open(&docker, &selector, fwd.container_port, via).await
+                }
+            };
+            match opened {
+                Ok((container, connection)) => {
+                    println!(
+                        "📞 [{}] New connection from {} → {} ({})",
+                        fwd.name, addr, container, connection.how
+                    );
+                    relay_to(
+                        &fwd.name,
+                        client,
+                        connection.reader,
+                        connection.writer,
+                        protocol.as_ref().as_ref(),
+                    )
+                    .await;
+                    println!("🔌 [{}] Connection from {} closed", fwd.name, addr);
+                }
+                Err(e) => eprintln!("❌ [{}] {}", fwd.name, e),
+            }
+        });
+    }
+}
+
+impl Plugin for DockerForwardPlugin {
+    fn name(&self) -> &'static str {
+        "docker_forward"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Port forwarding to Docker and Podman containers with protocol-aware traffic logging"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Port-forward to containers as defined in config file")
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .short('n')
+                    .value_name("NAME")
+                    .help("Only start the forward with this name"),
+            )
+            .arg(
+                Arg::new("socket")
+                    .long("socket")
+                    .value_name("SOCKET")
+                    .help("Docker or Podman socket to use"),
+            )
+            .arg(
+                Arg::new("protocol")
+                    .long("protocol")
+                    .value_name("PROTOCOL")
+                    .help("Protocol for message decoding: tcp, http, postgres")
+                    .value_parser(["tcp", "http", "postgres"]),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(DockerForwardPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<DockerConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        plugin_api::metrics::init(self.name());
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let forwards: Vec<DockerForward> = match matches.get_one::<String>("name") {
+                Some(name) => config
+                    .forward
+                    .into_iter()
+                    .filter(|fwd| &fwd.name == name)
+                    .collect(),
+                None => config.forward,
+            };
+            if forwards.is_empty() {
+                match matches.get_one::<String>("name") {
+                    Some(name) => eprintln!("❌ No forward found with name: {}", name),
+                    None => eprintln!("❌ No forwards found in config file"),
+                }
+                std::process::exit(1);
+            }
+
+            let socket = matches
+                .get_one::<String>("socket")
+                .or(config.socket.as_ref());
+            let docker = match docker::connect(socket.map(String::as_str)) {
+                Ok(docker) => docker,
+                Err(e) => {
+                    eprintln!("❌ {:#}", e);
+                    eprintln!(
+                        "💡 Is Docker or Podman running? Set `socket` in the config to pick one"
+                    );
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🚀 Starting {} container forward(s)", forwards.len());
+            let protocol = matches.get_one::<String>("protocol").cloned();
+            let handles: Vec<_> = forwards
+                .into_iter()
+                .map(|fwd| {
+                    let docker = docker.clone();
+                    let protocol = protocol.clone();
+                    tokio::spawn(async move {
+                        let name = fwd.name.clone();
+                        if let Err(e) = run_forward(docker, fwd, protocol.as_deref()).await {
+                            eprintln!("❌ [{}] {}", name, e);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(DockerForwardPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
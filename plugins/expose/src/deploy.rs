@@ -0,0 +1,143 @@
+// The relay's Deployment and Service. Both are created by server-side apply under the
+// plugin's field manager and labelled as managed by it, so an existing object of the
+// same name that someone else owns is never taken over or deleted.
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::core::v1::{Pod, Service};
+use kube::api::{Api, DeleteParams, ListParams, Patch, PatchParams};
+use kube::Client;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+const FIELD_MANAGER: &str = "proxy-expose";
+const MANAGED_BY: &str = "app.kubernetes.io/managed-by";
+const NAME_LABEL: &str = "app.kubernetes.io/name";
+/// Where the agent listens in the pod; the Service maps its port to this one
+pub const RELAY_PORT: u16 = 8080;
+/// Image pulls on a cold node can take a while
+const POD_STARTUP: Duration = Duration::from_secs(180);
+
+pub fn selector(name: &str) -> String {
+    format!("{}={},{}={}", NAME_LABEL, name, MANAGED_BY, FIELD_MANAGER)
+}
+
+/// Fails when a Service of this name exists and wasn't created by the plugin
+async fn check_owner(services: &Api<Service>, name: &str) -> Result<()> {
+    if let Some(service) = services.get_opt(name).await? {
+        let labels = service.metadata.labels.unwrap_or_default();
+        if labels.get(MANAGED_BY).map(String::as_str) != Some(FIELD_MANAGER) {
+            return Err(anyhow!(
+                "Service {} already exists and isn't managed by expose; pick another --name",
+                name
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub async fn apply(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+    port: u16,
+    image: &str,
+) -> Result<()> {
+    let services: Api<Service> = Api::namespaced(client.clone(), namespace);
+    let deployments: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    check_owner(&services, name).await?;
+
+    let labels = json!({ NAME_LABEL: name, MANAGED_BY: FIELD_MANAGER });
+    let deployment = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": name, "labels": labels },
+        "spec": {
+            "replicas": 1,
+            "selector": { "matchLabels": labels },
+            "template": {
+                "metadata": {
+                    "labels": labels,
+                    // A sidecar would take the relay port's traffic first
+                    "annotations": { "sidecar.istio.io/inject": "false" }
+                },
+                "spec": {
+                    "terminationGracePeriodSeconds": 1,
+                    "containers": [{
+                        "name": "relay",
+                        "image": image,
+                        // Idles until the agent is started with exec
+                        "command": ["python3", "-c", "import time\nwhile True: time.sleep(3600)"],
+                        "ports": [{ "name": "relay", "containerPort": RELAY_PORT }],
+                        "resources": {
+                            "requests": { "cpu": "10m", "memory": "32Mi" },
+                            "limits": { "memory": "128Mi" }
+                        },
+                        "securityContext": {
+                            "runAsNonRoot": true,
+                            "runAsUser": 65534,
+                            "allowPrivilegeEscalation": false,
+                            "capabilities": { "drop": ["ALL"] },
+                            "seccompProfile": { "type": "RuntimeDefault" }
+                        }
+                    }]
+                }
+            }
+        }
+    });
+    let service = json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": { "name": name, "labels": labels },
+        "spec": {
+            "selector": labels,
+            "ports": [{ "name": "relay", "port": port, "targetPort": "relay" }]
+        }
+    });
+    let params = PatchParams::apply(FIELD_MANAGER).force();
+    deployments
+        .patch(name, &params, &Patch::Apply(&deployment))
+        .await?;
+    services
+        .patch(name, &params, &Patch::Apply(&service))
+        .await?;
+    Ok(())
+}
+
+/// The relay pod once it runs
+pub async fn wait_for_pod(pods: &Api<Pod>, name: &str) -> Result<String> {
+    let selector = selector(name);
+    let started = Instant::now();
+    loop {
+        let list = pods.list(&ListParams::default().labels(&selector)).await?;
+        let running = list.items.into_iter().find(|pod| {
+            pod.metadata.deletion_timestamp.is_none()
+                && pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running")
+        });
+        if let Some(pod) = running {
+            return Ok(pod.metadata.name.unwrap_or_default());
+        }
+        if started.elapsed() > POD_STARTUP {
+            return Err(anyhow!(
+                "The relay pod isn't running after {}s; see kubectl describe pods -l {}",
+                POD_STARTUP.as_secs(),
+                selector
+            ));
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Deletes the relay's Deployment and Service, if they are still there
+pub async fn remove(client: &Client, namespace: &str, name: &str) -> Result<()> {
+    let services: Api<Service> = Api::namespaced(client.clone(), namespace);
+    let deployments: Api<Deployment> = Api::namespaced(client.clone(), namespace);
+    check_owner(&services, name).await?;
+    let params = DeleteParams::background();
+    if deployments.get_opt(name).await?.is_some() {
+        deployments.delete(name, &params).await?;
+    }
+    if services.get_opt(name).await?.is_some() {
+        services.delete(name, &params).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+// The reverse of a port forward: a local port made reachable inside the cluster, so
+// in-cluster services and webhooks can call code running on this machine. The plugin
+// deploys a small relay (Deployment + Service) into the namespace and carries the relay's
+// connections back over an exec session, which needs no inbound access to the laptop.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, AttachParams};
+use kube::Client;
+use plugin_api::traffic::Protocol;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+mod deploy;
+mod relay;
+
+const DEFAULT_IMAGE: &str = "python:3.12-alpine";
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A session that stayed up this long starts the backoff over
+const STABLE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExposeConfig {
+    /// Namespace (default: the kubeconfig context's)
+    pub namespace: Option<String>,
+    /// Relay image; it needs python3 (default python:3.12-alpine)
+    pub image: Option<String>,
+    /// Protocol to log the exposed traffic as: tcp, http, postgres or mysql
+    pub protocol: Option<String>,
+}
+
+pub struct ExposePlugin;
+
+impl ExposePlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Expose Configuration
+namespace = "shop"
+image = "python:3.12-alpine"  # any image with python3
+protocol = "http"             # log the exposed traffic
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<ExposeConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: ExposeConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Everything can be given on the command line
+                Ok(ExposeConfig::default())
+            }
+        }
+        None => Ok(ExposeConfig::default()),
+    }
+}
+
+/// One exec session running the agent, relayed until it ends
+async fn session(
+    pods: &Api<Pod>,
+    namespace: &str,
+    name: &str,
+    local_port: u16,
+    protocol: Option<Protocol>,
+) -> Result<()> {
+    let pod = deploy::wait_for_pod(pods, name).await?;
+    let port = deploy::RELAY_PORT.to_string();
+    plugin_api::audit::record_k8s(
+        "expose",
+        "exec_transport",
+        &[
+            ("namespace", namespace),
+            ("pod", &pod),
+            ("container", "relay"),
+            ("local_port", &local_port.to_string()),
+        ],
+    );
+    let params = AttachParams::default()
+        .container("relay")
+        .stdin(true)
+        .stdout(true)
+        .stderr(false);
+    let mut process = pods
+        .exec(
+            &pod,
+            ["python3", "-u", "-c", relay::AGENT, port.as_str()],
+            &params,
+        )
+        .await?;
+    let (Some(stdin), Some(stdout)) = (process.stdin(), process.stdout()) else {
+        return Err(anyhow!("The exec session has no stdin or stdout"));
+    };
+    println!("🔗 [{}] Relay agent running in {}", name, pod);
+    relay::serve(stdout, stdin, local_port, name, protocol).await
+}
+
+/// Keeps the relay session up, starting the agent again when it ends
+async fn supervise(
+    pods: Api<Pod>,
+    namespace: String,
+    name: String,
+    local_port: u16,
+    protocol: Option<Protocol>,
+) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        if let Err(e) = session(&pods, &namespace, &name, local_port, protocol.clone()).await {
+            eprintln!("❌ [{}] {}", name, e);
+        }
+        if started.elapsed() >= STABLE {
+            backoff = MIN_BACKOFF;
+        }
+        println!("🔄 [{}] Reconnecting in {}s", name, backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+struct Exposure {
+    local_port: u16,
+    name: String,
+    port: u16,
+    keep: bool,
+}
+
+async fn start(config: ExposeConfig, exposure: Exposure) -> Result<()> {
+    let client = Client::try_default().await?;
+    let namespace = config
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+    let image = config.image.as_deref().unwrap_or(DEFAULT_IMAGE);
+    let protocol = config.protocol.as_deref().map(Protocol::from);
+    let name = exposure.name.clone();
+
+    println!("🚀 Deploying relay {} to {}", name, namespace);
+    deploy::apply(&client, &namespace, &name, exposure.port, image).await?;
+
+    let (stop, mut stopped) = tokio::sync::mpsc::unbounded_channel();
+    ctrlc::set_handler(move || {
+        let _ = stop.send(());
+    })?;
+
+    println!(
+        "✅ In-cluster: {}.{}.svc.cluster.local:{} → localhost:{}",
+        name, namespace, exposure.port, exposure.local_port
+    );
+    let pods: Api<Pod> = Api::namespaced(client.clone(), &namespace);
+    tokio::select! {
+        _ = supervise(pods, namespace.clone(), name.clone(), exposure.local_port, protocol) => {}
+        _ = stopped.recv() => println!("\n👋 Shutting down..."),
+    }
+
+    if exposure.keep {
+        println!(
+            "📌 Keeping {} in {}; run again to reconnect",
+            name, namespace
+        );
+    } else {
+        println!("🧹 Removing {} from {}", name, namespace);
+        deploy::remove(&client, &namespace, &name).await?;
+    }
+    Ok(())
+}
+
+impl Plugin for ExposePlugin {
+    fn name(&self) -> &'static str {
+        "expose"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Make a local port reachable inside a Kubernetes namespace"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Deploy a relay into a namespace and tunnel its traffic back to a local port")
+            .arg(
+                Arg::new("local-port")
+                    .value_name("LOCAL_PORT")
+                    .required(true)
+                    .value_parser(clap::value_parser!(u16))
+                    .help("Local port to expose"),
+            )
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .value_name("NAME")
+                    .help("Name of the relay's Deployment and Service (default expose-LOCAL_PORT)"),
+            )
+            .arg(
+                Arg::new("namespace")
+                    .long("namespace")
+                    .short('n')
+                    .value_name("NAMESPACE")
+                    .help("Override namespace from config file"),
+            )
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .short('p')
+                    .value_name("PORT")
+                    .value_parser(clap::value_parser!(u16))
+                    .help("Service port in the cluster (default: LOCAL_PORT)"),
+            )
+            .arg(
+                Arg::new("image")
+                    .long("image")
+                    .value_name("IMAGE")
+                    .help("Relay image with python3 (default python:3.12-alpine)"),
+            )
+            .arg(
+                Arg::new("protocol")
+                    .long("protocol")
+                    .value_name("PROTOCOL")
+                    .help("Log the exposed traffic as tcp, http, postgres or mysql"),
+            )
+            .arg(
+                Arg::new("keep")
+                    .long("keep")
+                    .action(ArgAction::SetTrue)
+                    .help("Leave the relay deployed on exit"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(ExposePlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<ExposeConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let mut config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if let Some(namespace) = matches.get_one::<String>("namespace") {
+            config.namespace = Some(namespace.clone());
+        }
+        if let Some(image) = matches.get_one::<String>("image") {
+            config.image = Some(image.clone());
+        }
+        if let Some(protocol) = matches.get_one::<String>("protocol") {
+            config.protocol = Some(protocol.clone());
+        }
+
+        let local_port = *matches
+            .get_one::<u16>("local-port")
+            .expect("LOCAL_PORT is required");
+        let exposure = Exposure {
+            local_port,
+            name: matches
+                .get_one::<String>("name")
+                .cloned()
+                .unwrap_or_else(|| format!("expose-{}", local_port)),
+            port: matches
+                .get_one::<u16>("port")
+                .copied()
+                .unwrap_or(local_port),
+            keep: matches.get_flag("keep"),
+        };
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        if let Err(e) = rt.block_on(start(config, exposure)) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(ExposePlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,227 @@
+// The tunnel between the relay pod and the local port. An agent started in the pod with
+// exec listens on the relay port and multiplexes its connections over the exec session's
+// stdin and stdout. Each frame is a connection id (u32), a kind (u8) and a payload length
+// (u32), big-endian, then the payload. OPEN announces a connection with its peer address,
+// DATA carries bytes, and CLOSE says that side is done sending.
+use anyhow::{anyhow, Result};
+use plugin_api::traffic::{log_message, Protocol};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+const OPEN: u8 = 1;
+const DATA: u8 = 2;
+const CLOSE: u8 = 3;
+
+/// Run in the pod as `python3 -u -c AGENT PORT`. It exits when stdin closes, that is when
+/// the exec session ends.
+pub const AGENT: &str = r#"
+import os, socket, struct, sys, threading
+port = int(sys.argv[1])
+out, inp = sys.stdout.buffer, sys.stdin.buffer
+lock = threading.Lock()
+conns, done = {}, {}
+
+def send(i, kind, data=b''):
+    with lock:
+        out.write(struct.pack('>IBI', i, kind, len(data)) + data)
+        out.flush()
+
+def finish(i):
+    with lock:
+        done[i] = done.get(i, 0) + 1
+        last = done[i] == 2
+    if last:
+        done.pop(i, None)
+        c = conns.pop(i, None)
+        if c:
+            c.close()
+
+def pump(i, c):
+    try:
+        while True:
+            data = c.recv(65536)
+            if not data:
+                break
+            send(i, 2, data)
+    except OSError:
+        pass
+    send(i, 3)
+    finish(i)
+
+def accept():
+    s = socket.socket()
+    s.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+    s.bind(('0.0.0.0', port))
+    s.listen(64)
+    n = 0
+    while True:
+        c, addr = s.accept()
+        n += 1
+        conns[n] = c
+        send(n, 1, ('%s:%d' % addr[:2]).encode())
+        threading.Thread(target=pump, args=(n, c), daemon=True).start()
+
+def read(n):
+    data = b''
+    while len(data) < n:
+        chunk = inp.read(n - len(data))
+        if not chunk:
+            os._exit(0)
+        data += chunk
+    return data
+
+threading.Thread(target=accept, daemon=True).start()
+while True:
+    i, kind, length = struct.unpack('>IBI', read(9))
+    data = read(length) if length else b''
+    c = conns.get(i)
+    if c is None:
+        continue
+    try:
+        if kind == 2:
+            c.sendall(data)
+        elif kind == 3:
+            c.shutdown(socket.SHUT_WR)
+    except OSError:
+        pass
+    if kind == 3:
+        finish(i)
+"#;
+
+fn frame(id: u32, kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(9 + data.len());
+    frame.extend_from_slice(&id.to_be_bytes());
+    frame.push(kind);
+    frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// Relays one connection from the pod to the local port; `incoming` delivers what the
+/// pod side sends, None once it is done sending
+async fn connect(
+    id: u32,
+    local_port: u16,
+    mut incoming: tokio::sync::mpsc::UnboundedReceiver<Option<Vec<u8>>>,
+    frames: UnboundedSender<Vec<u8>>,
+    tag: Arc<String>,
+    protocol: Arc<Option<Protocol>>,
+) {
+    let local = match TcpStream::connect(("127.0.0.1", local_port)).await {
+        Ok(local) => local,
+        Err(e) => {
+            eprintln!(
+                "❌ [{}] Nothing listening on localhost:{}: {}",
+                tag, local_port, e
+            );
+            let _ = frames.send(frame(id, CLOSE, &[]));
+            return;
+        }
+    };
+    let (mut reader, mut writer) = local.into_split();
+    let request = format!("[{}] → REQUEST", tag);
+    let response = format!("[{}] ← RESPONSE", tag);
+
+    let to_local = async {
+        while let Some(Some(data)) = incoming.recv().await {
+            if let Some(protocol) = protocol.as_ref() {
+                log_message(&request, protocol, &data);
+            }
+            if writer.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown().await;
+    };
+    let to_pod = async {
+        let mut buffer = vec![0u8; 16 * 1024];
+        loop {
+            match reader.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if let Some(protocol) = protocol.as_ref() {
+                        log_message(&response, protocol, &buffer[..n]);
+                    }
+                    let _ = frames.send(frame(id, DATA, &buffer[..n]));
+                }
+            }
+        }
+        let _ = frames.send(frame(id, CLOSE, &[]));
+    };
+    tokio::join!(to_local, to_pod);
+}
+
+/// Serves the agent's connections until the exec session ends
+pub async fn serve<R, W>(
+    mut from_pod: R,
+    mut to_pod: W,
+    local_port: u16,
+    tag: &str,
+    protocol: Option<Protocol>,
+) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (frames, mut outgoing) = unbounded_channel::<Vec<u8>>();
+    let writer = tokio::spawn(async move {
+        while let Some(frame) = outgoing.recv().await {
+            if to_pod.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+        // Closing stdin would end the exec session; it ends with the reader instead
+        std::future::pending::<()>().await;
+        drop(to_pod);
+    });
+    let tag = Arc::new(tag.to_string());
+    let protocol = Arc::new(protocol);
+    let mut connections: HashMap<u32, UnboundedSender<Option<Vec<u8>>>> = HashMap::new();
+
+    let result = loop {
+        let mut header = [0u8; 9];
+        if let Err(e) = from_pod.read_exact(&mut header).await {
+            break Err(anyhow!("The relay session ended: {}", e));
+        }
+        let id = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let length = u32::from_be_bytes([header[5], header[6], header[7], header[8]]) as usize;
+        let mut data = vec![0u8; length];
+        if let Err(e) = from_pod.read_exact(&mut data).await {
+            break Err(anyhow!("The relay session ended: {}", e));
+        }
+        match header[4] {
+            OPEN => {
+                println!(
+                    "📞 [{}] New connection from {}",
+                    tag,
+                    String::from_utf8_lossy(&data)
+                );
+                let (sender, receiver) = unbounded_channel();
+                connections.insert(id, sender);
+                let frames = frames.clone();
+                let tag = tag.clone();
+                let protocol = protocol.clone();
+                tokio::spawn(async move {
+                    connect(id, local_port, receiver, frames, tag.clone(), protocol).await;
+                    println!("🔌 [{}] Connection {} closed", tag, id);
+                });
+            }
+            DATA => {
+                if let Some(sender) = connections.get(&id) {
+                    let _ = sender.send(Some(data));
+                }
+            }
+            CLOSE => {
+                if let Some(sender) = connections.remove(&id) {
+                    let _ = sender.send(None);
+                }
+            }
+            kind => break Err(anyhow!("Unknown frame kind {} from the relay", kind)),
+        }
+    };
+    writer.abort();
+    result
+}
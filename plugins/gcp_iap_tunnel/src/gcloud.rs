@@ -0,0 +1,70 @@
+// gcloud calls. `gcloud compute start-iap-tunnel` does the IAP part; with auth = "adc"
+// it is pointed at the Application Default Credentials file, so tunnels run as the
+// same identity as client libraries instead of whoever ran `gcloud auth login`.
+use crate::IapTunnel;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::process::Command;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Auth {
+    #[default]
+    Adc,
+    Gcloud,
+}
+
+/// Where Application Default Credentials are read from, if they exist
+pub fn adc_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS") {
+        return Some(PathBuf::from(path));
+    }
+    let dir = if cfg!(windows) {
+        dirs::config_dir()?.join("gcloud")
+    } else {
+        dirs::home_dir()?.join(".config/gcloud")
+    };
+    Some(dir.join("application_default_credentials.json")).filter(|path| path.exists())
+}
+
+/// The tunnel, listening on 127.0.0.1:`listen_port`
+pub fn start_tunnel(
+    tunnel: &IapTunnel,
+    project: Option<&str>,
+    credentials: Option<&PathBuf>,
+    listen_port: u16,
+) -> Command {
+    let mut command = Command::new("gcloud");
+    command.arg("compute").arg("start-iap-tunnel");
+    match (&tunnel.instance, &tunnel.host) {
+        (Some(instance), _) => {
+            command.arg(instance).arg(tunnel.remote_port.to_string());
+            if let Some(zone) = &tunnel.zone {
+                command.arg(format!("--zone={}", zone));
+            }
+        }
+        (None, Some(host)) => {
+            command.arg(host).arg(tunnel.remote_port.to_string());
+            for (flag, value) in [
+                ("region", &tunnel.region),
+                ("network", &tunnel.network),
+                ("dest-group", &tunnel.dest_group),
+            ] {
+                if let Some(value) = value {
+                    command.arg(format!("--{}={}", flag, value));
+                }
+            }
+        }
+        (None, None) => {}
+    }
+    command.arg(format!("--local-host-port=127.0.0.1:{}", listen_port));
+    if let Some(project) = project {
+        command.arg(format!("--project={}", project));
+    }
+    if let Some(credentials) = credentials {
+        command.env("CLOUDSDK_AUTH_CREDENTIAL_FILE_OVERRIDE", credentials);
+    }
+    command.stdin(Stdio::null()).kill_on_drop(true);
+    command
+}
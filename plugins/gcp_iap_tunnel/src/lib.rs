@@ -0,0 +1,452 @@
+// Identity-Aware Proxy TCP tunnels to GCE VMs, or to internal hosts through an IAP
+// destination group. gcloud runs each tunnel on a private loopback port and is started
+// again when it exits; the configured local port is served here and relayed to it, so
+// the traffic is logged like the other forwarding plugins do. When the credentials are
+// renewed gcloud is started again with them, the old process serving the connections it
+// already has until they close.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::credentials::{self, Credentials};
+use plugin_api::net;
+use plugin_api::traffic::{relay_to, Protocol};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::process::Child;
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+
+mod gcloud;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A tunnel that lasted this long starts the backoff over
+const STABLE: Duration = Duration::from_secs(60);
+/// How long a tunnel started with renewed credentials gets to come up before it is used
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct IapConfig {
+    /// Default project for all tunnels; gcloud's configured project otherwise
+    pub project: Option<String>,
+    /// Credentials: adc (default, falls back to the gcloud login) or gcloud
+    #[serde(default)]
+    pub auth: gcloud::Auth,
+    /// Renews expired credentials; without it the gcloud login flow runs when there is a
+    /// terminal
+    pub refresh_command: Option<String>,
+    #[serde(default)]
+    pub tunnel: Vec<IapTunnel>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IapTunnel {
+    pub name: String,
+    /// VM name, with `zone`
+    pub instance: Option<String>,
+    pub zone: Option<String>,
+    /// Internal IP or host name, with `region`, `network` and `dest_group`
+    pub host: Option<String>,
+    pub region: Option<String>,
+    pub network: Option<String>,
+    pub dest_group: Option<String>,
+    pub project: Option<String>,
+    pub local_port: u16,
+    pub remote_port: u16,
+    /// Address to listen on: 127.0.0.1 (default), ::1, or :: for IPv4 and IPv6
+    pub bind: Option<String>,
+    /// Message decoding for the traffic log: tcp (default), http, postgres
+    pub protocol: Option<String>,
+    /// Print the forwarded traffic (default true)
+    pub log_traffic: Option<bool>,
+}
+
+impl IapTunnel {
+    fn validate(&self) -> Result<()> {
+        match (&self.instance, &self.host) {
+            (Some(_), None) if self.zone.is_some() => Ok(()),
+            (Some(_), None) => Err(anyhow!("Tunnel '{}' needs a zone", self.name)),
+            (None, Some(_))
+                if self.region.is_some() && self.network.is_some() && self.dest_group.is_some() =>
+            {
+                Ok(())
+            }
+            (None, Some(_)) => Err(anyhow!(
+                "Tunnel '{}' needs region, network and dest_group to reach a host",
+                self.name
+            )),
+            _ => Err(anyhow!(
+                "Tunnel '{}' needs either instance or host",
+                self.name
+            )),
+        }
+    }
+
+    fn target_desc(&self) -> String {
+        match (&self.instance, &self.host) {
+            (Some(instance), _) => format!(
+                "{}:{} ({})",
+                instance,
+                self.remote_port,
+                self.zone.as_deref().unwrap_or("?")
+            ),
+            (None, Some(host)) => format!(
+                "{} ({})",
+                net::host_port(host, self.remote_port),
+                self.dest_group.as_deref().unwrap_or("?")
+            ),
+            (None, None) => "invalid-config".to_string(),
+        }
+    }
+}
+
+pub struct GcpIapTunnelPlugin;
+
+impl GcpIapTunnelPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# GCP IAP Tunnel Configuration
+project = "my-project"
+auth = "adc"  # Options: adc (Application Default Credentials), gcloud
+# refresh_command = "gcloud auth application-default login --no-launch-browser"
+
+[[tunnel]]
+name = "bastion-ssh"
+instance = "bastion-1"
+zone = "europe-west1-b"
+local_port = 2222
+remote_port = 22
+
+# Internal host reached through an IAP destination group
+[[tunnel]]
+name = "orders-db"
+host = "10.20.0.5"
+region = "europe-west1"
+network = "prod-vpc"
+dest_group = "databases"
+local_port = 5432
+remote_port = 5432
+# bind = "::1"  # default 127.0.0.1; "::" listens on every interface, IPv4 and IPv6
+protocol = "postgres"  # Options: tcp, http, postgres
+# log_traffic = false
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<IapConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: IapConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", GcpIapTunnelPlugin::sample_config());
+                Ok(IapConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(IapConfig::default())
+        }
+    }
+}
+
+/// A free loopback port for gcloud to listen on
+fn private_port() -> std::io::Result<u16> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// The gcloud process new connections go to. Connections hold on to the one they went
+/// through; once the last holder is gone, a process replaced after a renewal is killed.
+struct Upstream {
+    port: u16,
+    _retired: oneshot::Sender<()>,
+}
+
+/// Waits until gcloud accepts connections on `port`, or has exited
+async fn wait_listening(gcloud: &mut Child, port: u16) {
+    let deadline = Instant::now() + READY_TIMEOUT;
+    while Instant::now() < deadline && matches!(gcloud.try_wait(), Ok(None)) {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+/// Keeps gcloud running, on a new port each time, and publishes it as the upstream
+async fn supervise(
+    tunnel: IapTunnel,
+    project: Option<String>,
+    credentials: Option<PathBuf>,
+    watched: Arc<Credentials>,
+    upstream: Arc<Mutex<Option<Arc<Upstream>>>>,
+) {
+    let mut renewed = watched.subscribe();
+    let mut backoff = MIN_BACKOFF;
+    let mut renewing = false;
+    loop {
+        let started = Instant::now();
+        let spawned = private_port().and_then(|port| {
+            let gcloud =
+                gcloud::start_tunnel(&tunnel, project.as_deref(), credentials.as_ref(), port)
+                    .spawn()?;
+            Ok((port, gcloud))
+        });
+        let status = match spawned {
+            Ok((port, mut gcloud)) => {
+                // The old process keeps taking connections until this one is up
+                if renewing {
+                    wait_listening(&mut gcloud, port).await;
+                }
+                let (retired, retirement) = oneshot::channel();
+                *upstream.lock().unwrap() = Some(Arc::new(Upstream {
+                    port,
+                    _retired: retired,
+                }));
+                tokio::select! {
+                    status = gcloud.wait() => status,
+                    _ = renewed.changed() => {
+                        println!(
+                            "🔑 [{}] Restarting the tunnel with the renewed credentials",
+                            tunnel.name
+                        );
+                        tokio::spawn(async move {
+                            tokio::select! {
+                                _ = retirement => {}
+                                _ = gcloud.wait() => {}
+                            }
+                        });
+                        renewing = true;
+                        continue;
+                    }
+                }
+            }
+            Err(e) => Err(e),
+        };
+        renewing = false;
+        match status {
+            Ok(status) => println!("⚠️  [{}] Tunnel ended ({})", tunnel.name, status),
+            Err(e) => eprintln!("❌ [{}] Failed to run gcloud: {}", tunnel.name, e),
+        }
+        if started.elapsed() >= STABLE {
+            backoff = MIN_BACKOFF;
+        }
+        println!(
+            "🔄 [{}] Reconnecting in {}s",
+            tunnel.name,
+            backoff.as_secs()
+        );
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn start_tunnel(
+    tunnel: IapTunnel,
+    project: Option<String>,
+    credentials: Option<PathBuf>,
+    watched: Arc<Credentials>,
+    protocol_override: Option<&str>,
+) -> Result<()> {
+    tunnel.validate()?;
+    let bind = tunnel
+        .bind
+        .clone()
+        .unwrap_or_else(|| net::LOOPBACK.to_string());
+    let listener = net::listen(&bind, tunnel.local_port).await?;
+    let protocol = tunnel.log_traffic.unwrap_or(true).then(|| {
+        Protocol::from(
+            protocol_override
+                .or(tunnel.protocol.as_deref())
+                .unwrap_or("tcp"),
+        )
+    });
+    let protocol = Arc::new(protocol);
+    println!(
+        "🎧 [{}] {} → {}",
+        tunnel.name,
+        net::host_port(&bind, tunnel.local_port),
+        tunnel.target_desc()
+    );
+
+    let name = tunnel.name.clone();
+    let upstream = Arc::new(Mutex::new(None));
+    tokio::spawn(supervise(
+        tunnel,
+        project,
+        credentials,
+        watched,
+        upstream.clone(),
+    ));
+
+    loop {
+        let (client, addr) = listener.accept().await?;
+        println!("📞 [{}] New connection from {}", name, addr);
+        let name = name.clone();
+        let protocol = protocol.clone();
+        let upstream = upstream.lock().unwrap().clone();
+        tokio::spawn(async move {
+            // Held until the connection closes
+            let Some(gcloud) = upstream else {
+                eprintln!("❌ [{}] Tunnel is not up yet", name);
+                return;
+            };
+            match TcpStream::connect(("127.0.0.1", gcloud.port)).await {
+                Ok(upstream) => {
+                    let (reader, writer) = upstream.into_split();
+                    relay_to(&name, client, reader, writer, protocol.as_ref().as_ref()).await;
+                    println!("🔌 [{}] Connection from {} closed", name, addr);
+                }
+                Err(e) => eprintln!("❌ [{}] Tunnel is not up yet: {}", name, e),
+            }
+        });
+    }
+}
+
+impl Plugin for GcpIapTunnelPlugin {
+    fn name(&self) -> &'static str {
+        "gcp_iap_tunnel"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "GCP Identity-Aware Proxy TCP tunnels with protocol-aware traffic logging"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("IAP TCP tunnels to GCE VMs and internal hosts as defined in config file")
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .short('n')
+                    .value_name("NAME")
+                    .help("Only start the tunnel with this name"),
+            )
+            .arg(
+                Arg::new("project")
+                    .long("project")
+                    .value_name("PROJECT")
+                    .help("Override the project for all tunnels"),
+            )
+            .arg(
+                Arg::new("protocol")
+                    .long("protocol")
+                    .value_name("PROTOCOL")
+                    .help("Protocol for message decoding: tcp, http, postgres")
+                    .value_parser(["tcp", "http", "postgres"]),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(GcpIapTunnelPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<IapConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        plugin_api::metrics::init(self.name());
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let tunnels: Vec<IapTunnel> = match matches.get_one::<String>("name") {
+                Some(name) => config
+                    .tunnel
+                    .into_iter()
+                    .filter(|tunnel| &tunnel.name == name)
+                    .collect(),
+                None => config.tunnel,
+            };
+            if tunnels.is_empty() {
+                eprintln!("❌ No tunnels to start");
+                eprintln!("💡 Add [[tunnel]] entries to the config file");
+                std::process::exit(1);
+            }
+
+            let credentials = match config.auth {
+                gcloud::Auth::Adc => {
+                    let path = gcloud::adc_path();
+                    match &path {
+                        Some(path) => println!("🔑 Using credentials from {}", path.display()),
+                        None => println!(
+                            "⚠️  No Application Default Credentials found, using the gcloud login"
+                        ),
+                    }
+                    path
+                }
+                gcloud::Auth::Gcloud => None,
+            };
+            let watched = credentials::watch(
+                credentials::Source::Gcp {
+                    adc: credentials.is_some(),
+                },
+                config.refresh_command.clone(),
+            );
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🚀 Starting {} IAP tunnel(s)", tunnels.len());
+            let protocol = matches.get_one::<String>("protocol").cloned();
+            let handles: Vec<_> = tunnels
+                .into_iter()
+                .map(|tunnel| {
+                    let project = matches
+                        .get_one::<String>("project")
+                        .or(tunnel.project.as_ref())
+                        .or(config.project.as_ref())
+                        .cloned();
+                    let credentials = credentials.clone();
+                    let watched = watched.clone();
+                    let protocol = protocol.clone();
+                    tokio::spawn(async move {
+                        let name = tunnel.name.clone();
+                        if let Err(e) =
+                            start_tunnel(tunnel, project, credentials, watched, protocol.as_deref())
+                                .await
+                        {
+                            eprintln!("❌ [{}] {}", name, e);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(GcpIapTunnelPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,217 @@
+// A gRPC-aware proxy: it sits in front of a gRPC backend (directly, or on the local port
+// of a k8s forward), forwards every call unchanged, and logs each request and response
+// message decoded with descriptors from server reflection (or a descriptor set file),
+// followed by the call's status and latency. Plaintext HTTP/2 (h2c) on both sides.
+use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::Plugin;
+use prost_reflect::DescriptorPool;
+use serde::Deserialize;
+use std::fs;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+mod proxy;
+mod reflection;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:50051";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GrpcProxyConfig {
+    /// Address to listen on (default 127.0.0.1:50051)
+    pub listen: Option<String>,
+    /// gRPC server, e.g. the local port of a k8s_port_forward forward
+    pub backend: Option<String>,
+    /// File from `protoc --descriptor_set_out --include_imports`, instead of reflection
+    pub descriptor_set: Option<String>,
+    /// Print decoded messages, not just call summaries (default true)
+    pub log_messages: Option<bool>,
+}
+
+pub struct GrpcProxyPlugin;
+
+impl GrpcProxyPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# gRPC Proxy Configuration
+listen = "127.0.0.1:50051"
+backend = "127.0.0.1:9090"  # e.g. a k8s_port_forward local port
+# descriptor_set = "/path/to/api.pb"  # when the server has no reflection
+# log_messages = false
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<GrpcProxyConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: GrpcProxyConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", GrpcProxyPlugin::sample_config());
+                Ok(GrpcProxyConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(GrpcProxyConfig::default())
+        }
+    }
+}
+
+impl Plugin for GrpcProxyPlugin {
+    fn name(&self) -> &'static str {
+        "grpc_proxy"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "gRPC proxy logging decoded messages using server reflection"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Proxy gRPC calls to a backend and log the decoded messages")
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .short('l')
+                    .value_name("ADDR")
+                    .help("Address to listen on (default 127.0.0.1:50051)"),
+            )
+            .arg(
+                Arg::new("backend")
+                    .long("backend")
+                    .short('b')
+                    .value_name("ADDR")
+                    .help("gRPC server to forward to, e.g. 127.0.0.1:9090"),
+            )
+            .arg(
+                Arg::new("descriptor-set")
+                    .long("descriptor-set")
+                    .value_name("FILE")
+                    .help("Descriptor set to decode with instead of server reflection"),
+            )
+            .arg(
+                Arg::new("quiet")
+                    .long("quiet")
+                    .short('q')
+                    .action(ArgAction::SetTrue)
+                    .help("Only print call summaries"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(GrpcProxyPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<GrpcProxyConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let Some(backend) = matches
+                .get_one::<String>("backend")
+                .or(config.backend.as_ref())
+                .cloned()
+            else {
+                eprintln!("❌ No backend configured");
+                eprintln!("💡 Set `backend` in the config file or pass --backend");
+                std::process::exit(1);
+            };
+            let listen = matches
+                .get_one::<String>("listen")
+                .or(config.listen.as_ref())
+                .cloned()
+                .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+
+            let pool = match matches
+                .get_one::<String>("descriptor-set")
+                .or(config.descriptor_set.as_ref())
+            {
+                Some(path) => {
+                    match fs::read(path)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|bytes| Ok(DescriptorPool::decode(bytes.as_slice())?))
+                    {
+                        Ok(pool) => Some(pool),
+                        Err(e) => {
+                            eprintln!("❌ Failed to load descriptor set {}: {}", path, e);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            let listener = match TcpListener::bind(&listen).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("❌ Failed to listen on {}: {}", listen, e);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            let log_messages = !matches.get_flag("quiet") && config.log_messages.unwrap_or(true);
+            println!("🚀 gRPC proxy {} → {}", listen, backend);
+            let proxy = Arc::new(proxy::Proxy::new(
+                proxy::Backend::new(backend),
+                pool,
+                log_messages,
+            ));
+            proxy.load_descriptors().await;
+
+            loop {
+                let (socket, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("❌ Accept failed: {}", e);
+                        continue;
+                    }
+                };
+                println!("📞 New connection from {}", addr);
+                let proxy = proxy.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = proxy.serve(socket).await {
+                        eprintln!("❌ [{}] {}", addr, e);
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(GrpcProxyPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
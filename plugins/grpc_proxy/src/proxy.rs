@@ -0,0 +1,389 @@
+// HTTP/2 proxying of gRPC calls. Each call is forwarded as its own stream to one shared
+// backend connection; the length-prefixed messages in both directions are decoded with
+// the method's descriptors as they pass, and a summary with the status and latency is
+// printed when the call ends.
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use h2::client::SendRequest;
+use h2::server::SendResponse;
+use h2::{RecvStream, SendStream};
+use http::{HeaderMap, Request, Response, Uri};
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::{Mutex, RwLock};
+
+/// gRPC message prefix: compressed flag and big-endian length
+const PREFIX_LEN: usize = 5;
+/// How often a failed reflection lookup is retried when calls come in
+const REFLECTION_RETRY: Duration = Duration::from_secs(10);
+const STATUS_UNAVAILABLE: &str = "14";
+
+const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+/// A message as framed on the wire
+pub fn frame(message: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(PREFIX_LEN + message.len());
+    framed.push(0);
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+    framed
+}
+
+pub struct Frame {
+    pub compressed: bool,
+    pub payload: Vec<u8>,
+}
+
+/// Reassembles messages from DATA frames, which split and join them arbitrarily
+#[derive(Default)]
+pub struct MessageFrames {
+    buffer: Vec<u8>,
+}
+
+impl MessageFrames {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Frame> {
+        self.buffer.extend_from_slice(chunk);
+        let mut frames = Vec::new();
+        while self.buffer.len() >= PREFIX_LEN {
+            let len = u32::from_be_bytes([
+                self.buffer[1],
+                self.buffer[2],
+                self.buffer[3],
+                self.buffer[4],
+            ]) as usize;
+            if self.buffer.len() < PREFIX_LEN + len {
+                break;
+            }
+            let rest = self.buffer.split_off(PREFIX_LEN + len);
+            let message = std::mem::replace(&mut self.buffer, rest);
+            frames.push(Frame {
+                compressed: message[0] == 1,
+                payload: message[PREFIX_LEN..].to_vec(),
+            });
+        }
+        frames
+    }
+}
+
+/// The gRPC server behind the proxy, over one HTTP/2 connection that is opened again
+/// when it breaks
+pub struct Backend {
+    addr: String,
+    sender: Mutex<Option<SendRequest<Bytes>>>,
+}
+
+impl Backend {
+    pub fn new(addr: String) -> Self {
+        Backend {
+            addr,
+            sender: Mutex::new(None),
+        }
+    }
+
+    async fn sender(&self) -> Result<SendRequest<Bytes>> {
+        let mut sender = self.sender.lock().await;
+        if let Some(existing) = sender.as_ref() {
+            if let Ok(ready) = existing.clone().ready().await {
+                return Ok(ready);
+            }
+        }
+        let tcp = TcpStream::connect(&self.addr).await?;
+        let (connected, connection) = h2::client::handshake(tcp).await?;
+        let addr = self.addr.clone();
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                eprintln!("⚠️  Connection to {} ended: {}", addr, e);
+            }
+        });
+        *sender = Some(connected.clone());
+        Ok(connected.ready().await?)
+    }
+
+    fn uri(&self, path: &str) -> Result<Uri> {
+        Ok(format!("http://{}{}", self.addr, path).parse()?)
+    }
+
+    /// A call with a single request body, for the proxy's own use (reflection)
+    pub async fn unary(
+        &self,
+        path: &str,
+        body: Bytes,
+    ) -> Result<(HeaderMap, Vec<u8>, Option<HeaderMap>)> {
+        let request = Request::post(self.uri(path)?)
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .body(())?;
+        let (response, mut stream) = self.sender().await?.send_request(request, false)?;
+        stream.send_data(body, true)?;
+        let (parts, mut body) = response.await?.into_parts();
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk?;
+            let _ = body.flow_control().release_capacity(chunk.len());
+            data.extend_from_slice(&chunk);
+        }
+        let trailers = body.trailers().await?;
+        Ok((parts.headers, data, trailers))
+    }
+}
+
+/// Prints the messages of one direction of a call
+struct MessageLog {
+    label: String,
+    descriptor: Option<MessageDescriptor>,
+    frames: MessageFrames,
+    enabled: bool,
+}
+
+impl MessageLog {
+    fn new(
+        path: &str,
+        direction: &str,
+        descriptor: Option<MessageDescriptor>,
+        enabled: bool,
+    ) -> Self {
+        MessageLog {
+            label: format!("[{}] {}", path, direction),
+            descriptor,
+            frames: MessageFrames::default(),
+            enabled,
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+        for frame in self.frames.push(chunk) {
+            let size = frame.payload.len();
+            match (&self.descriptor, frame.compressed) {
+                (_, true) => println!("📦 {} compressed message ({} bytes)", self.label, size),
+                (None, false) => {
+                    println!("📦 {} message ({} bytes, no descriptor)", self.label, size)
+                }
+                (Some(descriptor), false) => {
+                    println!("📦 {} {}", self.label, descriptor.full_name());
+                    match DynamicMessage::decode(descriptor.clone(), frame.payload.as_slice()) {
+                        Ok(message) => {
+                            let text = format!("{:#}", message);
+                            if text.is_empty() {
+                                println!("   (empty)");
+                            }
+                            for line in text.lines() {
+                                println!("   {}", line);
+                            }
+                        }
+                        Err(e) => println!("   (undecodable, {} bytes: {})", size, e),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Copies one direction of a stream and returns its trailers
+async fn pump(
+    mut from: RecvStream,
+    mut to: SendStream<Bytes>,
+    mut log: MessageLog,
+) -> Result<Option<HeaderMap>> {
+    while let Some(chunk) = from.data().await {
+        let chunk = chunk?;
+        let _ = from.flow_control().release_capacity(chunk.len());
+        log.push(&chunk);
+        to.send_data(chunk, false)?;
+    }
+    let trailers = from.trailers().await?;
+    match &trailers {
+        Some(trailers) => to.send_trailers(trailers.clone())?,
+        None => to.send_data(Bytes::new(), true)?,
+    }
+    Ok(trailers)
+}
+
+fn status_text(headers: &HeaderMap) -> Option<(String, bool)> {
+    let code = headers.get("grpc-status")?.to_str().ok()?;
+    let name = code
+        .parse::<usize>()
+        .ok()
+        .and_then(|code| STATUS_NAMES.get(code))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("status {}", code));
+    let text = match headers.get("grpc-message").and_then(|m| m.to_str().ok()) {
+        Some(message) if !message.is_empty() => format!("{}: {}", name, message),
+        _ => name,
+    };
+    Some((text, code == "0"))
+}
+
+pub struct Proxy {
+    pub backend: Backend,
+    pool: RwLock<Option<DescriptorPool>>,
+    /// When reflection last failed; None when it isn't used
+    reflection_failed: Mutex<Option<Instant>>,
+    log_messages: bool,
+}
+
+impl Proxy {
+    /// `pool` comes from a descriptor set; without one, server reflection is used
+    pub fn new(backend: Backend, pool: Option<DescriptorPool>, log_messages: bool) -> Self {
+        let reflection = pool.is_none();
+        Proxy {
+            backend,
+            pool: RwLock::new(pool),
+            reflection_failed: Mutex::new(reflection.then(|| Instant::now() - REFLECTION_RETRY)),
+            log_messages,
+        }
+    }
+
+    /// Loads descriptors through reflection unless they're there or it failed just now
+    pub async fn load_descriptors(&self) {
+        if self.pool.read().await.is_some() {
+            return;
+        }
+        let mut failed = self.reflection_failed.lock().await;
+        match *failed {
+            Some(at) if at.elapsed() >= REFLECTION_RETRY => {}
+            _ => return,
+        }
+        match crate::reflection::fetch(&self.backend).await {
+            Ok(pool) => {
+                let services: Vec<String> = pool
+                    .services()
+                    .map(|service| service.full_name().to_string())
+                    .collect();
+                println!("🔎 Reflection: {}", services.join(", "));
+                *self.pool.write().await = Some(pool);
+            }
+            Err(e) => {
+                eprintln!(
+                    "⚠️  Server reflection failed, messages won't be decoded: {:#}",
+                    e
+                );
+                *failed = Some(Instant::now());
+            }
+        }
+    }
+
+    async fn method(&self, path: &str) -> Option<MethodDescriptor> {
+        self.load_descriptors().await;
+        let (service, method) = path.trim_start_matches('/').rsplit_once('/')?;
+        let pool = self.pool.read().await;
+        let service = pool.as_ref()?.get_service_by_name(service)?;
+        let found = service.methods().find(|m| m.name() == method);
+        found
+    }
+
+    pub async fn serve(self: Arc<Self>, socket: TcpStream) -> Result<()> {
+        let mut connection = h2::server::handshake(socket).await?;
+        while let Some(call) = connection.accept().await {
+            let (request, respond) = call?;
+            let proxy = self.clone();
+            tokio::spawn(async move {
+                let path = request.uri().path().to_string();
+                if let Err(e) = proxy.forward(request, respond).await {
+                    eprintln!("❌ [{}] {:#}", path, e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn forward(
+        &self,
+        request: Request<RecvStream>,
+        mut respond: SendResponse<Bytes>,
+    ) -> Result<()> {
+        let started = Instant::now();
+        let path = request.uri().path().to_string();
+        let method = self.method(&path).await;
+        let (parts, body) = request.into_parts();
+        let target = parts
+            .uri
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or(&path);
+        let mut upstream = Request::builder()
+            .method(parts.method.clone())
+            .uri(self.backend.uri(target)?)
+            .body(())?;
+        *upstream.headers_mut() = parts.headers;
+
+        let mut sender = match self.backend.sender().await {
+            Ok(sender) => sender,
+            Err(e) => {
+                let unavailable = Response::builder()
+                    .header("content-type", "application/grpc")
+                    .header("grpc-status", STATUS_UNAVAILABLE)
+                    .header("grpc-message", "backend unavailable")
+                    .body(())?;
+                respond.send_response(unavailable, true)?;
+                return Err(anyhow!("Backend unavailable: {}", e));
+            }
+        };
+        println!("📞 [{}] call started", path);
+        let request_ended = body.is_end_stream();
+        let (response, stream) = sender.send_request(upstream, request_ended)?;
+
+        let requests = async {
+            if request_ended {
+                return Ok(None);
+            }
+            let log = MessageLog::new(
+                &path,
+                "→ REQUEST",
+                method.as_ref().map(MethodDescriptor::input),
+                self.log_messages,
+            );
+            pump(body, stream, log).await
+        };
+        let responses = async {
+            let (parts, body) = response.await?.into_parts();
+            let headers = parts.headers.clone();
+            let ended = body.is_end_stream();
+            let stream = respond.send_response(Response::from_parts(parts, ()), ended)?;
+            if ended {
+                return Ok::<_, anyhow::Error>(headers);
+            }
+            let log = MessageLog::new(
+                &path,
+                "← RESPONSE",
+                method.as_ref().map(MethodDescriptor::output),
+                self.log_messages,
+            );
+            Ok(pump(body, stream, log).await?.unwrap_or(headers))
+        };
+        let (sent, received) = tokio::join!(requests, responses);
+
+        let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+        let status = received.as_ref().ok().and_then(status_text);
+        match status {
+            Some((text, true)) => println!("✅ [{}] {} in {:.1}ms", path, text, elapsed),
+            Some((text, false)) => println!("❌ [{}] {} in {:.1}ms", path, text, elapsed),
+            None => println!("⚠️  [{}] ended without status in {:.1}ms", path, elapsed),
+        }
+        sent?;
+        received?;
+        Ok(())
+    }
+}
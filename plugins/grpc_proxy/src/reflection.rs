@@ -0,0 +1,202 @@
+// gRPC server reflection client. The message types of grpc/reflection/v1/reflection.proto
+// are declared here by hand (only the fields used), and the v1alpha service, which
+// shares them, is tried when the server doesn't implement v1.
+use crate::proxy::{frame, Backend, MessageFrames};
+use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
+use prost::Message;
+use prost_reflect::DescriptorPool;
+use prost_types::FileDescriptorProto;
+use std::collections::BTreeMap;
+
+const SERVICES: [&str; 2] = [
+    "grpc.reflection.v1.ServerReflection",
+    "grpc.reflection.v1alpha.ServerReflection",
+];
+
+#[derive(Clone, PartialEq, Message)]
+struct ServerReflectionRequest {
+    #[prost(string, tag = "1")]
+    host: String,
+    #[prost(oneof = "MessageRequest", tags = "3, 4, 7")]
+    message_request: Option<MessageRequest>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum MessageRequest {
+    #[prost(string, tag = "3")]
+    FileByFilename(String),
+    #[prost(string, tag = "4")]
+    FileContainingSymbol(String),
+    #[prost(string, tag = "7")]
+    ListServices(String),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ServerReflectionResponse {
+    #[prost(oneof = "MessageResponse", tags = "4, 6, 7")]
+    message_response: Option<MessageResponse>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum MessageResponse {
+    #[prost(message, tag = "4")]
+    Files(FileDescriptorResponse),
+    #[prost(message, tag = "6")]
+    Services(ListServiceResponse),
+    #[prost(message, tag = "7")]
+    Error(ErrorResponse),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FileDescriptorResponse {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    file_descriptor_proto: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ListServiceResponse {
+    #[prost(message, repeated, tag = "1")]
+    service: Vec<ServiceResponse>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ServiceResponse {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ErrorResponse {
+    #[prost(int32, tag = "1")]
+    error_code: i32,
+    #[prost(string, tag = "2")]
+    error_message: String,
+}
+
+/// One request on the bidirectional reflection stream, closed right after
+async fn call(
+    backend: &Backend,
+    service: &str,
+    request: MessageRequest,
+) -> Result<MessageResponse> {
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(request),
+    };
+    let body = frame(&request.encode_to_vec());
+    let (headers, data, trailers) = backend
+        .unary(
+            &format!("/{}/ServerReflectionInfo", service),
+            Bytes::from(body),
+        )
+        .await?;
+
+    let status = trailers
+        .as_ref()
+        .and_then(|trailers| trailers.get("grpc-status"))
+        .or_else(|| headers.get("grpc-status"))
+        .and_then(|status| status.to_str().ok())
+        .unwrap_or("0");
+    if status != "0" {
+        return Err(anyhow!("{} answered with gRPC status {}", service, status));
+    }
+
+    let mut frames = MessageFrames::default();
+    let message = frames
+        .push(&data)
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("{} sent no response", service))?;
+    let response = ServerReflectionResponse::decode(message.payload.as_slice())?;
+    match response.message_response {
+        Some(MessageResponse::Error(error)) => Err(anyhow!(
+            "Reflection error {}: {}",
+            error.error_code,
+            error.error_message
+        )),
+        Some(response) => Ok(response),
+        None => Err(anyhow!("Empty reflection response")),
+    }
+}
+
+fn add_files(
+    files: &mut BTreeMap<String, FileDescriptorProto>,
+    response: MessageResponse,
+) -> Result<()> {
+    if let MessageResponse::Files(response) = response {
+        for bytes in response.file_descriptor_proto {
+            let file = FileDescriptorProto::decode(bytes.as_slice())?;
+            files.insert(file.name().to_string(), file);
+        }
+    }
+    Ok(())
+}
+
+/// Descriptors of every service the backend exposes, with their dependencies
+pub async fn fetch(backend: &Backend) -> Result<DescriptorPool> {
+    let mut last_error = anyhow!("No reflection service");
+    for service in SERVICES {
+        let services = match call(
+            backend,
+            service,
+            MessageRequest::ListServices(String::new()),
+        )
+        .await
+        {
+            Ok(MessageResponse::Services(list)) => list.service,
+            Ok(_) => return Err(anyhow!("Unexpected answer to list_services")),
+            Err(e) => {
+                last_error = e;
+                continue;
+            }
+        };
+
+        let mut files = BTreeMap::new();
+        for name in services
+            .iter()
+            .map(|service| &service.name)
+            .filter(|name| !name.starts_with("grpc.reflection."))
+        {
+            let response = call(
+                backend,
+                service,
+                MessageRequest::FileContainingSymbol(name.clone()),
+            )
+            .await
+            .with_context(|| format!("Failed to fetch the descriptor of {}", name))?;
+            add_files(&mut files, response)?;
+        }
+        // Servers usually send dependencies along, but don't have to
+        loop {
+            let missing: Vec<String> = files
+                .values()
+                .flat_map(|file| file.dependency.iter())
+                .filter(|dependency| !files.contains_key(*dependency))
+                .cloned()
+                .collect();
+            if missing.is_empty() {
+                break;
+            }
+            for name in missing {
+                let response = call(
+                    backend,
+                    service,
+                    MessageRequest::FileByFilename(name.clone()),
+                )
+                .await
+                .with_context(|| format!("Failed to fetch {}", name))?;
+                let before = files.len();
+                add_files(&mut files, response)?;
+                if files.len() == before {
+                    return Err(anyhow!("Server did not send {}", name));
+                }
+            }
+        }
+
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_protos(files.into_values())?;
+        return Ok(pool);
+    }
+    Err(last_error)
+}
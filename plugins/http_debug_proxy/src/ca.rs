@@ -0,0 +1,132 @@
+// The local certificate authority. It is generated on first use into the plugin's state
+// directory and has to be trusted by the client once; certificates for intercepted hosts
+// are then issued from it on demand and kept for the lifetime of the process.
+use anyhow::{anyhow, Context, Result};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SerialNumber,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::ServerConfig;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use time::{Duration, OffsetDateTime};
+
+const CA_NAME: &str = "proxy http_debug_proxy CA";
+const CA_VALIDITY_DAYS: i64 = 3650;
+/// Kept under the 825 days Apple platforms accept for server certificates
+const LEAF_VALIDITY_DAYS: i64 = 365;
+
+pub struct Authority {
+    ca: Certificate,
+    ca_key: KeyPair,
+    /// One key for every issued certificate, which saves a key generation per host
+    leaf_key: KeyPair,
+    issued: Mutex<HashMap<String, Arc<ServerConfig>>>,
+}
+
+pub fn cert_path(dir: &Path) -> PathBuf {
+    dir.join("ca.pem")
+}
+
+fn key_path(dir: &Path) -> PathBuf {
+    dir.join("ca.key")
+}
+
+/// The CA's name and constraints. The in-memory CA is rebuilt from these and the stored
+/// key at startup, which gives the same subject and key identifier as the trusted file.
+fn ca_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CommonName, CA_NAME);
+    params.distinguished_name = name;
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    let now = OffsetDateTime::now_utc();
+    params.not_before = now - Duration::days(1);
+    params.not_after = now + Duration::days(CA_VALIDITY_DAYS);
+    params
+}
+
+/// Serial numbers must differ between certificates of one issuer; rcgen would otherwise
+/// derive them from the (shared) leaf key
+fn serial() -> SerialNumber {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default();
+    SerialNumber::from(nanos)
+}
+
+fn write_key(path: &Path, pem: &str) -> Result<()> {
+    fs::write(path, pem)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+impl Authority {
+    /// Loads the CA from `dir`, creating it first if there is none (or `regenerate`)
+    pub fn load_or_create(dir: &Path, regenerate: bool) -> Result<Self> {
+        let cert_path = cert_path(dir);
+        let key_path = key_path(dir);
+        let ca_key = if key_path.exists() && cert_path.exists() && !regenerate {
+            let pem = fs::read_to_string(&key_path)
+                .with_context(|| format!("Failed to read {}", key_path.display()))?;
+            KeyPair::from_pem(&pem).context("Invalid CA key")?
+        } else {
+            fs::create_dir_all(dir)?;
+            let key = KeyPair::generate()?;
+            let ca = ca_params().self_signed(&key)?;
+            write_key(&key_path, &key.serialize_pem())?;
+            fs::write(&cert_path, ca.pem())?;
+            println!("🔐 Created a new CA at {}", cert_path.display());
+            println!("💡 Trust it once to intercept HTTPS, see --print-ca");
+            key
+        };
+        let ca = ca_params().self_signed(&ca_key)?;
+        Ok(Authority {
+            ca,
+            ca_key,
+            leaf_key: KeyPair::generate()?,
+            issued: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// TLS settings presenting a certificate for `host`, issued on first use
+    pub fn server_config(&self, host: &str) -> Result<Arc<ServerConfig>> {
+        let mut issued = self
+            .issued
+            .lock()
+            .map_err(|_| anyhow!("Certificate cache poisoned"))?;
+        if let Some(config) = issued.get(host) {
+            return Ok(config.clone());
+        }
+
+        let mut params = CertificateParams::new(vec![host.to_string()])?;
+        params.distinguished_name.push(DnType::CommonName, host);
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.serial_number = Some(serial());
+        let now = OffsetDateTime::now_utc();
+        params.not_before = now - Duration::days(1);
+        params.not_after = now + Duration::days(LEAF_VALIDITY_DAYS);
+        let leaf = params.signed_by(&self.leaf_key, &self.ca, &self.ca_key)?;
+
+        let chain: Vec<CertificateDer<'static>> = vec![leaf.der().clone(), self.ca.der().clone()];
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(self.leaf_key.serialize_der()));
+        let mut config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)?;
+        // Only HTTP/1.1, which is what the traffic log decodes
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let config = Arc::new(config);
+        issued.insert(host.to_string(), config.clone());
+        Ok(config)
+    }
+}
@@ -0,0 +1,256 @@
+// A local HTTP(S) debugging proxy, mitmproxy-lite: point a client at it as its HTTP
+// proxy and every request and response is printed with the shared HTTP decoder. HTTPS
+// is intercepted with certificates from a CA the plugin generates and keeps in its
+// state directory; hosts can be left alone with `passthrough`. Headers such as
+// credentials can be added to the requests of chosen hosts.
+use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+mod ca;
+mod proxy;
+
+const PLUGIN_NAME: &str = "http_debug_proxy";
+const DEFAULT_LISTEN: &str = "127.0.0.1:8888";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DebugProxyConfig {
+    /// Address to listen on (default 127.0.0.1:8888)
+    pub listen: Option<String>,
+    /// Host patterns to intercept (default all): "*", "*.example.com", "api.example.com"
+    pub intercept: Option<Vec<String>>,
+    /// Host patterns tunnelled untouched, e.g. ones that pin certificates
+    #[serde(default)]
+    pub passthrough: Vec<String>,
+    /// Headers added to intercepted and plain HTTP requests, e.g. credentials
+    #[serde(default)]
+    pub inject: Vec<Inject>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Inject {
+    /// Host pattern, as in `intercept`
+    pub host: String,
+    pub header: String,
+    /// Literal value or secret reference ("secret:...", "vault:PATH#FIELD")
+    pub value: String,
+}
+
+pub struct HttpDebugProxyPlugin;
+
+impl HttpDebugProxyPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# HTTP Debug Proxy Configuration
+listen = "127.0.0.1:8888"
+intercept = ["*.example.com", "localhost"]  # default: every host
+passthrough = ["*.apple.com"]
+
+# [[inject]]
+# host = "api.example.com"
+# header = "Authorization"
+# value = "vault:kv/data/api#bearer"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<DebugProxyConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: DebugProxyConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!(
+                    "📝 Sample config:\n{}",
+                    HttpDebugProxyPlugin::sample_config()
+                );
+                Ok(DebugProxyConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(DebugProxyConfig::default())
+        }
+    }
+}
+
+fn print_ca_help(cert: &std::path::Path) {
+    println!("📜 CA certificate: {}", cert.display());
+    println!("💡 Trust it for the clients you debug:");
+    println!(
+        "   curl:   curl --cacert {} -x http://127.0.0.1:8888 https://...",
+        cert.display()
+    );
+    println!("   macOS:  sudo security add-trusted-cert -d -r trustRoot -k /Library/Keychains/System.keychain {}", cert.display());
+    println!("   Debian: sudo cp {} /usr/local/share/ca-certificates/proxy-debug.crt && sudo update-ca-certificates", cert.display());
+    println!("   Node:   NODE_EXTRA_CA_CERTS={}", cert.display());
+    println!(
+        "   Python: REQUESTS_CA_BUNDLE={} (replaces the default bundle)",
+        cert.display()
+    );
+}
+
+impl Plugin for HttpDebugProxyPlugin {
+    fn name(&self) -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "HTTP(S) debugging proxy that intercepts TLS and logs requests and responses"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Run a local HTTP proxy that decodes HTTP and intercepted HTTPS traffic")
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .short('l')
+                    .value_name("ADDR")
+                    .help("Address to listen on (default 127.0.0.1:8888)"),
+            )
+            .arg(
+                Arg::new("print-ca")
+                    .long("print-ca")
+                    .action(ArgAction::SetTrue)
+                    .help("Print where the CA certificate is and how to trust it, then exit"),
+            )
+            .arg(
+                Arg::new("regenerate-ca")
+                    .long("regenerate-ca")
+                    .action(ArgAction::SetTrue)
+                    .help("Replace the CA with a new one (it has to be trusted again)"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(HttpDebugProxyPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<DebugProxyConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let Some(dir) = plugin_api::plugin_state_dir(PLUGIN_NAME) else {
+            eprintln!("❌ Could not determine the state directory for the CA");
+            std::process::exit(1);
+        };
+        let authority = match ca::Authority::load_or_create(&dir, matches.get_flag("regenerate-ca"))
+        {
+            Ok(authority) => authority,
+            Err(e) => {
+                eprintln!("❌ Failed to set up the CA: {:#}", e);
+                std::process::exit(1);
+            }
+        };
+        if matches.get_flag("print-ca") {
+            print_ca_help(&ca::cert_path(&dir));
+            return;
+        }
+
+        let listen = matches
+            .get_one::<String>("listen")
+            .cloned()
+            .or(config.listen)
+            .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+        let intercept = config.intercept.unwrap_or_else(|| vec!["*".to_string()]);
+        let mut inject = Vec::new();
+        for rule in config.inject {
+            match plugin_api::resolve_secret(&rule.value) {
+                Ok(value) => inject.push(proxy::Injection {
+                    host: rule.host,
+                    header: rule.header,
+                    value,
+                }),
+                Err(e) => {
+                    eprintln!("❌ Failed to resolve the {} header: {}", rule.header, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        let intercepted = intercept.join(",");
+        let proxy = Arc::new(proxy::Proxy::new(
+            authority,
+            intercept,
+            config.passthrough,
+            inject,
+        ));
+
+        plugin_api::metrics::init(self.name());
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        rt.block_on(async {
+            let listener = match TcpListener::bind(&listen).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("❌ Failed to listen on {}: {}", listen, e);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🚀 HTTP debug proxy listening on {}", listen);
+            plugin_api::audit::record(
+                self.name(),
+                "mitm_enabled",
+                &[("listen", &listen), ("intercept", &intercepted)],
+            );
+            println!(
+                "💡 export https_proxy=http://{0} http_proxy=http://{0}",
+                listen
+            );
+            println!("📜 CA certificate: {}", ca::cert_path(&dir).display());
+
+            loop {
+                let (client, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("❌ Accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let proxy = proxy.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = proxy::handle(proxy, client).await {
+                        eprintln!("❌ [{}] {:#}", addr, e);
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(HttpDebugProxyPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,245 @@
+// One client connection of the explicit proxy. CONNECT tunnels are either intercepted
+// (TLS terminated with a certificate from the local CA, then re-encrypted to the real
+// host) or passed through untouched; plain HTTP requests in absolute form are sent on
+// to their host. Intercepted and plain HTTP traffic is logged with the HTTP decoder.
+// Requests to hosts with injected headers get them added and `Connection: close`, so
+// every request arrives on a new connection and passes through the injection.
+use crate::ca::Authority;
+use anyhow::{anyhow, Context, Result};
+use plugin_api::traffic::{log_message, relay_tagged, Protocol};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, RootCertStore};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Upper bound for a request head, to not buffer garbage forever
+const MAX_HEAD: usize = 64 * 1024;
+
+pub struct Proxy {
+    pub authority: Authority,
+    /// Host patterns to intercept: "*", "*.example.com" or an exact host
+    pub intercept: Vec<String>,
+    /// Host patterns tunnelled without interception, checked first
+    pub passthrough: Vec<String>,
+    pub inject: Vec<Injection>,
+    tls: Arc<ClientConfig>,
+}
+
+/// A header added to the requests of matching hosts; the value is already resolved
+pub struct Injection {
+    pub host: String,
+    pub header: String,
+    pub value: String,
+}
+
+fn matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix('*') {
+        Some("") => true,
+        Some(suffix) => host
+            .to_ascii_lowercase()
+            .ends_with(&suffix.to_ascii_lowercase()),
+        None => host.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// "host:port", "[::1]:port" or "host" with the default port
+fn split_host_port(authority: &str, default_port: u16) -> Result<(String, u16)> {
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, port.parse()?),
+        _ => (authority, default_port),
+    };
+    Ok((host.trim_matches(['[', ']']).to_string(), port))
+}
+
+impl Proxy {
+    pub fn new(
+        authority: Authority,
+        intercept: Vec<String>,
+        passthrough: Vec<String>,
+        inject: Vec<Injection>,
+    ) -> Self {
+        let mut roots = RootCertStore::empty();
+        let native = rustls_native_certs::load_native_certs();
+        for error in &native.errors {
+            eprintln!("⚠️  Skipping a system certificate: {}", error);
+        }
+        let (added, _) = roots.add_parsable_certificates(native.certs);
+        if added == 0 {
+            eprintln!("⚠️  No system root certificates found, upstream TLS will fail");
+        }
+        let mut tls = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Proxy {
+            authority,
+            intercept,
+            passthrough,
+            inject,
+            tls: Arc::new(tls),
+        }
+    }
+
+    fn intercepts(&self, host: &str) -> bool {
+        !self.passthrough.iter().any(|p| matches(p, host))
+            && self.intercept.iter().any(|p| matches(p, host))
+    }
+
+    fn injections(&self, host: &str) -> Vec<&Injection> {
+        self.inject
+            .iter()
+            .filter(|injection| matches(&injection.host, host))
+            .collect()
+    }
+}
+
+/// The request head with the injected headers replacing any of the same name
+fn inject_headers(head: &[u8], injections: &[&Injection]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(head);
+    let replaced = |line: &str| {
+        let name = line.split(':').next().unwrap_or_default().trim();
+        name.eq_ignore_ascii_case("connection")
+            || injections
+                .iter()
+                .any(|injection| name.eq_ignore_ascii_case(&injection.header))
+    };
+    let mut lines: Vec<String> = text
+        .trim_end()
+        .split("\r\n")
+        .enumerate()
+        .filter(|(i, line)| *i == 0 || !replaced(line))
+        .map(|(_, line)| line.to_string())
+        .collect();
+    for injection in injections {
+        lines.push(format!("{}: {}", injection.header, injection.value));
+    }
+    lines.push("Connection: close".to_string());
+    format!("{}\r\n\r\n", lines.join("\r\n")).into_bytes()
+}
+
+fn print_injections(host: &str, injections: &[&Injection]) {
+    let names: Vec<&str> = injections.iter().map(|i| i.header.as_str()).collect();
+    println!("💉 [{}] Injected {}", host, names.join(", "));
+}
+
+/// Reads up to the end of the request head; returns the head and anything after it
+async fn read_head<C: AsyncRead + Unpin>(client: &mut C) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buffer.split_off(end + 4);
+            return Ok((buffer, rest));
+        }
+        if buffer.len() > MAX_HEAD {
+            return Err(anyhow!("Request head too large"));
+        }
+        let n = client.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(anyhow!("Client closed before sending a request"));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+}
+
+pub async fn handle(proxy: Arc<Proxy>, mut client: TcpStream) -> Result<()> {
+    let (head, rest) = read_head(&mut client).await?;
+    let text = String::from_utf8_lossy(&head).into_owned();
+    let request_line = text.lines().next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        _ => return Err(anyhow!("Malformed request line: {}", request_line)),
+    };
+
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = split_host_port(target, 443)?;
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+        if proxy.intercepts(&host) {
+            intercept(&proxy, client, host, port).await
+        } else {
+            println!("🔀 [{}] Passing through {}:{}", host, host, port);
+            let upstream = TcpStream::connect((host.as_str(), port)).await?;
+            let (reader, writer) = upstream.into_split();
+            relay_tagged(&host, client, reader, writer, None).await;
+            Ok(())
+        }
+    } else {
+        let Some(url) = target.strip_prefix("http://") else {
+            client
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+                .await?;
+            return Err(anyhow!("Not a proxy request: {}", request_line));
+        };
+        let (authority, path) = match url.find('/') {
+            Some(slash) => (&url[..slash], &url[slash..]),
+            None => (url, "/"),
+        };
+        let (host, port) = split_host_port(authority, 80)?;
+        let mut upstream = TcpStream::connect((host.as_str(), port))
+            .await
+            .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+
+        // Origin-form request line for the server; the rest of the head is kept
+        let mut first = format!("{} {} {}", method, path, version).into_bytes();
+        first.extend_from_slice(&head[request_line.len()..]);
+        log_message(
+            &format!("[{}] → REQUEST", host),
+            &Protocol::Http,
+            &[first.as_slice(), rest.as_slice()].concat(),
+        );
+        let injections = proxy.injections(&host);
+        if !injections.is_empty() {
+            first = inject_headers(&first, &injections);
+            print_injections(&host, &injections);
+        }
+        first.extend_from_slice(&rest);
+        upstream.write_all(&first).await?;
+
+        let (reader, writer) = upstream.into_split();
+        relay_tagged(&host, client, reader, writer, Some(&Protocol::Http)).await;
+        Ok(())
+    }
+}
+
+async fn intercept(proxy: &Proxy, client: TcpStream, host: String, port: u16) -> Result<()> {
+    let acceptor = TlsAcceptor::from(proxy.authority.server_config(&host)?);
+    let mut client = acceptor.accept(client).await.with_context(|| {
+        format!(
+            "TLS handshake with the client failed for {} (is the CA trusted?)",
+            host
+        )
+    })?;
+
+    let upstream = TcpStream::connect((host.as_str(), port))
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+    let name = ServerName::try_from(host.clone())?;
+    let mut upstream = TlsConnector::from(proxy.tls.clone())
+        .connect(name, upstream)
+        .await
+        .with_context(|| format!("TLS handshake with {} failed", host))?;
+
+    println!("🔓 [{}] Intercepting {}:{}", host, host, port);
+    let injections = proxy.injections(&host);
+    if !injections.is_empty() {
+        // The first request is logged as sent; the relay logs the rest
+        let (head, rest) = read_head(&mut client).await?;
+        log_message(
+            &format!("[{}] → REQUEST", host),
+            &Protocol::Http,
+            &[head.as_slice(), rest.as_slice()].concat(),
+        );
+        let mut first = inject_headers(&head, &injections);
+        print_injections(&host, &injections);
+        first.extend_from_slice(&rest);
+        upstream.write_all(&first).await?;
+    }
+    let (reader, writer) = tokio::io::split(upstream);
+    relay_tagged(&host, client, reader, writer, Some(&Protocol::Http)).await;
+    Ok(())
+}
@@ -0,0 +1,414 @@
+// Shells and commands in pods through the native exec API, without kubectl: an
+// interactive TTY shell (raw terminal, window size kept in sync) when no command is
+// given, and otherwise the command's output on stdout and stderr and its exit code as
+// the plugin's own, so it works in scripts.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use kube::api::AttachParams;
+use kube::{Api, Client};
+use plugin_api::k8s::{find_pod, is_running};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::io::IsTerminal;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::runtime::Runtime;
+
+mod terminal;
+
+use terminal::RawMode;
+
+/// Starts bash where the image has it and sh otherwise
+const DEFAULT_SHELL: &str = "command -v bash >/dev/null 2>&1 && exec bash || exec sh";
+/// Annotation kubectl also uses to pick the container
+const DEFAULT_CONTAINER_ANNOTATION: &str = "kubectl.kubernetes.io/default-container";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct K8sExecConfig {
+    /// Namespace (default "default")
+    pub namespace: Option<String>,
+    pub pod_name: Option<String>,
+    /// Label selector, e.g. "app=api"; a running matching pod is used
+    pub pod_selector: Option<String>,
+    /// Container (default: the pod's default container, else its first one)
+    pub container: Option<String>,
+    /// Shell started without a command (default: bash, or sh where there is no bash)
+    pub shell: Option<String>,
+}
+
+pub struct K8sExecPlugin;
+
+impl K8sExecPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Kubernetes Exec Configuration
+namespace = "default"
+pod_selector = "app=api"  # Either use pod_name OR pod_selector
+# pod_name = "api-7d9f8b6c4-x2k4p"
+# container = "app"
+# shell = "/bin/zsh"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<K8sExecConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: K8sExecConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Defaults are fine with --pod or --selector; stdout belongs to the command
+                Ok(K8sExecConfig::default())
+            }
+        }
+        None => Ok(K8sExecConfig::default()),
+    }
+}
+
+/// The requested container, the annotated default one, or the first
+fn pick_container(pod: &Pod, requested: Option<&str>) -> Result<String> {
+    let names: Vec<&str> = pod
+        .spec
+        .as_ref()
+        .map(|spec| spec.containers.iter().map(|c| c.name.as_str()).collect())
+        .unwrap_or_default();
+    if let Some(requested) = requested {
+        if names.contains(&requested) {
+            return Ok(requested.to_string());
+        }
+        return Err(anyhow!(
+            "No container '{}' in the pod (has: {})",
+            requested,
+            names.join(", ")
+        ));
+    }
+    let annotated = pod
+        .metadata
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(DEFAULT_CONTAINER_ANNOTATION))
+        .filter(|name| names.contains(&name.as_str()));
+    let container = match annotated {
+        Some(name) => name.clone(),
+        None => names
+            .first()
+            .map(|name| name.to_string())
+            .ok_or_else(|| anyhow!("The pod has no containers"))?,
+    };
+    if names.len() > 1 {
+        eprintln!(
+            "📦 Defaulted container {} (of {}), pick one with --container",
+            container,
+            names.join(", ")
+        );
+    }
+    Ok(container)
+}
+
+/// Copies output as it arrives; a shell prompt has no newline to flush on
+async fn pump(mut from: impl AsyncRead + Unpin, mut to: impl AsyncWrite + Unpin) {
+    let mut buffer = vec![0u8; 8192];
+    loop {
+        match from.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => {
+                if to.write_all(&buffer[..n]).await.is_err() || to.flush().await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// The process exit code a finished exec reports
+fn exit_code(status: Status) -> i32 {
+    if status.status.as_deref() == Some("Success") {
+        return 0;
+    }
+    let code = status
+        .details
+        .as_ref()
+        .and_then(|details| details.causes.as_ref())
+        .and_then(|causes| {
+            causes
+                .iter()
+                .find(|cause| cause.reason.as_deref() == Some("ExitCode"))
+        })
+        .and_then(|cause| cause.message.as_deref())
+        .and_then(|message| message.parse().ok());
+    match code {
+        Some(code) => code,
+        None => {
+            eprintln!(
+                "❌ {}",
+                status.message.as_deref().unwrap_or("The command failed")
+            );
+            1
+        }
+    }
+}
+
+struct Target {
+    namespace: String,
+    pod: String,
+    container: String,
+}
+
+async fn exec(
+    pods: Api<Pod>,
+    target: &Target,
+    command: Vec<String>,
+    tty: bool,
+    stdin: bool,
+) -> Result<i32> {
+    let params = AttachParams::default()
+        .container(target.container.clone())
+        .stdin(stdin)
+        .stdout(true)
+        // A TTY merges stderr into stdout
+        .stderr(!tty)
+        .tty(tty);
+    let mut attached = pods.exec(&target.pod, command, &params).await?;
+    let status = attached
+        .take_status()
+        .ok_or_else(|| anyhow!("The exec session has no status channel"))?;
+
+    let raw_mode = if tty { Some(RawMode::enable()?) } else { None };
+    if tty {
+        if let Some(sizes) = attached.terminal_size() {
+            tokio::spawn(terminal::forward_size(sizes));
+        }
+    }
+    if let Some(mut remote) = attached.stdin() {
+        tokio::spawn(async move {
+            pump(tokio::io::stdin(), &mut remote).await;
+            // The exec protocol has no end of input: closing stdin ends the whole
+            // session, output and exit code included, so keep it open until the end
+            std::future::pending::<()>().await;
+            drop(remote);
+        });
+    }
+    let stdout = attached
+        .stdout()
+        .map(|remote| tokio::spawn(pump(remote, tokio::io::stdout())));
+    let stderr = attached
+        .stderr()
+        .map(|remote| tokio::spawn(pump(remote, tokio::io::stderr())));
+    for output in [stdout, stderr].into_iter().flatten() {
+        let _ = output.await;
+    }
+
+    let status = status.await;
+    drop(raw_mode);
+    match status {
+        Some(status) => Ok(exit_code(status)),
+        None => Err(anyhow!(
+            "The connection to {}/{} closed without an exit status",
+            target.namespace,
+            target.pod
+        )),
+    }
+}
+
+impl Plugin for K8sExecPlugin {
+    fn name(&self) -> &'static str {
+        "k8s_exec"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Interactive shells and commands in Kubernetes pods via the native API"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Open a shell or run a command in a Kubernetes pod")
+            .arg(
+                Arg::new("pod")
+                    .long("pod")
+                    .short('p')
+                    .value_name("POD_NAME")
+                    .help("Override pod name from config file"),
+            )
+            .arg(
+                Arg::new("selector")
+                    .long("selector")
+                    .short('s')
+                    .value_name("SELECTOR")
+                    .help("Override pod selector from config file (e.g., 'app=nginx')"),
+            )
+            .arg(
+                Arg::new("namespace")
+                    .long("namespace")
+                    .short('n')
+                    .value_name("NAMESPACE")
+                    .help("Override namespace from config file"),
+            )
+            .arg(
+                Arg::new("container")
+                    .long("container")
+                    .short('c')
+                    .value_name("CONTAINER")
+                    .help("Container to run in (default: the pod's default container)"),
+            )
+            .arg(
+                Arg::new("tty")
+                    .long("tty")
+                    .short('t')
+                    .action(ArgAction::SetTrue)
+                    .help("Run the command in a TTY, e.g. for top or psql"),
+            )
+            .arg(
+                Arg::new("stdin")
+                    .long("stdin")
+                    .short('i')
+                    .action(ArgAction::SetTrue)
+                    .help("Pass local stdin to the command; it must exit on its own, as the end of stdin can't be passed on"),
+            )
+            .arg(
+                Arg::new("command")
+                    .value_name("COMMAND")
+                    .num_args(1..)
+                    .last(true)
+                    .help("Command to run after --; an interactive shell without one"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(K8sExecPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<K8sExecConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let mut config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        // Override config with command line arguments
+        if let Some(pod) = matches.get_one::<String>("pod") {
+            config.pod_name = Some(pod.clone());
+            config.pod_selector = None;
+        }
+        if let Some(selector) = matches.get_one::<String>("selector") {
+            config.pod_selector = Some(selector.clone());
+            config.pod_name = None;
+        }
+        if let Some(namespace) = matches.get_one::<String>("namespace") {
+            config.namespace = Some(namespace.clone());
+        }
+        if let Some(container) = matches.get_one::<String>("container") {
+            config.container = Some(container.clone());
+        }
+        if config.pod_name.is_none() && config.pod_selector.is_none() {
+            eprintln!("❌ Must specify either --pod or --selector (or configure in config file)");
+            eprintln!("💡 Example: proxy k8s_exec --selector app=api");
+            eprintln!("💡 Example: proxy k8s_exec --pod my-pod -- env");
+            std::process::exit(1);
+        }
+
+        let command: Option<Vec<String>> = matches
+            .get_many::<String>("command")
+            .map(|command| command.cloned().collect());
+        let (command, tty, stdin) = match command {
+            Some(command) => {
+                let tty = matches.get_flag("tty");
+                if tty && !std::io::stdin().is_terminal() {
+                    eprintln!("❌ --tty needs a terminal on stdin");
+                    std::process::exit(1);
+                }
+                (command, tty, tty || matches.get_flag("stdin"))
+            }
+            None => {
+                if !std::io::stdin().is_terminal() {
+                    eprintln!("❌ An interactive shell needs a terminal; give a command after --");
+                    std::process::exit(1);
+                }
+                let shell = match &config.shell {
+                    Some(shell) => vec![shell.clone()],
+                    None => vec![
+                        "sh".to_string(),
+                        "-c".to_string(),
+                        DEFAULT_SHELL.to_string(),
+                    ],
+                };
+                (shell, true, true)
+            }
+        };
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        let result = rt.block_on(async {
+            let client = Client::try_default().await?;
+            let namespace = config
+                .namespace
+                .clone()
+                .unwrap_or_else(|| "default".to_string());
+            let pods: Api<Pod> = Api::namespaced(client, &namespace);
+            let pod = find_pod(
+                &pods,
+                config.pod_name.as_deref(),
+                config.pod_selector.as_deref(),
+            )
+            .await?;
+            let target = Target {
+                namespace,
+                pod: pod
+                    .metadata
+                    .name
+                    .clone()
+                    .ok_or_else(|| anyhow!("Pod has no name"))?,
+                container: pick_container(&pod, config.container.as_deref())?,
+            };
+            if !is_running(&pod) {
+                eprintln!("⚠️  Pod {} is not running", target.pod);
+            }
+            if tty {
+                eprintln!(
+                    "🐚 {}/{} ({}), exit the shell to disconnect",
+                    target.namespace, target.pod, target.container
+                );
+            }
+            plugin_api::audit::record_k8s(
+                self.name(),
+                "pod_exec",
+                &[
+                    ("namespace", &target.namespace),
+                    ("pod", &target.pod),
+                    ("container", &target.container),
+                    ("command", &command.join(" ")),
+                ],
+            );
+            exec(pods, &target, command, tty, stdin).await
+        });
+        match result {
+            Ok(code) => {
+                // Skip the runtime shutdown, which would wait for the blocking stdin read
+                std::process::exit(code);
+            }
+            Err(e) => {
+                eprintln!("❌ Exec error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(K8sExecPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
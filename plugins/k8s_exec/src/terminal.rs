@@ -0,0 +1,73 @@
+// The local side of a TTY session: raw mode while the remote shell owns the terminal,
+// and the window size sent to the pod at start and on every resize.
+use anyhow::Result;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use futures::channel::mpsc::Sender;
+use futures::SinkExt;
+use kube::api::TerminalSize;
+
+/// Keeps the terminal in raw mode until dropped, so keys like Ctrl+C and Tab reach the
+/// remote shell instead of being handled locally
+pub struct RawMode;
+
+impl RawMode {
+    pub fn enable() -> Result<Self> {
+        enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+    }
+}
+
+fn size() -> Option<TerminalSize> {
+    crossterm::terminal::size()
+        .ok()
+        .map(|(width, height)| TerminalSize { width, height })
+}
+
+/// Sends the current size, then every change until the session ends
+pub async fn forward_size(mut sizes: Sender<TerminalSize>) {
+    if let Some(size) = size() {
+        if sizes.send(size).await.is_err() {
+            return;
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let Ok(mut resized) = signal(SignalKind::window_change()) else {
+            return;
+        };
+        while resized.recv().await.is_some() {
+            if let Some(size) = size() {
+                if sizes.send(size).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        // No resize signal here, so poll
+        let mut last = crossterm::terminal::size().ok();
+        let mut interval = tokio::time::interval(std::time::Duration::from_millis(250));
+        loop {
+            interval.tick().await;
+            let current = crossterm::terminal::size().ok();
+            if current != last {
+                last = current;
+                if let Some((width, height)) = current {
+                    if sizes.send(TerminalSize { width, height }).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,330 @@
+// Maps what URL actually reaches a service: lists Ingresses and Gateway API HTTPRoutes
+// as host + path → backend service, with HTTPS and the TLS secret in use, and can send
+// a request to each external endpoint to check it answers.
+use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use kube::Client;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+mod probe;
+mod routes;
+
+use probe::{Outcome, Probe};
+use routes::Route;
+
+const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct K8sIngressConfig {
+    /// Namespace (default: the kubeconfig context's)
+    pub namespace: Option<String>,
+    /// Per-request timeout of --test (default 5000)
+    pub timeout_ms: Option<u64>,
+    /// Accept invalid certificates in --test, e.g. before cert-manager issued one
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+pub struct K8sIngressPlugin;
+
+impl K8sIngressPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Kubernetes Ingress Configuration
+namespace = "default"  # default: the kubeconfig context's namespace
+timeout_ms = 5000
+insecure = false  # accept invalid certificates when testing
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<K8sIngressConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: K8sIngressConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Everything has a working default, so don't print the sample every run
+                Ok(K8sIngressConfig::default())
+            }
+        }
+        None => Ok(K8sIngressConfig::default()),
+    }
+}
+
+fn path_note(route: &Route) -> String {
+    match route.path_type.as_str() {
+        "Prefix" | "PathPrefix" => String::new(),
+        "Default" => " (default backend)".to_string(),
+        other => format!(" ({})", other),
+    }
+}
+
+fn print_routes(routes: &[&Route]) {
+    let urls: Vec<String> = routes
+        .iter()
+        .map(|route| format!("{}{}", route.url(), path_note(route)))
+        .collect();
+    let url_width = urls
+        .iter()
+        .map(|url| url.chars().count())
+        .max()
+        .unwrap_or(0);
+    let backends: Vec<String> = routes
+        .iter()
+        .map(|route| {
+            let backends: Vec<String> = route.backends.iter().map(|b| b.to_string()).collect();
+            if backends.is_empty() {
+                "(no backend)".to_string()
+            } else {
+                backends.join(", ")
+            }
+        })
+        .collect();
+    let backend_width = backends
+        .iter()
+        .map(|b| b.chars().count())
+        .max()
+        .unwrap_or(0);
+    for ((route, url), backend) in routes.iter().zip(&urls).zip(&backends) {
+        let tls = match &route.tls_secret {
+            Some(secret) => format!("  🔒 {}", secret),
+            None if route.https => "  🔒".to_string(),
+            None => String::new(),
+        };
+        println!(
+            "  {:<url_width$}  → {:<backend_width$}  [{}]{}",
+            url,
+            backend,
+            route.source(),
+            tls
+        );
+    }
+}
+
+fn print_outcomes(routes: &[&Route], outcomes: &[Outcome]) {
+    let urls: Vec<String> = routes.iter().map(|route| route.url()).collect();
+    let width = urls
+        .iter()
+        .map(|url| url.chars().count())
+        .max()
+        .unwrap_or(0);
+    let (mut ok, mut failed) = (0, 0);
+    for (url, outcome) in urls.iter().zip(outcomes) {
+        match outcome {
+            Outcome::Status {
+                code,
+                millis,
+                location,
+            } => {
+                let icon = match code {
+                    0..=399 => "✅",
+                    400..=499 => "⚠️ ",
+                    _ => "❌",
+                };
+                if *code < 500 {
+                    ok += 1;
+                } else {
+                    failed += 1;
+                }
+                let redirect = location
+                    .as_ref()
+                    .map(|location| format!(" → {}", location))
+                    .unwrap_or_default();
+                println!(
+                    "  {} {:<width$}  {} in {}ms{}",
+                    icon, url, code, millis, redirect
+                );
+            }
+            Outcome::Failed(reason) => {
+                failed += 1;
+                println!("  ❌ {:<width$}  {}", url, reason);
+            }
+            Outcome::Skipped(reason) => println!("  ⏭️  {:<width$}  skipped: {}", url, reason),
+        }
+    }
+    println!("\n📊 {} answered, {} failed", ok, failed);
+}
+
+impl Plugin for K8sIngressPlugin {
+    fn name(&self) -> &'static str {
+        "k8s_ingress"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Map Ingresses, Gateways and HTTPRoutes to URLs and test them"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("List ingress and gateway routes as URLs and test their reachability")
+            .arg(
+                Arg::new("namespace")
+                    .long("namespace")
+                    .short('n')
+                    .value_name("NAMESPACE")
+                    .help("Override namespace from config file"),
+            )
+            .arg(
+                Arg::new("all-namespaces")
+                    .long("all-namespaces")
+                    .short('A')
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("namespace")
+                    .help("Routes of every namespace"),
+            )
+            .arg(
+                Arg::new("service")
+                    .long("service")
+                    .short('s')
+                    .value_name("SERVICE")
+                    .help("Only routes that end at this service"),
+            )
+            .arg(
+                Arg::new("host")
+                    .long("host")
+                    .value_name("HOST")
+                    .help("Only routes for hosts containing this"),
+            )
+            .arg(
+                Arg::new("test")
+                    .long("test")
+                    .short('t')
+                    .action(ArgAction::SetTrue)
+                    .help("Send a GET to every route and report the answer"),
+            )
+            .arg(
+                Arg::new("via-lb")
+                    .long("via-lb")
+                    .action(ArgAction::SetTrue)
+                    .requires("test")
+                    .help("Test against the load balancer address instead of DNS"),
+            )
+            .arg(
+                Arg::new("insecure")
+                    .long("insecure")
+                    .short('k')
+                    .action(ArgAction::SetTrue)
+                    .help("Accept invalid certificates when testing"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(K8sIngressPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<K8sIngressConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let client = match Client::try_default().await {
+                Ok(client) => client,
+                Err(e) => {
+                    eprintln!("❌ Failed to connect to Kubernetes: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let namespace = if matches.get_flag("all-namespaces") {
+                None
+            } else {
+                Some(
+                    matches
+                        .get_one::<String>("namespace")
+                        .cloned()
+                        .or(config.namespace.clone())
+                        .unwrap_or_else(|| client.default_namespace().to_string()),
+                )
+            };
+
+            let mut all = match routes::ingress_routes(&client, namespace.as_deref()).await {
+                Ok(routes) => routes,
+                Err(e) => {
+                    eprintln!("❌ Failed to list ingresses: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            match routes::gateway_routes(&client, namespace.as_deref()).await {
+                Ok(Some(routes)) => all.extend(routes),
+                Ok(None) => {}
+                Err(e) => eprintln!("⚠️  Failed to list HTTPRoutes: {}", e),
+            }
+
+            let service = matches.get_one::<String>("service");
+            let host = matches.get_one::<String>("host");
+            let mut selected: Vec<&Route> = all
+                .iter()
+                .filter(|route| {
+                    service
+                        .is_none_or(|service| route.backends.iter().any(|b| &b.service == service))
+                })
+                .filter(|route| {
+                    host.is_none_or(|host| {
+                        route
+                            .host
+                            .as_ref()
+                            .is_some_and(|h| h.contains(host.as_str()))
+                    })
+                })
+                .collect();
+            selected.sort_by(|a, b| (&a.host, &a.path, a.https).cmp(&(&b.host, &b.path, b.https)));
+
+            let scope = match &namespace {
+                Some(namespace) => format!("namespace {}", namespace),
+                None => "all namespaces".to_string(),
+            };
+            if selected.is_empty() {
+                match service {
+                    Some(service) => println!("📭 No routes to service {} in {}", service, scope),
+                    None => println!("📭 No ingress or gateway routes in {}", scope),
+                }
+                return;
+            }
+            println!("🌐 Routes in {} ({}):", scope, selected.len());
+            print_routes(&selected);
+
+            if matches.get_flag("test") {
+                let probe = Probe {
+                    timeout: Duration::from_millis(config.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS)),
+                    insecure: config.insecure || matches.get_flag("insecure"),
+                    via_lb: matches.get_flag("via-lb"),
+                };
+                let how = if probe.via_lb {
+                    "through the load balancer"
+                } else {
+                    "through DNS"
+                };
+                println!("\n🔎 Testing {} routes {}:", selected.len(), how);
+                let outcomes = probe.run(&selected).await;
+                print_outcomes(&selected, &outcomes);
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(K8sIngressPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
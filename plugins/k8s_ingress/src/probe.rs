@@ -0,0 +1,99 @@
+// Reachability tests for route URLs: one GET each, without following redirects, so the
+// answer is the one the ingress or gateway itself gives. With `via_lb` the host is sent
+// to the route's load balancer address instead of what DNS says, which checks a route
+// before its DNS record exists or points there.
+use crate::routes::Route;
+use anyhow::{anyhow, Result};
+use futures::stream::{self, StreamExt};
+use std::time::{Duration, Instant};
+
+/// Requests in flight at once
+const PARALLEL: usize = 8;
+
+pub struct Probe {
+    pub timeout: Duration,
+    /// Accept self-signed and otherwise invalid certificates
+    pub insecure: bool,
+    pub via_lb: bool,
+}
+
+pub enum Outcome {
+    Status {
+        code: u16,
+        millis: u128,
+        location: Option<String>,
+    },
+    Failed(String),
+    Skipped(&'static str),
+}
+
+impl Probe {
+    async fn client(&self, route: &Route, url: &reqwest::Url) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .danger_accept_invalid_certs(self.insecure);
+        if self.via_lb {
+            let address = route
+                .addresses
+                .first()
+                .ok_or_else(|| anyhow!("no load balancer address"))?;
+            let host = url.host_str().unwrap_or_default();
+            let port = url.port_or_known_default().unwrap_or(80);
+            // Hostname load balancers (e.g. AWS ELBs) are resolved here
+            let resolved = tokio::net::lookup_host((address.as_str(), port))
+                .await?
+                .next()
+                .ok_or_else(|| anyhow!("{} doesn't resolve", address))?;
+            builder = builder.resolve(host, resolved);
+        }
+        Ok(builder.build()?)
+    }
+
+    async fn test(&self, route: &Route) -> Outcome {
+        let Some(url) = route.testable_url() else {
+            return Outcome::Skipped("wildcard host");
+        };
+        let url = match reqwest::Url::parse(&url) {
+            Ok(url) => url,
+            Err(e) => return Outcome::Failed(e.to_string()),
+        };
+        let client = match self.client(route, &url).await {
+            Ok(client) => client,
+            Err(e) => return Outcome::Failed(e.to_string()),
+        };
+        let started = Instant::now();
+        match client.get(url).send().await {
+            Ok(response) => Outcome::Status {
+                code: response.status().as_u16(),
+                millis: started.elapsed().as_millis(),
+                location: response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .map(str::to_string),
+            },
+            Err(e) => Outcome::Failed(describe(&e)),
+        }
+    }
+
+    /// Outcomes in the order of `routes`
+    pub async fn run(&self, routes: &[&Route]) -> Vec<Outcome> {
+        stream::iter(routes.iter().map(|route| self.test(route)))
+            .buffered(PARALLEL)
+            .collect()
+            .await
+    }
+}
+
+/// The innermost cause, which says what actually went wrong (DNS, refused, TLS)
+fn describe(error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        return "timed out".to_string();
+    }
+    let mut source: &dyn std::error::Error = error;
+    while let Some(inner) = source.source() {
+        source = inner;
+    }
+    source.to_string()
+}
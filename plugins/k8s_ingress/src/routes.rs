@@ -0,0 +1,391 @@
+// Flattens Ingresses and Gateway API HTTPRoutes into one list of routes: the host and
+// path a request comes in on, whether it is HTTPS and with which certificate secret, and
+// the services it ends up at. The Gateway API is read as dynamic objects, so clusters
+// without its CRDs simply have no HTTPRoutes.
+use anyhow::Result;
+use k8s_openapi::api::networking::v1::{Ingress, IngressBackend};
+use kube::api::{ApiResource, DynamicObject, GroupVersionKind, ListParams};
+use kube::{Api, Client};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+const GATEWAY_GROUP: &str = "gateway.networking.k8s.io";
+
+#[derive(Clone)]
+pub struct Backend {
+    pub service: String,
+    pub port: Option<String>,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.port {
+            Some(port) => write!(f, "{}:{}", self.service, port),
+            None => write!(f, "{}", self.service),
+        }
+    }
+}
+
+pub struct Route {
+    /// "ingress" or "httproute"
+    pub kind: &'static str,
+    pub namespace: String,
+    pub name: String,
+    pub https: bool,
+    /// None when any host matches
+    pub host: Option<String>,
+    /// Listener port, when not the scheme's default
+    pub port: Option<u16>,
+    pub path: String,
+    /// e.g. "Prefix", "Exact"
+    pub path_type: String,
+    pub backends: Vec<Backend>,
+    pub tls_secret: Option<String>,
+    /// Load balancer IPs or hostnames the route is served on
+    pub addresses: Vec<String>,
+}
+
+impl Route {
+    pub fn scheme(&self) -> &'static str {
+        if self.https {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// The URL as written, e.g. "https://*.example.com/api"
+    pub fn url(&self) -> String {
+        let host = self
+            .host
+            .as_deref()
+            .or(self.addresses.first().map(String::as_str))
+            .unwrap_or("*");
+        self.url_for(host)
+    }
+
+    /// A URL that can be requested: no wildcard host, and a literal path
+    pub fn testable_url(&self) -> Option<String> {
+        let host = match &self.host {
+            Some(host) if host.contains('*') => return None,
+            Some(host) => host.as_str(),
+            None => self.addresses.first()?,
+        };
+        Some(self.url_for(host))
+    }
+
+    fn url_for(&self, host: &str) -> String {
+        let port = self
+            .port
+            .map(|port| format!(":{}", port))
+            .unwrap_or_default();
+        // Regex paths of ImplementationSpecific rules are tested up to their first pattern
+        let path = match self.path.find(['*', '(', '[', '^', '$']) {
+            Some(end) => &self.path[..end],
+            None => &self.path,
+        };
+        format!("{}://{}{}{}", self.scheme(), host, port, path)
+    }
+
+    pub fn source(&self) -> String {
+        format!("{}/{}/{}", self.kind, self.namespace, self.name)
+    }
+}
+
+/// "*.example.com" matches one or more labels in front of example.com
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix('*') {
+        Some(suffix) => host.len() > suffix.len() && host.ends_with(suffix),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+fn ingress_backend(backend: &IngressBackend) -> Backend {
+    if let Some(service) = &backend.service {
+        let port = service.port.as_ref().and_then(|port| {
+            port.number
+                .map(|number| number.to_string())
+                .or_else(|| port.name.clone())
+        });
+        return Backend {
+            service: service.name.clone(),
+            port,
+        };
+    }
+    match &backend.resource {
+        Some(resource) => Backend {
+            service: format!("{}/{}", resource.kind, resource.name),
+            port: None,
+        },
+        None => Backend {
+            service: "?".to_string(),
+            port: None,
+        },
+    }
+}
+
+fn from_ingress(ingress: &Ingress) -> Vec<Route> {
+    let namespace = ingress.metadata.namespace.clone().unwrap_or_default();
+    let name = ingress.metadata.name.clone().unwrap_or_default();
+    let Some(spec) = &ingress.spec else {
+        return Vec::new();
+    };
+    let addresses: Vec<String> = ingress
+        .status
+        .as_ref()
+        .and_then(|status| status.load_balancer.as_ref())
+        .and_then(|lb| lb.ingress.as_ref())
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.ip.clone().or_else(|| entry.hostname.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let tls = spec.tls.as_deref().unwrap_or_default();
+    // A TLS entry without hosts covers every host of the ingress
+    let tls_for = |host: Option<&str>| {
+        tls.iter().find(|entry| match (&entry.hosts, host) {
+            (None, _) => true,
+            (Some(hosts), _) if hosts.is_empty() => true,
+            (Some(hosts), Some(host)) => hosts.iter().any(|pattern| host_matches(pattern, host)),
+            (Some(_), None) => false,
+        })
+    };
+    let route = |host: Option<&String>, path: String, path_type: String, backend: Backend| {
+        let tls = tls_for(host.map(String::as_str));
+        Route {
+            kind: "ingress",
+            namespace: namespace.clone(),
+            name: name.clone(),
+            https: tls.is_some(),
+            host: host.cloned(),
+            port: None,
+            path,
+            path_type,
+            backends: vec![backend],
+            tls_secret: tls.and_then(|entry| entry.secret_name.clone()),
+            addresses: addresses.clone(),
+        }
+    };
+
+    let mut routes = Vec::new();
+    for rule in spec.rules.iter().flatten() {
+        let Some(http) = &rule.http else {
+            continue;
+        };
+        for path in &http.paths {
+            routes.push(route(
+                rule.host.as_ref(),
+                path.path.clone().unwrap_or_else(|| "/".to_string()),
+                path.path_type.clone(),
+                ingress_backend(&path.backend),
+            ));
+        }
+    }
+    if let Some(backend) = &spec.default_backend {
+        routes.push(route(
+            None,
+            "/".to_string(),
+            "Default".to_string(),
+            ingress_backend(backend),
+        ));
+    }
+    routes
+}
+
+pub async fn ingress_routes(client: &Client, namespace: Option<&str>) -> Result<Vec<Route>> {
+    let api: Api<Ingress> = match namespace {
+        Some(namespace) => Api::namespaced(client.clone(), namespace),
+        None => Api::all(client.clone()),
+    };
+    let ingresses = api.list(&ListParams::default()).await?;
+    Ok(ingresses.items.iter().flat_map(from_ingress).collect())
+}
+
+fn gateway_resource(kind: &str) -> ApiResource {
+    ApiResource::from_gvk(&GroupVersionKind::gvk(GATEWAY_GROUP, "v1", kind))
+}
+
+fn is_not_found(error: &kube::Error) -> bool {
+    matches!(error, kube::Error::Api(response) if response.code == 404)
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+struct Listener {
+    name: String,
+    hostname: Option<String>,
+    port: u16,
+    https: bool,
+    secret: Option<String>,
+}
+
+struct Gateway {
+    listeners: Vec<Listener>,
+    addresses: Vec<String>,
+}
+
+fn parse_gateway(object: &DynamicObject) -> Gateway {
+    let spec = &object.data["spec"];
+    let listeners = spec["listeners"]
+        .as_array()
+        .map(|listeners| {
+            listeners
+                .iter()
+                .filter_map(|listener| {
+                    let protocol = listener["protocol"].as_str()?;
+                    if protocol != "HTTP" && protocol != "HTTPS" {
+                        return None;
+                    }
+                    Some(Listener {
+                        name: text(&listener["name"]).unwrap_or_default(),
+                        hostname: text(&listener["hostname"]),
+                        port: listener["port"].as_u64().unwrap_or(0) as u16,
+                        https: protocol == "HTTPS",
+                        secret: text(&listener["tls"]["certificateRefs"][0]["name"]),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let addresses = object.data["status"]["addresses"]
+        .as_array()
+        .map(|addresses| addresses.iter().filter_map(|a| text(&a["value"])).collect())
+        .unwrap_or_default();
+    Gateway {
+        listeners,
+        addresses,
+    }
+}
+
+/// HTTPRoutes with their parent Gateways' listeners; None when the Gateway API isn't
+/// installed in the cluster
+pub async fn gateway_routes(
+    client: &Client,
+    namespace: Option<&str>,
+) -> Result<Option<Vec<Route>>> {
+    let route_resource = gateway_resource("HTTPRoute");
+    let api: Api<DynamicObject> = match namespace {
+        Some(namespace) => Api::namespaced_with(client.clone(), namespace, &route_resource),
+        None => Api::all_with(client.clone(), &route_resource),
+    };
+    let http_routes = match api.list(&ListParams::default()).await {
+        Ok(list) => list.items,
+        Err(e) if is_not_found(&e) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let gateway_resource = gateway_resource("Gateway");
+    let mut gateways: HashMap<(String, String), Option<Gateway>> = HashMap::new();
+    let mut routes = Vec::new();
+    for http_route in &http_routes {
+        let route_namespace = http_route.metadata.namespace.clone().unwrap_or_default();
+        let name = http_route.metadata.name.clone().unwrap_or_default();
+        let spec = &http_route.data["spec"];
+        let hostnames: Vec<String> = spec["hostnames"]
+            .as_array()
+            .map(|hostnames| hostnames.iter().filter_map(text).collect())
+            .unwrap_or_default();
+
+        for parent in spec["parentRefs"].as_array().into_iter().flatten() {
+            if parent["kind"]
+                .as_str()
+                .is_some_and(|kind| kind != "Gateway")
+            {
+                continue;
+            }
+            let Some(gateway_name) = text(&parent["name"]) else {
+                continue;
+            };
+            let gateway_namespace =
+                text(&parent["namespace"]).unwrap_or_else(|| route_namespace.clone());
+            let key = (gateway_namespace.clone(), gateway_name.clone());
+            if !gateways.contains_key(&key) {
+                let api: Api<DynamicObject> =
+                    Api::namespaced_with(client.clone(), &gateway_namespace, &gateway_resource);
+                let gateway = api.get_opt(&gateway_name).await?.map(|g| parse_gateway(&g));
+                gateways.insert(key.clone(), gateway);
+            }
+            let Some(gateway) = &gateways[&key] else {
+                continue;
+            };
+            let section = parent["sectionName"].as_str();
+            let port = parent["port"].as_u64().map(|port| port as u16);
+
+            for listener in &gateway.listeners {
+                if section.is_some_and(|section| section != listener.name)
+                    || port.is_some_and(|port| port != listener.port)
+                {
+                    continue;
+                }
+                // The route's hostnames narrow the listener's; without any, the listener's
+                let hosts: Vec<Option<String>> = match (&listener.hostname, hostnames.is_empty()) {
+                    (hostname, true) => vec![hostname.clone()],
+                    (None, false) => hostnames.iter().cloned().map(Some).collect(),
+                    (Some(pattern), false) => hostnames
+                        .iter()
+                        .filter(|host| host_matches(pattern, host) || host_matches(host, pattern))
+                        .cloned()
+                        .map(Some)
+                        .collect(),
+                };
+                let default_port = if listener.https { 443 } else { 80 };
+                for rule in spec["rules"].as_array().into_iter().flatten() {
+                    let backends: Vec<Backend> = rule["backendRefs"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|backend| {
+                            let name = text(&backend["name"])?;
+                            let service = match backend["kind"].as_str() {
+                                Some(kind) if kind != "Service" => format!("{}/{}", kind, name),
+                                _ => name,
+                            };
+                            Some(Backend {
+                                service,
+                                port: backend["port"].as_u64().map(|port| port.to_string()),
+                            })
+                        })
+                        .collect();
+                    let mut paths: Vec<(String, String)> = rule["matches"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(|m| {
+                            (
+                                text(&m["path"]["value"]).unwrap_or_else(|| "/".to_string()),
+                                text(&m["path"]["type"])
+                                    .unwrap_or_else(|| "PathPrefix".to_string()),
+                            )
+                        })
+                        .collect();
+                    if paths.is_empty() {
+                        paths.push(("/".to_string(), "PathPrefix".to_string()));
+                    }
+                    for host in &hosts {
+                        for (path, path_type) in &paths {
+                            routes.push(Route {
+                                kind: "httproute",
+                                namespace: route_namespace.clone(),
+                                name: name.clone(),
+                                https: listener.https,
+                                host: host.clone(),
+                                port: (listener.port != default_port).then_some(listener.port),
+                                path: path.clone(),
+                                path_type: path_type.clone(),
+                                backends: backends.clone(),
+                                tls_secret: listener.secret.clone(),
+                                addresses: gateway.addresses.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(Some(routes))
+}
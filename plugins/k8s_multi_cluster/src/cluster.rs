@@ -0,0 +1,167 @@
+// One cluster's end of the aggregate: a client for its kubeconfig context, and the pod
+// behind the service. The pod is looked up again when a port-forward to it fails, so a
+// rollout in one cluster doesn't take its forward down.
+use crate::ClusterConfig;
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::{Pod, Service};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::api::Api;
+use kube::config::KubeConfigOptions;
+use kube::{Client, Config};
+use plugin_api::audit;
+use plugin_api::k8s;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::Mutex;
+
+pub struct Backend {
+    pub name: String,
+    pub context: String,
+    pub namespace: String,
+    pub service: String,
+    /// The service port forwarded to
+    pub port: u16,
+    pods: Api<Pod>,
+    services: Api<Service>,
+    /// The pod and container port last forwarded to
+    target: Mutex<Option<(String, u16)>>,
+}
+
+impl Backend {
+    /// A client for the cluster's context, checked by resolving the service once
+    pub async fn connect(
+        cluster: &ClusterConfig,
+        service: &str,
+        namespace: Option<&str>,
+        port: Option<u16>,
+    ) -> Result<Self> {
+        let options = KubeConfigOptions {
+            context: Some(cluster.context.clone()),
+            ..Default::default()
+        };
+        let config = Config::from_kubeconfig(&options).await?;
+        let namespace = cluster
+            .namespace
+            .as_deref()
+            .or(namespace)
+            .unwrap_or(&config.default_namespace)
+            .to_string();
+        let client = Client::try_from(config)?;
+        let service = cluster.service.as_deref().unwrap_or(service).to_string();
+        let services: Api<Service> = Api::namespaced(client.clone(), &namespace);
+        let spec = services
+            .get(&service)
+            .await?
+            .spec
+            .ok_or_else(|| anyhow!("Service {} has no spec", service))?;
+        let ports = spec.ports.unwrap_or_default();
+        let port = match port {
+            Some(port) => port,
+            None => ports
+                .first()
+                .map(|p| p.port as u16)
+                .ok_or_else(|| anyhow!("Service {} has no ports", service))?,
+        };
+        let backend = Self {
+            name: cluster.name().to_string(),
+            context: cluster.context.clone(),
+            namespace: namespace.clone(),
+            service,
+            port,
+            pods: Api::namespaced(client, &namespace),
+            services,
+            target: Mutex::new(None),
+        };
+        let target = backend.resolve().await?;
+        backend.audit(&target.0, target.1);
+        *backend.target.lock().await = Some(target);
+        Ok(backend)
+    }
+
+    /// Records forwarding to a pod of this cluster in the audit log
+    fn audit(&self, pod: &str, port: u16) {
+        audit::record_k8s(
+            "k8s_multi_cluster",
+            "port_forward",
+            &[
+                ("context", &self.context),
+                ("namespace", &self.namespace),
+                ("service", &self.service),
+                ("pod", pod),
+                ("remote_port", &port.to_string()),
+            ],
+        );
+    }
+
+    /// A running pod behind the service and the container port its port maps to
+    async fn resolve(&self) -> Result<(String, u16)> {
+        let spec = self
+            .services
+            .get(&self.service)
+            .await?
+            .spec
+            .ok_or_else(|| anyhow!("Service {} has no spec", self.service))?;
+        let selector = spec
+            .selector
+            .unwrap_or_default()
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(",");
+        if selector.is_empty() {
+            return Err(anyhow!("Service {} has no selector", self.service));
+        }
+        let service_port = spec
+            .ports
+            .unwrap_or_default()
+            .into_iter()
+            .find(|p| p.port == self.port as i32)
+            .ok_or_else(|| anyhow!("Service {} has no port {}", self.service, self.port))?;
+        let pod = k8s::running_pod(&self.pods, &selector).await?;
+        let pod_port = match service_port.target_port {
+            Some(IntOrString::Int(number)) => number as u16,
+            Some(IntOrString::String(port_name)) => pod
+                .spec
+                .iter()
+                .flat_map(|spec| &spec.containers)
+                .flat_map(|container| container.ports.iter().flatten())
+                .find(|p| p.name.as_deref() == Some(port_name.as_str()))
+                .map(|p| p.container_port as u16)
+                .ok_or_else(|| anyhow!("No container port named {}", port_name))?,
+            None => self.port,
+        };
+        Ok((pod.metadata.name.unwrap_or_default(), pod_port))
+    }
+
+    /// The pod forwarded to, as "pod:port", for messages
+    pub async fn target(&self) -> String {
+        match self.target.lock().await.as_ref() {
+            Some((pod, port)) => format!("{}:{}", pod, port),
+            None => "?".to_string(),
+        }
+    }
+
+    async fn forward(&self, pod: &str, port: u16) -> Result<impl AsyncRead + AsyncWrite + Unpin> {
+        let mut forwarder = self.pods.portforward(pod, &[port]).await?;
+        forwarder
+            .take_stream(port)
+            .ok_or_else(|| anyhow!("Port-forward to {} has no stream for port {}", pod, port))
+    }
+
+    /// A stream to the service's pod, looking the pod up again if the last one is gone
+    pub async fn open(&self) -> Result<(String, impl AsyncRead + AsyncWrite + Unpin)> {
+        let last = self.target.lock().await.clone();
+        if let Some((pod, port)) = &last {
+            if let Ok(stream) = self.forward(pod, *port).await {
+                return Ok((pod.clone(), stream));
+            }
+        }
+        let (pod, port) = self.resolve().await?;
+        let stream = self.forward(&pod, port).await?;
+        if last.is_some_and(|(last, _)| last != pod) {
+            println!("🔄 [{}] Now forwarding to pod {}:{}", self.name, pod, port);
+            self.audit(&pod, port);
+        }
+        *self.target.lock().await = Some((pod.clone(), port));
+        Ok((pod, stream))
+    }
+}
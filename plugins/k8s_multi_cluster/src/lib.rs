@@ -0,0 +1,547 @@
+// The same service in several clusters, side by side on localhost: one forward per
+// kubeconfig context, either on sequential local ports or behind a single port that
+// routes each connection by an HTTP header, the Host or the TLS server name. Useful to
+// compare a service across regions or to check a migration before switching over.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::net;
+use plugin_api::traffic::{log_message, relay_tagged, Protocol};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+
+mod cluster;
+mod route;
+
+use cluster::Backend;
+
+const DEFAULT_LOCAL_PORT: u16 = 18080;
+const DEFAULT_HEADER: &str = "X-Cluster";
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Routing {
+    /// A local port per cluster
+    #[default]
+    Ports,
+    /// One port, routed by a request header or the Host
+    Header,
+    /// One port, routed by the TLS server name
+    Sni,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MultiClusterConfig {
+    /// Service to forward to in every cluster
+    pub service: Option<String>,
+    /// Namespace (default: each context's)
+    pub namespace: Option<String>,
+    /// Service port (default: the service's first)
+    pub port: Option<u16>,
+    /// ports (default), header or sni
+    #[serde(default)]
+    pub routing: Routing,
+    /// ports: the first cluster's port, the others get the ones after it; header and
+    /// sni: the shared port (default 18080)
+    pub local_port: Option<u16>,
+    /// Address to listen on: 127.0.0.1 (default), ::1, or :: for IPv4 and IPv6
+    pub bind: Option<String>,
+    /// header: the request header naming the cluster (default X-Cluster)
+    pub header: Option<String>,
+    /// Message decoding with ports routing: tcp, http, postgres, mysql (default tcp)
+    pub protocol: Option<String>,
+    /// Print the forwarded traffic (default true)
+    pub log_traffic: Option<bool>,
+    #[serde(default)]
+    pub cluster: Vec<ClusterConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ClusterConfig {
+    /// Name used for routing and in the log (default: the context)
+    pub name: Option<String>,
+    /// kubeconfig context
+    pub context: String,
+    /// Overrides for clusters where the service lives elsewhere
+    pub namespace: Option<String>,
+    pub service: Option<String>,
+}
+
+impl ClusterConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.context)
+    }
+}
+
+pub struct MultiClusterPlugin;
+
+impl MultiClusterPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Kubernetes Multi-Cluster Forward Configuration
+service = "checkout"
+namespace = "payments"
+port = 8080
+routing = "ports"  # Options: ports, header, sni
+local_port = 18080  # ports: us-east on 18080, eu-west on 18081
+# bind = "::1"  # default 127.0.0.1; "::" listens on every interface, IPv4 and IPv6
+protocol = "http"  # Options: tcp, http, postgres, mysql
+
+[[cluster]]
+name = "us-east"
+context = "prod-us-east"
+
+[[cluster]]
+name = "eu-west"
+context = "prod-eu-west"
+# namespace = "payments-eu"  # where the service lives elsewhere
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<MultiClusterConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: MultiClusterConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Contexts and the service can all come from the command line
+                Ok(MultiClusterConfig::default())
+            }
+        }
+        None => Ok(MultiClusterConfig::default()),
+    }
+}
+
+/// The backend a name refers to: its name, or the context
+fn pick<'a>(backends: &'a [Arc<Backend>], name: &str) -> Option<&'a Arc<Backend>> {
+    backends
+        .iter()
+        .find(|backend| backend.name.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            backends
+                .iter()
+                .find(|backend| backend.context.eq_ignore_ascii_case(name))
+        })
+}
+
+fn names(backends: &[Arc<Backend>]) -> String {
+    backends
+        .iter()
+        .map(|backend| backend.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+async fn serve_port(
+    backend: Arc<Backend>,
+    bind: String,
+    port: u16,
+    protocol: Option<Protocol>,
+) -> Result<()> {
+    let listener = net::listen(&bind, port).await?;
+    let protocol = Arc::new(protocol);
+    loop {
+        let (client, addr) = listener.accept().await?;
+        let backend = backend.clone();
+        let protocol = protocol.clone();
+        tokio::spawn(async move {
+            match backend.open().await {
+                Ok((pod, stream)) => {
+                    println!(
+                        "📞 [{}] New connection from {} → {}",
+                        backend.name, addr, pod
+                    );
+                    let (reader, writer) = tokio::io::split(stream);
+                    relay_tagged(
+                        &backend.name,
+                        client,
+                        reader,
+                        writer,
+                        protocol.as_ref().as_ref(),
+                    )
+                    .await;
+                    println!("🔌 [{}] Connection from {} closed", backend.name, addr);
+                }
+                Err(e) => eprintln!("❌ [{}] {}", backend.name, e),
+            }
+        });
+    }
+}
+
+/// Forwards an HTTP connection to the cluster its first request asks for
+async fn route_header(
+    backends: &[Arc<Backend>],
+    header: &str,
+    mut client: TcpStream,
+    log_traffic: bool,
+) -> Result<()> {
+    let addr = client.peer_addr()?;
+    let (head, rest) = route::read_head(&mut client).await?;
+    let name = route::from_head(&head, header);
+    let Some(backend) = name.as_deref().and_then(|name| pick(backends, name)) else {
+        let body = format!(
+            "No cluster for this request; set {} to one of: {}\n",
+            header,
+            names(backends)
+        );
+        let response = format!(
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        client.write_all(response.as_bytes()).await?;
+        return Err(anyhow!(
+            "No cluster named '{}' for {}",
+            name.unwrap_or_default(),
+            addr
+        ));
+    };
+
+    let (pod, stream) = backend.open().await?;
+    println!(
+        "📞 [{}] New connection from {} → {}",
+        backend.name, addr, pod
+    );
+    let protocol = log_traffic.then_some(Protocol::Http);
+    if let Some(protocol) = &protocol {
+        log_message(
+            &format!("[{}] → REQUEST", backend.name),
+            protocol,
+            &[head.as_slice(), rest.as_slice()].concat(),
+        );
+    }
+    let (reader, mut writer) = tokio::io::split(stream);
+    writer.write_all(&head).await?;
+    writer.write_all(&rest).await?;
+    // Later requests on a kept-alive connection go to the same cluster
+    relay_tagged(&backend.name, client, reader, writer, protocol.as_ref()).await;
+    println!("🔌 [{}] Connection from {} closed", backend.name, addr);
+    Ok(())
+}
+
+/// Passes a TLS connection to the cluster named by its server name
+async fn route_sni(backends: &[Arc<Backend>], mut client: TcpStream) -> Result<()> {
+    let addr = client.peer_addr()?;
+    let hello = route::read_client_hello(&mut client).await?;
+    let server_name =
+        route::server_name(&hello).ok_or_else(|| anyhow!("No server name from {}", addr))?;
+    let label = server_name.split('.').next().unwrap_or_default();
+    let backend = pick(backends, &server_name)
+        .or_else(|| pick(backends, label))
+        .ok_or_else(|| {
+            anyhow!(
+                "No cluster for {} from {}; the first label should be one of: {}",
+                server_name,
+                addr,
+                names(backends)
+            )
+        })?;
+
+    let (pod, stream) = backend.open().await?;
+    println!(
+        "🔐 [{}] TLS connection for {} from {} → {}",
+        backend.name, server_name, addr, pod
+    );
+    let (reader, mut writer) = tokio::io::split(stream);
+    writer.write_all(&hello).await?;
+    relay_tagged(&backend.name, client, reader, writer, None).await;
+    println!("🔌 [{}] Connection from {} closed", backend.name, addr);
+    Ok(())
+}
+
+async fn serve_shared(
+    backends: Vec<Arc<Backend>>,
+    bind: &str,
+    port: u16,
+    routing: Routing,
+    header: String,
+    log_traffic: bool,
+) -> Result<()> {
+    let listener = net::listen(bind, port).await?;
+    let shared = Arc::new((backends, header));
+    loop {
+        let (client, _) = listener.accept().await?;
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            let (backends, header) = shared.as_ref();
+            let result = match routing {
+                Routing::Sni => route_sni(backends, client).await,
+                _ => route_header(backends, header, client, log_traffic).await,
+            };
+            if let Err(e) = result {
+                eprintln!("❌ {}", e);
+            }
+        });
+    }
+}
+
+async fn start(config: MultiClusterConfig) -> Result<()> {
+    let service = config
+        .service
+        .clone()
+        .ok_or_else(|| anyhow!("No service given; use --service or set it in the config"))?;
+    if config.cluster.is_empty() {
+        println!("📝 Sample config:\n{}", MultiClusterPlugin::sample_config());
+        return Err(anyhow!(
+            "No clusters given; use --context or add [[cluster]] entries"
+        ));
+    }
+
+    ctrlc::set_handler(move || {
+        println!("\n👋 Shutting down...");
+        std::process::exit(0);
+    })?;
+
+    println!(
+        "🚀 Forwarding {} from {} cluster(s)",
+        service,
+        config.cluster.len()
+    );
+    let local_port = config.local_port.unwrap_or(DEFAULT_LOCAL_PORT);
+    let bind = config
+        .bind
+        .clone()
+        .unwrap_or_else(|| net::LOOPBACK.to_string());
+    // Connected together; a cluster that can't be reached is left out
+    let connections = config.cluster.iter().map(|cluster| {
+        Backend::connect(cluster, &service, config.namespace.as_deref(), config.port)
+    });
+    let mut backends = Vec::new();
+    for (index, (cluster, result)) in config
+        .cluster
+        .iter()
+        .zip(futures::future::join_all(connections).await)
+        .enumerate()
+    {
+        match result {
+            Ok(backend) => backends.push((index, Arc::new(backend))),
+            Err(e) => eprintln!(
+                "❌ [{}] Skipping context {}: {}",
+                cluster.name(),
+                cluster.context,
+                e
+            ),
+        }
+    }
+    if backends.is_empty() {
+        return Err(anyhow!("None of the clusters could be reached"));
+    }
+
+    let log_traffic = config.log_traffic.unwrap_or(true);
+    if config.routing == Routing::Ports {
+        let protocol =
+            log_traffic.then(|| Protocol::from(config.protocol.as_deref().unwrap_or("tcp")));
+        let mut handles = Vec::new();
+        for (index, backend) in backends {
+            // Ports follow the config order, so a skipped cluster doesn't shift the others
+            let port = local_port + index as u16;
+            println!(
+                "🎧 [{}] {} → svc/{}:{} in {}/{} ({})",
+                backend.name,
+                net::host_port(&bind, port),
+                backend.service,
+                backend.port,
+                backend.context,
+                backend.namespace,
+                backend.target().await
+            );
+            let protocol = protocol.clone();
+            let bind = bind.clone();
+            handles.push(tokio::spawn(async move {
+                let name = backend.name.clone();
+                if let Err(e) = serve_port(backend, bind, port, protocol).await {
+                    eprintln!("❌ [{}] {}", name, e);
+                }
+            }));
+        }
+        for handle in handles {
+            let _ = handle.await;
+        }
+        return Ok(());
+    }
+
+    let header = config
+        .header
+        .clone()
+        .unwrap_or_else(|| DEFAULT_HEADER.to_string());
+    for (_, backend) in &backends {
+        println!(
+            "🎯 [{}] svc/{}:{} in {}/{} ({})",
+            backend.name,
+            backend.service,
+            backend.port,
+            backend.context,
+            backend.namespace,
+            backend.target().await
+        );
+    }
+    let first = &backends[0].1.name;
+    match config.routing {
+        Routing::Sni => println!(
+            "🎧 {}, routed by TLS server name (https://{}.localhost:{})",
+            net::host_port(&bind, local_port),
+            first,
+            local_port
+        ),
+        _ => println!(
+            "🎧 {}, routed by the {} header or the Host (http://{}.localhost:{})",
+            net::host_port(&bind, local_port),
+            header,
+            first,
+            local_port
+        ),
+    }
+    let backends = backends.into_iter().map(|(_, backend)| backend).collect();
+    serve_shared(
+        backends,
+        &bind,
+        local_port,
+        config.routing,
+        header,
+        log_traffic,
+    )
+    .await
+}
+
+impl Plugin for MultiClusterPlugin {
+    fn name(&self) -> &'static str {
+        "k8s_multi_cluster"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Forward the same service from several clusters, on sequential ports or one routed port"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Forward a service from several kubeconfig contexts at once")
+            .arg(
+                Arg::new("context")
+                    .long("context")
+                    .short('c')
+                    .value_name("CONTEXT")
+                    .action(ArgAction::Append)
+                    .value_delimiter(',')
+                    .help("kubeconfig contexts to use instead of the configured clusters"),
+            )
+            .arg(
+                Arg::new("service")
+                    .long("service")
+                    .short('s')
+                    .value_name("SERVICE")
+                    .help("Override the service from config file"),
+            )
+            .arg(
+                Arg::new("namespace")
+                    .long("namespace")
+                    .short('n')
+                    .value_name("NAMESPACE")
+                    .help("Override the namespace from config file"),
+            )
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .short('p')
+                    .value_name("PORT")
+                    .value_parser(clap::value_parser!(u16))
+                    .help("Service port (default: the service's first)"),
+            )
+            .arg(
+                Arg::new("local-port")
+                    .long("local-port")
+                    .short('l')
+                    .value_name("PORT")
+                    .value_parser(clap::value_parser!(u16))
+                    .help("First local port, or the shared one with header or sni routing"),
+            )
+            .arg(
+                Arg::new("routing")
+                    .long("routing")
+                    .value_name("ROUTING")
+                    .value_parser(["ports", "header", "sni"])
+                    .help("A port per cluster, or one port routed by header or TLS server name"),
+            )
+            .arg(
+                Arg::new("protocol")
+                    .long("protocol")
+                    .value_name("PROTOCOL")
+                    .value_parser(["tcp", "http", "postgres", "mysql"])
+                    .help("Protocol for message decoding with ports routing"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(MultiClusterPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<MultiClusterConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let mut config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if let Some(contexts) = matches.get_many::<String>("context") {
+            config.cluster = contexts
+                .map(|context| ClusterConfig {
+                    name: None,
+                    context: context.clone(),
+                    namespace: None,
+                    service: None,
+                })
+                .collect();
+        }
+        if let Some(service) = matches.get_one::<String>("service") {
+            config.service = Some(service.clone());
+        }
+        if let Some(namespace) = matches.get_one::<String>("namespace") {
+            config.namespace = Some(namespace.clone());
+        }
+        if let Some(port) = matches.get_one::<u16>("port") {
+            config.port = Some(*port);
+        }
+        if let Some(port) = matches.get_one::<u16>("local-port") {
+            config.local_port = Some(*port);
+        }
+        if let Some(routing) = matches.get_one::<String>("routing") {
+            config.routing = match routing.as_str() {
+                "header" => Routing::Header,
+                "sni" => Routing::Sni,
+                _ => Routing::Ports,
+            };
+        }
+        if let Some(protocol) = matches.get_one::<String>("protocol") {
+            config.protocol = Some(protocol.clone());
+        }
+
+        plugin_api::metrics::init(self.name());
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        if let Err(e) = rt.block_on(start(config)) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(MultiClusterPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
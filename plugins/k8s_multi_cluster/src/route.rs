@@ -0,0 +1,95 @@
+// Picking the cluster for a connection on the shared port. HTTP requests name it in a
+// header, or in the first label of the Host (us-east.localhost:18080). TLS connections
+// name it in the first label of the SNI server name of the ClientHello, which is
+// forwarded untouched, so the service's own certificates are what the client sees.
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const MAX_HEAD: usize = 64 * 1024;
+/// TLS records are at most 16 KiB plus some overhead
+const MAX_RECORD: usize = 16 * 1024 + 2048;
+
+/// Reads up to the end of the HTTP request head; returns the head and what followed it
+pub async fn read_head<C: AsyncRead + Unpin>(client: &mut C) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buffer.split_off(end + 4);
+            return Ok((buffer, rest));
+        }
+        if buffer.len() > MAX_HEAD {
+            return Err(anyhow!("Request head too large"));
+        }
+        let n = client.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(anyhow!("Client closed before sending a request"));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// The cluster a request asks for: the header's value, else the Host's first label
+pub fn from_head(head: &[u8], header: &str) -> Option<String> {
+    let text = String::from_utf8_lossy(head);
+    let value = |name: &str| {
+        text.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+    value(header).or_else(|| {
+        let host = value("host")?;
+        host.split(['.', ':']).next().map(str::to_string)
+    })
+}
+
+/// Reads the first TLS record, which holds the ClientHello
+pub async fn read_client_hello<C: AsyncRead + Unpin>(client: &mut C) -> Result<Vec<u8>> {
+    let mut record = vec![0u8; 5];
+    client.read_exact(&mut record).await?;
+    // Content type 22 is a handshake
+    if record[0] != 22 {
+        return Err(anyhow!("Not a TLS connection"));
+    }
+    let length = u16::from_be_bytes([record[3], record[4]]) as usize;
+    if length > MAX_RECORD {
+        return Err(anyhow!("TLS record too large"));
+    }
+    record.resize(5 + length, 0);
+    client.read_exact(&mut record[5..]).await?;
+    Ok(record)
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<usize> {
+    Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]) as usize)
+}
+
+/// The SNI server name of a ClientHello record
+pub fn server_name(record: &[u8]) -> Option<String> {
+    // Record header, handshake type and length, client version, random
+    if record.get(5) != Some(&1) {
+        return None;
+    }
+    let mut at = 5 + 4 + 2 + 32;
+    at += 1 + *record.get(at)? as usize;
+    at += 2 + u16_at(record, at)?;
+    at += 1 + *record.get(at)? as usize;
+    let end = (at + 2 + u16_at(record, at)?).min(record.len());
+    at += 2;
+    while at + 4 <= end {
+        let kind = u16_at(record, at)?;
+        let length = u16_at(record, at + 2)?;
+        at += 4;
+        if kind == 0 {
+            // A list with one host_name entry: list length, name type, name length
+            let name_length = u16_at(record, at + 3)?;
+            let name = record.get(at + 5..at + 5 + name_length)?;
+            return String::from_utf8(name.to_vec()).ok();
+        }
+        at += length;
+    }
+    None
+}
@@ -0,0 +1,499 @@
+// Capture files for `proxy ollama_chat analyze`. With `capture` set, every forwarded HTTP
+// exchange and PostgreSQL simple query is appended to the file as one JSON record per
+// line:
+//
+//   {"time":"2026-01-02T10:00:00.000Z","pod":"default/web","method":"GET","path":"/health","status":200,"duration_ms":3.2}
+//   {"time":"2026-01-02T10:00:01.000Z","pod":"default/db","query":"SELECT 1","result":"SELECT 1","duration_ms":0.8}
+//
+// The time is when the request was read. The duration of an HTTP request runs until the
+// status line of its response, skipping interim 1xx ones; a PostgreSQL query's runs until
+// the server is ready for the next one, its result listing the tag of every statement in
+// it. Other protocols get one record per connection, with the bytes sent each way and how
+// long it stayed open.
+use chrono::{SecondsFormat, Utc};
+use plugin_api::traffic::Protocol;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Startup message codes of the PostgreSQL requests that aren't followed by messages
+const SSL_REQUEST: u32 = 80877103;
+const GSSENC_REQUEST: u32 = 80877104;
+const CANCEL_REQUEST: u32 = 80877102;
+/// The longest startup message a PostgreSQL server accepts
+const MAX_STARTUP: usize = 10_000;
+
+/// The file records are appended to, shared by every connection
+#[derive(Clone)]
+pub struct Capture {
+    file: Arc<Mutex<File>>,
+    pod: String,
+}
+
+/// A request waiting for its response
+struct Pending {
+    record: Value,
+    started: Instant,
+    /// The tags of the statements of a PostgreSQL query completed so far
+    results: Vec<String>,
+}
+
+/// Where a PostgreSQL connection is
+#[derive(Debug, PartialEq)]
+enum Postgres {
+    /// Before the startup message, which has no type byte
+    Startup,
+    /// After an SSL or GSSAPI encryption request, answered by a single byte
+    Negotiating,
+    Messages,
+    /// Encrypted, or not framed as expected: nothing more of it is recorded
+    Opaque,
+}
+
+/// The bytes of a stream that aren't a complete message yet
+#[derive(Default)]
+struct Framer {
+    buffer: Vec<u8>,
+    /// What's left of the body of a message that isn't kept
+    skip: usize,
+}
+
+/// A stream that isn't framed as PostgreSQL messages
+struct Garbled;
+
+/// What one connection has sent so far
+pub struct Connection {
+    capture: Capture,
+    protocol: Protocol,
+    /// In the order their responses come back; `None` for a PostgreSQL sync or function
+    /// call, which gets a ReadyForQuery of its own
+    pending: VecDeque<Option<Pending>>,
+    postgres: Postgres,
+    client: Framer,
+    server: Framer,
+    opened: (String, Instant),
+    sent: u64,
+    received: u64,
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn millis(since: Instant) -> f64 {
+    (since.elapsed().as_secs_f64() * 1000.0 * 10.0).round() / 10.0
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// The method and path of an HTTP request line at the start of `data`
+fn request_line(data: &[u8]) -> Option<(String, String)> {
+    let line = data.split(|&b| b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?.trim_end();
+    let mut parts = line.split(' ');
+    let (method, path, version) = (parts.next()?, parts.next()?, parts.next()?);
+    let method_like = !method.is_empty() && method.bytes().all(|b| b.is_ascii_uppercase());
+    (method_like && version.starts_with("HTTP/")).then(|| (method.to_string(), path.to_string()))
+}
+
+/// The status of an HTTP status line at the start of `data`
+fn status_line(data: &[u8]) -> Option<u16> {
+    let rest = data.strip_prefix(b"HTTP/")?;
+    let line = rest.split(|&b| b == b'\n').next()?;
+    std::str::from_utf8(line)
+        .ok()?
+        .split(' ')
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+fn be_u32(bytes: &[u8]) -> usize {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+}
+
+impl Framer {
+    fn push(&mut self, data: &[u8]) {
+        let skipped = self.skip.min(data.len());
+        self.skip -= skipped;
+        self.buffer.extend_from_slice(&data[skipped..]);
+    }
+
+    /// The code of the startup message, once it is complete
+    fn startup(&mut self) -> Result<Option<u32>, Garbled> {
+        if self.buffer.len() < 4 {
+            return Ok(None);
+        }
+        let length = be_u32(&self.buffer);
+        if !(8..=MAX_STARTUP).contains(&length) {
+            return Err(Garbled);
+        }
+        if self.buffer.len() < length {
+            return Ok(None);
+        }
+        let code = be_u32(&self.buffer[4..]) as u32;
+        self.buffer.drain(..length);
+        Ok(Some(code))
+    }
+
+    /// The next complete message, as (type, body). The body of a message `keep` doesn't
+    /// want is empty, and skipped as it arrives rather than buffered.
+    fn message(&mut self, keep: impl Fn(u8) -> bool) -> Result<Option<(u8, Vec<u8>)>, Garbled> {
+        if self.skip > 0 || self.buffer.len() < 5 {
+            return Ok(None);
+        }
+        let (kind, length) = (self.buffer[0], be_u32(&self.buffer[1..]));
+        if length < 4 {
+            return Err(Garbled);
+        }
+        let body = length - 4;
+        if !keep(kind) {
+            let available = (self.buffer.len() - 5).min(body);
+            self.buffer.drain(..5 + available);
+            self.skip = body - available;
+            return Ok(Some((kind, Vec::new())));
+        }
+        if self.buffer.len() < 5 + body {
+            return Ok(None);
+        }
+        let message = self.buffer[5..5 + body].to_vec();
+        self.buffer.drain(..5 + body);
+        Ok(Some((kind, message)))
+    }
+}
+
+impl Capture {
+    /// Opens `path` for appending, the records naming `pod`
+    pub fn open(path: &Path, pod: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+            pod: pod.to_string(),
+        })
+    }
+
+    fn write(&self, mut record: Value) {
+        record["pod"] = Value::from(self.pod.as_str());
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", record) {
+            eprintln!("⚠️  Failed to write the capture: {}", e);
+        }
+    }
+
+    pub fn connection(&self, protocol: &Protocol) -> Connection {
+        Connection {
+            capture: self.clone(),
+            protocol: protocol.clone(),
+            pending: VecDeque::new(),
+            postgres: Postgres::Startup,
+            client: Framer::default(),
+            server: Framer::default(),
+            opened: (now(), Instant::now()),
+            sent: 0,
+            received: 0,
+        }
+    }
+}
+
+impl Connection {
+    /// Takes a chunk the client sent
+    pub fn request(&mut self, data: &[u8]) {
+        self.sent += data.len() as u64;
+        match self.protocol {
+            Protocol::Http => {
+                if let Some((method, path)) = request_line(data) {
+                    self.wait(json!({ "time": now(), "method": method, "path": path }));
+                }
+            }
+            Protocol::Postgres => self.client_messages(data),
+            _ => {}
+        }
+    }
+
+    /// Takes a chunk the pod sent back
+    pub fn response(&mut self, data: &[u8]) {
+        self.received += data.len() as u64;
+        match self.protocol {
+            Protocol::Http => match status_line(data) {
+                // 101 Switching Protocols is the final response, other 1xx ones come first
+                Some(status) if status != 101 && (100..200).contains(&status) => {}
+                Some(status) => self.finish(|pending| {
+                    pending.record["status"] = Value::from(status);
+                }),
+                None => {}
+            },
+            Protocol::Postgres => self.server_messages(data),
+            _ => {}
+        }
+    }
+
+    fn wait(&mut self, record: Value) {
+        self.pending.push_back(Some(Pending {
+            record,
+            started: Instant::now(),
+            results: Vec::new(),
+        }));
+    }
+
+    fn client_messages(&mut self, data: &[u8]) {
+        self.client.push(data);
+        loop {
+            let next = match self.postgres {
+                Postgres::Startup => self.client.startup().map(|code| {
+                    code.map(|code| {
+                        self.postgres = match code {
+                            SSL_REQUEST | GSSENC_REQUEST => Postgres::Negotiating,
+                            CANCEL_REQUEST => Postgres::Opaque,
+                            _ => Postgres::Messages,
+                        };
+                    })
+                }),
+                Postgres::Messages => self.client.message(|kind| kind == b'Q').map(|message| {
+                    message.map(|(kind, body)| match kind {
+                        b'Q' => self.wait(json!({ "time": now(), "query": text(&body) })),
+                        b'S' | b'F' => self.pending.push_back(None),
+                        _ => {}
+                    })
+                }),
+                Postgres::Negotiating | Postgres::Opaque => return,
+            };
+            match next {
+                Ok(Some(())) => {}
+                Ok(None) => return,
+                Err(Garbled) => self.postgres = Postgres::Opaque,
+            }
+        }
+    }
+
+    fn server_messages(&mut self, mut data: &[u8]) {
+        match self.postgres {
+            Postgres::Negotiating => {
+                let Some((&answer, rest)) = data.split_first() else {
+                    return;
+                };
+                // Declined, so the client sends its startup message in the clear
+                self.postgres = match answer {
+                    b'N' => Postgres::Startup,
+                    _ => Postgres::Opaque,
+                };
+                data = rest;
+            }
+            Postgres::Opaque => return,
+            _ => {}
+        }
+        self.server.push(data);
+        loop {
+            match self
+                .server
+                .message(|kind| matches!(kind, b'C' | b'E' | b'Z'))
+            {
+                Ok(Some((b'C', body))) => self.result(text(&body)),
+                Ok(Some((b'E', _))) => self.result("error".to_string()),
+                // ReadyForQuery: everything the oldest query or sync asked for has come
+                Ok(Some((b'Z', _))) => self.finish(|pending| {
+                    if !pending.results.is_empty() {
+                        pending.record["result"] = Value::from(pending.results.join("; "));
+                    }
+                }),
+                Ok(Some(_)) => {}
+                Ok(None) => return,
+                Err(Garbled) => {
+                    self.postgres = Postgres::Opaque;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Adds the outcome of a statement to the oldest pending query
+    fn result(&mut self, result: String) {
+        if let Some(Some(pending)) = self.pending.front_mut() {
+            pending.results.push(result);
+        }
+    }
+
+    /// Writes the oldest pending request with its outcome
+    fn finish(&mut self, outcome: impl FnOnce(&mut Pending)) {
+        if let Some(Some(mut pending)) = self.pending.pop_front() {
+            outcome(&mut pending);
+            pending.record["duration_ms"] = Value::from(millis(pending.started));
+            self.capture.write(pending.record);
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if matches!(self.protocol, Protocol::Http | Protocol::Postgres) {
+            return;
+        }
+        let (time, opened) = &self.opened;
+        self.capture.write(json!({
+            "time": time,
+            "protocol": format!("{:?}", self.protocol).to_lowercase(),
+            "request_bytes": self.sent,
+            "response_bytes": self.received,
+            "duration_ms": millis(*opened),
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A capture in a fresh file under the temp directory
+    fn capture(name: &str) -> (Capture, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "k8s-native-capture-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        (Capture::open(&path, "default/web").unwrap(), path)
+    }
+
+    /// A protocol 3.0 startup message without parameters
+    const STARTUP: &[u8] = b"\0\0\0\x08\0\x03\0\0";
+
+    fn records(path: &Path) -> Vec<Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn http_exchanges_are_recorded_in_order() {
+        let (capture, path) = capture("http");
+        let mut connection = capture.connection(&Protocol::Http);
+        connection.request(b"GET /health HTTP/1.1\r\nHost: web\r\n\r\n");
+        connection.request(b"POST /orders?id=1 HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}");
+        connection.response(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+        connection.response(b"HTTP/1.1 503 Service Unavailable\r\n\r\n");
+        drop(connection);
+
+        let records = records(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["method"], "GET");
+        assert_eq!(records[0]["path"], "/health");
+        assert_eq!(records[0]["status"], 200);
+        assert_eq!(records[1]["path"], "/orders?id=1");
+        assert_eq!(records[1]["status"], 503);
+        assert_eq!(records[1]["pod"], "default/web");
+        assert!(records[1]["duration_ms"].is_f64());
+    }
+
+    #[test]
+    fn postgres_queries_end_when_the_server_is_ready() {
+        let (capture, path) = capture("postgres");
+        let mut connection = capture.connection(&Protocol::Postgres);
+        connection.request(STARTUP);
+        connection.response(b"R\0\0\0\x08\0\0\0\0Z\0\0\0\x05I");
+        connection.request(b"Q\0\0\0\x0eSELECT 1;\0");
+        // Row description and data row first, then CommandComplete and ReadyForQuery
+        connection.response(b"T\0\0\0\x06\0\0D\0\0\0\x06\0\0C\0\0\0\x0dSELECT 1\0Z\0\0\0\x05I");
+        drop(connection);
+
+        let records = records(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["query"], "SELECT 1;");
+        assert_eq!(records[0]["result"], "SELECT 1");
+    }
+
+    #[test]
+    fn postgres_statements_of_one_query_share_its_record() {
+        let (capture, path) = capture("postgres-multi");
+        let mut connection = capture.connection(&Protocol::Postgres);
+        connection.request(STARTUP);
+        connection.request(b"Q\0\0\0\x1cBEGIN; SELECT 1; COMMIT\0");
+        connection.request(b"Q\0\0\0\x0dSELECT 2\0");
+        connection.response(b"C\0\0\0\x0aBEGIN\0C\0\0\0\x0dSELECT 1\0C\0\0\0\x0bCOMMIT\0");
+        connection.response(b"Z\0\0\0\x05I");
+        connection.response(b"E\0\0\0\x05\0Z\0\0\0\x05I");
+        drop(connection);
+
+        let records = records(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["query"], "BEGIN; SELECT 1; COMMIT");
+        assert_eq!(records[0]["result"], "BEGIN; SELECT 1; COMMIT");
+        assert_eq!(records[1]["query"], "SELECT 2");
+        assert_eq!(records[1]["result"], "error");
+    }
+
+    #[test]
+    fn postgres_messages_split_across_reads_are_put_together() {
+        let (capture, path) = capture("postgres-split");
+        let mut connection = capture.connection(&Protocol::Postgres);
+        // Declined SSL first, then the startup message in the clear
+        connection.request(b"\0\0\0\x08\x04\xd2\x16\x2f");
+        connection.response(b"N");
+        connection.request(&STARTUP[..3]);
+        connection.request(&STARTUP[3..]);
+        connection.request(b"Q\0\0\0\x0eSEL");
+        connection.request(b"ECT 1;\0Q\0\0\0\x0dSELECT 2\0");
+        // A data row split inside its body, then the CommandComplete split in its length
+        connection.response(b"D\0\0\0\x0a\0\x01");
+        connection.response(b"\0\0\0\x00C\0\0");
+        connection.response(b"\0\x0dSELECT 1\0Z\0\0\0\x05I");
+        connection.response(b"C\0\0\0\x0dSELECT 1\0Z\0\0\0\x05I");
+        drop(connection);
+
+        let records = records(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["query"], "SELECT 1;");
+        assert_eq!(records[0]["result"], "SELECT 1");
+        assert_eq!(records[1]["query"], "SELECT 2");
+        assert_eq!(records[1]["result"], "SELECT 1");
+    }
+
+    #[test]
+    fn http_interim_responses_are_skipped() {
+        let (capture, path) = capture("http-continue");
+        let mut connection = capture.connection(&Protocol::Http);
+        connection.request(b"PUT /upload HTTP/1.1\r\nExpect: 100-continue\r\n\r\n");
+        connection.response(b"HTTP/1.1 100 Continue\r\n\r\n");
+        connection.request(b"payload");
+        connection.request(b"GET /health HTTP/1.1\r\n\r\n");
+        connection.response(b"HTTP/1.1 201 Created\r\n\r\n");
+        connection.response(b"HTTP/1.1 200 OK\r\n\r\n");
+        drop(connection);
+
+        let records = records(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["path"], "/upload");
+        assert_eq!(records[0]["status"], 201);
+        assert_eq!(records[1]["path"], "/health");
+        assert_eq!(records[1]["status"], 200);
+    }
+
+    #[test]
+    fn other_protocols_get_a_record_per_connection() {
+        let (capture, path) = capture("tcp");
+        let mut connection = capture.connection(&Protocol::Tcp);
+        connection.request(b"ping");
+        connection.response(b"pong!");
+        drop(connection);
+
+        let records = records(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["protocol"], "tcp");
+        assert_eq!(records[0]["request_bytes"], 4);
+        assert_eq!(records[0]["response_bytes"], 5);
+    }
+}
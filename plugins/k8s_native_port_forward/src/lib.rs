@@ -1,24 +1,31 @@
 use clap::{Arg, ArgMatches, Command};
-use plugin_api::Plugin;
+use plugin_api::AsyncPlugin;
 use serde::Deserialize;
-use std::fs;
-use tokio::runtime::Runtime;
 use anyhow::Result;
-use tokio::net::{TcpListener, TcpStream};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use kube::{Api, Client};
 use k8s_openapi::api::core::v1::Pod;
 use std::sync::Arc;
-use chrono::Utc;
+use plugin_api::net;
+use plugin_api::traffic::{log_message, Protocol};
+use capture::Capture;
+
+mod capture;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct K8sNativeConfig {
     pub namespace: String,
     pub pod_name: Option<String>,
     pub pod_selector: Option<String>, // label selector
-    pub local_port: u16,
-    pub remote_port: u16,
+    #[serde(default)]
+    pub local_port: u16, // unused when bind is a Unix socket
+    #[serde(default)]
+    pub remote_port: u16, // unused with remote_socket
+    pub bind: Option<String>, // 127.0.0.1 (default), ::1, :: for IPv4 and IPv6, or a Unix socket path
+    pub socket_mode: Option<u32>, // permissions of the Unix socket, e.g. 0o660
+    pub remote_socket: Option<String>, // Unix socket in the pod, e.g. /var/run/docker.sock
     pub protocol: Option<String>, // http, postgres, tcp (default)
+    pub capture: Option<String>, // JSON lines file for `proxy ollama_chat analyze`
 }
 
 impl Default for K8sNativeConfig {
@@ -29,7 +36,11 @@ impl Default for K8sNativeConfig {
             pod_selector: None,
             local_port: 8080,
             remote_port: 80,
+            bind: None,
+            socket_mode: None,
+            remote_socket: None,
             protocol: Some("tcp".to_string()),
+            capture: None,
         }
     }
 }
@@ -44,7 +55,14 @@ pod_name = "my-pod"  # Either use pod_name OR pod_selector
 # pod_selector = "app=nginx,version=v1"  # Label selector alternative
 local_port = 8080
 remote_port = 80
+# bind = "::1"  # default 127.0.0.1; "::" listens on every interface, IPv4 and IPv6
 protocol = "http"  # Options: tcp, http, postgres
+# capture = "/tmp/my-pod.jsonl"  # Appends a record per request, for `proxy ollama_chat analyze`
+
+# For a Unix socket on either end (the pod needs socat or nc -U for remote_socket):
+# bind = "/tmp/my-pod-docker.sock"
+# socket_mode = 0o600
+# remote_socket = "/var/run/docker.sock"
 
 # Example configurations:
 # For HTTP service:
@@ -63,28 +81,11 @@ protocol = "http"  # Options: tcp, http, postgres
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Protocol {
-    Tcp,
-    Http,
-    Postgres,
-}
-
-impl From<&str> for Protocol {
-    fn from(s: &str) -> Self {
-        match s.to_lowercase().as_str() {
-            "http" => Protocol::Http,
-            "postgres" | "postgresql" => Protocol::Postgres,
-            _ => Protocol::Tcp,
-        }
-    }
-}
-
 fn load_config(plugin_name: &str) -> Result<K8sNativeConfig> {
     match plugin_api::plugin_config_path(plugin_name) {
         Some(config_path) => {
-            if config_path.exists() {
-                let content = fs::read_to_string(config_path)?;
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
                 let config: K8sNativeConfig = toml::from_str(&content)?;
                 Ok(config)
             } else {
@@ -101,111 +102,6 @@ fn load_config(plugin_name: &str) -> Result<K8sNativeConfig> {
     }
 }
 
-fn log_message(direction: &str, protocol: &Protocol, data: &[u8]) {
-    let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string();
-
-    match protocol {
-        Protocol::Http => log_http_message(direction, data, &timestamp),
-        Protocol::Postgres => log_postgres_message(direction, data, &timestamp),
-        Protocol::Tcp => log_tcp_message(direction, data, &timestamp),
-    }
-}
-
-fn log_http_message(direction: &str, data: &[u8], timestamp: &str) {
-    if let Ok(text) = std::str::from_utf8(data) {
-        // Try to parse as HTTP
-        if text.starts_with("GET ") || text.starts_with("POST ") ||
-           text.starts_with("PUT ") || text.starts_with("DELETE ") ||
-           text.starts_with("HTTP/") {
-            println!("🌐 [{}] {} HTTP Message:", timestamp, direction);
-
-            // Split headers and body
-            if let Some(header_end) = text.find("\r\n\r\n") {
-                let headers = &text[..header_end];
-                let body = &text[header_end + 4..];
-
-                println!("   Headers:");
-                for line in headers.lines() {
-                    println!("     {}", line);
-                }
-
-                if !body.is_empty() {
-                    println!("   Body:");
-                    println!("     {}", body);
-                }
-            } else {
-                println!("   {}", text);
-            }
-        } else {
-            log_tcp_message(direction, data, timestamp);
-        }
-    } else {
-        log_tcp_message(direction, data, timestamp);
-    }
-}
-
-fn log_postgres_message(direction: &str, data: &[u8], timestamp: &str) {
-    if data.is_empty() {
-        return;
-    }
-
-    println!("🐘 [{}] {} PostgreSQL Message:", timestamp, direction);
-
-    // Basic PostgreSQL protocol parsing
-    if data.len() >= 5 {
-        let msg_type = data[0] as char;
-        let length = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
-
-        match msg_type {
-            'Q' => {
-                if let Ok(query) = std::str::from_utf8(&data[5..]) {
-                    println!("   Query: {}", query.trim_end_matches('\0'));
-                }
-            }
-            'P' => println!("   Parse message (length: {})", length),
-            'B' => println!("   Bind message (length: {})", length),
-            'E' => println!("   Execute message (length: {})", length),
-            'S' => println!("   Sync message"),
-            'X' => println!("   Terminate message"),
-            'T' => println!("   Row Description (length: {})", length),
-            'D' => println!("   Data Row (length: {})", length),
-            'C' => {
-                if let Ok(command) = std::str::from_utf8(&data[5..]) {
-                    println!("   Command Complete: {}", command.trim_end_matches('\0'));
-                }
-            }
-            'Z' => println!("   Ready for Query"),
-            'R' => println!("   Authentication Response (length: {})", length),
-            _ => {
-                println!("   Unknown message type '{}' (length: {})", msg_type, length);
-                println!("   Raw data: {}", hex::encode(&data[..std::cmp::min(50, data.len())]));
-            }
-        }
-    } else {
-        log_tcp_message(direction, data, timestamp);
-    }
-}
-
-fn log_tcp_message(direction: &str, data: &[u8], timestamp: &str) {
-    println!("🔌 [{}] {} TCP Message ({} bytes):", timestamp, direction, data.len());
-
-    // Show first 100 bytes as hex and try to show as text if printable
-    let preview_len = std::cmp::min(100, data.len());
-    let preview = &data[..preview_len];
-
-    println!("   Hex: {}", hex::encode(preview));
-
-    if let Ok(text) = std::str::from_utf8(preview) {
-        if text.chars().all(|c| c.is_ascii() && (c.is_ascii_graphic() || c.is_ascii_whitespace())) {
-            println!("   Text: {}", text.replace('\n', "\\n").replace('\r', "\\r"));
-        }
-    }
-
-    if data.len() > preview_len {
-        println!("   ... ({} more bytes)", data.len() - preview_len);
-    }
-}
-
 async fn find_pod_by_selector(client: &Client, namespace: &str, selector: &str) -> Result<String> {
     let pods: Api<Pod> = Api::namespaced(client.clone(), namespace);
 
@@ -232,14 +128,44 @@ async fn find_pod_by_selector(client: &Client, namespace: &str, selector: &str)
     Ok(pod_name.clone())
 }
 
+/// The command run in the pod that connects its stdin and stdout to the remote end
+fn exec_command(remote_port: u16, remote_socket: Option<String>) -> Vec<String> {
+    // Use bash with /dev/tcp for bidirectional TCP connection
+    // This works in most containers that have bash without additional tools
+    // The script:
+    // 1. Opens a bidirectional connection to localhost:port via file descriptor 3
+    // 2. Starts background process to copy from FD 3 to stdout
+    // 3. Copies from stdin to FD 3 in foreground
+    // 4. When stdin closes, kills the background job and closes FD 3
+    match remote_socket {
+        // A Unix socket needs socat or nc -U in the container; the path is passed as $1
+        Some(path) => vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "if command -v socat >/dev/null 2>&1; then exec socat - UNIX-CONNECT:\"$1\"; fi; exec nc -U \"$1\"".to_string(),
+            "sh".to_string(),
+            path,
+        ],
+        None => vec![
+            "bash".to_string(),
+            "-c".to_string(),
+            format!(
+                "exec 3<>/dev/tcp/localhost/{}; (cat <&3 &); cat >&3; kill %1 2>/dev/null; exec 3>&-",
+                remote_port
+            ),
+        ],
+    }
+}
+
 // Handle connection using native Kubernetes API
 async fn handle_native_connection(
-    mut client_stream: TcpStream,
+    client_stream: net::Stream,
     k8s_client: Client,
     namespace: String,
     pod_name: String,
-    remote_port: u16,
+    exec_command: Vec<String>,
     protocol: Protocol,
+    capture: Option<Capture>,
 ) -> Result<()> {
     use kube::api::AttachParams;
 
@@ -259,29 +185,17 @@ async fn handle_native_connection(
         max_stderr_buf_size: None,
     };
 
-    // Use bash with /dev/tcp for bidirectional TCP connection
-    // This works in most containers that have bash without additional tools
-    // The script:
-    // 1. Opens a bidirectional connection to localhost:port via file descriptor 3
-    // 2. Starts background process to copy from FD 3 to stdout
-    // 3. Copies from stdin to FD 3 in foreground
-    // 4. When stdin closes, kills the background job and closes FD 3
-    let exec_command = vec![
-        "bash".to_string(),
-        "-c".to_string(),
-        format!(
-            "exec 3<>/dev/tcp/localhost/{}; (cat <&3 &); cat >&3; kill %1 2>/dev/null; exec 3>&-",
-            remote_port
-        ),
-    ];
-
     let mut attached = pods
         .exec(&pod_name, exec_command, &attach_params)
         .await?;
 
     println!("✅ Connected to pod via native Kubernetes API");
+    let session = plugin_api::usage::session_k8s(&format!("{}/{}", namespace, pod_name));
+    let session = &session;
+    let capture = capture.map(|capture| std::sync::Mutex::new(capture.connection(&protocol)));
+    let capture = &capture;
 
-    let (mut client_read, mut client_write) = client_stream.split();
+    let (mut client_read, mut client_write) = tokio::io::split(client_stream);
 
     let protocol_clone = protocol.clone();
     let protocol_clone2 = protocol.clone();
@@ -299,6 +213,10 @@ async fn handle_native_connection(
                 Ok(n) => {
                     let data = &buffer[..n];
                     log_message("→ REQUEST", &protocol_clone, data);
+                    session.add_bytes(n as u64, 0);
+                    if let Some(capture) = capture {
+                        capture.lock().unwrap_or_else(|e| e.into_inner()).request(data);
+                    }
 
                     if let Err(e) = pod_stdin.write_all(data).await {
                         eprintln!("Error writing to pod: {}", e);
@@ -323,6 +241,10 @@ async fn handle_native_connection(
                 Ok(n) => {
                     let data = &buffer[..n];
                     log_message("← RESPONSE", &protocol_clone2, data);
+                    session.add_bytes(0, n as u64);
+                    if let Some(capture) = capture {
+                        capture.lock().unwrap_or_else(|e| e.into_inner()).response(data);
+                    }
 
                     if let Err(e) = client_write.write_all(data).await {
                         eprintln!("Error writing to client: {}", e);
@@ -357,9 +279,19 @@ async fn start_port_forward(config: K8sNativeConfig, protocol_override: Option<S
     println!("🚀 Starting Kubernetes Native Port Forward with Message Logging");
     println!("📡 Namespace: {}", config.namespace);
     println!("🎯 Protocol: {:?}", protocol);
-    println!("🔌 Local port: {}", config.local_port);
-    println!("🎯 Remote port (kubectl): {}", config.remote_port);
-    println!("🎯 Remote port (pod): {}", config.remote_port);
+    let bind = config.bind.as_deref().unwrap_or(net::LOOPBACK);
+    if net::unix_path(bind).is_none() && config.local_port == 0 {
+        return Err(anyhow::anyhow!("Must specify local_port unless bind is a Unix socket"));
+    }
+    if config.remote_socket.is_none() && config.remote_port == 0 {
+        return Err(anyhow::anyhow!("Must specify either remote_port or remote_socket"));
+    }
+    let remote = match &config.remote_socket {
+        Some(path) => path.clone(),
+        None => config.remote_port.to_string(),
+    };
+    println!("🔌 Local: {}", net::listen_address(bind, config.local_port));
+    println!("🎯 Remote (pod): {}", remote);
 
     // Create Kubernetes client
     let k8s_client = Client::try_default().await?;
@@ -377,6 +309,16 @@ async fn start_port_forward(config: K8sNativeConfig, protocol_override: Option<S
         return Err(anyhow::anyhow!("Must specify either pod_name or pod_selector"));
     };
 
+    let capture = match &config.capture {
+        Some(path) => {
+            let capture = Capture::open(std::path::Path::new(path), &format!("{}/{}", config.namespace, pod_name))
+                .map_err(|e| anyhow::anyhow!("Failed to open capture file {}: {}", path, e))?;
+            println!("📼 Capturing requests to {}", path);
+            Some(capture)
+        }
+        None => None,
+    };
+
     println!("📝 Strategy: Using native Kubernetes API (exec + socat)");
     println!("   This uses the Kubernetes API SDK directly without kubectl\n");
 
@@ -389,18 +331,30 @@ async fn start_port_forward(config: K8sNativeConfig, protocol_override: Option<S
         std::process::exit(0);
     })?;
 
-    println!("🎧 Listening on 127.0.0.1:{}", config.local_port);
-    println!("🔄 Forwarding to pod {}:{} via native K8s API", pod_name, config.remote_port);
+    println!("🎧 Listening on {}", net::listen_address(bind, config.local_port));
+    println!("🔄 Forwarding to pod {}:{} via native K8s API", pod_name, remote);
     println!("⚡ Ready to log {} traffic", match protocol {
         Protocol::Http => "HTTP",
         Protocol::Postgres => "PostgreSQL",
+        Protocol::Mysql => "MySQL",
         Protocol::Tcp => "TCP",
     });
 
     println!();
 
     // Start listening for connections
-    let listener = TcpListener::bind(format!("127.0.0.1:{}", config.local_port)).await?;
+    let listener = net::bind(bind, config.local_port, config.socket_mode).await?;
+    plugin_api::audit::record_k8s(
+        "k8s_native_port_forward",
+        "exec_transport",
+        &[
+            ("namespace", &config.namespace),
+            ("pod", &pod_name),
+            ("local_port", &net::listen_address(bind, config.local_port)),
+            ("remote_port", &remote),
+        ],
+    );
+    plugin_api::usage::init("k8s_native_port_forward");
 
     while running.load(std::sync::atomic::Ordering::SeqCst) {
         match listener.accept().await {
@@ -411,7 +365,8 @@ async fn start_port_forward(config: K8sNativeConfig, protocol_override: Option<S
                 let namespace_clone = config.namespace.clone();
                 let protocol_clone = protocol.clone();
                 let client_clone = k8s_client.clone();
-                let remote_port = config.remote_port;
+                let exec_command = exec_command(config.remote_port, config.remote_socket.clone());
+                let capture = capture.clone();
 
                 tokio::spawn(async move {
                     if let Err(e) = handle_native_connection(
@@ -419,8 +374,9 @@ async fn start_port_forward(config: K8sNativeConfig, protocol_override: Option<S
                         client_clone,
                         namespace_clone,
                         pod_name_clone,
-                        remote_port,
+                        exec_command,
                         protocol_clone,
+                        capture,
                     ).await {
                         eprintln!("❌ Connection error: {}", e);
                     }
@@ -435,7 +391,7 @@ async fn start_port_forward(config: K8sNativeConfig, protocol_override: Option<S
     Ok(())
 }
 
-impl Plugin for K8sNativePortForwardPlugin {
+impl AsyncPlugin for K8sNativePortForwardPlugin {
     fn name(&self) -> &'static str {
         "k8s_native_port_forward"
     }
@@ -495,75 +451,92 @@ impl Plugin for K8sNativePortForwardPlugin {
                     .help("Protocol for message decoding: tcp, http, postgres")
                     .value_parser(["tcp", "http", "postgres"]),
             )
+            .arg(
+                Arg::new("capture")
+                    .long("capture")
+                    .value_name("FILE")
+                    .help("Append a JSON record per request to FILE, for `proxy ollama_chat analyze`"),
+            )
     }
 
-    fn run(&self, matches: &ArgMatches) {
-        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(K8sNativePortForwardPlugin::sample_config())
+    }
 
-        rt.block_on(async {
-            let mut config = match load_config(self.name()) {
-                Ok(config) => config,
-                Err(e) => {
-                    eprintln!("❌ Failed to load config: {}", e);
-                    std::process::exit(1);
-                }
-            };
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<K8sNativeConfig>(config)
+    }
 
-            // Override config with command line arguments
-            if let Some(pod) = matches.get_one::<String>("pod") {
-                if pod.is_empty() {
-                    eprintln!("❌ Pod name cannot be empty");
-                    std::process::exit(1);
-                }
-                config.pod_name = Some(pod.clone());
-                config.pod_selector = None; // Clear selector if pod name is specified
+    async fn run(&self, matches: &ArgMatches) {
+        let mut config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
             }
+        };
 
-            if let Some(selector) = matches.get_one::<String>("selector") {
-                if selector.is_empty() {
-                    eprintln!("❌ Pod selector cannot be empty");
-                    std::process::exit(1);
-                }
-                config.pod_selector = Some(selector.clone());
-                config.pod_name = None; // Clear pod name if selector is specified
+        // Override config with command line arguments
+        if let Some(pod) = matches.get_one::<String>("pod") {
+            if pod.is_empty() {
+                eprintln!("❌ Pod name cannot be empty");
+                std::process::exit(1);
             }
+            config.pod_name = Some(pod.clone());
+            config.pod_selector = None; // Clear selector if pod name is specified
+        }
 
-            if let Some(namespace) = matches.get_one::<String>("namespace") {
-                if namespace.is_empty() {
-                    eprintln!("❌ Namespace cannot be empty");
-                    std::process::exit(1);
-                }
-                config.namespace = namespace.clone();
+        if let Some(selector) = matches.get_one::<String>("selector") {
+            if selector.is_empty() {
+                eprintln!("❌ Pod selector cannot be empty");
+                std::process::exit(1);
             }
+            config.pod_selector = Some(selector.clone());
+            config.pod_name = None; // Clear pod name if selector is specified
+        }
 
-            if let Some(local_port) = matches.get_one::<u16>("local-port") {
-                config.local_port = *local_port;
+        if let Some(namespace) = matches.get_one::<String>("namespace") {
+            if namespace.is_empty() {
+                eprintln!("❌ Namespace cannot be empty");
+                std::process::exit(1);
             }
+            config.namespace = namespace.clone();
+        }
 
-            if let Some(remote_port) = matches.get_one::<u16>("remote-port") {
-                config.remote_port = *remote_port;
-            }
+        if let Some(local_port) = matches.get_one::<u16>("local-port") {
+            config.local_port = *local_port;
+        }
 
-            // Validate that either pod name or selector is provided
-            if config.pod_name.is_none() && config.pod_selector.is_none() {
-                eprintln!("❌ Must specify either --pod or --selector (or configure in config file)");
-                eprintln!("💡 Example: proxy k8s_native_port_forward --pod my-pod --local-port 8080 --remote-port 80");
-                eprintln!("💡 Example: proxy k8s_native_port_forward --selector app=nginx --local-port 8080 --remote-port 80");
-                std::process::exit(1);
-            }
+        if let Some(remote_port) = matches.get_one::<u16>("remote-port") {
+            config.remote_port = *remote_port;
+        }
 
-            let protocol_override = matches.get_one::<String>("protocol").cloned();
+        // Validate that either pod name or selector is provided
+        if config.pod_name.is_none() && config.pod_selector.is_none() {
+            eprintln!("❌ Must specify either --pod or --selector (or configure in config file)");
+            eprintln!("💡 Example: proxy k8s_native_port_forward --pod my-pod --local-port 8080 --remote-port 80");
+            eprintln!("💡 Example: proxy k8s_native_port_forward --selector app=nginx --local-port 8080 --remote-port 80");
+            std::process::exit(1);
+        }
 
-            if let Err(e) = start_port_forward(config, protocol_override).await {
-                eprintln!("❌ Port forward error: {}", e);
-                std::process::exit(1);
-            }
-        });
+        if let Some(capture) = matches.get_one::<String>("capture") {
+            config.capture = Some(capture.clone());
+        }
+
+        let protocol_override = matches.get_one::<String>("protocol").cloned();
+
+        if let Err(e) = start_port_forward(config, protocol_override).await {
+            eprintln!("❌ Port forward error: {}", e);
+            std::process::exit(1);
+        }
     }
 }
 
 #[no_mangle]
 #[allow(improper_ctypes_definitions)]
-pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+pub extern "C" fn create_plugin() -> Box<dyn plugin_api::Plugin> {
     Box::new(K8sNativePortForwardPlugin)
 }
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,53 @@
+// TCP probes through the local end of a forward.
+//
+// kubectl port-forward keeps listening locally even when the stream to the pod is
+// gone, so a plain connect() is not enough: kubectl accepts the connection and then
+// closes it as soon as it fails to open the remote stream. A probe therefore connects
+// and waits briefly for the socket to be closed. Receiving data or hitting the read
+// timeout both mean the stream to the pod is alive.
+use std::io::{ErrorKind, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Default number of consecutive failed probes before a forward is restarted
+pub const DEFAULT_FAILURES: u32 = 3;
+
+/// Where to reach a forward listening on kubectl's --address: the first address, the
+/// matching loopback for a wildcard and 127.0.0.1 for localhost
+fn probe_addr(address: Option<&str>, local_port: u16) -> Option<SocketAddr> {
+    let host = address
+        .and_then(|address| address.split(',').next())
+        .map(|host| plugin_api::net::unbracket(host.trim()))
+        .unwrap_or("localhost");
+    let ip = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        Ok(IpAddr::V6(ip)) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        Ok(ip) => ip,
+        Err(_) if host == "localhost" => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        Err(_) => return (host, local_port).to_socket_addrs().ok()?.next(),
+    };
+    Some(SocketAddr::new(ip, local_port))
+}
+
+/// Returns true when the forward on `local_port` still reaches the remote side
+pub fn probe(address: Option<&str>, local_port: u16) -> bool {
+    let Some(addr) = probe_addr(address, local_port) else {
+        return false;
+    };
+    let mut stream = match TcpStream::connect_timeout(&addr, PROBE_TIMEOUT) {
+        Ok(stream) => stream,
+        Err(_) => return false,
+    };
+    if stream.set_read_timeout(Some(PROBE_TIMEOUT)).is_err() {
+        return false;
+    }
+
+    let mut buf = [0u8; 1];
+    match stream.read(&mut buf) {
+        Ok(0) => false,
+        Ok(_) => true,
+        Err(e) => matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut),
+    }
+}
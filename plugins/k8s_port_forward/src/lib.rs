@@ -1,25 +1,145 @@
 // --- Module scope ---
 use clap::{Arg, ArgMatches, Command};
+use plugin_api::net;
 use plugin_api::Plugin;
 // Removed unused log imports
 use serde::Deserialize;
 use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command as ProcessCommand;
-use std::process::Stdio;
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Deserialize)]
+mod keepalive;
+mod lifecycle;
+mod state;
+
+#[derive(Debug, Default, Deserialize)]
 pub struct ForwardConfig {
+    #[serde(default)]
     pub forward: Vec<PortForward>,
+    /// Glob pattern(s) of forward fragment files, relative to this config file
+    pub include: Option<Include>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Include {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Include {
+    fn patterns(&self) -> &[String] {
+        match self {
+            Include::One(pattern) => std::slice::from_ref(pattern),
+            Include::Many(patterns) => patterns,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct PortForward {
     pub name: Option<String>,
-    pub labels: Option<String>, // e.g. "app=nginx,version=v1"
+    pub labels: Option<String>,         // e.g. "app=nginx,version=v1"
+    pub field_selector: Option<String>, // e.g. "status.phase=Running,spec.nodeName=node-1"
     pub namespace: String,
     pub r#type: String, // "pod" or "service"
     pub local_port: u16,
-    pub remote_port: u16,
+    pub remote_port: RemotePort,
+    /// Local addresses kubectl listens on (--address), e.g. "::1" or "0.0.0.0,::";
+    /// localhost, both 127.0.0.1 and ::1, by default
+    pub address: Option<String>,
+    /// Forward to every pod matched by `labels` on local_port, local_port+1, ...
+    #[serde(default)]
+    pub fan_out: bool,
+    /// Seconds between TCP probes through the local port; probing is off when unset
+    pub keepalive_interval: Option<u64>,
+    /// Consecutive failed probes before the forward is restarted (default 3)
+    pub keepalive_failures: Option<u32>,
+    /// kubectl context this forward is meant for; a mismatch asks for confirmation
+    pub expected_context: Option<String>,
+}
+
+/// Remote port given either as a number or as a port name from the resource spec
+#[derive(Debug, Deserialize, Clone)]
+#[serde(try_from = "RemotePortValue")]
+pub enum RemotePort {
+    Number(u16),
+    Named(String),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RemotePortValue {
+    Number(u16),
+    Named(String),
+}
+
+impl TryFrom<RemotePortValue> for RemotePort {
+    type Error = String;
+
+    /// A name has to be a valid Kubernetes port name (an IANA service name), which also
+    /// keeps it safe to put in a kubectl jsonpath
+    fn try_from(value: RemotePortValue) -> Result<Self, Self::Error> {
+        match value {
+            RemotePortValue::Number(port) => Ok(RemotePort::Number(port)),
+            RemotePortValue::Named(name) => {
+                let valid = (1..=15).contains(&name.len())
+                    && name
+                        .bytes()
+                        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+                    && name.bytes().any(|b| b.is_ascii_lowercase())
+                    && !name.starts_with('-')
+                    && !name.ends_with('-')
+                    && !name.contains("--");
+                if valid {
+                    Ok(RemotePort::Named(name))
+                } else {
+                    Err(format!(
+                        "invalid port name '{}': at most 15 lowercase letters, digits and \
+                         single inner hyphens, with at least one letter",
+                        name
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for RemotePort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemotePort::Number(port) => write!(f, "{}", port),
+            RemotePort::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl PortForward {
+    /// Human readable description of the label/field selectors of this forward
+    fn selector_desc(&self) -> String {
+        match (&self.labels, &self.field_selector) {
+            (Some(labels), Some(fields)) => format!("labels '{}' and fields '{}'", labels, fields),
+            (Some(labels), None) => format!("labels '{}'", labels),
+            (None, Some(fields)) => format!("fields '{}'", fields),
+            (None, None) => "no selector".to_string(),
+        }
+    }
+
+    /// Short description of the forward target used in status output
+    fn target_desc(&self) -> String {
+        match (&self.name, &self.labels, &self.field_selector) {
+            (Some(name), None, None) => name.clone(),
+            (_, Some(labels), None) => format!("labels:{}", labels),
+            (_, None, Some(fields)) => format!("fields:{}", fields),
+            (_, Some(labels), Some(fields)) => format!("labels:{} fields:{}", labels, fields),
+            _ => "invalid-config".to_string(),
+        }
+    }
 }
 
 pub struct ProxyPlugin;
@@ -27,12 +147,17 @@ pub struct ProxyPlugin;
 impl ProxyPlugin {
     /// Returns a sample config file for this plugin (TOML format)
     pub fn sample_config() -> &'static str {
-        r#"[[forward]]
+        r#"# Extra forward files merged into this one (k8s_port_forward.conf.d/ is always merged)
+# include = "forwards.d/*.toml"
+
+[[forward]]
 name = "my-service"
 namespace = "default"
 type = "service"
 local_port = 8080
 remote_port = 80
+expected_context = "dev-cluster"  # asks for confirmation when kubectl points elsewhere
+# address = "::1"  # kubectl --address; default localhost (127.0.0.1 and ::1)
 
 [[forward]]
 labels = "app=nginx,version=v1"
@@ -41,23 +166,344 @@ type = "pod"
 local_port = 9090
 remote_port = 9000
 
+[[forward]]
+labels = "app=worker"
+field_selector = "status.phase=Running"  # combined with labels when listing pods
+namespace = "default"
+type = "pod"
+local_port = 7000
+remote_port = 8080
+fan_out = true  # one forward per matched pod on 7000, 7001, ...
+
 [[forward]]
 name = "my-pod"
 namespace = "default"
 type = "pod"
 local_port = 3000
 remote_port = 3000
+keepalive_interval = 30  # probe every 30s, restart after 3 failed probes
+keepalive_failures = 3
+
+[[forward]]
+name = "my-api"
+namespace = "default"
+type = "service"
+local_port = 8081
+remote_port = "http"  # resolved from the Service's port names
 "#
     }
 }
 
 fn load_config(plugin_name: &str) -> Option<ForwardConfig> {
     let config_path = plugin_api::plugin_config_path(plugin_name)?;
-    let content = fs::read_to_string(config_path).ok()?;
-    toml::from_str(&content).ok()
+    // A project defining its forwards gets exactly those, not the home fragments too
+    let fragments_dir = plugin_api::plugin_config_fragments_dir(plugin_name)
+        .filter(|dir| dir.is_dir())
+        .filter(|_| !plugin_api::project::defines(plugin_name, "forward"));
+
+    // The main file may be omitted entirely when forwards live in fragments
+    let mut cfg: ForwardConfig = match plugin_api::read_config(&config_path) {
+        Ok(content) => match toml::from_str(&content) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                eprintln!("❌ {}: {}", config_path.display(), e);
+                return None;
+            }
+        },
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            eprintln!("❌ {}", e);
+            return None;
+        }
+        Err(_) if fragments_dir.is_some() => ForwardConfig::default(),
+        Err(_) => return None,
+    };
+
+    let mut fragments = Vec::new();
+    if let Some(include) = &cfg.include {
+        let base = config_path.parent().unwrap_or(Path::new("."));
+        for pattern in include.patterns() {
+            let full_pattern = base.join(pattern);
+            match glob::glob(&full_pattern.to_string_lossy()) {
+                Ok(paths) => {
+                    let mut matched: Vec<PathBuf> = paths.flatten().collect();
+                    matched.sort();
+                    fragments.extend(matched);
+                }
+                Err(e) => eprintln!("Invalid include pattern '{}': {}", pattern, e),
+            }
+        }
+    }
+    if let Some(dir) = fragments_dir {
+        if let Ok(entries) = fs::read_dir(&dir) {
+            let mut matched: Vec<PathBuf> = entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    matches!(
+                        path.extension().and_then(|ext| ext.to_str()),
+                        Some("toml") | Some("conf")
+                    )
+                })
+                .collect();
+            matched.sort();
+            fragments.extend(matched);
+        }
+    }
+
+    for path in fragments {
+        let fragment: ForwardConfig = match plugin_api::read_config(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| toml::from_str(&content).map_err(|e| e.to_string()))
+        {
+            Ok(fragment) => fragment,
+            Err(e) => {
+                eprintln!("Skipping config fragment {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if fragment.include.is_some() {
+            eprintln!(
+                "Ignoring 'include' in config fragment {} (only allowed in the main config)",
+                path.display()
+            );
+        }
+        cfg.forward.extend(fragment.forward);
+    }
+
+    Some(cfg)
+}
+
+/// Lists the resources of `kind` matching label and/or field selectors, as `kind/name` strings
+fn list_resources_by_selector(
+    kind: &str,
+    labels: Option<&str>,
+    field_selector: Option<&str>,
+    namespace: &str,
+) -> Option<Vec<String>> {
+    let mut list_cmd = ProcessCommand::new("kubectl");
+    list_cmd.arg("get").arg(kind);
+    if let Some(labels) = labels {
+        list_cmd.arg("-l").arg(labels);
+    }
+    if let Some(field_selector) = field_selector {
+        list_cmd.arg("--field-selector").arg(field_selector);
+    }
+    list_cmd
+        .arg("-n")
+        .arg(namespace)
+        .arg("--no-headers")
+        .arg("-o")
+        .arg("name");
+
+    match list_cmd.output() {
+        Ok(output) if !output.status.success() => {
+            eprintln!(
+                "Failed to list {} resources: {}",
+                kind,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Ok(output) => Some(
+            std::str::from_utf8(&output.stdout)
+                .unwrap_or("")
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+        Err(e) => {
+            eprintln!("Failed to list {} resources: {}", kind, e);
+            None
+        }
+    }
+}
+
+/// Resolves a named port against a `kind/name` resource: Service port names for
+/// services, container port names for pods
+fn resolve_remote_port(resource: &str, namespace: &str, port: &RemotePort) -> Option<u16> {
+    let name = match port {
+        RemotePort::Number(port) => return Some(*port),
+        RemotePort::Named(name) => name,
+    };
+
+    let jsonpath = if resource.starts_with("svc/") || resource.starts_with("service/") {
+        format!("jsonpath={{.spec.ports[?(@.name==\"{}\")].port}}", name)
+    } else {
+        format!(
+            "jsonpath={{.spec.containers[*].ports[?(@.name==\"{}\")].containerPort}}",
+            name
+        )
+    };
+
+    let output = match ProcessCommand::new("kubectl")
+        .arg("get")
+        .arg(resource)
+        .arg("-n")
+        .arg(namespace)
+        .arg("-o")
+        .arg(jsonpath)
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            eprintln!(
+                "Failed to look up port '{}' on {}: {}",
+                name,
+                resource,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return None;
+        }
+        Err(e) => {
+            eprintln!("Failed to look up port '{}' on {}: {}", name, resource, e);
+            return None;
+        }
+    };
+
+    let resolved = std::str::from_utf8(&output.stdout)
+        .unwrap_or("")
+        .split_whitespace()
+        .next()
+        .and_then(|port| port.parse::<u16>().ok());
+    match resolved {
+        Some(port) => {
+            println!("Resolved port '{}' on {} to {}", name, resource, port);
+            Some(port)
+        }
+        None => {
+            eprintln!("No port named '{}' found on {}", name, resource);
+            None
+        }
+    }
+}
+
+/// Resolves the forward config into the list of `(resource, local_port)` pairs to forward
+fn resolve_targets(fwd: &PortForward, kind: &str) -> Option<Vec<(String, u16)>> {
+    // Handle name vs selectors
+    match (&fwd.name, &fwd.labels, &fwd.field_selector) {
+        (Some(name), None, None) => Some(vec![(format!("{}/{}", kind, name), fwd.local_port)]),
+        (_, labels, field_selector) if labels.is_some() || field_selector.is_some() => {
+            let selector = fwd.selector_desc();
+            // First, list matching resources to show what we found
+            let resources = list_resources_by_selector(
+                kind,
+                labels.as_deref(),
+                field_selector.as_deref(),
+                &fwd.namespace,
+            )?;
+
+            if resources.is_empty() {
+                eprintln!("No {} found matching {}", kind, selector);
+                return None;
+            } else if resources.len() > 1 {
+                println!(
+                    "Found {} {}(s) matching {}: {}",
+                    resources.len(),
+                    kind,
+                    selector,
+                    resources.join(", ")
+                );
+            } else {
+                println!("Found {} matching {}: {}", kind, selector, resources[0]);
+            }
+
+            if !fwd.fan_out || resources.len() == 1 {
+                if resources.len() > 1 {
+                    println!("Using the first one: {}", resources[0]);
+                }
+                // Use the actual name of the first resource
+                return Some(vec![(resources[0].clone(), fwd.local_port)]);
+            }
+
+            // One forward per matched resource on consecutive local ports
+            let mut targets = Vec::with_capacity(resources.len());
+            for (offset, resource) in resources.into_iter().enumerate() {
+                let Some(local_port) = u16::try_from(offset)
+                    .ok()
+                    .and_then(|offset| fwd.local_port.checked_add(offset))
+                else {
+                    eprintln!(
+                        "Not enough local ports above {} to fan out to every match, stopping at {}",
+                        fwd.local_port,
+                        targets.len()
+                    );
+                    break;
+                };
+                targets.push((resource, local_port));
+            }
+            println!("Fanning out to {} forwards:", targets.len());
+            for (resource, local_port) in &targets {
+                println!(
+                    "  {}:{} -> {}",
+                    resource,
+                    fwd.remote_port,
+                    local_end(fwd.address.as_deref(), *local_port)
+                );
+            }
+            Some(targets)
+        }
+        _ => {
+            eprintln!(
+                "Must specify either 'name' or 'labels'/'field_selector' for port-forward config"
+            );
+            None
+        }
+    }
+}
+
+/// Compares the active kubectl context with the one the forward expects, asking the
+/// user to confirm on mismatch. Returns the context to run kubectl in, the expected one
+/// or the current one the user confirmed, or `None` when the forward must not be started.
+fn confirm_context(fwd: &PortForward, expected: &str) -> Option<String> {
+    let current = match ProcessCommand::new("kubectl")
+        .arg("config")
+        .arg("current-context")
+        .output()
+    {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        Err(e) => {
+            eprintln!("Failed to read the current kubectl context: {}", e);
+            return None;
+        }
+    };
+    if current == expected {
+        return Some(current);
+    }
+
+    eprintln!(
+        "Warning: forward {} expects kubectl context '{}' but the current context is '{}'",
+        fwd.target_desc(),
+        expected,
+        if current.is_empty() {
+            "<none>"
+        } else {
+            &current
+        }
+    );
+    if current.is_empty() {
+        eprintln!("Skipping forward {}", fwd.target_desc());
+        return None;
+    }
+    if !std::io::stdin().is_terminal() {
+        eprintln!("Refusing to start the forward without an interactive confirmation");
+        return None;
+    }
+
+    print!("Start the forward anyway? [y/N] ");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return None;
+    }
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        eprintln!("Skipping forward {}", fwd.target_desc());
+        return None;
+    }
+    Some(current)
 }
 
-fn spawn_kubectl_port_forward(fwd: &PortForward) {
+fn spawn_kubectl_port_forward(plugin_name: &str, fwd: &PortForward) {
     let kind = match fwd.r#type.as_str() {
         "pod" => "pod",
         "service" => "svc",
@@ -67,119 +513,248 @@ fn spawn_kubectl_port_forward(fwd: &PortForward) {
         }
     };
 
-    let port_map = format!("{}:{}", fwd.local_port, fwd.remote_port);
-    let mut cmd = ProcessCommand::new("kubectl");
-    cmd.arg("port-forward");
+    // Pinned for every kubectl started, restarts included, so switching contexts
+    // meanwhile doesn't move the forward to another cluster
+    let context = match &fwd.expected_context {
+        Some(expected) => match confirm_context(fwd, expected) {
+            Some(context) => Some(context),
+            None => return,
+        },
+        None => None,
+    };
 
-    // Handle name vs labels
-    match (&fwd.name, &fwd.labels) {
-        (Some(name), None) => {
-            let target = format!("{}/{}", kind, name);
-            cmd.arg(target);
-        }
-        (_, Some(labels)) => {
-            // First, list matching resources to show what we found
-            let mut list_cmd = ProcessCommand::new("kubectl");
-            list_cmd
-                .arg("get")
-                .arg(kind)
-                .arg("-l")
-                .arg(labels)
-                .arg("-n")
-                .arg(&fwd.namespace)
-                .arg("--no-headers")
-                .arg("-o")
-                .arg("name");
-
-            match list_cmd.output() {
-                Ok(output) => {
-                    let resources: Vec<&str> = std::str::from_utf8(&output.stdout)
-                        .unwrap_or("")
-                        .lines()
-                        .filter(|line| !line.is_empty())
-                        .collect();
-
-                    if resources.is_empty() {
-                        eprintln!("No {} found matching labels: {}", kind, labels);
-                        return;
-                    } else if resources.len() > 1 {
-                        println!(
-                            "Found {} {}(s) matching labels '{}': {}",
-                            resources.len(),
-                            kind,
-                            labels,
-                            resources.join(", ")
-                        );
-                        println!("Using the first one: {}", resources[0]);
-                    } else {
-                        println!(
-                            "Found {} matching labels '{}': {}",
-                            kind, labels, resources[0]
-                        );
-                    }
+    let Some(targets) = resolve_targets(fwd, kind) else {
+        return;
+    };
 
-                    // Use the actual name of the first resource
-                    cmd.arg(resources[0]);
-                }
-                Err(e) => {
-                    eprintln!("Failed to list resources with labels {}: {}", labels, e);
-                    return;
-                }
+    let mut tasks = Vec::with_capacity(targets.len());
+    for (resource, local_port) in targets {
+        let Some(remote_port) = resolve_remote_port(&resource, &fwd.namespace, &fwd.remote_port)
+        else {
+            continue;
+        };
+        tasks.push(ForwardTask {
+            resource,
+            namespace: fwd.namespace.clone(),
+            context: context.clone(),
+            address: fwd.address.clone(),
+            local_port,
+            remote_port,
+        });
+    }
+
+    if tasks.is_empty() {
+        return;
+    }
+
+    let target_desc = fwd.target_desc();
+    println!(
+        "Spawning {} kubectl port-forward(s) for {} (blocking, Ctrl-C will terminate)",
+        tasks.len(),
+        target_desc
+    );
+
+    let keepalive = fwd.keepalive_interval.map(|secs| {
+        let failures = fwd
+            .keepalive_failures
+            .unwrap_or(keepalive::DEFAULT_FAILURES)
+            .max(1);
+        println!(
+            "Keepalive: probing every {}s, restarting after {} consecutive failures",
+            secs, failures
+        );
+        (Duration::from_secs(secs.max(1)), failures)
+    });
+
+    // One slot per forward holding its current kubectl process, so Ctrl-C
+    // reaches processes started by restarts as well
+    let running = Arc::new(AtomicBool::new(true));
+    let slots: Arc<Vec<ChildSlot>> = Arc::new(tasks.iter().map(|_| Mutex::new(None)).collect());
+
+    // Set up Ctrl-C handler to kill every child
+    let r = running.clone();
+    let handler_slots = slots.clone();
+    let _ = ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+        // Try to kill the child processes
+        for slot in handler_slots.iter() {
+            if let Some(handle) = slot.lock().unwrap().as_ref() {
+                handle.terminate();
             }
         }
-        (None, None) => {
-            eprintln!("Must specify either 'name' or 'labels' for port-forward config");
-            return;
+    });
+
+    let tracker = state::StateTracker::new(
+        plugin_name,
+        tasks
+            .iter()
+            .map(|task| {
+                state::ForwardState::new(
+                    &task.resource,
+                    &task.namespace,
+                    task.local_port,
+                    task.address.as_deref(),
+                    task.remote_port,
+                )
+            })
+            .collect(),
+    );
+
+    std::thread::scope(|scope| {
+        for (index, (task, slot)) in tasks.iter().zip(slots.iter()).enumerate() {
+            let running = &running;
+            let tracker = &tracker;
+            scope.spawn(move || supervise(task, index, keepalive, running, slot, tracker));
         }
-    }
+    });
+    running.store(false, Ordering::SeqCst);
+    tracker.finish();
+}
 
-    cmd.arg(port_map)
+/// The local end of a forward for messages: its first address, localhost by default
+fn local_end(address: Option<&str>, port: u16) -> String {
+    let address = address.and_then(|address| address.split(',').next());
+    net::host_port(address.unwrap_or("localhost").trim(), port)
+}
+
+/// A single kubectl port-forward, after label and named port resolution
+struct ForwardTask {
+    resource: String,
+    namespace: String,
+    /// kubectl context the forward runs in, the current one when unset
+    context: Option<String>,
+    address: Option<String>,
+    local_port: u16,
+    remote_port: u16,
+}
+
+type ChildSlot = Mutex<Option<Arc<lifecycle::ChildHandle>>>;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+fn spawn_kubectl(task: &ForwardTask) -> Option<(Child, lifecycle::ChildHandle)> {
+    let mut cmd = ProcessCommand::new("kubectl");
+    cmd.arg("port-forward")
+        .arg(&task.resource)
+        .arg(format!("{}:{}", task.local_port, task.remote_port))
         .arg("-n")
-        .arg(&fwd.namespace)
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit());
+        .arg(&task.namespace);
+    if let Some(context) = &task.context {
+        cmd.arg("--context").arg(context);
+    }
+    if let Some(address) = &task.address {
+        cmd.arg("--address").arg(address);
+    }
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    lifecycle::configure(&mut cmd);
     match cmd.spawn() {
-        Ok(mut child) => {
-            let target_desc = match (&fwd.name, &fwd.labels) {
-                (Some(name), None) => name.clone(),
-                (None, Some(labels)) => format!("labels:{}", labels),
-                _ => "unknown".to_string(),
-            };
-            println!(
-                "Spawned kubectl port-forward for {} (blocking, Ctrl-C will terminate)",
-                target_desc
-            );
-            // Set up Ctrl-C handler to kill child
-            let child_id = child.id();
-            let running = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
-            let r = running.clone();
-            let _ = ctrlc::set_handler(move || {
-                r.store(false, std::sync::atomic::Ordering::SeqCst);
-                // Try to kill the child process
-                #[cfg(unix)]
-                unsafe {
-                    libc::kill(child_id as i32, libc::SIGTERM);
+        Ok(child) => {
+            // Tie the child to our lifetime so it cannot outlive the plugin
+            let handle = match lifecycle::ChildHandle::attach(&child) {
+                Ok(handle) => handle,
+                Err(e) => {
+                    eprintln!(
+                        "Warning: could not tie kubectl to the plugin lifetime: {}",
+                        e
+                    );
+                    lifecycle::ChildHandle::detached(&child)
                 }
-                #[cfg(windows)]
-                {
-                    let _ = ProcessCommand::new("taskkill")
-                        .arg("/PID")
-                        .arg(child_id.to_string())
-                        .arg("/F")
-                        .status();
+            };
+            Some((child, handle))
+        }
+        Err(e) => {
+            eprintln!("Failed to spawn kubectl for {}: {}", task.resource, e);
+            None
+        }
+    }
+}
+
+/// Runs one forward until it exits or Ctrl-C is pressed. With keepalive enabled the
+/// forward is probed periodically and restarted when it exits or stops answering.
+fn supervise(
+    task: &ForwardTask,
+    index: usize,
+    keepalive: Option<(Duration, u32)>,
+    running: &AtomicBool,
+    slot: &ChildSlot,
+    tracker: &state::StateTracker,
+) {
+    loop {
+        let Some((mut child, handle)) = spawn_kubectl(task) else {
+            tracker.stopped(index, Some("failed to spawn kubectl".to_string()));
+            return;
+        };
+        tracker.started(index);
+        let handle = Arc::new(handle);
+        *slot.lock().unwrap() = Some(handle.clone());
+        // Ctrl-C may have fired before the handle was registered
+        if !running.load(Ordering::SeqCst) {
+            handle.terminate();
+        }
+
+        let mut stale = None;
+        let status = match keepalive {
+            None => child.wait(),
+            Some((interval, max_failures)) => {
+                let mut failures = 0;
+                let mut next_probe = Instant::now() + interval;
+                loop {
+                    match child.try_wait() {
+                        Ok(Some(status)) => break Ok(status),
+                        Ok(None) => {}
+                        Err(e) => break Err(e),
+                    }
+                    if running.load(Ordering::SeqCst) && Instant::now() >= next_probe {
+                        if keepalive::probe(task.address.as_deref(), task.local_port) {
+                            failures = 0;
+                        } else {
+                            failures += 1;
+                            eprintln!(
+                                "Keepalive probe {}/{} failed for {} on {}",
+                                failures,
+                                max_failures,
+                                task.resource,
+                                local_end(task.address.as_deref(), task.local_port)
+                            );
+                            if failures >= max_failures {
+                                eprintln!("Forward for {} looks stale, restarting", task.resource);
+                                stale = Some(format!("{} keepalive probes failed", failures));
+                                handle.terminate();
+                                break child.wait();
+                            }
+                        }
+                        next_probe = Instant::now() + interval;
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
                 }
-            });
-            // Wait for child to exit
-            let status = child.wait();
-            running.store(false, std::sync::atomic::Ordering::SeqCst);
-            match status {
-                Ok(s) => println!("kubectl exited with status: {}", s),
-                Err(e) => eprintln!("kubectl wait error: {}", e),
             }
+        };
+        *slot.lock().unwrap() = None;
+
+        let reason = match status {
+            Ok(s) => {
+                println!("kubectl for {} exited with status: {}", task.resource, s);
+                format!("kubectl exited with {}", s)
+            }
+            Err(e) => {
+                eprintln!("kubectl wait error for {}: {}", task.resource, e);
+                format!("kubectl wait error: {}", e)
+            }
+        };
+        let reason = stale.unwrap_or(reason);
+
+        if !running.load(Ordering::SeqCst) {
+            tracker.stopped(index, None);
+            return;
         }
-        Err(e) => {
-            eprintln!("Failed to spawn kubectl: {}", e);
+        if keepalive.is_none() {
+            tracker.stopped(index, Some(reason));
+            return;
         }
+        tracker.restarting(index, reason);
+        println!("Restarting kubectl port-forward for {}", task.resource);
+        std::thread::sleep(RESTART_DELAY);
     }
 }
 
@@ -206,11 +781,30 @@ impl Plugin for ProxyPlugin {
                     .help("Name of the port-forward config to use (from config file)")
                     .required(false)
             )
+            .arg(
+                Arg::new("status")
+                    .long("status")
+                    .help("Show uptime, restarts and last failure of running forwards")
+                    .action(clap::ArgAction::SetTrue),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(ProxyPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<ForwardConfig>(config)
     }
 
     fn run(&self, matches: &ArgMatches) {
         env_logger::init();
 
+        if matches.get_flag("status") {
+            state::print_status(self.name());
+            return;
+        }
+
         match load_config(self.name()) {
             Some(cfg) => {
                 let name_filter = matches.get_one::<String>("name");
@@ -250,14 +844,13 @@ impl Plugin for ProxyPlugin {
                     if forwards.len() > 1 && name_filter.is_some() {
                         println!("Found {} matching configurations:", forwards.len());
                         for fwd in &forwards {
-                            let target_desc = match (&fwd.name, &fwd.labels) {
-                                (Some(name), None) => name.clone(),
-                                (None, Some(labels)) => format!("labels:{}", labels),
-                                _ => "invalid-config".to_string(),
-                            };
+                            let target_desc = fwd.target_desc();
                             println!(
-                                "  {} {}:{} -> localhost:{}",
-                                fwd.r#type, target_desc, fwd.remote_port, fwd.local_port
+                                "  {} {}:{} -> {}",
+                                fwd.r#type,
+                                target_desc,
+                                fwd.remote_port,
+                                local_end(fwd.address.as_deref(), fwd.local_port)
                             );
                         }
                         println!("Using the first match only.\n");
@@ -265,21 +858,20 @@ impl Plugin for ProxyPlugin {
 
                     // Only use the first forward to avoid conflicts
                     let fwd = &forwards[0];
-                    let target_desc = match (&fwd.name, &fwd.labels) {
-                        (Some(name), None) => name.clone(),
-                        (None, Some(labels)) => format!("labels:{}", labels),
-                        _ => "invalid-config".to_string(),
-                    };
+                    let target_desc = fwd.target_desc();
 
                     if forwards.len() == 1 || name_filter.is_none() {
                         println!("Starting port-forward:");
                     }
                     println!(
-                        "  {} {}:{} -> localhost:{}",
-                        fwd.r#type, target_desc, fwd.remote_port, fwd.local_port
+                        "  {} {}:{} -> {}",
+                        fwd.r#type,
+                        target_desc,
+                        fwd.remote_port,
+                        local_end(fwd.address.as_deref(), fwd.local_port)
                     );
 
-                    spawn_kubectl_port_forward(fwd);
+                    spawn_kubectl_port_forward(self.name(), fwd);
                 }
             }
             None => {
@@ -295,6 +887,9 @@ pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
     Box::new(ProxyPlugin)
 }
 
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
+
 // Example config (save as ~/.cohandv/proxy/config/plugins.d/k8s_port_forward.conf):
 /*
 [[forward]]
@@ -311,6 +906,14 @@ type = "pod"
 local_port = 9090
 remote_port = 9000
 
+[[forward]]
+labels = "app=worker"
+namespace = "default"
+type = "pod"
+local_port = 7000
+remote_port = 8080
+fan_out = true  # one forward per matched pod on 7000, 7001, ...
+
 [[forward]]
 name = "my-pod"
 namespace = "default"
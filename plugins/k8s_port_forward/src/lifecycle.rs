@@ -0,0 +1,153 @@
+// Ties spawned kubectl processes to the lifetime of the plugin.
+//
+// On Unix the child is terminated with SIGTERM from the Ctrl-C handler. On Windows
+// the child is started in its own process group (so console Ctrl-C events reach only
+// the plugin) and assigned to a job object created with KILL_ON_JOB_CLOSE: when the
+// plugin exits for any reason, including the console window being closed, the last
+// handle to the job is released and Windows terminates kubectl.exe with it.
+use std::io;
+use std::process::{Child, Command};
+
+/// Applies the platform specific spawn flags required by [`ChildHandle::attach`].
+pub fn configure(cmd: &mut Command) {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP);
+    }
+    #[cfg(not(windows))]
+    let _ = cmd;
+}
+
+#[cfg(unix)]
+pub struct ChildHandle {
+    pid: i32,
+}
+
+#[cfg(unix)]
+impl ChildHandle {
+    pub fn attach(child: &Child) -> io::Result<Self> {
+        Ok(Self {
+            pid: child.id() as i32,
+        })
+    }
+
+    pub fn detached(child: &Child) -> Self {
+        Self {
+            pid: child.id() as i32,
+        }
+    }
+
+    pub fn terminate(&self) {
+        unsafe {
+            libc::kill(self.pid, libc::SIGTERM);
+        }
+    }
+}
+
+#[cfg(windows)]
+pub struct ChildHandle {
+    pid: u32,
+    job: Option<job::JobObject>,
+}
+
+#[cfg(windows)]
+impl ChildHandle {
+    pub fn attach(child: &Child) -> io::Result<Self> {
+        use std::os::windows::io::AsRawHandle;
+
+        let job = job::JobObject::kill_on_close()?;
+        job.assign(child.as_raw_handle())?;
+        Ok(Self {
+            pid: child.id(),
+            job: Some(job),
+        })
+    }
+
+    /// Tracks the child without a job object, used when [`ChildHandle::attach`] fails.
+    pub fn detached(child: &Child) -> Self {
+        Self {
+            pid: child.id(),
+            job: None,
+        }
+    }
+
+    pub fn terminate(&self) {
+        match &self.job {
+            Some(job) => job.terminate(),
+            None => {
+                // No job object: fall back to killing the process tree by pid
+                let _ = Command::new("taskkill")
+                    .arg("/PID")
+                    .arg(self.pid.to_string())
+                    .arg("/T")
+                    .arg("/F")
+                    .status();
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod job {
+    use std::io;
+    use std::mem;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+
+    pub struct JobObject(HANDLE);
+
+    // The job handle is only used through thread safe Win32 calls
+    unsafe impl Send for JobObject {}
+    unsafe impl Sync for JobObject {}
+
+    impl JobObject {
+        pub fn kill_on_close() -> io::Result<Self> {
+            unsafe {
+                let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if handle.is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                let job = JobObject(handle);
+
+                let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = mem::zeroed();
+                info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+                let ok = SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const _,
+                    mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                );
+                if ok == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(job)
+            }
+        }
+
+        pub fn assign(&self, process: HANDLE) -> io::Result<()> {
+            if unsafe { AssignProcessToJobObject(self.0, process) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        pub fn terminate(&self) {
+            unsafe {
+                TerminateJobObject(self.0, 1);
+            }
+        }
+    }
+
+    impl Drop for JobObject {
+        fn drop(&mut self) {
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
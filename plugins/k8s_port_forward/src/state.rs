@@ -0,0 +1,344 @@
+// Per-forward runtime state persisted in the plugin state directory: every plugin process
+// writes its own `forwards-<pid>.json` and `--status` reads them all, flagging those left
+// behind by processes that are gone.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use plugin_api::{audit, metrics, notify, processes, usage};
+
+/// Drops of a forward within this long of its last notification aren't notified
+const NOTIFY_INTERVAL_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    pub at: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardState {
+    pub resource: String,
+    pub namespace: String,
+    pub local_port: u16,
+    /// The local addresses kubectl listens on, as configured
+    #[serde(default)]
+    pub address: Option<String>,
+    pub remote_port: u16,
+    /// "running", "restarting" or "stopped"
+    pub status: String,
+    /// When the forward was first started (seconds since the epoch)
+    pub started_at: u64,
+    /// When the current kubectl process was started
+    pub running_since: u64,
+    pub restarts: u32,
+    pub last_failure: Option<Failure>,
+}
+
+impl ForwardState {
+    pub fn new(
+        resource: &str,
+        namespace: &str,
+        local_port: u16,
+        address: Option<&str>,
+        remote_port: u16,
+    ) -> Self {
+        Self {
+            resource: resource.to_string(),
+            namespace: namespace.to_string(),
+            local_port,
+            address: address.map(str::to_string),
+            remote_port,
+            status: "stopped".to_string(),
+            started_at: 0,
+            running_since: 0,
+            restarts: 0,
+            last_failure: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StateFile {
+    pub pid: u32,
+    pub forwards: Vec<ForwardState>,
+}
+
+/// Records forward lifecycle events and mirrors them to this process' state file
+pub struct StateTracker {
+    plugin_name: String,
+    path: Option<PathBuf>,
+    state: Mutex<StateFile>,
+    /// When each forward's drop was last notified
+    notified: Mutex<HashMap<usize, u64>>,
+    /// Usage sessions of the running forwards, kept across restarts
+    sessions: Mutex<HashMap<usize, usage::Session>>,
+}
+
+/// Mirrors a forward's status, and a restart when there was one, to the shared metrics
+fn export(forward: &ForwardState, restarted: bool) {
+    let resource = format!("{}/{}", forward.namespace, forward.resource);
+    let local_port = forward.local_port.to_string();
+    let labels = [
+        ("resource", resource.as_str()),
+        ("local_port", local_port.as_str()),
+    ];
+    let up = if forward.status == "running" {
+        1.0
+    } else {
+        0.0
+    };
+    metrics::gauge_set(
+        "proxy_port_forward_up",
+        "Whether a port-forward is running",
+        &labels,
+        up,
+    );
+    if restarted {
+        metrics::counter_add(
+            "proxy_port_forward_restarts_total",
+            "Port-forward restarts after a failure",
+            &labels,
+            1,
+        );
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl StateTracker {
+    pub fn new(plugin_name: &str, forwards: Vec<ForwardState>) -> Self {
+        metrics::init(plugin_name);
+        let pid = std::process::id();
+        let path = plugin_api::plugin_state_dir(plugin_name).and_then(|dir| {
+            fs::create_dir_all(&dir).ok()?;
+            prune(&dir);
+            Some(dir.join(format!("forwards-{}.json", pid)))
+        });
+        Self {
+            plugin_name: plugin_name.to_string(),
+            path,
+            state: Mutex::new(StateFile { pid, forwards }),
+            notified: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn update(&self, index: usize, f: impl FnOnce(&mut ForwardState)) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(forward) = state.forwards.get_mut(index) {
+            let restarts = forward.restarts;
+            f(forward);
+            export(forward, forward.restarts > restarts);
+        }
+        if let Some(path) = &self.path {
+            if let Ok(json) = serde_json::to_string_pretty(&*state) {
+                let _ = fs::write(path, json);
+            }
+        }
+    }
+
+    fn notify_dropped(&self, index: usize, forward: &ForwardState, reason: &str, at: u64) {
+        let mut notified = self.notified.lock().unwrap();
+        if notified
+            .get(&index)
+            .is_some_and(|last| at < last + NOTIFY_INTERVAL_SECS)
+        {
+            return;
+        }
+        notified.insert(index, at);
+        notify::send(
+            &self.plugin_name,
+            "forward_dropped",
+            &format!(
+                "Port-forward to {}/{} dropped",
+                forward.namespace, forward.resource
+            ),
+            &format!(
+                "{} -> {}: {}",
+                crate::local_end(forward.address.as_deref(), forward.local_port),
+                forward.remote_port,
+                reason
+            ),
+        );
+    }
+
+    pub fn started(&self, index: usize) {
+        let at = now();
+        self.update(index, |forward| {
+            if forward.started_at == 0 {
+                forward.started_at = at;
+            }
+            forward.running_since = at;
+            forward.status = "running".to_string();
+            audit::record_k8s(
+                &self.plugin_name,
+                "port_forward",
+                &[
+                    ("namespace", &forward.namespace),
+                    ("resource", &forward.resource),
+                    ("local_port", &forward.local_port.to_string()),
+                    ("remote_port", &forward.remote_port.to_string()),
+                ],
+            );
+            self.sessions
+                .lock()
+                .unwrap()
+                .entry(index)
+                .or_insert_with(|| {
+                    usage::session_k8s(&format!("{}/{}", forward.namespace, forward.resource))
+                });
+        });
+    }
+
+    pub fn restarting(&self, index: usize, reason: String) {
+        let at = now();
+        self.update(index, |forward| {
+            forward.restarts += 1;
+            forward.status = "restarting".to_string();
+            self.notify_dropped(index, forward, &reason, at);
+            forward.last_failure = Some(Failure { at, reason });
+        });
+    }
+
+    pub fn stopped(&self, index: usize, reason: Option<String>) {
+        let at = now();
+        self.update(index, |forward| {
+            forward.status = "stopped".to_string();
+            self.sessions.lock().unwrap().remove(&index);
+            if let Some(reason) = reason {
+                self.notify_dropped(index, forward, &reason, at);
+                forward.last_failure = Some(Failure { at, reason });
+            }
+        });
+    }
+
+    /// Removes the state file once every forward of this process has ended
+    pub fn finish(&self) {
+        self.sessions.lock().unwrap().clear();
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    let (days, hours, minutes, seconds) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    if days > 0 {
+        format!("{}d{}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h{}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m{}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// The state files in `dir`, sorted by name
+fn state_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| {
+                            name.starts_with("forwards-") && name.ends_with(".json")
+                        })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+fn read_state(path: &Path) -> Option<StateFile> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+/// Removes the state files of processes that did not exit cleanly
+fn prune(dir: &Path) {
+    for path in state_files(dir) {
+        if read_state(&path).is_some_and(|state| !processes::alive(state.pid)) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+/// Prints uptime, restarts and the last failure of every forward known to the state dir
+pub fn print_status(plugin_name: &str) {
+    let Some(dir) = plugin_api::plugin_state_dir(plugin_name) else {
+        eprintln!("Could not determine the state directory");
+        return;
+    };
+
+    let now = now();
+    let mut rows = 0;
+    for path in state_files(&dir) {
+        let Some(state) = read_state(&path) else {
+            continue;
+        };
+        let alive = processes::alive(state.pid);
+
+        if rows == 0 {
+            println!(
+                "{:<8} {:<36} {:<13} {:<11} {:<8} {:<9} LAST FAILURE",
+                "PID", "RESOURCE", "PORTS", "STATUS", "UPTIME", "RESTARTS"
+            );
+        }
+        for forward in &state.forwards {
+            rows += 1;
+            let status = if alive {
+                forward.status.as_str()
+            } else {
+                "dead"
+            };
+            let uptime = if alive && forward.status == "running" {
+                format_duration(now.saturating_sub(forward.running_since))
+            } else {
+                "-".to_string()
+            };
+            let last_failure = match &forward.last_failure {
+                Some(failure) => format!(
+                    "{} ({} ago)",
+                    failure.reason,
+                    format_duration(now.saturating_sub(failure.at))
+                ),
+                None => "-".to_string(),
+            };
+            println!(
+                "{:<8} {:<36} {:<13} {:<11} {:<8} {:<9} {}",
+                state.pid,
+                format!("{}/{}", forward.namespace, forward.resource),
+                format!("{}:{}", forward.local_port, forward.remote_port),
+                status,
+                uptime,
+                forward.restarts,
+                last_failure
+            );
+        }
+    }
+
+    if rows == 0 {
+        println!("No active port-forwards");
+    }
+}
@@ -0,0 +1,177 @@
+// The local side of a sync: which files take part, what changed since the last scan, and
+// the tar archives carried to and from the pod.
+use anyhow::{anyhow, Context, Result};
+use glob::Pattern;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// GNU tar reads stdin in records of 20 blocks and waits for a whole one, and the exec
+/// stream can't signal the end of input, so archives are padded to full records
+const RECORD_SIZE: usize = 20 * 512;
+
+/// Include and exclude globs, matched against paths relative to the synced root
+pub struct Filter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let compile = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|glob| Pattern::new(glob).with_context(|| format!("Invalid glob '{}'", glob)))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Filter {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// Whether the directory, and so everything under it, is excluded
+    fn skips_dir(&self, relative: &str) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches(relative))
+    }
+
+    /// Whether the file takes part: not excluded itself or through a parent directory,
+    /// and matching an include glob when there are any
+    pub fn allows(&self, relative: &str) -> bool {
+        let mut prefix = String::new();
+        for part in relative.split('/') {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(part);
+            if self.skips_dir(&prefix) {
+                return false;
+            }
+        }
+        self.include.is_empty() || self.include.iter().any(|pattern| pattern.matches(relative))
+    }
+}
+
+/// The files under a root, by relative path, with what tells a change
+pub type Snapshot = BTreeMap<String, (SystemTime, u64)>;
+
+/// The files to sync: everything allowed under `root`, or `root` itself when it is a file
+pub fn scan(root: &Path, filter: &Filter) -> Result<Snapshot> {
+    let mut snapshot = Snapshot::new();
+    let metadata = fs::metadata(root).with_context(|| format!("Can't read {}", root.display()))?;
+    if metadata.is_file() {
+        snapshot.insert(String::new(), (metadata.modified()?, metadata.len()));
+        return Ok(snapshot);
+    }
+    let mut pending = vec![PathBuf::new()];
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(root.join(&dir)) {
+            Ok(entries) => entries,
+            // Removed while scanning
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = dir.join(entry.file_name());
+            let relative = path.to_string_lossy().replace('\\', "/");
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if !filter.skips_dir(&relative) {
+                    pending.push(path);
+                }
+            } else if filter.allows(&relative) {
+                snapshot.insert(relative, (metadata.modified()?, metadata.len()));
+            }
+        }
+    }
+    Ok(snapshot)
+}
+
+/// Files added or modified in `now`, and files gone from it
+pub fn diff(before: &Snapshot, now: &Snapshot) -> (Vec<String>, Vec<String>) {
+    let changed = now
+        .iter()
+        .filter(|(path, state)| before.get(*path) != Some(state))
+        .map(|(path, _)| path.clone())
+        .collect();
+    let removed = before
+        .keys()
+        .filter(|path| !now.contains_key(*path))
+        .cloned()
+        .collect();
+    (changed, removed)
+}
+
+/// A tar of the given files under `root`. A single-file root (empty relative path) is
+/// stored as `name`. Files that vanished since the scan are left out.
+pub fn pack(root: &Path, files: &[String], name: &str) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.follow_symlinks(false);
+    for relative in files {
+        let (source, stored) = if relative.is_empty() {
+            (root.to_path_buf(), name.to_string())
+        } else {
+            (root.join(relative), relative.clone())
+        };
+        match builder.append_path_with_name(&source, &stored) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(anyhow!("Can't add {}: {}", source.display(), e)),
+        }
+    }
+    let mut archive = builder.into_inner()?;
+    let padded = archive.len().div_ceil(RECORD_SIZE) * RECORD_SIZE;
+    archive.resize(padded.max(RECORD_SIZE), 0);
+    Ok(archive)
+}
+
+/// Unpacks a tar of `name` (a file or a directory) to `destination`, keeping the files the
+/// filter allows; returns how many were written
+pub fn unpack(archive: &[u8], name: &str, destination: &Path, filter: &Filter) -> Result<usize> {
+    let mut written = 0;
+    let mut entries = tar::Archive::new(archive);
+    for entry in entries.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path
+            .components()
+            .any(|part| !matches!(part, Component::Normal(_)))
+        {
+            return Err(anyhow!(
+                "Refusing unsafe path {} in the archive",
+                path.display()
+            ));
+        }
+        let relative = path
+            .strip_prefix(name)
+            .map_err(|_| anyhow!("Unexpected {} in the archive", path.display()))?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let is_dir = entry.header().entry_type().is_dir();
+        let target = if relative.is_empty() {
+            if is_dir {
+                fs::create_dir_all(destination)?;
+                continue;
+            }
+            // A single file, into a directory that exists or as the given path
+            if destination.is_dir() {
+                destination.join(name)
+            } else {
+                destination.to_path_buf()
+            }
+        } else {
+            if is_dir || !filter.allows(&relative) {
+                continue;
+            }
+            destination.join(&relative)
+        };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&target)?;
+        written += 1;
+    }
+    Ok(written)
+}
@@ -0,0 +1,479 @@
+// Files between local paths and pods, with tar over exec like kubectl cp, and a watch
+// mode that pushes each saved change. Together with a port forward this gives a fast
+// loop for code running in a pod: edit locally, and the pod's reloader picks it up.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use kube::Client;
+use plugin_api::k8s::find_pod;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+mod archive;
+mod remote;
+
+use archive::Filter;
+use remote::Remote;
+
+/// Annotation kubectl also uses to pick the container
+const DEFAULT_CONTAINER_ANNOTATION: &str = "kubectl.kubernetes.io/default-container";
+/// Excluded when no excludes are given
+const DEFAULT_EXCLUDE: &str = ".git";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct K8sSyncConfig {
+    /// Namespace (default: the kubeconfig context's)
+    pub namespace: Option<String>,
+    pub pod_name: Option<String>,
+    /// Label selector, e.g. "app=api"; a running matching pod is used
+    pub pod_selector: Option<String>,
+    /// Container (default: the pod's default container, else its first one)
+    pub container: Option<String>,
+    /// Globs of the files to sync, relative to the synced directory (default: all)
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs of files and directories to leave out (default: .git)
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+pub struct K8sSyncPlugin;
+
+impl K8sSyncPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Kubernetes Sync Configuration
+namespace = "default"
+pod_selector = "app=api"  # Either use pod_name OR pod_selector
+# pod_name = "api-7d9f8b6c4-x2k4p"
+# container = "app"
+include = ["src/**", "*.toml"]
+exclude = [".git", "target", "**/__pycache__"]
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<K8sSyncConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: K8sSyncConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Everything can be given on the command line
+                Ok(K8sSyncConfig::default())
+            }
+        }
+        None => Ok(K8sSyncConfig::default()),
+    }
+}
+
+/// The requested container, the annotated default one, or the first
+fn pick_container(pod: &Pod, requested: Option<&str>) -> Result<String> {
+    let names: Vec<&str> = pod
+        .spec
+        .as_ref()
+        .map(|spec| spec.containers.iter().map(|c| c.name.as_str()).collect())
+        .unwrap_or_default();
+    if let Some(requested) = requested {
+        if names.contains(&requested) {
+            return Ok(requested.to_string());
+        }
+        return Err(anyhow!(
+            "No container '{}' in the pod (has: {})",
+            requested,
+            names.join(", ")
+        ));
+    }
+    let annotated = pod
+        .metadata
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(DEFAULT_CONTAINER_ANNOTATION))
+        .filter(|name| names.contains(&name.as_str()));
+    match annotated {
+        Some(name) => Ok(name.clone()),
+        None => names
+            .first()
+            .map(|name| name.to_string())
+            .ok_or_else(|| anyhow!("The pod has no containers")),
+    }
+}
+
+async fn connect(config: &K8sSyncConfig) -> Result<Remote> {
+    let client = Client::try_default().await?;
+    let namespace = config
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+    let pods: Api<Pod> = Api::namespaced(client, &namespace);
+    let pod = find_pod(
+        &pods,
+        config.pod_name.as_deref(),
+        config.pod_selector.as_deref(),
+    )
+    .await?;
+    let container = pick_container(&pod, config.container.as_deref())?;
+    let pod = pod
+        .metadata
+        .name
+        .ok_or_else(|| anyhow!("Pod has no name"))?;
+    Ok(Remote {
+        pods,
+        namespace,
+        pod,
+        container,
+    })
+}
+
+fn size(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Where a local root goes in the pod: the directory to extract into and the name a
+/// single file is stored under. A directory maps onto the remote path; a file becomes the
+/// remote path, or keeps its name in it when that ends with '/'.
+fn destination(local: &Path, remote_path: &str) -> (String, String) {
+    let file_name = local
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if local.is_dir() || remote_path.ends_with('/') {
+        (remote_path.to_string(), file_name)
+    } else {
+        remote::split(remote_path)
+    }
+}
+
+/// Pushes files under `local` to the pod; returns the archive size
+async fn push_files(
+    remote: &Remote,
+    local: &Path,
+    remote_path: &str,
+    files: &[String],
+) -> Result<usize> {
+    let (dir, name) = destination(local, remote_path);
+    let archive = archive::pack(local, files, &name)?;
+    let bytes = archive.len();
+    remote.extract(&dir, archive).await?;
+    Ok(bytes)
+}
+
+async fn push(remote: &Remote, local: &Path, remote_path: &str, filter: &Filter) -> Result<()> {
+    let files: Vec<String> = archive::scan(local, filter)?.into_keys().collect();
+    if files.is_empty() {
+        println!("📭 Nothing to push from {}", local.display());
+        return Ok(());
+    }
+    let bytes = push_files(remote, local, remote_path, &files).await?;
+    println!(
+        "📤 Pushed {} file(s) ({}) to {}:{}",
+        files.len(),
+        size(bytes),
+        remote.pod,
+        remote_path
+    );
+    Ok(())
+}
+
+async fn pull(remote: &Remote, remote_path: &str, local: &Path, filter: &Filter) -> Result<()> {
+    let archive = remote.archive(remote_path).await?;
+    let (_, name) = remote::split(remote_path);
+    let written = archive::unpack(&archive, &name, local, filter)?;
+    println!(
+        "📥 Pulled {} file(s) from {}:{} to {}",
+        written,
+        remote.pod,
+        remote_path,
+        local.display()
+    );
+    Ok(())
+}
+
+/// Pushes every change under `local` until Ctrl+C
+async fn watch(
+    remote: &Remote,
+    local: &Path,
+    remote_path: &str,
+    filter: &Filter,
+    delete: bool,
+) -> Result<()> {
+    ctrlc::set_handler(move || {
+        println!("\n👋 Shutting down...");
+        std::process::exit(0);
+    })?;
+    push(remote, local, remote_path, filter).await?;
+    let mut known = archive::scan(local, filter)?;
+    let (dir, _) = destination(local, remote_path);
+    println!(
+        "👀 Watching {} → {}:{}, press Ctrl+C to stop",
+        local.display(),
+        remote.pod,
+        remote_path
+    );
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let now = match archive::scan(local, filter) {
+            Ok(now) => now,
+            Err(e) => {
+                eprintln!("⚠️  {}", e);
+                continue;
+            }
+        };
+        let (changed, removed) = archive::diff(&known, &now);
+        if !changed.is_empty() {
+            match push_files(remote, local, remote_path, &changed).await {
+                Ok(_) => {
+                    for path in &changed {
+                        let shown = if path.is_empty() { remote_path } else { path };
+                        println!("🔄 {}", shown);
+                    }
+                }
+                Err(e) => {
+                    // Tried again on the next scan
+                    eprintln!("❌ {}", e);
+                    continue;
+                }
+            }
+        }
+        let removed: Vec<String> = removed
+            .into_iter()
+            .filter(|path| !path.is_empty())
+            .collect();
+        if delete && !removed.is_empty() {
+            match remote.remove(&dir, &removed).await {
+                Ok(()) => {
+                    for path in &removed {
+                        println!("🗑️  {}", path);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    continue;
+                }
+            }
+        }
+        known = now;
+    }
+}
+
+async fn execute(matches: &ArgMatches, config: K8sSyncConfig, filter: Filter) -> Result<()> {
+    let remote = connect(&config).await?;
+    let paths = |sub: &ArgMatches, first: &str, second: &str| {
+        (
+            sub.get_one::<String>(first).cloned().unwrap_or_default(),
+            sub.get_one::<String>(second).cloned().unwrap_or_default(),
+        )
+    };
+    match matches.subcommand() {
+        Some(("push", sub)) => {
+            let (local, remote_path) = paths(sub, "local", "remote");
+            push(&remote, Path::new(&local), &remote_path, &filter).await
+        }
+        Some(("pull", sub)) => {
+            let (remote_path, local) = paths(sub, "remote", "local");
+            pull(&remote, &remote_path, Path::new(&local), &filter).await
+        }
+        Some(("watch", sub)) => {
+            let (local, remote_path) = paths(sub, "local", "remote");
+            watch(
+                &remote,
+                Path::new(&local),
+                &remote_path,
+                &filter,
+                sub.get_flag("delete"),
+            )
+            .await
+        }
+        _ => Ok(()),
+    }
+}
+
+fn local_arg() -> Arg {
+    Arg::new("local")
+        .value_name("LOCAL_PATH")
+        .required(true)
+        .help("Local file or directory")
+}
+
+fn remote_arg() -> Arg {
+    Arg::new("remote")
+        .value_name("REMOTE_PATH")
+        .required(true)
+        .help("Path in the container")
+}
+
+impl Plugin for K8sSyncPlugin {
+    fn name(&self) -> &'static str {
+        "k8s_sync"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Copy files to and from pods, and push local changes as they are saved"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Copy files between local paths and a Kubernetes pod, or keep them in sync")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("push")
+                    .about("Copy a local file or directory into the pod")
+                    .arg(local_arg())
+                    .arg(remote_arg()),
+            )
+            .subcommand(
+                Command::new("pull")
+                    .about("Copy a file or directory from the pod")
+                    .arg(remote_arg())
+                    .arg(local_arg()),
+            )
+            .subcommand(
+                Command::new("watch")
+                    .about("Push a local directory, then every change to it until Ctrl+C")
+                    .arg(local_arg())
+                    .arg(remote_arg())
+                    .arg(
+                        Arg::new("delete")
+                            .long("delete")
+                            .action(ArgAction::SetTrue)
+                            .help("Remove files from the pod when they are deleted locally"),
+                    ),
+            )
+            .arg(
+                Arg::new("pod")
+                    .long("pod")
+                    .short('p')
+                    .global(true)
+                    .value_name("POD_NAME")
+                    .help("Override pod name from config file"),
+            )
+            .arg(
+                Arg::new("selector")
+                    .long("selector")
+                    .short('s')
+                    .global(true)
+                    .value_name("SELECTOR")
+                    .help("Override pod selector from config file (e.g., 'app=nginx')"),
+            )
+            .arg(
+                Arg::new("namespace")
+                    .long("namespace")
+                    .short('n')
+                    .global(true)
+                    .value_name("NAMESPACE")
+                    .help("Override namespace from config file"),
+            )
+            .arg(
+                Arg::new("container")
+                    .long("container")
+                    .short('c')
+                    .global(true)
+                    .value_name("CONTAINER")
+                    .help("Container to sync with (default: the pod's default container)"),
+            )
+            .arg(
+                Arg::new("include")
+                    .long("include")
+                    .global(true)
+                    .value_name("GLOB")
+                    .action(ArgAction::Append)
+                    .help("Only sync files matching this glob (repeatable)"),
+            )
+            .arg(
+                Arg::new("exclude")
+                    .long("exclude")
+                    .global(true)
+                    .value_name("GLOB")
+                    .action(ArgAction::Append)
+                    .help("Leave out files and directories matching this glob (repeatable)"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(K8sSyncPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<K8sSyncConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let mut config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let sub = matches.subcommand().map(|(_, sub)| sub).unwrap_or(matches);
+
+        // Override config with command line arguments
+        if let Some(pod) = sub.get_one::<String>("pod") {
+            config.pod_name = Some(pod.clone());
+            config.pod_selector = None;
+        }
+        if let Some(selector) = sub.get_one::<String>("selector") {
+            config.pod_selector = Some(selector.clone());
+            config.pod_name = None;
+        }
+        if let Some(namespace) = sub.get_one::<String>("namespace") {
+            config.namespace = Some(namespace.clone());
+        }
+        if let Some(container) = sub.get_one::<String>("container") {
+            config.container = Some(container.clone());
+        }
+        if let Some(include) = sub.get_many::<String>("include") {
+            config.include = include.cloned().collect();
+        }
+        if let Some(exclude) = sub.get_many::<String>("exclude") {
+            config.exclude = exclude.cloned().collect();
+        }
+        if config.exclude.is_empty() {
+            config.exclude.push(DEFAULT_EXCLUDE.to_string());
+        }
+        if config.pod_name.is_none() && config.pod_selector.is_none() {
+            eprintln!("❌ Must specify either --pod or --selector (or configure in config file)");
+            eprintln!("💡 Example: proxy k8s_sync watch ./src /app/src --selector app=api");
+            std::process::exit(1);
+        }
+
+        let filter = match Filter::new(&config.include, &config.exclude) {
+            Ok(filter) => filter,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        };
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        if let Err(e) = rt.block_on(execute(matches, config, filter)) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(K8sSyncPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
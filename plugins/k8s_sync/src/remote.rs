@@ -0,0 +1,136 @@
+// The pod side of a sync, through exec like kubectl cp: tar and sh in the container do
+// the work, with archives going over the exec session's stdin and stdout.
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use kube::api::{Api, AttachParams};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+pub struct Remote {
+    pub pods: Api<Pod>,
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+}
+
+/// Splits a remote path into its directory and last component
+pub fn split(path: &str) -> (String, String) {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rsplit_once('/') {
+        Some(("", name)) => ("/".to_string(), name.to_string()),
+        Some((dir, name)) => (dir.to_string(), name.to_string()),
+        None => (".".to_string(), trimmed.to_string()),
+    }
+}
+
+fn failure(status: &Status, stderr: &[u8]) -> Option<String> {
+    if status.status.as_deref() == Some("Success") {
+        return None;
+    }
+    let stderr = String::from_utf8_lossy(stderr).trim().to_string();
+    let message = if stderr.is_empty() {
+        status
+            .message
+            .clone()
+            .unwrap_or_else(|| "command failed".to_string())
+    } else {
+        stderr
+    };
+    if message.contains("not found") && message.contains("tar") {
+        return Some(format!(
+            "{}\n💡 The container needs tar; sync to a container that has it",
+            message
+        ));
+    }
+    Some(message)
+}
+
+async fn read_all(stream: Option<impl AsyncRead + Unpin>) -> Vec<u8> {
+    let mut output = Vec::new();
+    if let Some(mut stream) = stream {
+        let _ = stream.read_to_end(&mut output).await;
+    }
+    output
+}
+
+impl Remote {
+    /// Runs a shell script in the container with `args` as $1.., feeding it `input`;
+    /// returns its stdout
+    async fn run(&self, script: &str, args: &[&str], input: Option<Vec<u8>>) -> Result<Vec<u8>> {
+        let mut command = vec!["sh", "-c", script, "sh"];
+        command.extend_from_slice(args);
+        plugin_api::audit::record_k8s(
+            "k8s_sync",
+            "pod_exec",
+            &[
+                ("namespace", &self.namespace),
+                ("pod", &self.pod),
+                ("container", &self.container),
+                ("command", &command.join(" ")),
+            ],
+        );
+        let params = AttachParams::default()
+            .container(self.container.clone())
+            .stdin(input.is_some())
+            .stdout(true)
+            .stderr(true);
+        let mut attached = self.pods.exec(&self.pod, command, &params).await?;
+        let status = attached
+            .take_status()
+            .ok_or_else(|| anyhow!("The exec session has no status channel"))?;
+
+        let writer = match (attached.stdin(), input) {
+            (Some(mut stdin), Some(input)) => Some(tokio::spawn(async move {
+                let _ = stdin.write_all(&input).await;
+                let _ = stdin.flush().await;
+                // Closing stdin would end the session before tar is done
+                std::future::pending::<()>().await;
+                drop(stdin);
+            })),
+            _ => None,
+        };
+        let (stdout, stderr) =
+            tokio::join!(read_all(attached.stdout()), read_all(attached.stderr()));
+        if let Some(writer) = writer {
+            writer.abort();
+        }
+
+        let status = status.await.ok_or_else(|| {
+            anyhow!(
+                "The connection to {} closed without an exit status",
+                self.pod
+            )
+        })?;
+        match failure(&status, &stderr) {
+            Some(message) => Err(anyhow!("{}: {}", self.pod, message)),
+            None => Ok(stdout),
+        }
+    }
+
+    /// Extracts a tar into `dir`, creating it
+    pub async fn extract(&self, dir: &str, archive: Vec<u8>) -> Result<()> {
+        self.run(
+            r#"mkdir -p "$1" && tar -xof - -C "$1""#,
+            &[dir],
+            Some(archive),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// A tar of the file or directory at `path`, stored under its last component
+    pub async fn archive(&self, path: &str) -> Result<Vec<u8>> {
+        let (dir, name) = split(path);
+        self.run(r#"cd "$1" && tar -cf - "$2""#, &[&dir, &name], None)
+            .await
+    }
+
+    /// Removes the given paths under `dir`
+    pub async fn remove(&self, dir: &str, paths: &[String]) -> Result<()> {
+        let mut args = vec![dir];
+        args.extend(paths.iter().map(String::as_str));
+        self.run(r#"cd "$1" && shift && rm -rf -- "$@""#, &args, None)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+// Avro values in the schema registry's wire format (a zero byte, the 4-byte schema id,
+// then the Avro binary encoding), decoded to JSON with the writer's schema fetched from
+// the registry. Unions are shown as their value, without the branch name.
+use crate::protocol::Reader;
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+pub struct Registry {
+    url: String,
+    http: reqwest::Client,
+    /// Schemas by id, with their named types by full and by short name
+    schemas: HashMap<u32, (Value, HashMap<String, Value>)>,
+}
+
+/// Collects the named types (records, enums, fixed) defined in a schema
+fn collect_names(schema: &Value, namespace: &str, names: &mut HashMap<String, Value>) {
+    match schema {
+        Value::Array(branches) => {
+            for branch in branches {
+                collect_names(branch, namespace, names);
+            }
+        }
+        Value::Object(object) => {
+            let mut namespace = namespace.to_string();
+            if let Some(name) = object.get("name").and_then(Value::as_str) {
+                if let Some(own) = object.get("namespace").and_then(Value::as_str) {
+                    namespace = own.to_string();
+                }
+                let full = match name.rsplit_once('.') {
+                    Some((space, _)) => {
+                        namespace = space.to_string();
+                        name.to_string()
+                    }
+                    None if namespace.is_empty() => name.to_string(),
+                    None => format!("{}.{}", namespace, name),
+                };
+                let short = full.rsplit('.').next().unwrap_or(&full).to_string();
+                names.insert(full, schema.clone());
+                names.entry(short).or_insert_with(|| schema.clone());
+            }
+            for field in object
+                .get("fields")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                collect_names(&field["type"], &namespace, names);
+            }
+            for key in ["items", "values"] {
+                if let Some(inner) = object.get(key) {
+                    collect_names(inner, &namespace, names);
+                }
+            }
+            if let Some(inner @ (Value::Object(_) | Value::Array(_))) = object.get("type") {
+                collect_names(inner, &namespace, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn text(reader: &mut Reader) -> Result<String> {
+    let length = reader.varint()?;
+    Ok(String::from_utf8_lossy(reader.take(length.max(0) as usize)?).into_owned())
+}
+
+/// Reads the blocks of an array or map, calling `item` for each element
+fn blocks(reader: &mut Reader, mut item: impl FnMut(&mut Reader) -> Result<()>) -> Result<()> {
+    loop {
+        let mut count = reader.varint()?;
+        if count == 0 {
+            return Ok(());
+        }
+        if count < 0 {
+            // Followed by the block's size in bytes
+            count = -count;
+            reader.varint()?;
+        }
+        for _ in 0..count {
+            item(reader)?;
+        }
+    }
+}
+
+fn decode(schema: &Value, reader: &mut Reader, names: &HashMap<String, Value>) -> Result<Value> {
+    let kind = match schema {
+        Value::String(kind) => kind.as_str(),
+        Value::Array(branches) => {
+            let index = reader.varint()?;
+            let branch = branches
+                .get(index as usize)
+                .ok_or_else(|| anyhow!("Union branch {} out of range", index))?;
+            return decode(branch, reader, names);
+        }
+        Value::Object(object) => match object.get("type") {
+            Some(Value::String(kind)) => kind.as_str(),
+            // {"type": {...}} wraps another schema
+            Some(inner) => return decode(inner, reader, names),
+            None => return Err(anyhow!("Schema without a type")),
+        },
+        _ => return Err(anyhow!("Invalid schema {}", schema)),
+    };
+    Ok(match kind {
+        "null" => Value::Null,
+        "boolean" => Value::Bool(reader.take(1)?[0] != 0),
+        "int" | "long" => Value::from(reader.varint()?),
+        "float" => Value::from(f32::from_le_bytes(reader.take(4)?.try_into()?) as f64),
+        "double" => Value::from(f64::from_le_bytes(reader.take(8)?.try_into()?)),
+        "bytes" | "string" => Value::String(text(reader)?),
+        "record" | "error" => {
+            let mut record = Map::new();
+            for field in schema["fields"].as_array().into_iter().flatten() {
+                let name = field["name"].as_str().unwrap_or_default().to_string();
+                record.insert(name, decode(&field["type"], reader, names)?);
+            }
+            Value::Object(record)
+        }
+        "enum" => {
+            let index = reader.varint()?;
+            schema["symbols"]
+                .get(index as usize)
+                .cloned()
+                .ok_or_else(|| anyhow!("Enum symbol {} out of range", index))?
+        }
+        "array" => {
+            let mut items = Vec::new();
+            blocks(reader, |reader| {
+                items.push(decode(&schema["items"], reader, names)?);
+                Ok(())
+            })?;
+            Value::Array(items)
+        }
+        "map" => {
+            let mut map = Map::new();
+            blocks(reader, |reader| {
+                let key = text(reader)?;
+                map.insert(key, decode(&schema["values"], reader, names)?);
+                Ok(())
+            })?;
+            Value::Object(map)
+        }
+        "fixed" => {
+            let size = schema["size"].as_u64().unwrap_or_default() as usize;
+            Value::String(String::from_utf8_lossy(reader.take(size)?).into_owned())
+        }
+        name => {
+            let named = names
+                .get(name)
+                .or_else(|| names.get(name.rsplit('.').next().unwrap_or(name)))
+                .ok_or_else(|| anyhow!("Unknown Avro type {}", name))?;
+            decode(named, reader, names)?
+        }
+    })
+}
+
+impl Registry {
+    pub fn new(url: &str) -> Self {
+        Registry {
+            url: url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            schemas: HashMap::new(),
+        }
+    }
+
+    async fn schema(&mut self, id: u32) -> Result<&(Value, HashMap<String, Value>)> {
+        if !self.schemas.contains_key(&id) {
+            let url = format!("{}/schemas/ids/{}", self.url, id);
+            let reply: Value = self
+                .http
+                .get(&url)
+                .send()
+                .await
+                .map_err(|e| anyhow!("Schema registry {}: {}", self.url, e))?
+                .error_for_status()
+                .map_err(|e| anyhow!("Schema {}: {}", id, e))?
+                .json()
+                .await?;
+            let text = reply["schema"]
+                .as_str()
+                .ok_or_else(|| anyhow!("Schema {} has no Avro schema", id))?;
+            let schema: Value = serde_json::from_str(text)?;
+            let mut names = HashMap::new();
+            collect_names(&schema, "", &mut names);
+            self.schemas.insert(id, (schema, names));
+        }
+        Ok(&self.schemas[&id])
+    }
+
+    pub async fn decode(&mut self, data: &[u8]) -> Result<Value> {
+        if data.len() < 5 || data[0] != 0 {
+            return Err(anyhow!(
+                "Not in the schema registry's wire format (zero byte and schema id)"
+            ));
+        }
+        let id = u32::from_be_bytes(data[1..5].try_into()?);
+        let (schema, names) = self.schema(id).await?;
+        let mut reader = Reader::new(&data[5..]);
+        decode(schema, &mut reader, names).map_err(|e| anyhow!("Schema {}: {}", id, e))
+    }
+}
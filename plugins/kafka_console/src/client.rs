@@ -0,0 +1,715 @@
+// A small Kafka client that works through tunnels. Brokers advertise addresses that only
+// resolve inside the cluster, so every broker connection is rewritten: in Kubernetes to a
+// port-forward to the broker's pod (found by pod IP, or by the first label of its DNS
+// name), otherwise to an address from the broker map, e.g. a local SSH or SSM tunnel.
+use crate::protocol::{self, Reader, Writer};
+use crate::records::{self, Record};
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, ListParams};
+use plugin_api::k8s;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const CLIENT_ID: &str = "proxy-kafka-console";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest response accepted, well above what the fetch limits ask for
+const MAX_RESPONSE_SIZE: usize = 256 * 1024 * 1024;
+/// Fetch size limits
+const FETCH_MAX_BYTES: i32 = 16 * 1024 * 1024;
+const PARTITION_MAX_BYTES: i32 = 4 * 1024 * 1024;
+
+/// Where broker connections go
+pub enum Connector {
+    Direct {
+        bootstrap: String,
+        /// Advertised "host:port" → reachable "host:port"
+        broker_map: BTreeMap<String, String>,
+    },
+    Kubernetes {
+        pods: Api<Pod>,
+        namespace: String,
+        pod_name: Option<String>,
+        pod_selector: Option<String>,
+        port: u16,
+    },
+}
+
+struct Connection {
+    reader: Box<dyn AsyncRead + Unpin + Send>,
+    writer: Box<dyn AsyncWrite + Unpin + Send>,
+    correlation: i32,
+}
+
+impl Connection {
+    fn new<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Connection {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            correlation: 0,
+        }
+    }
+
+    async fn exchange(&mut self, api_key: i16, version: i16, body: &[u8]) -> Result<Vec<u8>> {
+        self.correlation += 1;
+        let mut request = Writer::default();
+        request
+            .i16(api_key)
+            .i16(version)
+            .i32(self.correlation)
+            .nullable_string(Some(CLIENT_ID));
+        request.buf.extend_from_slice(body);
+        self.writer
+            .write_all(&(request.buf.len() as i32).to_be_bytes())
+            .await?;
+        self.writer.write_all(&request.buf).await?;
+        self.writer.flush().await?;
+
+        let size = self.reader.read_i32().await?;
+        if size < 4 || size as usize > MAX_RESPONSE_SIZE {
+            return Err(anyhow!("Invalid response size {}", size));
+        }
+        let mut response = vec![0u8; size as usize];
+        self.reader.read_exact(&mut response).await?;
+        let correlation = i32::from_be_bytes(response[..4].try_into()?);
+        if correlation != self.correlation {
+            return Err(anyhow!("Response out of order"));
+        }
+        response.drain(..4);
+        Ok(response)
+    }
+
+    async fn call(&mut self, api_key: i16, version: i16, body: &[u8]) -> Result<Vec<u8>> {
+        tokio::time::timeout(REQUEST_TIMEOUT, self.exchange(api_key, version, body))
+            .await
+            .map_err(|_| anyhow!("No response after {}s", REQUEST_TIMEOUT.as_secs()))?
+    }
+}
+
+impl Connector {
+    async fn forward(pods: &Api<Pod>, namespace: &str, pod: &str, port: u16) -> Result<Connection> {
+        plugin_api::audit::record_k8s(
+            "kafka_console",
+            "port_forward",
+            &[
+                ("namespace", namespace),
+                ("pod", pod),
+                ("remote_port", &port.to_string()),
+            ],
+        );
+        let mut forwarder = pods.portforward(pod, &[port]).await?;
+        let stream = forwarder
+            .take_stream(port)
+            .ok_or_else(|| anyhow!("No stream for port {}", port))?;
+        Ok(Connection::new(stream))
+    }
+
+    async fn tcp(address: &str) -> Result<Connection> {
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| anyhow!("Can't connect to {}: {}", address, e))?;
+        let _ = stream.set_nodelay(true);
+        Ok(Connection::new(stream))
+    }
+
+    /// The first connection, before any broker is known; with where it went
+    async fn bootstrap(&self) -> Result<(Connection, String)> {
+        match self {
+            Connector::Direct { bootstrap, .. } => {
+                Ok((Self::tcp(bootstrap).await?, bootstrap.clone()))
+            }
+            Connector::Kubernetes {
+                pods,
+                namespace,
+                pod_name,
+                pod_selector,
+                port,
+            } => {
+                let pod = match pod_name {
+                    Some(name) => name.clone(),
+                    None => {
+                        let selector = pod_selector.as_deref().unwrap_or_default();
+                        let pod = k8s::running_pod(pods, selector).await?;
+                        pod.metadata
+                            .name
+                            .ok_or_else(|| anyhow!("Pod has no name"))?
+                    }
+                };
+                let route = format!("pod {}:{}", pod, port);
+                Ok((Self::forward(pods, namespace, &pod, *port).await?, route))
+            }
+        }
+    }
+
+    /// The pod behind an advertised broker address
+    async fn broker_pod(&self, pods: &Api<Pod>, host: &str) -> Result<String> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            let list = pods.list(&ListParams::default()).await?;
+            return list
+                .items
+                .iter()
+                .find(|pod| {
+                    pod.status
+                        .as_ref()
+                        .and_then(|status| status.pod_ip.as_deref())
+                        == Some(&ip.to_string())
+                })
+                .and_then(|pod| pod.metadata.name.clone())
+                .ok_or_else(|| anyhow!("No pod has the broker's IP {}", ip));
+        }
+        // kafka-0.kafka-headless.ns.svc... is pod kafka-0
+        let name = host.split('.').next().unwrap_or(host);
+        match pods.get_opt(name).await? {
+            Some(_) => Ok(name.to_string()),
+            None => Err(anyhow!(
+                "Can't tell which pod is broker {} (no pod named {})",
+                host,
+                name
+            )),
+        }
+    }
+
+    /// A connection to a broker by its advertised address; with where it went
+    async fn broker(&self, broker: &Broker, only_broker: bool) -> Result<(Connection, String)> {
+        let advertised = plugin_api::net::host_port(&broker.host, broker.port as u16);
+        match self {
+            Connector::Direct { broker_map, .. } => {
+                let address = broker_map.get(&advertised).unwrap_or(&advertised);
+                Ok((Self::tcp(address).await?, address.clone()))
+            }
+            Connector::Kubernetes {
+                pods, namespace, ..
+            } => {
+                let pod = match self.broker_pod(pods, &broker.host).await {
+                    Ok(pod) => pod,
+                    // A single broker advertising a service name is the bootstrap pod
+                    Err(_) if only_broker => return self.bootstrap().await,
+                    Err(e) => return Err(e),
+                };
+                let port = u16::try_from(broker.port)?;
+                let route = format!("pod {}:{}", pod, port);
+                Ok((Self::forward(pods, namespace, &pod, port).await?, route))
+            }
+        }
+    }
+}
+
+pub struct Broker {
+    pub id: i32,
+    pub host: String,
+    pub port: i32,
+}
+
+pub struct PartitionMetadata {
+    pub id: i32,
+    pub error: i16,
+    pub leader: i32,
+    pub replicas: Vec<i32>,
+    pub isr: Vec<i32>,
+}
+
+pub struct TopicMetadata {
+    pub name: String,
+    pub error: i16,
+    pub internal: bool,
+    pub partitions: Vec<PartitionMetadata>,
+}
+
+pub struct FetchedPartition {
+    pub partition: i32,
+    pub error: i16,
+    pub high_watermark: i64,
+    pub records: Vec<Record>,
+    /// Where the next fetch continues, past any transaction markers
+    pub next_offset: Option<i64>,
+}
+
+pub struct Cluster {
+    connector: Connector,
+    bootstrap: Connection,
+    pub brokers: BTreeMap<i32, Broker>,
+    /// Where each broker's connection went, for display
+    pub routes: BTreeMap<i32, String>,
+    connections: HashMap<i32, Connection>,
+}
+
+fn int_array(reader: &mut Reader) -> Result<Vec<i32>> {
+    (0..reader.array()?).map(|_| reader.i32()).collect()
+}
+
+impl Cluster {
+    pub async fn connect(connector: Connector) -> Result<(Self, String)> {
+        let (bootstrap, route) = connector.bootstrap().await?;
+        Ok((
+            Cluster {
+                connector,
+                bootstrap,
+                brokers: BTreeMap::new(),
+                routes: BTreeMap::new(),
+                connections: HashMap::new(),
+            },
+            route,
+        ))
+    }
+
+    async fn broker(&mut self, id: i32) -> Result<&mut Connection> {
+        if !self.connections.contains_key(&id) {
+            let broker = self
+                .brokers
+                .get(&id)
+                .ok_or_else(|| anyhow!("Unknown broker {}", id))?;
+            let (connection, route) = self
+                .connector
+                .broker(broker, self.brokers.len() == 1)
+                .await
+                .map_err(|e| anyhow!("Broker {}: {}", id, e))?;
+            eprintln!(
+                "🔀 Broker {} ({}:{}) → {}",
+                id, broker.host, broker.port, route
+            );
+            self.routes.insert(id, route);
+            self.connections.insert(id, connection);
+        }
+        Ok(self.connections.get_mut(&id).unwrap())
+    }
+
+    /// Drops a broker's connection, e.g. after it stopped leading a partition
+    pub fn forget(&mut self, id: i32) {
+        self.connections.remove(&id);
+    }
+
+    /// Metadata v4: brokers and the given topics (all topics when None), without creating
+    /// missing ones
+    pub async fn metadata(&mut self, topics: Option<&[String]>) -> Result<Vec<TopicMetadata>> {
+        let mut request = Writer::default();
+        match topics {
+            Some(topics) => {
+                request.i32(topics.len() as i32);
+                for topic in topics {
+                    request.string(topic);
+                }
+            }
+            None => {
+                request.i32(-1);
+            }
+        }
+        request.i8(0);
+        let response = self
+            .bootstrap
+            .call(protocol::METADATA, 4, &request.buf)
+            .await?;
+
+        let mut reader = Reader::new(&response);
+        reader.i32()?;
+        self.brokers.clear();
+        for _ in 0..reader.array()? {
+            let broker = Broker {
+                id: reader.i32()?,
+                host: reader.string()?,
+                port: reader.i32()?,
+            };
+            reader.nullable_string()?;
+            self.brokers.insert(broker.id, broker);
+        }
+        reader.nullable_string()?;
+        reader.i32()?;
+        let mut topics = Vec::new();
+        for _ in 0..reader.array()? {
+            let error = reader.i16()?;
+            let name = reader.string()?;
+            let internal = reader.i8()? != 0;
+            let mut partitions = Vec::new();
+            for _ in 0..reader.array()? {
+                partitions.push(PartitionMetadata {
+                    error: reader.i16()?,
+                    id: reader.i32()?,
+                    leader: reader.i32()?,
+                    replicas: int_array(&mut reader)?,
+                    isr: int_array(&mut reader)?,
+                });
+            }
+            partitions.sort_by_key(|partition| partition.id);
+            topics.push(TopicMetadata {
+                name,
+                error,
+                internal,
+                partitions,
+            });
+        }
+        topics.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(topics)
+    }
+
+    /// ListOffsets v1: the offset of each partition at `timestamp` (-1 latest, -2
+    /// earliest), asked of its leader
+    pub async fn list_offsets(
+        &mut self,
+        topic: &str,
+        partitions: &[(i32, i32)],
+        timestamp: i64,
+    ) -> Result<BTreeMap<i32, i64>> {
+        let mut by_leader: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
+        for (partition, leader) in partitions {
+            by_leader.entry(*leader).or_default().push(*partition);
+        }
+        let mut offsets = BTreeMap::new();
+        for (leader, partitions) in by_leader {
+            let mut request = Writer::default();
+            request
+                .i32(-1)
+                .i32(1)
+                .string(topic)
+                .i32(partitions.len() as i32);
+            for partition in &partitions {
+                request.i32(*partition).i64(timestamp);
+            }
+            let response = self
+                .broker(leader)
+                .await?
+                .call(protocol::LIST_OFFSETS, 1, &request.buf)
+                .await?;
+            let mut reader = Reader::new(&response);
+            for _ in 0..reader.array()? {
+                reader.string()?;
+                for _ in 0..reader.array()? {
+                    let partition = reader.i32()?;
+                    let error = reader.i16()?;
+                    reader.i64()?;
+                    let offset = reader.i64()?;
+                    if error != 0 {
+                        return Err(anyhow!(
+                            "Offsets of {}/{}: {}",
+                            topic,
+                            partition,
+                            protocol::error_name(error)
+                        ));
+                    }
+                    offsets.insert(partition, offset);
+                }
+            }
+        }
+        Ok(offsets)
+    }
+
+    /// Fetch v4 from one leader, reading uncommitted records
+    pub async fn fetch(
+        &mut self,
+        leader: i32,
+        topic: &str,
+        partitions: &[(i32, i64)],
+        max_wait: Duration,
+    ) -> Result<Vec<FetchedPartition>> {
+        let mut request = Writer::default();
+        request
+            .i32(-1)
+            .i32(max_wait.as_millis() as i32)
+            .i32(1)
+            .i32(FETCH_MAX_BYTES)
+            .i8(0)
+            .i32(1)
+            .string(topic)
+            .i32(partitions.len() as i32);
+        for (partition, offset) in partitions {
+            request
+                .i32(*partition)
+                .i64(*offset)
+                .i32(PARTITION_MAX_BYTES);
+        }
+        let response = self
+            .broker(leader)
+            .await?
+            .call(protocol::FETCH, 4, &request.buf)
+            .await?;
+
+        let mut reader = Reader::new(&response);
+        reader.i32()?;
+        let mut fetched = Vec::new();
+        for _ in 0..reader.array()? {
+            reader.string()?;
+            for _ in 0..reader.array()? {
+                let partition = reader.i32()?;
+                let error = reader.i16()?;
+                let high_watermark = reader.i64()?;
+                reader.i64()?;
+                for _ in 0..reader.array()? {
+                    reader.i64()?;
+                    reader.i64()?;
+                }
+                let data = reader.nullable_bytes()?.unwrap_or_default();
+                let (records, next_offset) =
+                    records::decode(data).map_err(|e| anyhow!("{}/{}: {}", topic, partition, e))?;
+                fetched.push(FetchedPartition {
+                    partition,
+                    error,
+                    high_watermark,
+                    records,
+                    next_offset,
+                });
+            }
+        }
+        Ok(fetched)
+    }
+
+    /// Produce v3 to one leader, waiting for all in-sync replicas; the base offset of each
+    /// partition's batch
+    pub async fn produce(
+        &mut self,
+        leader: i32,
+        topic: &str,
+        batches: &[(i32, Vec<u8>)],
+    ) -> Result<BTreeMap<i32, i64>> {
+        let mut request = Writer::default();
+        request
+            .nullable_string(None)
+            .i16(-1)
+            .i32(REQUEST_TIMEOUT.as_millis() as i32)
+            .i32(1)
+            .string(topic)
+            .i32(batches.len() as i32);
+        for (partition, batch) in batches {
+            request.i32(*partition).bytes(batch);
+        }
+        let response = self
+            .broker(leader)
+            .await?
+            .call(protocol::PRODUCE, 3, &request.buf)
+            .await?;
+
+        let mut reader = Reader::new(&response);
+        let mut offsets = BTreeMap::new();
+        for _ in 0..reader.array()? {
+            reader.string()?;
+            for _ in 0..reader.array()? {
+                let partition = reader.i32()?;
+                let error = reader.i16()?;
+                let base_offset = reader.i64()?;
+                reader.i64()?;
+                if error != 0 {
+                    return Err(anyhow!(
+                        "Producing to {}/{}: {}",
+                        topic,
+                        partition,
+                        protocol::error_name(error)
+                    ));
+                }
+                offsets.insert(partition, base_offset);
+            }
+        }
+        Ok(offsets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Where the mock broker advertises itself, which only the broker map resolves
+    const ADVERTISED_HOST: &str = "kafka-0.kafka-headless.data.svc";
+    const ADVERTISED_PORT: i32 = 9092;
+
+    /// A broker answering each request with `answer(api_key, version, body)`; its address
+    async fn mock_broker(answer: fn(i16, i16, &[u8]) -> Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    while let Ok(size) = socket.read_i32().await {
+                        let mut request = vec![0u8; size as usize];
+                        socket.read_exact(&mut request).await.unwrap();
+                        let mut reader = Reader::new(&request);
+                        let api_key = reader.i16().unwrap();
+                        let version = reader.i16().unwrap();
+                        let correlation = reader.i32().unwrap();
+                        assert_eq!(
+                            reader.nullable_string().unwrap().as_deref(),
+                            Some(CLIENT_ID)
+                        );
+                        let body =
+                            answer(api_key, version, reader.take(reader.remaining()).unwrap());
+                        let mut response = Writer::default();
+                        response.i32(body.len() as i32 + 4).i32(correlation);
+                        response.buf.extend_from_slice(&body);
+                        socket.write_all(&response.buf).await.unwrap();
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    /// Metadata v4 with the advertised broker leading both partitions of "orders"
+    fn metadata_response() -> Vec<u8> {
+        let mut response = Writer::default();
+        response
+            .i32(0)
+            .i32(1)
+            .i32(0)
+            .string(ADVERTISED_HOST)
+            .i32(ADVERTISED_PORT)
+            .nullable_string(None)
+            .nullable_string(Some("cluster"))
+            .i32(0)
+            .i32(2);
+        response.i16(0).string("payments").i8(0).i32(0);
+        response.i16(0).string("orders").i8(0).i32(2);
+        for partition in [1, 0] {
+            response
+                .i16(0)
+                .i32(partition)
+                .i32(0)
+                .i32(1)
+                .i32(0)
+                .i32(1)
+                .i32(0);
+        }
+        response.buf
+    }
+
+    fn answer(api_key: i16, version: i16, body: &[u8]) -> Vec<u8> {
+        let mut request = Reader::new(body);
+        let mut response = Writer::default();
+        match (api_key, version) {
+            (protocol::METADATA, 4) => return metadata_response(),
+            (protocol::FETCH, 4) => {
+                let batch = records::encode(
+                    &[Record {
+                        offset: 0,
+                        timestamp: 0,
+                        key: Some(b"order-1".to_vec()),
+                        value: Some(b"{\"status\":\"paid\"}".to_vec()),
+                        headers: Vec::new(),
+                    }],
+                    1_760_000_000_000,
+                );
+                response
+                    .i32(0)
+                    .i32(1)
+                    .string("orders")
+                    .i32(1)
+                    .i32(0)
+                    .i16(0)
+                    .i64(1)
+                    .i64(1)
+                    .i32(0)
+                    .bytes(&batch);
+            }
+            (protocol::PRODUCE, 3) => {
+                request.nullable_string().unwrap();
+                request.i16().unwrap();
+                request.i32().unwrap();
+                request.array().unwrap();
+                let topic = request.string().unwrap();
+                request.array().unwrap();
+                let partition = request.i32().unwrap();
+                // Unknown topics are refused, the way a broker without auto-creation does
+                let error = if topic == "orders" { 0 } else { 3 };
+                response
+                    .i32(1)
+                    .string(&topic)
+                    .i32(1)
+                    .i32(partition)
+                    .i16(error)
+                    .i64(41)
+                    .i64(-1)
+                    .i32(0);
+            }
+            other => panic!("Unexpected request {:?}", other),
+        }
+        response.buf
+    }
+
+    /// A cluster bootstrapped from the mock broker, and the broker's address
+    async fn cluster() -> (Cluster, String) {
+        let address = mock_broker(answer).await;
+        let broker_map = BTreeMap::from([(
+            format!("{}:{}", ADVERTISED_HOST, ADVERTISED_PORT),
+            address.clone(),
+        )]);
+        let (cluster, route) = Cluster::connect(Connector::Direct {
+            bootstrap: address.clone(),
+            broker_map,
+        })
+        .await
+        .unwrap();
+        assert_eq!(route, address);
+        (cluster, address)
+    }
+
+    #[tokio::test]
+    async fn metadata_lists_brokers_and_topics_in_order() {
+        let (mut cluster, _) = cluster().await;
+        let topics = cluster.metadata(None).await.unwrap();
+
+        let broker = &cluster.brokers[&0];
+        assert_eq!(
+            (broker.host.as_str(), broker.port),
+            (ADVERTISED_HOST, ADVERTISED_PORT)
+        );
+        let names: Vec<&str> = topics.iter().map(|topic| topic.name.as_str()).collect();
+        assert_eq!(names, ["orders", "payments"]);
+        let partitions: Vec<i32> = topics[0].partitions.iter().map(|p| p.id).collect();
+        assert_eq!(partitions, [0, 1]);
+        assert_eq!(topics[0].partitions[0].replicas, [0]);
+    }
+
+    #[tokio::test]
+    async fn fetches_through_the_broker_map() {
+        let (mut cluster, address) = cluster().await;
+        cluster
+            .metadata(Some(&["orders".to_string()]))
+            .await
+            .unwrap();
+        let fetched = cluster
+            .fetch(0, "orders", &[(0, 0)], Duration::from_millis(10))
+            .await
+            .unwrap();
+
+        assert_eq!(cluster.routes[&0], address);
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].high_watermark, 1);
+        assert_eq!(fetched[0].next_offset, Some(1));
+        let record = &fetched[0].records[0];
+        assert_eq!(record.key.as_deref(), Some(&b"order-1"[..]));
+        assert_eq!(record.value.as_deref(), Some(&b"{\"status\":\"paid\"}"[..]));
+    }
+
+    #[tokio::test]
+    async fn produce_returns_base_offsets_and_names_errors() {
+        let (mut cluster, _) = cluster().await;
+        cluster.metadata(None).await.unwrap();
+        let batch = records::encode(&[], 0);
+
+        let offsets = cluster
+            .produce(0, "orders", &[(1, batch.clone())])
+            .await
+            .unwrap();
+        assert_eq!(offsets, BTreeMap::from([(1, 41)]));
+        let error = cluster
+            .produce(0, "missing", &[(0, batch)])
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Producing to missing/0: UNKNOWN_TOPIC_OR_PARTITION"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_brokers_are_errors() {
+        let (mut cluster, _) = cluster().await;
+        let error = cluster
+            .fetch(7, "orders", &[(0, 0)], Duration::from_millis(10))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Unknown broker 7");
+    }
+}
@@ -0,0 +1,1063 @@
+// A kcat-like Kafka console that works from outside the cluster. Brokers advertise
+// in-cluster addresses, which breaks clients behind a plain port-forward; here every
+// broker gets its own forward (or mapped tunnel address), so listing topics, consuming
+// and producing work against any partition leader.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use kube::Client;
+use plugin_api::{net, Plugin};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, IsTerminal};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+mod avro;
+mod client;
+mod protocol;
+mod records;
+
+use avro::Registry;
+use client::{Cluster, Connector, TopicMetadata};
+use records::Record;
+
+const DEFAULT_PORT: u16 = 9092;
+/// How long a fetch waits on the broker for new messages
+const FETCH_WAIT: Duration = Duration::from_millis(500);
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Fetch errors that mean the partition moved to another broker
+const LEADER_ERRORS: [i16; 4] = [5, 6, 74, 75];
+const OFFSET_OUT_OF_RANGE: i16 = 1;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct KafkaConsoleConfig {
+    /// Namespace (default: the kubeconfig context's)
+    pub namespace: Option<String>,
+    /// Any broker pod, for the first connection
+    pub pod_name: Option<String>,
+    /// Label selector of the broker pods, e.g. "app=kafka"
+    pub pod_selector: Option<String>,
+    /// Broker port in the pods (default: 9092)
+    pub port: Option<u16>,
+    /// Connect to this "host:port" instead of going through Kubernetes
+    pub bootstrap: Option<String>,
+    /// Advertised broker address → reachable address, for direct connections
+    #[serde(default)]
+    pub broker_map: BTreeMap<String, String>,
+    /// Schema registry URL, for --format avro
+    pub schema_registry: Option<String>,
+}
+
+pub struct KafkaConsolePlugin;
+
+impl KafkaConsolePlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Kafka Console Configuration
+namespace = "kafka"
+pod_selector = "app.kubernetes.io/name=kafka"  # Either use pod_name OR pod_selector
+# pod_name = "kafka-0"
+port = 9092
+# schema_registry = "http://localhost:8081"
+
+# Without Kubernetes, e.g. through SSH tunnels: the bootstrap address, and where each
+# advertised broker address is reachable locally
+# bootstrap = "localhost:19092"
+# [broker_map]
+# "b-1.kafka.internal:9092" = "localhost:19092"
+# "b-2.kafka.internal:9092" = "localhost:19093"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<KafkaConsoleConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: KafkaConsoleConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Everything can be given on the command line
+                Ok(KafkaConsoleConfig::default())
+            }
+        }
+        None => Ok(KafkaConsoleConfig::default()),
+    }
+}
+
+/// "NAME=VALUE" pairs, for --broker-map and --header
+fn parse_pair(pair: &str) -> Result<(String, String)> {
+    pair.split_once('=')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .ok_or_else(|| anyhow!("Expected NAME=VALUE, got '{}'", pair))
+}
+
+async fn connector(config: &KafkaConsoleConfig) -> Result<Connector> {
+    if let Some(bootstrap) = &config.bootstrap {
+        let bootstrap = match net::split_host_port(bootstrap) {
+            (_, Some(_)) => bootstrap.clone(),
+            (host, None) => net::host_port(host, DEFAULT_PORT),
+        };
+        return Ok(Connector::Direct {
+            bootstrap,
+            broker_map: config.broker_map.clone(),
+        });
+    }
+    let client = Client::try_default().await?;
+    let namespace = config
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+    let pods: Api<Pod> = Api::namespaced(client, &namespace);
+    Ok(Connector::Kubernetes {
+        pods,
+        namespace,
+        pod_name: config.pod_name.clone(),
+        pod_selector: config.pod_selector.clone(),
+        port: config.port.unwrap_or(DEFAULT_PORT),
+    })
+}
+
+async fn topic_metadata(cluster: &mut Cluster, topic: &str) -> Result<TopicMetadata> {
+    let found = cluster
+        .metadata(Some(&[topic.to_string()]))
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No metadata for topic {}", topic))?;
+    if found.error != 0 {
+        return Err(anyhow!(
+            "Topic {}: {}",
+            topic,
+            protocol::error_name(found.error)
+        ));
+    }
+    Ok(found)
+}
+
+fn join(ids: &[i32]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+async fn list_topics(cluster: &mut Cluster) -> Result<()> {
+    let topics = cluster.metadata(None).await?;
+    println!("🖥️  Brokers:");
+    for broker in cluster.brokers.values() {
+        println!("   {}  {}:{}", broker.id, broker.host, broker.port);
+    }
+    if topics.is_empty() {
+        println!("📭 No topics");
+        return Ok(());
+    }
+    let width = topics
+        .iter()
+        .map(|topic| topic.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    println!();
+    println!(
+        "{:<width$}  {:>10}  {:>11}",
+        "TOPIC", "PARTITIONS", "REPLICATION"
+    );
+    for topic in &topics {
+        let replication = topic
+            .partitions
+            .first()
+            .map(|partition| partition.replicas.len())
+            .unwrap_or(0);
+        let internal = if topic.internal { "  (internal)" } else { "" };
+        println!(
+            "{:<width$}  {:>10}  {:>11}{}",
+            topic.name,
+            topic.partitions.len(),
+            replication,
+            internal
+        );
+    }
+    Ok(())
+}
+
+async fn describe_topic(cluster: &mut Cluster, topic: &str) -> Result<()> {
+    let found = topic_metadata(cluster, topic).await?;
+    let leaders: Vec<(i32, i32)> = found
+        .partitions
+        .iter()
+        .filter(|partition| partition.error == 0)
+        .map(|partition| (partition.id, partition.leader))
+        .collect();
+    let earliest = cluster.list_offsets(topic, &leaders, -2).await?;
+    let latest = cluster.list_offsets(topic, &leaders, -1).await?;
+    println!("📚 {} ({} partitions)", found.name, found.partitions.len());
+    println!(
+        "{:>9}  {:>6}  {:<12}  {:<12}  {:>12}  {:>12}  {:>10}",
+        "PARTITION", "LEADER", "REPLICAS", "ISR", "EARLIEST", "LATEST", "MESSAGES"
+    );
+    for partition in &found.partitions {
+        if partition.error != 0 {
+            println!(
+                "{:>9}  {}",
+                partition.id,
+                protocol::error_name(partition.error)
+            );
+            continue;
+        }
+        let first = earliest.get(&partition.id).copied().unwrap_or_default();
+        let last = latest.get(&partition.id).copied().unwrap_or_default();
+        println!(
+            "{:>9}  {:>6}  {:<12}  {:<12}  {:>12}  {:>12}  {:>10}",
+            partition.id,
+            partition.leader,
+            join(&partition.replicas),
+            join(&partition.isr),
+            first,
+            last,
+            last - first
+        );
+    }
+    Ok(())
+}
+
+/// Where consuming starts in each partition
+#[derive(Clone, Copy)]
+enum Start {
+    Beginning,
+    End,
+    At(i64),
+    /// This many messages before the end
+    FromEnd(i64),
+}
+
+fn parse_start(value: &str) -> Result<Start> {
+    match value {
+        "beginning" | "earliest" => Ok(Start::Beginning),
+        "end" | "latest" => Ok(Start::End),
+        _ => {
+            let offset: i64 = value.parse().map_err(|_| {
+                anyhow!(
+                    "Invalid offset '{}' (beginning, end, an offset, or -N for the last N)",
+                    value
+                )
+            })?;
+            if offset < 0 {
+                Ok(Start::FromEnd(-offset))
+            } else {
+                Ok(Start::At(offset))
+            }
+        }
+    }
+}
+
+struct ConsumeOptions {
+    partition: Option<i32>,
+    start: Start,
+    count: Option<usize>,
+    follow: bool,
+    key_format: String,
+    value_format: String,
+    json: bool,
+}
+
+struct Position {
+    leader: i32,
+    next: i64,
+    /// Where consuming stops without --follow
+    end: i64,
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes a key or value; what can't be decoded is shown as text after a warning
+async fn decode(
+    registry: &mut Option<Registry>,
+    data: Option<&[u8]>,
+    format: &str,
+    label: &str,
+) -> Value {
+    let Some(data) = data else {
+        return Value::Null;
+    };
+    let decoded = match format {
+        "json" => serde_json::from_slice(data).map_err(|e| anyhow!("not JSON: {}", e)),
+        "avro" => match registry.as_mut() {
+            Some(registry) => registry.decode(data).await,
+            None => Err(anyhow!("no schema registry configured")),
+        },
+        "hex" => Ok(Value::String(hex(data))),
+        _ => Ok(Value::String(String::from_utf8_lossy(data).into_owned())),
+    };
+    decoded.unwrap_or_else(|e| {
+        eprintln!("⚠️  {}: {}", label, e);
+        Value::String(String::from_utf8_lossy(data).into_owned())
+    })
+}
+
+fn timestamp(millis: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(millis)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+        .unwrap_or_else(|| millis.to_string())
+}
+
+async fn print_record(
+    registry: &mut Option<Registry>,
+    options: &ConsumeOptions,
+    partition: i32,
+    record: &Record,
+) -> Result<()> {
+    let label = format!("p{} @{}", partition, record.offset);
+    let key = decode(
+        registry,
+        record.key.as_deref(),
+        &options.key_format,
+        &format!("{} key", label),
+    )
+    .await;
+    let value = decode(
+        registry,
+        record.value.as_deref(),
+        &options.value_format,
+        &label,
+    )
+    .await;
+    let headers: Map<String, Value> = record
+        .headers
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .as_deref()
+                .map(|value| Value::String(String::from_utf8_lossy(value).into_owned()))
+                .unwrap_or(Value::Null);
+            (name.clone(), value)
+        })
+        .collect();
+
+    if options.json {
+        let line = json!({
+            "partition": partition,
+            "offset": record.offset,
+            "timestamp": chrono::DateTime::from_timestamp_millis(record.timestamp)
+                .map(|time| time.to_rfc3339()),
+            "key": key,
+            "value": value,
+            "headers": headers,
+        });
+        println!("{}", serde_json::to_string(&line)?);
+        return Ok(());
+    }
+
+    let mut heading = format!("📨 {}  {}", label, timestamp(record.timestamp));
+    match &key {
+        Value::Null => {}
+        Value::String(key) => heading.push_str(&format!("  key={}", key)),
+        key => heading.push_str(&format!("  key={}", key)),
+    }
+    println!("{}", heading);
+    for (name, value) in &headers {
+        let value = value.as_str().unwrap_or("(null)");
+        println!("   🏷️  {}={}", name, value);
+    }
+    match &value {
+        Value::Null => println!("(null)"),
+        Value::String(text) => println!("{}", text),
+        value => println!("{}", serde_json::to_string_pretty(value)?),
+    }
+    Ok(())
+}
+
+async fn consume(
+    cluster: &mut Cluster,
+    registry: &mut Option<Registry>,
+    topic: &str,
+    options: &ConsumeOptions,
+) -> Result<()> {
+    let found = topic_metadata(cluster, topic).await?;
+    let leaders: Vec<(i32, i32)> = found
+        .partitions
+        .iter()
+        .filter(|partition| {
+            options
+                .partition
+                .is_none_or(|wanted| wanted == partition.id)
+        })
+        .map(|partition| (partition.id, partition.leader))
+        .collect();
+    if leaders.is_empty() {
+        return Err(anyhow!(
+            "Topic {} has no partition {}",
+            topic,
+            options.partition.unwrap_or_default()
+        ));
+    }
+    let earliest = cluster.list_offsets(topic, &leaders, -2).await?;
+    let latest = cluster.list_offsets(topic, &leaders, -1).await?;
+    let mut positions: BTreeMap<i32, Position> = BTreeMap::new();
+    for (partition, leader) in &leaders {
+        let first = earliest.get(partition).copied().unwrap_or_default();
+        let end = latest.get(partition).copied().unwrap_or_default();
+        let next = match options.start {
+            Start::Beginning => first,
+            Start::End => end,
+            Start::At(offset) => offset.clamp(first, end),
+            Start::FromEnd(count) => (end - count).max(first),
+        };
+        positions.insert(
+            *partition,
+            Position {
+                leader: *leader,
+                next,
+                end,
+            },
+        );
+    }
+
+    if options.follow {
+        ctrlc::set_handler(move || {
+            eprintln!("\n👋 Shutting down...");
+            std::process::exit(0);
+        })?;
+    }
+    eprintln!(
+        "📡 Consuming {} ({} partition(s)){}",
+        topic,
+        positions.len(),
+        if options.follow {
+            ", press Ctrl+C to stop"
+        } else {
+            ""
+        }
+    );
+
+    let mut consumed = 0;
+    loop {
+        let mut by_leader: BTreeMap<i32, Vec<(i32, i64)>> = BTreeMap::new();
+        for (partition, position) in &positions {
+            if options.follow || position.next < position.end {
+                by_leader
+                    .entry(position.leader)
+                    .or_default()
+                    .push((*partition, position.next));
+            }
+        }
+        if by_leader.is_empty() {
+            break;
+        }
+
+        let mut moved = false;
+        for (leader, wanted) in by_leader {
+            let fetched = match cluster.fetch(leader, topic, &wanted, FETCH_WAIT).await {
+                Ok(fetched) => fetched,
+                Err(e) if options.follow => {
+                    // The broker may be restarting; find the leaders again
+                    eprintln!("⚠️  Broker {}: {}", leader, e);
+                    cluster.forget(leader);
+                    moved = true;
+                    tokio::time::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            for part in fetched {
+                let Some(position) = positions.get_mut(&part.partition) else {
+                    continue;
+                };
+                match part.error {
+                    0 => {}
+                    OFFSET_OUT_OF_RANGE => {
+                        let reset = cluster
+                            .list_offsets(topic, &[(part.partition, leader)], -2)
+                            .await?;
+                        let first = reset.get(&part.partition).copied().unwrap_or_default();
+                        eprintln!(
+                            "⏭️  p{} @{} was deleted, continuing from @{}",
+                            part.partition, position.next, first
+                        );
+                        position.next = first;
+                        continue;
+                    }
+                    error if LEADER_ERRORS.contains(&error) => {
+                        moved = true;
+                        continue;
+                    }
+                    error => {
+                        return Err(anyhow!(
+                            "{}/{}: {}",
+                            topic,
+                            part.partition,
+                            protocol::error_name(error)
+                        ))
+                    }
+                }
+                for record in &part.records {
+                    // Batches can start before the offset asked for
+                    if record.offset < position.next {
+                        continue;
+                    }
+                    if !options.follow && record.offset >= position.end {
+                        break;
+                    }
+                    print_record(registry, options, part.partition, record).await?;
+                    position.next = record.offset + 1;
+                    consumed += 1;
+                    if options.count == Some(consumed) {
+                        return Ok(());
+                    }
+                }
+                if let Some(next) = part.next_offset {
+                    position.next = position.next.max(next);
+                }
+                // Truncated by a leader change: nothing more will come below it
+                position.end = position.end.min(part.high_watermark);
+            }
+        }
+
+        if moved {
+            let refreshed = topic_metadata(cluster, topic).await?;
+            for partition in refreshed.partitions {
+                if let Some(position) = positions.get_mut(&partition.id) {
+                    if position.leader != partition.leader {
+                        eprintln!(
+                            "🔁 p{} is now led by broker {}",
+                            partition.id, partition.leader
+                        );
+                        cluster.forget(position.leader);
+                        position.leader = partition.leader;
+                    }
+                }
+            }
+        }
+    }
+    eprintln!("🏁 Reached the end of {} ({} message(s))", topic, consumed);
+    Ok(())
+}
+
+struct ProduceOptions {
+    partition: Option<i32>,
+    key: Option<String>,
+    /// Splits each input line into key and value
+    delimiter: Option<String>,
+    headers: Vec<(String, String)>,
+    json: bool,
+}
+
+/// A message from a line of input
+fn message(options: &ProduceOptions, line: &str, number: usize) -> Result<Record> {
+    let (key, value) = match &options.delimiter {
+        Some(delimiter) => {
+            let (key, value) = line
+                .split_once(delimiter.as_str())
+                .ok_or_else(|| anyhow!("Line {} has no key delimiter '{}'", number, delimiter))?;
+            (Some(key.to_string()), value)
+        }
+        None => (options.key.clone(), line),
+    };
+    if options.json {
+        serde_json::from_str::<Value>(value)
+            .map_err(|e| anyhow!("Line {} isn't valid JSON: {}", number, e))?;
+    }
+    Ok(Record {
+        offset: 0,
+        timestamp: 0,
+        key: key.map(String::into_bytes),
+        value: Some(value.as_bytes().to_vec()),
+        headers: options
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), Some(value.as_bytes().to_vec())))
+            .collect(),
+    })
+}
+
+struct Producer {
+    topic: String,
+    /// Leader of each partition
+    leaders: BTreeMap<i32, i32>,
+    next_partition: usize,
+}
+
+impl Producer {
+    /// The explicit partition, the Java client's partition for the key, or round-robin
+    fn partition(&mut self, options: &ProduceOptions, record: &Record) -> i32 {
+        if let Some(partition) = options.partition {
+            return partition;
+        }
+        let count = self.leaders.len();
+        let index = match &record.key {
+            Some(key) => (protocol::murmur2(key) & 0x7fff_ffff) as usize % count,
+            None => {
+                self.next_partition += 1;
+                self.next_partition % count
+            }
+        };
+        *self.leaders.keys().nth(index).unwrap_or(&0)
+    }
+
+    /// Sends messages in one batch per partition; the offsets they got, per partition
+    async fn send(
+        &mut self,
+        cluster: &mut Cluster,
+        options: &ProduceOptions,
+        messages: Vec<Record>,
+    ) -> Result<BTreeMap<i32, (i64, usize)>> {
+        let mut by_partition: BTreeMap<i32, Vec<Record>> = BTreeMap::new();
+        for record in messages {
+            let partition = self.partition(options, &record);
+            by_partition.entry(partition).or_default().push(record);
+        }
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut by_leader: BTreeMap<i32, Vec<(i32, Vec<u8>)>> = BTreeMap::new();
+        let mut counts = BTreeMap::new();
+        for (partition, records) in &by_partition {
+            let leader = self.leaders[partition];
+            counts.insert(*partition, records.len());
+            by_leader
+                .entry(leader)
+                .or_default()
+                .push((*partition, records::encode(records, now)));
+        }
+        let mut produced = BTreeMap::new();
+        for (leader, batches) in by_leader {
+            let offsets = cluster.produce(leader, &self.topic, &batches).await?;
+            for (partition, offset) in offsets {
+                produced.insert(partition, (offset, counts[&partition]));
+            }
+        }
+        Ok(produced)
+    }
+}
+
+fn offsets(produced: &BTreeMap<i32, (i64, usize)>) -> String {
+    produced
+        .iter()
+        .map(|(partition, (offset, count))| match count {
+            1 => format!("p{} @{}", partition, offset),
+            _ => format!("p{} @{}-{}", partition, offset, offset + *count as i64 - 1),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+async fn produce(
+    cluster: &mut Cluster,
+    topic: &str,
+    values: Vec<String>,
+    options: &ProduceOptions,
+) -> Result<()> {
+    let found = topic_metadata(cluster, topic).await?;
+    let mut producer = Producer {
+        topic: topic.to_string(),
+        leaders: found
+            .partitions
+            .iter()
+            .map(|partition| (partition.id, partition.leader))
+            .collect(),
+        next_partition: 0,
+    };
+    if producer.leaders.is_empty() {
+        return Err(anyhow!("Topic {} has no partitions", topic));
+    }
+    if let Some(partition) = options.partition {
+        if !producer.leaders.contains_key(&partition) {
+            return Err(anyhow!("Topic {} has no partition {}", topic, partition));
+        }
+    }
+
+    if !values.is_empty() {
+        let messages = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| message(options, value, i + 1))
+            .collect::<Result<Vec<_>>>()?;
+        let count = messages.len();
+        let produced = producer.send(cluster, options, messages).await?;
+        println!(
+            "✅ Produced {} message(s) to {}: {}",
+            count,
+            topic,
+            offsets(&produced)
+        );
+        return Ok(());
+    }
+
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        // Typed messages go out one by one
+        eprintln!("✏️  One message per line, Ctrl+D to finish");
+        for (i, line) in stdin.lock().lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let sent = match message(options, &line, i + 1) {
+                Ok(record) => producer.send(cluster, options, vec![record]).await,
+                Err(e) => Err(e),
+            };
+            match sent {
+                Ok(produced) => println!("✅ {}", offsets(&produced)),
+                Err(e) => eprintln!("❌ {}", e),
+            }
+        }
+        return Ok(());
+    }
+
+    let mut messages = Vec::new();
+    for (i, line) in stdin.lock().lines().enumerate() {
+        let line = line?;
+        if !line.is_empty() {
+            messages.push(message(options, &line, i + 1)?);
+        }
+    }
+    if messages.is_empty() {
+        println!("📭 Nothing to produce");
+        return Ok(());
+    }
+    let count = messages.len();
+    let produced = producer.send(cluster, options, messages).await?;
+    println!(
+        "✅ Produced {} message(s) to {}: {}",
+        count,
+        topic,
+        offsets(&produced)
+    );
+    Ok(())
+}
+
+async fn execute(matches: &ArgMatches, config: KafkaConsoleConfig) -> Result<()> {
+    let connector = connector(&config).await?;
+    let (mut cluster, route) = Cluster::connect(connector).await?;
+    eprintln!("🔗 Bootstrap → {}", route);
+    let mut registry = config.schema_registry.as_deref().map(Registry::new);
+
+    match matches.subcommand() {
+        Some(("topics", sub)) => match sub.get_one::<String>("topic") {
+            Some(topic) => describe_topic(&mut cluster, topic).await,
+            None => list_topics(&mut cluster).await,
+        },
+        Some(("consume", sub)) => {
+            let topic = sub.get_one::<String>("topic").cloned().unwrap_or_default();
+            let format = |name: &str| {
+                sub.get_one::<String>(name)
+                    .cloned()
+                    .unwrap_or_else(|| "string".to_string())
+            };
+            let (key_format, value_format) = (format("key-format"), format("format"));
+            if registry.is_none() && (key_format == "avro" || value_format == "avro") {
+                return Err(anyhow!(
+                    "Avro needs a schema registry (--registry or schema_registry in the config)"
+                ));
+            }
+            let options = ConsumeOptions {
+                partition: sub.get_one::<i32>("partition").copied(),
+                start: parse_start(
+                    sub.get_one::<String>("offset")
+                        .map(String::as_str)
+                        .unwrap_or("beginning"),
+                )?,
+                count: sub.get_one::<usize>("count").copied(),
+                follow: sub.get_flag("follow"),
+                key_format,
+                value_format,
+                json: sub.get_flag("json"),
+            };
+            consume(&mut cluster, &mut registry, &topic, &options).await
+        }
+        Some(("produce", sub)) => {
+            let topic = sub.get_one::<String>("topic").cloned().unwrap_or_default();
+            let headers = sub
+                .get_many::<String>("header")
+                .into_iter()
+                .flatten()
+                .map(|pair| parse_pair(pair))
+                .collect::<Result<Vec<_>>>()?;
+            let options = ProduceOptions {
+                partition: sub.get_one::<i32>("partition").copied(),
+                key: sub.get_one::<String>("key").cloned(),
+                delimiter: sub.get_one::<String>("key-delimiter").cloned(),
+                headers,
+                json: sub.get_one::<String>("format").is_some_and(|f| f == "json"),
+            };
+            let values = sub
+                .get_many::<String>("value")
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            produce(&mut cluster, &topic, values, &options).await
+        }
+        _ => Ok(()),
+    }
+}
+
+fn topic_arg() -> Arg {
+    Arg::new("topic")
+        .value_name("TOPIC")
+        .required(true)
+        .help("Topic name")
+}
+
+fn partition_arg() -> Arg {
+    Arg::new("partition")
+        .long("partition")
+        .short('P')
+        .value_name("PARTITION")
+        .value_parser(clap::value_parser!(i32))
+}
+
+impl Plugin for KafkaConsolePlugin {
+    fn name(&self) -> &'static str {
+        "kafka_console"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "List, consume and produce Kafka messages through per-broker forwards"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Kafka console that reaches every broker through its own forward")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("topics")
+                    .about("List brokers and topics, or one topic's partitions and offsets")
+                    .arg(
+                        Arg::new("topic")
+                            .value_name("TOPIC")
+                            .help("Show this topic's partitions"),
+                    ),
+            )
+            .subcommand(
+                Command::new("consume")
+                    .about("Print messages from a topic")
+                    .arg(topic_arg())
+                    .arg(partition_arg().help("Only this partition (default: all)"))
+                    .arg(
+                        Arg::new("offset")
+                            .long("offset")
+                            .short('o')
+                            .value_name("OFFSET")
+                            .allow_hyphen_values(true)
+                            .help("beginning (default), end, an offset, or -N for the last N"),
+                    )
+                    .arg(
+                        Arg::new("count")
+                            .long("count")
+                            .short('c')
+                            .value_name("N")
+                            .value_parser(clap::value_parser!(usize))
+                            .help("Stop after N messages"),
+                    )
+                    .arg(
+                        Arg::new("follow")
+                            .long("follow")
+                            .short('f')
+                            .action(ArgAction::SetTrue)
+                            .help("Keep waiting for new messages instead of stopping at the end"),
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_name("FORMAT")
+                            .value_parser(["string", "json", "avro", "hex"])
+                            .help("How values are decoded (default: string)"),
+                    )
+                    .arg(
+                        Arg::new("key-format")
+                            .long("key-format")
+                            .value_name("FORMAT")
+                            .value_parser(["string", "json", "avro", "hex"])
+                            .help("How keys are decoded (default: string)"),
+                    )
+                    .arg(
+                        Arg::new("json")
+                            .long("json")
+                            .action(ArgAction::SetTrue)
+                            .help("Print one JSON object per message"),
+                    ),
+            )
+            .subcommand(
+                Command::new("produce")
+                    .about("Send messages given as arguments, or one per line of stdin")
+                    .arg(topic_arg())
+                    .arg(
+                        Arg::new("value")
+                            .value_name("VALUE")
+                            .num_args(0..)
+                            .help("Messages to send (default: read from stdin)"),
+                    )
+                    .arg(partition_arg().help("Partition (default: by key, else round-robin)"))
+                    .arg(
+                        Arg::new("key")
+                            .long("key")
+                            .short('k')
+                            .value_name("KEY")
+                            .help("Key of every message"),
+                    )
+                    .arg(
+                        Arg::new("key-delimiter")
+                            .long("key-delimiter")
+                            .short('K')
+                            .value_name("DELIMITER")
+                            .conflicts_with("key")
+                            .help("Split each line into key and value at this delimiter"),
+                    )
+                    .arg(
+                        Arg::new("header")
+                            .long("header")
+                            .short('H')
+                            .value_name("NAME=VALUE")
+                            .action(ArgAction::Append)
+                            .help("Header on every message (repeatable)"),
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_name("FORMAT")
+                            .value_parser(["string", "json"])
+                            .help("json: refuse values that aren't valid JSON"),
+                    ),
+            )
+            .arg(
+                Arg::new("pod")
+                    .long("pod")
+                    .short('p')
+                    .global(true)
+                    .value_name("POD_NAME")
+                    .help("Override pod name from config file"),
+            )
+            .arg(
+                Arg::new("selector")
+                    .long("selector")
+                    .short('s')
+                    .global(true)
+                    .value_name("SELECTOR")
+                    .help("Override pod selector from config file (e.g., 'app=kafka')"),
+            )
+            .arg(
+                Arg::new("namespace")
+                    .long("namespace")
+                    .short('n')
+                    .global(true)
+                    .value_name("NAMESPACE")
+                    .help("Override namespace from config file"),
+            )
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .global(true)
+                    .value_name("PORT")
+                    .value_parser(clap::value_parser!(u16))
+                    .help("Broker port in the pods (default: 9092)"),
+            )
+            .arg(
+                Arg::new("bootstrap")
+                    .long("bootstrap")
+                    .short('b')
+                    .global(true)
+                    .value_name("HOST:PORT")
+                    .help("Connect to this address instead of through Kubernetes"),
+            )
+            .arg(
+                Arg::new("broker-map")
+                    .long("broker-map")
+                    .global(true)
+                    .value_name("ADVERTISED=LOCAL")
+                    .action(ArgAction::Append)
+                    .help("Local address of an advertised broker (repeatable)"),
+            )
+            .arg(
+                Arg::new("registry")
+                    .long("registry")
+                    .global(true)
+                    .value_name("URL")
+                    .help("Schema registry URL, for --format avro"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(KafkaConsolePlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<KafkaConsoleConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let mut config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let sub = matches.subcommand().map(|(_, sub)| sub).unwrap_or(matches);
+
+        // Override config with command line arguments
+        if let Some(pod) = sub.get_one::<String>("pod") {
+            config.pod_name = Some(pod.clone());
+            config.pod_selector = None;
+            config.bootstrap = None;
+        }
+        if let Some(selector) = sub.get_one::<String>("selector") {
+            config.pod_selector = Some(selector.clone());
+            config.pod_name = None;
+            config.bootstrap = None;
+        }
+        if let Some(namespace) = sub.get_one::<String>("namespace") {
+            config.namespace = Some(namespace.clone());
+        }
+        if let Some(port) = sub.get_one::<u16>("port") {
+            config.port = Some(*port);
+        }
+        if let Some(bootstrap) = sub.get_one::<String>("bootstrap") {
+            config.bootstrap = Some(bootstrap.clone());
+        }
+        for pair in sub.get_many::<String>("broker-map").into_iter().flatten() {
+            match parse_pair(pair) {
+                Ok((advertised, local)) => {
+                    config.broker_map.insert(advertised, local);
+                }
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        if let Some(registry) = sub.get_one::<String>("registry") {
+            config.schema_registry = Some(registry.clone());
+        }
+        if config.bootstrap.is_none() && config.pod_name.is_none() && config.pod_selector.is_none()
+        {
+            eprintln!(
+                "❌ Must specify --pod, --selector or --bootstrap (or configure in config file)"
+            );
+            eprintln!("💡 Example: proxy kafka_console topics --selector app=kafka -n kafka");
+            std::process::exit(1);
+        }
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        if let Err(e) = rt.block_on(execute(matches, config)) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(KafkaConsolePlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,237 @@
+// The Kafka wire format, for the non-flexible API versions this plugin speaks: big-endian
+// integers, int16-prefixed strings and int32-prefixed arrays and bytes, plus the zigzag
+// varints used inside record batches. Also the two checksums the protocol needs.
+use anyhow::{anyhow, Result};
+
+pub const PRODUCE: i16 = 0;
+pub const FETCH: i16 = 1;
+pub const LIST_OFFSETS: i16 = 2;
+pub const METADATA: i16 = 3;
+
+/// Builds a request body
+#[derive(Default)]
+pub struct Writer {
+    pub buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn i8(&mut self, value: i8) -> &mut Self {
+        self.buf.push(value as u8);
+        self
+    }
+
+    pub fn i16(&mut self, value: i16) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i32(&mut self, value: i32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i64(&mut self, value: i64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn string(&mut self, value: &str) -> &mut Self {
+        self.i16(value.len() as i16);
+        self.buf.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    pub fn nullable_string(&mut self, value: Option<&str>) -> &mut Self {
+        match value {
+            Some(value) => self.string(value),
+            None => self.i16(-1),
+        }
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.i32(value.len() as i32);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn varint(&mut self, value: i64) -> &mut Self {
+        let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+        while zigzag >= 0x80 {
+            self.buf.push((zigzag as u8) | 0x80);
+            zigzag >>= 7;
+        }
+        self.buf.push(zigzag as u8);
+        self
+    }
+
+    /// Varint-length-prefixed bytes, -1 for null
+    pub fn varint_bytes(&mut self, value: Option<&[u8]>) -> &mut Self {
+        match value {
+            Some(value) => {
+                self.varint(value.len() as i64);
+                self.buf.extend_from_slice(value);
+            }
+            None => {
+                self.varint(-1);
+            }
+        }
+        self
+    }
+}
+
+/// Reads a response body
+pub struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Reader { buf }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < count {
+            return Err(anyhow!("Truncated Kafka response"));
+        }
+        let (taken, rest) = self.buf.split_at(count);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    pub fn i8(&mut self) -> Result<i8> {
+        Ok(self.take(1)?[0] as i8)
+    }
+
+    pub fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    pub fn nullable_string(&mut self) -> Result<Option<String>> {
+        let length = self.i16()?;
+        if length < 0 {
+            return Ok(None);
+        }
+        Ok(Some(
+            String::from_utf8_lossy(self.take(length as usize)?).into_owned(),
+        ))
+    }
+
+    pub fn string(&mut self) -> Result<String> {
+        Ok(self.nullable_string()?.unwrap_or_default())
+    }
+
+    /// An array's element count; null arrays count as empty
+    pub fn array(&mut self) -> Result<usize> {
+        Ok(self.i32()?.max(0) as usize)
+    }
+
+    pub fn nullable_bytes(&mut self) -> Result<Option<&'a [u8]>> {
+        let length = self.i32()?;
+        if length < 0 {
+            return Ok(None);
+        }
+        self.take(length as usize).map(Some)
+    }
+
+    pub fn varint(&mut self) -> Result<i64> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        Err(anyhow!("Invalid varint"))
+    }
+
+    /// Varint-length-prefixed bytes, None for null
+    pub fn varint_bytes(&mut self) -> Result<Option<&'a [u8]>> {
+        let length = self.varint()?;
+        if length < 0 {
+            return Ok(None);
+        }
+        self.take(length as usize).map(Some)
+    }
+}
+
+/// CRC-32C (Castagnoli), which record batches are checksummed with
+pub fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The murmur2 hash of the Java client's default partitioner, so keyed messages land on
+/// the same partition as when other producers send them
+pub fn murmur2(data: &[u8]) -> u32 {
+    const M: u32 = 0x5bd1_e995;
+    let mut hash = 0x9747_b28c ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        hash = hash.wrapping_mul(M) ^ k;
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, &byte) in tail.iter().enumerate().rev() {
+            hash ^= (byte as u32) << (8 * i);
+        }
+        hash = hash.wrapping_mul(M);
+    }
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(M);
+    hash ^ (hash >> 15)
+}
+
+/// The name of a Kafka error code, for messages
+pub fn error_name(code: i16) -> String {
+    let name = match code {
+        1 => "OFFSET_OUT_OF_RANGE",
+        2 => "CORRUPT_MESSAGE",
+        3 => "UNKNOWN_TOPIC_OR_PARTITION",
+        5 => "LEADER_NOT_AVAILABLE",
+        6 => "NOT_LEADER_OR_FOLLOWER",
+        7 => "REQUEST_TIMED_OUT",
+        8 => "BROKER_NOT_AVAILABLE",
+        9 => "REPLICA_NOT_AVAILABLE",
+        10 => "MESSAGE_TOO_LARGE",
+        17 => "INVALID_TOPIC_EXCEPTION",
+        19 => "NOT_ENOUGH_REPLICAS",
+        20 => "NOT_ENOUGH_REPLICAS_AFTER_APPEND",
+        29 => "TOPIC_AUTHORIZATION_FAILED",
+        31 => "CLUSTER_AUTHORIZATION_FAILED",
+        35 => "UNSUPPORTED_VERSION",
+        43 => "UNSUPPORTED_FOR_MESSAGE_FORMAT",
+        74 => "FENCED_LEADER_EPOCH",
+        75 => "UNKNOWN_LEADER_EPOCH",
+        76 => "UNSUPPORTED_COMPRESSION_TYPE",
+        87 => "INVALID_RECORD",
+        _ => return format!("error {}", code),
+    };
+    name.to_string()
+}
@@ -0,0 +1,318 @@
+// Record batches (message format v2, Kafka 0.11 and later): decoding what Fetch returns,
+// compressed batches included (gzip, snappy, lz4 and zstd), and encoding the batches
+// Produce sends.
+use crate::protocol::{crc32c, Reader, Writer};
+use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
+use std::io::Read;
+
+/// Attribute bits of a batch
+const COMPRESSION_MASK: i16 = 0x07;
+const CONTROL_BIT: i16 = 0x20;
+/// The start of snappy data framed the way the Java client does it (xerial snappy-java): a
+/// version and a compatible version follow, then blocks each prefixed with their length
+const XERIAL_SNAPPY_MAGIC: &[u8] = b"\x82SNAPPY\0";
+
+pub struct Record {
+    pub offset: i64,
+    /// Milliseconds since the epoch
+    pub timestamp: i64,
+    pub key: Option<Vec<u8>>,
+    pub value: Option<Vec<u8>>,
+    pub headers: Vec<(String, Option<Vec<u8>>)>,
+}
+
+/// Snappy data as the Java client frames it, or a single raw block as librdkafka sends it
+fn unsnappy(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = snap::raw::Decoder::new();
+    let Some(framed) = data.strip_prefix(XERIAL_SNAPPY_MAGIC) else {
+        return Ok(decoder.decompress_vec(data)?);
+    };
+    let mut reader = Reader::new(framed);
+    reader.i32()?;
+    reader.i32()?;
+    let mut output = Vec::new();
+    while reader.remaining() > 0 {
+        let length = reader.i32()?;
+        let block = reader.take(length.max(0) as usize)?;
+        output.extend_from_slice(&decoder.decompress_vec(block)?);
+    }
+    Ok(output)
+}
+
+fn decompress(codec: i16, data: &[u8]) -> Result<Vec<u8>> {
+    match codec {
+        0 => Ok(data.to_vec()),
+        1 => {
+            let mut output = Vec::new();
+            GzDecoder::new(data).read_to_end(&mut output)?;
+            Ok(output)
+        }
+        2 => unsnappy(data),
+        3 => {
+            let mut output = Vec::new();
+            lz4_flex::frame::FrameDecoder::new(data).read_to_end(&mut output)?;
+            Ok(output)
+        }
+        4 => Ok(zstd::stream::decode_all(data)?),
+        other => Err(anyhow!("Unknown compression codec {}", other)),
+    }
+}
+
+fn decode_record(reader: &mut Reader, base_offset: i64, base_timestamp: i64) -> Result<Record> {
+    let length = reader.varint()?;
+    let mut record = Reader::new(reader.take(length.max(0) as usize)?);
+    record.i8()?;
+    let timestamp = base_timestamp + record.varint()?;
+    let offset = base_offset + record.varint()?;
+    let key = record.varint_bytes()?.map(<[u8]>::to_vec);
+    let value = record.varint_bytes()?.map(<[u8]>::to_vec);
+    let mut headers = Vec::new();
+    for _ in 0..record.varint()?.max(0) {
+        let name = record.varint_bytes()?.unwrap_or_default();
+        let value = record.varint_bytes()?.map(<[u8]>::to_vec);
+        headers.push((String::from_utf8_lossy(name).into_owned(), value));
+    }
+    Ok(Record {
+        offset,
+        timestamp,
+        key,
+        value,
+        headers,
+    })
+}
+
+/// The records of the batches in a fetched partition, and the offset after the last batch
+/// (which can be past the last record, for transaction markers). A batch cut off at the end
+/// of the response (the broker stops at the size limit) is left for the next fetch.
+pub fn decode(data: &[u8]) -> Result<(Vec<Record>, Option<i64>)> {
+    let mut records = Vec::new();
+    let mut next_offset = None;
+    let mut reader = Reader::new(data);
+    while reader.remaining() >= 12 {
+        let base_offset = reader.i64()?;
+        let length = reader.i32()? as usize;
+        if reader.remaining() < length {
+            break;
+        }
+        let mut batch = Reader::new(reader.take(length)?);
+        batch.i32()?;
+        let magic = batch.i8()?;
+        if magic != 2 {
+            return Err(anyhow!(
+                "Message format v{} isn't supported (only v2, Kafka 0.11 and later)",
+                magic
+            ));
+        }
+        batch.i32()?;
+        let attributes = batch.i16()?;
+        let last_offset_delta = batch.i32()?;
+        next_offset = Some(base_offset + last_offset_delta as i64 + 1);
+        let base_timestamp = batch.i64()?;
+        batch.i64()?;
+        batch.i64()?;
+        batch.i16()?;
+        batch.i32()?;
+        let count = batch.i32()?;
+        // Transaction markers, not messages
+        if attributes & CONTROL_BIT != 0 {
+            continue;
+        }
+        let body = decompress(
+            attributes & COMPRESSION_MASK,
+            batch.take(batch.remaining())?,
+        )?;
+        let mut body = Reader::new(&body);
+        for _ in 0..count {
+            records.push(decode_record(&mut body, base_offset, base_timestamp)?);
+        }
+    }
+    Ok((records, next_offset))
+}
+
+/// An uncompressed batch of the given records, timestamped now
+pub fn encode(records: &[Record], timestamp: i64) -> Vec<u8> {
+    let mut body = Writer::default();
+    for (i, record) in records.iter().enumerate() {
+        let mut encoded = Writer::default();
+        encoded
+            .i8(0)
+            .varint(0)
+            .varint(i as i64)
+            .varint_bytes(record.key.as_deref())
+            .varint_bytes(record.value.as_deref())
+            .varint(record.headers.len() as i64);
+        for (name, value) in &record.headers {
+            encoded
+                .varint_bytes(Some(name.as_bytes()))
+                .varint_bytes(value.as_deref());
+        }
+        body.varint(encoded.buf.len() as i64);
+        body.buf.extend_from_slice(&encoded.buf);
+    }
+
+    // Everything after the checksum is what it covers
+    let mut checked = Writer::default();
+    checked
+        .i16(0)
+        .i32(records.len() as i32 - 1)
+        .i64(timestamp)
+        .i64(timestamp)
+        // No producer id, epoch or sequence: not idempotent
+        .i64(-1)
+        .i16(-1)
+        .i32(-1)
+        .i32(records.len() as i32);
+    checked.buf.extend_from_slice(&body.buf);
+
+    let mut batch = Writer::default();
+    batch
+        .i64(0)
+        .i32((4 + 1 + 4 + checked.buf.len()) as i32)
+        .i32(-1)
+        .i8(2)
+        .i32(crc32c(&checked.buf) as i32);
+    batch.buf.extend_from_slice(&checked.buf);
+    batch.buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Offset of the records in an encoded batch, after the batch header
+    const RECORDS_START: usize = 61;
+    /// Offset of the attributes in an encoded batch
+    const ATTRIBUTES_AT: usize = 21;
+
+    fn record(key: &str, value: &str) -> Record {
+        Record {
+            offset: 0,
+            timestamp: 0,
+            key: Some(key.as_bytes().to_vec()),
+            value: Some(value.as_bytes().to_vec()),
+            headers: vec![
+                ("trace".into(), Some(b"abc".to_vec())),
+                ("empty".into(), None),
+            ],
+        }
+    }
+
+    /// The records of a batch, repeated so compression has something to do
+    fn records() -> Vec<Record> {
+        (0..20)
+            .map(|i| {
+                record(
+                    &format!("order-{}", i),
+                    &"{\"status\":\"paid\"}".repeat(i + 1),
+                )
+            })
+            .collect()
+    }
+
+    /// `encode`'s batch with its records compressed by `compress` as `codec`
+    fn compressed(codec: i16, compress: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        let mut batch = encode(&records(), 1_760_000_000_000);
+        let body = compress(&batch[RECORDS_START..]);
+        batch.truncate(RECORDS_START);
+        batch.extend_from_slice(&body);
+        batch[ATTRIBUTES_AT..ATTRIBUTES_AT + 2].copy_from_slice(&codec.to_be_bytes());
+        let length = (batch.len() - 12) as i32;
+        batch[8..12].copy_from_slice(&length.to_be_bytes());
+        batch
+    }
+
+    fn assert_decodes(batch: &[u8]) {
+        let (decoded, next_offset) = decode(batch).unwrap();
+        let expected = records();
+        assert_eq!(decoded.len(), expected.len());
+        for (i, (decoded, expected)) in decoded.iter().zip(&expected).enumerate() {
+            assert_eq!(decoded.offset, i as i64);
+            assert_eq!(decoded.timestamp, 1_760_000_000_000);
+            assert_eq!(decoded.key, expected.key);
+            assert_eq!(decoded.value, expected.value);
+            assert_eq!(decoded.headers, expected.headers);
+        }
+        assert_eq!(next_offset, Some(expected.len() as i64));
+    }
+
+    #[test]
+    fn decodes_uncompressed_batches() {
+        assert_decodes(&encode(&records(), 1_760_000_000_000));
+    }
+
+    #[test]
+    fn decodes_gzip_batches() {
+        assert_decodes(&compressed(1, |body| {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        }));
+    }
+
+    #[test]
+    fn decodes_snappy_batches_framed_by_the_java_client() {
+        assert_decodes(&compressed(2, |body| {
+            let mut framed = XERIAL_SNAPPY_MAGIC.to_vec();
+            framed.extend_from_slice(&1i32.to_be_bytes());
+            framed.extend_from_slice(&1i32.to_be_bytes());
+            // Two blocks, as a large batch would be split
+            for block in body.chunks(body.len() / 2 + 1) {
+                let compressed = snap::raw::Encoder::new().compress_vec(block).unwrap();
+                framed.extend_from_slice(&(compressed.len() as i32).to_be_bytes());
+                framed.extend_from_slice(&compressed);
+            }
+            framed
+        }));
+    }
+
+    #[test]
+    fn decodes_raw_snappy_batches() {
+        assert_decodes(&compressed(2, |body| {
+            snap::raw::Encoder::new().compress_vec(body).unwrap()
+        }));
+    }
+
+    #[test]
+    fn decodes_lz4_batches() {
+        assert_decodes(&compressed(3, |body| {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+            encoder.write_all(body).unwrap();
+            encoder.finish().unwrap()
+        }));
+    }
+
+    #[test]
+    fn decodes_zstd_batches() {
+        assert_decodes(&compressed(4, |body| {
+            zstd::stream::encode_all(body, 3).unwrap()
+        }));
+    }
+
+    #[test]
+    fn unknown_codecs_are_errors() {
+        let error = decode(&compressed(5, <[u8]>::to_vec)).err().unwrap();
+        assert_eq!(error.to_string(), "Unknown compression codec 5");
+    }
+
+    #[test]
+    fn batch_cut_off_at_the_end_is_left_for_the_next_fetch() {
+        let batch = encode(&records(), 1_760_000_000_000);
+        let mut data = batch.clone();
+        data.extend_from_slice(&batch[..batch.len() / 2]);
+        let (decoded, next_offset) = decode(&data).unwrap();
+        assert_eq!(decoded.len(), records().len());
+        assert_eq!(next_offset, Some(records().len() as i64));
+    }
+
+    #[test]
+    fn control_batches_are_skipped_but_advance_the_offset() {
+        let mut batch = encode(&records()[..1], 1_760_000_000_000);
+        batch[ATTRIBUTES_AT + 1] |= CONTROL_BIT as u8;
+        let (decoded, next_offset) = decode(&batch).unwrap();
+        assert!(decoded.is_empty());
+        assert_eq!(next_offset, Some(1));
+    }
+}
@@ -0,0 +1,556 @@
+// HTTP load generation against a local forward or any URL: a fixed request rate or as
+// fast as `concurrency` workers allow, for a duration or a number of requests. Reports
+// latency percentiles, error rate and throughput; tagged runs are saved so a run after a
+// deploy can be compared with one from before.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::Plugin;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::runtime::Runtime;
+
+mod load;
+
+use load::{Plan, Report};
+
+const DEFAULT_CONCURRENCY: usize = 10;
+const DEFAULT_DURATION: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LoadTestConfig {
+    #[serde(default)]
+    pub test: Vec<TestConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TestConfig {
+    pub name: String,
+    pub url: String,
+    /// HTTP method (default GET)
+    pub method: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+    /// Requests per second; as fast as the workers go when not set
+    pub rate: Option<f64>,
+    /// Requests in flight at most (default 10)
+    pub concurrency: Option<usize>,
+    /// Run length (default 10s unless `requests` is set)
+    pub duration_secs: Option<u64>,
+    /// Stop after this many requests
+    pub requests: Option<u64>,
+    /// Per-request timeout (default 10000)
+    pub timeout_ms: Option<u64>,
+}
+
+/// A tagged run as saved in the state directory
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedRun {
+    tag: String,
+    target: String,
+    method: String,
+    url: String,
+    rate: Option<f64>,
+    concurrency: usize,
+    /// Unix seconds
+    finished_at: u64,
+    report: Report,
+}
+
+pub struct LoadTestPlugin;
+
+impl LoadTestPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Load Test Configuration
+[[test]]
+name = "api-health"
+url = "http://127.0.0.1:8080/health"  # e.g. a k8s_port_forward local port
+rate = 50
+concurrency = 20
+duration_secs = 30
+
+[[test]]
+name = "create-order"
+url = "http://127.0.0.1:8080/api/orders"
+method = "POST"
+headers = { "content-type" = "application/json" }
+body = '{"item": "test", "quantity": 1}'
+requests = 500
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<LoadTestConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: LoadTestConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", LoadTestPlugin::sample_config());
+                Ok(LoadTestConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(LoadTestConfig::default())
+        }
+    }
+}
+
+fn runs_dir(plugin_name: &str) -> Result<PathBuf> {
+    plugin_api::plugin_state_dir(plugin_name)
+        .map(|dir| dir.join("runs"))
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))
+}
+
+fn run_path(plugin_name: &str, tag: &str) -> Result<PathBuf> {
+    if tag.is_empty()
+        || tag.starts_with('.')
+        || !tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(anyhow!(
+            "Invalid tag '{}': use letters, digits, '-', '_' and '.'",
+            tag
+        ));
+    }
+    Ok(runs_dir(plugin_name)?.join(format!("{}.json", tag)))
+}
+
+fn load_run(plugin_name: &str, tag: &str) -> Result<SavedRun> {
+    let path = run_path(plugin_name, tag)?;
+    let content = fs::read_to_string(&path).map_err(|_| anyhow!("No run tagged '{}'", tag))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn save_run(plugin_name: &str, run: &SavedRun) -> Result<PathBuf> {
+    let path = run_path(plugin_name, &run.tag)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(run)?)?;
+    Ok(path)
+}
+
+fn list_runs(plugin_name: &str) -> Result<()> {
+    let dir = runs_dir(plugin_name)?;
+    let mut runs: Vec<SavedRun> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str(&content).ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    if runs.is_empty() {
+        println!("📭 No saved runs; tag one with --tag");
+        return Ok(());
+    }
+    runs.sort_by_key(|run| run.finished_at);
+    println!("📚 Saved runs:");
+    for run in runs {
+        println!(
+            "  {:<16} {:<16} {:>8.1} req/s  p95 {:>8.1}ms  errors {:>5.1}%",
+            run.tag,
+            run.target,
+            run.report.throughput,
+            run.report.latency_ms.p95,
+            run.report.error_rate() * 100.0
+        );
+    }
+    Ok(())
+}
+
+fn print_report(label: &str, report: &Report) {
+    let latency = &report.latency_ms;
+    let outcomes: Vec<String> = report
+        .outcomes
+        .iter()
+        .map(|(outcome, count)| format!("{} ×{}", outcome, count))
+        .collect();
+    println!("\n📊 {}", label);
+    println!(
+        "  Requests:   {} in {:.1}s ({:.1} req/s)",
+        report.requests, report.elapsed_secs, report.throughput
+    );
+    println!(
+        "  Errors:     {} ({:.2}%)",
+        report.errors,
+        report.error_rate() * 100.0
+    );
+    println!(
+        "  Latency:    mean {:.1}  p50 {:.1}  p90 {:.1}  p95 {:.1}  p99 {:.1}  max {:.1} (ms)",
+        latency.mean, latency.p50, latency.p90, latency.p95, latency.p99, latency.max
+    );
+    println!("  Outcomes:   {}", outcomes.join(", "));
+}
+
+fn change(before: f64, after: f64) -> String {
+    if before == 0.0 {
+        return String::new();
+    }
+    format!(" ({:+.1}%)", (after - before) / before * 100.0)
+}
+
+fn print_comparison(before_label: &str, before: &Report, after_label: &str, after: &Report) {
+    println!("\n📈 {} → {}", before_label, after_label);
+    println!(
+        "  Throughput  {:>9.1} → {:>9.1} req/s{}",
+        before.throughput,
+        after.throughput,
+        change(before.throughput, after.throughput)
+    );
+    println!(
+        "  Error rate  {:>8.2}% → {:>8.2}%",
+        before.error_rate() * 100.0,
+        after.error_rate() * 100.0
+    );
+    let (b, a) = (&before.latency_ms, &after.latency_ms);
+    for (name, before, after) in [
+        ("mean", b.mean, a.mean),
+        ("p50", b.p50, a.p50),
+        ("p90", b.p90, a.p90),
+        ("p95", b.p95, a.p95),
+        ("p99", b.p99, a.p99),
+        ("max", b.max, a.max),
+    ] {
+        println!(
+            "  {:<10}  {:>9.1} → {:>9.1} ms{}",
+            name,
+            before,
+            after,
+            change(before, after)
+        );
+    }
+}
+
+/// The configured test named `target`, or an ad-hoc one when it is a URL
+fn resolve_target(config: &LoadTestConfig, target: &str) -> Result<TestConfig> {
+    if let Some(test) = config.test.iter().find(|test| test.name == target) {
+        return Ok(test.clone());
+    }
+    if target.starts_with("http://") || target.starts_with("https://") {
+        return Ok(TestConfig {
+            name: target.to_string(),
+            url: target.to_string(),
+            method: None,
+            headers: BTreeMap::new(),
+            body: None,
+            rate: None,
+            concurrency: None,
+            duration_secs: None,
+            requests: None,
+            timeout_ms: None,
+        });
+    }
+    Err(anyhow!("No test named '{}' and not a URL", target))
+}
+
+impl Plugin for LoadTestPlugin {
+    fn name(&self) -> &'static str {
+        "load_test"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "HTTP load generator with latency percentiles and run comparison"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Generate HTTP load and report latency, errors and throughput")
+            .arg(
+                Arg::new("target")
+                    .value_name("TEST|URL")
+                    .help("A configured test name, or a URL"),
+            )
+            .arg(
+                Arg::new("rate")
+                    .long("rate")
+                    .short('r')
+                    .value_name("RPS")
+                    .value_parser(clap::value_parser!(f64))
+                    .help("Requests per second (default: as fast as possible)"),
+            )
+            .arg(
+                Arg::new("concurrency")
+                    .long("concurrency")
+                    .short('c')
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))
+                    .help("Requests in flight at most (default 10)"),
+            )
+            .arg(
+                Arg::new("duration")
+                    .long("duration")
+                    .short('d')
+                    .value_name("SECS")
+                    .value_parser(clap::value_parser!(u64))
+                    .help("Run length in seconds (default 10)"),
+            )
+            .arg(
+                Arg::new("requests")
+                    .long("requests")
+                    .short('n')
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(u64))
+                    .help("Stop after this many requests"),
+            )
+            .arg(
+                Arg::new("method")
+                    .long("method")
+                    .short('X')
+                    .value_name("METHOD")
+                    .help("HTTP method (default GET)"),
+            )
+            .arg(
+                Arg::new("header")
+                    .long("header")
+                    .short('H')
+                    .value_name("NAME: VALUE")
+                    .action(ArgAction::Append)
+                    .help("Extra request header, can be repeated"),
+            )
+            .arg(
+                Arg::new("body")
+                    .long("body")
+                    .value_name("BODY")
+                    .help("Request body"),
+            )
+            .arg(
+                Arg::new("tag")
+                    .long("tag")
+                    .short('t')
+                    .value_name("TAG")
+                    .help("Save the run under this tag, e.g. before-deploy"),
+            )
+            .arg(
+                Arg::new("compare")
+                    .long("compare")
+                    .value_name("TAG")
+                    .num_args(1..=2)
+                    .help("Compare with a saved run, or two saved runs without a target"),
+            )
+            .arg(
+                Arg::new("runs")
+                    .long("runs")
+                    .action(ArgAction::SetTrue)
+                    .help("List saved runs"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(LoadTestPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<LoadTestConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        if matches.get_flag("runs") {
+            if let Err(e) = list_runs(self.name()) {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        let compare: Vec<&String> = matches
+            .get_many::<String>("compare")
+            .into_iter()
+            .flatten()
+            .collect();
+        let Some(target) = matches.get_one::<String>("target") else {
+            if let [before, after] = compare[..] {
+                match (load_run(self.name(), before), load_run(self.name(), after)) {
+                    (Ok(before), Ok(after)) => {
+                        print_comparison(&before.tag, &before.report, &after.tag, &after.report)
+                    }
+                    (Err(e), _) | (_, Err(e)) => {
+                        eprintln!("❌ {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            eprintln!("❌ Give a test name or URL");
+            if let Ok(config) = load_config(self.name()) {
+                for test in &config.test {
+                    println!("  {} → {}", test.name, test.url);
+                }
+            }
+            std::process::exit(1);
+        };
+        if compare.len() > 1 {
+            eprintln!("❌ --compare takes one tag when running a test");
+            std::process::exit(1);
+        }
+
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let test = match resolve_target(&config, target) {
+            Ok(test) => test,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        };
+        // Load the baseline first so a typo doesn't cost a whole run
+        let baseline = match compare.first() {
+            Some(tag) => match load_run(self.name(), tag) {
+                Ok(run) => Some(run),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            },
+            None => None,
+        };
+
+        let method = matches
+            .get_one::<String>("method")
+            .or(test.method.as_ref())
+            .map(|method| method.to_ascii_uppercase())
+            .unwrap_or_else(|| "GET".to_string());
+        let method = match Method::from_bytes(method.as_bytes()) {
+            Ok(method) => method,
+            Err(_) => {
+                eprintln!("❌ Invalid method {}", method);
+                std::process::exit(1);
+            }
+        };
+        let mut headers = test.headers.clone();
+        for header in matches.get_many::<String>("header").into_iter().flatten() {
+            match header.split_once(':') {
+                Some((name, value)) => {
+                    headers.insert(name.trim().to_string(), value.trim().to_string());
+                }
+                None => {
+                    eprintln!("❌ Invalid header '{}', expected 'Name: value'", header);
+                    std::process::exit(1);
+                }
+            }
+        }
+        let rate = matches.get_one::<f64>("rate").copied().or(test.rate);
+        if rate.is_some_and(|rate| rate <= 0.0) {
+            eprintln!("❌ The rate must be above 0");
+            std::process::exit(1);
+        }
+        let requests = matches
+            .get_one::<u64>("requests")
+            .copied()
+            .or(test.requests);
+        let duration = matches
+            .get_one::<u64>("duration")
+            .copied()
+            .or(test.duration_secs)
+            .map(Duration::from_secs)
+            .or(requests.is_none().then_some(DEFAULT_DURATION));
+        let plan = Plan {
+            url: test.url.clone(),
+            method: method.clone(),
+            headers,
+            body: matches.get_one::<String>("body").cloned().or(test.body),
+            rate,
+            concurrency: matches
+                .get_one::<usize>("concurrency")
+                .copied()
+                .or(test.concurrency)
+                .unwrap_or(DEFAULT_CONCURRENCY)
+                .max(1),
+            duration,
+            requests,
+        };
+
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_millis(
+                test.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS),
+            ))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("❌ Failed to create HTTP client: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let limit = match (duration, requests) {
+            (Some(duration), Some(requests)) => {
+                format!("{} requests or {}s", requests, duration.as_secs())
+            }
+            (Some(duration), None) => format!("{}s", duration.as_secs()),
+            (None, Some(requests)) => format!("{} requests", requests),
+            (None, None) => unreachable!("a duration is set when requests isn't"),
+        };
+        let pace = match rate {
+            Some(rate) => format!("{} req/s", rate),
+            None => "max rate".to_string(),
+        };
+        println!(
+            "🚀 {} {} — {}, {} workers, {}",
+            method, plan.url, pace, plan.concurrency, limit
+        );
+        println!("💡 Press Ctrl+C to stop early");
+
+        let concurrency = plan.concurrency;
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        let report = rt.block_on(load::run(client, plan));
+
+        print_report(&test.name, &report);
+        if let Some(tag) = matches.get_one::<String>("tag") {
+            let run = SavedRun {
+                tag: tag.clone(),
+                target: test.name.clone(),
+                method: method.to_string(),
+                url: test.url.clone(),
+                rate,
+                concurrency,
+                finished_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|now| now.as_secs())
+                    .unwrap_or_default(),
+                report: report.clone(),
+            };
+            match save_run(self.name(), &run) {
+                Ok(path) => println!("💾 Saved as '{}' ({})", tag, path.display()),
+                Err(e) => eprintln!("❌ Failed to save the run: {}", e),
+            }
+        }
+        if let Some(baseline) = baseline {
+            let label = matches
+                .get_one::<String>("tag")
+                .cloned()
+                .unwrap_or_else(|| "this run".to_string());
+            print_comparison(&baseline.tag, &baseline.report, &label, &report);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(LoadTestPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
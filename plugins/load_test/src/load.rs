@@ -0,0 +1,240 @@
+// The load generator. A feeder hands out one permit per request, paced to the target rate
+// (or as fast as the workers take them), until the duration or request count is reached;
+// `concurrency` workers each send a request per permit and keep their own measurements,
+// which are merged into a report at the end.
+use reqwest::{Client, Method};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::MissedTickBehavior;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Plan {
+    pub url: String,
+    pub method: Method,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+    /// Requests per second; as fast as possible when not set
+    pub rate: Option<f64>,
+    pub concurrency: usize,
+    pub duration: Option<Duration>,
+    pub requests: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Latency {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// Results of one run; errors are failed requests and responses with status >= 400
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub requests: u64,
+    pub errors: u64,
+    pub elapsed_secs: f64,
+    /// Completed requests per second
+    pub throughput: f64,
+    /// In milliseconds, over all completed requests
+    pub latency_ms: Latency,
+    /// Count per status code, or per failure ("timeout", "connect", "error")
+    pub outcomes: BTreeMap<String, u64>,
+}
+
+impl Report {
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.requests as f64
+        }
+    }
+}
+
+#[derive(Default)]
+struct Measurements {
+    latencies: Vec<f64>,
+    outcomes: BTreeMap<String, u64>,
+    errors: u64,
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+async fn feed(plan: &Plan, permits: mpsc::Sender<()>) {
+    let deadline = plan.duration.map(|duration| Instant::now() + duration);
+    let mut ticker = plan.rate.map(|rate| {
+        let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+        ticker
+    });
+    let mut sent = 0u64;
+    loop {
+        if plan.requests.is_some_and(|requests| sent >= requests)
+            || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return;
+        }
+        if let Some(ticker) = ticker.as_mut() {
+            ticker.tick().await;
+        }
+        let permit = permits.send(());
+        let sent_in_time = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), permit)
+                .await
+                .is_ok_and(|sent| sent.is_ok()),
+            None => permit.await.is_ok(),
+        };
+        if !sent_in_time {
+            return;
+        }
+        sent += 1;
+    }
+}
+
+async fn work(
+    client: Client,
+    plan: Arc<Plan>,
+    permits: Arc<Mutex<mpsc::Receiver<()>>>,
+    done: Arc<AtomicU64>,
+    failed: Arc<AtomicU64>,
+) -> Measurements {
+    let mut measurements = Measurements::default();
+    loop {
+        if permits.lock().await.recv().await.is_none() {
+            return measurements;
+        }
+        let mut request = client.request(plan.method.clone(), &plan.url);
+        for (name, value) in &plan.headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = &plan.body {
+            request = request.body(body.clone());
+        }
+
+        let started = Instant::now();
+        let outcome = match request.send().await {
+            Ok(response) => {
+                let status = response.status();
+                // The body is part of the request's latency
+                match response.bytes().await {
+                    Ok(_) if status.as_u16() < 400 => Ok(status.as_u16().to_string()),
+                    Ok(_) => Err(status.as_u16().to_string()),
+                    Err(_) => Err("error".to_string()),
+                }
+            }
+            Err(e) if e.is_timeout() => Err("timeout".to_string()),
+            Err(e) if e.is_connect() => Err("connect".to_string()),
+            Err(_) => Err("error".to_string()),
+        };
+        measurements
+            .latencies
+            .push(started.elapsed().as_secs_f64() * 1000.0);
+        done.fetch_add(1, Ordering::Relaxed);
+        let key = match outcome {
+            Ok(key) => key,
+            Err(key) => {
+                measurements.errors += 1;
+                failed.fetch_add(1, Ordering::Relaxed);
+                key
+            }
+        };
+        *measurements.outcomes.entry(key).or_default() += 1;
+    }
+}
+
+pub async fn run(client: Client, plan: Plan) -> Report {
+    let plan = Arc::new(plan);
+    let (sender, receiver) = mpsc::channel(plan.concurrency);
+    let receiver = Arc::new(Mutex::new(receiver));
+    let done = Arc::new(AtomicU64::new(0));
+    let failed = Arc::new(AtomicU64::new(0));
+
+    let started = Instant::now();
+    let workers: Vec<_> = (0..plan.concurrency)
+        .map(|_| {
+            tokio::spawn(work(
+                client.clone(),
+                plan.clone(),
+                receiver.clone(),
+                done.clone(),
+                failed.clone(),
+            ))
+        })
+        .collect();
+
+    let progress = {
+        let (done, failed) = (done.clone(), failed.clone());
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                println!(
+                    "⏱️  {:>4.0}s  {} requests, {} errors",
+                    started.elapsed().as_secs_f64(),
+                    done.load(Ordering::Relaxed),
+                    failed.load(Ordering::Relaxed)
+                );
+            }
+        })
+    };
+
+    // Ctrl+C ends the run early; requests in flight still count
+    tokio::select! {
+        _ = feed(&plan, sender) => {}
+        _ = tokio::signal::ctrl_c() => println!("\n🛑 Stopping, waiting for requests in flight..."),
+    }
+    let mut merged = Measurements::default();
+    for worker in workers {
+        if let Ok(measurements) = worker.await {
+            merged.latencies.extend(measurements.latencies);
+            merged.errors += measurements.errors;
+            for (key, count) in measurements.outcomes {
+                *merged.outcomes.entry(key).or_default() += count;
+            }
+        }
+    }
+    progress.abort();
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let mut latencies = merged.latencies;
+    latencies.sort_by(f64::total_cmp);
+    let requests = latencies.len() as u64;
+    Report {
+        requests,
+        errors: merged.errors,
+        elapsed_secs: elapsed,
+        throughput: if elapsed > 0.0 {
+            requests as f64 / elapsed
+        } else {
+            0.0
+        },
+        latency_ms: Latency {
+            mean: if requests > 0 {
+                latencies.iter().sum::<f64>() / requests as f64
+            } else {
+                0.0
+            },
+            p50: percentile(&latencies, 0.50),
+            p90: percentile(&latencies, 0.90),
+            p95: percentile(&latencies, 0.95),
+            p99: percentile(&latencies, 0.99),
+            max: latencies.last().copied().unwrap_or(0.0),
+        },
+        outcomes: merged.outcomes,
+    }
+}
@@ -0,0 +1,376 @@
+// Decoded HTTP traffic of a workload in an Istio mesh, where the apps talk mTLS and a
+// forward to the app port sees only ciphertext. The plugin forwards to the Envoy admin
+// port of each selected pod's sidecar itself, starts an admin tap there, and renders the
+// traces with the shared HTTP logging.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use kube::Client;
+use plugin_api::k8s;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+mod tap;
+
+const DEFAULT_ADMIN_PORT: u16 = 15000;
+const DEFAULT_CONFIG_ID: &str = "mesh_tap";
+/// Envoy buffers 1 KiB of each body by default, which cuts most JSON short
+const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MeshTapConfig {
+    /// Namespace (default: the kubeconfig context's)
+    pub namespace: Option<String>,
+    /// Label selector of the workload, e.g. "app=checkout"
+    pub selector: Option<String>,
+    /// Envoy admin port of the sidecar (default 15000)
+    pub admin_port: Option<u16>,
+    /// config_id of the tap filter in the EnvoyFilter (default mesh_tap)
+    pub config_id: Option<String>,
+    /// Bytes of each body to capture (default 65536)
+    pub max_body_bytes: Option<usize>,
+}
+
+pub struct MeshTapPlugin;
+
+impl MeshTapPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Mesh Tap Configuration
+namespace = "shop"
+selector = "app=checkout"
+admin_port = 15000
+config_id = "mesh_tap"  # must match the EnvoyFilter, see --envoy-filter
+max_body_bytes = 65536
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<MeshTapConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: MeshTapConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Everything can be given on the command line
+                Ok(MeshTapConfig::default())
+            }
+        }
+        None => Ok(MeshTapConfig::default()),
+    }
+}
+
+fn has_sidecar(pod: &Pod) -> bool {
+    pod.spec.iter().any(|spec| {
+        spec.containers
+            .iter()
+            .chain(spec.init_containers.iter().flatten())
+            .any(|container| container.name == "istio-proxy")
+    })
+}
+
+/// The running pods to tap: the named one, or those matching the selector
+async fn select_pods(
+    pods: &Api<Pod>,
+    name: Option<&str>,
+    selector: Option<&str>,
+) -> Result<Vec<Pod>> {
+    let found = match (name, selector) {
+        (Some(name), _) => vec![pods.get(name).await?],
+        (None, Some(selector)) => k8s::running_pods(pods, selector).await?,
+        (None, None) => return Err(anyhow!("Must specify either --pod or --selector")),
+    };
+    let running: Vec<Pod> = found.into_iter().filter(k8s::is_running).collect();
+    if running.is_empty() {
+        return Err(anyhow!("No running pods to tap"));
+    }
+    for pod in &running {
+        if !has_sidecar(pod) {
+            println!(
+                "⚠️  {} has no istio-proxy container; is it in the mesh?",
+                pod.metadata.name.as_deref().unwrap_or("?")
+            );
+        }
+    }
+    Ok(running)
+}
+
+/// A local port forwarded to the pod's Envoy admin port
+async fn admin_bridge(pods: Api<Pod>, pod: String, admin_port: u16) -> Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let pods = pods.clone();
+            let pod = pod.clone();
+            tokio::spawn(async move {
+                let stream = match pods.portforward(&pod, &[admin_port]).await {
+                    Ok(mut forwarder) => forwarder.take_stream(admin_port),
+                    Err(e) => {
+                        eprintln!("❌ [{}] Port forward to the admin port failed: {}", pod, e);
+                        return;
+                    }
+                };
+                if let Some(mut stream) = stream {
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut stream).await;
+                }
+            });
+        }
+    });
+    Ok(port)
+}
+
+/// Streams the traces of one pod's sidecar until the tap ends
+async fn tap_pod(pods: Api<Pod>, pod: String, admin_port: u16, body: Value) -> Result<()> {
+    let port = admin_bridge(pods, pod.clone(), admin_port).await?;
+    let response = reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{}/tap", port))
+        .json(&body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        let hint = if text.contains("Unknown config id") {
+            "\n💡 The tap filter isn't installed; apply the output of --envoy-filter"
+        } else {
+            ""
+        };
+        return Err(anyhow!(
+            "Envoy refused the tap ({}): {}{}",
+            status,
+            text.trim(),
+            hint
+        ));
+    }
+    println!("👂 [{}] Tapping", pod);
+
+    // Traces arrive as consecutive JSON documents, not always one per chunk
+    let mut pending: Vec<u8> = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        pending.extend_from_slice(&chunk?);
+        let mut traces = serde_json::Deserializer::from_slice(&pending).into_iter::<Value>();
+        let mut consumed = 0;
+        while let Some(trace) = traces.next() {
+            match trace {
+                Ok(trace) => {
+                    tap::render(&pod, &trace);
+                    consumed = traces.byte_offset();
+                }
+                Err(e) if e.is_eof() => break,
+                Err(e) => return Err(anyhow!("Unreadable trace from Envoy: {}", e)),
+            }
+        }
+        pending.drain(..consumed);
+    }
+    println!("🔌 [{}] Tap ended", pod);
+    Ok(())
+}
+
+fn parse_labels(selector: &str) -> Result<Vec<(String, String)>> {
+    selector
+        .split(',')
+        .map(|pair| {
+            pair.split_once('=')
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .ok_or_else(|| anyhow!("The EnvoyFilter needs key=value labels, not '{}'", pair))
+        })
+        .collect()
+}
+
+async fn start(config: MeshTapConfig, pod: Option<&String>, path: Option<&String>) -> Result<()> {
+    let client = Client::try_default().await?;
+    let namespace = config
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+    let pods: Api<Pod> = Api::namespaced(client, &namespace);
+    let selected = select_pods(&pods, pod.map(String::as_str), config.selector.as_deref()).await?;
+
+    let config_id = config.config_id.as_deref().unwrap_or(DEFAULT_CONFIG_ID);
+    let body = tap::request(
+        config_id,
+        path.map(String::as_str),
+        config.max_body_bytes.unwrap_or(DEFAULT_MAX_BODY_BYTES),
+    );
+    let admin_port = config.admin_port.unwrap_or(DEFAULT_ADMIN_PORT);
+
+    ctrlc::set_handler(move || {
+        println!("\n👋 Shutting down...");
+        std::process::exit(0);
+    })?;
+
+    println!(
+        "🚀 Tapping {} pod(s) in {} (tap filter '{}')",
+        selected.len(),
+        namespace,
+        config_id
+    );
+    let handles: Vec<_> = selected
+        .into_iter()
+        .filter_map(|pod| pod.metadata.name)
+        .map(|name| {
+            plugin_api::audit::record_k8s(
+                "mesh_tap",
+                "port_forward",
+                &[
+                    ("namespace", &namespace),
+                    ("pod", &name),
+                    ("remote_port", &admin_port.to_string()),
+                ],
+            );
+            let pods = pods.clone();
+            let body = body.clone();
+            tokio::spawn(async move {
+                if let Err(e) = tap_pod(pods, name.clone(), admin_port, body).await {
+                    eprintln!("❌ [{}] {}", name, e);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.await;
+    }
+    Ok(())
+}
+
+impl Plugin for MeshTapPlugin {
+    fn name(&self) -> &'static str {
+        "mesh_tap"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Decoded HTTP traffic of Istio workloads through the Envoy tap API"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Capture the HTTP traffic of a workload in an Istio mesh via its Envoy sidecar")
+            .arg(
+                Arg::new("pod")
+                    .long("pod")
+                    .short('p')
+                    .value_name("POD_NAME")
+                    .help("Tap this pod"),
+            )
+            .arg(
+                Arg::new("selector")
+                    .long("selector")
+                    .short('l')
+                    .value_name("SELECTOR")
+                    .help("Tap every running pod matching this selector (e.g., 'app=checkout')"),
+            )
+            .arg(
+                Arg::new("namespace")
+                    .long("namespace")
+                    .short('n')
+                    .value_name("NAMESPACE")
+                    .help("Override namespace from config file"),
+            )
+            .arg(
+                Arg::new("path")
+                    .long("path")
+                    .value_name("PREFIX")
+                    .help("Only requests whose path starts with this"),
+            )
+            .arg(
+                Arg::new("max-body")
+                    .long("max-body")
+                    .value_name("BYTES")
+                    .value_parser(clap::value_parser!(usize))
+                    .help("Bytes of each body to capture (default 65536)"),
+            )
+            .arg(
+                Arg::new("envoy-filter")
+                    .long("envoy-filter")
+                    .action(ArgAction::SetTrue)
+                    .help("Print the EnvoyFilter that installs the tap filter for the selector"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(MeshTapPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<MeshTapConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let mut config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let pod = matches.get_one::<String>("pod");
+        if let Some(selector) = matches.get_one::<String>("selector") {
+            config.selector = Some(selector.clone());
+        } else if pod.is_some() {
+            config.selector = None;
+        }
+        if let Some(namespace) = matches.get_one::<String>("namespace") {
+            config.namespace = Some(namespace.clone());
+        }
+        if let Some(bytes) = matches.get_one::<usize>("max-body") {
+            config.max_body_bytes = Some(*bytes);
+        }
+
+        if matches.get_flag("envoy-filter") {
+            let labels = config
+                .selector
+                .as_deref()
+                .ok_or_else(|| anyhow!("--envoy-filter needs a selector (--selector or config)"))
+                .and_then(parse_labels);
+            match labels {
+                Ok(labels) => print!(
+                    "{}",
+                    tap::envoy_filter(
+                        config.namespace.as_deref().unwrap_or("default"),
+                        &labels,
+                        config.config_id.as_deref().unwrap_or(DEFAULT_CONFIG_ID),
+                    )
+                ),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+
+        if pod.is_none() && config.selector.is_none() {
+            eprintln!("❌ Must specify either --pod or --selector (or configure in config file)");
+            eprintln!("💡 Example: proxy mesh_tap --selector app=checkout --namespace shop");
+            eprintln!("📝 Sample config:\n{}", MeshTapPlugin::sample_config());
+            std::process::exit(1);
+        }
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        if let Err(e) = rt.block_on(start(config, pod, matches.get_one::<String>("path"))) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(MeshTapPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,139 @@
+// Envoy's admin tap: POST /tap with a tap config attaches to every tap filter registered
+// under its config_id and streams each matched request/response pair back as a JSON
+// trace, already decrypted since the sidecar terminates the mesh mTLS. Istio doesn't
+// install the tap filter, so `envoy_filter` prints the EnvoyFilter that does.
+use plugin_api::traffic::{log_message, Protocol};
+use serde_json::{json, Value};
+
+/// The body of POST /tap; `path_prefix` limits it to matching requests
+pub fn request(config_id: &str, path_prefix: Option<&str>, max_body_bytes: usize) -> Value {
+    let predicate = match path_prefix {
+        Some(prefix) => json!({
+            "http_request_headers_match": {
+                "headers": [{ "name": ":path", "string_match": { "prefix": prefix } }]
+            }
+        }),
+        None => json!({ "any_match": true }),
+    };
+    json!({
+        "config_id": config_id,
+        "tap_config": {
+            "match": predicate,
+            "output_config": {
+                "sinks": [{ "format": "JSON_BODY_AS_STRING", "streaming_admin": {} }],
+                "max_buffered_rx_bytes": max_body_bytes,
+                "max_buffered_tx_bytes": max_body_bytes
+            }
+        }
+    })
+}
+
+/// The EnvoyFilter adding the tap filter, under `config_id`, to the HTTP filter chains of
+/// the workloads with these labels, inbound and outbound
+pub fn envoy_filter(namespace: &str, labels: &[(String, String)], config_id: &str) -> String {
+    let labels: String = labels
+        .iter()
+        .map(|(key, value)| format!("      {}: {}\n", key, value))
+        .collect();
+    // Object names can't have underscores, config ids can
+    let name = config_id.replace('_', "-");
+    format!(
+        r#"apiVersion: networking.istio.io/v1alpha3
+kind: EnvoyFilter
+metadata:
+  name: {name}
+  namespace: {namespace}
+spec:
+  workloadSelector:
+    labels:
+{labels}  configPatches:
+  - applyTo: HTTP_FILTER
+    match:
+      context: ANY
+      listener:
+        filterChain:
+          filter:
+            name: envoy.filters.network.http_connection_manager
+            subFilter:
+              name: envoy.filters.http.router
+    patch:
+      operation: INSERT_BEFORE
+      value:
+        name: envoy.filters.http.tap
+        typed_config:
+          "@type": type.googleapis.com/envoy.extensions.filters.http.tap.v3.Tap
+          common_config:
+            admin_config:
+              config_id: {config_id}
+"#
+    )
+}
+
+/// Rebuilds an HTTP/1.1 message from a traced one, so the shared HTTP logging can show it.
+/// HTTP/2 pseudo-headers become the request or status line.
+fn message(traced: &Value, is_request: bool) -> (Vec<u8>, bool) {
+    let mut pseudo = std::collections::HashMap::new();
+    let mut headers = String::new();
+    for header in traced["headers"].as_array().into_iter().flatten() {
+        let key = header["key"].as_str().unwrap_or_default();
+        let value = header["value"].as_str().unwrap_or_default();
+        if key.starts_with(':') {
+            pseudo.insert(key.to_string(), value.to_string());
+        } else {
+            headers.push_str(&format!("{}: {}\r\n", key, value));
+        }
+    }
+    let pseudo = |key: &str| pseudo.get(key).cloned().unwrap_or_default();
+    let mut text = if is_request {
+        let mut line = format!("{} {} HTTP/1.1\r\n", pseudo(":method"), pseudo(":path"));
+        if !pseudo(":authority").is_empty() {
+            line.push_str(&format!("host: {}\r\n", pseudo(":authority")));
+        }
+        line
+    } else {
+        format!("HTTP/1.1 {}\r\n", pseudo(":status"))
+    };
+    text.push_str(&headers);
+    text.push_str("\r\n");
+    let mut bytes = text.into_bytes();
+    let body = &traced["body"];
+    bytes.extend_from_slice(body["as_string"].as_str().unwrap_or_default().as_bytes());
+    (bytes, body["truncated"].as_bool().unwrap_or(false))
+}
+
+fn address(value: &Value) -> Option<String> {
+    let socket = &value["socket_address"];
+    Some(format!(
+        "{}:{}",
+        socket["address"].as_str()?,
+        socket["port_value"].as_u64()?
+    ))
+}
+
+/// Logs one trace as a request and its response, tagged with the pod
+pub fn render(pod: &str, trace: &Value) {
+    let trace = &trace["http_buffered_trace"];
+    if trace.is_null() {
+        return;
+    }
+    let connection = &trace["downstream_connection"];
+    if let (Some(from), Some(to)) = (
+        address(&connection["remote_address"]),
+        address(&connection["local_address"]),
+    ) {
+        println!("🔎 [{}] {} → {}", pod, from, to);
+    }
+    for (key, direction, is_request) in [
+        ("request", "→ REQUEST", true),
+        ("response", "← RESPONSE", false),
+    ] {
+        if trace[key].is_null() {
+            continue;
+        }
+        let (bytes, truncated) = message(&trace[key], is_request);
+        log_message(&format!("[{}] {}", pod, direction), &Protocol::Http, &bytes);
+        if truncated {
+            println!("✂️  [{}] Body truncated; raise --max-body to see more", pod);
+        }
+    }
+}
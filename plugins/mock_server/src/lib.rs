@@ -0,0 +1,291 @@
+// An HTTP mock server for frontend work against backends that aren't available: routes
+// from the config (or TOML/YAML route files) answer with canned, templated responses,
+// optionally delayed or failing at a given rate. With --record every request goes to the
+// real upstream and the responses are saved, to be replayed as routes later.
+use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+use tokio::sync::RwLock;
+
+mod routes;
+mod server;
+
+use routes::Route;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+const RECORDINGS_FILE: &str = "recordings.yaml";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MockConfig {
+    /// Address to listen on (default 127.0.0.1:8080)
+    pub listen: Option<String>,
+    /// Real backend, e.g. "http://localhost:3000", for --record and passthrough
+    pub upstream: Option<String>,
+    /// Forward requests no route matches to the upstream (default false)
+    pub passthrough: Option<bool>,
+    /// Files with more routes (.toml, .yaml or .yml)
+    #[serde(default)]
+    pub routes_files: Vec<String>,
+    /// Where recordings are saved (default: recordings.yaml in the state directory)
+    pub recordings: Option<String>,
+    /// Answer with recorded responses when no route matches (default true)
+    pub replay: Option<bool>,
+    #[serde(default)]
+    pub route: Vec<Route>,
+}
+
+pub struct MockServerPlugin;
+
+impl MockServerPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Mock Server Configuration
+listen = "127.0.0.1:8080"
+# upstream = "http://localhost:3000"  # for --record and passthrough
+# passthrough = true
+# routes_files = ["/path/to/api-mocks.yaml"]
+
+[[route]]
+method = "GET"
+path = "/api/users/{id}"
+headers = { "content-type" = "application/json" }
+body = '{"id": "{{path.id}}", "name": "User {{path.id}}"}'
+latency_ms = 200
+
+[[route]]
+method = "POST"
+path = "/api/orders"
+status = 201
+body = '{"created": {{timestamp}}}'
+failure_rate = 0.2
+failure_status = 500
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<MockConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: MockConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", MockServerPlugin::sample_config());
+                Ok(MockConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(MockConfig::default())
+        }
+    }
+}
+
+impl Plugin for MockServerPlugin {
+    fn name(&self) -> &'static str {
+        "mock_server"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "HTTP mock server with templated routes, recording and replay"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Serve canned HTTP responses, or record a real backend to replay later")
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .short('l')
+                    .value_name("ADDR")
+                    .help("Address to listen on (default 127.0.0.1:8080)"),
+            )
+            .arg(
+                Arg::new("upstream")
+                    .long("upstream")
+                    .short('u')
+                    .value_name("URL")
+                    .help("Real backend for recording and passthrough"),
+            )
+            .arg(
+                Arg::new("routes")
+                    .long("routes")
+                    .short('r')
+                    .value_name("FILE")
+                    .action(ArgAction::Append)
+                    .help("Extra route file (.toml or .yaml), can be repeated"),
+            )
+            .arg(
+                Arg::new("record")
+                    .long("record")
+                    .action(ArgAction::SetTrue)
+                    .help("Forward every request to the upstream and save the responses"),
+            )
+            .arg(
+                Arg::new("passthrough")
+                    .long("passthrough")
+                    .action(ArgAction::SetTrue)
+                    .help("Forward requests no route matches to the upstream"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(MockServerPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<MockConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let listen = matches
+                .get_one::<String>("listen")
+                .or(config.listen.as_ref())
+                .cloned()
+                .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+            let upstream = matches
+                .get_one::<String>("upstream")
+                .or(config.upstream.as_ref())
+                .cloned();
+            let record = matches.get_flag("record");
+            if record && upstream.is_none() {
+                eprintln!("❌ --record needs an upstream");
+                eprintln!("💡 Set `upstream` in the config file or pass --upstream");
+                std::process::exit(1);
+            }
+
+            let mut routes = config.route;
+            let files = config
+                .routes_files
+                .iter()
+                .chain(matches.get_many::<String>("routes").into_iter().flatten());
+            for file in files {
+                match routes::load_file(Path::new(file)) {
+                    Ok(loaded) => routes.extend(loaded),
+                    Err(e) => {
+                        eprintln!("❌ Failed to load routes: {:#}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            let recordings_path = config
+                .recordings
+                .map(PathBuf::from)
+                .or_else(|| {
+                    plugin_api::plugin_state_dir(self.name()).map(|dir| dir.join(RECORDINGS_FILE))
+                })
+                .unwrap_or_else(|| PathBuf::from(RECORDINGS_FILE));
+            let recordings =
+                if (record || config.replay.unwrap_or(true)) && recordings_path.exists() {
+                    match routes::load_file(&recordings_path) {
+                        Ok(recordings) => recordings,
+                        Err(e) => {
+                            eprintln!("⚠️  Ignoring recordings: {:#}", e);
+                            Vec::new()
+                        }
+                    }
+                } else {
+                    Vec::new()
+                };
+
+            let listener = match TcpListener::bind(&listen).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("❌ Failed to listen on {}: {}", listen, e);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🚀 Mock server on http://{}", listen);
+            for route in &routes {
+                println!("  {} → {}", route.describe(), route.status());
+            }
+            if record {
+                println!(
+                    "⏺️  Recording {} into {}",
+                    upstream.as_deref().unwrap_or_default(),
+                    recordings_path.display()
+                );
+            } else if !recordings.is_empty() {
+                println!(
+                    "📼 Replaying {} recorded responses from {}",
+                    recordings.len(),
+                    recordings_path.display()
+                );
+            }
+
+            let mock = Arc::new(server::Mock {
+                routes,
+                recordings: RwLock::new(recordings),
+                recordings_path,
+                upstream,
+                record,
+                passthrough: matches.get_flag("passthrough") || config.passthrough.unwrap_or(false),
+                http: reqwest::Client::new(),
+            });
+
+            loop {
+                let (socket, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("❌ Accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let mock = mock.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request| mock.clone().handle(request));
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(socket), service)
+                        .await
+                    {
+                        eprintln!("❌ [{}] {}", addr, e);
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(MockServerPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
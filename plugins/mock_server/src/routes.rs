@@ -0,0 +1,188 @@
+// Mock routes: request matchers, canned responses and the small template language used in
+// response bodies and headers. Routes come from the plugin config, from route files (TOML
+// or YAML, both with a top-level `route` list) and from recordings.
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_FAILURE_STATUS: u16 = 503;
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Route {
+    /// HTTP method; any method when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// "/api/users/{id}": `{name}` captures a segment, `*` matches one segment, or the
+    /// rest of the path when it is the last one
+    pub path: String,
+    /// Query parameters that must be present with these values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub query: BTreeMap<String, String>,
+    /// Response status (default 200)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Response body template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// File with the response body template, instead of `body`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_file: Option<String>,
+    /// Delay before answering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Share of requests (0.0 to 1.0) answered with `failure_status` instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_rate: Option<f64>,
+    /// Status of simulated failures (default 503)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_status: Option<u16>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RouteFile {
+    #[serde(default)]
+    pub route: Vec<Route>,
+}
+
+/// Routes from a .toml, .yaml or .yml file
+pub fn load_file(path: &Path) -> Result<Vec<Route>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let file: RouteFile = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&content)?,
+        Some("toml") => toml::from_str(&content)?,
+        _ => return Err(anyhow!("{}: expected .toml, .yaml or .yml", path.display())),
+    };
+    Ok(file.route)
+}
+
+/// What a request carries that routes match against and templates can use
+pub struct RequestInfo {
+    pub method: String,
+    pub path: String,
+    pub query: BTreeMap<String, String>,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+pub fn parse_query(query: &str) -> BTreeMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (pair.to_string(), String::new()),
+        })
+        .collect()
+}
+
+impl Route {
+    /// Path parameters when the route matches the request
+    pub fn matches(&self, request: &RequestInfo) -> Option<BTreeMap<String, String>> {
+        if let Some(method) = &self.method {
+            if !method.eq_ignore_ascii_case(&request.method) {
+                return None;
+            }
+        }
+        if self
+            .query
+            .iter()
+            .any(|(key, value)| request.query.get(key) != Some(value))
+        {
+            return None;
+        }
+
+        let pattern: Vec<&str> = self.path.trim_matches('/').split('/').collect();
+        let path: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        let mut params = BTreeMap::new();
+        for (i, segment) in pattern.iter().enumerate() {
+            if *segment == "*" && i == pattern.len() - 1 {
+                return Some(params);
+            }
+            let actual = path.get(i)?;
+            if let Some(name) = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                params.insert(name.to_string(), actual.to_string());
+            } else if *segment != "*" && segment != actual {
+                return None;
+            }
+        }
+        (pattern.len() == path.len()).then_some(params)
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status.unwrap_or(200)
+    }
+
+    /// Whether this request should get a simulated failure
+    pub fn fails(&self) -> bool {
+        self.failure_rate
+            .is_some_and(|rate| rate > 0.0 && fastrand::f64() < rate)
+    }
+
+    pub fn failure_status(&self) -> u16 {
+        self.failure_status.unwrap_or(DEFAULT_FAILURE_STATUS)
+    }
+
+    pub fn body_template(&self) -> Result<String> {
+        match &self.body_file {
+            Some(file) => {
+                fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))
+            }
+            None => Ok(self.body.clone().unwrap_or_default()),
+        }
+    }
+
+    /// One line describing the matcher, for logs
+    pub fn describe(&self) -> String {
+        let mut description = format!("{} {}", self.method.as_deref().unwrap_or("*"), self.path);
+        if !self.query.is_empty() {
+            let query: Vec<String> = self
+                .query
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            description.push('?');
+            description.push_str(&query.join("&"));
+        }
+        description
+    }
+}
+
+/// Replaces `{{path.NAME}}`, `{{query.NAME}}`, `{{header.NAME}}`, `{{method}}`, `{{path}}`,
+/// `{{body}}` and `{{timestamp}}` (Unix milliseconds); unknown placeholders stay as they are
+pub fn render(template: &str, request: &RequestInfo, params: &BTreeMap<String, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        let placeholder = &rest[start..start + end + 2];
+        let name = placeholder[2..placeholder.len() - 2].trim();
+        let value = match name.split_once('.') {
+            Some(("path", key)) => params.get(key).cloned(),
+            Some(("query", key)) => request.query.get(key).cloned(),
+            Some(("header", key)) => request.headers.get(&key.to_ascii_lowercase()).cloned(),
+            _ => match name {
+                "method" => Some(request.method.clone()),
+                "path" => Some(request.path.clone()),
+                "body" => Some(request.body.clone()),
+                "timestamp" => SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|now| now.as_millis().to_string()),
+                _ => None,
+            },
+        };
+        output.push_str(value.as_deref().unwrap_or(placeholder));
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    output
+}
@@ -0,0 +1,296 @@
+// Request handling: routes answer with their canned responses, and the upstream, when
+// configured, is used for recording (every request is forwarded and the response kept as
+// a route) and for passthrough of requests no route matches.
+use crate::routes::{self, RequestInfo, Route, RouteFile};
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::http::request::Parts;
+use hyper::{HeaderMap, Request, Response, StatusCode};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Headers that describe one transfer rather than the response, never copied or recorded
+const HOP_HEADERS: [&str; 6] = [
+    "connection",
+    "content-length",
+    "date",
+    "keep-alive",
+    "transfer-encoding",
+    "upgrade",
+];
+
+pub struct Mock {
+    /// Configured routes, checked before recordings
+    pub routes: Vec<Route>,
+    pub recordings: RwLock<Vec<Route>>,
+    pub recordings_path: PathBuf,
+    pub upstream: Option<String>,
+    /// Forward everything to the upstream and record the responses
+    pub record: bool,
+    /// Forward requests no route matches to the upstream
+    pub passthrough: bool,
+    pub http: reqwest::Client,
+}
+
+/// Where an answer came from, for the log line
+enum Source {
+    Route(String),
+    Failure(String),
+    Recorded,
+    Proxied,
+    NoRoute,
+}
+
+fn response(
+    status: u16,
+    headers: &BTreeMap<String, String>,
+    body: impl Into<Bytes>,
+) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::OK);
+    for (name, value) in headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                response.headers_mut().append(name, value);
+            }
+            _ => eprintln!("⚠️  Skipping invalid header {}: {}", name, value),
+        }
+    }
+    response
+}
+
+fn json_response(status: u16, body: serde_json::Value) -> Response<Full<Bytes>> {
+    let headers = BTreeMap::from([("content-type".to_string(), "application/json".to_string())]);
+    response(status, &headers, body.to_string())
+}
+
+fn header_map(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter(|(name, _)| !HOP_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+impl Mock {
+    async fn find(&self, request: &RequestInfo) -> Option<(Route, BTreeMap<String, String>)> {
+        if let Some(found) = self
+            .routes
+            .iter()
+            .find_map(|route| Some((route.clone(), route.matches(request)?)))
+        {
+            return Some(found);
+        }
+        // The recording with the most matching query parameters, since one without any
+        // matches every query string
+        self.recordings
+            .read()
+            .await
+            .iter()
+            .filter_map(|route| Some((route.clone(), route.matches(request)?)))
+            .max_by_key(|(route, _)| route.query.len())
+    }
+
+    async fn respond(
+        &self,
+        route: &Route,
+        request: &RequestInfo,
+        params: &BTreeMap<String, String>,
+    ) -> Result<(Response<Full<Bytes>>, Source)> {
+        if let Some(latency) = route.latency_ms {
+            tokio::time::sleep(Duration::from_millis(latency)).await;
+        }
+        if route.fails() {
+            return Ok((
+                json_response(
+                    route.failure_status(),
+                    serde_json::json!({ "error": "simulated failure" }),
+                ),
+                Source::Failure(route.describe()),
+            ));
+        }
+        let headers = route
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), routes::render(value, request, params)))
+            .collect();
+        let body = routes::render(&route.body_template()?, request, params);
+        Ok((
+            response(route.status(), &headers, body),
+            Source::Route(route.describe()),
+        ))
+    }
+
+    async fn forward(
+        &self,
+        parts: &Parts,
+        target: &str,
+        body: Bytes,
+    ) -> Result<(u16, BTreeMap<String, String>, Bytes)> {
+        let upstream = self
+            .upstream
+            .as_deref()
+            .ok_or_else(|| anyhow!("No upstream configured"))?;
+        let mut headers = parts.headers.clone();
+        headers.remove("host");
+        for name in HOP_HEADERS {
+            headers.remove(name);
+        }
+        let reply = self
+            .http
+            .request(
+                parts.method.clone(),
+                format!("{}{}", upstream.trim_end_matches('/'), target),
+            )
+            .headers(headers)
+            .body(body)
+            .send()
+            .await?;
+        let status = reply.status().as_u16();
+        let headers = header_map(reply.headers());
+        Ok((status, headers, reply.bytes().await?))
+    }
+
+    async fn record(
+        &self,
+        request: &RequestInfo,
+        status: u16,
+        headers: BTreeMap<String, String>,
+        body: &Bytes,
+    ) -> Result<()> {
+        let body = String::from_utf8(body.to_vec())
+            .map_err(|_| anyhow!("Binary response body, not recorded"))?;
+        let route = Route {
+            method: Some(request.method.clone()),
+            path: request.path.clone(),
+            query: request.query.clone(),
+            status: Some(status),
+            headers,
+            body: (!body.is_empty()).then_some(body),
+            ..Default::default()
+        };
+
+        let mut recordings = self.recordings.write().await;
+        recordings.retain(|existing| {
+            existing.method != route.method
+                || existing.path != route.path
+                || existing.query != route.query
+        });
+        recordings.push(route);
+        let file = RouteFile {
+            route: recordings.clone(),
+        };
+        if let Some(dir) = self.recordings_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.recordings_path, serde_yaml::to_string(&file)?)?;
+        Ok(())
+    }
+
+    async fn answer(
+        &self,
+        parts: &Parts,
+        target: &str,
+        request: &RequestInfo,
+        body: Bytes,
+    ) -> Result<(Response<Full<Bytes>>, Source)> {
+        if self.record {
+            let (status, headers, reply) = self.forward(parts, target, body).await?;
+            if let Err(e) = self.record(request, status, headers.clone(), &reply).await {
+                eprintln!("⚠️  {} {}: {}", request.method, target, e);
+            }
+            return Ok((response(status, &headers, reply), Source::Recorded));
+        }
+        if let Some((route, params)) = self.find(request).await {
+            return self.respond(&route, request, &params).await;
+        }
+        if self.passthrough && self.upstream.is_some() {
+            let (status, headers, reply) = self.forward(parts, target, body).await?;
+            return Ok((response(status, &headers, reply), Source::Proxied));
+        }
+        Ok((
+            json_response(
+                404,
+                serde_json::json!({
+                    "error": "no mock route",
+                    "method": request.method,
+                    "path": request.path,
+                }),
+            ),
+            Source::NoRoute,
+        ))
+    }
+
+    pub async fn handle(
+        self: Arc<Self>,
+        request: Request<Incoming>,
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        let started = Instant::now();
+        let (parts, body) = request.into_parts();
+        let target = parts
+            .uri
+            .path_and_query()
+            .map(|target| target.as_str().to_string())
+            .unwrap_or_else(|| parts.uri.path().to_string());
+        let body = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => {
+                eprintln!("❌ {} {}: {}", parts.method, target, e);
+                return Ok(json_response(
+                    400,
+                    serde_json::json!({ "error": e.to_string() }),
+                ));
+            }
+        };
+        let request = RequestInfo {
+            method: parts.method.to_string(),
+            path: parts.uri.path().to_string(),
+            query: routes::parse_query(parts.uri.query().unwrap_or_default()),
+            headers: header_map(&parts.headers),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        };
+
+        let elapsed = || started.elapsed().as_millis();
+        match self.answer(&parts, &target, &request, body).await {
+            Ok((response, source)) => {
+                let status = response.status().as_u16();
+                let line = format!("{} {} → {}", request.method, target, status);
+                match source {
+                    Source::Route(route) => {
+                        println!("🎭 {} ({}) in {}ms", line, route, elapsed())
+                    }
+                    Source::Failure(route) => {
+                        println!(
+                            "💥 {} simulated failure ({}) in {}ms",
+                            line,
+                            route,
+                            elapsed()
+                        )
+                    }
+                    Source::Recorded => println!("⏺️  {} recorded in {}ms", line, elapsed()),
+                    Source::Proxied => println!("↗️  {} from upstream in {}ms", line, elapsed()),
+                    Source::NoRoute => println!("❓ {} no route", line),
+                }
+                Ok(response)
+            }
+            Err(e) => {
+                eprintln!("❌ {} {}: {:#}", request.method, target, e);
+                Ok(json_response(
+                    502,
+                    serde_json::json!({ "error": format!("{:#}", e) }),
+                ))
+            }
+        }
+    }
+}
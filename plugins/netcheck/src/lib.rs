@@ -0,0 +1,510 @@
+// "Is it the network or the app": TCP, TLS and HTTP checks against a set of targets
+// (addresses, URLs, the ports forwarded by k8s_port_forward, or the pods behind a
+// selector), all run at once, with a pass/fail matrix as a table or as JSON.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, ListParams};
+use kube::Client;
+use plugin_api::net;
+use plugin_api::Plugin;
+use rustls::{ClientConfig, RootCertStore};
+use serde::Deserialize;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+mod probe;
+
+use probe::{Check, Outcome, Target};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 3;
+/// Where k8s_port_forward keeps the forwards of its running processes
+const PORT_FORWARD_PLUGIN: &str = "k8s_port_forward";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NetcheckConfig {
+    /// Seconds each check may take (default 3)
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub target: Vec<TargetConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TargetConfig {
+    pub name: Option<String>,
+    /// "host:port", "tls://host:port", or an http:// or https:// URL
+    pub address: String,
+    /// Server name for the TLS handshake (default: the host)
+    pub sni: Option<String>,
+}
+
+pub struct NetcheckPlugin;
+
+impl NetcheckPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Netcheck Configuration
+timeout_secs = 3
+
+[[target]]
+name = "postgres"
+address = "db.internal:5432"
+
+[[target]]
+name = "api"
+address = "https://api.internal.example.com/healthz"
+
+[[target]]
+name = "api (forwarded)"
+address = "https://localhost:8443/healthz"
+sni = "api.internal.example.com"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<NetcheckConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: NetcheckConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Targets can be given on the command line
+                Ok(NetcheckConfig::default())
+            }
+        }
+        None => Ok(NetcheckConfig::default()),
+    }
+}
+
+/// A target from "host:port", "tls://host:port" or an http(s) URL
+fn parse_target(address: &str, name: Option<String>, sni: Option<String>) -> Result<Target> {
+    let (scheme, rest) = match address.split_once("://") {
+        Some((scheme, rest)) => (scheme.to_lowercase(), rest),
+        None => (String::new(), address),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], Some(rest[slash..].to_string())),
+        None => (rest, None),
+    };
+    let (tls, http, default_port) = match scheme.as_str() {
+        "" => (false, None, None),
+        "tls" => (true, None, None),
+        "http" => (
+            false,
+            Some(path.unwrap_or_else(|| "/".to_string())),
+            Some(80),
+        ),
+        "https" => (
+            true,
+            Some(path.unwrap_or_else(|| "/".to_string())),
+            Some(443),
+        ),
+        other => return Err(anyhow!("Unknown scheme '{}' in {}", other, address)),
+    };
+    let (host, port) = net::split_host_port(authority);
+    let host = host.to_string();
+    let port = port
+        .map(|port| {
+            port.parse::<u16>()
+                .map_err(|_| anyhow!("Invalid port in {}", address))
+        })
+        .transpose()?;
+    let port = port
+        .or(default_port)
+        .ok_or_else(|| anyhow!("{} needs a port (host:port)", address))?;
+    Ok(Target {
+        name: name.unwrap_or_else(|| address.to_string()),
+        host,
+        port,
+        tls,
+        sni,
+        http,
+        unusable: None,
+    })
+}
+
+/// The local ports of the forwards k8s_port_forward has running
+fn forwarded_targets() -> Result<Vec<Target>> {
+    let dir = plugin_api::plugin_state_dir(PORT_FORWARD_PLUGIN)
+        .ok_or_else(|| anyhow!("Could not determine the k8s_port_forward state directory"))?;
+    let mut targets = Vec::new();
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(targets);
+    };
+    let mut paths: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("forwards-") && name.ends_with(".json"))
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        let Some(state) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        else {
+            continue;
+        };
+        for forward in state["forwards"].as_array().into_iter().flatten() {
+            if forward["status"].as_str() == Some("stopped") {
+                continue;
+            }
+            let Some(port) = forward["local_port"].as_u64() else {
+                continue;
+            };
+            targets.push(Target {
+                name: format!(
+                    "{} ({})",
+                    forward["resource"].as_str().unwrap_or("?"),
+                    forward["namespace"].as_str().unwrap_or("?")
+                ),
+                host: "127.0.0.1".to_string(),
+                port: port as u16,
+                tls: false,
+                sni: None,
+                http: None,
+                unusable: None,
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// The pods matching the selector, at their pod IPs
+async fn pod_targets(namespace: Option<&str>, selector: &str, port: u16) -> Result<Vec<Target>> {
+    let client = Client::try_default().await?;
+    let namespace = namespace
+        .map(str::to_string)
+        .unwrap_or_else(|| client.default_namespace().to_string());
+    let pods: Api<Pod> = Api::namespaced(client, &namespace);
+    let list = pods.list(&ListParams::default().labels(selector)).await?;
+    if list.items.is_empty() {
+        return Err(anyhow!("No pods match selector: {}", selector));
+    }
+    Ok(list
+        .items
+        .into_iter()
+        .map(|pod| {
+            let status = pod.status.unwrap_or_default();
+            let ip = status.pod_ip.unwrap_or_default();
+            let unusable = if ip.is_empty() {
+                Some(format!(
+                    "no pod IP ({})",
+                    status.phase.as_deref().unwrap_or("unknown")
+                ))
+            } else {
+                None
+            };
+            Target {
+                name: format!("pod/{}", pod.metadata.name.unwrap_or_default()),
+                host: ip,
+                port,
+                tls: false,
+                sni: None,
+                http: None,
+                unusable,
+            }
+        })
+        .collect())
+}
+
+fn tls_config() -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    for error in &native.errors {
+        eprintln!("⚠️  Skipping a system certificate: {}", error);
+    }
+    let (added, _) = roots.add_parsable_certificates(native.certs);
+    if added == 0 {
+        eprintln!("⚠️  No system root certificates found, TLS checks will fail");
+    }
+    let mut tls = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Arc::new(tls)
+}
+
+/// A column of the table, cut to its width; failures are spelled out under the table
+fn cell(check: &Option<Check>) -> String {
+    let text = match check {
+        Some(check) if check.ok => format!("✓ {} {}ms", check.detail, check.ms),
+        Some(check) => format!("✗ {}", check.detail),
+        None => "—".to_string(),
+    };
+    if text.chars().count() > 22 {
+        format!("{}…", text.chars().take(21).collect::<String>())
+    } else {
+        text
+    }
+}
+
+fn print_table(outcomes: &[Outcome]) {
+    let name_width = outcomes
+        .iter()
+        .map(|outcome| outcome.target.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(6, 40);
+    println!(
+        "{:<name_width$}  {:<22}  {:<22}  {:<22}  {:<22}  VERDICT",
+        "TARGET", "ADDRESS", "TCP", "TLS", "HTTP"
+    );
+    for outcome in outcomes {
+        let verdict = match outcome.verdict.as_str() {
+            "ok" => "✅ ok",
+            "network" => "🌐 network",
+            "tls" => "🔒 tls",
+            _ => "📦 app",
+        };
+        println!(
+            "{:<name_width$}  {:<22}  {:<22}  {:<22}  {:<22}  {}",
+            outcome.target,
+            outcome.address,
+            cell(&outcome.tcp),
+            cell(&outcome.tls),
+            cell(&outcome.http),
+            verdict
+        );
+    }
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.verdict != "ok")
+        .count();
+    println!();
+    for outcome in outcomes {
+        for (layer, check) in [
+            ("tcp", &outcome.tcp),
+            ("tls", &outcome.tls),
+            ("http", &outcome.http),
+        ] {
+            if let Some(check) = check.as_ref().filter(|check| !check.ok) {
+                println!("   {} {}: {}", outcome.target, layer, check.detail);
+            }
+        }
+    }
+    if failed > 0 {
+        println!();
+    }
+    if failed == 0 {
+        println!("✅ All {} target(s) reachable", outcomes.len());
+    } else {
+        println!("❌ {} of {} target(s) failing", failed, outcomes.len());
+    }
+}
+
+async fn check(targets: Vec<Target>, timeout: Duration, json: bool) -> Result<bool> {
+    let tls = tls_config();
+    let checks = targets
+        .into_iter()
+        .map(|target| tokio::spawn(probe::run(target, tls.clone(), timeout)));
+    let mut outcomes = Vec::new();
+    for handle in checks.collect::<Vec<_>>() {
+        outcomes.push(handle.await?);
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&outcomes)?);
+    } else {
+        print_table(&outcomes);
+    }
+    Ok(outcomes.iter().all(|outcome| outcome.verdict == "ok"))
+}
+
+impl Plugin for NetcheckPlugin {
+    fn name(&self) -> &'static str {
+        "netcheck"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "TCP, TLS and HTTP checks of hosts, forwarded ports and pods"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Check TCP, TLS and HTTP reachability of targets and show a pass/fail matrix")
+            .arg(
+                Arg::new("targets").value_name("TARGET").num_args(0..).help(
+                    "host:port, tls://host:port or an http(s) URL (default: configured targets)",
+                ),
+            )
+            .arg(
+                Arg::new("forwards")
+                    .long("forwards")
+                    .action(ArgAction::SetTrue)
+                    .help("Also check the local ports of running k8s_port_forward forwards"),
+            )
+            .arg(
+                Arg::new("selector")
+                    .long("selector")
+                    .short('l')
+                    .value_name("SELECTOR")
+                    .requires("port")
+                    .help("Also check the pods matching this selector at their pod IPs"),
+            )
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .short('p')
+                    .value_name("PORT")
+                    .value_parser(clap::value_parser!(u16))
+                    .help("Port to check on the selected pods"),
+            )
+            .arg(
+                Arg::new("namespace")
+                    .long("namespace")
+                    .short('n')
+                    .value_name("NAMESPACE")
+                    .help("Namespace of the selected pods"),
+            )
+            .arg(
+                Arg::new("tls")
+                    .long("tls")
+                    .action(ArgAction::SetTrue)
+                    .help("Do a TLS handshake on every target"),
+            )
+            .arg(
+                Arg::new("http")
+                    .long("http")
+                    .value_name("PATH")
+                    .help("Send a GET for PATH to every target"),
+            )
+            .arg(
+                Arg::new("sni")
+                    .long("sni")
+                    .value_name("NAME")
+                    .help("Server name for TLS handshakes (default: each target's host)"),
+            )
+            .arg(
+                Arg::new("timeout")
+                    .long("timeout")
+                    .value_name("SECONDS")
+                    .value_parser(clap::value_parser!(u64))
+                    .help("Seconds each check may take (default 3)"),
+            )
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Print the results as JSON"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(NetcheckPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<NetcheckConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let sni = matches.get_one::<String>("sni").cloned();
+        let parsed: Result<Vec<Target>> = match matches.get_many::<String>("targets") {
+            Some(addresses) => addresses
+                .map(|address| parse_target(address, None, sni.clone()))
+                .collect(),
+            None => config
+                .target
+                .iter()
+                .map(|target| {
+                    parse_target(
+                        &target.address,
+                        target.name.clone(),
+                        target.sni.clone().or_else(|| sni.clone()),
+                    )
+                })
+                .collect(),
+        };
+        let mut targets = match parsed {
+            Ok(targets) => targets,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        };
+        if matches.get_flag("forwards") {
+            match forwarded_targets() {
+                Ok(forwards) if forwards.is_empty() => {
+                    eprintln!("⚠️  No running k8s_port_forward forwards found")
+                }
+                Ok(forwards) => targets.extend(forwards),
+                Err(e) => eprintln!("⚠️  {}", e),
+            }
+        }
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        if let (Some(selector), Some(port)) = (
+            matches.get_one::<String>("selector"),
+            matches.get_one::<u16>("port"),
+        ) {
+            let namespace = matches.get_one::<String>("namespace").map(String::as_str);
+            match rt.block_on(pod_targets(namespace, selector, *port)) {
+                Ok(pods) => targets.extend(pods),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        if targets.is_empty() {
+            eprintln!("❌ Nothing to check: give targets, --forwards or --selector (or configure in config file)");
+            eprintln!(
+                "💡 Example: proxy netcheck db.internal:5432 https://api.example.com/healthz"
+            );
+            eprintln!("📝 Sample config:\n{}", NetcheckPlugin::sample_config());
+            std::process::exit(1);
+        }
+        for target in &mut targets {
+            target.tls |= matches.get_flag("tls");
+            if let Some(path) = matches.get_one::<String>("http") {
+                target.http = Some(path.clone());
+            }
+            if sni.is_some() && target.sni.is_none() {
+                target.sni = sni.clone();
+            }
+        }
+
+        let timeout = Duration::from_secs(
+            matches
+                .get_one::<u64>("timeout")
+                .copied()
+                .or(config.timeout_secs)
+                .unwrap_or(DEFAULT_TIMEOUT_SECS),
+        );
+        match rt.block_on(check(targets, timeout, matches.get_flag("json"))) {
+            Ok(true) => {}
+            // Failing checks fail the command, for scripts
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(NetcheckPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
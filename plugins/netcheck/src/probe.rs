@@ -0,0 +1,227 @@
+// The checks, layered the way a request is: a TCP connect (with the DNS lookup before
+// it), a TLS handshake on that connection, then an HTTP request over it. The first layer
+// that fails tells whether it is the network, TLS or the app, and the layers above it
+// are not attempted.
+use rustls::pki_types::ServerName;
+use rustls::ClientConfig;
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+    /// Server name for the handshake (default: the host)
+    pub sni: Option<String>,
+    /// Path of the HTTP probe
+    pub http: Option<String>,
+    /// Why the target can't be checked at all, e.g. a pod without an IP
+    pub unusable: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    pub ms: u128,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Outcome {
+    pub target: String,
+    pub address: String,
+    pub tcp: Option<Check>,
+    pub tls: Option<Check>,
+    pub http: Option<Check>,
+    /// "ok", or the layer that failed: "network", "tls" or "app"
+    pub verdict: String,
+}
+
+fn describe(error: &std::io::Error) -> String {
+    match error.kind() {
+        ErrorKind::ConnectionRefused => "refused".to_string(),
+        ErrorKind::ConnectionReset => "reset".to_string(),
+        ErrorKind::TimedOut => "timeout".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+/// Runs `step` with the timeout, timing it
+async fn timed<T, F>(timeout: Duration, step: F) -> (Result<T, String>, u128)
+where
+    F: std::future::Future<Output = Result<T, String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(timeout, step).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timeout after {}s", timeout.as_secs_f32())),
+    };
+    (result, started.elapsed().as_millis())
+}
+
+async fn connect(host: &str, port: u16) -> Result<TcpStream, String> {
+    let addresses: Vec<_> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("DNS: {}", e))?
+        .collect();
+    let mut last = "DNS: no addresses".to_string();
+    for address in addresses {
+        match TcpStream::connect(address).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last = describe(&e),
+        }
+    }
+    Err(last)
+}
+
+/// Sends a GET and returns the status line's code and reason
+async fn get<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    path: &str,
+) -> Result<(u16, String), String> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: proxy-netcheck\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        path,
+        if host.contains(':') {
+            format!("[{}]", host)
+        } else {
+            host.to_string()
+        }
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| describe(&e))?;
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.contains(&b'\n') {
+        let n = stream.read(&mut buffer).await.map_err(|e| describe(&e))?;
+        if n == 0 {
+            return Err("closed without a response".to_string());
+        }
+        head.extend_from_slice(&buffer[..n]);
+    }
+    let line = String::from_utf8_lossy(&head);
+    let line = line.lines().next().unwrap_or_default();
+    let mut parts = line.splitn(3, ' ');
+    let code = parts
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| format!("not HTTP: {}", line))?;
+    Ok((code, parts.next().unwrap_or_default().to_string()))
+}
+
+fn http_check(result: Result<(u16, String), String>, ms: u128) -> Check {
+    match result {
+        Ok((code, reason)) => Check {
+            ok: (200..400).contains(&code),
+            ms,
+            detail: format!("{} {}", code, reason).trim().to_string(),
+        },
+        Err(detail) => Check {
+            ok: false,
+            ms,
+            detail,
+        },
+    }
+}
+
+pub async fn run(target: Target, tls: Arc<ClientConfig>, timeout: Duration) -> Outcome {
+    let mut outcome = Outcome {
+        target: target.name.clone(),
+        address: plugin_api::net::host_port(&target.host, target.port),
+        tcp: None,
+        tls: None,
+        http: None,
+        verdict: "network".to_string(),
+    };
+    if let Some(reason) = &target.unusable {
+        outcome.tcp = Some(Check {
+            ok: false,
+            ms: 0,
+            detail: reason.clone(),
+        });
+        return outcome;
+    }
+
+    let (stream, ms) = timed(timeout, connect(&target.host, target.port)).await;
+    let mut stream = match stream {
+        Ok(stream) => {
+            outcome.tcp = Some(Check {
+                ok: true,
+                ms,
+                detail: "open".to_string(),
+            });
+            stream
+        }
+        Err(detail) => {
+            outcome.tcp = Some(Check {
+                ok: false,
+                ms,
+                detail,
+            });
+            return outcome;
+        }
+    };
+
+    if !target.tls {
+        if let Some(path) = &target.http {
+            let (result, ms) = timed(timeout, get(&mut stream, &target.host, path)).await;
+            outcome.http = Some(http_check(result, ms));
+        }
+    } else {
+        outcome.verdict = "tls".to_string();
+        let name = target.sni.clone().unwrap_or_else(|| target.host.clone());
+        let handshake = async {
+            let server_name = ServerName::try_from(name).map_err(|e| e.to_string())?;
+            TlsConnector::from(tls)
+                .connect(server_name, stream)
+                .await
+                .map_err(|e| describe(&e))
+        };
+        let (stream, ms) = timed(timeout, handshake).await;
+        let mut stream = match stream {
+            Ok(stream) => {
+                let version = stream
+                    .get_ref()
+                    .1
+                    .protocol_version()
+                    .map(|version| format!("{:?}", version).replace('_', "."))
+                    .unwrap_or_default();
+                outcome.tls = Some(Check {
+                    ok: true,
+                    ms,
+                    detail: version,
+                });
+                stream
+            }
+            Err(detail) => {
+                outcome.tls = Some(Check {
+                    ok: false,
+                    ms,
+                    detail,
+                });
+                return outcome;
+            }
+        };
+        if let Some(path) = &target.http {
+            let (result, ms) = timed(timeout, get(&mut stream, &target.host, path)).await;
+            outcome.http = Some(http_check(result, ms));
+        }
+    }
+
+    outcome.verdict = match &outcome.http {
+        Some(check) if !check.ok => "app",
+        _ => "ok",
+    }
+    .to_string();
+    outcome
+}
@@ -0,0 +1,270 @@
+// Questions about captured traffic. `analyze` reads HAR files (log.entries) or JSON
+// captures (an array of records, or one record per line, like the files
+// `k8s_native_port_forward --capture` writes), turns every request into a one-line
+// summary and puts the result in the system prompt. Captures too large for the
+// context window are summarized chunk by chunk by the model first; totals and the
+// slowest requests are always computed locally so they stay exact.
+use crate::{chat_turn, OllamaConfig, Render};
+use anyhow::{anyhow, Context};
+use reqwest::Client;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Budget for the capture digest, in characters (roughly 6k tokens)
+const CONTEXT_CHARS: usize = 24_000;
+/// Long fields (bodies, queries) are cut to this many characters in the summaries
+const FIELD_CHARS: usize = 200;
+const SLOWEST: usize = 10;
+
+const ANALYZE_PROMPT: &str = "You are analyzing network traffic captured through a \
+Kubernetes port-forward. Answer questions using only the capture below; quote the exact \
+requests, statuses and timings you rely on, and say so when the capture doesn't contain \
+the answer.";
+
+const CHUNK_PROMPT: &str = "Summarize this part of a traffic capture for later questions. \
+Group requests by endpoint or query, keep exact counts, status codes, errors and the \
+slowest timings, and leave out anything unremarkable.";
+
+/// One captured request, reduced to what questions usually need
+struct Entry {
+    line: String,
+    endpoint: Option<String>,
+    status: Option<i64>,
+    millis: Option<f64>,
+}
+
+fn truncate(text: &str) -> String {
+    let text = text.replace('\n', " ");
+    match text.char_indices().nth(FIELD_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+fn har_entry(entry: &Value) -> Entry {
+    let request = &entry["request"];
+    let response = &entry["response"];
+    let method = request["method"].as_str().unwrap_or("?");
+    let url = request["url"].as_str().unwrap_or("?");
+    let status = response["status"].as_i64();
+    let millis = entry["time"].as_f64();
+    let mut line = format!(
+        "{} {} {} -> {}",
+        entry["startedDateTime"].as_str().unwrap_or("-"),
+        method,
+        url,
+        status.map_or("?".to_string(), |s| s.to_string())
+    );
+    if let Some(millis) = millis {
+        line.push_str(&format!(" in {:.0}ms", millis));
+    }
+    if let Some(size) = response["content"]["size"].as_i64() {
+        line.push_str(&format!(", {} bytes", size));
+    }
+    if let Some(text) = response["content"]["text"].as_str() {
+        if status.is_some_and(|s| s >= 400) {
+            line.push_str(&format!(", body: {}", truncate(text)));
+        }
+    }
+    Entry {
+        line,
+        endpoint: Some(format!(
+            "{} {}",
+            method,
+            url.split('?').next().unwrap_or(url)
+        )),
+        status,
+        millis,
+    }
+}
+
+/// Records that aren't HAR entries are kept as compact JSON, with the usual field names
+/// picked out for the totals
+fn record(record: &Value) -> Entry {
+    let field = |names: &[&str]| names.iter().find_map(|name| record.get(*name));
+    let endpoint = match (
+        field(&["method"]).and_then(Value::as_str),
+        field(&["path", "url", "query"]).and_then(Value::as_str),
+    ) {
+        (Some(method), Some(path)) => Some(format!("{} {}", method, path)),
+        (None, Some(path)) => Some(truncate(path)),
+        _ => None,
+    };
+    Entry {
+        line: truncate(&record.to_string()),
+        endpoint,
+        status: field(&["status", "status_code"]).and_then(Value::as_i64),
+        millis: field(&["duration_ms", "time", "elapsed_ms"]).and_then(Value::as_f64),
+    }
+}
+
+fn load(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let entries = match serde_json::from_str::<Value>(&content) {
+        Ok(value) => match value["log"]["entries"].as_array() {
+            Some(entries) => entries.iter().map(har_entry).collect(),
+            None => match value {
+                Value::Array(records) => records.iter().map(record).collect(),
+                other => vec![record(&other)],
+            },
+        },
+        // Not a single document: one JSON record per line
+        Err(_) => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str::<Value>(line).map(|value| record(&value)))
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("{} is not a HAR or JSON capture", path.display()))?,
+    };
+    Ok(entries)
+}
+
+/// Counts per endpoint and status plus the slowest requests, computed over everything
+fn totals(entries: &[Entry]) -> String {
+    let mut out = format!("Requests: {}\n", entries.len());
+
+    let mut statuses = std::collections::BTreeMap::new();
+    for status in entries.iter().filter_map(|e| e.status) {
+        *statuses.entry(status).or_insert(0) += 1;
+    }
+    if !statuses.is_empty() {
+        let statuses: Vec<String> = statuses
+            .iter()
+            .map(|(status, count)| format!("{}: {}", status, count))
+            .collect();
+        out.push_str(&format!("By status: {}\n", statuses.join(", ")));
+    }
+
+    let mut endpoints = std::collections::BTreeMap::<&str, (usize, f64, usize)>::new();
+    for entry in entries {
+        if let Some(endpoint) = &entry.endpoint {
+            let (count, total_ms, errors) = endpoints.entry(endpoint).or_default();
+            *count += 1;
+            *total_ms += entry.millis.unwrap_or(0.0);
+            if entry.status.is_some_and(|s| s >= 400) {
+                *errors += 1;
+            }
+        }
+    }
+    if !endpoints.is_empty() {
+        out.push_str("By endpoint (count, average time, errors):\n");
+        for (endpoint, (count, total_ms, errors)) in &endpoints {
+            out.push_str(&format!(
+                "- {}: {}, {:.0}ms, {}\n",
+                endpoint,
+                count,
+                total_ms / *count as f64,
+                errors
+            ));
+        }
+    }
+
+    let mut timed: Vec<&Entry> = entries.iter().filter(|e| e.millis.is_some()).collect();
+    timed.sort_by(|a, b| {
+        b.millis
+            .partial_cmp(&a.millis)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    if !timed.is_empty() {
+        out.push_str("Slowest requests:\n");
+        for entry in timed.iter().take(SLOWEST) {
+            out.push_str(&format!("- {}\n", entry.line));
+        }
+    }
+    out
+}
+
+/// Splits the summary lines into pieces of at most CONTEXT_CHARS
+fn chunks(lines: &[String]) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in lines {
+        if !current.is_empty() && current.len() + line.len() >= CONTEXT_CHARS {
+            chunks.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Builds the system prompt for questions about the captures in `paths`
+pub async fn system_prompt(
+    client: &Client,
+    config: &OllamaConfig,
+    paths: &[String],
+) -> anyhow::Result<String> {
+    let mut sections = Vec::new();
+    for path in paths {
+        let entries = load(Path::new(path))?;
+        if entries.is_empty() {
+            return Err(anyhow!("{} contains no captured requests", path));
+        }
+        eprintln!("🔎 Loaded {} request(s) from {}", entries.len(), path);
+
+        let lines: Vec<String> = entries.iter().map(|e| e.line.clone()).collect();
+        let pieces = chunks(&lines);
+        let detail = if pieces.len() == 1 {
+            pieces.into_iter().next().unwrap_or_default()
+        } else {
+            let mut summaries = Vec::new();
+            for (i, piece) in pieces.iter().enumerate() {
+                eprintln!("📝 Summarizing part {}/{} of {}", i + 1, pieces.len(), path);
+                let mut messages = Vec::new();
+                let reply = chat_turn(
+                    client,
+                    config,
+                    &mut messages,
+                    &format!("{}\n\n{}", CHUNK_PROMPT, piece),
+                    Vec::new(),
+                    Render::Quiet,
+                )
+                .await?;
+                summaries.push(format!("Part {}:\n{}", i + 1, reply.content.trim()));
+            }
+            summaries.join("\n\n")
+        };
+        sections.push(format!(
+            "<capture path=\"{}\">\n{}\nRequests:\n{}\n</capture>",
+            path,
+            totals(&entries),
+            detail.trim_end()
+        ));
+    }
+
+    Ok(format!("{}\n\n{}", ANALYZE_PROMPT, sections.join("\n\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_forward_captures_are_read_as_json_lines() {
+        let path = std::env::temp_dir().join(format!("analyze-{}.jsonl", std::process::id()));
+        fs::write(
+            &path,
+            concat!(
+                r#"{"time":"2026-01-02T10:00:00.000Z","pod":"default/web","method":"GET","path":"/health","status":200,"duration_ms":3.2}"#,
+                "\n",
+                r#"{"time":"2026-01-02T10:00:01.000Z","pod":"default/db","query":"SELECT 1","result":"SELECT 1","duration_ms":0.8}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        let entries = load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].endpoint.as_deref(), Some("GET /health"));
+        assert_eq!(entries[0].status, Some(200));
+        assert_eq!(entries[0].millis, Some(3.2));
+        assert_eq!(entries[1].endpoint.as_deref(), Some("SELECT 1"));
+        assert_eq!(entries[1].millis, Some(0.8));
+    }
+}
@@ -0,0 +1,230 @@
+// Files attached as conversation context. Attachments are read from a file, a directory
+// (recursively, skipping hidden entries and symlinked directories) or a glob, wrapped in
+// delimited blocks and sent together with the next user message. Images go into the
+// message's `images` field instead.
+use crate::{OllamaConfig, Provider};
+use anyhow::{anyhow, Context};
+use base64::Engine;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Larger files are skipped; attach an excerpt instead
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+/// Upper bound for everything attached in one go, to stay inside the context window
+const MAX_TOTAL_BYTES: usize = 1024 * 1024;
+/// Ollama rejects requests much larger than this
+const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+/// How much of a file is inspected when deciding whether it is binary
+const SNIFF_BYTES: usize = 8 * 1024;
+/// Upper bound for the files a pattern may match, checked before any is read
+const MAX_FILES: usize = 10_000;
+
+pub struct Attachment {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// Reads every text file matched by `pattern` (a path, a directory or a glob).
+/// Files that are binary or too large are reported and skipped. `queued` bytes are
+/// already attached and count towards the total limit.
+pub fn collect(pattern: &str, queued: usize) -> anyhow::Result<Vec<Attachment>> {
+    collect_limited(pattern, Some(MAX_TOTAL_BYTES), queued)
+}
+
+/// Like `collect`, with a caller-chosen limit for the total size (None for no limit)
+pub fn collect_limited(
+    pattern: &str,
+    max_total: Option<usize>,
+    queued: usize,
+) -> anyhow::Result<Vec<Attachment>> {
+    let mut paths = Vec::new();
+    let path = Path::new(pattern);
+    if path.exists() {
+        walk(path, &mut paths)?;
+    } else {
+        for entry in glob::glob(pattern).with_context(|| format!("Invalid glob '{}'", pattern))? {
+            walk(&entry?, &mut paths)?;
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow!("Nothing matches '{}'", pattern));
+    }
+
+    let mut attachments = Vec::new();
+    let mut total = queued;
+    for path in paths {
+        let size = fs::metadata(&path)?.len();
+        if size > MAX_FILE_BYTES {
+            eprintln!(
+                "⚠️  Skipping {} ({} KB, limit is {} KB)",
+                path.display(),
+                size / 1024,
+                MAX_FILE_BYTES / 1024
+            );
+            continue;
+        }
+        let bytes =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let Some(content) = text(bytes) else {
+            eprintln!("⚠️  Skipping binary file {}", path.display());
+            continue;
+        };
+        total += content.len();
+        if let Some(max_total) = max_total.filter(|&max| total > max) {
+            eprintln!(
+                "⚠️  Stopping at {}: attachments are limited to {} KB in total",
+                path.display(),
+                max_total / 1024
+            );
+            break;
+        }
+        attachments.push(Attachment { path, content });
+    }
+    Ok(attachments)
+}
+
+fn walk(path: &Path, paths: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    if !path.is_dir() {
+        if paths.len() == MAX_FILES {
+            return Err(anyhow!(
+                "More than {} files match, attach a narrower path or glob",
+                MAX_FILES
+            ));
+        }
+        paths.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            !path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with('.'))
+        })
+        .collect();
+    entries.sort();
+    for entry in entries {
+        // Followed, a link to a directory can lead back to one of its ancestors
+        if entry.is_dir()
+            && fs::symlink_metadata(&entry).is_ok_and(|metadata| metadata.is_symlink())
+        {
+            continue;
+        }
+        walk(&entry, paths)?;
+    }
+    Ok(())
+}
+
+/// Returns the file as text, or None when it looks binary (NUL bytes or invalid UTF-8)
+fn text(bytes: Vec<u8>) -> Option<String> {
+    if bytes[..bytes.len().min(SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Wraps the attachments in delimited blocks the model can tell apart from the question
+pub fn render(attachments: &[Attachment]) -> String {
+    let mut out = String::from("The following files are attached as context:\n");
+    for attachment in attachments {
+        out.push_str(&format!(
+            "\n<file path=\"{}\">\n{}\n</file>\n",
+            attachment.path.display(),
+            attachment.content.trim_end()
+        ));
+    }
+    out
+}
+
+pub fn total_bytes(attachments: &[Attachment]) -> usize {
+    attachments.iter().map(|a| a.content.len()).sum()
+}
+
+/// Reads an image and base64-encodes it for the `images` field of an Ollama message
+pub fn image(config: &OllamaConfig, path: &str) -> anyhow::Result<String> {
+    if config.provider != Provider::Ollama {
+        return Err(anyhow!(
+            "Images are only supported with the Ollama provider (configured: {:?})",
+            config.provider
+        ));
+    }
+    let size = fs::metadata(path)
+        .with_context(|| format!("Failed to read {}", path))?
+        .len();
+    if size > MAX_IMAGE_BYTES {
+        return Err(anyhow!(
+            "{} is {} MB, images are limited to {} MB",
+            path,
+            size / (1024 * 1024),
+            MAX_IMAGE_BYTES / (1024 * 1024)
+        ));
+    }
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    if !is_image(&bytes) {
+        return Err(anyhow!("{} is not a PNG, JPEG, GIF or WebP image", path));
+    }
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// Checks the magic bytes of the formats vision models accept
+fn is_image(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"\x89PNG\r\n\x1a\n")
+        || bytes.starts_with(&[0xff, 0xd8, 0xff])
+        || bytes.starts_with(b"GIF8")
+        || (bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for one test
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ollama-attach-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_not_followed() {
+        let dir = scratch("symlink");
+        fs::create_dir(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("src/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("src/main.rs"), dir.join("linked.rs")).unwrap();
+
+        let attachments = collect(&dir.to_string_lossy(), 0).unwrap();
+        let paths: Vec<PathBuf> = attachments.into_iter().map(|a| a.path).collect();
+        assert_eq!(paths, vec![dir.join("linked.rs"), dir.join("src/main.rs")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn too_many_files_are_refused_before_reading() {
+        let dir = scratch("many");
+        let mut paths = vec![PathBuf::new(); MAX_FILES];
+        fs::write(dir.join("one.txt"), "one").unwrap();
+        assert!(walk(&dir, &mut paths).is_err());
+        paths.clear();
+        walk(&dir, &mut paths).unwrap();
+        assert_eq!(paths, vec![dir.join("one.txt")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_total_limit_counts_what_is_already_attached() {
+        let dir = scratch("total");
+        fs::write(dir.join("a.txt"), "a".repeat(1024)).unwrap();
+        fs::write(dir.join("b.txt"), "b".repeat(1024)).unwrap();
+        let pattern = dir.to_string_lossy();
+        assert_eq!(collect(&pattern, 0).unwrap().len(), 2);
+        assert_eq!(collect(&pattern, MAX_TOTAL_BYTES - 1024).unwrap().len(), 1);
+        assert!(collect(&pattern, MAX_TOTAL_BYTES).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
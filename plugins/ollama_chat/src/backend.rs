@@ -0,0 +1,427 @@
+// Chat transports. Every provider streams its reply as text lines (NDJSON for Ollama,
+// server-sent events for OpenAI-compatible servers and Anthropic), so a backend only
+// has to build the HTTP request and turn one line of the response into text.
+use crate::stats::Usage;
+use crate::{KeepAlive, Message, OllamaConfig, Provider};
+use anyhow::anyhow;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Default output limit for Anthropic, which requires one on every request
+const ANTHROPIC_MAX_TOKENS: u32 = 4096;
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// One parsed line of a (streamed) response
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub text: Option<String>,
+    pub done: bool,
+    /// Token counts, when the provider reports them on this line
+    pub usage: Option<Usage>,
+}
+
+pub trait ChatBackend: Send + Sync {
+    /// Name used in error messages
+    fn name(&self) -> &'static str;
+
+    /// Builds the chat request for the conversation so far
+    fn request(
+        &self,
+        client: &Client,
+        config: &OllamaConfig,
+        messages: &[Message],
+    ) -> RequestBuilder;
+
+    /// Parses one line of the response body. Returns None for lines that carry
+    /// nothing (keep-alives, event names, blank lines).
+    fn parse_line(&self, line: &str) -> anyhow::Result<Option<Chunk>>;
+}
+
+pub fn for_provider(provider: Provider) -> Box<dyn ChatBackend> {
+    match provider {
+        Provider::Ollama => Box::new(Ollama),
+        Provider::Openai => Box::new(OpenAi),
+        Provider::Anthropic => Box::new(Anthropic),
+    }
+}
+
+/// A message as sent over the wire: only what the APIs accept
+#[derive(Debug, Serialize)]
+struct WireMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+    /// Ollama only; the other backends never get images
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    images: &'a [String],
+}
+
+impl<'a> From<&'a Message> for WireMessage<'a> {
+    fn from(message: &'a Message) -> Self {
+        Self {
+            role: &message.role,
+            content: &message.content,
+            images: &message.images,
+        }
+    }
+}
+
+fn wire(messages: &[Message]) -> Vec<WireMessage<'_>> {
+    messages.iter().map(WireMessage::from).collect()
+}
+
+/// Strips the `data:` prefix of a server-sent event; other SSE fields are ignored
+fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim)
+}
+
+// --- Ollama ---------------------------------------------------------------------
+
+struct Ollama;
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    messages: Vec<WireMessage<'a>>,
+    stream: bool,
+    /// OllamaOptions merged with the [options] table
+    options: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a KeepAlive>,
+    /// "json" or a JSON Schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<i32>,
+    /// Ollama's name for max_tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repeat_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+}
+
+/// The dedicated settings, plus the entries of the [options] table they don't set
+fn ollama_options(config: &OllamaConfig) -> Value {
+    let mut options = json!(OllamaOptions {
+        temperature: config.temperature,
+        top_p: config.top_p,
+        top_k: config.top_k,
+        num_predict: config.max_tokens,
+        stop: &config.stop,
+        seed: config.seed,
+        repeat_penalty: config.repeat_penalty,
+        presence_penalty: config.presence_penalty,
+        frequency_penalty: config.frequency_penalty,
+    });
+    if let Value::Object(map) = &mut options {
+        for (key, value) in &config.options {
+            map.entry(key.as_str()).or_insert_with(|| value.clone());
+        }
+    }
+    options
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    message: Option<Message>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+    /// The final message carries the counters, durations are in nanoseconds
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+    eval_duration: Option<u64>,
+}
+
+impl ChatBackend for Ollama {
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    fn request(
+        &self,
+        client: &Client,
+        config: &OllamaConfig,
+        messages: &[Message],
+    ) -> RequestBuilder {
+        client
+            .post(format!("{}/api/chat", config.url))
+            .json(&OllamaRequest {
+                model: &config.model,
+                messages: wire(messages),
+                stream: config.stream.unwrap_or(true),
+                options: ollama_options(config),
+                keep_alive: config.keep_alive.as_ref(),
+                format: config.format.as_ref(),
+            })
+    }
+
+    fn parse_line(&self, line: &str) -> anyhow::Result<Option<Chunk>> {
+        // Skip invalid JSON lines
+        let Ok(response) = serde_json::from_str::<OllamaResponse>(line) else {
+            return Ok(None);
+        };
+        if let Some(error) = response.error {
+            return Err(anyhow!("Ollama API error: {}", error));
+        }
+        let usage = response.done.then(|| Usage {
+            prompt_tokens: response.prompt_eval_count,
+            completion_tokens: response.eval_count,
+            generation: response.eval_duration.map(Duration::from_nanos),
+        });
+        Ok(Some(Chunk {
+            text: response.message.map(|m| m.content),
+            done: response.done,
+            usage,
+        }))
+    }
+}
+
+// --- OpenAI-compatible ------------------------------------------------------------
+
+struct OpenAi;
+
+#[derive(Debug, Serialize)]
+struct OpenAiRequest<'a> {
+    model: &'a str,
+    messages: Vec<WireMessage<'a>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+}
+
+/// Maps the structured output setting onto OpenAI's response_format
+fn openai_response_format(format: &Value) -> Value {
+    if format.is_object() {
+        json!({
+            "type": "json_schema",
+            "json_schema": { "name": "response", "schema": format },
+        })
+    } else {
+        json!({ "type": "json_object" })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiResponse {
+    #[serde(default)]
+    choices: Vec<OpenAiChoice>,
+    error: Option<ApiError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiChoice {
+    /// Set on streamed chunks
+    delta: Option<OpenAiContent>,
+    /// Set on non-streamed responses
+    message: Option<OpenAiContent>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiContent {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+impl ChatBackend for OpenAi {
+    fn name(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    fn request(
+        &self,
+        client: &Client,
+        config: &OllamaConfig,
+        messages: &[Message],
+    ) -> RequestBuilder {
+        let request = client
+            .post(format!("{}/chat/completions", config.url))
+            .json(&OpenAiRequest {
+                model: &config.model,
+                messages: wire(messages),
+                stream: config.stream.unwrap_or(true),
+                temperature: config.temperature,
+                top_p: config.top_p,
+                max_tokens: config.max_tokens,
+                stop: &config.stop,
+                seed: config.seed,
+                presence_penalty: config.presence_penalty,
+                frequency_penalty: config.frequency_penalty,
+                response_format: config.format.as_ref().map(openai_response_format),
+            });
+        // Local OpenAI-compatible servers usually run without a key
+        match config.api_key() {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    fn parse_line(&self, line: &str) -> anyhow::Result<Option<Chunk>> {
+        let data = sse_data(line).unwrap_or(line);
+        if data == "[DONE]" {
+            return Ok(Some(Chunk {
+                done: true,
+                ..Chunk::default()
+            }));
+        }
+        let Ok(response) = serde_json::from_str::<OpenAiResponse>(data) else {
+            return Ok(None);
+        };
+        if let Some(error) = response.error {
+            return Err(anyhow!("OpenAI API error: {}", error.message));
+        }
+        let Some(choice) = response.choices.into_iter().next() else {
+            return Ok(None);
+        };
+        // Streams end with [DONE]; a full response is complete on its own
+        let done = choice.message.is_some() && choice.finish_reason.is_some();
+        Ok(Some(Chunk {
+            text: choice.delta.or(choice.message).and_then(|c| c.content),
+            done,
+            ..Chunk::default()
+        }))
+    }
+}
+
+// --- Anthropic --------------------------------------------------------------------
+
+struct Anthropic;
+
+#[derive(Debug, Serialize)]
+struct AnthropicRequest<'a> {
+    model: &'a str,
+    /// Anthropic takes the system prompt separately from the conversation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<&'a str>,
+    messages: Vec<WireMessage<'a>>,
+    max_tokens: u32,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<i32>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop_sequences: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    /// content_block_delta events
+    delta: Option<AnthropicText>,
+    /// Non-streamed responses
+    #[serde(default)]
+    content: Vec<AnthropicText>,
+    error: Option<ApiError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicText {
+    text: Option<String>,
+}
+
+impl ChatBackend for Anthropic {
+    fn name(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    fn request(
+        &self,
+        client: &Client,
+        config: &OllamaConfig,
+        messages: &[Message],
+    ) -> RequestBuilder {
+        let system = messages
+            .iter()
+            .find(|m| m.role == "system")
+            .map(|m| m.content.as_str());
+        let request = client
+            .post(format!("{}/v1/messages", config.url))
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&AnthropicRequest {
+                model: &config.model,
+                system,
+                messages: messages
+                    .iter()
+                    .filter(|m| m.role != "system")
+                    .map(WireMessage::from)
+                    .collect(),
+                max_tokens: config.max_tokens.unwrap_or(ANTHROPIC_MAX_TOKENS),
+                stream: config.stream.unwrap_or(true),
+                temperature: config.temperature,
+                top_p: config.top_p,
+                top_k: config.top_k,
+                stop_sequences: &config.stop,
+            });
+        match config.api_key() {
+            Some(key) => request.header("x-api-key", key),
+            None => request,
+        }
+    }
+
+    fn parse_line(&self, line: &str) -> anyhow::Result<Option<Chunk>> {
+        let data = sse_data(line).unwrap_or(line);
+        let Ok(event) = serde_json::from_str::<AnthropicEvent>(data) else {
+            return Ok(None);
+        };
+        match event.kind.as_str() {
+            "content_block_delta" => Ok(Some(Chunk {
+                text: event.delta.and_then(|d| d.text),
+                ..Chunk::default()
+            })),
+            "message_stop" => Ok(Some(Chunk {
+                done: true,
+                ..Chunk::default()
+            })),
+            "message" => Ok(Some(Chunk {
+                text: Some(event.content.into_iter().filter_map(|c| c.text).collect()),
+                done: true,
+                ..Chunk::default()
+            })),
+            "error" => Err(anyhow!(
+                "Anthropic API error: {}",
+                event.error.map(|e| e.message).unwrap_or_default()
+            )),
+            _ => Ok(None),
+        }
+    }
+}
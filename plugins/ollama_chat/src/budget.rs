@@ -0,0 +1,85 @@
+// Per-reply budgets. With max_response_secs or max_output_tokens set, a streamed reply
+// that runs past either limit is reported once, and with budget_action = "cancel" it is
+// stopped the same way Ctrl-C stops it, keeping what arrived so far.
+use crate::{tui, OllamaConfig, CANCEL};
+use serde::Deserialize;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    #[default]
+    Warn,
+    Cancel,
+}
+
+pub struct Budget {
+    deadline: Option<Instant>,
+    seconds: u64,
+    max_tokens: Option<u64>,
+    action: BudgetAction,
+    /// Streamed pieces so far; Ollama sends one token per piece, other providers a few
+    tokens: u64,
+    time_reported: bool,
+    tokens_reported: bool,
+}
+
+impl Budget {
+    pub fn new(config: &OllamaConfig, started: Instant) -> Self {
+        let seconds = config.max_response_secs.unwrap_or(0);
+        Self {
+            deadline: config
+                .max_response_secs
+                .map(|secs| started + Duration::from_secs(secs)),
+            seconds,
+            max_tokens: config.max_output_tokens,
+            action: config.budget_action.unwrap_or_default(),
+            tokens: 0,
+            time_reported: false,
+            tokens_reported: false,
+        }
+    }
+
+    /// Resolves when the time budget runs out; never, once that has been reported
+    pub async fn overtime(&self) {
+        match self.deadline {
+            Some(deadline) if !self.time_reported => {
+                tokio::time::sleep_until(deadline.into()).await
+            }
+            _ => std::future::pending().await,
+        }
+    }
+
+    fn exceeded(&self, what: String) {
+        match self.action {
+            BudgetAction::Warn => tui::warn(&format!("\n⏱️  {} (Ctrl+C stops it)", what)),
+            BudgetAction::Cancel => {
+                tui::warn(&format!("\n⏱️  {}, stopping it", what));
+                CANCEL.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub fn report_time(&mut self) {
+        self.time_reported = true;
+        self.exceeded(format!(
+            "The reply has taken more than {}s (max_response_secs)",
+            self.seconds
+        ));
+    }
+
+    /// Counts one streamed piece of text against the output budget
+    pub fn count(&mut self) {
+        self.tokens += 1;
+        if let Some(max) = self.max_tokens {
+            if self.tokens > max && !self.tokens_reported {
+                self.tokens_reported = true;
+                self.exceeded(format!(
+                    "The reply is longer than {} tokens (max_output_tokens)",
+                    max
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,341 @@
+// State of an interactive conversation: the effective configuration (which slash
+// commands may change at runtime), the message history and the active session name.
+use crate::{
+    attach, chat_turn, enforce_format, markdown, presets, rag, session, stats, tui, write, Message,
+    OllamaConfig, Render, Reply,
+};
+use reqwest::Client;
+
+/// How much of the first question and answer the model sees when titling a session
+const TITLE_EXCERPT_CHARS: usize = 1000;
+
+pub struct Chat {
+    pub client: Client,
+    pub config: OllamaConfig,
+    pub messages: Vec<Message>,
+    /// Name of the session the conversation is saved to after every reply
+    pub session: Option<String>,
+    /// Generated title of the session, see `save_untitled`
+    pub title: Option<String>,
+    pub render: Render,
+    /// Token and timing totals, printed when the chat ends
+    pub stats: stats::SessionStats,
+    /// Files waiting to be sent with the next message
+    pub attachments: Vec<attach::Attachment>,
+    /// Local index searched for excerpts on every message
+    pub rag: Option<rag::Index>,
+    /// Base64-encoded images waiting to be sent with the next message
+    pub images: Vec<String>,
+    /// File every reply is written to (--output)
+    pub output: Option<write::Target>,
+}
+
+impl Chat {
+    pub fn new(client: Client, config: OllamaConfig) -> Self {
+        let messages = initial_messages(&config);
+        Self {
+            client,
+            config,
+            messages,
+            session: None,
+            title: None,
+            render: Render::Decorated,
+            stats: stats::SessionStats::default(),
+            attachments: Vec::new(),
+            rag: None,
+            images: Vec::new(),
+            output: None,
+        }
+    }
+
+    /// Drops the conversation, keeping only the system prompt
+    pub fn reset(&mut self) {
+        self.messages = initial_messages(&self.config);
+    }
+
+    /// Replaces the system prompt for this and future turns
+    pub fn set_system_prompt(&mut self, prompt: Option<String>) {
+        self.messages.retain(|m| m.role != "system");
+        if let Some(prompt) = &prompt {
+            self.messages.insert(
+                0,
+                Message {
+                    role: "system".to_string(),
+                    content: prompt.clone(),
+                    images: Vec::new(),
+                    at: None,
+                },
+            );
+        }
+        self.config.system_prompt = prompt;
+    }
+
+    /// Switches to a preset: its model and parameters, and its system prompt for this
+    /// and future turns
+    pub fn apply_preset(&mut self, name: &str) -> anyhow::Result<()> {
+        let preset = presets::find(&self.config, name)?;
+        let mut config = self.config.clone();
+        preset.apply(&mut config);
+        let system_prompt = config.system_prompt.clone();
+        self.config = config;
+        self.set_system_prompt(system_prompt);
+        Ok(())
+    }
+
+    /// Queues the files matched by `pattern` for the next message, within the total
+    /// limit left by those already queued. Returns the number of files and their total
+    /// size in bytes.
+    pub fn attach(&mut self, pattern: &str) -> anyhow::Result<(usize, usize)> {
+        let attachments = attach::collect(pattern, attach::total_bytes(&self.attachments))?;
+        let added = (attachments.len(), attach::total_bytes(&attachments));
+        self.attachments.extend(attachments);
+        Ok(added)
+    }
+
+    /// Queues an image for the next message
+    pub fn attach_image(&mut self, path: &str) -> anyhow::Result<()> {
+        let image = attach::image(&self.config, path)?;
+        self.images.push(image);
+        Ok(())
+    }
+
+    /// Sends a user message, prints its statistics and saves the active session afterwards
+    pub async fn send(&mut self, input: &str) -> anyhow::Result<Reply> {
+        // Looked up by what was typed alone: attached files would swamp the question in
+        // the embedding and may not fit the embedding model's context
+        let excerpts = match &self.rag {
+            Some(index) => Some(index.retrieve(&self.client, &self.config, input).await?),
+            None => None,
+        };
+        let input = if self.attachments.is_empty() {
+            input.to_string()
+        } else {
+            format!("{}\n{}", attach::render(&self.attachments), input)
+        };
+        let prompt = match &excerpts {
+            Some(excerpts) => format!("{}\n{}", excerpts, input),
+            None => input.clone(),
+        };
+        let reply = chat_turn(
+            &self.client,
+            &self.config,
+            &mut self.messages,
+            &prompt,
+            self.images.clone(),
+            self.render,
+        )
+        .await?;
+        // Only drop the attachments once they made it into the conversation
+        self.attachments.clear();
+        self.images.clear();
+        // Retrieved excerpts are only needed for this answer; keeping them would grow
+        // every following request
+        if excerpts.is_some() {
+            let question = self.messages.len() - 2;
+            self.messages[question].content = input;
+        }
+        let reply = enforce_format(
+            &self.client,
+            &self.config,
+            &mut self.messages,
+            reply,
+            self.render,
+        )
+        .await?;
+        // The full-screen chat shows the statistics in its status bar
+        if self.render != Render::Tui {
+            reply.stats.print();
+        }
+        self.stats.add(&reply.stats);
+        if let Some(target) = &self.output {
+            match target.write(&reply.content) {
+                Ok(bytes) => tui::say(&format!("💾 {}", target.describe(bytes))),
+                Err(e) => tui::say(&format!("❌ {}", e)),
+            }
+        }
+        self.autosave();
+        Ok(reply)
+    }
+
+    /// Writes the last answer to a file, returning the bytes written
+    pub fn write_last(&self, target: &write::Target) -> anyhow::Result<usize> {
+        let answer = self
+            .messages
+            .iter()
+            .rfind(|m| m.role == "assistant")
+            .ok_or_else(|| anyhow::anyhow!("No answer to write yet"))?;
+        target.write(&answer.content)
+    }
+
+    /// Sends a message with some parameters overridden for this request only
+    pub async fn send_with(
+        &mut self,
+        overrides: &presets::Preset,
+        input: &str,
+    ) -> anyhow::Result<Reply> {
+        let configured = self.config.clone();
+        overrides.apply(&mut self.config);
+        let result = self.send(input).await;
+        self.config = configured;
+        result
+    }
+
+    /// Drops the last answer and sends the last user message again, optionally with a
+    /// different temperature for this one attempt
+    pub async fn retry(&mut self, temperature: Option<f32>) -> anyhow::Result<Reply> {
+        let last_user = self
+            .messages
+            .iter()
+            .rposition(|m| m.role == "user")
+            .ok_or_else(|| anyhow::anyhow!("Nothing to retry yet"))?;
+        let input = self.messages[last_user].content.clone();
+        let removed = self.messages.split_off(last_user);
+        // Send the same images again
+        self.images = removed[0].images.clone();
+
+        let configured = self.config.temperature;
+        if temperature.is_some() {
+            self.config.temperature = temperature;
+        }
+        let result = self.send(&input).await;
+        self.config.temperature = configured;
+
+        match result {
+            Ok(reply) => Ok(reply),
+            Err(e) => {
+                // Put the previous exchange back so a failed retry loses nothing
+                self.images.clear();
+                self.messages.extend(removed);
+                Err(e)
+            }
+        }
+    }
+
+    /// Drops the last user message and the answer to it. Returns the dropped message.
+    pub fn undo(&mut self) -> anyhow::Result<String> {
+        let last_user = self
+            .messages
+            .iter()
+            .rposition(|m| m.role == "user")
+            .ok_or_else(|| anyhow::anyhow!("Nothing to undo"))?;
+        let removed = self.messages.split_off(last_user);
+        self.autosave();
+        Ok(removed[0].content.clone())
+    }
+
+    /// Copies the nth (1-based, default last) code block of the last answer to the
+    /// clipboard. Returns the block number and its line count.
+    pub fn copy_code_block(&self, n: Option<usize>) -> anyhow::Result<(usize, usize)> {
+        let answer = self
+            .messages
+            .iter()
+            .rfind(|m| m.role == "assistant")
+            .ok_or_else(|| anyhow::anyhow!("No answer to copy from yet"))?;
+        let blocks = markdown::code_blocks(&answer.content);
+        if blocks.is_empty() {
+            return Err(anyhow::anyhow!("The last answer has no code blocks"));
+        }
+        let n = n.unwrap_or(blocks.len());
+        let block = n
+            .checked_sub(1)
+            .and_then(|i| blocks.get(i))
+            .ok_or_else(|| {
+                anyhow::anyhow!("No code block {}, the last answer has {}", n, blocks.len())
+            })?;
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(block.clone()))
+            .map_err(|e| anyhow::anyhow!("Clipboard unavailable: {}", e))?;
+        Ok((n, block.lines().count()))
+    }
+
+    pub fn save(&mut self, name: &str) -> anyhow::Result<()> {
+        let mut saved = session::Session::new(name, &self.config.model, self.messages.clone());
+        saved.title = self.title.clone();
+        session::save(&mut saved)?;
+        self.session = Some(name.to_string());
+        Ok(())
+    }
+
+    pub fn load(&mut self, name: &str) -> anyhow::Result<usize> {
+        let saved = session::load(name)?;
+        let count = saved.messages.len();
+        self.messages = saved.messages;
+        self.title = saved.title;
+        self.session = Some(name.to_string());
+        Ok(count)
+    }
+
+    /// Saves a conversation that has no session name yet under a name derived from a
+    /// title the model writes for the first exchange. Returns the name.
+    pub async fn save_untitled(&mut self) -> anyhow::Result<String> {
+        let excerpt = |role: &str| -> Option<String> {
+            let message = self.messages.iter().find(|m| m.role == role)?;
+            Some(message.content.chars().take(TITLE_EXCERPT_CHARS).collect())
+        };
+        let (Some(question), Some(answer)) = (excerpt("user"), excerpt("assistant")) else {
+            return Err(anyhow::anyhow!(
+                "Nothing to save yet; ask something first or use /save <name>"
+            ));
+        };
+
+        // A structured output format would turn the title into JSON
+        let config = OllamaConfig {
+            format: None,
+            ..self.config.clone()
+        };
+        let prompt = format!(
+            "Write a short title (at most six words) for a conversation that starts like \
+             this. Reply with the title only.\n\nUser: {}\n\nAssistant: {}",
+            question, answer
+        );
+        let title = match chat_turn(
+            &self.client,
+            &config,
+            &mut Vec::new(),
+            &prompt,
+            Vec::new(),
+            Render::Quiet,
+        )
+        .await
+        {
+            Ok(reply) => reply
+                .content
+                .lines()
+                .map(|line| line.trim().trim_matches(['"', '\'', '*', '#']).trim())
+                .find(|line| !line.is_empty())
+                .map(|line| line.chars().take(80).collect::<String>()),
+            Err(e) => {
+                tui::say(&format!("⚠️  Could not generate a title: {}", e));
+                None
+            }
+        };
+
+        let name = session::name_from_title(title.as_deref().unwrap_or(""));
+        self.title = title;
+        self.save(&name)?;
+        Ok(name)
+    }
+
+    /// Keeps the active session on disk up to date
+    fn autosave(&mut self) {
+        if let Some(name) = self.session.clone() {
+            if let Err(e) = self.save(&name) {
+                tui::say(&format!("⚠️  Could not save session '{}': {}", name, e));
+            }
+        }
+    }
+}
+
+pub fn initial_messages(config: &OllamaConfig) -> Vec<Message> {
+    let mut messages = Vec::new();
+    // Add system prompt if configured
+    if let Some(system_prompt) = &config.system_prompt {
+        messages.push(Message {
+            role: "system".to_string(),
+            content: system_prompt.clone(),
+            images: Vec::new(),
+            at: None,
+        });
+    }
+    messages
+}
@@ -0,0 +1,273 @@
+// Command generation for --mode cmd. Every answer is a single shell command with a short
+// explanation, requested as structured output; the command is shown, run only after
+// confirmation, and its output can be sent back so the model can suggest the next step.
+use crate::chat::Chat;
+use crate::{
+    chat, chat_turn, enforce_format, input, OllamaConfig, Render, CANCEL, IN_FLIGHT, PLUGIN_NAME,
+};
+use anyhow::Context;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::Command;
+
+const CMD_PROMPT: &str = "You turn requests into shell commands for the user's terminal. \
+    Answer every message with exactly one command in the `command` field (pipes and && \
+    are fine, but no scripts spanning several lines) and a short explanation of what it \
+    does and what to look for in its output in the `explanation` field. Prefer read-only \
+    commands when troubleshooting and point out in the explanation when a command changes \
+    anything. When the user sends the output of a command, use it to suggest the next \
+    step. When nothing is left to run, leave `command` empty and answer in `explanation`.";
+
+/// Command output sent back to the model is cut to its last this many characters
+const FEEDBACK_CHARS: usize = 8000;
+/// Bytes of each output kept while a command runs: more than FEEDBACK_CHARS characters of
+/// any width, so the feedback can tell whether it was cut
+const KEPT_BYTES: usize = FEEDBACK_CHARS * 4 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Chat,
+    Cmd,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chat" => Ok(Mode::Chat),
+            "cmd" => Ok(Mode::Cmd),
+            other => Err(format!("unknown mode '{}', expected chat or cmd", other)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Suggestion {
+    command: String,
+    explanation: String,
+}
+
+/// Turns the configuration into one for command generation: the instructions go in
+/// front of any system prompt and replies have to match the suggestion schema
+pub fn configure(config: &mut OllamaConfig) {
+    config.system_prompt = Some(match config.system_prompt.take() {
+        Some(prompt) => format!("{}\n\n{}", CMD_PROMPT, prompt),
+        None => CMD_PROMPT.to_string(),
+    });
+    config.format = Some(json!({
+        "type": "object",
+        "properties": {
+            "command": { "type": "string" },
+            "explanation": { "type": "string" }
+        },
+        "required": ["command", "explanation"]
+    }));
+}
+
+fn parse(reply: &str) -> anyhow::Result<Suggestion> {
+    let value: Value = serde_json::from_str(crate::structured::strip_fences(reply))?;
+    serde_json::from_value(value).context("The model did not answer with a command")
+}
+
+/// Asks for a single command and prints it for scripts: the command on stdout, the
+/// explanation on stderr. Nothing is run.
+pub async fn one_shot(client: &Client, config: &OllamaConfig, prompt: &str) -> anyhow::Result<()> {
+    let mut messages = chat::initial_messages(config);
+    let reply = chat_turn(
+        client,
+        config,
+        &mut messages,
+        prompt,
+        Vec::new(),
+        Render::Quiet,
+    )
+    .await?;
+    let reply = enforce_format(client, config, &mut messages, reply, Render::Quiet).await?;
+    let suggestion = parse(&reply.content)?;
+    eprintln!("💡 {}", suggestion.explanation.trim());
+    if !suggestion.command.trim().is_empty() {
+        println!("{}", suggestion.command.trim());
+    }
+    Ok(())
+}
+
+/// Asks a yes/no question on the terminal
+fn confirm(question: &str, default: bool) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+    print!("{} {} ", question, if default { "[Y/n]" } else { "[y/N]" });
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    match answer.trim() {
+        "" => default,
+        answer => matches!(answer, "y" | "Y" | "yes"),
+    }
+}
+
+/// Copies a command's output to the terminal as it comes, returning the end of it
+async fn tee(mut from: impl AsyncRead + Unpin, mut to: impl AsyncWrite + Unpin) -> Vec<u8> {
+    let mut kept = Vec::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let n = match from.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let _ = to.write_all(&buffer[..n]).await;
+        let _ = to.flush().await;
+        kept.extend_from_slice(&buffer[..n]);
+        if kept.len() > 2 * KEPT_BYTES {
+            kept.drain(..kept.len() - KEPT_BYTES);
+        }
+    }
+    kept
+}
+
+/// Runs the command through the shell, streaming its output to the terminal, and
+/// describes the result for the model
+async fn run_command(command: &str) -> anyhow::Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    plugin_api::audit::record(PLUGIN_NAME, "command_executed", &[("command", command)]);
+    let mut child = shell
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", command))?;
+    let stdout = child.stdout.take().context("No stdout")?;
+    let stderr = child.stderr.take().context("No stderr")?;
+    let (stdout, stderr, status) = tokio::join!(
+        tee(stdout, tokio::io::stdout()),
+        tee(stderr, tokio::io::stderr()),
+        child.wait()
+    );
+    let status = status.with_context(|| format!("Failed to run {}", command))?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    let stderr = String::from_utf8_lossy(&stderr);
+    let success = status.success();
+    let status = match status.code() {
+        Some(code) => format!("exited with status {}", code),
+        None => "was killed by a signal".to_string(),
+    };
+    if success {
+        println!("✅ The command {}\n", status);
+    } else {
+        println!("⚠️  The command {}\n", status);
+    }
+
+    let mut feedback = format!("I ran `{}`. It {}.", command, status);
+    for (name, text) in [("stdout", stdout), ("stderr", stderr)] {
+        let text = text.trim_end();
+        if text.is_empty() {
+            feedback.push_str(&format!("\n{}: (empty)", name));
+            continue;
+        }
+        let chars = text.chars().count();
+        let tail: String = text
+            .chars()
+            .skip(chars.saturating_sub(FEEDBACK_CHARS))
+            .collect();
+        let cut = if chars > FEEDBACK_CHARS {
+            " (only the end)"
+        } else {
+            ""
+        };
+        feedback.push_str(&format!("\n{}{}:\n```\n{}\n```", name, cut, tail));
+    }
+    Ok(feedback)
+}
+
+/// Suggests, confirms and runs commands for one request until the model has nothing
+/// left to run or the user stops
+async fn troubleshoot(chat: &mut Chat, request: &str) -> anyhow::Result<()> {
+    let mut message = request.to_string();
+    loop {
+        let reply = chat.send(&message).await?;
+        let suggestion = parse(&reply.content)?;
+        let command = suggestion.command.trim();
+        println!("💡 {}", suggestion.explanation.trim());
+        if command.is_empty() {
+            println!();
+            return Ok(());
+        }
+        println!("   $ {}", command);
+        if !confirm("▶️  Run it?", false) {
+            println!("⏭️  Not run\n");
+            return Ok(());
+        }
+        let feedback = run_command(command).await?;
+        if !confirm("↩️  Send the output back?", true) {
+            return Ok(());
+        }
+        message = feedback;
+    }
+}
+
+/// Reads requests until the user leaves; `clear` starts over
+pub async fn run_loop(mut chat: Chat) -> anyhow::Result<()> {
+    // The suggestion is printed once it has been checked, not streamed as JSON
+    chat.render = Render::Quiet;
+    println!(
+        "🛠️  Command mode with {}: describe what you want to do",
+        chat.config.model
+    );
+    println!("💬 Every command is shown before it runs (clear to start over, exit to quit)\n");
+
+    // Ctrl+C stops a reply; a running command gets the signal itself
+    ctrlc::set_handler(|| {
+        if IN_FLIGHT.load(Ordering::SeqCst) {
+            CANCEL.store(true, Ordering::SeqCst);
+        }
+    })?;
+
+    let mut editor = input::LineEditor::new(&chat.config)?;
+    loop {
+        match editor.read("🧑 ")? {
+            input::Input::Eof | input::Input::Interrupted => break,
+            input::Input::Line(input) => {
+                let input = input.trim();
+                if input.is_empty() {
+                    continue;
+                }
+                if input.eq_ignore_ascii_case("clear") || input == "/clear" {
+                    chat.reset();
+                    println!("🧹 Conversation cleared!\n");
+                    continue;
+                }
+                if ["exit", "quit", "/exit", "/quit"]
+                    .iter()
+                    .any(|cmd| input.eq_ignore_ascii_case(cmd))
+                {
+                    break;
+                }
+                if let Err(e) = troubleshoot(&mut chat, input).await {
+                    println!("❌ Error: {}\n", e);
+                }
+            }
+        }
+    }
+    chat.stats.print_summary();
+    println!("👋 Command mode ended.");
+    Ok(())
+}
@@ -0,0 +1,345 @@
+// In-chat slash commands. Input starting with '/' is parsed here before anything is
+// sent to the model, so runtime configuration changes don't require a restart.
+use crate::chat::Chat;
+use crate::{export, presets, search, structured, write};
+
+pub enum SlashCommand {
+    Help,
+    Clear,
+    Exit,
+    Model(Option<String>),
+    System(Option<String>),
+    Temp(Option<f32>),
+    Save(Option<String>),
+    Load(String),
+    Search(String),
+    Retry(Option<f32>),
+    Undo,
+    Attach(String),
+    Image(String),
+    Preset(Option<String>),
+    Export(String),
+    Copy(Option<usize>),
+    Write(write::Target),
+    Format(Option<String>),
+    /// Parameter overrides for a single message, and the message
+    With(presets::Preset, String),
+}
+
+/// What the chat loop should do after a command ran
+pub enum Flow {
+    Continue,
+    Exit,
+}
+
+const HELP: &[(&str, &str)] = &[
+    ("/help", "Show this help"),
+    ("/clear", "Reset the conversation (keeps the system prompt)"),
+    ("/model [name]", "Show or switch the model"),
+    (
+        "/system [prompt]",
+        "Show or replace the system prompt ('/system -' removes it)",
+    ),
+    ("/temp [value]", "Show or set the temperature"),
+    ("/preset [name]", "List presets or switch to one"),
+    (
+        "/format [json|file|off]",
+        "Show or set structured output (JSON, or a JSON Schema file)",
+    ),
+    (
+        "/save [name]",
+        "Save the conversation (defaults to the active session or a generated name)",
+    ),
+    ("/load <name>", "Load a saved conversation"),
+    (
+        "/search <query>",
+        "Find related questions and answers in saved conversations",
+    ),
+    (
+        "/export <file>",
+        "Write the conversation to a .md or .json file",
+    ),
+    (
+        "/with k=v.. <msg>",
+        "Send one message with other parameters (temp, top_p, top_k, max_tokens, model)",
+    ),
+    (
+        "/retry [temp]",
+        "Send the last message again, optionally at another temperature",
+    ),
+    ("/undo", "Drop the last message and its answer"),
+    (
+        "/copy [n]",
+        "Copy the nth (default: last) code block of the last answer",
+    ),
+    (
+        "/write <file>",
+        "Write the last answer to a file (--append, --code for code blocks only)",
+    ),
+    (
+        "/attach <path>",
+        "Attach a file, directory or glob to the next message",
+    ),
+    (
+        "/image <path>",
+        "Attach an image to the next message (vision models)",
+    ),
+    ("/exit", "Leave the chat"),
+];
+
+fn optional(arg: &str) -> Option<String> {
+    (!arg.is_empty()).then(|| arg.to_string())
+}
+
+/// Parses the `key=value` overrides at the start of a /with argument, returning them
+/// and the message that follows
+fn parse_with(arg: &str) -> Result<(presets::Preset, String), String> {
+    let mut overrides = presets::Preset::default();
+    let mut rest = arg;
+    loop {
+        let (token, remainder) = match rest.split_once(char::is_whitespace) {
+            Some((token, remainder)) => (token, remainder.trim_start()),
+            None => (rest, ""),
+        };
+        let Some((key, value)) = token.split_once('=') else {
+            break;
+        };
+        let invalid = || format!("Invalid value '{}' for {}", value, key);
+        match key {
+            "temp" | "temperature" => {
+                overrides.temperature = Some(value.parse().map_err(|_| invalid())?)
+            }
+            "top_p" => overrides.top_p = Some(value.parse().map_err(|_| invalid())?),
+            "top_k" => overrides.top_k = Some(value.parse().map_err(|_| invalid())?),
+            "max_tokens" => overrides.max_tokens = Some(value.parse().map_err(|_| invalid())?),
+            "model" => overrides.model = Some(value.to_string()),
+            _ => {
+                return Err(format!(
+                    "Unknown parameter '{}' (use temp, top_p, top_k, max_tokens or model)",
+                    key
+                ))
+            }
+        }
+        rest = remainder;
+    }
+    if rest.is_empty() {
+        return Err("Usage: /with temp=0.1 top_p=0.5 <message>".to_string());
+    }
+    Ok((overrides, rest.to_string()))
+}
+
+/// Parses a slash command. Returns None when the input is a regular message.
+pub fn parse(input: &str) -> Option<Result<SlashCommand, String>> {
+    let rest = input.strip_prefix('/')?;
+    let (name, arg) = match rest.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (rest, ""),
+    };
+
+    let command = match name.to_lowercase().as_str() {
+        "help" | "?" => Ok(SlashCommand::Help),
+        "clear" => Ok(SlashCommand::Clear),
+        "exit" | "quit" => Ok(SlashCommand::Exit),
+        "model" => Ok(SlashCommand::Model(optional(arg))),
+        "system" => Ok(SlashCommand::System(optional(arg))),
+        "temp" | "temperature" => match arg {
+            "" => Ok(SlashCommand::Temp(None)),
+            value => value
+                .parse::<f32>()
+                .map(|t| SlashCommand::Temp(Some(t)))
+                .map_err(|_| format!("Invalid temperature '{}'", value)),
+        },
+        "preset" => Ok(SlashCommand::Preset(optional(arg))),
+        "format" => Ok(SlashCommand::Format(optional(arg))),
+        "save" => Ok(SlashCommand::Save(optional(arg))),
+        "load" => match arg {
+            "" => Err("Usage: /load <name>".to_string()),
+            name => Ok(SlashCommand::Load(name.to_string())),
+        },
+        "search" => match arg {
+            "" => Err("Usage: /search <query>".to_string()),
+            query => Ok(SlashCommand::Search(query.to_string())),
+        },
+        "export" => match arg {
+            "" => Err("Usage: /export <file.md|file.json>".to_string()),
+            path => Ok(SlashCommand::Export(path.to_string())),
+        },
+        "retry" => match arg {
+            "" => Ok(SlashCommand::Retry(None)),
+            value => value
+                .parse::<f32>()
+                .map(|t| SlashCommand::Retry(Some(t)))
+                .map_err(|_| format!("Invalid temperature '{}'", value)),
+        },
+        "with" => {
+            parse_with(arg).map(|(overrides, message)| SlashCommand::With(overrides, message))
+        }
+        "undo" => Ok(SlashCommand::Undo),
+        "copy" => match arg {
+            "" => Ok(SlashCommand::Copy(None)),
+            value => value
+                .parse::<usize>()
+                .map(|n| SlashCommand::Copy(Some(n)))
+                .map_err(|_| format!("Invalid code block number '{}'", value)),
+        },
+        "write" => write::Target::parse(arg).map(SlashCommand::Write),
+        "attach" => match arg {
+            "" => Err("Usage: /attach <path>".to_string()),
+            path => Ok(SlashCommand::Attach(path.to_string())),
+        },
+        "image" => match arg {
+            "" => Err("Usage: /image <path>".to_string()),
+            path => Ok(SlashCommand::Image(path.to_string())),
+        },
+        other => Err(format!("Unknown command '/{}', try /help", other)),
+    };
+    Some(command)
+}
+
+pub fn print_help() {
+    println!("📖 Commands:");
+    for (usage, description) in HELP {
+        println!("   {:<18} {}", usage, description);
+    }
+    println!();
+}
+
+pub async fn execute(chat: &mut Chat, command: SlashCommand) -> Flow {
+    match command {
+        SlashCommand::Help => print_help(),
+        SlashCommand::Clear => {
+            chat.reset();
+            println!("🧹 Conversation cleared!\n");
+        }
+        SlashCommand::Exit => return Flow::Exit,
+        SlashCommand::Model(None) => println!("🤖 Model: {}\n", chat.config.model),
+        SlashCommand::Model(Some(model)) => {
+            println!("🤖 Switched model: {} → {}\n", chat.config.model, model);
+            chat.config.model = model;
+        }
+        SlashCommand::System(None) => match &chat.config.system_prompt {
+            Some(prompt) => println!("📝 System prompt: {}\n", prompt),
+            None => println!("📝 No system prompt\n"),
+        },
+        SlashCommand::System(Some(prompt)) if prompt == "-" => {
+            chat.set_system_prompt(None);
+            println!("📝 System prompt removed\n");
+        }
+        SlashCommand::System(Some(prompt)) => {
+            chat.set_system_prompt(Some(prompt));
+            println!("📝 System prompt updated\n");
+        }
+        SlashCommand::Temp(None) => match chat.config.temperature {
+            Some(temperature) => println!("🌡️  Temperature: {}\n", temperature),
+            None => println!("🌡️  Temperature: model default\n"),
+        },
+        SlashCommand::Temp(Some(temperature)) => {
+            chat.config.temperature = Some(temperature);
+            println!("🌡️  Temperature set to {}\n", temperature);
+        }
+        SlashCommand::Preset(None) => {
+            if let Err(e) = presets::print_presets(&chat.config) {
+                println!("❌ {}\n", e);
+            }
+        }
+        SlashCommand::Preset(Some(name)) => match chat.apply_preset(&name) {
+            Ok(()) => println!(
+                "🎭 Switched to preset '{}' (model: {})\n",
+                name, chat.config.model
+            ),
+            Err(e) => println!("❌ {}\n", e),
+        },
+        SlashCommand::Format(None) => match &chat.config.format {
+            Some(format) => println!("🧾 Output format: {}\n", structured::describe(format)),
+            None => println!("🧾 Output format: free text\n"),
+        },
+        SlashCommand::Format(Some(arg)) if arg == "off" => {
+            chat.config.format = None;
+            println!("🧾 Structured output disabled\n");
+        }
+        SlashCommand::Format(Some(arg)) => match structured::parse_format(&arg) {
+            Ok(format) => {
+                println!("🧾 Replies must now be {}\n", structured::describe(&format));
+                chat.config.format = Some(format);
+            }
+            Err(e) => println!("❌ {}\n", e),
+        },
+        SlashCommand::Copy(n) => match chat.copy_code_block(n) {
+            Ok((n, lines)) => println!("📋 Copied code block {} ({} lines)\n", n, lines),
+            Err(e) => println!("❌ {}\n", e),
+        },
+        SlashCommand::Write(target) => match chat.write_last(&target) {
+            Ok(bytes) => println!("💾 {}\n", target.describe(bytes)),
+            Err(e) => println!("❌ {}\n", e),
+        },
+        SlashCommand::Export(path) => match export::export(chat, std::path::Path::new(&path)) {
+            Ok(()) => println!("📤 Conversation exported to {}\n", path),
+            Err(e) => println!("❌ Export failed: {}\n", e),
+        },
+        SlashCommand::Save(name) => match name.or_else(|| chat.session.clone()) {
+            Some(name) => match chat.save(&name) {
+                Ok(()) => println!("💾 Saved session '{}'\n", name),
+                Err(e) => println!("❌ Could not save session: {}\n", e),
+            },
+            None => match chat.save_untitled().await {
+                Ok(name) => println!(
+                    "💾 Saved session '{}'{}\n",
+                    name,
+                    chat.title
+                        .as_deref()
+                        .map(|title| format!(" ({})", title))
+                        .unwrap_or_default()
+                ),
+                Err(e) => println!("❌ Could not save session: {}\n", e),
+            },
+        },
+        SlashCommand::Load(name) => match chat.load(&name) {
+            Ok(count) => println!("📂 Loaded session '{}' ({} messages)\n", name, count),
+            Err(e) => println!("❌ {}\n", e),
+        },
+        SlashCommand::Search(query) => {
+            if let Err(e) = search::search(&chat.client, &chat.config, &query).await {
+                println!("❌ Search failed: {}\n", e);
+            }
+        }
+        SlashCommand::Retry(temperature) => match chat.retry(temperature).await {
+            Ok(_) => println!(),
+            Err(e) => println!("❌ Error: {}\n", e),
+        },
+        SlashCommand::With(overrides, message) => {
+            match chat.send_with(&overrides, &message).await {
+                Ok(_) => println!(),
+                Err(e) => println!("❌ Error: {}\n", e),
+            }
+        }
+        SlashCommand::Undo => match chat.undo() {
+            Ok(message) => {
+                let preview: String = message
+                    .lines()
+                    .next()
+                    .unwrap_or("")
+                    .chars()
+                    .take(60)
+                    .collect();
+                println!("↩️  Removed: {}\n", preview);
+            }
+            Err(e) => println!("❌ {}\n", e),
+        },
+        SlashCommand::Attach(path) => match chat.attach(&path) {
+            Ok((files, bytes)) => println!(
+                "📎 Attached {} file(s), {} KB; they are sent with your next message\n",
+                files,
+                bytes.div_ceil(1024)
+            ),
+            Err(e) => println!("❌ {}\n", e),
+        },
+        SlashCommand::Image(path) => match chat.attach_image(&path) {
+            Ok(()) => println!(
+                "🖼️  Attached image {}; it is sent with your next message\n",
+                path
+            ),
+            Err(e) => println!("❌ {}\n", e),
+        },
+    }
+    Flow::Continue
+}
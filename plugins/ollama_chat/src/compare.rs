@@ -0,0 +1,344 @@
+// Side-by-side model comparison for --compare. Every prompt goes to all models at once,
+// each model keeping its own history. In the blocks layout the first model's reply is
+// streamed as usual and the others follow as labeled blocks once it is done. In the
+// columns layout every reply is streamed into its own column, fed through a channel by
+// `forward`; the columns show the latest lines of each reply while they arrive and are
+// printed in full once all are done.
+use crate::{chat, chat_turn, input, OllamaConfig, Output, Render, Reply};
+use crossterm::cursor::{MoveToColumn, MoveUp};
+use crossterm::execute;
+use crossterm::terminal::{Clear, ClearType};
+use futures::future::join_all;
+use reqwest::Client;
+use std::future::Future;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Blocks,
+    Columns,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blocks" => Ok(Layout::Blocks),
+            "columns" => Ok(Layout::Columns),
+            other => Err(format!(
+                "unknown layout '{}', expected blocks or columns",
+                other
+            )),
+        }
+    }
+}
+
+/// Narrower terminals fall back to blocks, columns would be unreadable
+const MIN_COLUMN_WIDTH: usize = 30;
+/// How often the columns are redrawn while replies stream in
+const REDRAW: Duration = Duration::from_millis(100);
+
+/// Where the columns' streamed reply text goes while a prompt is out, with its column
+static COLUMNS: Mutex<Option<UnboundedSender<(usize, String)>>> = Mutex::new(None);
+
+/// Called by the reply output for every streamed piece of a column's text
+pub(crate) fn forward(column: usize, text: &str) {
+    if let Some(sink) = COLUMNS.lock().expect("columns lock").as_ref() {
+        let _ = sink.send((column, text.to_string()));
+    }
+}
+
+pub struct Comparison {
+    client: Client,
+    configs: Vec<OllamaConfig>,
+    histories: Vec<Vec<crate::Message>>,
+    layout: Layout,
+    render: Render,
+}
+
+impl Comparison {
+    pub fn new(
+        client: Client,
+        config: &OllamaConfig,
+        models: &[String],
+        layout: Layout,
+        render: Render,
+    ) -> Self {
+        let configs: Vec<OllamaConfig> = models
+            .iter()
+            .map(|model| OllamaConfig {
+                model: model.clone(),
+                ..config.clone()
+            })
+            .collect();
+        let histories = configs.iter().map(chat::initial_messages).collect();
+        Self {
+            client,
+            configs,
+            histories,
+            layout,
+            render,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.histories = self.configs.iter().map(chat::initial_messages).collect();
+    }
+
+    fn column_width(&self) -> Option<usize> {
+        let (width, _) = crossterm::terminal::size().ok()?;
+        let columns = self.configs.len();
+        let width = (width as usize).saturating_sub(3 * (columns - 1)) / columns;
+        (width >= MIN_COLUMN_WIDTH).then_some(width)
+    }
+
+    /// Sends `input` to every model and prints the replies
+    pub async fn ask(&mut self, input: &str) {
+        let column_width = match self.layout {
+            Layout::Columns => self.column_width(),
+            Layout::Blocks => None,
+        };
+        // Only one reply can be streamed to the terminal in blocks, the first model's
+        let streamed = column_width.is_none() && self.render != Render::Quiet;
+        // Columns are redrawn in place, which needs a terminal
+        let live = column_width.is_some() && io::stdout().is_terminal();
+        if streamed {
+            println!("━━ {} ━━", self.configs[0].model);
+        }
+
+        let client = &self.client;
+        let render = self.render;
+        let replies = join_all(
+            self.configs
+                .iter()
+                .zip(self.histories.iter_mut())
+                .enumerate()
+                .map(|(i, (config, messages))| async move {
+                    let render = if i == 0 && streamed {
+                        render
+                    } else if live {
+                        Render::Column(i)
+                    } else {
+                        Render::Quiet
+                    };
+                    chat_turn(client, config, messages, input, Vec::new(), render).await
+                }),
+        );
+        let replies = match column_width.filter(|_| live) {
+            Some(width) => stream_columns(&self.configs, width, replies).await,
+            None => replies.await,
+        };
+
+        match column_width {
+            Some(width) => self.print_columns(&replies, width),
+            None => {
+                for (i, reply) in replies.iter().enumerate() {
+                    let model = &self.configs[i].model;
+                    if !(i == 0 && streamed) {
+                        println!("━━ {} ━━", model);
+                    }
+                    match reply {
+                        Ok(reply) => {
+                            if !(i == 0 && streamed) {
+                                let mut out = Output::new(self.render);
+                                let printed = out.write(&reply.content).and_then(|_| out.finish());
+                                if let Err(e) = printed {
+                                    println!("❌ Error: {}", e);
+                                }
+                            }
+                            reply.stats.print();
+                        }
+                        Err(e) => println!("❌ Error: {}", e),
+                    }
+                    println!();
+                }
+            }
+        }
+    }
+
+    fn print_columns(&self, replies: &[anyhow::Result<Reply>], width: usize) {
+        let columns: Vec<Vec<String>> = replies
+            .iter()
+            .zip(&self.configs)
+            .map(|(reply, config)| {
+                let mut lines = wrap(&config.model, width);
+                lines.push("─".repeat(width));
+                match reply {
+                    Ok(reply) => {
+                        lines.extend(wrap(reply.content.trim(), width));
+                        lines.push(String::new());
+                        lines.extend(wrap(&summary(reply), width));
+                    }
+                    Err(e) => lines.extend(wrap(&format!("❌ Error: {}", e), width)),
+                }
+                lines
+            })
+            .collect();
+
+        print_rows(&columns, width);
+        println!();
+    }
+}
+
+/// Prints the columns' lines next to each other, returning the number of rows
+fn print_rows(columns: &[Vec<String>], width: usize) -> usize {
+    let rows = columns.iter().map(Vec::len).max().unwrap_or(0);
+    for row in 0..rows {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|lines| {
+                let cell = lines.get(row).map(String::as_str).unwrap_or("");
+                let padding = width.saturating_sub(cell.chars().count());
+                format!("{}{}", cell, " ".repeat(padding))
+            })
+            .collect();
+        println!("{}", cells.join(" │ ").trim_end());
+    }
+    rows
+}
+
+/// Erases the last `rows` rows printed
+fn erase(rows: usize) -> io::Result<()> {
+    if rows == 0 {
+        return Ok(());
+    }
+    execute!(
+        io::stdout(),
+        MoveUp(rows as u16),
+        MoveToColumn(0),
+        Clear(ClearType::FromCursorDown)
+    )
+}
+
+/// Redraws the replies streamed so far, each column showing the latest lines that fit
+/// the terminal, and returns the number of rows drawn
+fn draw_columns(configs: &[OllamaConfig], texts: &[String], width: usize, drawn: usize) -> usize {
+    // Rows scrolled out of the terminal can't be erased again
+    let height = crossterm::terminal::size().map_or(24, |(_, height)| height as usize);
+    let columns: Vec<Vec<String>> = configs
+        .iter()
+        .zip(texts)
+        .map(|(config, text)| {
+            let mut lines = wrap(&config.model, width);
+            lines.push("─".repeat(width));
+            let body = height.saturating_sub(lines.len() + 1).max(1);
+            let text = wrap(if text.is_empty() { "…" } else { text.trim() }, width);
+            lines.extend_from_slice(&text[text.len().saturating_sub(body)..]);
+            lines
+        })
+        .collect();
+    if erase(drawn).is_err() {
+        return drawn;
+    }
+    let rows = print_rows(&columns, width);
+    let _ = io::stdout().flush();
+    rows
+}
+
+/// Shows the replies in columns as they stream in, until all have arrived
+async fn stream_columns(
+    configs: &[OllamaConfig],
+    width: usize,
+    replies: impl Future<Output = Vec<anyhow::Result<Reply>>>,
+) -> Vec<anyhow::Result<Reply>> {
+    let (tx, mut rx) = unbounded_channel();
+    *COLUMNS.lock().expect("columns lock") = Some(tx);
+
+    let mut texts = vec![String::new(); configs.len()];
+    let mut drawn = 0;
+    let mut tick = tokio::time::interval(REDRAW);
+    tokio::pin!(replies);
+    let replies = loop {
+        tokio::select! {
+            replies = &mut replies => break replies,
+            Some((column, text)) = rx.recv() => texts[column].push_str(&text),
+            _ = tick.tick() => drawn = draw_columns(configs, &texts, width, drawn),
+        }
+    };
+    *COLUMNS.lock().expect("columns lock") = None;
+    // Printed again in full, with the statistics
+    let _ = erase(drawn);
+    replies
+}
+
+/// The stats line in a form that fits a column
+fn summary(reply: &Reply) -> String {
+    let stats = &reply.stats;
+    let mut parts = Vec::new();
+    if let Some(tokens) = stats.usage.completion_tokens {
+        parts.push(format!("{} out", tokens));
+    }
+    if let Some(rate) = stats.tokens_per_second() {
+        parts.push(format!("{:.1} tok/s", rate));
+    }
+    parts.push(format!("{:.1}s", stats.latency.as_secs_f64()));
+    format!("📊 {}", parts.join(" · "))
+}
+
+/// Wraps text at word boundaries (or mid-word for words longer than a line)
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let needed = if line.is_empty() { 0 } else { 1 } + word.chars().count();
+            if !line.is_empty() && line.chars().count() + needed > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            for c in word.chars() {
+                if line.chars().count() == width {
+                    lines.push(std::mem::take(&mut line));
+                }
+                line.push(c);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Prompts and compares until the user leaves; `clear` starts over for all models
+pub async fn run_loop(mut comparison: Comparison) -> anyhow::Result<()> {
+    let models: Vec<&str> = comparison
+        .configs
+        .iter()
+        .map(|c| c.model.as_str())
+        .collect();
+    println!("⚖️  Comparing {}", models.join(" vs "));
+    println!("💬 Type your messages (clear to start over, exit to quit)\n");
+
+    let mut editor = input::LineEditor::new(&comparison.configs[0])?;
+    loop {
+        match editor.read("🧑 ")? {
+            input::Input::Eof | input::Input::Interrupted => break,
+            input::Input::Line(input) => {
+                let input = input.trim();
+                if input.is_empty() {
+                    continue;
+                }
+                if input.eq_ignore_ascii_case("clear") || input == "/clear" {
+                    comparison.reset();
+                    println!("🧹 Conversations cleared!\n");
+                    continue;
+                }
+                if ["exit", "quit", "/exit", "/quit"]
+                    .iter()
+                    .any(|cmd| input.eq_ignore_ascii_case(cmd))
+                {
+                    break;
+                }
+                comparison.ask(input).await;
+            }
+        }
+    }
+    println!("👋 Comparison ended.");
+    Ok(())
+}
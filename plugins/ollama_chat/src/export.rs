@@ -0,0 +1,142 @@
+// Conversation export for documentation and bug reports. The format follows the file
+// extension: .md for a readable transcript, .json for tooling.
+use crate::chat::Chat;
+use anyhow::anyhow;
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Serialize)]
+struct Export<'a> {
+    exported_at: DateTime<Utc>,
+    session: Option<&'a str>,
+    provider: String,
+    url: &'a str,
+    model: &'a str,
+    parameters: Parameters,
+    messages: Vec<ExportedMessage<'a>>,
+}
+
+#[derive(Serialize)]
+struct Parameters {
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    top_k: Option<i32>,
+    max_tokens: Option<u32>,
+    seed: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct ExportedMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+    timestamp: Option<DateTime<Utc>>,
+    /// Image data is left out, only the number of images is kept
+    #[serde(skip_serializing_if = "is_zero")]
+    images: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+fn snapshot(chat: &Chat) -> Export<'_> {
+    let config = &chat.config;
+    Export {
+        exported_at: Utc::now(),
+        session: chat.session.as_deref(),
+        provider: format!("{:?}", config.provider).to_lowercase(),
+        url: &config.url,
+        model: &config.model,
+        parameters: Parameters {
+            temperature: config.temperature,
+            top_p: config.top_p,
+            top_k: config.top_k,
+            max_tokens: config.max_tokens,
+            seed: config.seed,
+        },
+        messages: chat
+            .messages
+            .iter()
+            .map(|m| ExportedMessage {
+                role: &m.role,
+                content: &m.content,
+                timestamp: m.at,
+                images: m.images.len(),
+            })
+            .collect(),
+    }
+}
+
+fn markdown(export: &Export) -> String {
+    let mut out = String::from("# Chat export\n\n");
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "default".to_string());
+    let parameters = &export.parameters;
+    for (key, value) in [
+        ("Exported", local(export.exported_at)),
+        ("Session", optional(export.session.map(str::to_string))),
+        ("Provider", export.provider.clone()),
+        ("URL", export.url.to_string()),
+        ("Model", export.model.to_string()),
+        (
+            "Temperature",
+            optional(parameters.temperature.map(|t| t.to_string())),
+        ),
+        ("Top P", optional(parameters.top_p.map(|t| t.to_string()))),
+        ("Top K", optional(parameters.top_k.map(|t| t.to_string()))),
+        (
+            "Max tokens",
+            optional(parameters.max_tokens.map(|t| t.to_string())),
+        ),
+        ("Seed", optional(parameters.seed.map(|s| s.to_string()))),
+    ] {
+        out.push_str(&format!("- **{}:** {}\n", key, value));
+    }
+
+    for message in &export.messages {
+        let title = match message.role {
+            "system" => "📝 System",
+            "user" => "🧑 User",
+            "assistant" => "🤖 Assistant",
+            other => other,
+        };
+        out.push_str(&format!("\n## {}", title));
+        if let Some(at) = message.timestamp {
+            out.push_str(&format!(" ({})", local(at)));
+        }
+        out.push_str("\n\n");
+        if message.images > 0 {
+            out.push_str(&format!("_{} image(s) attached_\n\n", message.images));
+        }
+        out.push_str(message.content.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn local(at: DateTime<Utc>) -> String {
+    at.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Writes the conversation to `path` as Markdown or JSON, depending on the extension
+pub fn export(chat: &Chat, path: &Path) -> anyhow::Result<()> {
+    let export = snapshot(chat);
+    let content = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("md" | "markdown") => markdown(&export),
+        Some("json") => serde_json::to_string_pretty(&export)?,
+        _ => {
+            return Err(anyhow!(
+                "Unknown export format for {}: use a .md or .json file",
+                path.display()
+            ))
+        }
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
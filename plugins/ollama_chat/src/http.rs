@@ -0,0 +1,167 @@
+// HTTP plumbing shared by the chat, model and index requests: timeouts from the config,
+// retries with exponential backoff for transient failures, and errors that say what to
+// check instead of surfacing raw reqwest messages. Proxies come from HTTPS_PROXY /
+// NO_PROXY (or proxy_url in the config), extra root certificates from ca_bundle, and
+// the `auth` headers for endpoints behind an authenticating reverse proxy.
+use crate::{tui, OllamaConfig, Provider};
+use anyhow::{anyhow, Context};
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::{Certificate, Client, NoProxy, Proxy, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use std::fs;
+use std::time::Duration;
+
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+/// Generous, because loading a large model can take a minute before the first token
+const DEFAULT_READ_TIMEOUT_SECS: u64 = 300;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Credentials sent with every request. Values may reference secrets ("secret:NAME",
+/// "secret:env:VAR", "secret:file:/path") so they don't have to be in the config file.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Auth {
+    Bearer { token: String },
+    Basic { username: String, password: String },
+}
+
+impl Auth {
+    fn header(&self) -> anyhow::Result<HeaderValue> {
+        let resolve = |value: &str| plugin_api::resolve_secret(value).map_err(|e| anyhow!(e));
+        let value = match self {
+            Auth::Bearer { token } => format!("Bearer {}", resolve(token)?),
+            Auth::Basic { username, password } => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!(
+                    "{}:{}",
+                    resolve(username)?,
+                    resolve(password)?
+                ))
+            ),
+        };
+        let mut header = HeaderValue::from_str(&value)
+            .context("The auth credentials contain invalid characters")?;
+        header.set_sensitive(true);
+        Ok(header)
+    }
+}
+
+pub fn client(config: &OllamaConfig) -> anyhow::Result<Client> {
+    let mut builder = Client::builder()
+        .connect_timeout(Duration::from_secs(
+            config
+                .connect_timeout
+                .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+        ))
+        .read_timeout(Duration::from_secs(
+            config.read_timeout.unwrap_or(DEFAULT_READ_TIMEOUT_SECS),
+        ));
+
+    // Requests that set their own Authorization (hosted provider keys) keep it
+    if let Some(auth) = &config.auth {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, auth.header()?);
+        builder = builder.default_headers(headers);
+    }
+
+    // Without proxy_url, reqwest picks up HTTP(S)_PROXY and NO_PROXY by itself
+    if let Some(url) = &config.proxy_url {
+        let proxy = Proxy::all(url)
+            .with_context(|| format!("Invalid proxy_url '{}'", url))?
+            .no_proxy(NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &config.ca_bundle {
+        let pem = fs::read(path).with_context(|| format!("Failed to read ca_bundle {}", path))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("{} is not a PEM certificate bundle", path))?;
+        if certificates.is_empty() {
+            return Err(anyhow!("No certificates found in ca_bundle {}", path));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    if config.insecure_skip_verify.unwrap_or(false) {
+        eprintln!("⚠️  TLS certificate verification is disabled (insecure_skip_verify)");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Statuses worth retrying: rate limits and an overloaded or restarting server
+fn transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    ) || status.as_u16() == 529
+}
+
+/// Errors worth retrying: a connection that was never made, or a timeout when sending the
+/// request again can't repeat its effect. A chat or pull POST that timed out may still
+/// be running on the server.
+fn transient_error(e: &reqwest::Error, idempotent: bool) -> bool {
+    e.is_connect() || (idempotent && e.is_timeout())
+}
+
+/// Sends the request built by `build`, retrying transient failures with backoff. Only
+/// getting a response is retried; a body that fails midway is reported as is.
+pub async fn send(
+    config: &OllamaConfig,
+    build: impl Fn() -> RequestBuilder,
+) -> anyhow::Result<Response> {
+    let max_retries = config.max_retries.unwrap_or(DEFAULT_MAX_RETRIES);
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let (client, request) = build().build_split();
+        let request = request?;
+        let idempotent = request.method().is_idempotent();
+        let retry = match client.execute(request).await {
+            Ok(response) if transient_status(response.status()) && attempt < max_retries => {
+                format!("server answered {}", response.status())
+            }
+            Ok(response) => return Ok(response),
+            Err(e) if transient_error(&e, idempotent) && attempt < max_retries => {
+                describe(config, e).to_string()
+            }
+            Err(e) => return Err(describe(config, e)),
+        };
+        attempt += 1;
+        tui::warn(&format!(
+            "⏳ {}; retrying in {:.1}s ({}/{})",
+            retry,
+            backoff.as_secs_f64(),
+            attempt,
+            max_retries
+        ));
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+/// Turns a reqwest error into a message that points at the likely cause
+pub fn describe(config: &OllamaConfig, e: reqwest::Error) -> anyhow::Error {
+    if e.is_connect() {
+        let hint = match config.provider {
+            Provider::Ollama => "is Ollama running? Start it with `ollama serve`",
+            _ => "check the url and your network connection",
+        };
+        anyhow!("Could not connect to {}: {}", config.url, hint)
+    } else if e.is_timeout() {
+        anyhow!(
+            "Request to {} timed out (see connect_timeout/read_timeout in the config)",
+            config.url
+        )
+    } else {
+        e.into()
+    }
+}
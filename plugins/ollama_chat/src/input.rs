@@ -0,0 +1,108 @@
+// Line editor for the chat prompt: arrow-key history, Ctrl-R reverse search and
+// multi-line input. A message spans several lines when it contains an unclosed ```
+// fence (Enter keeps going until the fence is closed) or when Alt-Enter is used to
+// insert a newline explicitly. Prompts are kept in history.txt in the plugin state
+// directory so earlier runs can be recalled (history = false in the config turns this
+// off, and lines starting with a space are never recorded).
+use crate::{OllamaConfig, PLUGIN_NAME};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Cmd, Config, Editor, EventHandler, Helper, KeyCode, KeyEvent, Modifiers};
+use std::fs;
+use std::path::PathBuf;
+
+/// Prompts kept across runs
+const MAX_HISTORY: usize = 1000;
+
+pub enum Input {
+    Line(String),
+    /// Ctrl-C at the prompt
+    Interrupted,
+    /// Ctrl-D or end of piped input
+    Eof,
+}
+
+struct ChatHelper;
+
+impl Completer for ChatHelper {
+    type Candidate = String;
+}
+
+impl Hinter for ChatHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ChatHelper {}
+
+impl Validator for ChatHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        if ctx.input().matches("```").count() % 2 == 1 {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Helper for ChatHelper {}
+
+pub struct LineEditor {
+    editor: Editor<ChatHelper, DefaultHistory>,
+    /// File the history is loaded from and appended to
+    history: Option<PathBuf>,
+}
+
+/// Where prompts are remembered, or None when history is disabled
+fn history_path(config: &OllamaConfig) -> Option<PathBuf> {
+    if !config.history.unwrap_or(true) {
+        return None;
+    }
+    plugin_api::plugin_state_dir(PLUGIN_NAME).map(|dir| dir.join("history.txt"))
+}
+
+impl LineEditor {
+    pub fn new(config: &OllamaConfig) -> anyhow::Result<Self> {
+        let mut editor = Editor::with_config(
+            Config::builder()
+                .max_history_size(MAX_HISTORY)?
+                .history_ignore_space(true)
+                .build(),
+        )?;
+        editor.set_helper(Some(ChatHelper));
+        let history = history_path(config);
+        if let Some(path) = &history {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            // A missing file just means there is no history yet
+            let _ = editor.load_history(path);
+        }
+        editor.bind_sequence(
+            KeyEvent(KeyCode::Enter, Modifiers::ALT),
+            EventHandler::Simple(Cmd::Newline),
+        );
+        Ok(Self { editor, history })
+    }
+
+    pub fn read(&mut self, prompt: &str) -> anyhow::Result<Input> {
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() && self.editor.add_history_entry(line.as_str())? {
+                    if let Some(path) = &self.history {
+                        if let Err(e) = self.editor.append_history(path) {
+                            eprintln!("⚠️  Could not save history to {}: {}", path.display(), e);
+                        }
+                    }
+                }
+                Ok(Input::Line(line))
+            }
+            Err(ReadlineError::Interrupted) => Ok(Input::Interrupted),
+            Err(ReadlineError::Eof) => Ok(Input::Eof),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// Cluster context for --k8s-context. Reads the current kubeconfig context, the
+// namespaces, pods that aren't healthy and recent warning events, and formats them as a
+// block for the system prompt. Only list calls are made; nothing in the cluster changes.
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::core::v1::{Event, Namespace, Pod};
+use kube::api::ListParams;
+use kube::config::Kubeconfig;
+use kube::{Api, Client};
+
+/// Caps that keep the block small enough to leave room for the conversation
+const MAX_PODS: usize = 30;
+const MAX_EVENTS: usize = 30;
+/// Event messages can be long (full image pull errors and such)
+const MESSAGE_CHARS: usize = 300;
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MESSAGE_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Why a pod needs attention, or None when it is running (or completed) normally
+fn pod_problems(pod: &Pod) -> Option<String> {
+    let status = pod.status.as_ref()?;
+    let phase = status.phase.as_deref().unwrap_or("Unknown");
+    if phase == "Succeeded" {
+        return None;
+    }
+    let mut problems = Vec::new();
+    if phase != "Running" {
+        problems.push(format!("phase {}", phase));
+    }
+    for container in status.container_statuses.iter().flatten() {
+        if container.ready {
+            continue;
+        }
+        let waiting = container.state.as_ref().and_then(|s| s.waiting.as_ref());
+        if let Some(waiting) = waiting {
+            problems.push(format!(
+                "container {} waiting: {}{}",
+                container.name,
+                waiting.reason.as_deref().unwrap_or("unknown reason"),
+                waiting
+                    .message
+                    .as_deref()
+                    .map(|m| format!(" ({})", truncate(m)))
+                    .unwrap_or_default()
+            ));
+        } else if phase == "Running" {
+            problems.push(format!("container {} not ready", container.name));
+        }
+        let terminated = container
+            .last_state
+            .as_ref()
+            .and_then(|s| s.terminated.as_ref());
+        if let Some(terminated) = terminated {
+            problems.push(format!(
+                "container {} last terminated: {} (exit code {}), {} restarts",
+                container.name,
+                terminated.reason.as_deref().unwrap_or("unknown reason"),
+                terminated.exit_code,
+                container.restart_count
+            ));
+        }
+    }
+    if problems.is_empty() {
+        None
+    } else {
+        Some(problems.join("; "))
+    }
+}
+
+fn event_time(event: &Event) -> Option<DateTime<Utc>> {
+    event
+        .last_timestamp
+        .as_ref()
+        .map(|t| t.0)
+        .or(event.event_time.as_ref().map(|t| t.0))
+        .or(event.metadata.creation_timestamp.as_ref().map(|t| t.0))
+}
+
+/// Builds the cluster state block appended to the system prompt
+pub async fn context_block() -> anyhow::Result<String> {
+    let context = Kubeconfig::read()
+        .ok()
+        .and_then(|config| config.current_context)
+        .unwrap_or_else(|| "in-cluster".to_string());
+    let client = Client::try_default()
+        .await
+        .context("Could not connect to the Kubernetes cluster")?;
+
+    let namespaces: Api<Namespace> = Api::all(client.clone());
+    let namespaces: Vec<String> = namespaces
+        .list(&ListParams::default())
+        .await
+        .context("Failed to list namespaces")?
+        .items
+        .into_iter()
+        .filter_map(|ns| ns.metadata.name)
+        .collect();
+
+    let pods: Api<Pod> = Api::all(client.clone());
+    let pods = pods
+        .list(&ListParams::default())
+        .await
+        .context("Failed to list pods")?
+        .items;
+    let unhealthy: Vec<String> = pods
+        .iter()
+        .filter_map(|pod| {
+            let problems = pod_problems(pod)?;
+            Some(format!(
+                "- {}/{}: {}",
+                pod.metadata.namespace.as_deref().unwrap_or("-"),
+                pod.metadata.name.as_deref().unwrap_or("-"),
+                problems
+            ))
+        })
+        .collect();
+
+    let events: Api<Event> = Api::all(client);
+    let mut warnings: Vec<Event> = events
+        .list(&ListParams::default().fields("type=Warning"))
+        .await
+        .context("Failed to list events")?
+        .items;
+    warnings.sort_by_key(|event| std::cmp::Reverse(event_time(event)));
+
+    let mut block = format!(
+        "<kubernetes-cluster context=\"{}\">\nNamespaces: {}\n",
+        context,
+        namespaces.join(", ")
+    );
+    block.push_str(&format!(
+        "Pods: {} total, {} need attention\n",
+        pods.len(),
+        unhealthy.len()
+    ));
+    for line in unhealthy.iter().take(MAX_PODS) {
+        block.push_str(line);
+        block.push('\n');
+    }
+    if unhealthy.len() > MAX_PODS {
+        block.push_str(&format!("- … and {} more\n", unhealthy.len() - MAX_PODS));
+    }
+    if !warnings.is_empty() {
+        block.push_str("Recent warning events (newest first):\n");
+        for event in warnings.iter().take(MAX_EVENTS) {
+            let object = &event.involved_object;
+            block.push_str(&format!(
+                "- {} {}/{} {}: {} {}{}\n",
+                event_time(event)
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                object.namespace.as_deref().unwrap_or("-"),
+                object.kind.as_deref().unwrap_or("?"),
+                object.name.as_deref().unwrap_or("?"),
+                event.reason.as_deref().unwrap_or(""),
+                truncate(event.message.as_deref().unwrap_or("").trim()),
+                event
+                    .count
+                    .filter(|count| *count > 1)
+                    .map(|count| format!(" (x{})", count))
+                    .unwrap_or_default()
+            ));
+        }
+    }
+    block.push_str("</kubernetes-cluster>");
+    Ok(block)
+}
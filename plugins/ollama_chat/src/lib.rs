@@ -1,15 +1,100 @@
+use chrono::{DateTime, Utc};
 use clap::{Arg, ArgMatches, Command};
 use futures::StreamExt;
-use plugin_api::Plugin;
+use plugin_api::telemetry::Span;
+use plugin_api::AsyncPlugin;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::io::{self, Write};
-use tokio::runtime::Runtime;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 // Crossterm imports for future terminal enhancements if needed
 
+mod analyze;
+mod attach;
+mod backend;
+mod budget;
+mod chat;
+mod cmd;
+mod commands;
+mod compare;
+mod export;
+mod http;
+mod input;
+mod k8s;
+mod markdown;
+mod models;
+mod presets;
+mod rag;
+mod redact;
+mod search;
+mod session;
+mod stats;
+mod structured;
+mod templates;
+mod tui;
+mod write;
+
+pub(crate) const PLUGIN_NAME: &str = "ollama_chat";
+
+/// Which API the chat talks to
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    Ollama,
+    /// OpenAI or any server exposing the OpenAI chat completions API
+    #[serde(alias = "openai-compatible")]
+    Openai,
+    Anthropic,
+}
+
+impl Provider {
+    fn default_url(self) -> &'static str {
+        match self {
+            Provider::Ollama => "http://localhost:11434",
+            Provider::Openai => "https://api.openai.com/v1",
+            Provider::Anthropic => "https://api.anthropic.com",
+        }
+    }
+
+    fn default_api_key_env(self) -> Option<&'static str> {
+        match self {
+            Provider::Ollama => None,
+            Provider::Openai => Some("OPENAI_API_KEY"),
+            Provider::Anthropic => Some("ANTHROPIC_API_KEY"),
+        }
+    }
+}
+
+/// How long Ollama keeps the model loaded after a request: seconds (negative keeps it
+/// loaded forever, 0 unloads it right away) or a duration string like "30m"
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    Seconds(i64),
+    Duration(String),
+}
+
+impl std::str::FromStr for KeepAlive {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse::<i64>() {
+            Ok(seconds) => KeepAlive::Seconds(seconds),
+            Err(_) => KeepAlive::Duration(s.to_string()),
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct OllamaConfig {
+    #[serde(default)]
+    pub provider: Provider,
+    /// Defaults to the provider's public endpoint when left out
+    #[serde(default)]
     pub url: String,
     pub model: String,
     pub temperature: Option<f32>,
@@ -17,51 +102,131 @@ pub struct OllamaConfig {
     pub top_k: Option<i32>,
     pub system_prompt: Option<String>,
     pub stream: Option<bool>,
+    /// Longest reply in tokens (num_predict for Ollama)
+    pub max_tokens: Option<u32>,
+    /// Generation stops at any of these strings
+    #[serde(default)]
+    pub stop: Vec<String>,
+    /// Fixed seed for reproducible replies (Ollama and OpenAI-compatible servers)
+    pub seed: Option<i64>,
+    /// Ollama only: penalty for repeating recent tokens (Ollama default 1.1)
+    pub repeat_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    /// API key for hosted providers; prefer api_key_env to keep it out of the file
+    pub api_key: Option<String>,
+    /// Environment variable holding the API key (defaults to OPENAI_API_KEY or ANTHROPIC_API_KEY)
+    pub api_key_env: Option<String>,
+    /// Ollama model used to embed documents for --rag (defaults to nomic-embed-text)
+    pub embedding_model: Option<String>,
+    /// Number of excerpts retrieved per message with --rag
+    pub rag_top_k: Option<usize>,
+    /// Structured output: "json", or a JSON Schema the reply has to match
+    pub format: Option<serde_json::Value>,
+    /// Ollama only: how long the model stays loaded after a request (Ollama default 5m)
+    pub keep_alive: Option<KeepAlive>,
+    /// Seconds to wait for a connection (default 10)
+    pub connect_timeout: Option<u64>,
+    /// Seconds to wait for the next bytes of a response (default 300)
+    pub read_timeout: Option<u64>,
+    /// Retries for refused connections, 429/502/503/504 and timeouts of requests that
+    /// can safely be repeated (default 3)
+    pub max_retries: Option<u32>,
+    /// Credentials for an Ollama behind an authenticating reverse proxy
+    pub auth: Option<http::Auth>,
+    /// Proxy for all requests; HTTPS_PROXY/HTTP_PROXY/NO_PROXY are used when unset
+    pub proxy_url: Option<String>,
+    /// PEM file with additional root certificates (corporate CAs)
+    pub ca_bundle: Option<String>,
+    /// Accept any TLS certificate. Last resort, prefer ca_bundle
+    pub insecure_skip_verify: Option<bool>,
+    /// Seconds after which a reply is reported (or stopped, see budget_action)
+    pub max_response_secs: Option<u64>,
+    /// Streamed tokens after which a reply is reported (or stopped)
+    pub max_output_tokens: Option<u64>,
+    /// What happens when a reply exceeds its budget: warn (default) or cancel
+    pub budget_action: Option<budget::BudgetAction>,
+    /// Seconds after which a finished reply is notified (default 30, 0 never), see
+    /// notifications.conf
+    pub notify_after_secs: Option<u64>,
+    /// Remember prompts across runs in the state directory (default true)
+    pub history: Option<bool>,
+    /// What to do with secrets found in outgoing messages: redact (default), confirm or off
+    pub redact: Option<redact::RedactMode>,
+    /// Ollama only: [options] table passed as is in the request's options, for num_ctx,
+    /// num_gpu, mirostat and anything else Ollama accepts
+    #[serde(default)]
+    pub options: BTreeMap<String, serde_json::Value>,
+    /// Named system prompt/model/parameter bundles, see presets.rs
+    #[serde(default)]
+    pub presets: BTreeMap<String, presets::Preset>,
+}
+
+impl OllamaConfig {
+    pub fn api_key(&self) -> Option<String> {
+        if let Some(key) = &self.api_key {
+            return Some(key.clone());
+        }
+        let var = self
+            .api_key_env
+            .as_deref()
+            .or(self.provider.default_api_key_env())?;
+        std::env::var(var).ok().filter(|key| !key.is_empty())
+    }
 }
 
 impl Default for OllamaConfig {
     fn default() -> Self {
         Self {
-            url: "http://localhost:11434".to_string(),
+            provider: Provider::Ollama,
+            url: Provider::Ollama.default_url().to_string(),
             model: "llama3.1:8b".to_string(),
             temperature: Some(0.7),
             top_p: Some(0.9),
             top_k: Some(40),
             system_prompt: Some("You are a helpful AI assistant.".to_string()),
             stream: Some(true),
+            max_tokens: None,
+            stop: Vec::new(),
+            seed: None,
+            repeat_penalty: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            api_key: None,
+            api_key_env: None,
+            embedding_model: None,
+            rag_top_k: None,
+            format: None,
+            keep_alive: None,
+            connect_timeout: None,
+            read_timeout: None,
+            max_retries: None,
+            auth: None,
+            proxy_url: None,
+            ca_bundle: None,
+            insecure_skip_verify: None,
+            max_response_secs: None,
+            notify_after_secs: None,
+            max_output_tokens: None,
+            budget_action: None,
+            history: None,
+            redact: None,
+            options: BTreeMap::new(),
+            presets: BTreeMap::new(),
         }
     }
 }
 
-#[derive(Debug, Serialize)]
-struct ChatRequest {
-    model: String,
-    messages: Vec<Message>,
-    stream: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    options: Option<ChatOptions>,
-}
-
-#[derive(Debug, Serialize)]
-struct ChatOptions {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    temperature: Option<f32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    top_p: Option<f32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    top_k: Option<i32>,
-}
-
 #[derive(Debug, Serialize, Deserialize, Clone)]
-struct Message {
-    role: String,
-    content: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct ChatResponse {
-    message: Option<Message>,
-    done: bool,
+pub(crate) struct Message {
+    pub(crate) role: String,
+    pub(crate) content: String,
+    /// Base64-encoded images for vision models (Ollama only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) images: Vec<String>,
+    /// When the message was sent or received; unset for the system prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) at: Option<DateTime<Utc>>,
 }
 
 pub struct OllamaChatPlugin;
@@ -77,6 +242,14 @@ top_k = 40
 system_prompt = "You are a helpful AI assistant specialized in software development and technical support."
 stream = true
 
+# Constrain and reproduce generation:
+# max_tokens = 1024
+# stop = ["\n\n\n"]
+# seed = 42
+# repeat_penalty = 1.1
+# presence_penalty = 0.0
+# frequency_penalty = 0.0
+
 # Alternative configurations:
 # For Code Generation:
 # model = "codellama:13b"
@@ -86,17 +259,113 @@ stream = true
 # model = "llama3.1:70b"
 # temperature = 0.8
 # system_prompt = "You are a friendly and knowledgeable assistant."
+
+# Hosted models (provider = "ollama" | "openai" | "anthropic"):
+# provider = "anthropic"
+# model = "claude-sonnet-4-5"
+# max_tokens = 4096
+# api_key_env = "ANTHROPIC_API_KEY"
+
+# Any OpenAI-compatible server (vLLM, llama.cpp, LM Studio, OpenAI itself):
+# provider = "openai"
+# url = "http://localhost:8000/v1"
+# model = "gpt-4o-mini"
+
+# Structured output for scripts; replies that aren't valid JSON are sent back for
+# correction. Use --schema <file> to require a JSON Schema.
+# format = "json"
+
+# Keep the model in memory between prompts ("30m", or -1 for forever); start the chat
+# with --preload to load it before the first prompt
+# keep_alive = "30m"
+
+# Network behaviour (seconds / attempts):
+# connect_timeout = 10
+# read_timeout = 300
+# max_retries = 3
+
+# Ollama behind a reverse proxy that requires authentication. Values can reference a
+# secret: "secret:NAME" (file in ~/.cohandv/proxy/secrets), "secret:env:VAR" or
+# "secret:file:/path"
+# auth = { type = "bearer", token = "secret:ollama-token" }
+# auth = { type = "basic", username = "me", password = "secret:env:OLLAMA_PASSWORD" }
+
+# Corporate networks: HTTPS_PROXY/NO_PROXY are honored; proxy_url overrides them, and
+# ca_bundle adds root certificates. insecure_skip_verify is a last resort.
+# proxy_url = "http://proxy.internal:3128"
+# ca_bundle = "/etc/ssl/certs/corp-ca.pem"
+# insecure_skip_verify = false
+
+# Budgets for a single reply, so a slow model can't lock up the terminal. By default
+# an overrun is only reported; budget_action = "cancel" stops the reply instead
+# max_response_secs = 120
+# max_output_tokens = 2000
+# budget_action = "cancel"
+
+# Replies taking longer than this are notified when they are done, through the
+# channels in notifications.conf (0 never notifies)
+# notify_after_secs = 30
+
+# Prompts are remembered across runs (↑ recalls them); start a line with a space to
+# keep it out of the history, or turn it off entirely
+# history = false
+
+# Credentials found in outgoing messages (cloud keys, tokens, private keys) are
+# redacted; "confirm" asks before sending them (--tui can't ask, so it redacts), "off"
+# disables the check
+# redact = "redact"
+
+# Local document search (proxy ollama_chat index <dir>, then --rag <name>):
+# embedding_model = "nomic-embed-text"
+# rag_top_k = 4
+
+# Any other Ollama option, passed through as is. The settings above take precedence
+# over the same option set here.
+# [options]
+# num_ctx = 8192
+# num_gpu = 99
+# mirostat = 2
+
+# Presets, selected with --preset <name> or /preset <name>. Built-in: sre, code-review,
+# shell. Presets can also live in ollama_chat.conf.d/presets/<name>.toml.
+# [presets.k8s]
+# description = "Kubernetes troubleshooting"
+# system_prompt = "You are a Kubernetes expert. Prefer kubectl commands in your answers."
+# model = "qwen2.5-coder:14b"
+# temperature = 0.2
 "#
     }
 }
 
-fn load_config(plugin_name: &str) -> anyhow::Result<OllamaConfig> {
+/// How streamed replies are written to the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Render {
+    /// Interactive chat: emoji prefix and a blank line after each reply
+    Decorated,
+    /// Like Decorated, with the reply rendered as terminal Markdown
+    Markdown,
+    /// Scripts and pipelines: only the model output
+    Plain,
+    /// Nothing is printed, for replies that have to be checked before they are shown
+    Quiet,
+    /// The full-screen chat: text is handed to the TUI instead of being printed
+    Tui,
+    /// A column of --compare's columns layout: text is handed to its live view
+    Column(usize),
+}
+
+fn load_config(plugin_name: &str, quiet: bool) -> anyhow::Result<OllamaConfig> {
     match plugin_api::plugin_config_path(plugin_name) {
         Some(config_path) => {
-            if config_path.exists() {
-                let content = fs::read_to_string(config_path)?;
-                let config: OllamaConfig = toml::from_str(&content)?;
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let mut config: OllamaConfig = toml::from_str(&content)?;
+                if config.url.is_empty() {
+                    config.url = config.provider.default_url().to_string();
+                }
                 Ok(config)
+            } else if quiet {
+                Ok(OllamaConfig::default())
             } else {
                 println!("⚠️  Config file not found, using defaults.");
                 println!("💡 Create config at: {}", config_path.display());
@@ -105,130 +374,543 @@ fn load_config(plugin_name: &str) -> anyhow::Result<OllamaConfig> {
             }
         }
         None => {
-            println!("⚠️  Could not determine config path, using defaults.");
+            if !quiet {
+                println!("⚠️  Could not determine config path, using defaults.");
+            }
             Ok(OllamaConfig::default())
         }
     }
 }
 
+/// Set while a request is in flight, so Ctrl-C stops the reply instead of the chat
+static IN_FLIGHT: AtomicBool = AtomicBool::new(false);
+/// Set by the Ctrl-C handler to stop the reply being streamed
+static CANCEL: AtomicBool = AtomicBool::new(false);
+
+/// Resolves once the user asked to stop the current reply
+async fn cancelled() {
+    while !CANCEL.load(Ordering::SeqCst) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Marks a request as in flight for as long as it is alive
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        CANCEL.store(false, Ordering::SeqCst);
+        IN_FLIGHT.store(true, Ordering::SeqCst);
+        InFlight
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A complete answer together with its token usage and timing
+pub(crate) struct Reply {
+    pub(crate) content: String,
+    pub(crate) stats: stats::TurnStats,
+}
+
+/// Sends the conversation and streams the answer, traced as one span
 async fn send_chat_message(
     client: &Client,
     config: &OllamaConfig,
     messages: &[Message],
-) -> anyhow::Result<()> {
-    let options = ChatOptions {
-        temperature: config.temperature,
-        top_p: config.top_p,
-        top_k: config.top_k,
-    };
+    render: Render,
+) -> anyhow::Result<Reply> {
+    let span = Span::start("chat request");
+    span.set_attribute(
+        "llm.provider",
+        backend::for_provider(config.provider).name(),
+    );
+    span.set_attribute("llm.model", &config.model);
+    span.set_int("llm.messages", messages.len() as i64);
+    let result = stream_reply(client, config, messages, render).await;
+    match &result {
+        Ok(reply) => {
+            let usage = reply.stats.usage;
+            if let Some(tokens) = usage.prompt_tokens {
+                span.set_int("llm.prompt_tokens", tokens as i64);
+            }
+            if let Some(tokens) = usage.completion_tokens {
+                span.set_int("llm.completion_tokens", tokens as i64);
+            }
+            let notify_after = config.notify_after_secs.unwrap_or(30);
+            if notify_after > 0 && reply.stats.latency.as_secs() >= notify_after {
+                let preview: String = reply.content.chars().take(200).collect();
+                plugin_api::notify::send(
+                    PLUGIN_NAME,
+                    "chat_reply_ready",
+                    &format!(
+                        "{} replied after {}s",
+                        config.model,
+                        reply.stats.latency.as_secs()
+                    ),
+                    preview.trim(),
+                );
+            }
+        }
+        Err(e) => span.set_error(e),
+    }
+    result
+}
 
-    let request = ChatRequest {
-        model: config.model.clone(),
-        messages: messages.to_vec(),
-        stream: config.stream.unwrap_or(true),
-        options: Some(options),
+async fn stream_reply(
+    client: &Client,
+    config: &OllamaConfig,
+    messages: &[Message],
+    render: Render,
+) -> anyhow::Result<Reply> {
+    let started = Instant::now();
+    let _in_flight = InFlight::start();
+    let backend = backend::for_provider(config.provider);
+    // Loading a model counts against the time budget too
+    let mut budget = budget::Budget::new(config, started);
+    let request = http::send(config, || backend.request(client, config, messages));
+    tokio::pin!(request);
+    let response = loop {
+        tokio::select! {
+            response = &mut request => break response?,
+            _ = cancelled() => return Err(anyhow::anyhow!("Cancelled")),
+            _ = budget.overtime() => budget.report_time(),
+        }
     };
 
-    let response = client
-        .post(format!("{}/api/chat", config.url))
-        .json(&request)
-        .send()
-        .await?;
-
     if !response.status().is_success() {
+        let status = response.status();
         let error_text = response.text().await.unwrap_or_default();
-        return Err(anyhow::anyhow!("Ollama API error: {}", error_text));
+        return Err(anyhow::anyhow!(
+            "{} API error ({}): {}",
+            backend.name(),
+            status,
+            error_text
+        ));
     }
 
-    print!("🤖 ");
-    io::stdout().flush()?;
+    if matches!(render, Render::Decorated | Render::Markdown) {
+        print!("🤖 ");
+        io::stdout().flush()?;
+    }
+    let mut out = Output::new(render);
 
+    // Accumulate the streamed reply so it can be recorded in the conversation
+    let mut reply = String::new();
+    let mut usage = stats::Usage::default();
+    let finished = |content: String, usage: stats::Usage| Reply {
+        content,
+        stats: stats::TurnStats {
+            usage,
+            latency: started.elapsed(),
+        },
+    };
+
+    if !config.stream.unwrap_or(true) {
+        // Non-streaming responses arrive as one (possibly pretty-printed) document
+        let body = response.text().await?;
+        if let Some(chunk) = backend.parse_line(body.trim())? {
+            if let Some(text) = chunk.text {
+                out.write(&text)?;
+                reply.push_str(&text);
+            }
+            usage = chunk.usage.unwrap_or(usage);
+        }
+        out.finish()?;
+        return Ok(finished(reply, usage));
+    }
+
+    // Lines (and UTF-8 sequences) may be split across chunks, so keep the
+    // incomplete tail around until the rest arrives
+    let mut pending: Vec<u8> = Vec::new();
     let mut stream = response.bytes_stream();
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        let text = String::from_utf8_lossy(&chunk);
+    loop {
+        let chunk = tokio::select! {
+            chunk = stream.next() => chunk,
+            _ = budget.overtime() => {
+                // In cancel mode this sets CANCEL, handled on the next iteration
+                budget.report_time();
+                continue;
+            }
+            _ = cancelled() => {
+                // Keep what arrived so far as the (truncated) answer
+                out.finish()?;
+                if matches!(render, Render::Decorated | Render::Markdown) {
+                    println!("⏹️  Stopped");
+                }
+                if reply.is_empty() {
+                    return Err(anyhow::anyhow!("Cancelled"));
+                }
+                return Ok(finished(reply, usage));
+            }
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        pending.extend_from_slice(&chunk.map_err(|e| http::describe(config, e))?);
 
-        for line in text.lines() {
-            if line.trim().is_empty() {
+        while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
                 continue;
             }
 
-            match serde_json::from_str::<ChatResponse>(line) {
-                Ok(chat_response) => {
-                    if let Some(message) = chat_response.message {
-                        print!("{}", message.content);
-                        io::stdout().flush()?;
-                    }
-                    if chat_response.done {
-                        println!("\n");
-                        return Ok(());
-                    }
+            if let Some(chunk) = backend.parse_line(line)? {
+                if let Some(text) = chunk.text {
+                    out.write(&text)?;
+                    reply.push_str(&text);
+                    budget.count();
                 }
-                Err(_) => {
-                    // Skip invalid JSON lines
-                    continue;
+                usage = chunk.usage.unwrap_or(usage);
+                if chunk.done {
+                    out.finish()?;
+                    return Ok(finished(reply, usage));
                 }
             }
         }
     }
 
-    println!("\n");
-    Ok(())
+    // The last line may not end with a newline
+    let line = String::from_utf8_lossy(&pending);
+    if let Some(chunk) = backend.parse_line(line.trim())? {
+        if let Some(text) = chunk.text {
+            out.write(&text)?;
+            reply.push_str(&text);
+        }
+        usage = chunk.usage.unwrap_or(usage);
+    }
+
+    out.finish()?;
+    Ok(finished(reply, usage))
 }
 
-async fn run_chat_loop(config: OllamaConfig) -> anyhow::Result<()> {
-    let client = Client::new();
-    let mut messages = Vec::new();
+/// Where streamed reply text goes: straight to stdout or through the Markdown renderer
+struct Output {
+    render: Render,
+    markdown: Option<markdown::Renderer>,
+}
 
-    // Add system prompt if configured
-    if let Some(system_prompt) = &config.system_prompt {
-        messages.push(Message {
-            role: "system".to_string(),
-            content: system_prompt.clone(),
-        });
+impl Output {
+    fn new(render: Render) -> Self {
+        Self {
+            render,
+            markdown: (render == Render::Markdown).then(markdown::Renderer::new),
+        }
+    }
+
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        match (&mut self.markdown, self.render) {
+            (Some(markdown), _) => markdown.push(text),
+            (None, Render::Quiet) => Ok(()),
+            (None, Render::Tui) => {
+                tui::forward(text);
+                Ok(())
+            }
+            (None, Render::Column(column)) => {
+                compare::forward(column, text);
+                Ok(())
+            }
+            (None, _) => {
+                print!("{}", text);
+                io::stdout().flush()
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match &mut self.markdown {
+            // The renderer ends every line itself
+            Some(markdown) => {
+                markdown.finish()?;
+                println!();
+            }
+            None if matches!(self.render, Render::Quiet | Render::Tui | Render::Column(_)) => {}
+            None if self.render == Render::Plain => println!(),
+            None => println!("\n"),
+        }
+        Ok(())
+    }
+}
+
+/// Sends one user turn (with optional images) and records both sides of the exchange in `messages`.
+/// On failure the user message is removed again so the history stays consistent.
+async fn chat_turn(
+    client: &Client,
+    config: &OllamaConfig,
+    messages: &mut Vec<Message>,
+    input: &str,
+    images: Vec<String>,
+    render: Render,
+) -> anyhow::Result<Reply> {
+    messages.push(Message {
+        role: "user".to_string(),
+        content: redact::guard(config, input),
+        images,
+        at: Some(Utc::now()),
+    });
+
+    match send_chat_message(client, config, messages, render).await {
+        Ok(reply) => {
+            messages.push(Message {
+                role: "assistant".to_string(),
+                content: reply.content.clone(),
+                images: Vec::new(),
+                at: Some(Utc::now()),
+            });
+            Ok(reply)
+        }
+        Err(e) => {
+            // Remove the failed user message
+            messages.pop();
+            Err(e)
+        }
+    }
+}
+
+/// Asks the model to correct replies that don't match the requested output format,
+/// up to structured::MAX_CORRECTIONS times
+async fn enforce_format(
+    client: &Client,
+    config: &OllamaConfig,
+    messages: &mut Vec<Message>,
+    mut reply: Reply,
+    render: Render,
+) -> anyhow::Result<Reply> {
+    let Some(format) = &config.format else {
+        return Ok(reply);
+    };
+    let mut corrections = 0;
+    loop {
+        let problem = match structured::check(format, &reply.content) {
+            Ok(()) => return Ok(reply),
+            Err(problem) => problem,
+        };
+        if corrections == structured::MAX_CORRECTIONS {
+            return Err(anyhow::anyhow!(
+                "The reply is not valid {}: {}",
+                structured::describe(format),
+                problem
+            ));
+        }
+        corrections += 1;
+        tui::warn(&format!(
+            "⚠️  Reply rejected because {}; asking for a correction",
+            problem
+        ));
+        reply = chat_turn(
+            client,
+            config,
+            messages,
+            &structured::correction(&problem),
+            Vec::new(),
+            render,
+        )
+        .await?;
+    }
+}
+
+/// Builds the one-shot prompt from --prompt or --template and/or piped stdin. Returns
+/// None when the chat should run interactively: with neither, and nothing piped in, as
+/// from an editor or a job that leaves stdin empty.
+fn one_shot_prompt(matches: &ArgMatches) -> anyhow::Result<Option<String>> {
+    let mut piped = if io::stdin().is_terminal() {
+        None
+    } else {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        Some(content).filter(|content| !content.trim().is_empty())
+    };
+
+    let prompt = match matches.get_one::<String>("template") {
+        Some(name) => {
+            let template = templates::load(name)?;
+            let mut vars = HashMap::new();
+            for arg in matches.get_many::<String>("var").into_iter().flatten() {
+                let (name, value) = templates::parse_var(arg)?;
+                vars.insert(name, value);
+            }
+            // A template that places stdin itself doesn't get it appended as well
+            if templates::placeholders(&template)
+                .iter()
+                .any(|n| n == "stdin")
+            {
+                vars.insert("stdin".to_string(), piped.take().unwrap_or_default());
+            }
+            Some(templates::render(&template, &vars)?)
+        }
+        None => matches.get_one::<String>("prompt").cloned(),
+    };
+
+    Ok(match (prompt, piped) {
+        (Some(prompt), Some(content)) => Some(format!("{}\n\n{}", prompt, content)),
+        (Some(prompt), None) => Some(prompt),
+        (None, Some(content)) => Some(content),
+        (None, None) => None,
+    })
+}
+
+/// Answers a single prompt, writing only the model output to stdout
+async fn run_one_shot(
+    config: OllamaConfig,
+    prompt: String,
+    context: &[String],
+    images: &[String],
+    rag: Option<&rag::Index>,
+    output: Option<&write::Target>,
+) -> anyhow::Result<()> {
+    let client = http::client(&config)?;
+    let images = images
+        .iter()
+        .map(|path| attach::image(&config, path))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut attachments = Vec::new();
+    for pattern in context {
+        attachments.extend(attach::collect(pattern, attach::total_bytes(&attachments))?);
+    }
+    let mut prefix = String::new();
+    if !attachments.is_empty() {
+        prefix.push_str(&attach::render(&attachments));
+        prefix.push('\n');
+    }
+    if let Some(index) = rag {
+        prefix.push_str(&index.retrieve(&client, &config, &prompt).await?);
+        prefix.push('\n');
+    }
+    let prompt = format!("{}{}", prefix, prompt);
+
+    // With a format set, only a reply that passed the checks is printed
+    let render = if config.format.is_some() {
+        Render::Quiet
+    } else {
+        Render::Plain
+    };
+    let mut messages = chat::initial_messages(&config);
+    let reply = chat_turn(&client, &config, &mut messages, &prompt, images, render).await?;
+    let reply = enforce_format(&client, &config, &mut messages, reply, render).await?;
+    if render == Render::Quiet {
+        println!("{}", reply.content.trim());
+    }
+    if let Some(target) = output {
+        let bytes = target.write(&reply.content)?;
+        eprintln!("💾 {}", target.describe(bytes));
+    }
+    Ok(())
+}
+
+async fn run_chat_loop(
+    mut chat: chat::Chat,
+    session_name: Option<String>,
+    context: &[String],
+    images: &[String],
+    export_on_exit: Option<&Path>,
+) -> anyhow::Result<()> {
+    // Resume the named session if it was saved before; new ones are created on first save
+    if let Some(name) = session_name {
+        session::validate_name(&name)?;
+        if session::exists(&name) {
+            let count = chat.load(&name)?;
+            println!("📂 Resumed session '{}' ({} messages)", name, count);
+        } else {
+            println!("🆕 New session '{}'", name);
+            chat.session = Some(name);
+        }
     }
 
     println!("🚀 Ollama Chat Interface");
-    println!("📡 Connected to: {}", config.url);
-    println!("🤖 Using model: {}", config.model);
-    println!("💬 Type your messages (Ctrl+C to exit, 'clear' to reset conversation)\n");
+    println!(
+        "📡 Connected to: {} ({:?})",
+        chat.config.url, chat.config.provider
+    );
+    println!("🤖 Using model: {}", chat.config.model);
+    println!("💬 Type your messages (Ctrl+C stops a reply or exits, /help for commands)");
+    println!("⌨️  ↑/↓ history, Ctrl-R search, Alt-Enter or ``` for multi-line input\n");
 
-    // Set up Ctrl+C handler
-    let running = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    for pattern in context {
+        let (files, bytes) = chat.attach(pattern)?;
+        println!(
+            "📎 Attached {} file(s) from {} ({} KB)",
+            files,
+            pattern,
+            bytes.div_ceil(1024)
+        );
+    }
+    for path in images {
+        chat.attach_image(path)?;
+        println!("🖼️  Attached image {}", path);
+    }
+    if let Some(index) = &chat.rag {
+        println!(
+            "📚 Answering with {} chunks from {}",
+            index.chunks.len(),
+            index.root.display()
+        );
+    }
+    if !context.is_empty() || !images.is_empty() || chat.rag.is_some() {
+        println!();
+    }
+
+    if chat.render == Render::Tui {
+        tui::run(&mut chat).await?;
+    } else {
+        line_loop(&mut chat).await?;
+    }
+
+    chat.stats.print_summary();
+    if let Some(path) = export_on_exit {
+        match export::export(&chat, path) {
+            Ok(()) => println!("📤 Conversation exported to {}", path.display()),
+            Err(e) => println!("❌ Export failed: {}", e),
+        }
+    }
+    println!("👋 Chat session ended.");
+    Ok(())
+}
+
+/// The line-based prompt: reads messages and slash commands until the user leaves
+async fn line_loop(chat: &mut chat::Chat) -> anyhow::Result<()> {
+    let mut editor = input::LineEditor::new(&chat.config)?;
+
+    // Set up Ctrl+C handler: stop the reply being streamed, or leave the chat
+    let running = std::sync::Arc::new(AtomicBool::new(true));
     let r = running.clone();
     ctrlc::set_handler(move || {
-        r.store(false, std::sync::atomic::Ordering::SeqCst);
+        if IN_FLIGHT.load(Ordering::SeqCst) {
+            CANCEL.store(true, Ordering::SeqCst);
+            return;
+        }
+        r.store(false, Ordering::SeqCst);
         println!("\n👋 Goodbye!");
         std::process::exit(0);
     })?;
 
     loop {
         // Check if we should continue
-        if !running.load(std::sync::atomic::Ordering::SeqCst) {
+        if !running.load(Ordering::SeqCst) {
             break;
         }
 
-        print!("🧑 ");
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {
+        match editor.read("🧑 ")? {
+            input::Input::Eof => break,
+            input::Input::Interrupted => {
+                println!("👋 Goodbye!");
+                break;
+            }
+            input::Input::Line(input) => {
                 let input = input.trim();
 
                 if input.is_empty() {
                     continue;
                 }
 
+                // Plain-word shortcuts kept for compatibility with earlier versions
                 if input.eq_ignore_ascii_case("clear") {
-                    messages.clear();
-                    // Re-add system prompt if configured
-                    if let Some(system_prompt) = &config.system_prompt {
-                        messages.push(Message {
-                            role: "system".to_string(),
-                            content: system_prompt.clone(),
-                        });
-                    }
+                    chat.reset();
                     println!("🧹 Conversation cleared!\n");
                     continue;
                 }
@@ -237,40 +919,36 @@ async fn run_chat_loop(config: OllamaConfig) -> anyhow::Result<()> {
                     break;
                 }
 
-                // Add user message
-                messages.push(Message {
-                    role: "user".to_string(),
-                    content: input.to_string(),
-                });
+                match commands::parse(input) {
+                    Some(Ok(command)) => match commands::execute(chat, command).await {
+                        commands::Flow::Continue => continue,
+                        commands::Flow::Exit => break,
+                    },
+                    Some(Err(e)) => {
+                        println!("❌ {}\n", e);
+                        continue;
+                    }
+                    None => {}
+                }
 
-                // Send to Ollama and stream response
-                match send_chat_message(&client, &config, &messages).await {
+                // Send to Ollama, stream the response and record the exchange
+                match chat.send(input).await {
                     Ok(_) => {
-                        // Add assistant response placeholder (we don't capture the streamed response)
-                        // In a real implementation, you'd capture the full response
                         println!();
                     }
                     Err(e) => {
                         println!("❌ Error: {}\n", e);
-                        // Remove the failed user message
-                        messages.pop();
                     }
                 }
             }
-            Err(e) => {
-                println!("❌ Input error: {}", e);
-                break;
-            }
         }
     }
-
-    println!("👋 Chat session ended.");
     Ok(())
 }
 
-impl Plugin for OllamaChatPlugin {
+impl AsyncPlugin for OllamaChatPlugin {
     fn name(&self) -> &'static str {
-        "ollama_chat"
+        PLUGIN_NAME
     }
 
     fn version(&self) -> &'static str {
@@ -278,7 +956,7 @@ impl Plugin for OllamaChatPlugin {
     }
 
     fn description(&self) -> &'static str {
-        "Interactive streaming chat interface for Ollama"
+        "Interactive streaming chat interface for Ollama and hosted models"
     }
 
     fn subcommand(&self) -> Command {
@@ -296,7 +974,7 @@ impl Plugin for OllamaChatPlugin {
                     .long("url")
                     .short('u')
                     .value_name("URL")
-                    .help("Override the Ollama URL from config file"),
+                    .help("Override the API URL from config file"),
             )
             .arg(
                 Arg::new("temperature")
@@ -306,43 +984,650 @@ impl Plugin for OllamaChatPlugin {
                     .help("Set temperature (0.0-1.0)")
                     .value_parser(clap::value_parser!(f32)),
             )
+            .arg(
+                Arg::new("session")
+                    .long("session")
+                    .short('s')
+                    .value_name("NAME")
+                    .help("Resume (or start) a named session that is saved after every reply"),
+            )
+            .arg(
+                Arg::new("prompt")
+                    .long("prompt")
+                    .short('p')
+                    .value_name("PROMPT")
+                    .help("Answer a single prompt and exit; piped stdin is appended to it"),
+            )
+            .arg(
+                Arg::new("template")
+                    .long("template")
+                    .value_name("NAME")
+                    .conflicts_with("prompt")
+                    .help("Answer a prompt rendered from a template file and exit"),
+            )
+            .arg(
+                Arg::new("var")
+                    .long("var")
+                    .value_name("NAME=VALUE")
+                    .requires("template")
+                    .help("Template variable; NAME=@file reads the value from a file (repeatable)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("context")
+                    .long("context")
+                    .short('c')
+                    .value_name("GLOB")
+                    .help("Attach files matching a path, directory or glob to the first message (repeatable)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("image")
+                    .long("image")
+                    .short('i')
+                    .value_name("PATH")
+                    .help("Attach an image to the first message for vision models (repeatable)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("export-on-exit")
+                    .long("export-on-exit")
+                    .value_name("FILE")
+                    .help("Write the conversation to a .md or .json file when the chat ends"),
+            )
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .short('f')
+                    .value_name("FORMAT")
+                    .value_parser(["json"])
+                    .help("Ask for JSON output and check every reply parses"),
+            )
+            .arg(
+                Arg::new("schema")
+                    .long("schema")
+                    .value_name("FILE")
+                    .help("Ask for JSON matching this JSON Schema and validate every reply"),
+            )
+            .arg(
+                Arg::new("keep-alive")
+                    .long("keep-alive")
+                    .value_name("DURATION")
+                    .allow_negative_numbers(true)
+                    .help("How long Ollama keeps the model loaded, e.g. 30m, or -1 for forever")
+                    .value_parser(clap::value_parser!(KeepAlive)),
+            )
+            .arg(
+                Arg::new("preload")
+                    .long("preload")
+                    .help("Load the model before the first prompt so it doesn't stall")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("tui")
+                    .long("tui")
+                    .help("Full-screen chat with a scrollable history and a status bar")
+                    .conflicts_with("compare")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .value_name("FILE")
+                    .help("Write every reply to a file"),
+            )
+            .arg(
+                Arg::new("append")
+                    .long("append")
+                    .requires("output")
+                    .help("Append replies to the --output file instead of replacing it")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("code-only")
+                    .long("code-only")
+                    .requires("output")
+                    .help("Only write the code blocks of replies to the --output file")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("max-tokens")
+                    .long("max-tokens")
+                    .value_name("N")
+                    .help("Maximum length of a reply in tokens")
+                    .value_parser(clap::value_parser!(u32)),
+            )
+            .arg(
+                Arg::new("stop")
+                    .long("stop")
+                    .value_name("TEXT")
+                    .help("Stop generating at this text (repeatable)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("seed")
+                    .long("seed")
+                    .value_name("N")
+                    .help("Seed for reproducible replies")
+                    .value_parser(clap::value_parser!(i64)),
+            )
+            .arg(
+                Arg::new("raw")
+                    .long("raw")
+                    .help("Print replies as raw Markdown instead of rendering them")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .subcommand(Command::new("sessions").about("List saved chat sessions"))
+            .arg(
+                Arg::new("compare")
+                    .long("compare")
+                    .value_name("MODELS")
+                    .value_delimiter(',')
+                    .num_args(1)
+                    .conflicts_with_all(["session", "context", "image", "rag"])
+                    .help("Send every prompt to several models, e.g. --compare llama3.1,mistral"),
+            )
+            .arg(
+                Arg::new("compare-layout")
+                    .long("compare-layout")
+                    .value_name("LAYOUT")
+                    .value_parser(clap::value_parser!(compare::Layout))
+                    .default_value("blocks")
+                    .help("How --compare shows the replies: blocks or columns"),
+            )
+            .arg(
+                Arg::new("mode")
+                    .long("mode")
+                    .value_name("MODE")
+                    .value_parser(clap::value_parser!(cmd::Mode))
+                    .default_value("chat")
+                    .conflicts_with_all(["compare", "tui", "format", "schema"])
+                    .help("chat, or cmd to get shell commands that run after confirmation"),
+            )
+            .arg(
+                Arg::new("k8s-context")
+                    .long("k8s-context")
+                    .help("Add read-only state of the current Kubernetes cluster to the system prompt")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("preset")
+                    .long("preset")
+                    .value_name("NAME")
+                    .help("Start from a named preset (system prompt, model and parameters)"),
+            )
+            .arg(
+                Arg::new("rag")
+                    .long("rag")
+                    .value_name("INDEX")
+                    .help("Retrieve relevant excerpts from a local index for every message"),
+            )
+            .subcommand(models::subcommand())
+            .subcommand(
+                Command::new("index")
+                    .about("Embed the files of a directory into a local index for --rag")
+                    .arg(
+                        Arg::new("path")
+                            .value_name("DIR")
+                            .required(true)
+                            .help("Directory (or file/glob) to index"),
+                    )
+                    .arg(
+                        Arg::new("name")
+                            .long("name")
+                            .short('n')
+                            .value_name("NAME")
+                            .help("Index name (defaults to the directory name)"),
+                    ),
+            )
+            .subcommand(
+                Command::new("analyze")
+                    .about("Ask questions about captured traffic (HAR or JSON capture files)")
+                    .arg(
+                        Arg::new("files")
+                            .value_name("FILE")
+                            .required(true)
+                            .num_args(1..)
+                            .help("Capture files to analyze, e.g. written by k8s_native_port_forward --capture"),
+                    )
+                    .arg(
+                        Arg::new("prompt")
+                            .long("prompt")
+                            .short('p')
+                            .value_name("QUESTION")
+                            .help("Answer a single question and exit"),
+                    ),
+            )
     }
 
-    fn run(&self, matches: &ArgMatches) {
-        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(OllamaChatPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<OllamaConfig>(config)
+    }
 
-        rt.block_on(async {
-            let mut config = match load_config(self.name()) {
-                Ok(config) => config,
+    async fn run(&self, matches: &ArgMatches) {
+        if matches.subcommand_matches("sessions").is_some() {
+            if let Err(e) = session::print_sessions() {
+                eprintln!("❌ Failed to list sessions: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        let models = matches.subcommand_matches("models");
+        let index = matches.subcommand_matches("index");
+        let analyze = matches.subcommand_matches("analyze");
+        let one_shot = match matches.subcommand() {
+            None => one_shot_prompt(matches),
+            Some(("analyze", analyze)) => Ok(analyze.get_one::<String>("prompt").cloned()),
+            Some(_) => Ok(None),
+        };
+        let one_shot = match one_shot {
+            Ok(prompt) => prompt,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let mut config = match load_config(self.name(), one_shot.is_some()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        if let Some(name) = matches.get_one::<String>("preset") {
+            match presets::find(&config, name) {
+                Ok(preset) => preset.apply(&mut config),
                 Err(e) => {
-                    eprintln!("❌ Failed to load config: {}", e);
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        // Override config with command line arguments
+        if let Some(model) = matches.get_one::<String>("model") {
+            config.model = model.clone();
+        }
+
+        if let Some(url) = matches.get_one::<String>("url") {
+            config.url = url.clone();
+        }
+
+        if let Some(temperature) = matches.get_one::<f32>("temperature") {
+            config.temperature = Some(*temperature);
+        }
+
+        if let Some(max_tokens) = matches.get_one::<u32>("max-tokens") {
+            config.max_tokens = Some(*max_tokens);
+        }
+
+        if let Some(stop) = matches.get_many::<String>("stop") {
+            config.stop = stop.cloned().collect();
+        }
+
+        if let Some(seed) = matches.get_one::<i64>("seed") {
+            config.seed = Some(*seed);
+        }
+
+        let format = matches
+            .get_one::<String>("schema")
+            .or(matches.get_one::<String>("format"));
+        if let Some(format) = format {
+            match structured::parse_format(format) {
+                Ok(format) => config.format = Some(format),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        if let Some(keep_alive) = matches.get_one::<KeepAlive>("keep-alive") {
+            config.keep_alive = Some(keep_alive.clone());
+        }
+
+        if let Some(index) = index {
+            let path = Path::new(
+                index
+                    .get_one::<String>("path")
+                    .expect("path is a required argument"),
+            );
+            let name = index.get_one::<String>("name").cloned().unwrap_or_else(|| {
+                path.canonicalize()
+                    .ok()
+                    .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+                    .unwrap_or_else(|| "default".to_string())
+            });
+            if let Err(e) = rag::build(&config, path, &name).await {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        if let Some(models) = models {
+            if let Err(e) = models::run(&config, models).await {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        if let Some(analyze) = analyze {
+            let files: Vec<String> = analyze
+                .get_many::<String>("files")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            let prompt = match http::client(&config) {
+                Ok(client) => analyze::system_prompt(&client, &config, &files).await,
+                Err(e) => Err(e),
+            };
+            match prompt {
+                Ok(prompt) => config.system_prompt = Some(prompt),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        if matches.get_flag("k8s-context") {
+            match k8s::context_block().await {
+                Ok(block) => {
+                    config.system_prompt = Some(match config.system_prompt.take() {
+                        Some(prompt) => format!("{}\n\n{}", prompt, block),
+                        None => block,
+                    })
+                }
+                Err(e) => {
+                    eprintln!("❌ {:#}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        // Captures and cluster state end up in the system prompt, not in chat_turn
+        if analyze.is_some() || matches.get_flag("k8s-context") {
+            config.system_prompt = config
+                .system_prompt
+                .as_deref()
+                .map(|prompt| redact::guard(&config, prompt));
+        }
+
+        let mode = *matches
+            .get_one::<cmd::Mode>("mode")
+            .expect("mode has a default");
+        if mode == cmd::Mode::Cmd {
+            cmd::configure(&mut config);
+        }
+
+        let context: Vec<String> = matches
+            .get_many::<String>("context")
+            .map(|values| values.cloned().collect())
+            .unwrap_or_default();
+
+        let images: Vec<String> = matches
+            .get_many::<String>("image")
+            .map(|values| values.cloned().collect())
+            .unwrap_or_default();
+
+        let rag = match matches.get_one::<String>("rag").map(|name| rag::load(name)) {
+            Some(Ok(index)) => Some(index),
+            Some(Err(e)) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            None => None,
+        };
+
+        // Rendering only makes sense on a terminal
+        let render = if matches.get_flag("tui") && io::stdout().is_terminal() {
+            Render::Tui
+        } else if matches.get_flag("raw") || !io::stdout().is_terminal() {
+            Render::Decorated
+        } else {
+            Render::Markdown
+        };
+
+        if let Some(models) = matches.get_many::<String>("compare") {
+            let models: Vec<String> = models.cloned().collect();
+            if models.len() < 2 {
+                eprintln!("❌ --compare needs at least two models, separated by commas");
+                std::process::exit(1);
+            }
+            let client = match http::client(&config) {
+                Ok(client) => client,
+                Err(e) => {
+                    eprintln!("❌ {}", e);
                     std::process::exit(1);
                 }
             };
+            let layout = *matches
+                .get_one::<compare::Layout>("compare-layout")
+                .expect("compare-layout has a default");
+            let render = if one_shot.is_some() {
+                Render::Plain
+            } else {
+                render
+            };
+            let mut comparison = compare::Comparison::new(client, &config, &models, layout, render);
+            let result = match one_shot {
+                Some(prompt) => {
+                    comparison.ask(&prompt).await;
+                    Ok(())
+                }
+                None => compare::run_loop(comparison).await,
+            };
+            if let Err(e) = result {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        let output = matches
+            .get_one::<String>("output")
+            .map(|path| write::Target {
+                path: path.into(),
+                append: matches.get_flag("append"),
+                code_only: matches.get_flag("code-only"),
+            });
 
-            // Override config with command line arguments
-            if let Some(model) = matches.get_one::<String>("model") {
-                config.model = model.clone();
+        if let (Some(prompt), cmd::Mode::Cmd) = (&one_shot, mode) {
+            let result = match http::client(&config) {
+                Ok(client) => cmd::one_shot(&client, &config, prompt).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
             }
+            return;
+        }
 
-            if let Some(url) = matches.get_one::<String>("url") {
-                config.url = url.clone();
+        if let Some(prompt) = one_shot {
+            if let Err(e) = run_one_shot(
+                config,
+                prompt,
+                &context,
+                &images,
+                rag.as_ref(),
+                output.as_ref(),
+            )
+            .await
+            {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
             }
+            return;
+        }
 
-            if let Some(temperature) = matches.get_one::<f32>("temperature") {
-                config.temperature = Some(*temperature);
+        let session_name = matches.get_one::<String>("session").cloned();
+
+        if matches.get_flag("preload") {
+            if let Err(e) = models::preload(&config).await {
+                println!("⚠️  Could not preload {}: {}", config.model, e);
             }
+        }
 
-            if let Err(e) = run_chat_loop(config).await {
+        let mut chat = match http::client(&config) {
+            Ok(client) => chat::Chat::new(client, config),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        };
+        chat.render = render;
+        chat.rag = rag;
+        chat.output = output;
+
+        if mode == cmd::Mode::Cmd {
+            if let Err(e) = cmd::run_loop(chat).await {
                 eprintln!("❌ Chat error: {}", e);
                 std::process::exit(1);
             }
-        });
+            return;
+        }
+
+        if let Err(e) = run_chat_loop(
+            chat,
+            session_name,
+            &context,
+            &images,
+            matches.get_one::<String>("export-on-exit").map(Path::new),
+        )
+        .await
+        {
+            eprintln!("❌ Chat error: {}", e);
+            std::process::exit(1);
+        }
     }
 }
 
 #[no_mangle]
 #[allow(improper_ctypes_definitions)]
-pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+pub extern "C" fn create_plugin() -> Box<dyn plugin_api::Plugin> {
     Box::new(OllamaChatPlugin)
 }
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Minimal stand-in for Ollama's /api/chat: answers each request with the next
+    /// canned reply, streamed as NDJSON, and hands back every request body it saw.
+    async fn mock_ollama(
+        replies: Vec<&'static str>,
+    ) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for reply in replies {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let body = loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(header_end) = text.find("\r\n\r\n") {
+                        let content_length = text[..header_end]
+                            .lines()
+                            .find_map(|line| {
+                                let (name, value) = line.split_once(':')?;
+                                name.eq_ignore_ascii_case("content-length")
+                                    .then(|| value.trim().parse::<usize>().ok())?
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= header_end + 4 + content_length {
+                            break text[header_end + 4..].to_string();
+                        }
+                    }
+                };
+                bodies.push(body);
+
+                let stream = format!(
+                    "{}\n{}\n",
+                    serde_json::json!({"message": {"role": "assistant", "content": reply}, "done": false}),
+                    serde_json::json!({"done": true})
+                );
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/x-ndjson\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    stream.len(),
+                    stream
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            bodies
+        });
+
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn second_turn_includes_first_answer() {
+        let (url, server) = mock_ollama(vec!["Paris.", "About 2.1 million."]).await;
+        let config = OllamaConfig {
+            url,
+            ..OllamaConfig::default()
+        };
+        let client = Client::new();
+        let mut messages = Vec::new();
+
+        let first = chat_turn(
+            &client,
+            &config,
+            &mut messages,
+            "Capital of France?",
+            Vec::new(),
+            Render::Plain,
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.content, "Paris.");
+        chat_turn(
+            &client,
+            &config,
+            &mut messages,
+            "Population?",
+            Vec::new(),
+            Render::Plain,
+        )
+        .await
+        .unwrap();
+
+        let bodies = server.await.unwrap();
+        let second: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+        let sent: Vec<(&str, &str)> = second["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| (m["role"].as_str().unwrap(), m["content"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                ("user", "Capital of France?"),
+                ("assistant", "Paris."),
+                ("user", "Population?"),
+            ]
+        );
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[3].content, "About 2.1 million.");
+    }
+}
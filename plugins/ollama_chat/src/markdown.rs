@@ -0,0 +1,255 @@
+// Terminal rendering of streamed Markdown replies. Text is rendered a line at a time
+// as it arrives: headings, lists, quotes and inline **bold**/*italic*/`code` are
+// styled with crossterm, fenced code blocks are highlighted with syntect, and table
+// rows are held back until the table ends so the columns can be aligned.
+use crossterm::style::Stylize;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::as_24_bit_terminal_escaped;
+
+const THEME: &str = "base16-ocean.dark";
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    &THEMES.get_or_init(ThemeSet::load_defaults).themes[THEME]
+}
+
+pub struct Renderer {
+    /// Text received since the last newline
+    line: String,
+    /// Highlighter of the fenced code block being rendered
+    code: Option<HighlightLines<'static>>,
+    /// Rows of the table being collected
+    table: Vec<Vec<String>>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            line: String::new(),
+            code: None,
+            table: Vec::new(),
+        }
+    }
+
+    /// Adds streamed text, rendering every line it completes
+    pub fn push(&mut self, text: &str) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        for c in text.chars() {
+            if c == '\n' {
+                let line = std::mem::take(&mut self.line);
+                self.render_line(&mut out, &line)?;
+            } else {
+                self.line.push(c);
+            }
+        }
+        out.flush()
+    }
+
+    /// Renders whatever is still buffered at the end of a reply
+    pub fn finish(&mut self) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.render_line(&mut out, &line)?;
+        }
+        self.flush_table(&mut out)?;
+        if self.code.take().is_some() {
+            // Unterminated code block: reset any colour left behind
+            write!(out, "\x1b[0m")?;
+        }
+        out.flush()
+    }
+
+    fn render_line(&mut self, out: &mut impl Write, line: &str) -> io::Result<()> {
+        let trimmed = line.trim_start();
+
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            self.flush_table(out)?;
+            if self.code.take().is_none() {
+                let syntax = syntaxes()
+                    .find_syntax_by_token(lang.trim())
+                    .unwrap_or_else(|| syntaxes().find_syntax_plain_text());
+                self.code = Some(HighlightLines::new(syntax, theme()));
+            }
+            return writeln!(out, "{}", line.dark_grey());
+        }
+
+        if let Some(highlighter) = &mut self.code {
+            // The newline-aware syntaxes expect each line with its terminator
+            let line = format!("{line}\n");
+            let ranges = highlighter
+                .highlight_line(&line, syntaxes())
+                .map_err(io::Error::other)?;
+            return write!(out, "{}\x1b[0m", as_24_bit_terminal_escaped(&ranges, false));
+        }
+
+        if trimmed.starts_with('|') {
+            self.table.push(split_row(trimmed));
+            return Ok(());
+        }
+        self.flush_table(out)?;
+
+        let indent = &line[..line.len() - trimmed.len()];
+        if let Some((level, title)) = heading(trimmed) {
+            let title = inline(title);
+            if level <= 2 {
+                writeln!(out, "{}", title.bold().underlined().cyan())
+            } else {
+                writeln!(out, "{}", title.bold().cyan())
+            }
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|bullet| trimmed.strip_prefix(bullet))
+        {
+            writeln!(out, "{}{} {}", indent, "•".cyan(), inline(item))
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            writeln!(
+                out,
+                "{}{}",
+                "│ ".dark_grey(),
+                inline(quote.trim_start()).italic()
+            )
+        } else if is_rule(trimmed) {
+            writeln!(out, "{}", "─".repeat(40).dark_grey())
+        } else {
+            writeln!(out, "{}{}", indent, inline(trimmed))
+        }
+    }
+
+    /// Prints the collected table with aligned columns
+    fn flush_table(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.table.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.table);
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut widths = vec![0; columns];
+        for row in rows.iter().filter(|row| !is_separator(row)) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(visible_width(cell));
+            }
+        }
+
+        for (i, row) in rows.iter().enumerate() {
+            if is_separator(row) {
+                let line: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+                writeln!(out, "{}", line.join("┼").dark_grey())?;
+                continue;
+            }
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(col, width)| {
+                    let cell = row.get(col).map(String::as_str).unwrap_or("");
+                    let padding = " ".repeat(width - visible_width(cell));
+                    // The first row is the header
+                    let text = if i == 0 {
+                        inline(cell).bold().to_string()
+                    } else {
+                        inline(cell)
+                    };
+                    format!(" {}{} ", text, padding)
+                })
+                .collect();
+            writeln!(out, "{}", cells.join(&"│".dark_grey().to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let title = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then_some((level, title))
+}
+
+fn is_rule(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&c| line.chars().all(|x| x == c || x == ' '))
+}
+
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim().trim_start_matches('|').trim_end_matches('|');
+    line.split('|')
+        .map(|cell| cell.trim().to_string())
+        .collect()
+}
+
+/// The |---|:---:| row between a table header and its body
+fn is_separator(row: &[String]) -> bool {
+    row.iter()
+        .all(|cell| !cell.is_empty() && cell.chars().all(|c| matches!(c, '-' | ':' | ' ')))
+}
+
+/// Width of a cell once the inline markers are gone
+fn visible_width(cell: &str) -> usize {
+    cell.replace("**", "").replace('`', "").chars().count()
+}
+
+/// Styles **bold**, *italic* and `code` spans. Unmatched markers are kept as typed.
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                out.push_str(&after[..end].yellow().to_string());
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix("**") {
+            if let Some(end) = after.find("**") {
+                out.push_str(&inline(&after[..end]).bold().to_string());
+                rest = &after[end + 2..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('*') {
+            // "* " is a literal asterisk, not the start of an italic span
+            if !after.starts_with(|c: char| c.is_whitespace() || c == '*') {
+                if let Some(end) = after.find('*') {
+                    out.push_str(&inline(&after[..end]).italic().to_string());
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Contents of the fenced code blocks in `text`, in order
+pub fn code_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            match current.take() {
+                Some(lines) => blocks.push(lines.join("\n")),
+                None => current = Some(Vec::new()),
+            }
+        } else if let Some(lines) = &mut current {
+            lines.push(line);
+        }
+    }
+    // An unterminated block (e.g. a stopped reply) still counts
+    if let Some(lines) = current {
+        blocks.push(lines.join("\n"));
+    }
+    blocks
+}
@@ -0,0 +1,243 @@
+// Model management through the Ollama API: list installed models (/api/tags), pull new
+// ones with per-layer progress bars (/api/pull), delete them (/api/delete) and load one
+// into memory ahead of time (/api/generate without a prompt).
+use crate::{http, OllamaConfig, Provider};
+use anyhow::anyhow;
+use clap::{Arg, ArgMatches, Command};
+use futures::StreamExt;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Instant;
+
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<ModelInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelInfo {
+    name: String,
+    size: u64,
+    modified_at: String,
+    details: Option<ModelDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelDetails {
+    parameter_size: Option<String>,
+    quantization_level: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullProgress {
+    #[serde(default)]
+    status: String,
+    digest: Option<String>,
+    total: Option<u64>,
+    completed: Option<u64>,
+    error: Option<String>,
+}
+
+pub fn subcommand() -> Command {
+    let model = || {
+        Arg::new("model")
+            .value_name("MODEL")
+            .required(true)
+            .help("Model name, e.g. llama3.1:8b")
+    };
+    Command::new("models")
+        .about("Manage the models installed in Ollama")
+        .subcommand_required(true)
+        .subcommand(Command::new("list").about("List installed models"))
+        .subcommand(Command::new("pull").about("Download a model").arg(model()))
+        .subcommand(
+            Command::new("rm")
+                .about("Delete an installed model")
+                .arg(model()),
+        )
+}
+
+pub async fn run(config: &OllamaConfig, matches: &ArgMatches) -> anyhow::Result<()> {
+    if config.provider != Provider::Ollama {
+        return Err(anyhow!(
+            "Model management needs the Ollama provider (configured: {:?})",
+            config.provider
+        ));
+    }
+
+    let client = http::client(config)?;
+    match matches.subcommand() {
+        Some(("list", _)) => list(&client, config).await,
+        Some(("pull", args)) => pull(&client, config, model_arg(args)).await,
+        Some(("rm", args)) => remove(&client, config, model_arg(args)).await,
+        _ => unreachable!("clap requires a models subcommand"),
+    }
+}
+
+fn model_arg(matches: &ArgMatches) -> &str {
+    matches
+        .get_one::<String>("model")
+        .expect("model is a required argument")
+}
+
+async fn api_error(response: reqwest::Response) -> anyhow::Error {
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    // Ollama reports errors as {"error": "..."}
+    let message = serde_json::from_str::<serde_json::Value>(&text)
+        .ok()
+        .and_then(|v| v["error"].as_str().map(str::to_string))
+        .unwrap_or(text);
+    anyhow!("Ollama API error ({}): {}", status, message)
+}
+
+async fn list(client: &Client, config: &OllamaConfig) -> anyhow::Result<()> {
+    let response = http::send(config, || client.get(format!("{}/api/tags", config.url))).await?;
+    if !response.status().is_success() {
+        return Err(api_error(response).await);
+    }
+
+    let tags: TagsResponse = response.json().await?;
+    if tags.models.is_empty() {
+        println!("📭 No models installed");
+        println!("💡 Download one with: proxy ollama_chat models pull <model>");
+        return Ok(());
+    }
+
+    println!(
+        "{:<36} {:>10} {:>8} {:<8} MODIFIED",
+        "NAME", "SIZE", "PARAMS", "QUANT"
+    );
+    for model in tags.models {
+        let details = model.details.as_ref();
+        println!(
+            "{:<36} {:>10} {:>8} {:<8} {}",
+            model.name,
+            indicatif::HumanBytes(model.size).to_string(),
+            details
+                .and_then(|d| d.parameter_size.as_deref())
+                .unwrap_or("-"),
+            details
+                .and_then(|d| d.quantization_level.as_deref())
+                .unwrap_or("-"),
+            // RFC 3339 timestamp, trimmed to the minute
+            model.modified_at.get(..16).unwrap_or(&model.modified_at)
+        );
+    }
+    Ok(())
+}
+
+async fn pull(client: &Client, config: &OllamaConfig, model: &str) -> anyhow::Result<()> {
+    let response = http::send(config, || {
+        client
+            .post(format!("{}/api/pull", config.url))
+            .json(&json!({ "model": model, "stream": true }))
+    })
+    .await?;
+    if !response.status().is_success() {
+        return Err(api_error(response).await);
+    }
+
+    println!("📥 Pulling {}", model);
+    let progress = MultiProgress::new();
+    let style = ProgressStyle::with_template(
+        "{msg:<20} [{bar:40.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} ETA {eta}",
+    )?
+    .progress_chars("=> ");
+    // One bar per layer, keyed by digest
+    let mut bars: HashMap<String, ProgressBar> = HashMap::new();
+    let status = progress.add(ProgressBar::new_spinner());
+
+    let mut pending: Vec<u8> = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        pending.extend_from_slice(&chunk?);
+        while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=newline).collect();
+            let Ok(update) = serde_json::from_slice::<PullProgress>(&line) else {
+                continue;
+            };
+            if let Some(error) = update.error {
+                progress.clear()?;
+                return Err(anyhow!("Pull failed: {}", error));
+            }
+
+            match (update.digest, update.total) {
+                (Some(digest), Some(total)) => {
+                    let bar = bars.entry(digest.clone()).or_insert_with(|| {
+                        let bar = progress.insert_before(&status, ProgressBar::new(total));
+                        bar.set_style(style.clone());
+                        // sha256:abcdef... → abcdef012345
+                        let short = digest.trim_start_matches("sha256:");
+                        bar.set_message(short.get(..12).unwrap_or(short).to_string());
+                        bar
+                    });
+                    bar.set_position(update.completed.unwrap_or(0));
+                    if update.completed == Some(total) {
+                        bar.finish();
+                    }
+                }
+                _ => status.set_message(update.status.clone()),
+            }
+
+            if update.status == "success" {
+                status.finish_and_clear();
+                println!("✅ Pulled {}", model);
+                return Ok(());
+            }
+        }
+    }
+
+    status.finish_and_clear();
+    Err(anyhow!(
+        "Pull of {} ended before Ollama reported success",
+        model
+    ))
+}
+
+async fn remove(client: &Client, config: &OllamaConfig, model: &str) -> anyhow::Result<()> {
+    let response = http::send(config, || {
+        client
+            .delete(format!("{}/api/delete", config.url))
+            .json(&json!({ "model": model }))
+    })
+    .await?;
+    if !response.status().is_success() {
+        return Err(api_error(response).await);
+    }
+    println!("🗑️  Deleted {}", model);
+    Ok(())
+}
+
+/// Loads the configured model into memory so the first prompt doesn't wait for it
+pub async fn preload(config: &OllamaConfig) -> anyhow::Result<()> {
+    if config.provider != Provider::Ollama {
+        return Ok(());
+    }
+    println!("⏳ Loading {}...", config.model);
+    let started = Instant::now();
+    let client = http::client(config)?;
+    let mut body = json!({ "model": config.model });
+    if let Some(keep_alive) = &config.keep_alive {
+        body["keep_alive"] = serde_json::to_value(keep_alive)?;
+    }
+    let response = http::send(config, || {
+        client
+            .post(format!("{}/api/generate", config.url))
+            .json(&body)
+    })
+    .await?;
+    if !response.status().is_success() {
+        return Err(api_error(response).await);
+    }
+    println!(
+        "✅ {} loaded in {:.1}s",
+        config.model,
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
@@ -0,0 +1,133 @@
+// Named bundles of system prompt, model and sampling parameters. Presets come from
+// three places, later ones overriding earlier ones with the same name: the built-in
+// library below, `[presets.<name>]` tables in the config file, and one file per preset
+// in ollama_chat.conf.d/presets/<name>.toml.
+use crate::{OllamaConfig, PLUGIN_NAME};
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Preset {
+    pub description: Option<String>,
+    pub system_prompt: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i32>,
+    pub max_tokens: Option<u32>,
+}
+
+impl Preset {
+    /// Overrides the settings the preset defines, leaving the others alone
+    pub fn apply(&self, config: &mut OllamaConfig) {
+        if let Some(system_prompt) = &self.system_prompt {
+            config.system_prompt = Some(system_prompt.clone());
+        }
+        if let Some(model) = &self.model {
+            config.model = model.clone();
+        }
+        config.temperature = self.temperature.or(config.temperature);
+        config.top_p = self.top_p.or(config.top_p);
+        config.top_k = self.top_k.or(config.top_k);
+        config.max_tokens = self.max_tokens.or(config.max_tokens);
+    }
+}
+
+fn builtin() -> BTreeMap<String, Preset> {
+    let preset = |description: &str, system_prompt: &str, temperature: f32| Preset {
+        description: Some(description.to_string()),
+        system_prompt: Some(system_prompt.to_string()),
+        temperature: Some(temperature),
+        ..Preset::default()
+    };
+    BTreeMap::from([
+        (
+            "sre".to_string(),
+            preset(
+                "Incident response and operations",
+                "You are an experienced site reliability engineer. Be concise and \
+                 actionable: give the most likely causes first, the commands to confirm \
+                 them, and call out anything that is risky to run in production.",
+                0.3,
+            ),
+        ),
+        (
+            "code-review".to_string(),
+            preset(
+                "Review code for bugs and clarity",
+                "You are a meticulous senior engineer reviewing code. Point out bugs, \
+                 edge cases, security issues and unclear naming, in order of severity, \
+                 and suggest concrete fixes. Don't restate what the code does.",
+                0.2,
+            ),
+        ),
+        (
+            "shell".to_string(),
+            preset(
+                "Answer with shell commands",
+                "You are a shell expert. Answer with the command(s) that solve the task \
+                 in a single code block, followed by at most two sentences of explanation.",
+                0.1,
+            ),
+        ),
+    ])
+}
+
+/// Files in ollama_chat.conf.d/presets, each defining one preset named after the file
+fn load_files() -> anyhow::Result<BTreeMap<String, Preset>> {
+    let mut presets = BTreeMap::new();
+    let Some(dir) = plugin_api::plugin_config_fragments_dir(PLUGIN_NAME) else {
+        return Ok(presets);
+    };
+    let Ok(entries) = fs::read_dir(dir.join("presets")) else {
+        return Ok(presets);
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let content = plugin_api::read_config(&path)?;
+        let preset: Preset = toml::from_str(&content)
+            .with_context(|| format!("Invalid preset file {}", path.display()))?;
+        presets.insert(name.to_string(), preset);
+    }
+    Ok(presets)
+}
+
+/// Every available preset, by name
+pub fn all(config: &OllamaConfig) -> anyhow::Result<BTreeMap<String, Preset>> {
+    let mut presets = builtin();
+    presets.extend(config.presets.clone());
+    presets.extend(load_files()?);
+    Ok(presets)
+}
+
+pub fn find(config: &OllamaConfig, name: &str) -> anyhow::Result<Preset> {
+    let presets = all(config)?;
+    presets.get(name).cloned().ok_or_else(|| {
+        anyhow!(
+            "Unknown preset '{}', available: {}",
+            name,
+            presets.keys().cloned().collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+pub fn print_presets(config: &OllamaConfig) -> anyhow::Result<()> {
+    println!("🎭 Presets:");
+    for (name, preset) in all(config)? {
+        println!(
+            "   {:<18} {}",
+            name,
+            preset.description.as_deref().unwrap_or("")
+        );
+    }
+    println!();
+    Ok(())
+}
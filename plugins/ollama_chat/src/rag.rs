@@ -0,0 +1,232 @@
+// Local retrieval-augmented chat. `index` splits the files of a directory into chunks,
+// embeds them through Ollama's /api/embeddings and stores the result under the plugin
+// state directory (indexes/<name>.json). With --rag, every message is embedded the same
+// way and the closest chunks are sent along with it.
+use crate::{attach, http, OllamaConfig, Provider, PLUGIN_NAME};
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+pub const DEFAULT_TOP_K: usize = 4;
+/// Chunks are cut at line boundaries once they reach this many characters
+const CHUNK_CHARS: usize = 1500;
+/// Lines repeated at the start of the next chunk so context isn't cut mid-thought
+const OVERLAP_LINES: usize = 3;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Index {
+    pub name: String,
+    pub model: String,
+    pub root: PathBuf,
+    pub created_at: DateTime<Utc>,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Chunk {
+    pub path: PathBuf,
+    /// First line of the chunk, 1-based
+    pub line: usize,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+fn indexes_dir() -> anyhow::Result<PathBuf> {
+    plugin_api::plugin_state_dir(PLUGIN_NAME)
+        .map(|dir| dir.join("indexes"))
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))
+}
+
+fn index_path(name: &str) -> anyhow::Result<PathBuf> {
+    crate::session::validate_name(name)?;
+    Ok(indexes_dir()?.join(format!("{name}.json")))
+}
+
+/// Embeddings always come from Ollama, even when chatting with a hosted provider
+fn embeddings_url(config: &OllamaConfig) -> String {
+    match config.provider {
+        Provider::Ollama => config.url.clone(),
+        _ => Provider::Ollama.default_url().to_string(),
+    }
+}
+
+pub fn embedding_model(config: &OllamaConfig) -> String {
+    config
+        .embedding_model
+        .clone()
+        .unwrap_or_else(|| DEFAULT_EMBEDDING_MODEL.to_string())
+}
+
+pub async fn embed(
+    client: &Client,
+    config: &OllamaConfig,
+    model: &str,
+    text: &str,
+) -> anyhow::Result<Vec<f32>> {
+    let response = http::send(config, || {
+        client
+            .post(format!("{}/api/embeddings", embeddings_url(config)))
+            .json(&json!({ "model": model, "prompt": text }))
+    })
+    .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Ollama API error ({}): {}", status, error_text));
+    }
+    Ok(response.json::<EmbeddingResponse>().await?.embedding)
+}
+
+/// Splits a file into chunks of roughly CHUNK_CHARS, returning (first line, text)
+fn split(content: &str) -> Vec<(usize, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start;
+        let mut size = 0;
+        while end < lines.len() && (end == start || size + lines[end].len() < CHUNK_CHARS) {
+            size += lines[end].len() + 1;
+            end += 1;
+        }
+        let text = lines[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push((start + 1, text));
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = end.saturating_sub(OVERLAP_LINES).max(start + 1);
+    }
+    chunks
+}
+
+/// Builds (or rebuilds) the index `name` from the files under `root`
+pub async fn build(config: &OllamaConfig, root: &Path, name: &str) -> anyhow::Result<()> {
+    let path = index_path(name)?;
+    let files = attach::collect_limited(&root.to_string_lossy(), None, 0)?;
+    let pieces: Vec<(PathBuf, usize, String)> = files
+        .iter()
+        .flat_map(|file| {
+            split(&file.content)
+                .into_iter()
+                .map(|(line, text)| (file.path.clone(), line, text))
+        })
+        .collect();
+
+    let model = embedding_model(config);
+    println!(
+        "🔎 Indexing {} chunks from {} files with {}",
+        pieces.len(),
+        files.len(),
+        model
+    );
+    let progress = ProgressBar::new(pieces.len() as u64);
+    progress.set_style(ProgressStyle::with_template(
+        "[{bar:40.cyan/blue}] {pos}/{len} {elapsed_precise} ETA {eta}",
+    )?);
+
+    let client = http::client(config)?;
+    let mut chunks = Vec::with_capacity(pieces.len());
+    for (file, line, text) in pieces {
+        // Include the path so questions naming a file find its chunks
+        let embedding = embed(
+            &client,
+            config,
+            &model,
+            &format!("{}\n{}", file.display(), text),
+        )
+        .await
+        .with_context(|| format!("Failed to embed {}:{}", file.display(), line))?;
+        chunks.push(Chunk {
+            path: file,
+            line,
+            text,
+            embedding,
+        });
+        progress.inc(1);
+    }
+    progress.finish_and_clear();
+
+    let index = Index {
+        name: name.to_string(),
+        model,
+        root: root.canonicalize().unwrap_or_else(|_| root.to_path_buf()),
+        created_at: Utc::now(),
+        chunks,
+    };
+    fs::create_dir_all(indexes_dir()?)?;
+    fs::write(&path, serde_json::to_string(&index)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("✅ Index '{}' written to {}", name, path.display());
+    println!("💡 Chat with it using: proxy ollama_chat --rag {}", name);
+    Ok(())
+}
+
+pub fn load(name: &str) -> anyhow::Result<Index> {
+    let path = index_path(name)?;
+    let content = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "No index named '{}', create it with: proxy ollama_chat index <dir> --name {}",
+            name, name
+        )
+    })?;
+    serde_json::from_str(&content).with_context(|| format!("Corrupt index file {}", path.display()))
+}
+
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+impl Index {
+    /// Returns the excerpts most relevant to `query`, wrapped for the prompt
+    pub async fn retrieve(
+        &self,
+        client: &Client,
+        config: &OllamaConfig,
+        query: &str,
+    ) -> anyhow::Result<String> {
+        let query = embed(client, config, &self.model, query).await?;
+        let mut scored: Vec<(f32, &Chunk)> = self
+            .chunks
+            .iter()
+            .map(|chunk| (cosine(&query, &chunk.embedding), chunk))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut out = format!(
+            "Relevant excerpts from {} (use them if they help answer the question):\n",
+            self.root.display()
+        );
+        for (_, chunk) in scored
+            .iter()
+            .take(config.rag_top_k.unwrap_or(DEFAULT_TOP_K))
+        {
+            out.push_str(&format!(
+                "\n<excerpt path=\"{}\" line=\"{}\">\n{}\n</excerpt>\n",
+                chunk.path.display(),
+                chunk.line,
+                chunk.text
+            ));
+        }
+        Ok(out)
+    }
+}
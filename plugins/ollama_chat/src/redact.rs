@@ -0,0 +1,131 @@
+// Outbound secret guard. Messages are scanned for things that look like credentials
+// (cloud keys, tokens, private key blocks) before they leave the machine. By default
+// matches are replaced with a [REDACTED ...] marker; with redact = "confirm" the user
+// decides whether to send them as they are, and "off" disables the scan. The full-screen
+// chat can't ask, so there "confirm" redacts.
+use crate::{tui, OllamaConfig};
+use regex::Regex;
+use serde::Deserialize;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::OnceLock;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactMode {
+    #[default]
+    Redact,
+    Confirm,
+    Off,
+}
+
+/// What to look for. For patterns with a capture group only the group is replaced, so
+/// "Authorization: Bearer" stays readable.
+const PATTERNS: &[(&str, &str)] = &[
+    (
+        "private key",
+        r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+    ),
+    ("AWS access key", r"\b((?:AKIA|ASIA)[0-9A-Z]{16})\b"),
+    (
+        "AWS secret key",
+        r#"(?i)aws_secret_access_key["']?\s*[:=]\s*["']?([A-Za-z0-9/+=]{40})"#,
+    ),
+    (
+        "bearer token",
+        r"(?i)\bbearer\s+([A-Za-z0-9\-._~+/]{16,}=*)",
+    ),
+    (
+        "GitHub token",
+        r"\b((?:ghp|gho|ghu|ghs|ghr)_[A-Za-z0-9]{36,})\b",
+    ),
+    ("GitHub token", r"\b(github_pat_[A-Za-z0-9_]{22,})\b"),
+    ("Slack token", r"\b(xox[abprs]-[A-Za-z0-9-]{10,})\b"),
+    ("API key", r"\b(sk-(?:ant-|proj-)?[A-Za-z0-9_\-]{20,})\b"),
+    ("Google API key", r"\b(AIza[0-9A-Za-z_\-]{35})\b"),
+    (
+        "JWT",
+        r"\b(eyJ[A-Za-z0-9_\-]{10,}\.eyJ[A-Za-z0-9_\-]{10,}\.[A-Za-z0-9_\-]{10,})\b",
+    ),
+    (
+        "password",
+        r#"(?i)\b(?:password|passwd|secret|api_key|apikey|token)["']?\s*[:=]\s*["']?([^\s"',;\[]{8,})"#,
+    ),
+];
+
+fn patterns() -> &'static [(&'static str, Regex)] {
+    static COMPILED: OnceLock<Vec<(&'static str, Regex)>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        PATTERNS
+            .iter()
+            .map(|(kind, pattern)| (*kind, Regex::new(pattern).expect("valid secret pattern")))
+            .collect()
+    })
+}
+
+/// Replaces every match with a marker, returning the new text and the kinds found
+fn redact(text: &str) -> (String, Vec<&'static str>) {
+    let mut text = text.to_string();
+    let mut found = Vec::new();
+    for (kind, regex) in patterns() {
+        let mut hit = false;
+        text = regex
+            .replace_all(&text, |caps: &regex::Captures| {
+                hit = true;
+                let marker = format!("[REDACTED {}]", kind);
+                match caps.get(1) {
+                    Some(secret) => {
+                        let whole = caps.get(0).expect("group 0 always matches");
+                        let start = secret.start() - whole.start();
+                        let end = secret.end() - whole.start();
+                        let whole = whole.as_str();
+                        format!("{}{}{}", &whole[..start], marker, &whole[end..])
+                    }
+                    None => marker,
+                }
+            })
+            .into_owned();
+        if hit && !found.contains(kind) {
+            found.push(*kind);
+        }
+    }
+    (text, found)
+}
+
+/// Asks whether to send the secrets unredacted; anything but yes means no
+fn confirm(found: &[&str]) -> bool {
+    // The full-screen chat reads the keys in raw mode, and its event loop waits for this
+    // request, so a prompt would hang it
+    if tui::active() || !io::stdin().is_terminal() {
+        return false;
+    }
+    eprint!(
+        "⚠️  The message looks like it contains secrets ({}). Send them unredacted? [y/N] ",
+        found.join(", ")
+    );
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+/// Applies the configured redaction to outgoing text
+pub fn guard(config: &OllamaConfig, text: &str) -> String {
+    let mode = config.redact.unwrap_or_default();
+    if mode == RedactMode::Off {
+        return text.to_string();
+    }
+    let (redacted, found) = redact(text);
+    if found.is_empty() {
+        return redacted;
+    }
+    if mode == RedactMode::Confirm && confirm(&found) {
+        return text.to_string();
+    }
+    tui::warn(&format!(
+        "🔒 Redacted {} before sending (set redact = \"off\" or \"confirm\" to change this)",
+        found.join(", ")
+    ));
+    redacted
+}
@@ -0,0 +1,137 @@
+// Semantic search across saved sessions for /search. Every question and its answer in
+// a saved session is embedded like a --rag chunk; the embeddings are cached per session
+// under the state directory (search/<name>.json) and rebuilt when the session changes.
+use crate::{rag, session, OllamaConfig, PLUGIN_NAME};
+use anyhow::anyhow;
+use chrono::{DateTime, Local, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+const RESULTS: usize = 5;
+/// Exchanges are cut to this length before embedding
+const EMBED_CHARS: usize = 2000;
+const SNIPPET_CHARS: usize = 160;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Cache {
+    model: String,
+    /// updated_at of the session the cache was built from
+    session_updated_at: DateTime<Utc>,
+    exchanges: Vec<Exchange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Exchange {
+    question: String,
+    answer: String,
+    at: Option<DateTime<Utc>>,
+    embedding: Vec<f32>,
+}
+
+fn cache_path(name: &str) -> anyhow::Result<PathBuf> {
+    plugin_api::plugin_state_dir(PLUGIN_NAME)
+        .map(|dir| dir.join("search").join(format!("{name}.json")))
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))
+}
+
+/// Loads the cached embeddings of a session, embedding it again if it changed
+async fn exchanges(
+    client: &Client,
+    config: &OllamaConfig,
+    model: &str,
+    session: &session::Session,
+) -> anyhow::Result<Vec<Exchange>> {
+    let path = cache_path(&session.name)?;
+    if let Some(cache) = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Cache>(&content).ok())
+    {
+        if cache.model == model && cache.session_updated_at == session.updated_at {
+            return Ok(cache.exchanges);
+        }
+    }
+
+    let mut exchanges = Vec::new();
+    let messages = &session.messages;
+    for (i, message) in messages.iter().enumerate() {
+        if message.role != "user" {
+            continue;
+        }
+        let Some(answer) = messages.get(i + 1).filter(|m| m.role == "assistant") else {
+            continue;
+        };
+        let text: String = format!("Q: {}\nA: {}", message.content, answer.content)
+            .chars()
+            .take(EMBED_CHARS)
+            .collect();
+        exchanges.push(Exchange {
+            question: message.content.clone(),
+            answer: answer.content.clone(),
+            at: message.at,
+            embedding: rag::embed(client, config, model, &text).await?,
+        });
+    }
+
+    let cache = Cache {
+        model: model.to_string(),
+        session_updated_at: session.updated_at,
+        exchanges,
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, serde_json::to_string(&cache)?)?;
+    Ok(cache.exchanges)
+}
+
+fn snippet(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// Prints the saved exchanges closest to `query`
+pub async fn search(client: &Client, config: &OllamaConfig, query: &str) -> anyhow::Result<()> {
+    let sessions = session::list()?;
+    if sessions.is_empty() {
+        println!("📭 No saved sessions to search\n");
+        return Ok(());
+    }
+    let model = rag::embedding_model(config);
+    let query = rag::embed(client, config, &model, query).await?;
+
+    println!("🔎 Searching {} saved session(s)...", sessions.len());
+    let mut results = Vec::new();
+    for session in &sessions {
+        for exchange in exchanges(client, config, &model, session).await? {
+            let score = rag::cosine(&query, &exchange.embedding);
+            results.push((score, session, exchange));
+        }
+    }
+    results.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    if results.is_empty() {
+        println!("📭 The saved sessions contain no exchanges yet\n");
+        return Ok(());
+    }
+    println!();
+    for (score, session, exchange) in results.iter().take(RESULTS) {
+        let when = exchange
+            .at
+            .unwrap_or(session.updated_at)
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M");
+        match &session.title {
+            Some(title) => println!("📂 {} ({}) · {} · {:.2}", session.name, title, when, score),
+            None => println!("📂 {} · {} · {:.2}", session.name, when, score),
+        }
+        println!("   🧑 {}", snippet(&exchange.question));
+        println!("   🤖 {}\n", snippet(&exchange.answer));
+    }
+    println!("💡 Open one with: /load <name>\n");
+    Ok(())
+}
@@ -0,0 +1,172 @@
+// Saved conversations, stored as JSON files under the plugin state directory
+// (~/.cohandv/proxy/state/ollama_chat/sessions/<name>.json).
+use crate::{Message, PLUGIN_NAME};
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub name: String,
+    /// Short description generated by the model for sessions saved without a name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub model: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub messages: Vec<Message>,
+}
+
+impl Session {
+    pub fn new(name: &str, model: &str, messages: Vec<Message>) -> Self {
+        let now = Utc::now();
+        Self {
+            name: name.to_string(),
+            title: None,
+            model: model.to_string(),
+            created_at: now,
+            updated_at: now,
+            messages,
+        }
+    }
+}
+
+pub fn sessions_dir() -> anyhow::Result<PathBuf> {
+    plugin_api::plugin_state_dir(PLUGIN_NAME)
+        .map(|dir| dir.join("sessions"))
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))
+}
+
+/// Session names become file names, so keep them to a safe character set
+pub fn validate_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Invalid session name '{}': use letters, digits, '-', '_' and '.'",
+            name
+        ))
+    }
+}
+
+fn session_path(name: &str) -> anyhow::Result<PathBuf> {
+    validate_name(name)?;
+    Ok(sessions_dir()?.join(format!("{name}.json")))
+}
+
+pub fn exists(name: &str) -> bool {
+    session_path(name).is_ok_and(|path| path.exists())
+}
+
+/// Turns a title into a session name that isn't taken yet
+pub fn name_from_title(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(40).collect();
+    let slug = slug.trim_matches('-');
+    let base = if slug.is_empty() {
+        format!("session-{}", Local::now().format("%Y%m%d-%H%M%S"))
+    } else {
+        slug.to_string()
+    };
+    let mut name = base.clone();
+    let mut n = 2;
+    while exists(&name) {
+        name = format!("{}-{}", base, n);
+        n += 1;
+    }
+    name
+}
+
+/// Writes the session, keeping the original creation time when overwriting
+pub fn save(session: &mut Session) -> anyhow::Result<PathBuf> {
+    let path = session_path(&session.name)?;
+    if let Ok(previous) = load(&session.name) {
+        session.created_at = previous.created_at;
+    }
+    session.updated_at = Utc::now();
+
+    fs::create_dir_all(sessions_dir()?)?;
+    fs::write(&path, serde_json::to_string_pretty(session)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+pub fn load(name: &str) -> anyhow::Result<Session> {
+    let path = session_path(name)?;
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("No saved session named '{}'", name))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Corrupt session file {}", path.display()))
+}
+
+/// Returns every saved session, most recently updated first
+pub fn list() -> anyhow::Result<Vec<Session>> {
+    let dir = sessions_dir()?;
+    let mut sessions = Vec::new();
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(serde_json::from_str::<Session>(&content)?))
+            {
+                Ok(session) => sessions.push(session),
+                Err(e) => eprintln!("⚠️  Skipping {}: {}", path.display(), e),
+            }
+        }
+    }
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_at));
+    Ok(sessions)
+}
+
+pub fn print_sessions() -> anyhow::Result<()> {
+    let sessions = list()?;
+    if sessions.is_empty() {
+        println!("📭 No saved sessions in {}", sessions_dir()?.display());
+        return Ok(());
+    }
+
+    println!("💾 Saved sessions:\n");
+    println!(
+        "{:<28} {:<20} {:>9}  {:<16}  TITLE",
+        "NAME", "MODEL", "MESSAGES", "UPDATED"
+    );
+    for session in sessions {
+        let turns = session
+            .messages
+            .iter()
+            .filter(|m| m.role != "system")
+            .count();
+        println!(
+            "{:<28} {:<20} {:>9}  {:<16}  {}",
+            session.name,
+            session.model,
+            turns,
+            session
+                .updated_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            session.title.as_deref().unwrap_or("")
+        );
+    }
+    println!("\n💡 Resume with: proxy ollama_chat --session <name>");
+    Ok(())
+}
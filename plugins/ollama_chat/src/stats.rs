@@ -0,0 +1,83 @@
+// Token usage and timing. Ollama reports token counts and the generation time in the
+// final message of a stream; the total latency is measured on our side so it also
+// covers model loading and the network.
+use std::time::Duration;
+
+/// Counters reported by the backend. Providers that don't report them leave them unset.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Usage {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    /// Time spent generating the completion tokens
+    pub generation: Option<Duration>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TurnStats {
+    pub usage: Usage,
+    /// From sending the request until the last token arrived
+    pub latency: Duration,
+}
+
+impl TurnStats {
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let tokens = self.usage.completion_tokens?;
+        let secs = self.usage.generation?.as_secs_f64();
+        (secs > 0.0).then(|| tokens as f64 / secs)
+    }
+
+    pub fn print(&self) {
+        let mut parts = Vec::new();
+        if let Some(tokens) = self.usage.prompt_tokens {
+            parts.push(format!("{} in", tokens));
+        }
+        if let Some(tokens) = self.usage.completion_tokens {
+            parts.push(format!("{} out", tokens));
+        }
+        if let Some(rate) = self.tokens_per_second() {
+            parts.push(format!("{:.1} tok/s", rate));
+        }
+        parts.push(format!("{:.1}s", self.latency.as_secs_f64()));
+        println!("📊 {}", parts.join(" · "));
+    }
+}
+
+/// Running totals for the whole chat session
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    turns: u32,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    generation: Duration,
+    latency: Duration,
+}
+
+impl SessionStats {
+    pub fn add(&mut self, turn: &TurnStats) {
+        self.turns += 1;
+        self.prompt_tokens += turn.usage.prompt_tokens.unwrap_or(0);
+        self.completion_tokens += turn.usage.completion_tokens.unwrap_or(0);
+        self.generation += turn.usage.generation.unwrap_or_default();
+        self.latency += turn.latency;
+    }
+
+    pub fn print_summary(&self) {
+        if self.turns == 0 {
+            return;
+        }
+        println!(
+            "📊 Session: {} replies, {} tokens in, {} tokens out, {:.1}s total",
+            self.turns,
+            self.prompt_tokens,
+            self.completion_tokens,
+            self.latency.as_secs_f64()
+        );
+        let secs = self.generation.as_secs_f64();
+        if secs > 0.0 {
+            println!(
+                "   Average speed: {:.1} tok/s",
+                self.completion_tokens as f64 / secs
+            );
+        }
+    }
+}
@@ -0,0 +1,68 @@
+// Structured output. With a format set, Ollama is asked for JSON (or for JSON matching a
+// schema), every reply is checked locally, and a reply that doesn't parse or validate is
+// sent back to the model with a description of the problem.
+use anyhow::Context;
+use serde_json::Value;
+use std::fs;
+
+/// How often the model may correct itself before the reply is rejected
+pub const MAX_CORRECTIONS: u32 = 2;
+
+/// Parses a --format or /format argument: "json", or the path of a JSON Schema file
+pub fn parse_format(arg: &str) -> anyhow::Result<Value> {
+    if arg.eq_ignore_ascii_case("json") {
+        return Ok(Value::String("json".to_string()));
+    }
+    let content =
+        fs::read_to_string(arg).with_context(|| format!("Failed to read schema {}", arg))?;
+    let schema: Value =
+        serde_json::from_str(&content).with_context(|| format!("{} is not valid JSON", arg))?;
+    jsonschema::validator_for(&schema)
+        .map_err(|e| anyhow::anyhow!("{} is not a valid JSON Schema: {}", arg, e))?;
+    Ok(schema)
+}
+
+/// Models sometimes wrap JSON in a ```json fence despite being asked not to
+pub fn strip_fences(reply: &str) -> &str {
+    let trimmed = reply.trim();
+    trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(|inner| inner.trim_start_matches("json").trim())
+        .unwrap_or(trimmed)
+}
+
+/// Checks a reply against the requested format. The error describes the problem in a
+/// way that can be handed back to the model.
+pub fn check(format: &Value, reply: &str) -> Result<(), String> {
+    let value: Value = serde_json::from_str(strip_fences(reply))
+        .map_err(|e| format!("it is not valid JSON ({})", e))?;
+    if format.is_object() {
+        let validator = jsonschema::validator_for(format)
+            .map_err(|e| format!("the schema is invalid ({})", e))?;
+        validator.validate(&value).map_err(|e| {
+            format!(
+                "it does not match the JSON Schema ({} at '{}')",
+                e, e.instance_path
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Follow-up message asking the model to fix its previous reply
+pub fn correction(problem: &str) -> String {
+    format!(
+        "Your previous reply was rejected because {}. Reply again with only the corrected \
+         JSON value: no explanations and no code fences.",
+        problem
+    )
+}
+
+pub fn describe(format: &Value) -> String {
+    if format.is_object() {
+        "JSON matching a schema".to_string()
+    } else {
+        "JSON".to_string()
+    }
+}
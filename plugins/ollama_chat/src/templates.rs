@@ -0,0 +1,91 @@
+// Prompt templates for one-shot mode. A template is a text file with {{name}}
+// placeholders, looked up in ollama_chat.conf.d/templates/<name>.tmpl (or given as a
+// path), and filled from --var name=value flags. Values starting with '@' are read from
+// a file, and piped stdin is available as {{stdin}}.
+use crate::PLUGIN_NAME;
+use anyhow::{anyhow, Context};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn templates_dir() -> Option<PathBuf> {
+    plugin_api::plugin_config_fragments_dir(PLUGIN_NAME).map(|dir| dir.join("templates"))
+}
+
+pub fn load(name: &str) -> anyhow::Result<String> {
+    let path = if Path::new(name).is_file() {
+        PathBuf::from(name)
+    } else {
+        templates_dir()
+            .map(|dir| dir.join(format!("{name}.tmpl")))
+            .filter(|path| path.is_file())
+            .ok_or_else(|| {
+                anyhow!(
+                    "No template named '{}' (looked in {})",
+                    name,
+                    templates_dir()
+                        .map(|dir| dir.display().to_string())
+                        .unwrap_or_else(|| "the config directory".to_string())
+                )
+            })?
+    };
+    fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Parses a --var flag: name=value, or name=@file to use the file's content
+pub fn parse_var(arg: &str) -> anyhow::Result<(String, String)> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid --var '{}', expected name=value", arg))?;
+    let value = match value.strip_prefix('@') {
+        Some(file) => {
+            fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?
+        }
+        None => value.to_string(),
+    };
+    Ok((name.trim().to_string(), value))
+}
+
+/// Names of the placeholders used in the template
+pub fn placeholders(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim().to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &rest[start + end + 2..];
+    }
+    names
+}
+
+/// Fills every placeholder, failing with the list of variables that weren't given
+pub fn render(template: &str, vars: &HashMap<String, String>) -> anyhow::Result<String> {
+    let missing: Vec<String> = placeholders(template)
+        .into_iter()
+        .filter(|name| !vars.contains_key(name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "Missing template variables: {} (pass them with --var name=value)",
+            missing.join(", ")
+        ));
+    }
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&vars[rest[start + 2..start + end].trim()]);
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
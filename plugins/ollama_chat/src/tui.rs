@@ -0,0 +1,417 @@
+// Full-screen chat for --tui: a scrollable conversation pane, an input box and a status
+// bar with the model, token totals and the last reply's latency. Replies are streamed
+// into the pane through a channel fed by `forward`, while the event loop keeps drawing
+// and reading keys so scrolling and Esc keep working during a reply. Notices printed
+// during a request (retries, saved files, redactions) go through `say` and `warn`, which
+// put them in the pane instead of over the screen.
+use crate::chat::Chat;
+use crate::{compare, CANCEL};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers, MouseEventKind,
+};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::io::{self, Stdout};
+use std::panic;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/// What is sent to the pane while a request is running
+enum Piece {
+    /// Streamed reply text
+    Text(String),
+    /// A notice, shown as an entry of its own
+    Note(String),
+}
+
+/// Where streamed reply text and notices go while a request is running
+static SINK: Mutex<Option<UnboundedSender<Piece>>> = Mutex::new(None);
+
+const TICK: Duration = Duration::from_millis(50);
+const PAGE: usize = 10;
+
+/// Whether a request of the full-screen chat is running, which owns the terminal
+pub(crate) fn active() -> bool {
+    SINK.lock().expect("sink lock").is_some()
+}
+
+/// Called by the reply output for every streamed piece of text
+pub(crate) fn forward(text: &str) {
+    if let Some(sink) = SINK.lock().expect("sink lock").as_ref() {
+        let _ = sink.send(Piece::Text(text.to_string()));
+    }
+}
+
+/// Hands a notice to the pane; false when no request of the full-screen chat is running
+fn note(text: &str) -> bool {
+    match SINK.lock().expect("sink lock").as_ref() {
+        Some(sink) => sink.send(Piece::Note(text.trim().to_string())).is_ok(),
+        None => false,
+    }
+}
+
+/// Prints a notice on stdout, or shows it in the full-screen chat
+pub(crate) fn say(text: &str) {
+    if !note(text) {
+        println!("{}", text);
+    }
+}
+
+/// Prints a notice on stderr, or shows it in the full-screen chat
+pub(crate) fn warn(text: &str) {
+    if !note(text) {
+        eprintln!("{}", text);
+    }
+}
+
+enum Speaker {
+    User,
+    Assistant,
+    Info,
+    Error,
+}
+
+struct Entry {
+    speaker: Speaker,
+    text: String,
+}
+
+#[derive(Default)]
+struct App {
+    transcript: Vec<Entry>,
+    /// Text of the reply being streamed
+    streaming: Option<String>,
+    input: String,
+    /// Lines scrolled up from the bottom of the conversation
+    scroll: usize,
+    tokens_in: u64,
+    tokens_out: u64,
+    latency: Option<Duration>,
+    model: String,
+}
+
+enum Action {
+    None,
+    Submit(String),
+    Cancel,
+    Quit,
+}
+
+impl App {
+    fn push(&mut self, speaker: Speaker, text: impl Into<String>) {
+        self.transcript.push(Entry {
+            speaker,
+            text: text.into(),
+        });
+        self.scroll = 0;
+    }
+
+    fn handle(&mut self, event: Event, busy: bool) -> Action {
+        match event {
+            Event::Mouse(mouse) => match mouse.kind {
+                MouseEventKind::ScrollUp => self.scroll += 3,
+                MouseEventKind::ScrollDown => self.scroll = self.scroll.saturating_sub(3),
+                _ => {}
+            },
+            Event::Key(key) if key.kind != KeyEventKind::Release => return self.key(key, busy),
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn key(&mut self, key: KeyEvent, busy: bool) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return if busy { Action::Cancel } else { Action::Quit },
+            KeyCode::Char('c') if ctrl => return if busy { Action::Cancel } else { Action::Quit },
+            KeyCode::Char('d') if ctrl && !busy && self.input.is_empty() => return Action::Quit,
+            KeyCode::Char('u') if ctrl => self.input.clear(),
+            KeyCode::Char(c) if !ctrl => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter if !busy && !self.input.trim().is_empty() => {
+                let input = std::mem::take(&mut self.input);
+                return Action::Submit(input.trim().to_string());
+            }
+            KeyCode::Up => self.scroll += 1,
+            KeyCode::Down => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::PageUp => self.scroll += PAGE,
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(PAGE),
+            KeyCode::End => self.scroll = 0,
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn lines(&self, width: usize) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        let streaming = self.streaming.as_ref().map(|text| Entry {
+            speaker: Speaker::Assistant,
+            text: if text.is_empty() {
+                "…".to_string()
+            } else {
+                text.clone()
+            },
+        });
+        for entry in self.transcript.iter().chain(streaming.as_ref()) {
+            let (title, color) = match entry.speaker {
+                Speaker::User => ("🧑 You".to_string(), Color::Cyan),
+                Speaker::Assistant => (format!("🤖 {}", self.model), Color::Green),
+                Speaker::Info => ("ℹ️  Info".to_string(), Color::Yellow),
+                Speaker::Error => ("❌ Error".to_string(), Color::Red),
+            };
+            lines.push(Line::from(Span::styled(
+                title,
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            )));
+            lines.extend(
+                compare::wrap(entry.text.trim_end(), width)
+                    .into_iter()
+                    .map(Line::from),
+            );
+            lines.push(Line::default());
+        }
+        lines
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [conversation, input, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let width = conversation.width.saturating_sub(2).max(1) as usize;
+        let height = conversation.height.saturating_sub(2) as usize;
+        let lines = self.lines(width);
+        let max_scroll = lines.len().saturating_sub(height);
+        self.scroll = self.scroll.min(max_scroll);
+        let top = (max_scroll - self.scroll) as u16;
+        let title = if self.scroll > 0 {
+            format!(" Conversation (↑{}, End to follow) ", self.scroll)
+        } else {
+            " Conversation ".to_string()
+        };
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .scroll((top, 0)),
+            conversation,
+        );
+
+        // Keep the end of long input visible
+        let inner = input.width.saturating_sub(2) as usize;
+        let chars = self.input.chars().count();
+        let visible: String = self
+            .input
+            .chars()
+            .skip(chars.saturating_sub(inner.saturating_sub(1)))
+            .collect();
+        let hint = if self.streaming.is_some() {
+            " Esc stops the reply "
+        } else {
+            " Enter sends · Esc quits · ↑/↓ PgUp/PgDn scroll "
+        };
+        frame.render_widget(
+            Paragraph::new(visible.as_str())
+                .block(Block::default().borders(Borders::ALL).title(hint)),
+            input,
+        );
+        frame.set_cursor_position(Position::new(
+            input.x + 1 + visible.chars().count() as u16,
+            input.y + 1,
+        ));
+
+        let mut parts = vec![
+            format!("🤖 {}", self.model),
+            format!("{} tokens in", self.tokens_in),
+            format!("{} out", self.tokens_out),
+        ];
+        if let Some(latency) = self.latency {
+            parts.push(format!("last reply {:.1}s", latency.as_secs_f64()));
+        }
+        if self.streaming.is_some() {
+            parts.push("⏳ generating".to_string());
+        }
+        frame.render_widget(
+            Paragraph::new(format!(" {}", parts.join(" · ")))
+                .style(Style::default().add_modifier(Modifier::REVERSED)),
+            status,
+        );
+    }
+}
+
+/// Reads every pending terminal event
+fn pending_events() -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    while event::poll(Duration::ZERO)? {
+        events.push(event::read()?);
+    }
+    Ok(events)
+}
+
+/// Handles input starting with '/'. Commands that print to the terminal are only
+/// available in the line-based chat.
+fn command(chat: &mut Chat, app: &mut App, input: &str) -> bool {
+    match input {
+        "/exit" | "/quit" => return false,
+        "/clear" => {
+            chat.reset();
+            app.transcript.clear();
+            app.push(Speaker::Info, "Conversation cleared");
+        }
+        "/help" | "/?" => app.push(
+            Speaker::Info,
+            "/clear resets the conversation, /exit leaves. Other commands are available \
+             in the line-based chat (without --tui).",
+        ),
+        other => app.push(
+            Speaker::Error,
+            format!(
+                "{} is not available in the full-screen chat, try /help",
+                other.split_whitespace().next().unwrap_or(other)
+            ),
+        ),
+    }
+    true
+}
+
+async fn send(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    chat: &mut Chat,
+    app: &mut App,
+    input: String,
+) -> anyhow::Result<bool> {
+    app.push(Speaker::User, input.clone());
+    app.streaming = Some(String::new());
+    let (tx, mut rx) = unbounded_channel();
+    *SINK.lock().expect("sink lock") = Some(tx);
+
+    let mut quit = false;
+    let mut tick = tokio::time::interval(TICK);
+    let result = {
+        let reply = chat.send(&input);
+        tokio::pin!(reply);
+        loop {
+            tokio::select! {
+                result = &mut reply => break result,
+                Some(piece) = rx.recv() => match piece {
+                    Piece::Text(text) => {
+                        if let Some(streaming) = &mut app.streaming {
+                            streaming.push_str(&text);
+                        }
+                    }
+                    Piece::Note(text) => app.push(Speaker::Info, text),
+                },
+                _ = tick.tick() => {
+                    for event in pending_events()? {
+                        match app.handle(event, true) {
+                            Action::Cancel => CANCEL.store(true, Ordering::SeqCst),
+                            Action::Quit => {
+                                quit = true;
+                                CANCEL.store(true, Ordering::SeqCst);
+                            }
+                            _ => {}
+                        }
+                    }
+                    terminal.draw(|frame| app.draw(frame))?;
+                }
+            }
+        }
+    };
+    *SINK.lock().expect("sink lock") = None;
+    app.streaming = None;
+
+    match result {
+        Ok(reply) => {
+            app.tokens_in += reply.stats.usage.prompt_tokens.unwrap_or(0);
+            app.tokens_out += reply.stats.usage.completion_tokens.unwrap_or(0);
+            app.latency = Some(reply.stats.latency);
+            app.push(Speaker::Assistant, reply.content);
+        }
+        Err(e) => app.push(Speaker::Error, e.to_string()),
+    }
+    // Anything written past `say` and `warn` (a failed desktop notification) would
+    // otherwise stay on screen
+    terminal.clear()?;
+    Ok(!quit)
+}
+
+async fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    chat: &mut Chat,
+) -> anyhow::Result<()> {
+    let mut app = App {
+        model: chat.config.model.clone(),
+        ..App::default()
+    };
+    // Show a resumed session
+    for message in &chat.messages {
+        match message.role.as_str() {
+            "user" => app.push(Speaker::User, message.content.clone()),
+            "assistant" => app.push(Speaker::Assistant, message.content.clone()),
+            _ => {}
+        }
+    }
+
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let keep_going = match app.handle(event::read()?, false) {
+            Action::Quit => false,
+            Action::Submit(input) if input.starts_with('/') => command(chat, &mut app, &input),
+            Action::Submit(input) => send(terminal, chat, &mut app, input).await?,
+            _ => true,
+        };
+        if !keep_going {
+            return Ok(());
+        }
+    }
+}
+
+fn restore() -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)
+}
+
+/// Runs the chat full-screen until the user leaves
+pub async fn run(chat: &mut Chat) -> anyhow::Result<()> {
+    // A panic would otherwise leave the shell in raw mode, its message lost on the
+    // alternate screen
+    let previous = Arc::new(panic::take_hook());
+    let hook = previous.clone();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        hook(info);
+    }));
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    let result = match Terminal::new(CrosstermBackend::new(io::stdout())) {
+        Ok(mut terminal) => event_loop(&mut terminal, chat).await,
+        Err(e) => Err(e.into()),
+    };
+    // Restore the terminal even when the chat failed
+    restore()?;
+    // Dropping this hook lets go of its share of the previous one
+    let _ = panic::take_hook();
+    if let Ok(previous) = Arc::try_unwrap(previous) {
+        panic::set_hook(previous);
+    }
+    result
+}
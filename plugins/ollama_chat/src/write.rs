@@ -0,0 +1,80 @@
+// Replies written to files, for --output and /write: the whole reply or only its code
+// blocks, replacing the file or appended to it, so generated manifests and scripts
+// land where they are needed without copy and paste.
+use crate::markdown;
+use anyhow::{anyhow, Context};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub path: PathBuf,
+    pub append: bool,
+    pub code_only: bool,
+}
+
+impl Target {
+    /// Parses the /write argument: [--append] [--code] <path>
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        let mut append = false;
+        let mut code_only = false;
+        let mut path = None;
+        for word in arg.split_whitespace() {
+            match word {
+                "--append" | "-a" => append = true,
+                "--code" | "-c" => code_only = true,
+                _ if path.is_none() => path = Some(PathBuf::from(word)),
+                _ => return Err("Usage: /write [--append] [--code] <path>".to_string()),
+            }
+        }
+        let path = path.ok_or("Usage: /write [--append] [--code] <path>")?;
+        Ok(Self {
+            path,
+            append,
+            code_only,
+        })
+    }
+
+    /// Writes `reply` (or its code blocks) to the file, returning the bytes written
+    pub fn write(&self, reply: &str) -> anyhow::Result<usize> {
+        let mut content = if self.code_only {
+            let blocks = markdown::code_blocks(reply);
+            if blocks.is_empty() {
+                return Err(anyhow!("The reply has no code blocks"));
+            }
+            blocks.join("\n\n")
+        } else {
+            reply.trim().to_string()
+        };
+        content.push('\n');
+
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.write_all(content.as_bytes())
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(content.len())
+    }
+
+    pub fn describe(&self, bytes: usize) -> String {
+        format!(
+            "{} {}{} ({} bytes)",
+            if self.append { "Appended to" } else { "Wrote" },
+            self.path.display(),
+            if self.code_only {
+                ", code blocks only"
+            } else {
+                ""
+            },
+            bytes
+        )
+    }
+}
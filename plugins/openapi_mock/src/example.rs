@@ -0,0 +1,186 @@
+// Example responses: the spec's own examples when it has them, otherwise a value built
+// from the schema that still validates against it (enums, formats, bounds and required
+// properties are respected). Recursive schemas stop at the first repeated `$ref`.
+use crate::spec::Spec;
+use serde_json::{json, Map, Value};
+
+/// Nesting past this depth ends in null, for deeply nested inline schemas
+const MAX_DEPTH: usize = 16;
+
+/// The example of a media type object: `example`, the named or first of `examples`, the
+/// schema's example, or one generated from the schema
+pub fn for_media(spec: &Spec, media: &Value, name: Option<&str>) -> Value {
+    if let Some(examples) = media["examples"].as_object() {
+        let chosen = match name {
+            Some(name) => examples.get(name),
+            None => examples.values().next(),
+        };
+        if let Some(example) = chosen {
+            let example = spec.resolve(example);
+            if let Some(value) = example.get("value") {
+                return value.clone();
+            }
+        }
+    }
+    if let Some(example) = media.get("example") {
+        return example.clone();
+    }
+    generate(spec, &media["schema"])
+}
+
+fn string(schema: &Value) -> Value {
+    let value = match schema["format"].as_str().unwrap_or_default() {
+        "date-time" => "2024-01-01T12:00:00Z",
+        "date" => "2024-01-01",
+        "time" => "12:00:00",
+        "uuid" => "3fa85f64-5717-4562-b3fc-2c963f66afa6",
+        "email" => "user@example.com",
+        "uri" | "url" => "https://example.com",
+        "hostname" => "example.com",
+        "ipv4" => "192.0.2.1",
+        "ipv6" => "2001:db8::1",
+        "byte" => "ZXhhbXBsZQ==",
+        "password" => "********",
+        _ => "string",
+    };
+    let mut value = value.to_string();
+    if let Some(min) = schema["minLength"].as_u64() {
+        while (value.chars().count() as u64) < min {
+            value.push('x');
+        }
+    }
+    if let Some(max) = schema["maxLength"].as_u64() {
+        value = value.chars().take(max as usize).collect();
+    }
+    Value::String(value)
+}
+
+fn number(schema: &Value, integer: bool) -> Value {
+    let minimum = schema["minimum"].as_f64();
+    let maximum = schema["maximum"].as_f64();
+    let exclusive = |key| schema[key].as_bool() == Some(true);
+    let mut value = match (minimum, maximum) {
+        (Some(minimum), _) if exclusive("exclusiveMinimum") => minimum + 1.0,
+        (Some(minimum), _) => minimum,
+        (None, Some(maximum)) if maximum < 0.0 => maximum,
+        _ => 0.0,
+    };
+    if let Some(maximum) = maximum {
+        if value > maximum || (value == maximum && exclusive("exclusiveMaximum")) {
+            value = maximum - 1.0;
+        }
+    }
+    if integer {
+        json!(value.ceil() as i64)
+    } else {
+        json!(value)
+    }
+}
+
+fn object(spec: &Spec, schema: &Value, refs: &mut Vec<String>, depth: usize) -> Value {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let mut object = Map::new();
+    for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+        let property_schema = spec.resolve(property);
+        // Only what a server sends back
+        if property_schema["writeOnly"].as_bool() == Some(true) {
+            continue;
+        }
+        // An optional property leading back into a schema being built is left out
+        let recursive = property["$ref"]
+            .as_str()
+            .is_some_and(|reference| refs.iter().any(|seen| seen == reference));
+        if recursive && !required.contains(&name.as_str()) {
+            continue;
+        }
+        object.insert(name.clone(), build(spec, property, refs, depth + 1));
+    }
+    Value::Object(object)
+}
+
+pub fn generate(spec: &Spec, schema: &Value) -> Value {
+    build(spec, schema, &mut Vec::new(), 0)
+}
+
+fn build(spec: &Spec, schema: &Value, refs: &mut Vec<String>, depth: usize) -> Value {
+    if let Some(reference) = schema["$ref"].as_str() {
+        if refs.iter().any(|seen| seen == reference) {
+            return Value::Null;
+        }
+        refs.push(reference.to_string());
+        let value = build(spec, spec.resolve(schema), refs, depth);
+        refs.pop();
+        return value;
+    }
+    if depth > MAX_DEPTH {
+        return Value::Null;
+    }
+    if let Some(example) = schema.get("example") {
+        return example.clone();
+    }
+    if let Some(value) = schema.get("default") {
+        return value.clone();
+    }
+    if let Some(first) = schema["enum"].as_array().and_then(|values| values.first()) {
+        return first.clone();
+    }
+    if let Some(parts) = schema["allOf"].as_array() {
+        let mut merged = Map::new();
+        for part in parts {
+            match build(spec, part, refs, depth + 1) {
+                Value::Object(fields) => merged.extend(fields),
+                other if merged.is_empty() => return other,
+                _ => {}
+            }
+        }
+        return Value::Object(merged);
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(first) = schema[key].as_array().and_then(|options| options.first()) {
+            return build(spec, first, refs, depth + 1);
+        }
+    }
+
+    let kind = match &schema["type"] {
+        Value::String(kind) => kind.as_str(),
+        // OpenAPI 3.1 type lists: the first that isn't null
+        Value::Array(kinds) => kinds
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|kind| *kind != "null")
+            .unwrap_or("null"),
+        _ if schema.get("properties").is_some() => "object",
+        _ if schema.get("items").is_some() => "array",
+        _ => "",
+    };
+    match kind {
+        "string" => string(schema),
+        "integer" => number(schema, true),
+        "number" => number(schema, false),
+        "boolean" => Value::Bool(true),
+        "array" => {
+            let recursive = schema["items"]["$ref"]
+                .as_str()
+                .is_some_and(|reference| refs.iter().any(|seen| seen == reference));
+            if recursive {
+                return Value::Array(Vec::new());
+            }
+            let item = build(spec, &schema["items"], refs, depth + 1);
+            let count = schema["minItems"].as_u64().unwrap_or(1).max(1) as usize;
+            let count = match schema["maxItems"].as_u64() {
+                Some(max) => count.min(max as usize),
+                None => count,
+            };
+            Value::Array(vec![item; count])
+        }
+        "object" => object(spec, schema, refs, depth),
+        "null" => Value::Null,
+        // A schema without a type accepts anything
+        _ => json!({}),
+    }
+}
@@ -0,0 +1,236 @@
+// A mock backend generated from an OpenAPI 3 spec: every operation answers on a local port
+// with the spec's examples, or with values built from its schemas, so client work can start
+// before the service exists. Requests are validated against the spec and what's wrong is
+// logged; overrides pin a path to another status, example or body.
+use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+mod example;
+mod server;
+mod spec;
+mod validate;
+
+use server::Override;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:4010";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OpenApiMockConfig {
+    /// OpenAPI 3 spec, YAML or JSON
+    pub spec: Option<String>,
+    /// Address to listen on (default 127.0.0.1:4010)
+    pub listen: Option<String>,
+    /// Answer requests that don't match the spec with 400 (default false: only log)
+    pub strict: Option<bool>,
+    #[serde(default, rename = "override")]
+    pub overrides: Vec<Override>,
+}
+
+pub struct OpenApiMockPlugin;
+
+impl OpenApiMockPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# OpenAPI Mock Configuration
+spec = "/path/to/openapi.yaml"
+listen = "127.0.0.1:4010"
+# strict = true  # answer invalid requests with 400 instead of only logging them
+
+# Answer with the spec's 404 response for one pet
+[[override]]
+method = "GET"
+path = "/pets/42"
+status = 404
+
+# Use a named example from the spec for every pet
+[[override]]
+method = "GET"
+path = "/pets/{petId}"
+example = "cat"
+
+[[override]]
+method = "POST"
+path = "/pets"
+status = 201
+body = '{"id": 7, "name": "Rex"}'
+latency_ms = 300
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<OpenApiMockConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: OpenApiMockConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // The spec can be given on the command line
+                Ok(OpenApiMockConfig::default())
+            }
+        }
+        None => Ok(OpenApiMockConfig::default()),
+    }
+}
+
+impl Plugin for OpenApiMockPlugin {
+    fn name(&self) -> &'static str {
+        "openapi_mock"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Mock backend serving example responses for every operation of an OpenAPI 3 spec"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Serve schema-valid example responses for an OpenAPI 3 spec")
+            .arg(
+                Arg::new("spec")
+                    .value_name("SPEC")
+                    .help("OpenAPI 3 spec, YAML or JSON (default: from the config file)"),
+            )
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .short('l')
+                    .value_name("ADDR")
+                    .help("Address to listen on (default 127.0.0.1:4010)"),
+            )
+            .arg(
+                Arg::new("strict")
+                    .long("strict")
+                    .action(ArgAction::SetTrue)
+                    .help("Answer requests that don't match the spec with 400"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(OpenApiMockPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<OpenApiMockConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let Some(spec_path) = matches
+            .get_one::<String>("spec")
+            .or(config.spec.as_ref())
+            .cloned()
+        else {
+            eprintln!("❌ No spec given (pass it as an argument or configure in config file)");
+            eprintln!("💡 Example: proxy openapi_mock ./openapi.yaml");
+            eprintln!("📝 Sample config:\n{}", OpenApiMockPlugin::sample_config());
+            std::process::exit(1);
+        };
+        let spec = match spec::load(Path::new(&spec_path)) {
+            Ok(spec) => spec,
+            Err(e) => {
+                eprintln!("❌ Failed to load spec: {:#}", e);
+                std::process::exit(1);
+            }
+        };
+        let listen = matches
+            .get_one::<String>("listen")
+            .or(config.listen.as_ref())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+        let strict = matches.get_flag("strict") || config.strict.unwrap_or(false);
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        rt.block_on(async {
+            let listener = match TcpListener::bind(&listen).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("❌ Failed to listen on {}: {}", listen, e);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!(
+                "🚀 Mocking {} ({} operations) on http://{}{}",
+                spec.title,
+                spec.operations.len(),
+                listen,
+                spec.base_path
+            );
+            for operation in &spec.operations {
+                println!("  {}", operation.describe());
+            }
+            for o in &config.overrides {
+                println!(
+                    "  🎛️  {} {} overridden",
+                    o.method.as_deref().unwrap_or("*"),
+                    o.path
+                );
+            }
+            if strict {
+                println!("🚫 Strict: requests that don't match the spec get a 400");
+            }
+
+            let mock = Arc::new(server::Mock {
+                spec,
+                overrides: config.overrides,
+                strict,
+            });
+
+            loop {
+                let (socket, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("❌ Accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let mock = mock.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request| mock.clone().handle(request));
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(socket), service)
+                        .await
+                    {
+                        eprintln!("❌ [{}] {}", addr, e);
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(OpenApiMockPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
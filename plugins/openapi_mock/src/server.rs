@@ -0,0 +1,282 @@
+// Request handling: find the operation, validate the request against it (logging what's
+// wrong, or refusing it in strict mode) and answer with an example response, unless an
+// override for the path says otherwise.
+use crate::example;
+use crate::spec::{Lookup, Operation, Spec};
+use crate::validate;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Request, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Override {
+    /// HTTP method; any method when not set
+    pub method: Option<String>,
+    /// A path template from the spec ("/pets/{petId}") or one concrete path ("/pets/42")
+    pub path: String,
+    /// Answer with the spec's response for this status
+    pub status: Option<u16>,
+    /// Name of one of the spec's `examples` to answer with
+    pub example: Option<String>,
+    /// Response body, instead of the spec's example
+    pub body: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Delay before answering
+    pub latency_ms: Option<u64>,
+}
+
+impl Override {
+    fn applies(&self, method: &str, template: &str, path: &str) -> bool {
+        self.method
+            .as_deref()
+            .is_none_or(|wanted| wanted.eq_ignore_ascii_case(method))
+            && (self.path == template || self.path == path)
+    }
+}
+
+pub struct Mock {
+    pub spec: Spec,
+    pub overrides: Vec<Override>,
+    /// Answer invalid requests with 400 instead of only logging them
+    pub strict: bool,
+}
+
+fn response(
+    status: u16,
+    headers: &BTreeMap<String, String>,
+    body: impl Into<Bytes>,
+) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::OK);
+    for (name, value) in headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                response.headers_mut().insert(name, value);
+            }
+            _ => eprintln!("⚠️  Skipping invalid header {}: {}", name, value),
+        }
+    }
+    response
+}
+
+fn json_response(status: u16, body: Value) -> Response<Full<Bytes>> {
+    let headers = BTreeMap::from([("content-type".to_string(), "application/json".to_string())]);
+    response(status, &headers, body.to_string())
+}
+
+fn parse_query(query: &str) -> BTreeMap<String, Vec<String>> {
+    let mut parsed: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        parsed
+            .entry(key.to_string())
+            .or_default()
+            .push(value.to_string());
+    }
+    parsed
+}
+
+/// The response the spec declares for `status`, or its first success response
+fn pick_response(operation: &Operation, status: Option<u16>) -> (u16, Option<&Value>) {
+    let responses = operation.responses.as_object();
+    let get = |key: &str| responses.and_then(|responses| responses.get(key));
+    if let Some(status) = status {
+        let declared = get(&status.to_string())
+            .or_else(|| get(&format!("{}XX", status / 100)))
+            .or_else(|| get("default"));
+        return (status, declared);
+    }
+    let codes: Vec<u16> = responses
+        .into_iter()
+        .flat_map(|responses| responses.keys())
+        .filter_map(|key| key.parse().ok())
+        .collect();
+    if let Some(code) = codes.iter().filter(|code| (200..300).contains(*code)).min() {
+        return (*code, get(&code.to_string()));
+    }
+    if let Some(declared) = get("2XX").or_else(|| get("default")) {
+        return (200, Some(declared));
+    }
+    match codes.iter().min() {
+        Some(code) => (*code, get(&code.to_string())),
+        None => (200, None),
+    }
+}
+
+impl Mock {
+    /// Status, headers and body of the example answer
+    fn example(
+        &self,
+        operation: &Operation,
+        chosen: Option<&Override>,
+    ) -> (u16, BTreeMap<String, String>, Bytes) {
+        let (status, declared) = pick_response(operation, chosen.and_then(|o| o.status));
+        let mut headers = BTreeMap::new();
+        let mut body = Bytes::new();
+        if let Some(declared) = declared.map(|declared| self.spec.resolve(declared)) {
+            for (name, header) in declared["headers"].as_object().into_iter().flatten() {
+                let header = self.spec.resolve(header);
+                let value = match example::generate(&self.spec, &header["schema"]) {
+                    Value::String(text) => text,
+                    other => other.to_string(),
+                };
+                headers.insert(name.to_ascii_lowercase(), value);
+            }
+            let content = declared["content"].as_object();
+            let media = content.and_then(|content| {
+                content
+                    .get_key_value("application/json")
+                    .or_else(|| content.iter().find(|(media, _)| media.contains("json")))
+                    .or_else(|| content.iter().next())
+            });
+            if let Some((media_type, media)) = media {
+                let example = example::for_media(
+                    &self.spec,
+                    media,
+                    chosen.and_then(|o| o.example.as_deref()),
+                );
+                body = match example {
+                    Value::String(text) if !media_type.contains("json") => Bytes::from(text),
+                    other => Bytes::from(other.to_string()),
+                };
+                let content_type = if media_type.contains('*') {
+                    "application/json"
+                } else {
+                    media_type
+                };
+                headers.insert("content-type".to_string(), content_type.to_string());
+            }
+        }
+        if let Some(chosen) = chosen {
+            if let Some(text) = &chosen.body {
+                body = Bytes::from(text.clone());
+            }
+            for (name, value) in &chosen.headers {
+                headers.insert(name.to_ascii_lowercase(), value.clone());
+            }
+        }
+        (status, headers, body)
+    }
+
+    pub async fn handle(
+        self: Arc<Self>,
+        request: Request<Incoming>,
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        let started = Instant::now();
+        let (parts, body) = request.into_parts();
+        let method = parts.method.to_string();
+        let path = parts.uri.path().to_string();
+        let target = parts
+            .uri
+            .path_and_query()
+            .map(|target| target.as_str().to_string())
+            .unwrap_or_else(|| path.clone());
+        let body = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => {
+                eprintln!("❌ {} {}: {}", method, target, e);
+                return Ok(json_response(
+                    400,
+                    serde_json::json!({ "error": e.to_string() }),
+                ));
+            }
+        };
+
+        let (operation, path_params) = match self.spec.find(&method, &path) {
+            Lookup::Operation(operation, params) => (operation, params),
+            Lookup::WrongMethod(allowed) => {
+                println!(
+                    "❓ {} {} → 405, the spec has {}",
+                    method,
+                    target,
+                    allowed.join(", ")
+                );
+                let mut answer = json_response(
+                    405,
+                    serde_json::json!({ "error": "method not allowed", "allowed": allowed }),
+                );
+                if let Ok(allow) = HeaderValue::from_str(&allowed.join(", ")) {
+                    answer.headers_mut().insert("allow", allow);
+                }
+                return Ok(answer);
+            }
+            Lookup::NotFound => {
+                println!("❓ {} {} → 404, not in the spec", method, target);
+                return Ok(json_response(
+                    404,
+                    serde_json::json!({
+                        "error": "no operation in the spec",
+                        "method": method,
+                        "path": path,
+                    }),
+                ));
+            }
+        };
+
+        let headers: BTreeMap<String, String> = parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let query = parse_query(parts.uri.query().unwrap_or_default());
+        let errors = validate::request(
+            &self.spec,
+            operation,
+            &validate::Request {
+                path_params: &path_params,
+                query: &query,
+                headers: &headers,
+                content_type: headers.get("content-type").map(String::as_str),
+                body: &body,
+            },
+        );
+        for error in &errors {
+            eprintln!("⚠️  {} {}: {}", method, target, error);
+        }
+        if self.strict && !errors.is_empty() {
+            println!(
+                "🚫 {} {} → 400 ({}) in {}ms",
+                method,
+                target,
+                operation.id.as_deref().unwrap_or(&operation.path),
+                started.elapsed().as_millis()
+            );
+            return Ok(json_response(
+                400,
+                serde_json::json!({ "error": "request validation failed", "details": errors }),
+            ));
+        }
+
+        let chosen = self
+            .overrides
+            .iter()
+            .find(|o| o.applies(&method, &operation.path, &path));
+        if let Some(latency) = chosen.and_then(|o| o.latency_ms) {
+            tokio::time::sleep(Duration::from_millis(latency)).await;
+        }
+        let (status, headers, body) = self.example(operation, chosen);
+        println!(
+            "{} {} {} → {} ({}{}) in {}ms",
+            if chosen.is_some() { "🎛️ " } else { "🎭" },
+            method,
+            target,
+            status,
+            operation.id.as_deref().unwrap_or(&operation.path),
+            if chosen.is_some() { ", override" } else { "" },
+            started.elapsed().as_millis()
+        );
+        Ok(response(status, &headers, body))
+    }
+}
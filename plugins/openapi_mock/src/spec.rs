@@ -0,0 +1,193 @@
+// The OpenAPI 3 document: loaded from JSON or YAML, with local `$ref`s followed on demand
+// and every operation indexed by method and path template for matching requests.
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+/// How many `$ref`s in a row are followed before giving up on a cycle
+const MAX_REF_DEPTH: usize = 32;
+
+pub struct Operation {
+    pub method: String,
+    /// The template from the spec, e.g. "/pets/{petId}"
+    pub path: String,
+    pub id: Option<String>,
+    /// Path-level and operation-level parameters, the latter winning
+    pub parameters: Vec<Value>,
+    pub request_body: Option<Value>,
+    pub responses: Value,
+}
+
+impl Operation {
+    /// "GET /pets/{petId}", with the operationId when there is one
+    pub fn describe(&self) -> String {
+        match &self.id {
+            Some(id) => format!("{} {} ({})", self.method, self.path, id),
+            None => format!("{} {}", self.method, self.path),
+        }
+    }
+
+    /// Path parameters when the template matches the path
+    fn matches(&self, path: &str) -> Option<BTreeMap<String, String>> {
+        let template: Vec<&str> = self.path.trim_matches('/').split('/').collect();
+        let actual: Vec<&str> = path.trim_matches('/').split('/').collect();
+        if template.len() != actual.len() {
+            return None;
+        }
+        let mut params = BTreeMap::new();
+        for (segment, value) in template.iter().zip(&actual) {
+            match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(name) if !value.is_empty() => {
+                    params.insert(name.to_string(), value.to_string());
+                }
+                Some(_) => return None,
+                None if segment == value => {}
+                None => return None,
+            }
+        }
+        Some(params)
+    }
+}
+
+pub struct Spec {
+    pub root: Value,
+    pub title: String,
+    /// Path of the first server URL, e.g. "/v1", accepted in front of every path
+    pub base_path: String,
+    pub operations: Vec<Operation>,
+}
+
+/// What a request resolves to
+pub enum Lookup<'a> {
+    Operation(&'a Operation, BTreeMap<String, String>),
+    /// The path exists, with other methods
+    WrongMethod(Vec<String>),
+    NotFound,
+}
+
+pub fn load(path: &Path) -> Result<Spec> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let root: Value = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&content)?,
+        _ => serde_yaml::from_str(&content)
+            .with_context(|| format!("{} is neither YAML nor JSON", path.display()))?,
+    };
+    let version = root["openapi"].as_str().unwrap_or_default();
+    if !version.starts_with('3') {
+        return Err(match root["swagger"].as_str() {
+            Some(swagger) => anyhow!(
+                "{} is Swagger {}, only OpenAPI 3 specs are supported",
+                path.display(),
+                swagger
+            ),
+            None => anyhow!("{} is not an OpenAPI 3 spec", path.display()),
+        });
+    }
+
+    let mut operations = Vec::new();
+    for (template, item) in root["paths"].as_object().into_iter().flatten() {
+        let item = resolve(&root, item);
+        let shared: Vec<Value> = item["parameters"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|parameter| resolve(&root, parameter).clone())
+            .collect();
+        for method in METHODS {
+            let Some(operation) = item.get(method) else {
+                continue;
+            };
+            let mut parameters = shared.clone();
+            for parameter in operation["parameters"].as_array().into_iter().flatten() {
+                let parameter = resolve(&root, parameter).clone();
+                parameters.retain(|existing| {
+                    existing["name"] != parameter["name"] || existing["in"] != parameter["in"]
+                });
+                parameters.push(parameter);
+            }
+            operations.push(Operation {
+                method: method.to_uppercase(),
+                path: template.clone(),
+                id: operation["operationId"].as_str().map(str::to_string),
+                parameters,
+                request_body: operation
+                    .get("requestBody")
+                    .map(|body| resolve(&root, body).clone()),
+                responses: operation["responses"].clone(),
+            });
+        }
+    }
+    // Literal segments before templated ones, so /pets/mine wins over /pets/{petId}
+    operations.sort_by_key(|operation: &Operation| operation.path.matches('{').count());
+    Ok(Spec {
+        title: root["info"]["title"].as_str().unwrap_or("API").to_string(),
+        base_path: base_path(root["servers"][0]["url"].as_str().unwrap_or_default()),
+        operations,
+        root,
+    })
+}
+
+/// The path part of a server URL, without the trailing slash
+fn base_path(url: &str) -> String {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|slash| &rest[slash..]).unwrap_or(""),
+        None => url,
+    };
+    // Server variables can't be known, so a templated base path isn't used
+    if path.contains('{') {
+        return String::new();
+    }
+    path.trim_end_matches('/').to_string()
+}
+
+/// Follows `$ref`s to local definitions ("#/components/..."); anything else is returned
+/// as it is
+pub fn resolve<'a>(root: &'a Value, mut value: &'a Value) -> &'a Value {
+    for _ in 0..MAX_REF_DEPTH {
+        let Some(pointer) = value["$ref"].as_str().and_then(|r| r.strip_prefix('#')) else {
+            return value;
+        };
+        match root.pointer(pointer) {
+            Some(target) => value = target,
+            None => return value,
+        }
+    }
+    value
+}
+
+impl Spec {
+    pub fn resolve<'a>(&'a self, value: &'a Value) -> &'a Value {
+        resolve(&self.root, value)
+    }
+
+    pub fn find(&self, method: &str, path: &str) -> Lookup<'_> {
+        let path = match path.strip_prefix(self.base_path.as_str()) {
+            Some(rest)
+                if !self.base_path.is_empty() && (rest.is_empty() || rest.starts_with('/')) =>
+            {
+                rest
+            }
+            _ => path,
+        };
+        let mut allowed = Vec::new();
+        for operation in &self.operations {
+            if let Some(params) = operation.matches(path) {
+                if operation.method.eq_ignore_ascii_case(method) {
+                    return Lookup::Operation(operation, params);
+                }
+                allowed.push(operation.method.clone());
+            }
+        }
+        if allowed.is_empty() {
+            Lookup::NotFound
+        } else {
+            Lookup::WrongMethod(allowed)
+        }
+    }
+}
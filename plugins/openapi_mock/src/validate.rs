@@ -0,0 +1,331 @@
+// Request validation against the operation: parameters (path, query and header, coerced
+// from their text by the schema's type) and JSON request bodies. Covers the schema
+// keywords specs use day to day, not all of JSON Schema.
+use crate::spec::{Operation, Spec};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// What a request carries that is validated
+pub struct Request<'a> {
+    pub path_params: &'a BTreeMap<String, String>,
+    /// Every value of each query parameter, in order
+    pub query: &'a BTreeMap<String, Vec<String>>,
+    /// Lowercased names
+    pub headers: &'a BTreeMap<String, String>,
+    pub content_type: Option<&'a str>,
+    pub body: &'a [u8],
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn is_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn short(value: &Value) -> String {
+    let text = value.to_string();
+    if text.chars().count() > 40 {
+        format!("{}…", text.chars().take(39).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// A bound from OpenAPI 3.0 (number plus boolean flag) or 3.1 (the exclusive number)
+fn bound(schema: &Value, inclusive: &str, exclusive: &str) -> Option<(f64, bool)> {
+    match &schema[exclusive] {
+        Value::Number(limit) => limit.as_f64().map(|limit| (limit, true)),
+        Value::Bool(flag) => schema[inclusive].as_f64().map(|limit| (limit, *flag)),
+        _ => schema[inclusive].as_f64().map(|limit| (limit, false)),
+    }
+}
+
+/// Checks `value` against `schema`, adding what's wrong to `errors` with `at` as the
+/// location
+pub fn check(spec: &Spec, schema: &Value, value: &Value, at: &str, errors: &mut Vec<String>) {
+    let schema = spec.resolve(schema);
+    let kinds: Vec<&str> = match &schema["type"] {
+        Value::String(kind) => vec![kind.as_str()],
+        Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if value.is_null() && (schema["nullable"].as_bool() == Some(true) || kinds.contains(&"null")) {
+        return;
+    }
+
+    for part in schema["allOf"].as_array().into_iter().flatten() {
+        check(spec, part, value, at, errors);
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(options) = schema[key].as_array() {
+            let matched = options.iter().any(|option| {
+                let mut option_errors = Vec::new();
+                check(spec, option, value, at, &mut option_errors);
+                option_errors.is_empty()
+            });
+            if !matched {
+                errors.push(format!("{}: matches none of the {} schemas", at, key));
+            }
+        }
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            errors.push(format!(
+                "{}: {} is not one of {}",
+                at,
+                short(value),
+                short(&Value::Array(allowed.clone()))
+            ));
+            return;
+        }
+    }
+    if !kinds.is_empty() && !kinds.iter().any(|kind| is_type(value, kind)) {
+        errors.push(format!(
+            "{}: expected {}, got {} {}",
+            at,
+            kinds.join(" or "),
+            type_name(value),
+            short(value)
+        ));
+        return;
+    }
+
+    match value {
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema["minLength"].as_u64().filter(|min| length < *min) {
+                errors.push(format!("{}: shorter than the minimum length {}", at, min));
+            }
+            if let Some(max) = schema["maxLength"].as_u64().filter(|max| length > *max) {
+                errors.push(format!("{}: longer than the maximum length {}", at, max));
+            }
+            if let Some(pattern) = schema["pattern"].as_str() {
+                match regex::Regex::new(pattern) {
+                    Ok(regex) if !regex.is_match(text) => errors.push(format!(
+                        "{}: {} doesn't match {}",
+                        at,
+                        short(value),
+                        pattern
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some((limit, exclusive)) = bound(schema, "minimum", "exclusiveMinimum") {
+                if number < limit || (exclusive && number == limit) {
+                    errors.push(format!("{}: {} is below the minimum {}", at, number, limit));
+                }
+            }
+            if let Some((limit, exclusive)) = bound(schema, "maximum", "exclusiveMaximum") {
+                if number > limit || (exclusive && number == limit) {
+                    errors.push(format!("{}: {} is above the maximum {}", at, number, limit));
+                }
+            }
+        }
+        Value::Array(items) => {
+            let count = items.len() as u64;
+            if let Some(min) = schema["minItems"].as_u64().filter(|min| count < *min) {
+                errors.push(format!("{}: fewer than {} items", at, min));
+            }
+            if let Some(max) = schema["maxItems"].as_u64().filter(|max| count > *max) {
+                errors.push(format!("{}: more than {} items", at, max));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(spec, item_schema, item, &format!("{}[{}]", at, i), errors);
+                }
+            }
+        }
+        Value::Object(fields) => {
+            let properties = schema["properties"].as_object();
+            for name in schema["required"].as_array().into_iter().flatten() {
+                let Some(name) = name.as_str() else {
+                    continue;
+                };
+                // Read-only properties are only sent by the server
+                let read_only = properties
+                    .and_then(|properties| properties.get(name))
+                    .is_some_and(|property| {
+                        spec.resolve(property)["readOnly"].as_bool() == Some(true)
+                    });
+                if !fields.contains_key(name) && !read_only {
+                    errors.push(format!("{}: missing required property {}", at, name));
+                }
+            }
+            for (name, field) in fields {
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) => {
+                        check(spec, property, field, &format!("{}.{}", at, name), errors)
+                    }
+                    None => match &schema["additionalProperties"] {
+                        Value::Bool(false) => {
+                            errors.push(format!("{}: unexpected property {}", at, name))
+                        }
+                        additional @ Value::Object(_) => {
+                            check(spec, additional, field, &format!("{}.{}", at, name), errors)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A parameter's text as the JSON value its schema describes, so it can be checked
+fn coerce(spec: &Spec, schema: &Value, raw: &[String]) -> Value {
+    let schema = spec.resolve(schema);
+    let scalar = |kind: &str, text: &str| match kind {
+        "integer" => text
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(text.to_string())),
+        "number" => text
+            .parse::<f64>()
+            .ok()
+            .and_then(|number| serde_json::Number::from_f64(number).map(Value::Number))
+            .unwrap_or_else(|| Value::String(text.to_string())),
+        "boolean" => match text {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => Value::String(text.to_string()),
+        },
+        _ => Value::String(text.to_string()),
+    };
+    match schema["type"].as_str().unwrap_or_default() {
+        "array" => {
+            let item_kind = spec.resolve(&schema["items"])["type"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            // Repeated (?id=1&id=2) or comma-separated (?id=1,2)
+            let values: Vec<&str> = if raw.len() == 1 {
+                raw[0].split(',').collect()
+            } else {
+                raw.iter().map(String::as_str).collect()
+            };
+            Value::Array(
+                values
+                    .into_iter()
+                    .map(|text| scalar(&item_kind, text))
+                    .collect(),
+            )
+        }
+        kind => scalar(kind, raw.last().map(String::as_str).unwrap_or_default()),
+    }
+}
+
+/// The media type of the request body the operation accepts for `content_type`
+fn accepted_media<'a>(content: &'a Value, content_type: &str) -> Option<&'a Value> {
+    let content = content.as_object()?;
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let major = essence.split('/').next().unwrap_or_default();
+    content.get(&essence).or_else(|| {
+        content.iter().find_map(|(media, value)| {
+            (media == "*/*" || media.strip_suffix("/*") == Some(major)).then_some(value)
+        })
+    })
+}
+
+/// Everything wrong with the request for this operation
+pub fn request(spec: &Spec, operation: &Operation, request: &Request) -> Vec<String> {
+    let mut errors = Vec::new();
+    for parameter in &operation.parameters {
+        let name = parameter["name"].as_str().unwrap_or_default();
+        let location = parameter["in"].as_str().unwrap_or_default();
+        let raw: Vec<String> = match location {
+            "path" => request.path_params.get(name).cloned().into_iter().collect(),
+            "query" => request.query.get(name).cloned().unwrap_or_default(),
+            "header" => request
+                .headers
+                .get(&name.to_ascii_lowercase())
+                .cloned()
+                .into_iter()
+                .collect(),
+            // Cookies aren't checked
+            _ => continue,
+        };
+        if raw.is_empty() {
+            if parameter["required"].as_bool() == Some(true) {
+                errors.push(format!("missing required {} parameter {}", location, name));
+            }
+            continue;
+        }
+        if let Some(schema) = parameter.get("schema") {
+            let value = coerce(spec, schema, &raw);
+            check(
+                spec,
+                schema,
+                &value,
+                &format!("{} parameter {}", location, name),
+                &mut errors,
+            );
+        }
+    }
+
+    let Some(body) = &operation.request_body else {
+        return errors;
+    };
+    if request.body.is_empty() {
+        if body["required"].as_bool() == Some(true) {
+            errors.push("missing required request body".to_string());
+        }
+        return errors;
+    }
+    let content_type = request.content_type.unwrap_or("application/json");
+    let Some(media) = accepted_media(&body["content"], content_type) else {
+        let accepted: Vec<&String> = body["content"]
+            .as_object()
+            .map(|content| content.keys().collect())
+            .unwrap_or_default();
+        errors.push(format!(
+            "content type {} not accepted (expected {})",
+            content_type,
+            accepted
+                .iter()
+                .map(|media| media.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        return errors;
+    };
+    if !content_type.contains("json") {
+        return errors;
+    }
+    match serde_json::from_slice::<Value>(request.body) {
+        Ok(value) => {
+            if let Some(schema) = media.get("schema") {
+                check(spec, schema, &value, "body", &mut errors);
+            }
+        }
+        Err(e) => errors.push(format!("body is not valid JSON: {}", e)),
+    }
+    errors
+}
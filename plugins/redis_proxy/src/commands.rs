@@ -0,0 +1,76 @@
+// Which keys a command reads or writes, for the keyspace summary. Covers the data
+// commands of strings, hashes, lists, sets, sorted sets, streams and HyperLogLogs;
+// anything else (scripts, admin, pub/sub) is counted but touches no keys here.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Where a command's keys are among its arguments
+enum Keys {
+    First,
+    All,
+    /// MSET-style key value pairs
+    Pairs,
+    FirstTwo,
+}
+
+fn spec(name: &str) -> Option<(Access, Keys)> {
+    use Access::*;
+    use Keys::*;
+    let spec = match name {
+        "GET" | "GETRANGE" | "STRLEN" | "TTL" | "PTTL" | "TYPE" | "DUMP" | "GETBIT"
+        | "BITCOUNT" | "HGET" | "HMGET" | "HGETALL" | "HKEYS" | "HVALS" | "HLEN" | "HEXISTS"
+        | "HSTRLEN" | "HSCAN" | "LRANGE" | "LLEN" | "LINDEX" | "LPOS" | "SMEMBERS"
+        | "SISMEMBER" | "SMISMEMBER" | "SCARD" | "SRANDMEMBER" | "SSCAN" | "ZRANGE"
+        | "ZRANGEBYSCORE" | "ZRANGEBYLEX" | "ZREVRANGE" | "ZREVRANGEBYSCORE" | "ZSCORE"
+        | "ZMSCORE" | "ZCARD" | "ZCOUNT" | "ZRANK" | "ZREVRANK" | "ZSCAN" | "XRANGE"
+        | "XREVRANGE" | "XLEN" => (Read, First),
+        "MGET" | "EXISTS" | "SINTER" | "SUNION" | "SDIFF" | "PFCOUNT" => (Read, All),
+        "SET" | "SETNX" | "SETEX" | "PSETEX" | "GETSET" | "GETDEL" | "GETEX" | "APPEND"
+        | "SETRANGE" | "SETBIT" | "INCR" | "INCRBY" | "INCRBYFLOAT" | "DECR" | "DECRBY"
+        | "EXPIRE" | "PEXPIRE" | "EXPIREAT" | "PEXPIREAT" | "PERSIST" | "HSET" | "HSETNX"
+        | "HMSET" | "HDEL" | "HINCRBY" | "HINCRBYFLOAT" | "LPUSH" | "RPUSH" | "LPUSHX"
+        | "RPUSHX" | "LPOP" | "RPOP" | "LSET" | "LREM" | "LTRIM" | "LINSERT" | "SADD" | "SREM"
+        | "SPOP" | "ZADD" | "ZREM" | "ZINCRBY" | "ZPOPMIN" | "ZPOPMAX" | "ZREMRANGEBYSCORE"
+        | "ZREMRANGEBYRANK" | "XADD" | "XDEL" | "XTRIM" | "PFADD" | "RESTORE" => (Write, First),
+        "DEL" | "UNLINK" => (Write, All),
+        "MSET" | "MSETNX" => (Write, Pairs),
+        "RENAME" | "RENAMENX" | "COPY" | "LMOVE" | "RPOPLPUSH" | "SMOVE" => (Write, FirstTwo),
+        _ => return None,
+    };
+    Some(spec)
+}
+
+/// How the command named in `args[0]` accesses keys, and which
+pub fn keys<'a>(args: &[&'a [u8]]) -> Option<(Access, Vec<&'a [u8]>)> {
+    let name = String::from_utf8_lossy(args.first()?).to_uppercase();
+    let (access, keys) = spec(&name)?;
+    let rest = &args[1..];
+    let keys = match keys {
+        Keys::First => rest.iter().take(1).copied().collect(),
+        Keys::All => rest.to_vec(),
+        Keys::Pairs => rest.iter().step_by(2).copied().collect(),
+        Keys::FirstTwo => rest.iter().take(2).copied().collect(),
+    };
+    Some((access, keys))
+}
+
+/// Whether a segment of a key is an id rather than part of its name: all digits, or
+/// long and containing digits like UUIDs and hashes
+fn is_id(segment: &str) -> bool {
+    let digits = segment.chars().any(|c| c.is_ascii_digit());
+    digits && (segment.chars().all(|c| c.is_ascii_digit()) || segment.len() >= 8)
+}
+
+/// The key with its ids replaced by `*`, e.g. "session:8f3a9c2e41:user" becomes
+/// "session:*:user", so keys of one kind are counted together
+pub fn pattern(key: &[u8]) -> String {
+    String::from_utf8_lossy(key)
+        .split(':')
+        .map(|segment| if is_id(segment) { "*" } else { segment })
+        .collect::<Vec<_>>()
+        .join(":")
+}
@@ -0,0 +1,352 @@
+// Redis sessions with the protocol visible: a local port per configured endpoint, reached
+// directly, through an SSH jump host or over the Kubernetes port-forward API. Commands
+// and replies are printed as they pass, each reply with its latency, and on exit a
+// summary of latency per command and, on request, of which keys were read and written.
+use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::net;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+
+mod commands;
+mod resp;
+mod session;
+mod transport;
+
+use session::{Session, Stats};
+use transport::Connector;
+
+const DEFAULT_PORT: u16 = 6379;
+/// Key patterns shown in the keyspace summary
+const TOP_PATTERNS: usize = 20;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RedisProxyConfig {
+    #[serde(default)]
+    pub endpoint: Vec<Endpoint>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    #[default]
+    Direct,
+    Ssh,
+    K8s,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Endpoint {
+    pub name: String,
+    /// direct (default), ssh or k8s
+    #[serde(default)]
+    pub transport: Transport,
+    /// Redis host; with ssh as seen from the jump host (default localhost)
+    pub host: Option<String>,
+    /// Redis port, the pod's with k8s (default 6379)
+    pub port: Option<u16>,
+    /// ssh: jump host, or a Host alias from ~/.ssh/config
+    pub ssh_host: Option<String>,
+    pub ssh_user: Option<String>,
+    /// k8s: namespace (default: the kubeconfig context's)
+    pub namespace: Option<String>,
+    pub pod_name: Option<String>,
+    pub pod_selector: Option<String>,
+    pub local_port: u16,
+    /// Address to listen on: 127.0.0.1 (default), ::1, or :: for IPv4 and IPv6
+    pub bind: Option<String>,
+    /// Print commands and replies (default true)
+    pub log_traffic: Option<bool>,
+}
+
+impl Endpoint {
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_PORT)
+    }
+}
+
+pub struct RedisProxyPlugin;
+
+impl RedisProxyPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Redis Proxy Configuration
+[[endpoint]]
+name = "local"
+host = "localhost"
+port = 6379
+local_port = 16379
+# bind = "::1"  # default 127.0.0.1; "::" listens on every interface, IPv4 and IPv6
+
+[[endpoint]]
+name = "sessions"
+transport = "ssh"
+ssh_host = "bastion.example.com"  # or a Host alias from ~/.ssh/config
+host = "sessions.abc123.cache.amazonaws.com"  # as seen from the jump host
+local_port = 16380
+
+[[endpoint]]
+name = "cache"
+transport = "k8s"
+namespace = "default"
+pod_selector = "app=redis"  # or pod_name = "redis-0"
+local_port = 16381
+# log_traffic = false
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<RedisProxyConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: RedisProxyConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", RedisProxyPlugin::sample_config());
+                Ok(RedisProxyConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(RedisProxyConfig::default())
+        }
+    }
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn print_summary(name: &str, stats: &Stats, keyspace: bool) {
+    if stats.commands.is_empty() {
+        return;
+    }
+    let mut commands: Vec<_> = stats.commands.iter().collect();
+    commands.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+    let width = commands
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    println!("\n📊 [{}] Commands:", name);
+    for (command, entry) in commands {
+        let mut latencies = entry.latencies.clone();
+        latencies.sort_by(|a, b| a.total_cmp(b));
+        let timing = if latencies.is_empty() {
+            String::new()
+        } else {
+            let avg = latencies.iter().sum::<f64>() / latencies.len() as f64;
+            format!(
+                "  avg {:.2}ms  p50 {:.2}ms  p95 {:.2}ms  max {:.2}ms",
+                avg,
+                percentile(&latencies, 0.50),
+                percentile(&latencies, 0.95),
+                latencies[latencies.len() - 1]
+            )
+        };
+        let errors = match entry.errors {
+            0 => String::new(),
+            n => format!("  errors {}", n),
+        };
+        println!(
+            "  {:<width$} {:>7}{}{}",
+            command, entry.count, timing, errors
+        );
+    }
+
+    if !keyspace || stats.keyspace.is_empty() {
+        return;
+    }
+    let mut patterns: Vec<_> = stats.keyspace.iter().collect();
+    patterns.sort_by(|a, b| {
+        (b.1.reads + b.1.writes)
+            .cmp(&(a.1.reads + a.1.writes))
+            .then(a.0.cmp(b.0))
+    });
+    let shown = patterns.len().min(TOP_PATTERNS);
+    let width = patterns[..shown]
+        .iter()
+        .map(|(pattern, _)| pattern.chars().count())
+        .max()
+        .unwrap_or(0);
+    println!(
+        "\n🔑 [{}] Keyspace ({} of {} key patterns):",
+        name,
+        shown,
+        patterns.len()
+    );
+    for (pattern, entry) in &patterns[..shown] {
+        println!(
+            "  {:<width$} {:>7} reads {:>7} writes {:>7} keys",
+            pattern,
+            entry.reads,
+            entry.writes,
+            entry.keys.len()
+        );
+    }
+}
+
+async fn start_endpoint(endpoint: Endpoint, stats: Arc<Mutex<Stats>>, log: bool) -> Result<()> {
+    let connector = Arc::new(Connector::new(&endpoint).await?);
+    let bind = endpoint.bind.as_deref().unwrap_or(net::LOOPBACK);
+    let listener = net::listen(bind, endpoint.local_port).await?;
+    println!(
+        "🎧 [{}] {} → {}",
+        endpoint.name,
+        net::host_port(bind, endpoint.local_port),
+        connector.describe()
+    );
+
+    let log = log && endpoint.log_traffic.unwrap_or(true);
+    loop {
+        let (client, addr) = listener.accept().await?;
+        // Latency is measured here, so don't add Nagle's delay to it
+        let _ = client.set_nodelay(true);
+        println!("📞 [{}] New connection from {}", endpoint.name, addr);
+        let name = endpoint.name.clone();
+        let connector = connector.clone();
+        let stats = stats.clone();
+        tokio::spawn(async move {
+            let upstream = match connector.connect().await {
+                Ok(upstream) => upstream,
+                Err(e) => {
+                    eprintln!("❌ [{}] Failed to reach Redis: {}", name, e);
+                    return;
+                }
+            };
+            let session = Session::new(name.clone(), stats, log);
+            let (client_reader, client_writer) = client.into_split();
+            tokio::join!(
+                session.pipe(client_reader, upstream.writer, true),
+                session.pipe(upstream.reader, client_writer, false)
+            );
+            println!("🔌 [{}] Connection from {} closed", name, addr);
+        });
+    }
+}
+
+impl Plugin for RedisProxyPlugin {
+    fn name(&self) -> &'static str {
+        "redis_proxy"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Redis sessions with inline command decoding, latency and keyspace summaries"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Forward local ports to Redis endpoints in config file, decoding commands")
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .short('n')
+                    .value_name("NAME")
+                    .help("Only start the endpoint with this name"),
+            )
+            .arg(
+                Arg::new("summary")
+                    .long("summary")
+                    .action(ArgAction::SetTrue)
+                    .help("Print the keys read and written, by pattern, on exit"),
+            )
+            .arg(
+                Arg::new("quiet")
+                    .long("quiet")
+                    .short('q')
+                    .action(ArgAction::SetTrue)
+                    .help("Don't print commands and replies, only the summary"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(RedisProxyPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<RedisProxyConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let endpoints: Vec<Endpoint> = match matches.get_one::<String>("name") {
+            Some(name) => config
+                .endpoint
+                .into_iter()
+                .filter(|endpoint| &endpoint.name == name)
+                .collect(),
+            None => config.endpoint,
+        };
+        if endpoints.is_empty() {
+            eprintln!("❌ No endpoints to start");
+            eprintln!("💡 Add [[endpoint]] entries to the config file");
+            std::process::exit(1);
+        }
+
+        let stats: Vec<(String, Arc<Mutex<Stats>>)> = endpoints
+            .iter()
+            .map(|endpoint| (endpoint.name.clone(), Arc::default()))
+            .collect();
+        let keyspace = matches.get_flag("summary");
+        let summaries = stats.clone();
+        if let Err(e) = ctrlc::set_handler(move || {
+            println!("\n👋 Shutting down...");
+            for (name, stats) in &summaries {
+                print_summary(name, &stats.lock().unwrap(), keyspace);
+            }
+            std::process::exit(0);
+        }) {
+            eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+            std::process::exit(1);
+        }
+
+        let log = !matches.get_flag("quiet");
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        rt.block_on(async {
+            println!("🚀 Starting {} Redis endpoint(s)", endpoints.len());
+            let handles: Vec<_> = endpoints
+                .into_iter()
+                .zip(stats)
+                .map(|(endpoint, (_, stats))| {
+                    tokio::spawn(async move {
+                        let name = endpoint.name.clone();
+                        if let Err(e) = start_endpoint(endpoint, stats, log).await {
+                            eprintln!("❌ [{}] {}", name, e);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(RedisProxyPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,419 @@
+// RESP, the Redis protocol: RESP2 as sent by every client, the RESP3 types of `HELLO 3`
+// connections, and inline commands as typed into telnet. Values are decoded as their
+// bytes arrive, however they are split across reads.
+use anyhow::{anyhow, Result};
+
+/// Longest string shown before it is cut
+const MAX_TEXT: usize = 60;
+/// Array items shown before the rest are counted
+const MAX_ITEMS: usize = 5;
+/// Largest value decoded; a connection sending a larger one is passed through undecoded
+const MAX_VALUE: usize = 16 * 1024 * 1024;
+/// First bytes of RESP2 and RESP3 values
+const TYPES: &[u8] = b"+-:_#,($!=*~>%|";
+
+#[derive(Debug)]
+pub enum Value {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Null,
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    /// Out-of-band RESP3 data such as pub/sub messages, not a reply to a command
+    Push(Vec<Value>),
+    /// Booleans, doubles and big numbers, kept as their text
+    Other(String),
+}
+
+fn line(buf: &[u8], start: usize) -> Option<(&[u8], usize)> {
+    let end = buf[start..].windows(2).position(|w| w == b"\r\n")? + start;
+    Some((&buf[start..end], end + 2))
+}
+
+fn number(text: &[u8]) -> Result<i64> {
+    std::str::from_utf8(text)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "invalid length or integer {:?}",
+                String::from_utf8_lossy(text)
+            )
+        })
+}
+
+/// An array, set, push, map or attribute still waiting for items
+struct Open {
+    kind: u8,
+    /// Items still to come; keys and values both count in maps and attributes
+    remaining: u64,
+    items: Vec<Value>,
+}
+
+impl Open {
+    /// The finished value; `None` for attributes, which only annotate the value after them
+    fn close(self) -> Option<Value> {
+        match self.kind {
+            b'>' => Some(Value::Push(self.items)),
+            b'%' => {
+                let mut items = self.items.into_iter();
+                let mut pairs = Vec::new();
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    pairs.push((key, value));
+                }
+                Some(Value::Map(pairs))
+            }
+            b'|' => None,
+            _ => Some(Value::Array(self.items)),
+        }
+    }
+}
+
+/// What starts at the front of a buffer: a whole value, or a container whose items follow
+enum Item {
+    Value(Value),
+    Open(Open),
+}
+
+/// The item at the front of `buf` and the bytes it took; `None` while it is incomplete
+fn item(buf: &[u8], inline: bool) -> Result<Option<(Item, usize)>> {
+    let Some(&kind) = buf.first() else {
+        return Ok(None);
+    };
+    if inline && !TYPES.contains(&kind) {
+        let Some((text, next)) = line(buf, 0) else {
+            return Ok(None);
+        };
+        let args = text
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(|word| Value::Bulk(word.to_vec()))
+            .collect();
+        return Ok(Some((Item::Value(Value::Array(args)), next)));
+    }
+    let Some((text, next)) = line(buf, 1) else {
+        return Ok(None);
+    };
+    let string = || String::from_utf8_lossy(text).into_owned();
+    let value = match kind {
+        b'+' => Value::Simple(string()),
+        b'-' => Value::Error(string()),
+        b':' => Value::Integer(number(text)?),
+        b'_' => Value::Null,
+        b'#' => Value::Other((text == b"t").to_string()),
+        b',' | b'(' => Value::Other(string()),
+        b'$' | b'!' | b'=' => {
+            let Ok(len) = usize::try_from(number(text)?) else {
+                return Ok(Some((Item::Value(Value::Null), next)));
+            };
+            if len > MAX_VALUE {
+                return Err(anyhow!("a {} byte string", len));
+            }
+            let Some(data) = buf
+                .get(next..next + len)
+                .filter(|_| buf.len() >= next + len + 2)
+            else {
+                return Ok(None);
+            };
+            let value = match kind {
+                b'!' => Value::Error(String::from_utf8_lossy(data).into_owned()),
+                // Verbatim strings start with their format, e.g. "txt:"
+                b'=' => Value::Bulk(data.get(4..).unwrap_or_default().to_vec()),
+                _ => Value::Bulk(data.to_vec()),
+            };
+            return Ok(Some((Item::Value(value), next + len + 2)));
+        }
+        b'*' | b'~' | b'>' | b'%' | b'|' => {
+            let Ok(count) = u64::try_from(number(text)?) else {
+                return Ok(Some((Item::Value(Value::Null), next)));
+            };
+            let remaining = if matches!(kind, b'%' | b'|') {
+                count
+                    .checked_mul(2)
+                    .ok_or_else(|| anyhow!("a map of {} entries", count))?
+            } else {
+                count
+            };
+            let open = Open {
+                kind,
+                remaining,
+                items: Vec::new(),
+            };
+            return Ok(Some((Item::Open(open), next)));
+        }
+        other => return Err(anyhow!("unknown RESP type byte 0x{:02x}", other)),
+    };
+    Ok(Some((Item::Value(value), next)))
+}
+
+/// Decodes the values of one direction of a connection as its bytes arrive. Containers
+/// still missing items are kept with the items read so far, so a value spread over many
+/// reads is parsed once rather than from its start on every read.
+pub struct Decoder {
+    /// Accepts commands written as plain words on a line, which servers take from clients
+    inline: bool,
+    buffer: Vec<u8>,
+    /// Where the bytes not parsed yet start in `buffer`
+    pos: usize,
+    open: Vec<Open>,
+    /// Bytes of the value being decoded already parsed
+    taken: usize,
+}
+
+impl Decoder {
+    pub fn new(inline: bool) -> Self {
+        Self {
+            inline,
+            buffer: Vec::new(),
+            pos: 0,
+            open: Vec::new(),
+            taken: 0,
+        }
+    }
+
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// The next complete value, `None` until more bytes arrive. Fails on bytes that aren't
+    /// RESP and on values larger than MAX_VALUE, which are not kept in memory.
+    pub fn next(&mut self) -> Result<Option<Value>> {
+        loop {
+            let inline = self.inline && self.open.is_empty();
+            let Some((item, used)) = item(&self.buffer[self.pos..], inline)? else {
+                let unparsed = self.buffer.len() - self.pos;
+                if self.taken + unparsed > MAX_VALUE {
+                    return Err(anyhow!("a value over {} bytes", MAX_VALUE));
+                }
+                self.buffer.drain(..self.pos);
+                self.pos = 0;
+                return Ok(None);
+            };
+            self.pos += used;
+            self.taken += used;
+            let mut value = match item {
+                Item::Value(value) => value,
+                Item::Open(open) if open.remaining > 0 => {
+                    self.open.push(open);
+                    continue;
+                }
+                Item::Open(open) => match open.close() {
+                    Some(value) => value,
+                    None => continue,
+                },
+            };
+            // The value is an item of the innermost open container, which it may complete
+            loop {
+                let Some(open) = self.open.last_mut() else {
+                    self.taken = 0;
+                    return Ok(Some(value));
+                };
+                open.items.push(value);
+                open.remaining -= 1;
+                if open.remaining > 0 {
+                    break;
+                }
+                match self.open.pop().and_then(Open::close) {
+                    Some(closed) => value = closed,
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+fn text(data: &[u8]) -> String {
+    let text = String::from_utf8_lossy(data);
+    if text.chars().count() > MAX_TEXT {
+        let cut: String = text.chars().take(MAX_TEXT).collect();
+        format!("{:?}… ({} bytes)", cut, data.len())
+    } else {
+        format!("{:?}", text)
+    }
+}
+
+/// A command argument: bare when it is a plain word, quoted otherwise
+fn word(data: &[u8]) -> String {
+    if !data.is_empty()
+        && data.len() <= MAX_TEXT
+        && data.iter().all(|b| b.is_ascii_graphic() && *b != b'"')
+    {
+        String::from_utf8_lossy(data).into_owned()
+    } else {
+        text(data)
+    }
+}
+
+impl Value {
+    /// The words of a command, for arrays of strings
+    pub fn args(&self) -> Option<Vec<&[u8]>> {
+        let Value::Array(values) = self else {
+            return None;
+        };
+        values
+            .iter()
+            .map(|value| match value {
+                Value::Bulk(data) => Some(data.as_slice()),
+                Value::Simple(text) => Some(text.as_bytes()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// One line in the style of redis-cli, long strings and arrays cut short
+    pub fn summary(&self) -> String {
+        match self {
+            Value::Simple(text) => text.clone(),
+            Value::Error(text) => format!("(error) {}", text),
+            Value::Integer(n) => format!("(integer) {}", n),
+            Value::Bulk(data) => text(data),
+            Value::Null => "(nil)".to_string(),
+            Value::Other(text) => text.clone(),
+            Value::Array(values) | Value::Push(values) => {
+                if values.is_empty() {
+                    return "(empty array)".to_string();
+                }
+                let shown: Vec<String> =
+                    values.iter().take(MAX_ITEMS).map(Value::summary).collect();
+                let more = match values.len().saturating_sub(MAX_ITEMS) {
+                    0 => String::new(),
+                    n => format!(", … (+{})", n),
+                };
+                format!("[{}] {}{}", values.len(), shown.join(", "), more)
+            }
+            Value::Map(pairs) => {
+                let shown: Vec<String> = pairs
+                    .iter()
+                    .take(MAX_ITEMS)
+                    .map(|(key, value)| format!("{} => {}", key.summary(), value.summary()))
+                    .collect();
+                let more = match pairs.len().saturating_sub(MAX_ITEMS) {
+                    0 => String::new(),
+                    n => format!(", … (+{})", n),
+                };
+                format!("{{{}}} {}{}", pairs.len(), shown.join(", "), more)
+            }
+        }
+    }
+}
+
+/// A command as typed in redis-cli, with the password of AUTH and HELLO masked
+pub fn command_line(args: &[&[u8]]) -> String {
+    let name = args
+        .first()
+        .map(|name| String::from_utf8_lossy(name).to_uppercase())
+        .unwrap_or_default();
+    let password = match name.as_str() {
+        "AUTH" => Some(args.len() - 1),
+        // HELLO protover AUTH username password
+        "HELLO" => args
+            .iter()
+            .position(|arg| arg.eq_ignore_ascii_case(b"AUTH"))
+            .map(|i| i + 2),
+        _ => None,
+    };
+    let words: Vec<String> = args
+        .iter()
+        .enumerate()
+        .take(MAX_ITEMS * 2)
+        .map(|(i, arg)| {
+            if Some(i) == password && i > 0 {
+                "****".to_string()
+            } else {
+                word(arg)
+            }
+        })
+        .collect();
+    let more = match args.len().saturating_sub(MAX_ITEMS * 2) {
+        0 => String::new(),
+        n => format!(" … (+{} args)", n),
+    };
+    format!("{}{}", words.join(" "), more)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The values in `bytes`, fed `chunk` bytes at a time
+    fn decode(bytes: &[u8], chunk: usize, inline: bool) -> Result<Vec<String>> {
+        let mut decoder = Decoder::new(inline);
+        let mut values = Vec::new();
+        for part in bytes.chunks(chunk) {
+            decoder.extend(part);
+            while let Some(value) = decoder.next()? {
+                values.push(value.summary());
+            }
+        }
+        Ok(values)
+    }
+
+    #[test]
+    fn values_split_anywhere_come_out_once() {
+        let bytes = b"*3\r\n$3\r\nSET\r\n$4\r\nuser\r\n$5\r\nalice\r\n+OK\r\n:42\r\n$-1\r\n*2\r\n*1\r\n+a\r\n*0\r\n";
+        let expected = [
+            "[3] \"SET\", \"user\", \"alice\"",
+            "OK",
+            "(integer) 42",
+            "(nil)",
+            "[2] [1] a, (empty array)",
+        ];
+        for chunk in 1..=bytes.len() {
+            assert_eq!(decode(bytes, chunk, false).unwrap(), expected, "{}", chunk);
+        }
+    }
+
+    #[test]
+    fn resp3_maps_pushes_and_attributes() {
+        let bytes = b"%2\r\n+first\r\n:1\r\n$6\r\nsecond\r\n#t\r\n\
+            |1\r\n+ttl\r\n:3600\r\n$5\r\nvalue\r\n\
+            >3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n=8\r\ntxt:hi!!\r\n\
+            *2\r\n|1\r\n+a\r\n+b\r\n:1\r\n_\r\n";
+        let expected = [
+            "{2} first => (integer) 1, \"second\" => true",
+            "\"value\"",
+            "[3] \"message\", \"news\", \"hi!!\"",
+            "[2] (integer) 1, (nil)",
+        ];
+        for chunk in [1, 3, bytes.len()] {
+            assert_eq!(decode(bytes, chunk, false).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn inline_commands_only_from_clients() {
+        let bytes = b"PING\r\nSET  key  value\r\n*1\r\n$4\r\nQUIT\r\n";
+        assert_eq!(
+            decode(bytes, 2, true).unwrap(),
+            [
+                "[1] \"PING\"",
+                "[3] \"SET\", \"key\", \"value\"",
+                "[1] \"QUIT\""
+            ]
+        );
+        assert!(decode(b"PING\r\n", 6, false).is_err());
+    }
+
+    #[test]
+    fn bad_or_oversized_values_stop_decoding() {
+        // Counted in u64, twice the largest map still fits: it waits for its entries
+        assert!(decode(b"%9223372036854775807\r\n", 64, false)
+            .unwrap()
+            .is_empty());
+        assert!(decode(b"$999999999999\r\n", 64, false).is_err());
+        assert!(decode(b":12x\r\n", 64, false).is_err());
+        // A value growing past the limit without ever completing
+        let mut bytes = b"*1000000000\r\n".to_vec();
+        bytes.extend(
+            b"$1024\r\n"
+                .iter()
+                .chain(&[b'x'; 1024])
+                .chain(b"\r\n")
+                .cycle()
+                .take(MAX_VALUE + 4096),
+        );
+        assert!(decode(&bytes, 65536, false).is_err());
+    }
+}
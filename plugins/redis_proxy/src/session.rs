@@ -0,0 +1,184 @@
+// One client connection: bytes are copied both ways unchanged while a copy of each
+// direction is parsed as RESP. Replies come back in command order, so each reply is
+// matched to the oldest command still waiting, which gives its latency. Once a connection
+// subscribes or runs MONITOR the server sends messages nobody asked for, and matching
+// stops for it.
+use crate::commands::{self, Access};
+use crate::resp::{self, Value};
+use chrono::Utc;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Distinct keys remembered per pattern; beyond that only accesses are counted
+const MAX_KEYS: usize = 10_000;
+
+#[derive(Default)]
+pub struct CommandStats {
+    pub count: u64,
+    pub errors: u64,
+    /// Milliseconds of every matched reply
+    pub latencies: Vec<f64>,
+}
+
+#[derive(Default)]
+pub struct KeyStats {
+    pub reads: u64,
+    pub writes: u64,
+    pub keys: HashSet<String>,
+}
+
+/// What an endpoint's connections did, for the summary at the end
+#[derive(Default)]
+pub struct Stats {
+    pub commands: BTreeMap<String, CommandStats>,
+    pub keyspace: BTreeMap<String, KeyStats>,
+}
+
+impl Stats {
+    fn command(&mut self, name: &str, args: &[&[u8]]) {
+        self.commands.entry(name.to_string()).or_default().count += 1;
+        let Some((access, keys)) = commands::keys(args) else {
+            return;
+        };
+        for key in keys {
+            let entry = self.keyspace.entry(commands::pattern(key)).or_default();
+            match access {
+                Access::Read => entry.reads += 1,
+                Access::Write => entry.writes += 1,
+            }
+            if entry.keys.len() < MAX_KEYS {
+                entry.keys.insert(String::from_utf8_lossy(key).into_owned());
+            }
+        }
+    }
+
+    fn reply(&mut self, name: &str, millis: f64, error: bool) {
+        let entry = self.commands.entry(name.to_string()).or_default();
+        entry.latencies.push(millis);
+        if error {
+            entry.errors += 1;
+        }
+    }
+}
+
+/// What the two directions of a connection share
+pub struct Session {
+    endpoint: String,
+    stats: Arc<Mutex<Stats>>,
+    /// Print every command and reply
+    log: bool,
+    pending: Mutex<VecDeque<(String, Instant)>>,
+    streaming: Mutex<bool>,
+}
+
+fn timestamp() -> String {
+    Utc::now().format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string()
+}
+
+impl Session {
+    pub fn new(endpoint: String, stats: Arc<Mutex<Stats>>, log: bool) -> Self {
+        Self {
+            endpoint,
+            stats,
+            log,
+            pending: Mutex::new(VecDeque::new()),
+            streaming: Mutex::new(false),
+        }
+    }
+
+    fn command(&self, value: Value) {
+        let Some(args) = value.args().filter(|args| !args.is_empty()) else {
+            return;
+        };
+        let name = String::from_utf8_lossy(args[0]).to_uppercase();
+        if self.log {
+            println!(
+                "🔴 [{}] [{}] → {}",
+                timestamp(),
+                self.endpoint,
+                resp::command_line(&args)
+            );
+        }
+        self.stats.lock().unwrap().command(&name, &args);
+
+        let mut streaming = self.streaming.lock().unwrap();
+        if matches!(
+            name.as_str(),
+            "SUBSCRIBE" | "PSUBSCRIBE" | "SSUBSCRIBE" | "MONITOR"
+        ) {
+            *streaming = true;
+        }
+        if !*streaming {
+            self.pending
+                .lock()
+                .unwrap()
+                .push_back((name, Instant::now()));
+        }
+    }
+
+    fn reply(&self, value: Value) {
+        let answered = match value {
+            Value::Push(_) => None,
+            _ => self.pending.lock().unwrap().pop_front(),
+        };
+        let timing = match &answered {
+            Some((name, sent)) => {
+                let millis = sent.elapsed().as_secs_f64() * 1000.0;
+                let error = matches!(value, Value::Error(_));
+                self.stats.lock().unwrap().reply(name, millis, error);
+                format!("{} ({:.2}ms) ", name, millis)
+            }
+            None => String::new(),
+        };
+        if self.log {
+            println!(
+                "🔴 [{}] [{}] ← {}{}",
+                timestamp(),
+                self.endpoint,
+                timing,
+                value.summary()
+            );
+        }
+    }
+
+    /// Copies `from` to `to`, parsing what passes as commands (`to_server`) or replies
+    pub async fn pipe<R, W>(&self, mut from: R, mut to: W, to_server: bool)
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut buffer = vec![0u8; 8192];
+        let mut decoder = Some(resp::Decoder::new(to_server));
+        loop {
+            let n = match from.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            if to.write_all(&buffer[..n]).await.is_err() {
+                break;
+            }
+            let Some(decoding) = decoder.as_mut() else {
+                continue;
+            };
+            decoding.extend(&buffer[..n]);
+            loop {
+                match decoding.next() {
+                    Ok(Some(value)) if to_server => self.command(value),
+                    Ok(Some(value)) => self.reply(value),
+                    Ok(None) => break,
+                    Err(e) => {
+                        eprintln!(
+                            "⚠️  [{}] Stopped decoding ({}), passing the rest through undecoded",
+                            self.endpoint, e
+                        );
+                        decoder = None;
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = to.shutdown().await;
+    }
+}
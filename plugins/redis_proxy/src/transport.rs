@@ -0,0 +1,182 @@
+// How connections reach Redis: straight to host:port, through `ssh -W` via a jump host
+// (so ~/.ssh/config, keys and the agent apply), or to a pod's port over the Kubernetes
+// port-forward API. Each client connection gets its own upstream connection.
+use crate::{Endpoint, Transport};
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use kube::Client;
+use plugin_api::k8s;
+use plugin_api::net;
+use std::process::Stdio;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+
+pub struct Upstream {
+    pub reader: Box<dyn AsyncRead + Unpin + Send>,
+    pub writer: Box<dyn AsyncWrite + Unpin + Send>,
+    /// The ssh process carrying the connection, killed when it is dropped
+    _ssh: Option<Child>,
+}
+
+impl Upstream {
+    fn new<R, W>(reader: R, writer: W, ssh: Option<Child>) -> Self
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            _ssh: ssh,
+        }
+    }
+}
+
+/// Opens connections for one endpoint
+pub enum Connector {
+    Direct(String, u16),
+    Ssh {
+        jump: String,
+        user: Option<String>,
+        target: String,
+    },
+    Kubernetes {
+        pods: Api<Pod>,
+        namespace: String,
+        pod_name: Option<String>,
+        pod_selector: Option<String>,
+        port: u16,
+    },
+}
+
+impl Connector {
+    pub async fn new(endpoint: &Endpoint) -> Result<Self> {
+        let port = endpoint.port();
+        let host = endpoint
+            .host
+            .as_deref()
+            .map_or("localhost", net::unbracket)
+            .to_string();
+        match endpoint.transport {
+            Transport::Direct => Ok(Connector::Direct(host, port)),
+            Transport::Ssh => Ok(Connector::Ssh {
+                jump: endpoint
+                    .ssh_host
+                    .clone()
+                    .ok_or_else(|| anyhow!("Endpoint '{}' needs an ssh_host", endpoint.name))?,
+                user: endpoint.ssh_user.clone(),
+                target: net::host_port(&host, port),
+            }),
+            Transport::K8s => {
+                if endpoint.pod_name.is_none() && endpoint.pod_selector.is_none() {
+                    return Err(anyhow!(
+                        "Endpoint '{}' needs a pod_name or pod_selector",
+                        endpoint.name
+                    ));
+                }
+                let client = Client::try_default().await?;
+                let namespace = endpoint
+                    .namespace
+                    .clone()
+                    .unwrap_or_else(|| client.default_namespace().to_string());
+                Ok(Connector::Kubernetes {
+                    pods: Api::namespaced(client, &namespace),
+                    namespace,
+                    pod_name: endpoint.pod_name.clone(),
+                    pod_selector: endpoint.pod_selector.clone(),
+                    port,
+                })
+            }
+        }
+    }
+
+    /// Where connections go, for the startup message
+    pub fn describe(&self) -> String {
+        match self {
+            Connector::Direct(host, port) => net::host_port(host, *port),
+            Connector::Ssh { jump, target, .. } => format!("{} via ssh {}", target, jump),
+            Connector::Kubernetes {
+                pod_name,
+                pod_selector,
+                port,
+                ..
+            } => {
+                let pod = pod_name.as_ref().or(pod_selector.as_ref());
+                format!(
+                    "pod {}:{}",
+                    pod.map(String::as_str).unwrap_or_default(),
+                    port
+                )
+            }
+        }
+    }
+
+    /// The pod to forward to, looked up again for every connection so restarts are
+    /// followed
+    async fn pod(pods: &Api<Pod>, name: Option<&str>, selector: Option<&str>) -> Result<String> {
+        if let Some(name) = name {
+            return Ok(name.to_string());
+        }
+        let pod = k8s::running_pod(pods, selector.unwrap_or_default()).await?;
+        pod.metadata.name.ok_or_else(|| anyhow!("Pod has no name"))
+    }
+
+    pub async fn connect(&self) -> Result<Upstream> {
+        match self {
+            Connector::Direct(host, port) => {
+                let stream = TcpStream::connect((host.as_str(), *port)).await?;
+                let _ = stream.set_nodelay(true);
+                let (reader, writer) = stream.into_split();
+                Ok(Upstream::new(reader, writer, None))
+            }
+            Connector::Ssh { jump, user, target } => {
+                let mut command = Command::new("ssh");
+                command.arg("-o").arg("BatchMode=yes");
+                if let Some(user) = user {
+                    command.arg("-l").arg(user);
+                }
+                let mut child = command
+                    .arg("-W")
+                    .arg(target)
+                    .arg(jump)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| anyhow!("Failed to start ssh: {}", e))?;
+                let writer = child.stdin.take().ok_or_else(|| anyhow!("No ssh stdin"))?;
+                let reader = child
+                    .stdout
+                    .take()
+                    .ok_or_else(|| anyhow!("No ssh stdout"))?;
+                Ok(Upstream::new(reader, writer, Some(child)))
+            }
+            Connector::Kubernetes {
+                pods,
+                namespace,
+                pod_name,
+                pod_selector,
+                port,
+            } => {
+                let pod = Self::pod(pods, pod_name.as_deref(), pod_selector.as_deref()).await?;
+                plugin_api::audit::record_k8s(
+                    "redis_proxy",
+                    "port_forward",
+                    &[
+                        ("namespace", namespace),
+                        ("pod", &pod),
+                        ("remote_port", &port.to_string()),
+                    ],
+                );
+                let mut forwarder = pods.portforward(&pod, &[*port]).await?;
+                let stream = forwarder
+                    .take_stream(*port)
+                    .ok_or_else(|| anyhow!("No stream for port {}", port))?;
+                let (reader, writer) = tokio::io::split(stream);
+                Ok(Upstream::new(reader, writer, None))
+            }
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// Local SOCKS5 server. Each connection is sent to the upstream of the first route
+// matching its host (or the default upstream): direct, through an SSH jump host, or
+// from inside a Kubernetes pod, so in-cluster services are reachable by their DNS
+// names without setting up a port forward for each of them.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::net::{self, Listener};
+use plugin_api::traffic::relay_to;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+mod socks;
+mod upstream;
+
+const PLUGIN_NAME: &str = "socks5_proxy";
+const DEFAULT_LISTEN: &str = "127.0.0.1:1080";
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Socks5Config {
+    /// Address the SOCKS server listens on (default 127.0.0.1:1080), or a Unix socket path
+    pub listen: Option<String>,
+    /// Permissions of the Unix socket listened on, e.g. 0o660 (default: umask)
+    pub socket_mode: Option<u32>,
+    /// Upstream for hosts no route matches: direct (default), ssh or k8s
+    #[serde(default)]
+    pub upstream: upstream::Kind,
+    pub ssh: Option<SshUpstream>,
+    pub k8s: Option<K8sUpstream>,
+    /// Checked in order, the first route whose pattern matches the host wins
+    #[serde(default)]
+    pub route: Vec<Route>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SshUpstream {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// Key to log in with; the SSH agent and ~/.ssh/config are used otherwise
+    pub identity_file: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct K8sUpstream {
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    pub pod_name: Option<String>,
+    /// Label selector, the first running pod is used
+    pub pod_selector: Option<String>,
+    /// Container to dial from; it needs bash
+    pub container: Option<String>,
+}
+
+fn default_namespace() -> String {
+    "default".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Route {
+    /// Exact host name, "*.suffix" or "*"
+    pub host: String,
+    pub upstream: upstream::Kind,
+}
+
+impl Route {
+    fn matches(&self, host: &str) -> bool {
+        match self.host.strip_prefix('*') {
+            Some("") => true,
+            Some(suffix) => host
+                .to_ascii_lowercase()
+                .ends_with(&suffix.to_ascii_lowercase()),
+            None => host.eq_ignore_ascii_case(&self.host),
+        }
+    }
+}
+
+impl Socks5Config {
+    fn upstream_for(&self, host: &str) -> upstream::Kind {
+        self.route
+            .iter()
+            .find(|route| route.matches(host))
+            .map(|route| route.upstream)
+            .unwrap_or(self.upstream)
+    }
+
+    fn uses(&self, kind: upstream::Kind) -> bool {
+        self.upstream == kind || self.route.iter().any(|route| route.upstream == kind)
+    }
+}
+
+pub struct Socks5ProxyPlugin;
+
+impl Socks5ProxyPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# SOCKS5 Proxy Configuration
+listen = "127.0.0.1:1080"  # or a Unix socket: "/tmp/socks5.sock"
+# socket_mode = 0o600
+upstream = "direct"  # Options: direct, ssh, k8s
+
+# Cluster names are resolved and dialed from inside a pod, everything else goes direct
+[[route]]
+host = "*.svc.cluster.local"
+upstream = "k8s"
+
+[[route]]
+host = "*.internal.example.com"
+upstream = "ssh"
+
+# Pod the k8s upstream connects from (needs bash)
+[k8s]
+namespace = "default"
+pod_selector = "app=toolbox"
+# pod_name = "toolbox-0"
+# container = "shell"
+
+# Jump host for the ssh upstream; the agent and ~/.ssh/config are honored
+[ssh]
+host = "bastion.example.com"
+# user = "me"
+# port = 22
+# identity_file = "~/.ssh/id_ed25519"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<Socks5Config> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: Socks5Config = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", Socks5ProxyPlugin::sample_config());
+                Ok(Socks5Config::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(Socks5Config::default())
+        }
+    }
+}
+
+async fn connect(
+    config: &Socks5Config,
+    gateway: Option<&upstream::Gateway>,
+    target: &socks::Target,
+    kind: upstream::Kind,
+) -> Result<upstream::Connection> {
+    match kind {
+        upstream::Kind::Direct => upstream::direct(target).await,
+        upstream::Kind::Ssh => {
+            let ssh = config
+                .ssh
+                .as_ref()
+                .ok_or_else(|| anyhow!("No [ssh] upstream configured"))?;
+            upstream::ssh(ssh, target).await
+        }
+        upstream::Kind::K8s => {
+            let gateway = gateway.ok_or_else(|| anyhow!("No [k8s] upstream configured"))?;
+            upstream::k8s(gateway, target).await
+        }
+    }
+}
+
+async fn handle_connection(
+    mut stream: net::Stream,
+    config: Arc<Socks5Config>,
+    gateway: Option<Arc<upstream::Gateway>>,
+) -> Result<()> {
+    let target = socks::accept(&mut stream).await?;
+    let kind = config.upstream_for(&target.host);
+    let connection = match connect(&config, gateway.as_deref(), &target, kind).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            let code = if kind == upstream::Kind::Direct {
+                socks::HOST_UNREACHABLE
+            } else {
+                socks::GENERAL_FAILURE
+            };
+            let _ = socks::reply(&mut stream, code).await;
+            return Err(anyhow!("{} via {:?}: {}", target, kind, e));
+        }
+    };
+    socks::reply(&mut stream, socks::SUCCEEDED).await?;
+    println!("🔗 {} via {:?}", target, kind);
+
+    // The carrier stays in `connection` until the relay is done
+    relay_to(
+        &target.to_string(),
+        stream,
+        connection.reader,
+        connection.writer,
+        None,
+    )
+    .await;
+
+    println!("🔌 {} closed", target);
+    Ok(())
+}
+
+async fn start_server(config: Socks5Config) -> Result<()> {
+    let listen = config
+        .listen
+        .clone()
+        .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+
+    println!("🚀 Starting SOCKS5 proxy");
+    println!("🎯 Default upstream: {:?}", config.upstream);
+    for route in &config.route {
+        println!("🧭 {} → {:?}", route.host, route.upstream);
+    }
+
+    let gateway = if config.uses(upstream::Kind::K8s) {
+        let k8s = config
+            .k8s
+            .as_ref()
+            .ok_or_else(|| anyhow!("The k8s upstream is used but [k8s] is not configured"))?;
+        println!(
+            "☸️  Pods in namespace {}: {}",
+            k8s.namespace,
+            k8s.pod_name
+                .as_deref()
+                .or(k8s.pod_selector.as_deref())
+                .unwrap_or("(none)")
+        );
+        let client = kube::Client::try_default().await?;
+        Some(Arc::new(upstream::Gateway::new(client, k8s.clone())))
+    } else {
+        None
+    };
+    if config.uses(upstream::Kind::Ssh) {
+        let ssh = config
+            .ssh
+            .as_ref()
+            .ok_or_else(|| anyhow!("The ssh upstream is used but [ssh] is not configured"))?;
+        println!("🔐 SSH jump host: {}", ssh.host);
+    }
+
+    ctrlc::set_handler(move || {
+        println!("\n👋 Shutting down...");
+        std::process::exit(0);
+    })?;
+
+    let listener = match net::unix_path(&listen) {
+        Some(path) => {
+            let listener = net::bind(&listen, 0, config.socket_mode).await?;
+            println!("🎧 Listening on {}", path);
+            println!(
+                "💡 Example: curl --proxy socks5h://localhost{} http://my-svc.my-ns.svc.cluster.local\n",
+                path
+            );
+            listener
+        }
+        None => {
+            let listener = Listener::Tcp(TcpListener::bind(&listen).await?);
+            println!("🎧 Listening on socks5://{}", listen);
+            println!(
+                "💡 Example: curl --socks5-hostname {} http://my-svc.my-ns.svc.cluster.local\n",
+                listen
+            );
+            listener
+        }
+    };
+    plugin_api::usage::init(PLUGIN_NAME);
+
+    let config = Arc::new(config);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let config = config.clone();
+                let gateway = gateway.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, config, gateway).await {
+                        eprintln!("❌ Connection error: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to accept connection: {}", e);
+            }
+        }
+    }
+}
+
+impl Plugin for Socks5ProxyPlugin {
+    fn name(&self) -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Local SOCKS5 proxy with direct, SSH and Kubernetes pod upstreams"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Local SOCKS5 proxy reaching hosts directly, through SSH or from a pod")
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .short('l')
+                    .value_name("ADDR")
+                    .help("Override the listen address from config file (e.g., 127.0.0.1:1080)"),
+            )
+            .arg(
+                Arg::new("upstream")
+                    .long("upstream")
+                    .short('u')
+                    .value_name("UPSTREAM")
+                    .value_parser(clap::value_parser!(upstream::Kind))
+                    .help("Override the default upstream: direct, ssh, k8s"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(Socks5ProxyPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<Socks5Config>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let mut config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Some(listen) = matches.get_one::<String>("listen") {
+                config.listen = Some(listen.clone());
+            }
+
+            if let Some(upstream) = matches.get_one::<upstream::Kind>("upstream") {
+                config.upstream = *upstream;
+            }
+
+            if let Err(e) = start_server(config).await {
+                eprintln!("❌ SOCKS5 proxy error: {}", e);
+                std::process::exit(1);
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(Socks5ProxyPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
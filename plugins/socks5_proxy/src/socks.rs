@@ -0,0 +1,92 @@
+// Server side of the SOCKS5 handshake (RFC 1928), limited to what browsers and CLIs
+// use: no authentication and the CONNECT command, with IPv4, IPv6 or domain targets.
+// Domains are passed on unresolved so the upstream can resolve them, which is what
+// makes cluster-internal names work through a pod.
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const NO_ACCEPTABLE_METHOD: u8 = 0xFF;
+const CONNECT: u8 = 1;
+
+pub const SUCCEEDED: u8 = 0;
+pub const GENERAL_FAILURE: u8 = 1;
+pub const HOST_UNREACHABLE: u8 = 4;
+const COMMAND_NOT_SUPPORTED: u8 = 7;
+const ADDRESS_NOT_SUPPORTED: u8 = 8;
+
+/// Destination requested by the client
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub host: String,
+    pub port: u16,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Negotiates the method and reads the CONNECT request
+pub async fn accept<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> Result<Target> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != VERSION {
+        return Err(anyhow!("Not a SOCKS5 client (version {})", header[0]));
+    }
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&NO_AUTH) {
+        stream.write_all(&[VERSION, NO_ACCEPTABLE_METHOD]).await?;
+        return Err(anyhow!("The client requires authentication"));
+    }
+    stream.write_all(&[VERSION, NO_AUTH]).await?;
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[1] != CONNECT {
+        reply(stream, COMMAND_NOT_SUPPORTED).await?;
+        return Err(anyhow!("Unsupported SOCKS command {}", request[1]));
+    }
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            stream.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let len = stream.read_u8().await? as usize;
+            let mut name = vec![0u8; len];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name).map_err(|_| anyhow!("Invalid domain name"))?
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            stream.read_exact(&mut ip).await?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        other => {
+            reply(stream, ADDRESS_NOT_SUPPORTED).await?;
+            return Err(anyhow!("Unsupported address type {}", other));
+        }
+    };
+    let port = stream.read_u16().await?;
+    Ok(Target { host, port })
+}
+
+/// Answers the CONNECT request. The bound address is not meaningful for a proxy that
+/// dials through SSH or a pod, so it is always 0.0.0.0:0.
+pub async fn reply<S: AsyncWrite + Unpin>(stream: &mut S, code: u8) -> Result<()> {
+    stream
+        .write_all(&[VERSION, code, 0, 1, 0, 0, 0, 0, 0, 0])
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,238 @@
+// Ways to reach a SOCKS target: a direct TCP connection, `ssh -W` through a jump host,
+// or the native forward transport of k8s_native_port_forward (bash /dev/tcp run with
+// exec in a pod), which resolves and dials the target from inside the cluster.
+use crate::socks::Target;
+use crate::{K8sUpstream, SshUpstream, PLUGIN_NAME};
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{AttachParams, ListParams};
+use kube::{Api, Client};
+use serde::Deserialize;
+use std::process::Stdio;
+use std::str::FromStr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Direct,
+    Ssh,
+    K8s,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "direct" => Ok(Kind::Direct),
+            "ssh" => Ok(Kind::Ssh),
+            "k8s" => Ok(Kind::K8s),
+            other => Err(format!(
+                "unknown upstream '{}', expected direct, ssh or k8s",
+                other
+            )),
+        }
+    }
+}
+
+/// An open connection to the target
+pub struct Connection {
+    pub reader: Box<dyn AsyncRead + Unpin + Send>,
+    pub writer: Box<dyn AsyncWrite + Unpin + Send>,
+    /// The ssh process or exec session carrying the connection, closed when dropped
+    _carrier: Option<Box<dyn Send>>,
+}
+
+pub async fn direct(target: &Target) -> Result<Connection> {
+    let stream = TcpStream::connect((target.host.as_str(), target.port)).await?;
+    let (reader, writer) = stream.into_split();
+    Ok(Connection {
+        reader: Box::new(reader),
+        writer: Box::new(writer),
+        _carrier: None,
+    })
+}
+
+pub async fn ssh(config: &SshUpstream, target: &Target) -> Result<Connection> {
+    let destination = match &config.user {
+        Some(user) => format!("{}@{}", user, config.host),
+        None => config.host.clone(),
+    };
+    let mut command = Command::new("ssh");
+    command
+        .arg("-W")
+        .arg(target.to_string())
+        .arg("-o")
+        .arg("BatchMode=yes");
+    if let Some(port) = config.port {
+        command.arg("-p").arg(port.to_string());
+    }
+    if let Some(identity) = &config.identity_file {
+        command.arg("-i").arg(identity);
+    }
+    // Browsers open many connections; share one SSH session between them
+    if cfg!(unix) {
+        if let Some(dir) = plugin_api::plugin_state_dir(PLUGIN_NAME) {
+            std::fs::create_dir_all(&dir)?;
+            command
+                .arg("-o")
+                .arg("ControlMaster=auto")
+                .arg("-o")
+                .arg(format!("ControlPath={}/ssh-%C", dir.display()))
+                .arg("-o")
+                .arg("ControlPersist=60");
+        }
+    }
+    let mut child = command
+        .arg(destination)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to start ssh: {}", e))?;
+    let writer = child.stdin.take().ok_or_else(|| anyhow!("No ssh stdin"))?;
+    let reader = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("No ssh stdout"))?;
+    Ok(Connection {
+        reader: Box::new(reader),
+        writer: Box::new(writer),
+        _carrier: Some(Box::new(child)),
+    })
+}
+
+/// The host ends up in a shell command, so only plain names and addresses pass
+fn valid_host(host: &str) -> bool {
+    !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':'))
+}
+
+async fn gateway_pod(client: &Client, config: &K8sUpstream) -> Result<String> {
+    if let Some(name) = &config.pod_name {
+        return Ok(name.clone());
+    }
+    let selector = config
+        .pod_selector
+        .as_deref()
+        .ok_or_else(|| anyhow!("The k8s upstream needs pod_name or pod_selector"))?;
+    let pods: Api<Pod> = Api::namespaced(client.clone(), &config.namespace);
+    let list = pods.list(&ListParams::default().labels(selector)).await?;
+    list.items
+        .iter()
+        .filter(|pod| {
+            pod.status
+                .as_ref()
+                .and_then(|status| status.phase.as_deref())
+                == Some("Running")
+        })
+        .find_map(|pod| pod.metadata.name.clone())
+        .ok_or_else(|| anyhow!("No running pod matches selector: {}", selector))
+}
+
+/// The pod the k8s upstream dials from. A selector is resolved on the first connection
+/// and again only when exec in the pod it found fails.
+pub struct Gateway {
+    client: Client,
+    config: K8sUpstream,
+    pod: Mutex<Option<String>>,
+}
+
+impl Gateway {
+    pub fn new(client: Client, config: K8sUpstream) -> Self {
+        Self {
+            client,
+            config,
+            pod: Mutex::new(None),
+        }
+    }
+
+    async fn pod(&self) -> Result<String> {
+        let mut pod = self.pod.lock().await;
+        if let Some(name) = pod.as_ref() {
+            return Ok(name.clone());
+        }
+        let name = gateway_pod(&self.client, &self.config).await?;
+        *pod = Some(name.clone());
+        Ok(name)
+    }
+
+    /// Drops `failed` so the next connection resolves the selector again, unless another
+    /// connection already did
+    async fn forget(&self, failed: &str) {
+        let mut pod = self.pod.lock().await;
+        if pod.as_deref() == Some(failed) {
+            *pod = None;
+        }
+    }
+
+    async fn exec(&self, pod: &str, target: &Target) -> Result<Connection> {
+        plugin_api::audit::record_k8s(
+            crate::PLUGIN_NAME,
+            "exec_transport",
+            &[
+                ("namespace", &self.config.namespace),
+                ("pod", pod),
+                (
+                    "target",
+                    &plugin_api::net::host_port(&target.host, target.port),
+                ),
+            ],
+        );
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &self.config.namespace);
+        let params = AttachParams {
+            container: self.config.container.clone(),
+            tty: false,
+            stdin: true,
+            stdout: true,
+            stderr: false,
+            ..AttachParams::default()
+        };
+        // The reading cat is a job of this shell, so it can be killed once the client is done
+        let script = format!(
+            "exec 3<>/dev/tcp/{}/{}; cat <&3 & pid=$!; cat >&3; kill $pid 2>/dev/null; exec 3>&-",
+            target.host, target.port
+        );
+        let mut attached = pods
+            .exec(
+                pod,
+                vec!["bash".to_string(), "-c".to_string(), script],
+                &params,
+            )
+            .await?;
+        let writer = attached.stdin().ok_or_else(|| anyhow!("No stdin"))?;
+        let reader = attached.stdout().ok_or_else(|| anyhow!("No stdout"))?;
+        Ok(Connection {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            _carrier: Some(Box::new(attached)),
+        })
+    }
+}
+
+pub async fn k8s(gateway: &Gateway, target: &Target) -> Result<Connection> {
+    if !valid_host(&target.host) {
+        return Err(anyhow!("Refusing unusual host name: {}", target.host));
+    }
+    let pod = gateway.pod().await?;
+    match gateway.exec(&pod, target).await {
+        // The pod may be gone; a selector can find another
+        Err(e) if gateway.config.pod_name.is_none() => {
+            eprintln!(
+                "⚠️  Exec in pod {} failed ({}), looking it up again",
+                pod, e
+            );
+            gateway.forget(&pod).await;
+            let pod = gateway.pod().await?;
+            gateway.exec(&pod, target).await
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,201 @@
+// Forwards of a tunnel, written like ssh's -L, -R and -D arguments, Unix socket paths
+// included. Every byte passes through this process so it can be logged with
+// plugin_api::traffic:
+// - local: a listener here, each connection carried by a channel of the session
+// - remote: the server listens, and each connection it hands over is dialed from here
+// - dynamic: a SOCKS5 listener here, each connection carried by a channel of the session
+use crate::session::Connection;
+use crate::socks;
+use crate::Tunnel;
+use anyhow::{anyhow, Result};
+use plugin_api::net;
+use plugin_api::traffic::{relay_to, Protocol};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream};
+
+const LOOPBACK: &str = "127.0.0.1";
+
+/// A parsed `[bind_address:]port:host:hostport`, either side of which may be a Unix
+/// socket path instead
+#[derive(Debug, Clone)]
+pub struct Spec {
+    /// Bind address, or the socket to listen on
+    pub bind: Option<String>,
+    /// Unused when listening on a socket
+    pub port: u16,
+    /// `host:port`, or a socket path, on the other end
+    pub target: String,
+}
+
+fn port(value: &str, spec: &str) -> Result<u16> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid port '{}' in forward '{}'", value, spec))
+}
+
+/// Splits a spec on the colons outside brackets, so IPv6 addresses can be given as [::1]
+fn fields(spec: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let (mut start, mut depth) = (0, 0);
+    for (i, c) in spec.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ':' if depth == 0 => {
+                fields.push(net::unbracket(&spec[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(net::unbracket(&spec[start..]));
+    fields
+}
+
+impl Spec {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid forward '{}', expected [bind_address:]port:host:hostport, with a \
+                 socket path for either side",
+                spec
+            )
+        };
+        let parts = fields(spec);
+        let (bind, listen_port, rest) = match parts.as_slice() {
+            [socket, rest @ ..] if net::unix_path(socket).is_some() => {
+                (Some(socket.to_string()), 0, rest)
+            }
+            [value, rest @ ..] if value.parse::<u16>().is_ok() => (None, port(value, spec)?, rest),
+            [bind, value, rest @ ..] => (Some(bind.to_string()), port(value, spec)?, rest),
+            _ => return Err(invalid()),
+        };
+        let target = match rest {
+            [socket] if net::unix_path(socket).is_some() => socket.to_string(),
+            [host, value] => net::host_port(host, port(value, spec)?),
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            bind,
+            port: listen_port,
+            target,
+        })
+    }
+}
+
+/// Parses a dynamic forward, `[bind_address:]port` or a socket path
+pub fn parse_dynamic(spec: &str) -> Result<(String, u16)> {
+    if net::unix_path(spec).is_some() {
+        return Ok((spec.to_string(), 0));
+    }
+    match net::split_host_port(spec) {
+        (bind, Some(value)) => Ok((bind.to_string(), port(value, spec)?)),
+        (value, None) => Ok((LOOPBACK.to_string(), port(value, spec)?)),
+    }
+}
+
+/// Everything a forward needs to open connections through the tunnel
+pub struct Context {
+    pub tunnel: Tunnel,
+    pub connection: Connection,
+    /// None when traffic logging is off
+    pub protocol: Option<Protocol>,
+}
+
+/// Carries one client connection to `target`, a `host:port` or socket path on the
+/// server, over a channel of the session
+async fn through_ssh<C>(context: &Context, client: C, target: &str) -> Result<()>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+{
+    let channel = context.connection.open(target).await?;
+    let (reader, writer) = tokio::io::split(channel);
+    relay_to(target, client, reader, writer, context.protocol.as_ref()).await;
+    Ok(())
+}
+
+/// -L: connections to bind:port reach host:hostport as seen from the SSH server
+pub async fn local(context: Arc<Context>, spec: Spec) -> Result<()> {
+    let bind = spec.bind.as_deref().unwrap_or(LOOPBACK);
+    let listener = net::bind(bind, spec.port, context.tunnel.socket_mode).await?;
+    let target = spec.target;
+    println!(
+        "🎧 [{}] L {} → {} (via {})",
+        context.tunnel.name,
+        net::listen_address(bind, spec.port),
+        target,
+        context.tunnel.host
+    );
+    loop {
+        let (client, addr) = listener.accept().await?;
+        println!("📞 [{}] {} → {}", context.tunnel.name, addr, target);
+        let context = context.clone();
+        let target = target.clone();
+        tokio::spawn(async move {
+            if let Err(e) = through_ssh(&context, client, &target).await {
+                eprintln!("❌ [{}] {}", context.tunnel.name, e);
+            }
+        });
+    }
+}
+
+/// -R: checks and announces `spec`; the session has the server listen for it each time
+/// it connects
+pub fn remote(context: &Context, spec: &Spec) -> Result<()> {
+    let remote_bind = match spec.bind.as_deref() {
+        Some(bind) if net::unix_path(bind).is_some() => {
+            return Err(anyhow!(
+                "Remote forwards from a socket on the server ({}) are not supported",
+                bind
+            ))
+        }
+        Some(bind) => net::host_port(bind, spec.port),
+        None => spec.port.to_string(),
+    };
+    println!(
+        "🎧 [{}] R {}:{} → {}",
+        context.tunnel.name, context.tunnel.host, remote_bind, spec.target
+    );
+    Ok(())
+}
+
+/// Dials `target` from here for a connection the server accepted on a -R forward
+pub async fn answer(context: Arc<Context>, target: String, channel: DuplexStream) {
+    println!(
+        "📞 [{}] {} → {}",
+        context.tunnel.name, context.tunnel.host, target
+    );
+    match net::connect(&target).await {
+        Ok(upstream) => {
+            let (reader, writer) = tokio::io::split(upstream);
+            relay_to(&target, channel, reader, writer, context.protocol.as_ref()).await;
+        }
+        Err(e) => eprintln!("❌ [{}] {}: {}", context.tunnel.name, target, e),
+    }
+}
+
+/// -D: a SOCKS5 server whose connections are dialed by the SSH server
+pub async fn dynamic(context: Arc<Context>, bind: String, port: u16) -> Result<()> {
+    let listener = net::bind(&bind, port, context.tunnel.socket_mode).await?;
+    println!(
+        "🎧 [{}] D socks5://{} (via {})",
+        context.tunnel.name,
+        net::listen_address(&bind, port),
+        context.tunnel.host
+    );
+    loop {
+        let (mut client, _) = listener.accept().await?;
+        let context = context.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let target = socks::accept(&mut client).await?;
+                println!("📞 [{}] SOCKS → {}", context.tunnel.name, target);
+                socks::reply(&mut client, socks::SUCCEEDED).await?;
+                through_ssh(&context, client, &target.to_string()).await
+            };
+            if let Err(e) = result.await {
+                eprintln!("❌ [{}] {}", context.tunnel.name, e);
+            }
+        });
+    }
+}
@@ -0,0 +1,269 @@
+// SSH tunnels with -L/-R/-D style forwards defined in the config. Each tunnel holds one
+// SSH session, opened with libssh2 (`session`), that logs in with a key file, the SSH
+// agent or the default keys in ~/.ssh and trusts the hosts in ~/.ssh/known_hosts; this
+// plugin keeps the sessions up and relays the forwarded bytes so they can be logged.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::traffic::Protocol;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+mod forward;
+mod session;
+mod socks;
+
+const PLUGIN_NAME: &str = "ssh_tunnel";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TunnelConfig {
+    #[serde(default)]
+    pub tunnel: Vec<Tunnel>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Tunnel {
+    pub name: String,
+    /// SSH server; ~/.ssh/config is not read, so this is a host name or address
+    pub host: String,
+    /// Login name (default: the local user)
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// Key to log in with; the SSH agent and the default keys in ~/.ssh are tried
+    /// otherwise
+    pub identity_file: Option<String>,
+    /// Seconds between SSH keepalives (default 30)
+    pub keepalive_interval: Option<u64>,
+    /// Message decoding for the traffic log: tcp (default), http, postgres
+    pub protocol: Option<String>,
+    /// Print the forwarded traffic (default true)
+    pub log_traffic: Option<bool>,
+    /// Like -L: "[bind_address:]port:host:hostport"; a socket path may replace
+    /// "[bind_address:]port", "host:hostport" or both
+    #[serde(default)]
+    pub local: Vec<String>,
+    /// Like -R: "[bind_address:]port:host:hostport", the port opened on the server
+    #[serde(default)]
+    pub remote: Vec<String>,
+    /// Like -D: "[bind_address:]port", or a socket path, for a SOCKS5 proxy through the
+    /// server
+    #[serde(default)]
+    pub dynamic: Vec<String>,
+    /// Permissions of the Unix sockets listened on here, e.g. 0o660 (default: umask)
+    pub socket_mode: Option<u32>,
+}
+
+pub struct SshTunnelPlugin;
+
+impl SshTunnelPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# SSH Tunnel Configuration
+[[tunnel]]
+name = "prod"
+host = "bastion.example.com"  # must be in ~/.ssh/known_hosts
+user = "me"
+# port = 22
+# identity_file = "~/.ssh/id_ed25519"  # the SSH agent and ~/.ssh/id_* are tried otherwise
+keepalive_interval = 30
+protocol = "postgres"  # Options: tcp, http, postgres
+# log_traffic = false
+
+# Like ssh -L: local port → host:port as seen from the server
+local = ["5432:db.internal:5432"]
+# Either side may be a Unix socket, e.g. the server's Docker socket:
+# local = ["/tmp/prod-docker.sock:/var/run/docker.sock"]
+# socket_mode = 0o600
+
+# Like ssh -R: port on the server → host:port as seen from here
+# remote = ["9000:localhost:3000"]
+
+# Like ssh -D: SOCKS5 proxy through the server
+# dynamic = ["1080"]
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<TunnelConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: TunnelConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", SshTunnelPlugin::sample_config());
+                Ok(TunnelConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(TunnelConfig::default())
+        }
+    }
+}
+
+/// Opens the forwards of a tunnel and keeps its session up
+async fn start_tunnel(tunnel: Tunnel, protocol_override: Option<&str>) -> Result<()> {
+    let local = tunnel
+        .local
+        .iter()
+        .map(|spec| forward::Spec::parse(spec))
+        .collect::<Result<Vec<_>>>()?;
+    let remote = tunnel
+        .remote
+        .iter()
+        .map(|spec| forward::Spec::parse(spec))
+        .collect::<Result<Vec<_>>>()?;
+    let dynamic = tunnel
+        .dynamic
+        .iter()
+        .map(|spec| forward::parse_dynamic(spec))
+        .collect::<Result<Vec<_>>>()?;
+    if local.is_empty() && remote.is_empty() && dynamic.is_empty() {
+        return Err(anyhow!("Tunnel '{}' has no forwards", tunnel.name));
+    }
+
+    let protocol = tunnel.log_traffic.unwrap_or(true).then(|| {
+        Protocol::from(
+            protocol_override
+                .or(tunnel.protocol.as_deref())
+                .unwrap_or("tcp"),
+        )
+    });
+    let context = Arc::new(forward::Context {
+        tunnel,
+        connection: session::Connection::default(),
+        protocol,
+    });
+
+    for spec in &remote {
+        forward::remote(&context, spec)?;
+    }
+    for spec in local {
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = forward::local(context.clone(), spec).await {
+                eprintln!("❌ [{}] Local forward failed: {}", context.tunnel.name, e);
+            }
+        });
+    }
+    for (bind, port) in dynamic {
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = forward::dynamic(context.clone(), bind, port).await {
+                eprintln!("❌ [{}] Dynamic forward failed: {}", context.tunnel.name, e);
+            }
+        });
+    }
+
+    session::supervise(context, remote).await;
+    Ok(())
+}
+
+impl Plugin for SshTunnelPlugin {
+    fn name(&self) -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "SSH local, remote and dynamic forwarding with protocol-aware traffic logging"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("SSH tunnels (-L/-R/-D style) with traffic logging and auto-reconnect")
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .short('n')
+                    .value_name("NAME")
+                    .help("Only start the tunnel with this name"),
+            )
+            .arg(
+                Arg::new("protocol")
+                    .long("protocol")
+                    .value_name("PROTOCOL")
+                    .help("Protocol for message decoding: tcp, http, postgres")
+                    .value_parser(["tcp", "http", "postgres"]),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(SshTunnelPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<TunnelConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        plugin_api::metrics::init(self.name());
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let config = match load_config(self.name()) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("❌ Failed to load config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let tunnels: Vec<Tunnel> = match matches.get_one::<String>("name") {
+                Some(name) => config
+                    .tunnel
+                    .into_iter()
+                    .filter(|tunnel| &tunnel.name == name)
+                    .collect(),
+                None => config.tunnel,
+            };
+            if tunnels.is_empty() {
+                eprintln!("❌ No tunnels to start");
+                eprintln!("💡 Add [[tunnel]] entries to the config file");
+                std::process::exit(1);
+            }
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🚀 Starting {} SSH tunnel(s)", tunnels.len());
+            let protocol = matches.get_one::<String>("protocol").cloned();
+            let handles: Vec<_> = tunnels
+                .into_iter()
+                .map(|tunnel| {
+                    let protocol = protocol.clone();
+                    tokio::spawn(async move {
+                        let name = tunnel.name.clone();
+                        if let Err(e) = start_tunnel(tunnel, protocol.as_deref()).await {
+                            eprintln!("❌ [{}] {}", name, e);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(SshTunnelPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,487 @@
+// The SSH session of a tunnel, opened with libssh2. One connection per tunnel carries the
+// remote forwards and every connection of the local and dynamic ones, and is reopened with
+// a backoff whenever it drops. It logs in with the configured key, or else the identities
+// of the SSH agent and the default keys in ~/.ssh, and only to a server whose host key is
+// in ~/.ssh/known_hosts, as ssh does in batch mode.
+//
+// libssh2 isn't async, and a blocking call would hold up every channel of the session, so
+// once logged in the session runs non-blocking on a thread of its own (`pump`), which moves
+// the bytes of all its channels. The async side sees a channel as one end of an in-memory
+// duplex stream.
+use crate::forward::{self, Context, Spec};
+use crate::Tunnel;
+use anyhow::{anyhow, Context as _, Result};
+use plugin_api::net;
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, Listener, Session};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
+use tokio::sync::{mpsc, oneshot};
+
+const DEFAULT_KEEPALIVE: u64 = 30;
+/// Keepalive intervals the server may leave unacknowledged before the connection is dropped
+#[cfg(target_os = "linux")]
+const KEEPALIVE_COUNT: u32 = 3;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A session that lasted this long starts the backoff over
+const STABLE: Duration = Duration::from_secs(60);
+/// How long connecting, logging in and opening the remote forwards may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long the session's thread waits when no channel had anything to move
+const IDLE: Duration = Duration::from_millis(5);
+/// Chunks queued in each direction of a channel before the side filling them waits
+const QUEUE: usize = 16;
+const CHUNK: usize = 16 * 1024;
+/// libssh2's "would block", for non-blocking calls that have to be made again
+const EAGAIN: i32 = -37;
+/// Tried in this order when neither identity_file nor the agent logs in
+const DEFAULT_KEYS: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Where a channel leads, as seen from the server
+enum Destination {
+    Tcp(String, u16),
+    Socket(String),
+}
+
+/// A forward's request for a channel
+struct Open {
+    destination: Destination,
+    reply: oneshot::Sender<Result<DuplexStream>>,
+}
+
+/// The tunnel's session while it is up, for the forwards to open channels on
+#[derive(Default)]
+pub struct Connection {
+    opens: Mutex<Option<std_mpsc::Sender<Open>>>,
+}
+
+impl Connection {
+    /// A channel to `target`, a `host:port` or a socket path on the server
+    pub async fn open(&self, target: &str) -> Result<DuplexStream> {
+        let destination = match net::unix_path(target) {
+            Some(path) => Destination::Socket(path.to_string()),
+            None => match net::split_host_port(target) {
+                (host, Some(port)) => Destination::Tcp(
+                    host.to_string(),
+                    port.parse()
+                        .map_err(|_| anyhow!("Invalid port in '{}'", target))?,
+                ),
+                (_, None) => return Err(anyhow!("No port in '{}'", target)),
+            },
+        };
+        let (reply, answer) = oneshot::channel();
+        let open = Open { destination, reply };
+        let sent = match self.opens.lock().unwrap().as_ref() {
+            Some(opens) => opens.send(open).is_ok(),
+            None => false,
+        };
+        if !sent {
+            return Err(anyhow!("The SSH session is not connected"));
+        }
+        answer
+            .await
+            .map_err(|_| anyhow!("The SSH session dropped"))?
+    }
+}
+
+fn would_block(e: &ssh2::Error) -> bool {
+    e.code() == ErrorCode::Session(EAGAIN)
+}
+
+fn ssh_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh"))
+}
+
+/// `path` with a leading `~/` replaced by the home directory
+fn expand(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn tcp_connect(host: &str, port: u16) -> Result<TcpStream> {
+    let mut last = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(match last {
+        Some(e) => anyhow!("Could not connect to {}: {}", net::host_port(host, port), e),
+        None => anyhow!("{} has no address", host),
+    })
+}
+
+/// Trusts the server only with the host key ~/.ssh/known_hosts has for it
+fn check_host_key(session: &Session, host: &str, port: u16) -> Result<()> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| anyhow!("The server sent no host key"))?;
+    let mut known_hosts = session.known_hosts()?;
+    if let Some(file) = ssh_dir()
+        .map(|dir| dir.join("known_hosts"))
+        .filter(|file| file.exists())
+    {
+        known_hosts.read_file(&file, KnownHostFileKind::OpenSSH)?;
+    }
+    let server = net::host_port(host, port);
+    match known_hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => Err(anyhow!(
+            "The host key of {} is not in ~/.ssh/known_hosts; connect once with ssh to check \
+             and add it",
+            server
+        )),
+        CheckResult::Mismatch => Err(anyhow!(
+            "The host key of {} does not match the one in ~/.ssh/known_hosts",
+            server
+        )),
+        CheckResult::Failure => Err(anyhow!("Could not check the host key of {}", server)),
+    }
+}
+
+/// Logs in with identity_file, or else each identity of the agent and the default keys
+fn login(session: &Session, tunnel: &Tunnel, user: &str) -> Result<()> {
+    if let Some(identity) = &tunnel.identity_file {
+        let key = expand(identity);
+        return session
+            .userauth_pubkey_file(user, None, &key, None)
+            .with_context(|| format!("Logging in as {} with {} failed", user, key.display()));
+    }
+    if let Ok(mut agent) = session.agent() {
+        if agent.connect().is_ok() && agent.list_identities().is_ok() {
+            for identity in agent.identities().unwrap_or_default() {
+                if agent.userauth(user, &identity).is_ok() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    let keys = ssh_dir()
+        .into_iter()
+        .flat_map(|dir| DEFAULT_KEYS.map(|name| dir.join(name)))
+        .filter(|key| key.exists());
+    for key in keys {
+        if session.userauth_pubkey_file(user, None, &key, None).is_ok() {
+            return Ok(());
+        }
+    }
+    Err(anyhow!(
+        "No key logged in as {}: set identity_file, or add the key to the SSH agent",
+        user
+    ))
+}
+
+/// Connects, checks the host key, logs in and has the server listen for the remote
+/// forwards, returning the session ready for `pump`
+fn connect(tunnel: &Tunnel, remote: &[Spec]) -> Result<(Session, Vec<(Listener, String)>)> {
+    let host = net::unbracket(&tunnel.host);
+    let port = tunnel.port.unwrap_or(22);
+    let user = match tunnel.user.clone() {
+        Some(user) => user,
+        None => std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .map_err(|_| anyhow!("Set user, the local user name is unknown"))?,
+    };
+    let keepalive = tunnel.keepalive_interval.unwrap_or(DEFAULT_KEEPALIVE);
+
+    let tcp = tcp_connect(host, port)?;
+    // A keepalive the server leaves unacknowledged for this long drops the connection;
+    // elsewhere that takes the system's retransmission timeout
+    #[cfg(target_os = "linux")]
+    socket2::SockRef::from(&tcp).set_tcp_user_timeout(Some(Duration::from_secs(
+        keepalive * KEEPALIVE_COUNT as u64,
+    )))?;
+    let mut session = Session::new()?;
+    session.set_timeout(CONNECT_TIMEOUT.as_millis() as u32);
+    session.set_tcp_stream(tcp);
+    session.handshake().context("SSH handshake failed")?;
+    check_host_key(&session, host, port)?;
+    login(&session, tunnel, &user)?;
+    session.set_keepalive(false, keepalive as u32);
+
+    let mut listeners = Vec::new();
+    for spec in remote {
+        // ssh -R listens on the server's loopback unless told otherwise, too
+        let bind = spec.bind.as_deref().unwrap_or("localhost");
+        let (listener, _) = session
+            .channel_forward_listen(spec.port, Some(net::unbracket(bind)), None)
+            .with_context(|| {
+                format!(
+                    "The server did not listen on {}",
+                    net::host_port(bind, spec.port)
+                )
+            })?;
+        listeners.push((listener, spec.target.clone()));
+    }
+    session.set_blocking(false);
+    Ok((session, listeners))
+}
+
+/// Carries the bytes between a channel's queues and the end of the duplex stream the
+/// forward doesn't hold
+async fn bridge(
+    stream: DuplexStream,
+    to_channel: mpsc::Sender<Vec<u8>>,
+    mut from_channel: mpsc::Receiver<Vec<u8>>,
+) {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let up = async move {
+        let mut buf = vec![0; CHUNK];
+        while let Ok(n) = reader.read(&mut buf).await {
+            if n == 0 || to_channel.send(buf[..n].to_vec()).await.is_err() {
+                break;
+            }
+        }
+    };
+    let down = async move {
+        while let Some(chunk) = from_channel.recv().await {
+            if writer.write_all(&chunk).await.is_err() {
+                return;
+            }
+        }
+        let _ = writer.shutdown().await;
+    };
+    tokio::join!(up, down);
+}
+
+/// A channel of the session and the queues to its duplex stream
+struct Pipe {
+    channel: ssh2::Channel,
+    /// What the forward wrote, for the channel
+    from_client: mpsc::Receiver<Vec<u8>>,
+    /// The rest of a chunk the channel didn't take yet
+    unsent: Vec<u8>,
+    /// What the channel read, for the forward; None once either side ended
+    to_client: Option<mpsc::Sender<Vec<u8>>>,
+    /// A chunk the forward's queue had no room for yet
+    unreceived: Option<Vec<u8>>,
+    eof_sent: bool,
+}
+
+impl Pipe {
+    fn new(channel: ssh2::Channel, runtime: &Handle) -> (Pipe, DuplexStream) {
+        let (ours, theirs) = tokio::io::duplex(CHUNK);
+        let (to_channel, from_client) = mpsc::channel(QUEUE);
+        let (to_client, from_channel) = mpsc::channel(QUEUE);
+        runtime.spawn(bridge(ours, to_channel, from_channel));
+        let pipe = Pipe {
+            channel,
+            from_client,
+            unsent: Vec::new(),
+            to_client: Some(to_client),
+            unreceived: None,
+            eof_sent: false,
+        };
+        (pipe, theirs)
+    }
+
+    /// Moves what it can in both directions, returning whether anything moved
+    fn pump(&mut self) -> io::Result<bool> {
+        let mut moved = false;
+        while let Some(to_client) = &self.to_client {
+            let chunk = match self.unreceived.take() {
+                Some(chunk) => chunk,
+                None => {
+                    let mut chunk = vec![0; CHUNK];
+                    match self.channel.read(&mut chunk) {
+                        Ok(0) => {
+                            self.to_client = None;
+                            break;
+                        }
+                        Ok(n) => {
+                            chunk.truncate(n);
+                            chunk
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                        Err(e) => return Err(e),
+                    }
+                }
+            };
+            match to_client.try_send(chunk) {
+                Ok(()) => moved = true,
+                Err(TrySendError::Full(chunk)) => {
+                    self.unreceived = Some(chunk);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => self.to_client = None,
+            }
+        }
+
+        while !self.eof_sent {
+            if self.unsent.is_empty() {
+                match self.from_client.try_recv() {
+                    Ok(chunk) => self.unsent = chunk,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        match self.channel.send_eof() {
+                            Ok(()) => self.eof_sent = true,
+                            Err(e) if would_block(&e) => {}
+                            Err(e) => return Err(e.into()),
+                        }
+                        break;
+                    }
+                }
+            }
+            match self.channel.write(&self.unsent) {
+                Ok(n) => {
+                    self.unsent.drain(..n);
+                    moved = true;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Both sides ended
+    fn done(&self) -> bool {
+        self.eof_sent && self.to_client.is_none()
+    }
+}
+
+/// Opens a channel to `destination`, or reports that it has to be asked again
+fn open_channel(
+    session: &Session,
+    destination: &Destination,
+) -> Result<ssh2::Channel, ssh2::Error> {
+    match destination {
+        Destination::Tcp(host, port) => session.channel_direct_tcpip(host, *port, None),
+        Destination::Socket(path) => session.channel_direct_streamlocal(path, None),
+    }
+}
+
+/// Runs the session until it fails: opens the channels the forwards ask for, accepts the
+/// connections to the remote forwards, moves the bytes of every channel and sends the
+/// keepalives. Returns why it ended.
+fn pump(
+    session: Session,
+    mut listeners: Vec<(Listener, String)>,
+    opens: std_mpsc::Receiver<Open>,
+    context: Arc<Context>,
+    runtime: Handle,
+) -> ssh2::Error {
+    let mut pending: VecDeque<Open> = VecDeque::new();
+    let mut pipes: Vec<Pipe> = Vec::new();
+    let mut closing: Vec<ssh2::Channel> = Vec::new();
+    let mut keepalive_at = Instant::now();
+    loop {
+        let mut moved = false;
+
+        if Instant::now() >= keepalive_at {
+            match session.keepalive_send() {
+                Ok(seconds) => {
+                    keepalive_at = Instant::now() + Duration::from_secs(seconds.max(1) as u64)
+                }
+                Err(e) if would_block(&e) => {}
+                Err(e) => return e,
+            }
+        }
+
+        pending.extend(opens.try_iter());
+        // libssh2 opens one channel at a time; the request first in line is continued
+        // until it is answered
+        while let Some(open) = pending.front() {
+            let result = match open_channel(&session, &open.destination) {
+                Err(e) if would_block(&e) => break,
+                result => result,
+            };
+            let open = pending.pop_front().expect("front request");
+            moved = true;
+            let reply = result.map_err(anyhow::Error::from).map(|channel| {
+                let (pipe, stream) = Pipe::new(channel, &runtime);
+                pipes.push(pipe);
+                stream
+            });
+            let _ = open.reply.send(reply);
+        }
+
+        for (listener, target) in &mut listeners {
+            loop {
+                match listener.accept() {
+                    Ok(channel) => {
+                        moved = true;
+                        let (pipe, stream) = Pipe::new(channel, &runtime);
+                        pipes.push(pipe);
+                        runtime.spawn(forward::answer(context.clone(), target.clone(), stream));
+                    }
+                    Err(e) if would_block(&e) => break,
+                    Err(e) => return e,
+                }
+            }
+        }
+
+        let mut index = 0;
+        while index < pipes.len() {
+            match pipes[index].pump() {
+                Ok(pumped) if !pipes[index].done() => {
+                    moved |= pumped;
+                    index += 1;
+                }
+                result => {
+                    if let Err(e) = result {
+                        eprintln!("❌ [{}] SSH channel failed: {}", context.tunnel.name, e);
+                    }
+                    closing.push(pipes.swap_remove(index).channel);
+                    moved = true;
+                }
+            }
+        }
+        closing.retain_mut(|channel| matches!(channel.close(), Err(e) if would_block(&e)));
+
+        if !moved {
+            std::thread::sleep(IDLE);
+        }
+    }
+}
+
+/// Keeps the session up, reconnecting with a backoff whenever it drops. `remote` are the
+/// -R forwards, which the server listens for again on every connection.
+pub async fn supervise(context: Arc<Context>, remote: Vec<Spec>) {
+    let name = context.tunnel.name.clone();
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        println!("🔐 [{}] Connecting to {}", name, context.tunnel.host);
+        let tunnel = context.tunnel.clone();
+        let specs = remote.clone();
+        let connected = tokio::task::spawn_blocking(move || connect(&tunnel, &specs))
+            .await
+            .unwrap_or_else(|e| Err(anyhow!(e)));
+        match connected {
+            Ok((session, listeners)) => {
+                println!("✅ [{}] Connected to {}", name, context.tunnel.host);
+                let (opens, requests) = std_mpsc::channel();
+                *context.connection.opens.lock().unwrap() = Some(opens);
+                let pumped = context.clone();
+                let runtime = Handle::current();
+                let ended = tokio::task::spawn_blocking(move || {
+                    pump(session, listeners, requests, pumped, runtime)
+                })
+                .await;
+                *context.connection.opens.lock().unwrap() = None;
+                match ended {
+                    Ok(e) => println!("⚠️  [{}] SSH session ended ({})", name, e),
+                    Err(e) => eprintln!("❌ [{}] SSH session failed: {}", name, e),
+                }
+            }
+            Err(e) => eprintln!("❌ [{}] {:#}", name, e),
+        }
+        if started.elapsed() >= STABLE {
+            backoff = MIN_BACKOFF;
+        }
+        println!("🔄 [{}] Reconnecting in {}s", name, backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
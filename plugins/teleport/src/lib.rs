@@ -0,0 +1,478 @@
+// Teleport-gated infrastructure in the same workflow as the other forwards: lists the
+// apps, databases and Kubernetes clusters tsh can reach, and runs `tsh proxy` sessions for
+// the configured ones on fixed local ports. App and database sessions are relayed through
+// this process so their traffic is logged like any other forward. When the Teleport
+// certificates are about to expire, `tsh login` runs again and the sessions restart.
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, Utc};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::net;
+use plugin_api::traffic::{relay_to, Protocol};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+use tokio::sync::Mutex;
+
+mod tsh;
+
+use tsh::Kind;
+
+const DEFAULT_RENEW_BEFORE_MINUTES: i64 = 5;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A session that lasted this long starts the backoff over
+const STABLE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TeleportConfig {
+    /// Teleport proxy address for `tsh login` (default: the current profile's)
+    pub proxy: Option<String>,
+    pub user: Option<String>,
+    /// Auth connector, e.g. "okta" or "github"
+    pub auth: Option<String>,
+    /// Leaf cluster to log in to
+    pub cluster: Option<String>,
+    /// Log in again this long before the certificates expire (default 5)
+    pub renew_before_minutes: Option<i64>,
+    #[serde(default)]
+    pub session: Vec<Session>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Session {
+    pub name: String,
+    /// One of app, db or kube: the Teleport resource to proxy
+    pub app: Option<String>,
+    pub db: Option<String>,
+    pub kube: Option<String>,
+    /// db: database user and name to connect as
+    pub db_user: Option<String>,
+    pub db_name: Option<String>,
+    pub local_port: u16,
+    /// app and db: address to listen on, 127.0.0.1 (default), ::1, or :: for IPv4 and IPv6
+    pub bind: Option<String>,
+    /// Message decoding for the traffic log: tcp, http, postgres, mysql (default: http
+    /// for apps, tcp for databases)
+    pub protocol: Option<String>,
+    /// Print the forwarded traffic (default true)
+    pub log_traffic: Option<bool>,
+}
+
+impl Session {
+    fn target(&self) -> Result<(Kind, &str)> {
+        match (&self.app, &self.db, &self.kube) {
+            (Some(app), None, None) => Ok((Kind::App, app)),
+            (None, Some(db), None) => Ok((Kind::Db, db)),
+            (None, None, Some(kube)) => Ok((Kind::Kube, kube)),
+            _ => Err(anyhow!(
+                "Session '{}' needs exactly one of app, db or kube",
+                self.name
+            )),
+        }
+    }
+}
+
+pub struct TeleportPlugin;
+
+impl TeleportPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Teleport Configuration
+proxy = "teleport.example.com:443"
+# user = "me@example.com"
+# auth = "okta"
+renew_before_minutes = 5
+
+[[session]]
+name = "grafana"
+app = "grafana"
+local_port = 3000
+
+[[session]]
+name = "orders-db"
+db = "orders-prod"
+db_user = "reader"
+db_name = "orders"
+local_port = 15432
+# bind = "::1"  # default 127.0.0.1; "::" listens on every interface, IPv4 and IPv6
+protocol = "postgres"  # Options: tcp, http, postgres, mysql
+
+[[session]]
+name = "prod-kube"
+kube = "prod"
+local_port = 18443  # tsh prints the KUBECONFIG to use
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<TeleportConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: TeleportConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Listing and logging in work without one
+                Ok(TeleportConfig::default())
+            }
+        }
+        None => Ok(TeleportConfig::default()),
+    }
+}
+
+fn local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%H:%M").to_string()
+}
+
+/// Makes sure the certificates are valid for longer than the renewal margin, logging in
+/// when they aren't. Returns when they expire. `lock` keeps sessions that notice at the
+/// same time from prompting for several logins.
+async fn ensure_login(config: &TeleportConfig, lock: &Mutex<()>) -> Result<DateTime<Utc>> {
+    let _guard = lock.lock().await;
+    let margin = chrono::Duration::minutes(
+        config
+            .renew_before_minutes
+            .unwrap_or(DEFAULT_RENEW_BEFORE_MINUTES),
+    );
+    match tsh::status().await? {
+        Some((_, until)) if until - margin > Utc::now() => return Ok(until),
+        Some((user, until)) => println!(
+            "🔑 Teleport certificates of {} expire at {}, logging in again",
+            user,
+            local_time(until)
+        ),
+        None => println!("🔑 Not logged in to Teleport"),
+    }
+    tsh::login(config).await?;
+    match tsh::status().await? {
+        Some((user, until)) => {
+            println!("✅ Logged in as {} until {}", user, local_time(until));
+            Ok(until)
+        }
+        None => Err(anyhow!("tsh login finished but there is no active profile")),
+    }
+}
+
+/// How long a session may run on certificates valid until `until`
+fn renew_in(config: &TeleportConfig, until: DateTime<Utc>) -> Duration {
+    let margin = chrono::Duration::minutes(
+        config
+            .renew_before_minutes
+            .unwrap_or(DEFAULT_RENEW_BEFORE_MINUTES),
+    );
+    let now = Utc::now();
+    // Certificates shorter-lived than the margin are used until they expire
+    let renew_at = if until - margin > now {
+        until - margin
+    } else {
+        until
+    };
+    (renew_at - now).to_std().unwrap_or_default()
+}
+
+/// Keeps `tsh proxy` running on `port`, renewing the login before it expires
+async fn supervise(session: Session, port: u16, config: Arc<TeleportConfig>, lock: Arc<Mutex<()>>) {
+    let Ok((kind, target)) = session.target() else {
+        return;
+    };
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let until = match ensure_login(&config, &lock).await {
+            Ok(until) => until,
+            Err(e) => {
+                eprintln!("❌ [{}] {}", session.name, e);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+
+        let mut command = tsh::proxy(&session, kind, target, port);
+        if kind != Kind::Kube {
+            // Its instructions name the private port; the local port is ours
+            command.stdout(Stdio::null());
+        }
+        let started = Instant::now();
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("❌ [{}] Failed to start tsh: {}", session.name, e);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+        let expiring = tokio::select! {
+            status = child.wait() => {
+                match status {
+                    Ok(status) => println!("⚠️  [{}] tsh proxy ended ({})", session.name, status),
+                    Err(e) => eprintln!("❌ [{}] tsh proxy failed: {}", session.name, e),
+                }
+                false
+            }
+            _ = tokio::time::sleep(renew_in(&config, until)) => true,
+        };
+        if expiring {
+            println!(
+                "🔑 [{}] Certificates expire soon, restarting after login",
+                session.name
+            );
+            let _ = child.kill().await;
+            backoff = MIN_BACKOFF;
+            continue;
+        }
+
+        if started.elapsed() >= STABLE {
+            backoff = MIN_BACKOFF;
+        }
+        println!("🔄 [{}] Restarting in {}s", session.name, backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn start_session(
+    session: Session,
+    config: Arc<TeleportConfig>,
+    lock: Arc<Mutex<()>>,
+) -> Result<()> {
+    let (kind, target) = session.target()?;
+    // tsh listens for kube sessions itself, on localhost
+    let bind = match kind {
+        Kind::Kube => "localhost",
+        _ => session.bind.as_deref().unwrap_or(net::LOOPBACK),
+    };
+    println!(
+        "🎧 [{}] {} → {} {}",
+        session.name,
+        net::host_port(bind, session.local_port),
+        kind.label(),
+        target
+    );
+    if kind == Kind::Kube {
+        // tsh serves kubectl itself, with a kubeconfig it writes
+        supervise(session.clone(), session.local_port, config, lock).await;
+        return Ok(());
+    }
+
+    let listener = net::listen(bind, session.local_port).await?;
+    let proxy_port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    tokio::spawn(supervise(session.clone(), proxy_port, config, lock));
+
+    let default_protocol = if kind == Kind::App { "http" } else { "tcp" };
+    let protocol = session
+        .log_traffic
+        .unwrap_or(true)
+        .then(|| Protocol::from(session.protocol.as_deref().unwrap_or(default_protocol)));
+    let protocol = Arc::new(protocol);
+    loop {
+        let (client, addr) = listener.accept().await?;
+        println!("📞 [{}] New connection from {}", session.name, addr);
+        let name = session.name.clone();
+        let protocol = protocol.clone();
+        tokio::spawn(async move {
+            match TcpStream::connect(("127.0.0.1", proxy_port)).await {
+                Ok(upstream) => {
+                    let (reader, writer) = upstream.into_split();
+                    relay_to(&name, client, reader, writer, protocol.as_ref().as_ref()).await;
+                    println!("🔌 [{}] Connection from {} closed", name, addr);
+                }
+                Err(e) => eprintln!("❌ [{}] tsh proxy is not listening yet: {}", name, e),
+            }
+        });
+    }
+}
+
+async fn list(what: &str) -> Result<()> {
+    match tsh::status().await? {
+        Some((user, until)) => println!(
+            "👤 {} (certificates valid until {})",
+            user,
+            local_time(until)
+        ),
+        None => return Err(anyhow!("Not logged in; run `proxy teleport login`")),
+    }
+    let kinds: &[Kind] = match what {
+        "apps" => &[Kind::App],
+        "dbs" => &[Kind::Db],
+        "kubes" => &[Kind::Kube],
+        _ => &[Kind::App, Kind::Db, Kind::Kube],
+    };
+    for &kind in kinds {
+        let resources = match tsh::list(kind).await {
+            Ok(resources) => resources,
+            Err(e) => {
+                eprintln!("⚠️  {}", e);
+                continue;
+            }
+        };
+        println!(
+            "\n{} {}s ({}):",
+            match kind {
+                Kind::App => "🌐",
+                Kind::Db => "🗄️ ",
+                Kind::Kube => "☸️ ",
+            },
+            kind.label(),
+            resources.len()
+        );
+        let width = resources.iter().map(|r| r.name.len()).max().unwrap_or(0);
+        let detail_width = resources
+            .iter()
+            .map(|r| r.detail.chars().count())
+            .max()
+            .unwrap_or(0);
+        for resource in &resources {
+            println!(
+                "  {:<width$}  {:<detail_width$}  {}",
+                resource.name,
+                resource.detail,
+                resource.labels.join(",")
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn start(mut config: TeleportConfig, name: Option<&String>) -> Result<()> {
+    let sessions: Vec<Session> = std::mem::take(&mut config.session)
+        .into_iter()
+        .filter(|session| name.is_none_or(|name| &session.name == name))
+        .collect();
+    if sessions.is_empty() {
+        println!("📝 Sample config:\n{}", TeleportPlugin::sample_config());
+        if let Some(path) = plugin_api::plugin_config_path("teleport") {
+            println!("💡 Add [[session]] entries at: {}", path.display());
+        }
+        return Err(anyhow!("No sessions to start"));
+    }
+    for session in &sessions {
+        session.target()?;
+    }
+
+    ctrlc::set_handler(move || {
+        println!("\n👋 Shutting down...");
+        std::process::exit(0);
+    })?;
+
+    // Log in up front, so the prompt isn't interleaved with session output
+    let config = Arc::new(config);
+    let lock = Arc::new(Mutex::new(()));
+    ensure_login(&config, &lock).await?;
+
+    println!("🚀 Starting {} Teleport session(s)", sessions.len());
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|session| {
+            let config = config.clone();
+            let lock = lock.clone();
+            tokio::spawn(async move {
+                let name = session.name.clone();
+                if let Err(e) = start_session(session, config, lock).await {
+                    eprintln!("❌ [{}] {}", name, e);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.await;
+    }
+    Ok(())
+}
+
+impl Plugin for TeleportPlugin {
+    fn name(&self) -> &'static str {
+        "teleport"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Teleport apps, databases and clusters through tsh, with automatic re-login"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("List and proxy Teleport apps, databases and Kubernetes clusters")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("ls").about("List what tsh can reach").arg(
+                    Arg::new("kind")
+                        .value_name("KIND")
+                        .value_parser(["apps", "dbs", "kubes"])
+                        .help("Only apps, dbs or kubes"),
+                ),
+            )
+            .subcommand(Command::new("login").about("Log in with tsh using the configured proxy"))
+            .subcommand(
+                Command::new("start")
+                    .about("Start the sessions in the config file")
+                    .arg(
+                        Arg::new("name")
+                            .long("name")
+                            .short('n')
+                            .value_name("NAME")
+                            .help("Only start the session with this name"),
+                    ),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(TeleportPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<TeleportConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        plugin_api::metrics::init(self.name());
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        let result = rt.block_on(async {
+            match matches.subcommand() {
+                Some(("ls", sub)) => {
+                    let what = sub.get_one::<String>("kind").map(String::as_str);
+                    list(what.unwrap_or("all")).await
+                }
+                Some(("login", _)) => {
+                    tsh::login(&config).await?;
+                    if let Some((user, until)) = tsh::status().await? {
+                        println!("✅ Logged in as {} until {}", user, local_time(until));
+                    }
+                    Ok(())
+                }
+                Some(("start", sub)) => start(config, sub.get_one::<String>("name")).await,
+                _ => Ok(()),
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(TeleportPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,182 @@
+// The tsh CLI, which holds the Teleport login: its certificates live in ~/.tsh, so
+// everything here works with whatever `tsh login` set up, SSO included. Listings use the
+// JSON output of `tsh apps ls`, `tsh db ls` and `tsh kube ls`; the fields read are the
+// ones shared by Teleport 12 and later.
+use crate::{Session, TeleportConfig};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::process::Stdio;
+use tokio::process::Command;
+
+pub const BINARY: &str = "tsh";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    App,
+    Db,
+    Kube,
+}
+
+impl Kind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::App => "app",
+            Kind::Db => "db",
+            Kind::Kube => "kube",
+        }
+    }
+}
+
+/// One entry of a listing
+pub struct Resource {
+    pub name: String,
+    /// Where it leads: the app URI, the database protocol and URI
+    pub detail: String,
+    pub labels: Vec<String>,
+}
+
+/// The active profile's user and certificate expiry; None when logged out
+pub async fn status() -> Result<Option<(String, DateTime<Utc>)>> {
+    let output = Command::new(BINARY)
+        .args(["status", "--format=json"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .context("Failed to run tsh; is it installed?")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let status: Value = serde_json::from_slice(&output.stdout)?;
+    let active = &status["active"];
+    let valid_until = active["valid_until"]
+        .as_str()
+        .and_then(|text| DateTime::parse_from_rfc3339(text).ok())
+        .map(|time| time.with_timezone(&Utc));
+    Ok(valid_until.map(|until| {
+        let user = active["username"].as_str().unwrap_or("?").to_string();
+        (user, until)
+    }))
+}
+
+/// `tsh login` on the terminal, so SSO can open the browser and prompts can be answered
+pub async fn login(config: &TeleportConfig) -> Result<()> {
+    let mut command = Command::new(BINARY);
+    command.arg("login");
+    if let Some(proxy) = &config.proxy {
+        command.arg(format!("--proxy={}", proxy));
+    }
+    if let Some(user) = &config.user {
+        command.arg(format!("--user={}", user));
+    }
+    if let Some(auth) = &config.auth {
+        command.arg(format!("--auth={}", auth));
+    }
+    if let Some(cluster) = &config.cluster {
+        command.arg(cluster);
+    }
+    let status = command
+        .status()
+        .await
+        .context("Failed to run tsh; is it installed?")?;
+    if !status.success() {
+        return Err(anyhow!("tsh login failed ({})", status));
+    }
+    Ok(())
+}
+
+fn labels(value: &Value) -> Vec<String> {
+    value
+        .as_object()
+        .map(|labels| {
+            labels
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value.as_str().unwrap_or_default()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn resource(kind: Kind, item: &Value) -> Resource {
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    match kind {
+        Kind::App => Resource {
+            name: text(&item["metadata"]["name"]),
+            detail: text(&item["spec"]["public_addr"]),
+            labels: labels(&item["metadata"]["labels"]),
+        },
+        Kind::Db => Resource {
+            name: text(&item["metadata"]["name"]),
+            detail: format!(
+                "{} {}",
+                text(&item["spec"]["protocol"]),
+                text(&item["spec"]["uri"])
+            ),
+            labels: labels(&item["metadata"]["labels"]),
+        },
+        Kind::Kube => Resource {
+            name: text(&item["kube_cluster_name"]),
+            detail: if item["selected"].as_bool().unwrap_or(false) {
+                "selected".to_string()
+            } else {
+                String::new()
+            },
+            labels: labels(&item["labels"]),
+        },
+    }
+}
+
+/// What the logged-in user can reach of one kind
+pub async fn list(kind: Kind) -> Result<Vec<Resource>> {
+    let args: &[&str] = match kind {
+        Kind::App => &["apps", "ls", "--format=json"],
+        Kind::Db => &["db", "ls", "--format=json"],
+        Kind::Kube => &["kube", "ls", "--format=json"],
+    };
+    let output = Command::new(BINARY)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Failed to run tsh; is it installed?")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "tsh {} failed: {}",
+            args[..2].join(" "),
+            stderr.trim()
+        ));
+    }
+    let items: Vec<Value> = serde_json::from_slice(&output.stdout)?;
+    Ok(items.iter().map(|item| resource(kind, item)).collect())
+}
+
+/// `tsh proxy` for a session, listening on `port`
+pub fn proxy(session: &Session, kind: Kind, target: &str, port: u16) -> Command {
+    let mut command = Command::new(BINARY);
+    command.arg("proxy");
+    match kind {
+        Kind::App => {
+            command.arg("app").arg(target);
+        }
+        Kind::Db => {
+            command.arg("db").arg("--tunnel");
+            if let Some(user) = &session.db_user {
+                command.arg(format!("--db-user={}", user));
+            }
+            if let Some(name) = &session.db_name {
+                command.arg(format!("--db-name={}", name));
+            }
+            command.arg(target);
+        }
+        Kind::Kube => {
+            command.arg("kube").arg(target);
+        }
+    }
+    command
+        .arg(format!("--port={}", port))
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    command
+}
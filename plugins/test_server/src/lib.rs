@@ -0,0 +1,198 @@
+// Small local test servers for exercising the proxy and forward plugins end to end without
+// any external dependency: TCP echo, a fixed-response HTTP server, and a byte sink and
+// source for throughput measurements. Point a forward or tunnel at them to check it.
+use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use tokio::runtime::Runtime;
+
+mod servers;
+
+use servers::{Mode, ServerConfig};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TestServerConfig {
+    /// Servers started without --mode; one of each mode when empty
+    #[serde(default)]
+    pub server: Vec<ServerConfig>,
+}
+
+pub struct TestServerPlugin;
+
+impl TestServerPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Test Server Configuration
+[[server]]
+mode = "echo"
+listen = "127.0.0.1:9001"
+
+[[server]]
+mode = "http"
+listen = "127.0.0.1:9002"
+status = 200
+body = '{"status": "ok"}'
+headers = { "content-type" = "application/json" }
+# delay_ms = 50
+
+[[server]]
+mode = "sink"
+listen = "127.0.0.1:9003"
+
+[[server]]
+mode = "source"
+listen = "127.0.0.1:9004"
+# bytes = 104857600  # close after 100 MiB
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<TestServerConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: TestServerConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", TestServerPlugin::sample_config());
+                Ok(TestServerConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(TestServerConfig::default())
+        }
+    }
+}
+
+impl Plugin for TestServerPlugin {
+    fn name(&self) -> &'static str {
+        "test_server"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Echo, HTTP, sink and source servers for testing forwards"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Run local echo, HTTP, sink or source servers to test tunnels")
+            .arg(
+                Arg::new("mode")
+                    .long("mode")
+                    .short('m')
+                    .value_name("MODE")
+                    .value_parser(["echo", "http", "sink", "source"])
+                    .help("Run one server in this mode instead of the configured ones"),
+            )
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .short('l')
+                    .value_name("ADDR")
+                    .requires("mode")
+                    .help("Address for --mode (default 127.0.0.1:9001 to 9004, by mode)"),
+            )
+            .arg(
+                Arg::new("quiet")
+                    .long("quiet")
+                    .short('q')
+                    .action(ArgAction::SetTrue)
+                    .help("Don't print every HTTP request"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(TestServerPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<TestServerConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+
+        rt.block_on(async {
+            let configs = match matches
+                .get_one::<String>("mode")
+                .and_then(|mode| Mode::parse(mode))
+            {
+                Some(mode) => {
+                    let mut config = ServerConfig::new(mode);
+                    config.listen = matches.get_one::<String>("listen").cloned();
+                    vec![config]
+                }
+                None => {
+                    let config = match load_config(self.name()) {
+                        Ok(config) => config,
+                        Err(e) => {
+                            eprintln!("❌ Failed to load config: {}", e);
+                            std::process::exit(1);
+                        }
+                    };
+                    if config.server.is_empty() {
+                        Mode::ALL.into_iter().map(ServerConfig::new).collect()
+                    } else {
+                        config.server
+                    }
+                }
+            };
+
+            let mut listeners = Vec::new();
+            for config in configs {
+                match servers::bind(&config).await {
+                    Ok(listener) => listeners.push((config, listener)),
+                    Err(e) => {
+                        eprintln!(
+                            "❌ Failed to listen on {} ({}): {}",
+                            config.listen(),
+                            config.mode,
+                            e
+                        );
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🚀 Test servers:");
+            for (config, _) in &listeners {
+                println!("  {:<6} on {}", config.mode, config.listen());
+            }
+            let log_requests = !matches.get_flag("quiet");
+            let handles: Vec<_> = listeners
+                .into_iter()
+                .map(|(config, listener)| {
+                    tokio::spawn(servers::serve(config, listener, log_requests))
+                })
+                .collect();
+            for handle in handles {
+                let _ = handle.await;
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(TestServerPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
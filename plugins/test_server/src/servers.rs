@@ -0,0 +1,263 @@
+// The test servers. TCP modes report bytes and throughput per connection when it closes;
+// the HTTP mode answers every request with the configured response (keep-alive, so it can
+// take a load test).
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Sends back everything it receives
+    Echo,
+    /// Answers every HTTP request with a fixed response
+    Http,
+    /// Reads and discards everything
+    Sink,
+    /// Writes data until the client closes or `bytes` have been sent
+    Source,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 4] = [Mode::Echo, Mode::Http, Mode::Sink, Mode::Source];
+
+    pub fn parse(name: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|mode| mode.to_string() == name)
+    }
+
+    pub fn default_listen(self) -> &'static str {
+        match self {
+            Mode::Echo => "127.0.0.1:9001",
+            Mode::Http => "127.0.0.1:9002",
+            Mode::Sink => "127.0.0.1:9003",
+            Mode::Source => "127.0.0.1:9004",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Mode::Echo => "echo",
+            Mode::Http => "http",
+            Mode::Sink => "sink",
+            Mode::Source => "source",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerConfig {
+    pub mode: Mode,
+    /// Address to listen on (default 127.0.0.1:9001 to 9004, by mode)
+    pub listen: Option<String>,
+    /// http: response status (default 200)
+    pub status: Option<u16>,
+    /// http: response body (default "ok\n")
+    pub body: Option<String>,
+    /// http: response headers
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// http: delay before answering
+    pub delay_ms: Option<u64>,
+    /// source: bytes to send before closing (default: until the client closes)
+    pub bytes: Option<u64>,
+}
+
+impl ServerConfig {
+    pub fn new(mode: Mode) -> Self {
+        ServerConfig {
+            mode,
+            listen: None,
+            status: None,
+            body: None,
+            headers: BTreeMap::new(),
+            delay_ms: None,
+            bytes: None,
+        }
+    }
+
+    pub fn listen(&self) -> &str {
+        self.listen
+            .as_deref()
+            .unwrap_or_else(|| self.mode.default_listen())
+    }
+}
+
+fn megabytes(bytes: u64) -> f64 {
+    bytes as f64 / 1_000_000.0
+}
+
+fn report(mode: Mode, peer: SocketAddr, direction: &str, bytes: u64, started: Instant) {
+    let secs = started.elapsed().as_secs_f64();
+    let rate = if secs > 0.0 {
+        megabytes(bytes) / secs
+    } else {
+        0.0
+    };
+    println!(
+        "📊 [{}] {} closed: {:.1} MB {} in {:.2}s ({:.1} MB/s)",
+        mode,
+        peer,
+        megabytes(bytes),
+        direction,
+        secs,
+        rate
+    );
+}
+
+async fn echo(mut socket: TcpStream) -> Result<u64> {
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let mut total = 0u64;
+    loop {
+        let n = socket.read(&mut buffer).await?;
+        if n == 0 {
+            return Ok(total);
+        }
+        socket.write_all(&buffer[..n]).await?;
+        total += n as u64;
+    }
+}
+
+async fn sink(mut socket: TcpStream) -> Result<u64> {
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let mut total = 0u64;
+    loop {
+        let n = socket.read(&mut buffer).await?;
+        if n == 0 {
+            return Ok(total);
+        }
+        total += n as u64;
+    }
+}
+
+async fn source(mut socket: TcpStream, limit: Option<u64>) -> Result<u64> {
+    // Printable data, so it's readable when a client shows it
+    let buffer: Vec<u8> = (0..BUFFER_SIZE).map(|i| b'a' + (i % 26) as u8).collect();
+    let mut total = 0u64;
+    loop {
+        let n = match limit {
+            Some(limit) if total >= limit => break,
+            Some(limit) => (limit - total).min(BUFFER_SIZE as u64) as usize,
+            None => BUFFER_SIZE,
+        };
+        if socket.write_all(&buffer[..n]).await.is_err() {
+            // The client closing is how an unlimited source ends
+            return Ok(total);
+        }
+        total += n as u64;
+    }
+    socket.shutdown().await?;
+    Ok(total)
+}
+
+async fn serve_tcp(config: ServerConfig, listener: TcpListener) {
+    loop {
+        let (socket, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("❌ [{}] Accept failed: {}", config.mode, e);
+                continue;
+            }
+        };
+        println!("📞 [{}] Connection from {}", config.mode, peer);
+        let mode = config.mode;
+        let limit = config.bytes;
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let (result, direction) = match mode {
+                Mode::Echo => (echo(socket).await, "echoed"),
+                Mode::Sink => (sink(socket).await, "received"),
+                _ => (source(socket, limit).await, "sent"),
+            };
+            match result {
+                Ok(bytes) => report(mode, peer, direction, bytes, started),
+                Err(e) => eprintln!("❌ [{}] {}: {}", mode, peer, e),
+            }
+        });
+    }
+}
+
+async fn serve_http(config: ServerConfig, listener: TcpListener, log_requests: bool) {
+    let config = Arc::new(config);
+    loop {
+        let (socket, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("❌ [http] Accept failed: {}", e);
+                continue;
+            }
+        };
+        let config = config.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request: hyper::Request<hyper::body::Incoming>| {
+                let config = config.clone();
+                async move {
+                    let method = request.method().clone();
+                    let uri = request.uri().clone();
+                    // Read the whole request, as a real server would
+                    let _ = request.into_body().collect().await;
+                    if let Some(delay) = config.delay_ms {
+                        tokio::time::sleep(Duration::from_millis(delay)).await;
+                    }
+                    let body = config.body.clone().unwrap_or_else(|| "ok\n".to_string());
+                    let mut response = Response::new(Full::new(Bytes::from(body)));
+                    *response.status_mut() = StatusCode::from_u16(config.status.unwrap_or(200))
+                        .unwrap_or(StatusCode::OK);
+                    for (name, value) in &config.headers {
+                        if let (Ok(name), Ok(value)) = (
+                            hyper::header::HeaderName::from_bytes(name.as_bytes()),
+                            hyper::header::HeaderValue::from_str(value),
+                        ) {
+                            response.headers_mut().insert(name, value);
+                        }
+                    }
+                    if log_requests {
+                        println!(
+                            "🌐 [http] {} {} {} → {}",
+                            peer,
+                            method,
+                            uri,
+                            response.status().as_u16()
+                        );
+                    }
+                    Ok::<_, Infallible>(response)
+                }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(socket), service)
+                .await
+            {
+                eprintln!("❌ [http] {}: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Binds the server's address, so errors show up before anything runs
+pub async fn bind(config: &ServerConfig) -> Result<TcpListener> {
+    Ok(TcpListener::bind(config.listen()).await?)
+}
+
+/// `log_requests` turns off the per-request lines of the HTTP mode, e.g. under load
+pub async fn serve(config: ServerConfig, listener: TcpListener, log_requests: bool) {
+    match config.mode {
+        Mode::Http => serve_http(config, listener, log_requests).await,
+        _ => serve_tcp(config, listener).await,
+    }
+}
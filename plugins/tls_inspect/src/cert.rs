@@ -0,0 +1,223 @@
+// The parts of an X.509 certificate worth looking at when a TLS endpoint misbehaves:
+// names, validity, key and signature algorithms, SANs and whether it is a CA.
+use crate::der::{self, Reader};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::net::IpAddr;
+
+pub struct Certificate {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    /// e.g. "RSA 2048" or "EC P-256"
+    pub key: String,
+    pub signature: String,
+    pub sans: Vec<String>,
+    pub is_ca: bool,
+}
+
+impl Certificate {
+    pub fn days_left(&self) -> i64 {
+        (self.not_after - Utc::now()).num_days()
+    }
+
+    /// The subject's common name, or the whole subject without one
+    pub fn short_name(&self) -> &str {
+        self.subject
+            .split(", ")
+            .find_map(|part| part.strip_prefix("CN="))
+            .unwrap_or(&self.subject)
+    }
+}
+
+fn oid_name(oid: &str) -> Option<&'static str> {
+    Some(match oid {
+        "2.5.4.3" => "CN",
+        "2.5.4.5" => "serialNumber",
+        "2.5.4.6" => "C",
+        "2.5.4.7" => "L",
+        "2.5.4.8" => "ST",
+        "2.5.4.10" => "O",
+        "2.5.4.11" => "OU",
+        "1.2.840.113549.1.9.1" => "emailAddress",
+        "1.2.840.113549.1.1.1" => "RSA",
+        "1.2.840.113549.1.1.5" => "SHA1-RSA",
+        "1.2.840.113549.1.1.10" => "RSA-PSS",
+        "1.2.840.113549.1.1.11" => "SHA256-RSA",
+        "1.2.840.113549.1.1.12" => "SHA384-RSA",
+        "1.2.840.113549.1.1.13" => "SHA512-RSA",
+        "1.2.840.10045.4.3.2" => "ECDSA-SHA256",
+        "1.2.840.10045.4.3.3" => "ECDSA-SHA384",
+        "1.2.840.10045.4.3.4" => "ECDSA-SHA512",
+        "1.2.840.10045.3.1.7" => "P-256",
+        "1.3.132.0.34" => "P-384",
+        "1.3.132.0.35" => "P-521",
+        "1.3.101.112" => "Ed25519",
+        "1.3.101.113" => "Ed448",
+        _ => return None,
+    })
+}
+
+fn named(oid: &str) -> String {
+    oid_name(oid)
+        .map(str::to_string)
+        .unwrap_or_else(|| oid.to_string())
+}
+
+/// A distinguished name as "CN=..., O=...", in the order the certificate has it
+fn name(value: &[u8]) -> Result<String> {
+    let mut parts = Vec::new();
+    let mut sets = Reader::new(value);
+    while !sets.is_empty() {
+        let mut attributes = Reader::new(sets.expect(der::SET)?);
+        while !attributes.is_empty() {
+            let mut attribute = Reader::new(attributes.expect(der::SEQUENCE)?);
+            let oid = der::oid(attribute.expect(der::OID)?);
+            let (_, text) = attribute.next()?;
+            parts.push(format!("{}={}", named(&oid), String::from_utf8_lossy(text)));
+        }
+    }
+    Ok(parts.join(", "))
+}
+
+fn time(tag: u8, value: &[u8]) -> Result<DateTime<Utc>> {
+    let text = std::str::from_utf8(value)?;
+    let full = match tag {
+        // Two-digit years: 50-99 are 19xx, the rest 20xx
+        der::UTC_TIME => {
+            let year: u32 = text.get(..2).unwrap_or_default().parse()?;
+            format!("{}{}", if year >= 50 { "19" } else { "20" }, text)
+        }
+        der::GENERALIZED_TIME => text.to_string(),
+        _ => return Err(anyhow!("Unexpected time tag {:#x}", tag)),
+    };
+    let parsed = NaiveDateTime::parse_from_str(&full, "%Y%m%d%H%M%SZ")
+        .map_err(|_| anyhow!("Invalid certificate time {}", text))?;
+    Ok(parsed.and_utc())
+}
+
+/// An integer's bytes without the sign padding
+fn unsigned(value: &[u8]) -> &[u8] {
+    match value {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        _ => value,
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn public_key(value: &[u8]) -> Result<String> {
+    let mut info = Reader::new(value);
+    let mut algorithm = Reader::new(info.expect(der::SEQUENCE)?);
+    let oid = der::oid(algorithm.expect(der::OID)?);
+    let key = info.expect(der::BIT_STRING)?;
+    Ok(match oid.as_str() {
+        "1.2.840.113549.1.1.1" => {
+            // Past the unused-bits byte: SEQUENCE { modulus, exponent }
+            let mut rsa = Reader::new(key.get(1..).unwrap_or_default());
+            let mut numbers = Reader::new(rsa.expect(der::SEQUENCE)?);
+            let modulus = unsigned(numbers.expect(der::INTEGER)?);
+            let bits = modulus.len() * 8
+                - modulus
+                    .first()
+                    .map(|byte| byte.leading_zeros() as usize)
+                    .unwrap_or(0);
+            format!("RSA {}", bits)
+        }
+        "1.2.840.10045.2.1" => {
+            let curve = algorithm
+                .optional(der::OID)?
+                .map(|curve| named(&der::oid(curve)))
+                .unwrap_or_else(|| "?".to_string());
+            format!("EC {}", curve)
+        }
+        _ => named(&oid),
+    })
+}
+
+fn general_names(value: &[u8]) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut reader = Reader::new(value);
+    while !reader.is_empty() {
+        let (tag, name) = reader.next()?;
+        names.push(match tag {
+            0x81 => format!("email:{}", String::from_utf8_lossy(name)),
+            0x82 => String::from_utf8_lossy(name).to_string(),
+            0x86 => format!("URI:{}", String::from_utf8_lossy(name)),
+            0x87 => match name.len() {
+                4 => IpAddr::from(<[u8; 4]>::try_from(name)?).to_string(),
+                16 => IpAddr::from(<[u8; 16]>::try_from(name)?).to_string(),
+                _ => format!("IP:{}", hex(name)),
+            },
+            _ => continue,
+        });
+    }
+    Ok(names)
+}
+
+pub fn parse(der: &[u8]) -> Result<Certificate> {
+    let mut outer = Reader::new(der);
+    let mut certificate = Reader::new(outer.expect(der::SEQUENCE)?);
+    let mut tbs = Reader::new(certificate.expect(der::SEQUENCE)?);
+    let mut signature = Reader::new(certificate.expect(der::SEQUENCE)?);
+    let signature = named(&der::oid(signature.expect(der::OID)?));
+
+    // Version, when not v1
+    tbs.optional(0xa0)?;
+    let serial = hex(unsigned(tbs.expect(der::INTEGER)?));
+    tbs.expect(der::SEQUENCE)?;
+    let issuer = name(tbs.expect(der::SEQUENCE)?)?;
+    let mut validity = Reader::new(tbs.expect(der::SEQUENCE)?);
+    let (tag, value) = validity.next()?;
+    let not_before = time(tag, value)?;
+    let (tag, value) = validity.next()?;
+    let not_after = time(tag, value)?;
+    let subject = name(tbs.expect(der::SEQUENCE)?)?;
+    let key = public_key(tbs.expect(der::SEQUENCE)?)?;
+    // Issuer and subject unique IDs
+    tbs.optional(0x81)?;
+    tbs.optional(0x82)?;
+
+    let mut sans = Vec::new();
+    let mut is_ca = false;
+    if let Some(extensions) = tbs.optional(0xa3)? {
+        let mut list = Reader::new(extensions);
+        let mut list = Reader::new(list.expect(der::SEQUENCE)?);
+        while !list.is_empty() {
+            let mut extension = Reader::new(list.expect(der::SEQUENCE)?);
+            let oid = der::oid(extension.expect(der::OID)?);
+            extension.optional(der::BOOLEAN)?;
+            let mut value = Reader::new(extension.expect(der::OCTET_STRING)?);
+            match oid.as_str() {
+                "2.5.29.17" => sans = general_names(value.expect(der::SEQUENCE)?)?,
+                "2.5.29.19" => {
+                    let mut constraints = Reader::new(value.expect(der::SEQUENCE)?);
+                    is_ca = constraints
+                        .optional(der::BOOLEAN)?
+                        .is_some_and(|ca| ca.first().is_some_and(|&byte| byte != 0));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(Certificate {
+        subject,
+        issuer,
+        serial,
+        not_before,
+        not_after,
+        key,
+        signature,
+        sans,
+        is_ca,
+    })
+}
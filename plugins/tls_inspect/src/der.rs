@@ -0,0 +1,106 @@
+// Just enough DER to walk an X.509 certificate: tag-length-value elements, read in order
+// from a slice. Only the single-byte tags certificates use are supported.
+use anyhow::{anyhow, Result};
+
+pub const BOOLEAN: u8 = 0x01;
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const OID: u8 = 0x06;
+pub const UTC_TIME: u8 = 0x17;
+pub const GENERALIZED_TIME: u8 = 0x18;
+pub const SEQUENCE: u8 = 0x30;
+pub const SET: u8 = 0x31;
+
+/// Reads the elements of a slice one after the other
+pub struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Reader { input }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    /// The tag of the next element, without reading it
+    pub fn peek(&self) -> Option<u8> {
+        self.input.first().copied()
+    }
+
+    /// The next element's tag and contents
+    pub fn next(&mut self) -> Result<(u8, &'a [u8])> {
+        let truncated = || anyhow!("Truncated DER");
+        let (&tag, rest) = self.input.split_first().ok_or_else(truncated)?;
+        if tag & 0x1f == 0x1f {
+            return Err(anyhow!("Unsupported DER tag {:#x}", tag));
+        }
+        let (&first, mut rest) = rest.split_first().ok_or_else(truncated)?;
+        let length = if first < 0x80 {
+            first as usize
+        } else {
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > 4 || rest.len() < count {
+                return Err(anyhow!("Invalid DER length"));
+            }
+            let length = rest[..count]
+                .iter()
+                .fold(0usize, |length, &byte| (length << 8) | byte as usize);
+            rest = &rest[count..];
+            length
+        };
+        if rest.len() < length {
+            return Err(truncated());
+        }
+        let (value, rest) = rest.split_at(length);
+        self.input = rest;
+        Ok((tag, value))
+    }
+
+    /// The next element's contents, which must have the given tag
+    pub fn expect(&mut self, tag: u8) -> Result<&'a [u8]> {
+        let (found, value) = self.next()?;
+        if found != tag {
+            return Err(anyhow!("Expected DER tag {:#x}, found {:#x}", tag, found));
+        }
+        Ok(value)
+    }
+
+    /// The next element when it has the given tag
+    pub fn optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>> {
+        if self.peek() == Some(tag) {
+            self.expect(tag).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// An object identifier in dotted form
+pub fn oid(value: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut current: u64 = 0;
+    for &byte in value {
+        current = (current << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 != 0 {
+            continue;
+        }
+        // The first number packs the first two arcs
+        if parts.is_empty() {
+            let first = (current / 40).min(2);
+            parts.push(first);
+            parts.push(current - first * 40);
+        } else {
+            parts.push(current);
+        }
+        current = 0;
+    }
+    parts
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
@@ -0,0 +1,656 @@
+// TLS certificate inspector: connects to an endpoint (directly, or through a running
+// k8s_port_forward forward while still asking for the real server name), shows the chain
+// the server sends with the negotiated protocol and cipher, and watches a list of
+// endpoints for certificates about to expire.
+use anyhow::{anyhow, Result};
+use chrono::{Local, Utc};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::net;
+use plugin_api::Plugin;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::Deserialize;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+use tokio_rustls::TlsConnector;
+
+mod cert;
+mod der;
+
+use cert::Certificate;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_WARN_DAYS: i64 = 30;
+const DEFAULT_INTERVAL_SECS: u64 = 3600;
+/// Where k8s_port_forward keeps the forwards of its running processes
+const PORT_FORWARD_PLUGIN: &str = "k8s_port_forward";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TlsInspectConfig {
+    /// Seconds a handshake may take (default 5)
+    pub timeout_secs: Option<u64>,
+    /// Warn when a certificate expires within this many days (default 30)
+    pub warn_days: Option<i64>,
+    /// Seconds between watch rounds (default 3600)
+    pub interval_secs: Option<u64>,
+    #[serde(default)]
+    pub endpoint: Vec<EndpointConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EndpointConfig {
+    /// "host[:port]" or an https:// URL
+    pub address: String,
+    /// Server name to ask for (default: the host)
+    pub sni: Option<String>,
+    /// Connect through this k8s_port_forward forward (resource or local port)
+    pub via: Option<String>,
+}
+
+pub struct TlsInspectPlugin;
+
+impl TlsInspectPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# TLS Inspect Configuration
+warn_days = 30
+interval_secs = 3600
+
+[[endpoint]]
+address = "api.example.com"
+
+[[endpoint]]
+address = "grafana.internal:3000"
+
+[[endpoint]]
+address = "auth.internal.example.com"
+via = "svc/auth"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<TlsInspectConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: TlsInspectConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Endpoints can be given on the command line
+                Ok(TlsInspectConfig::default())
+            }
+        }
+        None => Ok(TlsInspectConfig::default()),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Endpoint {
+    address: String,
+    host: String,
+    port: u16,
+    sni: Option<String>,
+    via: Option<String>,
+}
+
+/// An endpoint from "host[:port]" or an https:// URL; the port defaults to 443
+fn parse_endpoint(address: &str, sni: Option<String>, via: Option<String>) -> Result<Endpoint> {
+    let rest = address
+        .strip_prefix("https://")
+        .or_else(|| address.strip_prefix("tls://"))
+        .unwrap_or(address);
+    let authority = rest.split('/').next().unwrap_or_default();
+    let (host, port) = match net::split_host_port(authority) {
+        (host, Some(port)) => (
+            host,
+            port.parse::<u16>()
+                .map_err(|_| anyhow!("Invalid port in {}", address))?,
+        ),
+        (host, None) => (host, 443),
+    };
+    if host.is_empty() {
+        return Err(anyhow!("No host in {}", address));
+    }
+    Ok(Endpoint {
+        address: address.to_string(),
+        host: host.to_string(),
+        port,
+        sni,
+        via,
+    })
+}
+
+/// The local port of a running k8s_port_forward forward, by resource ("svc/api", or just
+/// "api") or by the port itself
+fn forward_port(via: &str) -> Result<u16> {
+    if let Ok(port) = via.parse::<u16>() {
+        return Ok(port);
+    }
+    let dir = plugin_api::plugin_state_dir(PORT_FORWARD_PLUGIN)
+        .ok_or_else(|| anyhow!("Could not determine the k8s_port_forward state directory"))?;
+    let entries =
+        fs::read_dir(&dir).map_err(|_| anyhow!("No k8s_port_forward forwards running"))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with("forwards-") || !name.ends_with(".json") {
+            continue;
+        }
+        let Some(state) = fs::read_to_string(entry.path())
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        else {
+            continue;
+        };
+        for forward in state["forwards"].as_array().into_iter().flatten() {
+            let resource = forward["resource"].as_str().unwrap_or_default();
+            let matches = resource == via || resource.rsplit('/').next() == Some(via);
+            if matches && forward["status"].as_str() != Some("stopped") {
+                if let Some(port) = forward["local_port"].as_u64() {
+                    return Ok(port as u16);
+                }
+            }
+        }
+    }
+    Err(anyhow!("No running k8s_port_forward forward for {}", via))
+}
+
+/// Accepts whatever the server presents so the chain can be shown, keeping the verdict
+/// of the real verification for the report
+#[derive(Debug)]
+struct Recorder {
+    webpki: Arc<WebPkiServerVerifier>,
+    verdict: Mutex<Option<Result<(), String>>>,
+}
+
+impl ServerCertVerifier for Recorder {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let verdict = self
+            .webpki
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        *self.verdict.lock().unwrap() = Some(verdict);
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.webpki.supported_verify_schemes()
+    }
+}
+
+fn root_store() -> Arc<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    for error in &native.errors {
+        eprintln!("⚠️  Skipping a system certificate: {}", error);
+    }
+    let (added, _) = roots.add_parsable_certificates(native.certs);
+    if added == 0 {
+        eprintln!("⚠️  No system root certificates found, no chain will verify");
+    }
+    Arc::new(roots)
+}
+
+struct Handshake {
+    /// Where the connection went: the endpoint, or the forward's local port
+    connected: String,
+    server_name: String,
+    protocol: String,
+    cipher: String,
+    group: Option<String>,
+    alpn: Option<String>,
+    /// Whether the chain verifies against the system roots for the server name
+    trust: Result<(), String>,
+    chain: Vec<Result<Certificate>>,
+}
+
+async fn handshake(
+    endpoint: &Endpoint,
+    roots: Arc<RootCertStore>,
+    timeout: Duration,
+) -> Result<Handshake> {
+    let connected = match &endpoint.via {
+        Some(via) => format!("127.0.0.1:{}", forward_port(via)?),
+        None => net::host_port(&endpoint.host, endpoint.port),
+    };
+    let server_name = endpoint
+        .sni
+        .clone()
+        .unwrap_or_else(|| endpoint.host.clone());
+
+    let recorder = Arc::new(Recorder {
+        webpki: WebPkiServerVerifier::builder(roots).build()?,
+        verdict: Mutex::new(None),
+    });
+    let mut config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(recorder.clone())
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    let name = ServerName::try_from(server_name.clone())
+        .map_err(|_| anyhow!("Invalid server name: {}", server_name))?;
+
+    let connect = async {
+        let tcp = TcpStream::connect(&connected)
+            .await
+            .map_err(|e| anyhow!("Can't connect to {}: {}", connected, e))?;
+        TlsConnector::from(Arc::new(config))
+            .connect(name, tcp)
+            .await
+            .map_err(|e| anyhow!("TLS handshake with {} failed: {}", connected, e))
+    };
+    let stream = tokio::time::timeout(timeout, connect)
+        .await
+        .map_err(|_| anyhow!("Timed out after {}s on {}", timeout.as_secs(), connected))??;
+
+    let (_, session) = stream.get_ref();
+    let protocol = session
+        .protocol_version()
+        .map(|version| format!("{:?}", version).replace('_', "."))
+        .unwrap_or_default();
+    let cipher = session
+        .negotiated_cipher_suite()
+        .map(|suite| format!("{:?}", suite.suite()))
+        .unwrap_or_default();
+    let group = session
+        .negotiated_key_exchange_group()
+        .map(|group| format!("{:?}", group.name()));
+    let alpn = session
+        .alpn_protocol()
+        .map(|alpn| String::from_utf8_lossy(alpn).to_string());
+    let chain = session
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .map(|der| cert::parse(der))
+        .collect();
+    let trust = recorder
+        .verdict
+        .lock()
+        .unwrap()
+        .take()
+        .unwrap_or_else(|| Err("the server sent no certificate".to_string()));
+
+    Ok(Handshake {
+        connected,
+        server_name,
+        protocol,
+        cipher,
+        group,
+        alpn,
+        trust,
+        chain,
+    })
+}
+
+fn expiry(certificate: &Certificate, warn_days: i64) -> String {
+    let days = certificate.days_left();
+    if certificate.not_after < Utc::now() {
+        format!("❌ expired {} days ago", -days)
+    } else if days < warn_days {
+        format!("⚠️  {} days left", days)
+    } else {
+        format!("{} days left", days)
+    }
+}
+
+fn print_handshake(endpoint: &Endpoint, handshake: &Handshake, warn_days: i64) {
+    println!(
+        "🔐 {} (server name {}, connected to {})",
+        endpoint.address, handshake.server_name, handshake.connected
+    );
+    let mut negotiated = vec![handshake.protocol.clone(), handshake.cipher.clone()];
+    negotiated.extend(handshake.group.clone());
+    if let Some(alpn) = &handshake.alpn {
+        negotiated.push(format!("ALPN {}", alpn));
+    }
+    println!("   Negotiated: {}", negotiated.join(", "));
+    match &handshake.trust {
+        Ok(()) => println!(
+            "   Trust:      ✅ chain verifies for {}",
+            handshake.server_name
+        ),
+        Err(e) => println!("   Trust:      ❌ {}", e),
+    }
+
+    for (i, certificate) in handshake.chain.iter().enumerate() {
+        println!();
+        let certificate = match certificate {
+            Ok(certificate) => certificate,
+            Err(e) => {
+                println!("📜 [{}] ❌ Can't parse the certificate: {}", i, e);
+                continue;
+            }
+        };
+        println!("📜 [{}] {}", i, certificate.subject);
+        println!("   Issuer:     {}", certificate.issuer);
+        println!("   Serial:     {}", certificate.serial);
+        println!(
+            "   Valid:      {} → {} ({})",
+            certificate.not_before.format("%Y-%m-%d %H:%M UTC"),
+            certificate.not_after.format("%Y-%m-%d %H:%M UTC"),
+            expiry(certificate, warn_days)
+        );
+        println!(
+            "   Key:        {}, signed with {}",
+            certificate.key, certificate.signature
+        );
+        if !certificate.sans.is_empty() {
+            println!("   SANs:       {}", certificate.sans.join(", "));
+        }
+        if certificate.is_ca {
+            println!("   CA:         yes");
+        }
+    }
+}
+
+/// The expiry and status columns of the watch table; whether the endpoint is fine
+fn watch_row(result: &Result<Handshake>, warn_days: i64) -> (bool, String) {
+    let handshake = match result {
+        Ok(handshake) => handshake,
+        Err(e) => return (false, format!("{:<10}  ❌ {}", "—", e)),
+    };
+    // The first certificate of the chain to expire, intermediates included
+    let Some((position, earliest)) = handshake
+        .chain
+        .iter()
+        .enumerate()
+        .filter_map(|(position, certificate)| Some((position, certificate.as_ref().ok()?)))
+        .min_by_key(|(_, certificate)| certificate.not_after)
+    else {
+        return (false, format!("{:<10}  ❌ no readable certificate", "—"));
+    };
+    let which = if position == 0 {
+        String::new()
+    } else {
+        format!(" ({})", earliest.short_name())
+    };
+    let days = earliest.days_left();
+    let (ok, status) = if earliest.not_after < Utc::now() {
+        (false, format!("❌ expired{}", which))
+    } else if days < warn_days {
+        (false, format!("⚠️  expires in {} days{}", days, which))
+    } else if let Err(e) = &handshake.trust {
+        (false, format!("⚠️  {}", e))
+    } else {
+        (true, format!("✅ {} days left", days))
+    };
+    (
+        ok,
+        format!("{:<10}  {}", earliest.not_after.format("%Y-%m-%d"), status),
+    )
+}
+
+/// Checks every endpoint at once and prints the table; whether all are fine
+async fn watch_round(
+    endpoints: &[Endpoint],
+    roots: &Arc<RootCertStore>,
+    timeout: Duration,
+    warn_days: i64,
+) -> bool {
+    let handles: Vec<_> = endpoints
+        .iter()
+        .cloned()
+        .map(|endpoint| {
+            let roots = roots.clone();
+            tokio::spawn(async move { handshake(&endpoint, roots, timeout).await })
+        })
+        .collect();
+    let width = endpoints
+        .iter()
+        .map(|endpoint| endpoint.address.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(8, 50);
+    println!("🕐 {}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+    println!("{:<width$}  {:<10}  STATUS", "ENDPOINT", "EXPIRES");
+    let mut all_ok = true;
+    for (endpoint, handle) in endpoints.iter().zip(handles) {
+        let result = match handle.await {
+            Ok(result) => result,
+            Err(e) => Err(anyhow!("{}", e)),
+        };
+        let (ok, row) = watch_row(&result, warn_days);
+        all_ok &= ok;
+        println!("{:<width$}  {}", endpoint.address, row);
+    }
+    println!();
+    all_ok
+}
+
+impl Plugin for TlsInspectPlugin {
+    fn name(&self) -> &'static str {
+        "tls_inspect"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Inspect TLS certificate chains and watch endpoints for expiring certificates"
+    }
+
+    fn subcommand(&self) -> Command {
+        let timeout_arg = Arg::new("timeout")
+            .long("timeout")
+            .value_name("SECONDS")
+            .value_parser(clap::value_parser!(u64))
+            .help("Seconds a handshake may take (default 5)");
+        let warn_arg = Arg::new("warn_days")
+            .long("warn-days")
+            .value_name("DAYS")
+            .value_parser(clap::value_parser!(i64))
+            .help("Warn about certificates expiring within DAYS (default 30)");
+        Command::new(self.name())
+            .about("Show the TLS certificate chain of an endpoint, or watch endpoints for expiring certificates")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("inspect")
+                    .about("Connect to an endpoint and show its chain, protocol and cipher")
+                    .arg(
+                        Arg::new("endpoint")
+                            .value_name("ENDPOINT")
+                            .required(true)
+                            .help("host[:port] or an https:// URL (port 443 by default)"),
+                    )
+                    .arg(
+                        Arg::new("sni")
+                            .long("sni")
+                            .value_name("NAME")
+                            .help("Server name to ask for (default: the host)"),
+                    )
+                    .arg(
+                        Arg::new("via")
+                            .long("via")
+                            .value_name("FORWARD")
+                            .help("Connect through a running k8s_port_forward forward, by resource or local port"),
+                    )
+                    .arg(timeout_arg.clone())
+                    .arg(warn_arg.clone()),
+            )
+            .subcommand(
+                Command::new("watch")
+                    .about("Check endpoints' certificates for upcoming expiry, every interval")
+                    .arg(
+                        Arg::new("endpoints")
+                            .value_name("ENDPOINT")
+                            .num_args(0..)
+                            .help("host[:port] or https:// URLs (default: configured endpoints)"),
+                    )
+                    .arg(
+                        Arg::new("interval")
+                            .long("interval")
+                            .value_name("SECONDS")
+                            .value_parser(clap::value_parser!(u64))
+                            .help("Seconds between checks (default 3600)"),
+                    )
+                    .arg(
+                        Arg::new("once")
+                            .long("once")
+                            .action(ArgAction::SetTrue)
+                            .help("Check once and exit, with 1 when any endpoint needs attention"),
+                    )
+                    .arg(timeout_arg)
+                    .arg(warn_arg),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(TlsInspectPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<TlsInspectConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let sub = matches.subcommand().map(|(_, sub)| sub).unwrap_or(matches);
+        let timeout = Duration::from_secs(
+            sub.get_one::<u64>("timeout")
+                .copied()
+                .or(config.timeout_secs)
+                .unwrap_or(DEFAULT_TIMEOUT_SECS),
+        );
+        let warn_days = sub
+            .get_one::<i64>("warn_days")
+            .copied()
+            .or(config.warn_days)
+            .unwrap_or(DEFAULT_WARN_DAYS);
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        let roots = root_store();
+
+        match matches.subcommand() {
+            Some(("inspect", sub)) => {
+                let address = sub.get_one::<String>("endpoint").unwrap();
+                let endpoint = match parse_endpoint(
+                    address,
+                    sub.get_one::<String>("sni").cloned(),
+                    sub.get_one::<String>("via").cloned(),
+                ) {
+                    Ok(endpoint) => endpoint,
+                    Err(e) => {
+                        eprintln!("❌ {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                match rt.block_on(handshake(&endpoint, roots, timeout)) {
+                    Ok(handshake) => print_handshake(&endpoint, &handshake, warn_days),
+                    Err(e) => {
+                        eprintln!("❌ {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Some(("watch", sub)) => {
+                let parsed: Result<Vec<Endpoint>> = match sub.get_many::<String>("endpoints") {
+                    Some(addresses) => addresses
+                        .map(|address| parse_endpoint(address, None, None))
+                        .collect(),
+                    None => config
+                        .endpoint
+                        .iter()
+                        .map(|endpoint| {
+                            parse_endpoint(
+                                &endpoint.address,
+                                endpoint.sni.clone(),
+                                endpoint.via.clone(),
+                            )
+                        })
+                        .collect(),
+                };
+                let endpoints = match parsed {
+                    Ok(endpoints) if !endpoints.is_empty() => endpoints,
+                    Ok(_) => {
+                        eprintln!("❌ No endpoints to watch (give them as arguments or configure in config file)");
+                        eprintln!("💡 Example: proxy tls_inspect watch api.example.com grafana.internal:3000");
+                        eprintln!("📝 Sample config:\n{}", TlsInspectPlugin::sample_config());
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        eprintln!("❌ {}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+                if sub.get_flag("once") {
+                    if !rt.block_on(watch_round(&endpoints, &roots, timeout, warn_days)) {
+                        std::process::exit(1);
+                    }
+                    return;
+                }
+                let interval = Duration::from_secs(
+                    sub.get_one::<u64>("interval")
+                        .copied()
+                        .or(config.interval_secs)
+                        .unwrap_or(DEFAULT_INTERVAL_SECS),
+                );
+                if let Err(e) = ctrlc::set_handler(move || {
+                    println!("\n👋 Shutting down...");
+                    std::process::exit(0);
+                }) {
+                    eprintln!("⚠️  Failed to set Ctrl+C handler: {}", e);
+                }
+                println!(
+                    "👀 Watching {} endpoint(s) every {}s, warning {} days ahead, press Ctrl+C to stop\n",
+                    endpoints.len(),
+                    interval.as_secs(),
+                    warn_days
+                );
+                rt.block_on(async {
+                    loop {
+                        watch_round(&endpoints, &roots, timeout, warn_days).await;
+                        tokio::time::sleep(interval).await;
+                    }
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(TlsInspectPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,207 @@
+// Vault HTTP API: logging in with the configured auth method and reading secrets. Only
+// the calls the plugin needs; KV v1 and v2 reads are told apart by the response shape.
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+
+/// Service account token mounted into pods
+const SERVICE_ACCOUNT_TOKEN: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum Auth {
+    /// A token from `token`, $VAULT_TOKEN or ~/.vault-token
+    Token { token: Option<String> },
+    AppRole {
+        role_id: String,
+        secret_id: String,
+        /// Auth mount path (default "approle")
+        mount: Option<String>,
+    },
+    Kubernetes {
+        role: String,
+        /// Service account JWT (default: the token mounted into pods)
+        jwt_path: Option<String>,
+        /// Auth mount path (default "kubernetes")
+        mount: Option<String>,
+    },
+}
+
+impl Default for Auth {
+    fn default() -> Self {
+        Auth::Token { token: None }
+    }
+}
+
+/// A token from a login, valid for `lease_secs` (0 means it doesn't expire)
+pub struct Login {
+    pub token: String,
+    pub lease_secs: u64,
+}
+
+pub struct Vault {
+    http: Client,
+    address: String,
+    namespace: Option<String>,
+}
+
+fn secret(value: &str) -> Result<String> {
+    // A vault: reference would run this plugin again to log in, without end
+    if value.starts_with("vault:") {
+        return Err(anyhow!("Vault credentials can't come from Vault itself"));
+    }
+    plugin_api::resolve_secret(value).map_err(|e| anyhow!(e))
+}
+
+/// The token of the token method, from the config, the environment or the CLI's file
+pub fn static_token(configured: Option<&str>) -> Result<String> {
+    if let Some(token) = configured {
+        return secret(token);
+    }
+    if let Ok(token) = std::env::var("VAULT_TOKEN") {
+        return Ok(token);
+    }
+    let path = dirs::home_dir()
+        .map(|home| home.join(".vault-token"))
+        .ok_or_else(|| anyhow!("Could not determine the home directory"))?;
+    let token = fs::read_to_string(&path).map_err(|_| {
+        anyhow!("No Vault token: set `token`, VAULT_TOKEN, or run `vault login` with the Vault CLI")
+    })?;
+    Ok(token.trim().to_string())
+}
+
+impl Vault {
+    pub fn new(address: &str, namespace: Option<String>, ca_cert: Option<&str>) -> Result<Self> {
+        let mut http = Client::builder();
+        if let Some(path) = ca_cert {
+            let pem = fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+            http = http.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+        }
+        Ok(Vault {
+            http: http.build()?,
+            address: address.trim_end_matches('/').to_string(),
+            namespace,
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}/v1/{}", self.address, path));
+        match &self.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        }
+    }
+
+    async fn send(request: RequestBuilder) -> Result<Value> {
+        let response = request.send().await?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let errors = body["errors"]
+                .as_array()
+                .map(|errors| {
+                    errors
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                })
+                .filter(|errors| !errors.is_empty())
+                .unwrap_or_else(|| status.to_string());
+            return Err(anyhow!("Vault answered {}: {}", status.as_u16(), errors));
+        }
+        Ok(body)
+    }
+
+    /// Logs in with approle or kubernetes auth; the token method has nothing to log in to
+    pub async fn login(&self, auth: &Auth) -> Result<Option<Login>> {
+        let (mount, payload) = match auth {
+            Auth::Token { .. } => return Ok(None),
+            Auth::AppRole {
+                role_id,
+                secret_id,
+                mount,
+            } => (
+                mount.as_deref().unwrap_or("approle"),
+                serde_json::json!({ "role_id": secret(role_id)?, "secret_id": secret(secret_id)? }),
+            ),
+            Auth::Kubernetes {
+                role,
+                jwt_path,
+                mount,
+            } => {
+                let path = jwt_path.as_deref().unwrap_or(SERVICE_ACCOUNT_TOKEN);
+                let jwt = fs::read_to_string(path).with_context(|| {
+                    format!("Failed to read the service account token {}", path)
+                })?;
+                (
+                    mount.as_deref().unwrap_or("kubernetes"),
+                    serde_json::json!({ "role": role, "jwt": jwt.trim() }),
+                )
+            }
+        };
+        let body = Self::send(
+            self.request(reqwest::Method::POST, &format!("auth/{}/login", mount))
+                .json(&payload),
+        )
+        .await?;
+        let token = body["auth"]["client_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The login response has no token"))?;
+        Ok(Some(Login {
+            token: token.to_string(),
+            lease_secs: body["auth"]["lease_duration"].as_u64().unwrap_or(0),
+        }))
+    }
+
+    /// The data of a secret; for KV v2 paths ("kv/data/NAME") the inner data
+    pub async fn read(&self, token: &str, path: &str) -> Result<Value> {
+        let body = Self::send(
+            self.request(reqwest::Method::GET, path.trim_start_matches('/'))
+                .header("X-Vault-Token", token),
+        )
+        .await
+        .with_context(|| format!("Failed to read {}", path))?;
+        let data = &body["data"];
+        if data["data"].is_object() && data.get("metadata").is_some() {
+            Ok(data["data"].clone())
+        } else {
+            Ok(data.clone())
+        }
+    }
+}
+
+/// One field of a secret as text; without a field name, the only field there is
+pub fn field(data: &Value, path: &str, name: Option<&str>) -> Result<String> {
+    let fields = data
+        .as_object()
+        .ok_or_else(|| anyhow!("{} holds no fields", path))?;
+    let value = match name {
+        Some(name) => fields.get(name).ok_or_else(|| {
+            let names: Vec<&str> = fields.keys().map(String::as_str).collect();
+            anyhow!(
+                "{} has no field '{}' (has: {})",
+                path,
+                name,
+                names.join(", ")
+            )
+        })?,
+        None if fields.len() == 1 => fields.values().next().expect("one field"),
+        None => {
+            let names: Vec<&str> = fields.keys().map(String::as_str).collect();
+            return Err(anyhow!(
+                "{} has several fields, pick one with #FIELD: {}",
+                path,
+                names.join(", ")
+            ));
+        }
+    };
+    Ok(match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    })
+}
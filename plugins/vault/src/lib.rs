@@ -0,0 +1,407 @@
+// HashiCorp Vault access for the other workflows: logs in with a token, AppRole or
+// Kubernetes auth (tokens from a login are cached in the state directory until they
+// expire), reads secrets, renders them into env files, and resolves the
+// "vault:PATH#FIELD" references plugin configs can use wherever secrets are accepted.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::Plugin;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::runtime::Runtime;
+
+mod client;
+
+use client::{Auth, Vault};
+
+const PLUGIN_NAME: &str = "vault";
+/// Cached tokens are renewed this long before they expire
+const EXPIRY_MARGIN_SECS: u64 = 60;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VaultConfig {
+    /// Vault server (default $VAULT_ADDR)
+    pub address: Option<String>,
+    /// Enterprise namespace (default $VAULT_NAMESPACE)
+    pub namespace: Option<String>,
+    /// CA certificate for the server (default $VAULT_CACERT)
+    pub ca_cert: Option<String>,
+    #[serde(default)]
+    pub auth: Auth,
+    #[serde(default)]
+    pub env_file: Vec<EnvFile>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnvFile {
+    pub path: String,
+    /// Variable name → "PATH#FIELD"
+    pub vars: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedToken {
+    address: String,
+    token: String,
+    /// Unix seconds; 0 for tokens that don't expire
+    expires_at: u64,
+}
+
+pub struct VaultPlugin;
+
+impl VaultPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Vault Configuration
+address = "https://vault.example.com:8200"  # default: $VAULT_ADDR
+# namespace = "team-a"
+# ca_cert = "/path/to/vault-ca.pem"
+
+[auth]
+method = "approle"  # "token", "approle" or "kubernetes"
+role_id = "secret:env:VAULT_ROLE_ID"
+secret_id = "secret:vault-secret-id"
+# method = "kubernetes"
+# role = "dev"
+
+[[env_file]]
+path = "/path/to/project/.env.local"
+[env_file.vars]
+DATABASE_PASSWORD = "kv/data/db#password"
+API_KEY = "kv/data/api#key"
+
+# Other plugin configs can use the same references wherever secrets are accepted:
+# token = "vault:kv/data/api#token"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<VaultConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: VaultConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Config-less use with VAULT_ADDR and VAULT_TOKEN is common, so stay quiet
+                // on stdout, which `get --encoded` is read from
+                eprintln!(
+                    "💡 No config at {}, using VAULT_ADDR and VAULT_TOKEN",
+                    config_path.display()
+                );
+                Ok(VaultConfig::default())
+            }
+        }
+        None => Ok(VaultConfig::default()),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+fn token_path() -> Option<PathBuf> {
+    plugin_api::plugin_state_dir(PLUGIN_NAME).map(|dir| dir.join("token.json"))
+}
+
+fn cached_token(address: &str) -> Option<String> {
+    let content = fs::read_to_string(token_path()?).ok()?;
+    let cached: CachedToken = serde_json::from_str(&content).ok()?;
+    let valid = cached.expires_at == 0 || cached.expires_at > now() + EXPIRY_MARGIN_SECS;
+    (cached.address == address && valid).then_some(cached.token)
+}
+
+fn save_token(address: &str, login: &client::Login) -> Result<()> {
+    let path = token_path().ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let cached = CachedToken {
+        address: address.to_string(),
+        token: login.token.clone(),
+        expires_at: if login.lease_secs == 0 {
+            0
+        } else {
+            now() + login.lease_secs
+        },
+    };
+    write_private(&path, &serde_json::to_string(&cached)?)
+}
+
+/// Writes a file only the user can read. The content goes to a new file beside it that is
+/// renamed over `path`, since the mode only applies to a file when it is created and an
+/// existing one would keep its own.
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("{} is not a file path", path.display()))?;
+        let partial = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&partial);
+        let written = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&partial)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .and_then(|()| fs::rename(&partial, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        Ok(written?)
+    }
+    #[cfg(not(unix))]
+    {
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// "PATH#FIELD", with or without the "vault:" prefix
+fn parse_reference(reference: &str) -> (&str, Option<&str>) {
+    let reference = reference.strip_prefix("vault:").unwrap_or(reference);
+    match reference.split_once('#') {
+        Some((path, field)) => (path, Some(field)),
+        None => (reference, None),
+    }
+}
+
+/// Quotes a value for an env file read by shells, docker and dotenv libraries
+fn env_quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+struct Session {
+    config: VaultConfig,
+    address: String,
+    vault: Vault,
+    token: Option<String>,
+    /// Whether `token` came from the cache and may have been revoked
+    from_cache: bool,
+}
+
+impl Session {
+    fn new(config: VaultConfig) -> Result<Self> {
+        let address = config
+            .address
+            .clone()
+            .or_else(|| std::env::var("VAULT_ADDR").ok())
+            .ok_or_else(|| anyhow!("No Vault address: set `address` or VAULT_ADDR"))?;
+        let namespace = config
+            .namespace
+            .clone()
+            .or_else(|| std::env::var("VAULT_NAMESPACE").ok());
+        let ca_cert = config
+            .ca_cert
+            .clone()
+            .or_else(|| std::env::var("VAULT_CACERT").ok());
+        let vault = Vault::new(&address, namespace, ca_cert.as_deref())?;
+        Ok(Session {
+            config,
+            address,
+            vault,
+            token: None,
+            from_cache: false,
+        })
+    }
+
+    async fn login(&mut self) -> Result<String> {
+        let login = self
+            .vault
+            .login(&self.config.auth)
+            .await?
+            .ok_or_else(|| anyhow!("The token auth method has no login"))?;
+        if let Err(e) = save_token(&self.address, &login) {
+            eprintln!("⚠️  Failed to cache the token: {}", e);
+        }
+        self.token = Some(login.token.clone());
+        self.from_cache = false;
+        Ok(login.token)
+    }
+
+    async fn token(&mut self) -> Result<String> {
+        if let Some(token) = &self.token {
+            return Ok(token.clone());
+        }
+        if let Auth::Token { token } = &self.config.auth {
+            let token = client::static_token(token.as_deref())?;
+            self.token = Some(token.clone());
+            return Ok(token);
+        }
+        if let Some(token) = cached_token(&self.address) {
+            self.token = Some(token.clone());
+            self.from_cache = true;
+            return Ok(token);
+        }
+        self.login().await
+    }
+
+    async fn get(&mut self, reference: &str) -> Result<String> {
+        let (path, field) = parse_reference(reference);
+        let token = self.token().await?;
+        let data = match self.vault.read(&token, path).await {
+            // A cached token may have been revoked before it expired
+            Err(_) if self.from_cache => {
+                let token = self.login().await?;
+                self.vault.read(&token, path).await?
+            }
+            result => result?,
+        };
+        client::field(&data, path, field)
+    }
+}
+
+impl Plugin for VaultPlugin {
+    fn name(&self) -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "HashiCorp Vault login, secret reads, env files and vault: references"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Read secrets from HashiCorp Vault for the other workflows")
+            .subcommand_required(true)
+            .subcommand(Command::new("login").about("Log in with the configured auth method"))
+            .subcommand(
+                Command::new("get")
+                    .about("Print one secret field")
+                    .arg(
+                        Arg::new("reference")
+                            .required(true)
+                            .value_name("PATH#FIELD")
+                            .help("e.g. kv/data/db#password"),
+                    )
+                    .arg(
+                        Arg::new("encoded")
+                            .long("encoded")
+                            .action(ArgAction::SetTrue)
+                            .hide(true),
+                    ),
+            )
+            .subcommand(
+                Command::new("env")
+                    .about("Render the configured env files")
+                    .arg(
+                        Arg::new("file")
+                            .long("file")
+                            .short('f')
+                            .value_name("PATH")
+                            .help("Only render the env file with this path"),
+                    ),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(VaultPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<VaultConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let mut session = match Session::new(config) {
+            Ok(session) => session,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        let result = rt.block_on(async {
+            match matches.subcommand() {
+                Some(("login", _)) => {
+                    session.login().await?;
+                    println!("✅ Logged in to {}", session.address);
+                    Ok(())
+                }
+                Some(("get", args)) => {
+                    let reference = args
+                        .get_one::<String>("reference")
+                        .expect("required argument");
+                    let secret = session.get(reference).await?;
+                    if args.get_flag("encoded") {
+                        println!(
+                            "{}{}",
+                            plugin_api::ENCODED_SECRET_PREFIX,
+                            hex::encode(secret)
+                        );
+                    } else {
+                        println!("{}", secret);
+                    }
+                    Ok(())
+                }
+                Some(("env", args)) => {
+                    let only = args.get_one::<String>("file");
+                    let files: Vec<EnvFile> = std::mem::take(&mut session.config.env_file)
+                        .into_iter()
+                        .filter(|file| only.is_none_or(|only| &file.path == only))
+                        .collect();
+                    if files.is_empty() {
+                        return Err(anyhow!("No [[env_file]] to render"));
+                    }
+                    let mut rendered = Vec::new();
+                    for file in files {
+                        let mut content = String::new();
+                        for (name, reference) in &file.vars {
+                            let value = session.get(reference).await?;
+                            content.push_str(&format!("{}={}\n", name, env_quote(&value)));
+                        }
+                        rendered.push((file.path, content, file.vars.len()));
+                    }
+                    for (path, content, count) in rendered {
+                        write_private(Path::new(&path), &content)?;
+                        println!("📝 {} ({} variables)", path, count);
+                    }
+                    Ok(())
+                }
+                _ => Err(anyhow!("Unknown command")),
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("❌ {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(VaultPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
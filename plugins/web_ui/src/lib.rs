@@ -0,0 +1,171 @@
+// A local browser dashboard for everything the proxy is doing: running plugin processes
+// with their connection counts, k8s_port_forward forwards, the latest decoded messages and
+// saved chat sessions, and what happens on the event bus. Plugins can be started in the
+// background and stopped from the page.
+use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+mod server;
+mod state;
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8070";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WebUiConfig {
+    /// Address to listen on (default 127.0.0.1:8070, [::1]:8070 for IPv6)
+    pub listen: Option<String>,
+    /// Hide the start and stop controls (default false)
+    pub read_only: Option<bool>,
+}
+
+pub struct WebUiPlugin;
+
+impl WebUiPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Web UI Configuration
+listen = "127.0.0.1:8070"
+# read_only = true  # only show, without the start and stop controls
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<WebUiConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: WebUiConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Everything can be given on the command line
+                Ok(WebUiConfig::default())
+            }
+        }
+        None => Ok(WebUiConfig::default()),
+    }
+}
+
+impl Plugin for WebUiPlugin {
+    fn name(&self) -> &'static str {
+        "web_ui"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Local browser dashboard for running forwards, relayed traffic and chat sessions"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Serve a local dashboard of running plugins, traffic and chat sessions")
+            .arg(
+                Arg::new("listen")
+                    .long("listen")
+                    .short('l')
+                    .value_name("ADDR")
+                    .help("Address to listen on (default 127.0.0.1:8070, [::1]:8070 for IPv6)"),
+            )
+            .arg(
+                Arg::new("read-only")
+                    .long("read-only")
+                    .action(ArgAction::SetTrue)
+                    .help("Only show, without the start and stop controls"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(WebUiPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<WebUiConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let listen = matches
+            .get_one::<String>("listen")
+            .or(config.listen.as_ref())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+        let read_only = matches.get_flag("read-only") || config.read_only.unwrap_or(false);
+
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        rt.block_on(async {
+            let listener = match TcpListener::bind(&listen).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("❌ Failed to listen on {}: {}", listen, e);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = ctrlc::set_handler(move || {
+                println!("\n👋 Shutting down...");
+                std::process::exit(0);
+            }) {
+                eprintln!("❌ Failed to set Ctrl+C handler: {}", e);
+                std::process::exit(1);
+            }
+
+            println!("🖥️  Dashboard on http://{}/", listen);
+            if read_only {
+                println!("🔒 Read-only: starting and stopping plugins is disabled");
+            } else {
+                std::thread::spawn(server::handle_requests);
+            }
+
+            let dashboard = Arc::new(server::Dashboard {
+                listen,
+                controls: !read_only,
+                plugin_name: self.name(),
+            });
+
+            loop {
+                let (socket, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("❌ Accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let dashboard = dashboard.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request| dashboard.clone().handle(request));
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(socket), service)
+                        .await
+                    {
+                        eprintln!("❌ [{}] {}", addr, e);
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(WebUiPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
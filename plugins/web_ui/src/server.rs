@@ -0,0 +1,193 @@
+// The dashboard's HTTP side: the page itself, the state it polls, and the start/stop
+// controls, which ask for plugin command lines to be started in the background and for
+// plugin processes to be interrupted over the event bus (plugin_api::events). `proxy
+// daemon` carries the requests out when it runs, supervising what it starts; otherwise
+// the dashboard does, through plugin_api::processes.
+use crate::state;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::{Method, Request, Response, StatusCode};
+use plugin_api::events::{self, Event};
+use plugin_api::processes;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+const DASHBOARD: &str = include_str!("dashboard.html");
+/// How long a start or stop request may take to be answered
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often requests are looked for, and whether the handler role is free
+const REQUEST_POLL: Duration = Duration::from_millis(250);
+
+pub struct Dashboard {
+    /// The address the browser reaches us on, checked against Host and Origin
+    pub listen: String,
+    /// Whether the start and stop controls are enabled
+    pub controls: bool,
+    pub plugin_name: &'static str,
+}
+
+#[derive(Deserialize)]
+struct StartRequest {
+    /// A plugin command line, e.g. "k8s_port_forward -c dev"
+    command: String,
+}
+
+#[derive(Deserialize)]
+struct StopRequest {
+    pid: u32,
+}
+
+fn response(
+    status: StatusCode,
+    content_type: &str,
+    body: impl Into<Bytes>,
+) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    if let Ok(value) = content_type.parse() {
+        response.headers_mut().insert("content-type", value);
+    }
+    response
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Full<Bytes>> {
+    response(status, "application/json", body.to_string())
+}
+
+fn error(status: StatusCode, message: impl std::fmt::Display) -> Response<Full<Bytes>> {
+    json_response(status, json!({ "error": message.to_string() }))
+}
+
+impl Dashboard {
+    /// Requests must name our address as Host, so a page served from elsewhere can't reach
+    /// us through DNS rebinding, and POSTs must come from our own page
+    fn trusted<B>(&self, request: &Request<B>) -> bool {
+        let header = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let local = |host: &str| {
+            let port = self.listen.rsplit(':').next().unwrap_or_default();
+            host == self.listen
+                || host == format!("localhost:{}", port)
+                || host == format!("127.0.0.1:{}", port)
+                || host == format!("[::1]:{}", port)
+        };
+        let Some(host) = header("host").filter(|host| local(host)) else {
+            return false;
+        };
+        request.method() != Method::POST || header("origin") == Some(&format!("http://{}", host))
+    }
+
+    fn start(&self, command: &str) -> anyhow::Result<Value> {
+        let args = shlex::split(command)
+            .filter(|args| !args.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Give a plugin command line, e.g. k8s_port_forward"))?;
+        if args[0] == self.plugin_name {
+            return Err(anyhow::anyhow!("{} is not a plugin to start", args[0]));
+        }
+        match events::request(|id| Event::Start { id, args }, REQUEST_TIMEOUT)? {
+            Event::Started { plugin, pid, .. } => {
+                println!("▶️  Started {} (pid {})", command, pid);
+                Ok(json!({ "started": plugin, "pid": pid }))
+            }
+            Event::Failed { message, .. } => Err(anyhow::anyhow!(message)),
+            answer => Err(anyhow::anyhow!("Unexpected answer: {}", answer)),
+        }
+    }
+
+    fn stop(&self, pid: u32) -> anyhow::Result<Value> {
+        match events::request(|id| Event::Stop { id, pid }, REQUEST_TIMEOUT)? {
+            Event::Stopped { plugin, pid, .. } => {
+                println!("⏹️  Stopped {} (pid {})", plugin, pid);
+                Ok(json!({ "stopped": plugin, "pid": pid }))
+            }
+            Event::Failed { message, .. } => Err(anyhow::anyhow!(message)),
+            answer => Err(anyhow::anyhow!("Unexpected answer: {}", answer)),
+        }
+    }
+
+    pub async fn handle(
+        self: Arc<Self>,
+        request: Request<Incoming>,
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        if !self.trusted(&request) {
+            return Ok(error(StatusCode::FORBIDDEN, "Not allowed from this origin"));
+        }
+        let (parts, body) = request.into_parts();
+        let body = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => return Ok(error(StatusCode::BAD_REQUEST, e)),
+        };
+
+        let answer = match (&parts.method, parts.uri.path()) {
+            (&Method::GET, "/") => response(StatusCode::OK, "text/html; charset=utf-8", DASHBOARD),
+            (&Method::GET, "/api/state") => {
+                json_response(StatusCode::OK, state::snapshot(self.controls))
+            }
+            (&Method::POST, "/api/start" | "/api/stop") if !self.controls => {
+                error(StatusCode::FORBIDDEN, "Controls are disabled (--read-only)")
+            }
+            (&Method::POST, "/api/start") => {
+                let dashboard = self.clone();
+                // Waiting on the answer blocks
+                match tokio::task::spawn_blocking(move || {
+                    serde_json::from_slice::<StartRequest>(&body)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|start| dashboard.start(&start.command))
+                })
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result)
+                {
+                    Ok(started) => json_response(StatusCode::OK, started),
+                    Err(e) => error(StatusCode::BAD_REQUEST, e),
+                }
+            }
+            (&Method::POST, "/api/stop") => {
+                let dashboard = self.clone();
+                match tokio::task::spawn_blocking(move || {
+                    serde_json::from_slice::<StopRequest>(&body)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|stop| dashboard.stop(stop.pid))
+                })
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result)
+                {
+                    Ok(stopped) => json_response(StatusCode::OK, stopped),
+                    Err(e) => error(StatusCode::BAD_REQUEST, e),
+                }
+            }
+            (&Method::GET, _) => error(StatusCode::NOT_FOUND, "Not found"),
+            _ => error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+        };
+        Ok(answer)
+    }
+}
+
+/// Carries out the start and stop requests on the event bus for as long as no other process
+/// does, so the controls work without `proxy daemon`, which takes the role over when it
+/// starts and hands it back when it exits
+pub fn handle_requests() {
+    let mut handler: Option<events::Handler> = None;
+    let mut requests = events::Subscriber::new();
+    loop {
+        if !handler.as_ref().is_some_and(events::Handler::held) {
+            handler = events::Handler::take();
+        }
+        let handling = handler.is_some();
+        for received in requests.poll() {
+            if handling {
+                processes::handle(&received.event);
+            }
+        }
+        std::thread::sleep(REQUEST_POLL);
+    }
+}
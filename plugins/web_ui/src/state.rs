@@ -0,0 +1,214 @@
+// What the dashboard shows, read fresh on every poll from what the other plugin processes
+// publish: which processes run (plugin_api::processes), their metrics files (connections
+// and bytes), the k8s_port_forward state files, the traffic feeds, the event bus and the
+// saved ollama_chat sessions.
+use plugin_api::events;
+use plugin_api::metrics;
+use plugin_api::processes;
+use plugin_api::traffic;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+
+const PORT_FORWARD_PLUGIN: &str = "k8s_port_forward";
+const CHAT_PLUGIN: &str = "ollama_chat";
+/// Recent messages sent to the dashboard
+const MESSAGES_SHOWN: usize = 50;
+/// Recent events on the bus sent to the dashboard
+const EVENTS_SHOWN: usize = 30;
+
+/// A running plugin process and its relay counters
+#[derive(Debug, Default)]
+pub struct Process {
+    pub plugin: String,
+    pub pid: u32,
+    pub active: f64,
+    pub connections: f64,
+    pub request_bytes: f64,
+    pub response_bytes: f64,
+}
+
+/// Splits `name{label="value",...} value` into its parts
+fn parse_sample(line: &str) -> Option<(&str, BTreeMap<String, String>, f64)> {
+    let (series, value) = line.rsplit_once(' ')?;
+    let value = value.parse().ok()?;
+    let Some((name, rest)) = series.split_once('{') else {
+        return Some((series, BTreeMap::new(), value));
+    };
+    let mut labels = BTreeMap::new();
+    let mut chars = rest.trim_end_matches('}').chars();
+    loop {
+        let label: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        if label.is_empty() || chars.next() != Some('"') {
+            break;
+        }
+        let mut value = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some(c) => value.push(c),
+                    None => break,
+                },
+                '"' => break,
+                c => value.push(c),
+            }
+        }
+        labels.insert(label.trim_start_matches(',').to_string(), value);
+    }
+    Some((name, labels, value))
+}
+
+/// Every running plugin process, by pid, with the counters of those publishing metrics
+pub fn processes() -> BTreeMap<u32, Process> {
+    let mut processes: BTreeMap<u32, Process> = processes::running()
+        .into_iter()
+        .map(|process| {
+            let running = Process {
+                plugin: process.plugin,
+                pid: process.pid,
+                ..Process::default()
+            };
+            (process.pid, running)
+        })
+        .collect();
+    for line in metrics::gather().lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((name, labels, value)) = parse_sample(line) else {
+            continue;
+        };
+        let Some(pid) = labels.get("pid").and_then(|pid| pid.parse().ok()) else {
+            continue;
+        };
+        let process = processes.entry(pid).or_insert_with(|| Process {
+            plugin: labels.get("plugin").cloned().unwrap_or_default(),
+            pid,
+            ..Process::default()
+        });
+        match name {
+            "proxy_relay_connections_active" => process.active += value,
+            "proxy_relay_connections_total" => process.connections += value,
+            "proxy_relay_bytes_total" => match labels.get("direction").map(String::as_str) {
+                Some("request") => process.request_bytes += value,
+                Some("response") => process.response_bytes += value,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    processes
+}
+
+/// The forwards of k8s_port_forward processes that are still running
+fn forwards(processes: &BTreeMap<u32, Process>) -> Vec<Value> {
+    let mut forwards = Vec::new();
+    let entries = plugin_api::plugin_state_dir(PORT_FORWARD_PLUGIN)
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .flatten();
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with("forwards-") || !name.ends_with(".json") {
+            continue;
+        }
+        let Some(state) = fs::read_to_string(entry.path())
+            .ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        else {
+            continue;
+        };
+        let pid = state["pid"].as_u64().unwrap_or_default();
+        if !processes.contains_key(&(pid as u32)) {
+            continue;
+        }
+        for forward in state["forwards"].as_array().into_iter().flatten() {
+            let mut forward = forward.clone();
+            forward["pid"] = json!(pid);
+            forwards.push(forward);
+        }
+    }
+    forwards
+}
+
+/// Saved chat sessions, most recently updated first
+fn sessions() -> Vec<Value> {
+    let mut sessions = Vec::new();
+    let entries = plugin_api::plugin_state_dir(CHAT_PLUGIN)
+        .and_then(|dir| fs::read_dir(dir.join("sessions")).ok())
+        .into_iter()
+        .flatten()
+        .flatten();
+    for entry in entries {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(session) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        else {
+            continue;
+        };
+        let messages = session["messages"].as_array().cloned().unwrap_or_default();
+        let last = messages
+            .last()
+            .and_then(|message| message["content"].as_str())
+            .map(|content| content.chars().take(120).collect::<String>());
+        sessions.push(json!({
+            "name": session["name"],
+            "title": session["title"],
+            "model": session["model"],
+            "updated_at": session["updated_at"],
+            "messages": messages.len(),
+            "last": last,
+        }));
+    }
+    sessions.sort_by(|a, b| {
+        let updated = |session: &Value| session["updated_at"].as_str().unwrap_or("").to_string();
+        updated(b).cmp(&updated(a))
+    });
+    sessions
+}
+
+/// Everything the dashboard shows, as sent to the browser
+pub fn snapshot(controls: bool) -> Value {
+    let processes = processes();
+    let messages: Vec<Value> = traffic::recent_messages(MESSAGES_SHOWN)
+        .into_iter()
+        .map(|message| {
+            let plugin = processes
+                .get(&message.pid)
+                .map(|process| process.plugin.as_str());
+            json!({
+                "pid": message.pid,
+                "plugin": plugin,
+                "timestamp": message.timestamp,
+                "direction": message.direction,
+                "protocol": message.protocol,
+                "summary": message.summary,
+            })
+        })
+        .collect();
+    json!({
+        "controls": controls,
+        "forwards": forwards(&processes),
+        "processes": processes.values().map(|process| json!({
+            "plugin": process.plugin,
+            "pid": process.pid,
+            "active": process.active,
+            "connections": process.connections,
+            "request_bytes": process.request_bytes,
+            "response_bytes": process.response_bytes,
+        })).collect::<Vec<_>>(),
+        "messages": messages,
+        "events": events::recent(EVENTS_SHOWN).into_iter().map(|received| json!({
+            "time": received.time,
+            "pid": received.pid,
+            "event": received.event.to_string(),
+        })).collect::<Vec<_>>(),
+        "sessions": sessions(),
+    })
+}
@@ -0,0 +1,212 @@
+// Deliveries: webhook requests received through the relay. Each one is saved in the
+// state directory before it is forwarded, so it can be listed and replayed later.
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use plugin_api::traffic::{log_message, Protocol};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Older deliveries are removed past this many
+const KEPT: usize = 500;
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(30);
+/// Set by the relay or per hop, not part of the delivery
+const SKIPPED_HEADERS: &[&str] = &[
+    "host",
+    "content-length",
+    "connection",
+    "transfer-encoding",
+    "accept-encoding",
+];
+/// Headers naming the event, by provider
+const EVENT_HEADERS: &[&str] = &["x-github-event", "x-gitlab-event", "x-event-key"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: String,
+    pub received_at: String,
+    pub headers: Vec<(String, String)>,
+    /// Query string, without the '?'
+    pub query: String,
+    /// Base64 of the body
+    pub body: String,
+}
+
+/// Percent-encodes a query string key or value
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+impl Delivery {
+    /// A delivery from the data of a relay event. The relay puts the headers at the top
+    /// level next to `body` (parsed when it was JSON), `query` and `timestamp`; gosmee
+    /// adds the raw body as base64 in `bodyB`.
+    pub fn from_event(data: &str) -> Result<Self> {
+        let event: serde_json::Map<String, Value> =
+            serde_json::from_str(data).context("Unreadable delivery from the relay")?;
+        let mut headers = Vec::new();
+        for (key, value) in &event {
+            if matches!(key.as_str(), "body" | "bodyB" | "query" | "timestamp")
+                || SKIPPED_HEADERS.contains(&key.to_lowercase().as_str())
+            {
+                continue;
+            }
+            let value = match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            headers.push((key.clone(), value));
+        }
+        let query = match event.get("query") {
+            Some(Value::Object(query)) => query
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    format!("{}={}", encode(key), encode(&value))
+                })
+                .collect::<Vec<_>>()
+                .join("&"),
+            _ => String::new(),
+        };
+        let body = match (event.get("bodyB"), event.get("body")) {
+            (Some(Value::String(raw)), _) => raw.clone(),
+            (_, Some(Value::String(text))) => STANDARD.encode(text),
+            (_, None | Some(Value::Null)) => String::new(),
+            (_, Some(parsed)) => STANDARD.encode(serde_json::to_vec(parsed)?),
+        };
+        let now = Utc::now();
+        Ok(Delivery {
+            // Sorts in the order received
+            id: now.format("%Y%m%d-%H%M%S-%3f").to_string(),
+            received_at: now.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            headers,
+            query,
+            body,
+        })
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        STANDARD.decode(&self.body).unwrap_or_default()
+    }
+
+    /// The provider's name for the event, e.g. "push"
+    pub fn event(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| EVENT_HEADERS.contains(&key.to_lowercase().as_str()))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The request to send to the local port
+    fn request(&self, port: u16, path: &str) -> Vec<u8> {
+        let body = self.body();
+        let target = if self.query.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}?{}", path, self.query)
+        };
+        let mut head = format!("POST {} HTTP/1.1\r\nHost: localhost:{}\r\n", target, port);
+        for (key, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        ));
+        let mut request = head.into_bytes();
+        request.extend_from_slice(&body);
+        request
+    }
+}
+
+pub fn save(dir: &Path, delivery: &Delivery) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(
+        dir.join(format!("{}.json", delivery.id)),
+        serde_json::to_vec_pretty(delivery)?,
+    )?;
+    let mut saved = ids(dir)?;
+    if saved.len() > KEPT {
+        for id in saved.drain(..saved.len() - KEPT) {
+            let _ = fs::remove_file(dir.join(format!("{}.json", id)));
+        }
+    }
+    Ok(())
+}
+
+/// The ids of the saved deliveries, oldest first
+pub fn ids(dir: &Path) -> Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut ids: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .map(str::to_string)
+        })
+        .collect();
+    ids.sort();
+    Ok(ids)
+}
+
+pub fn load(dir: &Path, id: &str) -> Result<Delivery> {
+    let path = dir.join(format!("{}.json", id));
+    let content = fs::read_to_string(&path)
+        .map_err(|_| anyhow!("No delivery {}; see `proxy webhook_relay list`", id))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Sends the delivery to the local port and logs both sides; returns the status line
+pub async fn forward(delivery: &Delivery, port: u16, path: &str) -> Result<String> {
+    let tag = format!("[{}]", delivery.id);
+    let request = delivery.request(port, path);
+    log_message(&format!("{} → REQUEST", tag), &Protocol::Http, &request);
+
+    let started = Instant::now();
+    let exchange = async {
+        let mut local = TcpStream::connect(("127.0.0.1", port))
+            .await
+            .map_err(|e| anyhow!("Nothing listening on localhost:{}: {}", port, e))?;
+        local.write_all(&request).await?;
+        let mut response = Vec::new();
+        local.read_to_end(&mut response).await?;
+        Ok::<_, anyhow::Error>(response)
+    };
+    let response = tokio::time::timeout(FORWARD_TIMEOUT, exchange)
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "localhost:{} didn't answer in {}s",
+                port,
+                FORWARD_TIMEOUT.as_secs()
+            )
+        })??;
+    log_message(&format!("{} ← RESPONSE", tag), &Protocol::Http, &response);
+
+    let status = String::from_utf8_lossy(&response)
+        .lines()
+        .next()
+        .and_then(|line| line.split_once(' ').map(|(_, status)| status.to_string()))
+        .unwrap_or_else(|| "no response".to_string());
+    Ok(format!("{} in {}ms", status, started.elapsed().as_millis()))
+}
@@ -0,0 +1,344 @@
+// Webhooks from SaaS providers to code running on this machine: the plugin subscribes to
+// a channel on a relay (smee.io by default, or a self-hosted one), whose public HTTPS URL
+// is what the provider is configured with. Every delivery is logged with the HTTP
+// decoder, forwarded to the local port and saved, so it can be replayed while iterating
+// on the handler without triggering the provider again.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+mod delivery;
+mod relay;
+
+use delivery::Delivery;
+
+const PLUGIN_NAME: &str = "webhook_relay";
+const DEFAULT_RELAY: &str = "https://smee.io";
+const DEFAULT_LIST_LIMIT: usize = 20;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A subscription that stayed up this long starts the backoff over
+const STABLE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WebhookRelayConfig {
+    /// Relay to create channels on (default https://smee.io)
+    pub relay: Option<String>,
+    /// Channel URL to use instead of the one created on first run
+    pub channel: Option<String>,
+    /// Local port deliveries are forwarded to
+    pub port: Option<u16>,
+    /// Path they are forwarded to (default /)
+    pub path: Option<String>,
+}
+
+pub struct WebhookRelayPlugin;
+
+impl WebhookRelayPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# Webhook Relay Configuration
+relay = "https://smee.io"  # or a self-hosted smee/gosmee server
+# channel = "https://smee.io/aBcD1234"  # default: created on first run and kept
+port = 3000
+path = "/webhooks/github"
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<WebhookRelayConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: WebhookRelayConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                // Everything can be given on the command line
+                Ok(WebhookRelayConfig::default())
+            }
+        }
+        None => Ok(WebhookRelayConfig::default()),
+    }
+}
+
+fn state_dir() -> Result<PathBuf> {
+    plugin_api::plugin_state_dir(PLUGIN_NAME)
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))
+}
+
+fn deliveries_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("deliveries"))
+}
+
+/// The channel to subscribe to. One created by the plugin is kept in the state
+/// directory, so the URL configured at the provider stays the same between runs.
+async fn channel(config: &WebhookRelayConfig, renew: bool) -> Result<String> {
+    if let Some(channel) = &config.channel {
+        return Ok(channel.clone());
+    }
+    let saved = state_dir()?.join("channel");
+    if !renew {
+        if let Ok(channel) = fs::read_to_string(&saved) {
+            return Ok(channel.trim().to_string());
+        }
+    }
+    let relay = config.relay.as_deref().unwrap_or(DEFAULT_RELAY);
+    let channel = relay::new_channel(relay).await?;
+    fs::create_dir_all(state_dir()?)?;
+    fs::write(&saved, &channel)?;
+    println!("🆕 Created channel {}", channel);
+    Ok(channel)
+}
+
+fn target(config: &WebhookRelayConfig, matches: &ArgMatches) -> Result<(u16, String)> {
+    let port = matches
+        .get_one::<u16>("port")
+        .copied()
+        .or(config.port)
+        .ok_or_else(|| anyhow!("No local port given and none in webhook_relay.conf"))?;
+    let path = matches
+        .get_one::<String>("path")
+        .cloned()
+        .or_else(|| config.path.clone())
+        .unwrap_or_else(|| "/".to_string());
+    Ok((port, path))
+}
+
+async fn handle(data: &str, port: u16, path: &str) -> Result<()> {
+    let delivery = Delivery::from_event(data)?;
+    println!(
+        "📬 [{}] Delivery{} ({} bytes)",
+        delivery.id,
+        delivery
+            .event()
+            .map(|event| format!(" '{}'", event))
+            .unwrap_or_default(),
+        delivery.body().len()
+    );
+    delivery::save(&deliveries_dir()?, &delivery)?;
+    match delivery::forward(&delivery, port, path).await {
+        Ok(status) => println!("✅ [{}] {}", delivery.id, status),
+        Err(e) => eprintln!(
+            "❌ [{}] {}; replay it with `proxy webhook_relay replay {}`",
+            delivery.id, e, delivery.id
+        ),
+    }
+    Ok(())
+}
+
+/// Forwards deliveries until the relay ends the subscription
+async fn subscribe(channel: &str, port: u16, path: &str) -> Result<()> {
+    let mut events = relay::Events::subscribe(channel).await?;
+    loop {
+        let event = events.next().await?;
+        match event.name.as_str() {
+            "ready" => println!("✅ Connected to the relay, waiting for deliveries"),
+            "ping" => {}
+            _ => {
+                if let Err(e) = handle(&event.data, port, path).await {
+                    eprintln!("❌ {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Forwards the channel's deliveries until Ctrl+C, subscribing again when the stream ends
+async fn listen(channel: String, port: u16, path: String) -> Result<()> {
+    ctrlc::set_handler(move || {
+        println!("\n👋 Shutting down...");
+        std::process::exit(0);
+    })?;
+    println!("🌍 Public URL: {}", channel);
+    println!("🎯 Forwarding to http://localhost:{}{}", port, path);
+
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        if let Err(e) = subscribe(&channel, port, &path).await {
+            eprintln!("❌ {}", e);
+        }
+        if started.elapsed() >= STABLE {
+            backoff = MIN_BACKOFF;
+        }
+        println!("🔄 Reconnecting in {}s", backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+fn list(limit: usize) -> Result<()> {
+    let dir = deliveries_dir()?;
+    let ids = delivery::ids(&dir)?;
+    if ids.is_empty() {
+        println!("📭 No deliveries yet");
+        return Ok(());
+    }
+    println!(
+        "{:<22} {:<24} {:<24} {:>8}",
+        "ID", "RECEIVED", "EVENT", "BYTES"
+    );
+    for id in ids.iter().rev().take(limit) {
+        let delivery = delivery::load(&dir, id)?;
+        println!(
+            "{:<22} {:<24} {:<24} {:>8}",
+            delivery.id,
+            delivery.received_at,
+            delivery.event().unwrap_or("-"),
+            delivery.body().len()
+        );
+    }
+    Ok(())
+}
+
+async fn execute(matches: &ArgMatches, mut config: WebhookRelayConfig) -> Result<()> {
+    match matches.subcommand() {
+        Some(("listen", sub)) => {
+            if let Some(relay) = sub.get_one::<String>("relay") {
+                config.relay = Some(relay.clone());
+            }
+            if let Some(channel) = sub.get_one::<String>("channel") {
+                config.channel = Some(channel.clone());
+            }
+            let (port, path) = target(&config, sub)?;
+            let channel = channel(&config, sub.get_flag("new-channel")).await?;
+            listen(channel, port, path).await
+        }
+        Some(("list", sub)) => list(
+            sub.get_one::<usize>("limit")
+                .copied()
+                .unwrap_or(DEFAULT_LIST_LIMIT),
+        ),
+        Some(("replay", sub)) => {
+            let (port, path) = target(&config, sub)?;
+            let dir = deliveries_dir()?;
+            let id = match sub.get_one::<String>("id") {
+                Some(id) => id.clone(),
+                None => delivery::ids(&dir)?
+                    .pop()
+                    .ok_or_else(|| anyhow!("No deliveries to replay"))?,
+            };
+            let delivery = delivery::load(&dir, &id)?;
+            println!("🔁 [{}] Replaying to http://localhost:{}{}", id, port, path);
+            let status = delivery::forward(&delivery, port, &path).await?;
+            println!("✅ [{}] {}", id, status);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn port_arg() -> Arg {
+    Arg::new("port")
+        .value_name("LOCAL_PORT")
+        .value_parser(clap::value_parser!(u16))
+        .help("Local port to forward to (default: port from config)")
+}
+
+fn path_arg() -> Arg {
+    Arg::new("path")
+        .long("path")
+        .value_name("PATH")
+        .help("Path to forward to (default /)")
+}
+
+impl Plugin for WebhookRelayPlugin {
+    fn name(&self) -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "Public webhook URL relayed to a local port, with replay"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Receive webhooks on a public URL and forward them to a local port")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("listen")
+                    .about("Forward the channel's deliveries to a local port until Ctrl+C")
+                    .arg(port_arg())
+                    .arg(path_arg())
+                    .arg(
+                        Arg::new("relay")
+                            .long("relay")
+                            .value_name("URL")
+                            .help("Relay to create the channel on (default https://smee.io)"),
+                    )
+                    .arg(
+                        Arg::new("channel")
+                            .long("channel")
+                            .value_name("URL")
+                            .help("Use this channel URL"),
+                    )
+                    .arg(
+                        Arg::new("new-channel")
+                            .long("new-channel")
+                            .action(ArgAction::SetTrue)
+                            .help("Create a new channel instead of the saved one"),
+                    ),
+            )
+            .subcommand(
+                Command::new("list").about("Show the saved deliveries").arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Show the N most recent (default 20)"),
+                ),
+            )
+            .subcommand(
+                Command::new("replay")
+                    .about("Send a saved delivery to the local port again")
+                    .arg(
+                        Arg::new("id")
+                            .value_name("ID")
+                            .help("Delivery to replay (default: the latest)"),
+                    )
+                    .arg(port_arg().long("port").short('p'))
+                    .arg(path_arg()),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(WebhookRelayPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<WebhookRelayConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        if let Err(e) = rt.block_on(execute(matches, config)) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(WebhookRelayPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,104 @@
+// The relay side, in the protocol of smee.io, which self-hosted servers such as smee or
+// gosmee speak too: a channel is a public HTTPS URL, and everything POSTed to it is
+// pushed to the channel's subscribers as a server-sent event.
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use std::collections::VecDeque;
+
+/// One server-sent event
+pub struct Event {
+    pub name: String,
+    pub data: String,
+}
+
+/// Asks the relay for a new channel; /new redirects to it
+pub async fn new_channel(relay: &str) -> Result<String> {
+    let base = Url::parse(relay)?;
+    let client = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .build()?;
+    let response = client.get(base.join("new")?).send().await?;
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "{} didn't hand out a channel ({}); is it a smee-compatible relay?",
+                relay,
+                response.status()
+            )
+        })?;
+    Ok(base.join(location)?.to_string())
+}
+
+/// Takes the complete events off the front of `pending`
+fn take_events(pending: &mut Vec<u8>) -> Vec<Event> {
+    let mut events = Vec::new();
+    while let Some(end) = pending.windows(2).position(|pair| pair == b"\n\n") {
+        let block: Vec<u8> = pending.drain(..end + 2).collect();
+        let mut event = Event {
+            name: "message".to_string(),
+            data: String::new(),
+        };
+        for line in String::from_utf8_lossy(&block).lines() {
+            if let Some(name) = line.strip_prefix("event:") {
+                event.name = name.trim().to_string();
+            } else if let Some(data) = line.strip_prefix("data:") {
+                if !event.data.is_empty() {
+                    event.data.push('\n');
+                }
+                event.data.push_str(data.strip_prefix(' ').unwrap_or(data));
+            }
+        }
+        events.push(event);
+    }
+    events
+}
+
+/// The events of a channel subscription
+pub struct Events {
+    stream: BoxStream<'static, reqwest::Result<Bytes>>,
+    pending: Vec<u8>,
+    ready: VecDeque<Event>,
+}
+
+impl Events {
+    pub async fn subscribe(channel: &str) -> Result<Self> {
+        let response = reqwest::Client::new()
+            .get(channel)
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("The relay answered {}", response.status()));
+        }
+        Ok(Events {
+            stream: response.bytes_stream().boxed(),
+            pending: Vec::new(),
+            ready: VecDeque::new(),
+        })
+    }
+
+    /// The next event; an error once the relay ends the stream
+    pub async fn next(&mut self) -> Result<Event> {
+        loop {
+            if let Some(event) = self.ready.pop_front() {
+                return Ok(event);
+            }
+            let chunk = self
+                .stream
+                .next()
+                .await
+                .ok_or_else(|| anyhow!("The relay closed the stream"))??;
+            // Line ends may be CRLF; the JSON in the data lines has no raw CRs
+            self.pending
+                .extend(chunk.iter().filter(|&&byte| byte != b'\r'));
+            self.ready.extend(take_events(&mut self.pending));
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// Dev access to private ranges (VPC subnets, office networks) without a system VPN client:
+// each configured tunnel runs boringtun, the user-space WireGuard implementation, as
+// boringtun-cli. The device gets its keys and peers over the WireGuard UAPI socket, its
+// address, and routes for the listed CIDRs. Everything is removed again when the plugin
+// exits. Creating TUN devices and routes needs root, so `up` runs under sudo.
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, Command};
+use plugin_api::Plugin;
+use serde::Deserialize;
+use tokio::runtime::Runtime;
+
+#[cfg(unix)]
+mod net;
+#[cfg(unix)]
+mod tunnel;
+#[cfg(unix)]
+mod uapi;
+
+const PLUGIN_NAME: &str = "wireguard";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WireguardConfig {
+    #[serde(default)]
+    pub tunnel: Vec<TunnelConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TunnelConfig {
+    pub name: String,
+    /// Device name (default: "utun" on macOS, which takes a free utunN, and wg-NAME
+    /// elsewhere)
+    pub interface: Option<String>,
+    /// Base64 private key, or a secret reference such as "secret:wg-staging"
+    pub private_key: String,
+    /// This end's address inside the tunnel, e.g. "10.100.0.2/32"
+    pub address: String,
+    pub mtu: Option<u16>,
+    /// CIDRs routed into the tunnel (default: the peers' allowed_ips)
+    #[serde(default)]
+    pub routes: Vec<String>,
+    #[serde(default)]
+    pub peer: Vec<Peer>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Peer {
+    pub public_key: String,
+    /// Secret reference to a preshared key
+    pub preshared_key: Option<String>,
+    /// host:port of the peer
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+    /// Seconds between keepalives, for peers behind NAT
+    pub persistent_keepalive: Option<u16>,
+}
+
+impl TunnelConfig {
+    pub fn routes(&self) -> Vec<String> {
+        if !self.routes.is_empty() {
+            return self.routes.clone();
+        }
+        self.peer
+            .iter()
+            .flat_map(|peer| peer.allowed_ips.iter().cloned())
+            .collect()
+    }
+}
+
+pub struct WireguardPlugin;
+
+impl WireguardPlugin {
+    pub fn sample_config() -> &'static str {
+        r#"# WireGuard Configuration
+[[tunnel]]
+name = "staging-vpc"
+private_key = "secret:wg-staging"  # base64, as from `wg genkey`
+address = "10.100.0.2/32"
+routes = ["10.20.0.0/16", "10.21.0.0/16"]  # default: the peers' allowed_ips
+
+[[tunnel.peer]]
+public_key = "q4EvWq1Z5YpGNYAMVp8pN0uH9nVtP5Xl2cG0qgNhHkA="
+endpoint = "vpn.staging.example.com:51820"
+allowed_ips = ["10.20.0.0/16", "10.21.0.0/16", "10.100.0.1/32"]
+persistent_keepalive = 25
+"#
+    }
+}
+
+fn load_config(plugin_name: &str) -> Result<WireguardConfig> {
+    match plugin_api::plugin_config_path(plugin_name) {
+        Some(config_path) => {
+            if plugin_api::config_exists(&config_path) {
+                let content = plugin_api::read_config(&config_path)?;
+                let config: WireguardConfig = toml::from_str(&content)?;
+                Ok(config)
+            } else {
+                println!("⚠️  Config file not found, using defaults.");
+                println!("💡 Create config at: {}", config_path.display());
+                println!("📝 Sample config:\n{}", WireguardPlugin::sample_config());
+                Ok(WireguardConfig::default())
+            }
+        }
+        None => {
+            println!("⚠️  Could not determine config path, using defaults.");
+            Ok(WireguardConfig::default())
+        }
+    }
+}
+
+/// The configured tunnels, or only the named one
+fn select(config: WireguardConfig, name: Option<&String>) -> Result<Vec<TunnelConfig>> {
+    let tunnels: Vec<TunnelConfig> = config
+        .tunnel
+        .into_iter()
+        .filter(|tunnel| name.is_none_or(|name| &tunnel.name == name))
+        .collect();
+    match name {
+        Some(name) if tunnels.is_empty() => Err(anyhow!("No tunnel named '{}'", name)),
+        _ if tunnels.is_empty() => Err(anyhow!("No tunnels configured")),
+        _ => Ok(tunnels),
+    }
+}
+
+#[cfg(unix)]
+async fn execute(matches: &ArgMatches, config: WireguardConfig) -> Result<()> {
+    match matches.subcommand() {
+        Some(("up", sub)) => {
+            let tunnels = select(config, sub.get_one::<String>("name"))?;
+            for tunnel in &tunnels {
+                if tunnel.peer.is_empty() {
+                    return Err(anyhow!("Tunnel '{}' has no peers", tunnel.name));
+                }
+            }
+            tunnel::up(tunnels).await
+        }
+        Some(("status", _)) => tunnel::status(&select(config, None)?).await,
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+async fn execute(_matches: &ArgMatches, _config: WireguardConfig) -> Result<()> {
+    Err(anyhow!(
+        "boringtun tunnels are only supported on Linux and macOS"
+    ))
+}
+
+impl Plugin for WireguardPlugin {
+    fn name(&self) -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &'static str {
+        "User-space WireGuard tunnels with routes to private ranges"
+    }
+
+    fn subcommand(&self) -> Command {
+        Command::new(self.name())
+            .about("Bring up WireGuard tunnels through boringtun and route private ranges")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("up")
+                    .about("Bring up the configured tunnels until Ctrl+C (needs sudo)")
+                    .arg(
+                        Arg::new("name")
+                            .long("name")
+                            .short('n')
+                            .value_name("NAME")
+                            .help("Only bring up the tunnel with this name"),
+                    ),
+            )
+            .subcommand(
+                Command::new("status").about("Show the peers and handshakes of running tunnels"),
+            )
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        Some(WireguardPlugin::sample_config())
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<plugin_api::config::ConfigError>> {
+        plugin_api::config::validate_toml::<WireguardConfig>(config)
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        let config = match load_config(self.name()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("❌ Failed to load config: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let rt = Runtime::new().expect("Failed to create Tokio runtime");
+        if let Err(e) = rt.block_on(execute(matches, config)) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn create_plugin() -> Box<dyn Plugin> {
+    Box::new(WireguardPlugin)
+}
+
+#[no_mangle]
+pub static PLUGIN_API_VERSION: u32 = plugin_api::PLUGIN_API_VERSION;
@@ -0,0 +1,86 @@
+// Interface addresses and routes, through the system tools: `ip` on Linux, `ifconfig` and
+// `route` on macOS. These run synchronously so the Ctrl+C handler can undo them too.
+use anyhow::{anyhow, Context, Result};
+use std::process::Command;
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            stderr.trim()
+        ));
+    }
+    Ok(())
+}
+
+fn is_ipv6(cidr: &str) -> bool {
+    cidr.contains(':')
+}
+
+/// Gives the interface its address inside the tunnel and brings it up
+#[cfg(target_os = "macos")]
+pub fn configure(interface: &str, address: &str, mtu: u16) -> Result<()> {
+    if is_ipv6(address) {
+        run("ifconfig", &[interface, "inet6", address, "alias"])?;
+    } else {
+        // Point-to-point: the destination is the address itself, as wg-quick does
+        let host = address.split('/').next().unwrap_or(address);
+        run("ifconfig", &[interface, "inet", address, host, "alias"])?;
+    }
+    run("ifconfig", &[interface, "mtu", &mtu.to_string(), "up"])
+}
+
+/// Gives the interface its address inside the tunnel and brings it up
+#[cfg(not(target_os = "macos"))]
+pub fn configure(interface: &str, address: &str, mtu: u16) -> Result<()> {
+    run("ip", &["address", "add", address, "dev", interface])?;
+    run(
+        "ip",
+        &[
+            "link",
+            "set",
+            "mtu",
+            &mtu.to_string(),
+            "up",
+            "dev",
+            interface,
+        ],
+    )
+}
+
+#[cfg(target_os = "macos")]
+pub fn add_route(interface: &str, cidr: &str) -> Result<()> {
+    let family = if is_ipv6(cidr) { "-inet6" } else { "-inet" };
+    run(
+        "route",
+        &["-q", "-n", "add", family, cidr, "-interface", interface],
+    )
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn add_route(interface: &str, cidr: &str) -> Result<()> {
+    let family = if is_ipv6(cidr) { "-6" } else { "-4" };
+    run("ip", &[family, "route", "add", cidr, "dev", interface])
+}
+
+#[cfg(target_os = "macos")]
+pub fn delete_route(interface: &str, cidr: &str) -> Result<()> {
+    let family = if is_ipv6(cidr) { "-inet6" } else { "-inet" };
+    run(
+        "route",
+        &["-q", "-n", "delete", family, cidr, "-interface", interface],
+    )
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn delete_route(interface: &str, cidr: &str) -> Result<()> {
+    let family = if is_ipv6(cidr) { "-6" } else { "-4" };
+    run("ip", &[family, "route", "del", cidr, "dev", interface])
+}
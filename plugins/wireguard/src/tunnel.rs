@@ -0,0 +1,354 @@
+// Bringing tunnels up and tearing them down. Each tunnel is a boringtun-cli process in the
+// foreground, which owns the TUN device and removes it with its socket when it exits.
+// What has to be undone is kept in a registry, so both a tunnel that fails and the Ctrl+C
+// handler delete the routes before stopping the process.
+use crate::{net, uapi, Peer, TunnelConfig, PLUGIN_NAME};
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::{Child, Command};
+
+pub const BINARY: &str = "boringtun-cli";
+const DEFAULT_MTU: u16 = 1420;
+/// How long boringtun-cli gets to create the device and its socket
+const STARTUP: Duration = Duration::from_secs(10);
+/// WireGuard rekeys every two minutes, so a peer quiet for longer than this is gone
+const HANDSHAKE_TIMEOUT: u64 = 180;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A tunnel that stayed up this long starts the backoff over
+const STABLE: Duration = Duration::from_secs(60);
+
+/// A running tunnel and what it changed on the system
+struct Active {
+    name: String,
+    interface: String,
+    pid: u32,
+    routes: Vec<String>,
+}
+
+impl Active {
+    /// Deletes the routes, then stops boringtun-cli unless it has already exited
+    fn teardown(&self, kill: bool) {
+        for cidr in self.routes.iter().rev() {
+            let _ = net::delete_route(&self.interface, cidr);
+        }
+        if kill {
+            unsafe {
+                libc::kill(self.pid as i32, libc::SIGTERM);
+            }
+        }
+    }
+}
+
+type Registry = Arc<Mutex<Vec<Active>>>;
+
+fn release(registry: &Registry, name: &str, kill: bool) {
+    let mut active = registry.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(index) = active.iter().position(|tunnel| tunnel.name == name) {
+        active.remove(index).teardown(kill);
+    }
+}
+
+pub fn require_root() -> Result<()> {
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(anyhow!(
+            "TUN devices, routes and the WireGuard sockets need root; run with sudo"
+        ));
+    }
+    Ok(())
+}
+
+fn resolve(reference: &str) -> Result<String> {
+    plugin_api::resolve_secret(reference).map_err(|e| anyhow!(e))
+}
+
+fn state_dir() -> Result<PathBuf> {
+    let dir = plugin_api::plugin_state_dir(PLUGIN_NAME)
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// The interface to ask boringtun-cli for. macOS only allows utunN names, and "utun"
+/// takes the next free one.
+fn requested_interface(tunnel: &TunnelConfig) -> String {
+    match &tunnel.interface {
+        Some(interface) => interface.clone(),
+        None if cfg!(target_os = "macos") => "utun".to_string(),
+        // Linux interface names are at most 15 bytes
+        None => format!("wg-{}", tunnel.name).chars().take(15).collect(),
+    }
+}
+
+/// The device a tunnel got, as boringtun-cli wrote it to the name file
+fn interface_of(tunnel: &TunnelConfig) -> String {
+    plugin_api::plugin_state_dir(PLUGIN_NAME)
+        .and_then(|dir| fs::read_to_string(dir.join(format!("{}.interface", tunnel.name))).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| requested_interface(tunnel))
+}
+
+async fn peer_settings(peer: &Peer) -> Result<uapi::PeerSettings> {
+    let preshared_key = peer.preshared_key.as_deref().map(resolve).transpose()?;
+    // The device wants an address; IPv4 is preferred like most clients do
+    let endpoint = match &peer.endpoint {
+        Some(endpoint) => {
+            let addrs: Vec<_> = tokio::net::lookup_host(endpoint)
+                .await
+                .with_context(|| format!("Failed to resolve {}", endpoint))?
+                .collect();
+            let addr = addrs
+                .iter()
+                .find(|addr| addr.is_ipv4())
+                .or(addrs.first())
+                .copied()
+                .ok_or_else(|| anyhow!("{} has no addresses", endpoint))?;
+            Some(addr)
+        }
+        None => None,
+    };
+    Ok(uapi::PeerSettings {
+        public_key: peer.public_key.clone(),
+        preshared_key,
+        endpoint,
+        allowed_ips: peer.allowed_ips.clone(),
+        persistent_keepalive: peer.persistent_keepalive,
+    })
+}
+
+/// Starts boringtun-cli and configures the device, its address and routes
+async fn bring_up(tunnel: &TunnelConfig, registry: &Registry) -> Result<(Child, String)> {
+    let private_key = resolve(&tunnel.private_key)?;
+    let mut peers = Vec::new();
+    for peer in &tunnel.peer {
+        peers.push(peer_settings(peer).await?);
+    }
+
+    let dir = state_dir()?;
+    let name_file = dir.join(format!("{}.interface", tunnel.name));
+    let _ = fs::remove_file(&name_file);
+    let log_path = dir.join(format!("{}.log", tunnel.name));
+    let log = fs::File::create(&log_path)?;
+    let requested = requested_interface(tunnel);
+    // A socket left behind by a crash would look like the new device is ready
+    let _ = fs::remove_file(uapi::socket_path(&requested));
+    let mut child = Command::new(BINARY)
+        .arg(&requested)
+        .arg("--foreground")
+        .arg("--disable-drop-privileges")
+        .env("WG_TUN_NAME_FILE", &name_file)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to run boringtun-cli; is it installed?")?;
+
+    // The socket is named after the device, which on macOS is known once it exists
+    let started = Instant::now();
+    let interface = loop {
+        let interface = fs::read_to_string(&name_file)
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|_| requested.clone());
+        if uapi::socket_path(&interface).exists() {
+            break interface;
+        }
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow!(
+                "boringtun-cli exited ({}), see {}",
+                status,
+                log_path.display()
+            ));
+        }
+        if started.elapsed() > STARTUP {
+            return Err(anyhow!(
+                "boringtun-cli didn't create {}, see {}",
+                requested,
+                log_path.display()
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    };
+    registry
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Active {
+            name: tunnel.name.clone(),
+            interface: interface.clone(),
+            pid: child.id().unwrap_or_default(),
+            routes: Vec::new(),
+        });
+
+    let setup = async {
+        uapi::configure(&interface, &private_key, &peers).await?;
+        net::configure(
+            &interface,
+            &tunnel.address,
+            tunnel.mtu.unwrap_or(DEFAULT_MTU),
+        )?;
+        for cidr in tunnel.routes() {
+            net::add_route(&interface, &cidr)?;
+            let mut active = registry.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(active) = active.iter_mut().find(|a| a.name == tunnel.name) {
+                active.routes.push(cidr);
+            }
+        }
+        Ok::<(), anyhow::Error>(())
+    };
+    if let Err(e) = setup.await {
+        release(registry, &tunnel.name, true);
+        return Err(e);
+    }
+    Ok((child, interface))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn short_key(key: &str) -> String {
+    format!("{}…", key.chars().take(8).collect::<String>())
+}
+
+fn size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GiB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1u64 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KiB", b as f64 / (1u64 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
+
+/// Reports peers as their handshakes start and stop
+async fn watch(name: &str, interface: &str) {
+    let mut connected: HashMap<String, bool> = HashMap::new();
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let Ok(peers) = uapi::status(interface).await else {
+            continue;
+        };
+        let now = now();
+        for peer in peers {
+            let fresh = peer
+                .last_handshake
+                .is_some_and(|at| now.saturating_sub(at) < HANDSHAKE_TIMEOUT);
+            let was = connected
+                .insert(peer.public_key.clone(), fresh)
+                .unwrap_or(false);
+            if fresh && !was {
+                println!(
+                    "🤝 [{}] Handshake with {} at {}",
+                    name,
+                    short_key(&peer.public_key),
+                    peer.endpoint.as_deref().unwrap_or("?")
+                );
+            } else if was && !fresh {
+                println!(
+                    "⚠️  [{}] No handshake with {} for {}s",
+                    name,
+                    short_key(&peer.public_key),
+                    HANDSHAKE_TIMEOUT
+                );
+            }
+        }
+    }
+}
+
+/// Keeps a tunnel up, bringing it up again when boringtun-cli exits
+async fn supervise(tunnel: TunnelConfig, registry: Registry) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        match bring_up(&tunnel, &registry).await {
+            Ok((mut child, interface)) => {
+                println!(
+                    "✅ [{}] {} is up as {}, routing {}",
+                    tunnel.name,
+                    interface,
+                    tunnel.address,
+                    tunnel.routes().join(", ")
+                );
+                tokio::select! {
+                    status = child.wait() => match status {
+                        Ok(status) => println!("⚠️  [{}] boringtun-cli exited ({})", tunnel.name, status),
+                        Err(e) => eprintln!("❌ [{}] boringtun-cli failed: {}", tunnel.name, e),
+                    },
+                    _ = watch(&tunnel.name, &interface) => {}
+                }
+                release(&registry, &tunnel.name, false);
+            }
+            Err(e) => eprintln!("❌ [{}] {}", tunnel.name, e),
+        }
+
+        if started.elapsed() >= STABLE {
+            backoff = MIN_BACKOFF;
+        }
+        println!("🔄 [{}] Restarting in {}s", tunnel.name, backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+pub async fn up(tunnels: Vec<TunnelConfig>) -> Result<()> {
+    require_root()?;
+    let registry: Registry = Arc::default();
+    let handler_registry = registry.clone();
+    ctrlc::set_handler(move || {
+        println!("\n👋 Shutting down...");
+        let mut active = handler_registry.lock().unwrap_or_else(|e| e.into_inner());
+        for tunnel in active.drain(..) {
+            println!("🧹 [{}] Removing {}", tunnel.name, tunnel.interface);
+            tunnel.teardown(true);
+        }
+        std::process::exit(0);
+    })?;
+
+    println!("🚀 Starting {} WireGuard tunnel(s)", tunnels.len());
+    let handles: Vec<_> = tunnels
+        .into_iter()
+        .map(|tunnel| tokio::spawn(supervise(tunnel, registry.clone())))
+        .collect();
+    for handle in handles {
+        let _ = handle.await;
+    }
+    Ok(())
+}
+
+pub async fn status(tunnels: &[TunnelConfig]) -> Result<()> {
+    require_root()?;
+    let now = now();
+    for tunnel in tunnels {
+        let interface = interface_of(tunnel);
+        if !uapi::socket_path(&interface).exists() {
+            println!("⏹️  {}  down", tunnel.name);
+            continue;
+        }
+        let peers = uapi::status(&interface).await?;
+        println!("🔐 {}  {}  {}", tunnel.name, interface, tunnel.address);
+        for peer in peers {
+            let handshake = match peer.last_handshake {
+                Some(at) => format!("handshake {}s ago", now.saturating_sub(at)),
+                None => "no handshake".to_string(),
+            };
+            println!(
+                "    {}  {}  {}  ↓ {}  ↑ {}",
+                short_key(&peer.public_key),
+                peer.endpoint.as_deref().unwrap_or("-"),
+                handshake,
+                size(peer.rx_bytes),
+                size(peer.tx_bytes)
+            );
+        }
+    }
+    Ok(())
+}
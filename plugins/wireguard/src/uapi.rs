@@ -0,0 +1,140 @@
+// The WireGuard cross-platform userspace API: boringtun listens on a Unix socket named
+// after the interface and takes `key=value` lines, a `set=1` or `get=1` request ended by
+// an empty line. Keys are hex on the socket and base64 everywhere else.
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+pub fn socket_path(interface: &str) -> PathBuf {
+    PathBuf::from("/var/run/wireguard").join(format!("{}.sock", interface))
+}
+
+fn key_hex(key: &str) -> Result<String> {
+    let bytes = STANDARD
+        .decode(key.trim())
+        .map_err(|e| anyhow!("Invalid key {}: {}", key, e))?;
+    if bytes.len() != 32 {
+        return Err(anyhow!("Invalid key {}: expected 32 bytes", key));
+    }
+    Ok(hex::encode(bytes))
+}
+
+fn key_base64(hex_key: &str) -> String {
+    hex::decode(hex_key)
+        .map(|bytes| STANDARD.encode(bytes))
+        .unwrap_or_else(|_| hex_key.to_string())
+}
+
+/// A peer as it is set on the device; keys are base64
+pub struct PeerSettings {
+    pub public_key: String,
+    pub preshared_key: Option<String>,
+    pub endpoint: Option<SocketAddr>,
+    pub allowed_ips: Vec<String>,
+    pub persistent_keepalive: Option<u16>,
+}
+
+/// What the device reports about a peer
+pub struct PeerStatus {
+    pub public_key: String,
+    pub endpoint: Option<String>,
+    /// Seconds since the epoch, None before the first handshake
+    pub last_handshake: Option<u64>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+async fn request(interface: &str, body: &str) -> Result<String> {
+    let path = socket_path(interface);
+    let mut stream = UnixStream::connect(&path)
+        .await
+        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+    stream.write_all(body.as_bytes()).await?;
+    let mut response = Vec::new();
+    let mut buf = [0u8; 4096];
+    while !response.ends_with(b"\n\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+    }
+    let response = String::from_utf8_lossy(&response).to_string();
+    match response
+        .lines()
+        .find_map(|line| line.strip_prefix("errno="))
+    {
+        Some("0") => Ok(response),
+        Some(errno) => Err(anyhow!(
+            "{} rejected the request (errno {})",
+            interface,
+            errno
+        )),
+        None => Err(anyhow!("No reply from {}", interface)),
+    }
+}
+
+/// Sets the private key and replaces the peers
+pub async fn configure(interface: &str, private_key: &str, peers: &[PeerSettings]) -> Result<()> {
+    let mut body = format!(
+        "set=1\nprivate_key={}\nreplace_peers=true\n",
+        key_hex(private_key)?
+    );
+    for peer in peers {
+        body.push_str(&format!("public_key={}\n", key_hex(&peer.public_key)?));
+        if let Some(key) = &peer.preshared_key {
+            body.push_str(&format!("preshared_key={}\n", key_hex(key)?));
+        }
+        if let Some(endpoint) = peer.endpoint {
+            body.push_str(&format!("endpoint={}\n", endpoint));
+        }
+        if let Some(interval) = peer.persistent_keepalive {
+            body.push_str(&format!("persistent_keepalive_interval={}\n", interval));
+        }
+        body.push_str("replace_allowed_ips=true\n");
+        for cidr in &peer.allowed_ips {
+            body.push_str(&format!("allowed_ip={}\n", cidr));
+        }
+    }
+    body.push('\n');
+    request(interface, &body).await?;
+    Ok(())
+}
+
+pub async fn status(interface: &str) -> Result<Vec<PeerStatus>> {
+    let response = request(interface, "get=1\n\n").await?;
+    let mut peers: Vec<PeerStatus> = Vec::new();
+    for line in response.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if key == "public_key" {
+            peers.push(PeerStatus {
+                public_key: key_base64(value),
+                endpoint: None,
+                last_handshake: None,
+                rx_bytes: 0,
+                tx_bytes: 0,
+            });
+            continue;
+        }
+        // Lines before the first public_key are about the interface
+        let Some(peer) = peers.last_mut() else {
+            continue;
+        };
+        match key {
+            "endpoint" => peer.endpoint = Some(value.to_string()),
+            "last_handshake_time_sec" => {
+                peer.last_handshake = value.parse().ok().filter(|&secs: &u64| secs > 0)
+            }
+            "rx_bytes" => peer.rx_bytes = value.parse().unwrap_or(0),
+            "tx_bytes" => peer.tx_bytes = value.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+    Ok(peers)
+}
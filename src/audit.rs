@@ -0,0 +1,225 @@
+// `proxy audit` exports the audit log the plugins append to (see plugin_api::audit),
+// filtered by time, plugin and action, as a table, JSON lines or CSV.
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+/// The keys every entry has, in the order they are exported
+const COMMON: [&str; 6] = ["time", "user", "host", "pid", "plugin", "action"];
+
+pub struct Filter {
+    pub since: Option<String>,
+    pub plugin: Option<String>,
+    pub action: Option<String>,
+}
+
+/// A time ago (30m, 24h, 7d, 2w), an RFC 3339 time or a plain date, taken as midnight UTC
+pub fn parse_since(since: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Some(unit) = since.chars().last() {
+        let count = since[..since.len() - unit.len_utf8()].parse::<i64>().ok();
+        let ago = count.and_then(|count| match unit {
+            'm' => Duration::try_minutes(count),
+            'h' => Duration::try_hours(count),
+            'd' => Duration::try_days(count),
+            'w' => Duration::try_weeks(count),
+            _ => None,
+        });
+        if let Some(ago) = ago {
+            return Ok(Utc::now() - ago);
+        }
+    }
+    NaiveDate::parse_from_str(since, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| {
+            anyhow!("--since takes a time ago (7d, 24h), a date (2024-05-01) or an RFC 3339 time")
+        })
+}
+
+fn read(filter: &Filter) -> Result<Vec<Map<String, Value>>> {
+    let since = filter.since.as_deref().map(parse_since).transpose()?;
+    let path = plugin_api::audit::log_path()
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {}", path.display())),
+    };
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let Ok(Value::Object(entry)) = serde_json::from_str(&line) else {
+            continue;
+        };
+        let field = |key: &str| entry.get(key).and_then(Value::as_str).unwrap_or_default();
+        if let Some(since) = since {
+            let time = DateTime::parse_from_rfc3339(field("time"));
+            if time.map_or(true, |time| time < since) {
+                continue;
+            }
+        }
+        if filter
+            .plugin
+            .as_deref()
+            .is_some_and(|plugin| plugin != field("plugin"))
+            || filter
+                .action
+                .as_deref()
+                .is_some_and(|action| action != field("action"))
+        {
+            continue;
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The common keys, then every detail key found in the entries
+fn columns(entries: &[Map<String, Value>]) -> Vec<String> {
+    let mut columns: Vec<String> = COMMON.iter().map(|key| key.to_string()).collect();
+    for entry in entries {
+        for key in entry.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    columns
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_table(out: &mut dyn Write, entries: &[Map<String, Value>]) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:<24} {:<12} {:<24} {:<18} {:<20} DETAILS",
+        "TIME", "USER", "PLUGIN", "ACTION", "CONTEXT"
+    )?;
+    for entry in entries {
+        let field = |key: &str| entry.get(key).map(text).unwrap_or_default();
+        let details: Vec<String> = entry
+            .iter()
+            .filter(|(key, _)| !COMMON.contains(&key.as_str()) && *key != "context")
+            .filter(|(_, value)| !text(value).is_empty())
+            .map(|(key, value)| format!("{}={}", key, text(value)))
+            .collect();
+        writeln!(
+            out,
+            "{:<24} {:<12} {:<24} {:<18} {:<20} {}",
+            field("time"),
+            field("user"),
+            field("plugin"),
+            field("action"),
+            field("context"),
+            details.join(" ")
+        )?;
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut dyn Write, entries: &[Map<String, Value>]) -> io::Result<()> {
+    let columns = columns(entries);
+    writeln!(out, "{}", columns.join(","))?;
+    for entry in entries {
+        let row: Vec<String> = columns
+            .iter()
+            .map(|column| csv_field(&entry.get(column).map(text).unwrap_or_default()))
+            .collect();
+        writeln!(out, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes the entries matching the filter in `format` (table, json or csv) to `output`,
+/// or to stdout
+pub fn export(filter: &Filter, format: &str, output: Option<&str>) -> Result<()> {
+    let entries = read(filter)?;
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => {
+            Box::new(File::create(path).with_context(|| format!("Failed to create {}", path))?)
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    if format == "table" && entries.is_empty() {
+        let path = plugin_api::audit::log_path().unwrap_or_default();
+        writeln!(out, "No audit entries in {}", path.display())?;
+        return Ok(());
+    }
+    match format {
+        "json" => {
+            for entry in &entries {
+                writeln!(out, "{}", Value::Object(entry.clone()))?;
+            }
+        }
+        "csv" => write_csv(&mut out, &entries)?,
+        _ => write_table(&mut out, &entries)?,
+    }
+    out.flush()?;
+    if let Some(path) = output {
+        // The export holds the same details as the log, keep it as private
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+        }
+        eprintln!("✅ Exported {} audit entries to {}", entries.len(), path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn since_takes_a_time_ago() {
+        for (since, ago) in [
+            ("30m", Duration::minutes(30)),
+            ("24h", Duration::hours(24)),
+            ("7d", Duration::days(7)),
+            ("2w", Duration::weeks(2)),
+        ] {
+            let expected = Utc::now() - ago;
+            let parsed = parse_since(since).unwrap();
+            assert!((parsed - expected).num_seconds().abs() <= 1, "{}", since);
+        }
+    }
+
+    #[test]
+    fn since_takes_a_date_or_an_rfc3339_time() {
+        assert_eq!(
+            parse_since("2024-05-01").unwrap().to_rfc3339(),
+            "2024-05-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_since("2024-05-01T12:30:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2024-05-01T10:30:00+00:00"
+        );
+    }
+
+    #[test]
+    fn since_rejects_anything_else() {
+        for since in ["", "d", "7y", "7 d", "yesterday", "2024-13-01"] {
+            assert!(parse_since(since).is_err(), "{:?}", since);
+        }
+    }
+}
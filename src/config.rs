@@ -0,0 +1,159 @@
+// `proxy config` manages the plugins' config files in plugins.d. `init` writes a plugin's
+// sample config there, the one plugins print when they find no config, to edit from.
+// `validate` has each plugin check its config files (see Plugin::validate_config), as
+// plugins otherwise skip settings they don't know and fall back to defaults.
+use anyhow::{anyhow, Context, Result};
+use plugin_api::Plugin;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Creates the plugin's config file from its sample config; an existing one is only
+/// replaced with `force`
+pub fn init(plugin: &dyn Plugin, force: bool) -> Result<()> {
+    let name = plugin.name();
+    let sample = plugin
+        .sample_config()
+        .ok_or_else(|| anyhow!("The {} plugin has no sample config", name))?;
+    let path = plugin_api::plugin_config_path(name)
+        .ok_or_else(|| anyhow!("Could not determine the config directory"))?;
+    if path.exists() && !force {
+        return Err(anyhow!(
+            "{} already exists, replace it with: proxy config init {} --force",
+            path.display(),
+            name
+        ));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(&path, sample).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("📝 Created {}", path.display());
+    println!("💡 Edit it, then run: proxy {}", name);
+    Ok(())
+}
+
+/// The config fragments in `dir`, as plugins read them: .toml and .conf files
+fn fragments(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut fragments: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("toml") | Some("conf")
+            )
+        })
+        .collect();
+    fragments.sort();
+    fragments
+}
+
+/// The config files of `plugin`: its .conf, when it or the project config has one, and
+/// the fragments in its .conf.d
+fn config_files(plugin: &str) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(path) = plugin_api::plugin_config_path(plugin) {
+        if plugin_api::config_exists(&path) {
+            files.push(path);
+        }
+    }
+    if let Some(dir) = plugin_api::plugin_config_fragments_dir(plugin) {
+        files.extend(fragments(&dir));
+    }
+    files
+}
+
+/// The plugins with files in plugins.d: NAME.conf and NAME.conf.d
+fn configured_plugins(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_str()?.to_string();
+            file_name
+                .strip_suffix(".conf")
+                .or_else(|| file_name.strip_suffix(".conf.d"))
+                .map(str::to_string)
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Has the plugins check their config files, all of those in plugins.d or only `only`'s
+pub fn validate(plugins: &[&dyn Plugin], only: Option<&str>) -> Result<()> {
+    let dir = plugin_api::plugin_config_dir()
+        .ok_or_else(|| anyhow!("Could not determine the config directory"))?;
+    let names = match only {
+        Some(name) => vec![name.to_string()],
+        None => configured_plugins(&dir),
+    };
+
+    let (mut checked, mut failed) = (0, 0);
+    for name in &names {
+        let files = config_files(name);
+        let Some(plugin) = plugins.iter().find(|plugin| plugin.name() == name) else {
+            if only.is_some() {
+                return Err(anyhow!(
+                    "{} is not an installed plugin, see proxy --list-plugins",
+                    name
+                ));
+            }
+            for path in files {
+                println!("⚠️  {}: no {} plugin is installed", path.display(), name);
+            }
+            continue;
+        };
+        if files.is_empty() && only.is_some() {
+            return Err(anyhow!(
+                "The {} plugin has no config, create one with: proxy config init {}",
+                name,
+                name
+            ));
+        }
+        for path in files {
+            checked += 1;
+            let errors = match plugin_api::read_config(&path) {
+                Ok(content) => plugin.validate_config(&content).err(),
+                Err(e) => Some(vec![plugin_api::config::ConfigError {
+                    path: String::new(),
+                    message: e.to_string(),
+                }]),
+            };
+            match errors {
+                None => println!("✅ {}", path.display()),
+                Some(errors) => {
+                    failed += 1;
+                    println!("❌ {}", path.display());
+                    for error in errors {
+                        println!("   {}", error);
+                    }
+                }
+            }
+        }
+    }
+
+    if checked == 0 {
+        println!(
+            "💡 No plugin configs in {}, create one with: proxy config init <plugin>",
+            dir.display()
+        );
+        return Ok(());
+    }
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} config files have errors",
+            failed,
+            checked
+        ));
+    }
+    println!();
+    println!("✅ {} config files are valid", checked);
+    Ok(())
+}
@@ -0,0 +1,418 @@
+// `proxy serve-control`: a JSON API over HTTP for tools that manage plugins without
+// parsing CLI output, such as IDE extensions and scripts. It lists the installed plugins,
+// the running plugin processes and k8s_port_forward forwards, and starts and stops
+// processes. Clients find the address and a bearer token in `control/endpoint.json` in the
+// state directory, which only the user can read. `proxy daemon start --control` serves it
+// from the supervisor, which then starts and stops the processes itself.
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use plugin_api::events::Event;
+use plugin_api::processes;
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:9465";
+const PORT_FORWARD_PLUGIN: &str = "k8s_port_forward";
+const MAX_REQUEST: usize = 64 * 1024;
+
+/// An installed plugin, as loaded by the host
+pub struct PluginInfo {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+}
+
+impl PluginInfo {
+    pub fn of(plugins: &[crate::loader::Loaded]) -> Vec<Self> {
+        plugins
+            .iter()
+            .map(|(plugin, _)| PluginInfo {
+                name: plugin.name().to_string(),
+                version: plugin.version().to_string(),
+                description: plugin.description().to_string(),
+            })
+            .collect()
+    }
+}
+
+/// How the daemon's supervisor carried out a start or stop request
+pub enum Answer {
+    Started {
+        pid: u32,
+        log: PathBuf,
+    },
+    Stopped {
+        plugin: String,
+    },
+    /// A stop for a process the supervisor doesn't run
+    NotSupervised,
+    Failed(String),
+}
+
+/// Where the daemon's supervisor takes start and stop requests, each with where to answer
+pub type Supervisor = mpsc::Sender<(Event, mpsc::Sender<Answer>)>;
+
+struct Control {
+    plugins: Vec<PluginInfo>,
+    token: String,
+    supervisor: Option<Supervisor>,
+}
+
+#[derive(Deserialize)]
+struct StartRequest {
+    /// The plugin command line, e.g. ["k8s_port_forward", "-c", "dev"]
+    args: Vec<String>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+/// A request that can't be parsed, answered with 400 Bad Request
+#[derive(Debug)]
+struct BadRequest(String);
+
+impl std::fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadRequest {}
+
+/// 32 random bytes from the OS, hex-encoded
+fn token() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow!("Failed to generate a token: {}", e))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Compares in time that doesn't depend on where the two differ, so the token can't be
+/// guessed byte by byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn endpoint_path() -> Option<PathBuf> {
+    plugin_api::plugin_state_dir("control").map(|dir| dir.join("endpoint.json"))
+}
+
+fn write_endpoint(path: &PathBuf, listen: &str, token: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let endpoint = json!({
+        "url": format!("http://{}", listen),
+        "token": token,
+        "pid": std::process::id(),
+    });
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // The mode only applies to a file created here; one left from before keeps its own
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    std::io::Write::write_all(
+        &mut file,
+        serde_json::to_string_pretty(&endpoint)?.as_bytes(),
+    )?;
+    Ok(())
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Option<Request>> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let header_end = loop {
+        if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if request.len() > MAX_REQUEST {
+            return Err(anyhow!("Request headers too large"));
+        }
+        let n = stream.read(&mut buffer).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buffer[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&request[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut parts = lines.next().unwrap_or_default().split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let mut content_length = 0;
+    let mut authorization = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                let value = value.trim();
+                content_length = value
+                    .parse()
+                    .map_err(|_| BadRequest(format!("Invalid Content-Length '{}'", value)))?;
+            }
+            "authorization" => authorization = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    if content_length > MAX_REQUEST {
+        return Err(anyhow!("Request body too large"));
+    }
+    let mut body = request[header_end..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buffer[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        authorization,
+        body,
+    }))
+}
+
+/// The forwards of every k8s_port_forward process still running
+fn forwards() -> Vec<Value> {
+    let running: Vec<u32> = processes::running()
+        .iter()
+        .map(|process| process.pid)
+        .collect();
+    let mut forwards = Vec::new();
+    let entries = plugin_api::plugin_state_dir(PORT_FORWARD_PLUGIN)
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .flatten();
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with("forwards-") || !name.ends_with(".json") {
+            continue;
+        }
+        let Some(state) = fs::read_to_string(entry.path())
+            .ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        else {
+            continue;
+        };
+        let pid = state["pid"].as_u64().unwrap_or_default();
+        if !running.contains(&(pid as u32)) {
+            continue;
+        }
+        for forward in state["forwards"].as_array().into_iter().flatten() {
+            let mut forward = forward.clone();
+            forward["pid"] = json!(pid);
+            forwards.push(forward);
+        }
+    }
+    forwards
+}
+
+impl Control {
+    /// Hands `event` to the supervisor, when there is one, and waits for its answer
+    fn ask(&self, event: Event) -> Option<Answer> {
+        let supervisor = self.supervisor.as_ref()?;
+        let (reply, answer) = mpsc::channel();
+        if supervisor.send((event, reply)).is_err() {
+            return Some(Answer::Failed("The daemon is shutting down".to_string()));
+        }
+        let answer = tokio::task::block_in_place(|| answer.recv());
+        Some(answer.unwrap_or_else(|_| Answer::Failed("The daemon is shutting down".to_string())))
+    }
+
+    fn route(&self, request: &Request) -> (u16, Value) {
+        let error = |status: u16, message: String| (status, json!({ "error": message }));
+        let expected = format!("Bearer {}", self.token);
+        let authorization = request.authorization.as_deref().unwrap_or_default();
+        if !constant_time_eq(authorization.as_bytes(), expected.as_bytes()) {
+            return error(401, "Missing or wrong bearer token".to_string());
+        }
+        let path = request.path.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["v1", "plugins"]) => {
+                let plugins: Vec<Value> = self
+                    .plugins
+                    .iter()
+                    .map(|plugin| {
+                        json!({
+                            "name": plugin.name,
+                            "version": plugin.version,
+                            "description": plugin.description,
+                        })
+                    })
+                    .collect();
+                (200, json!(plugins))
+            }
+            ("GET", ["v1", "processes"]) => {
+                let running: Vec<Value> = processes::running()
+                    .into_iter()
+                    .map(|process| json!({ "plugin": process.plugin, "pid": process.pid }))
+                    .collect();
+                (200, json!(running))
+            }
+            ("POST", ["v1", "processes"]) => {
+                let start = match serde_json::from_slice::<StartRequest>(&request.body) {
+                    Ok(start) => start,
+                    Err(e) => return error(400, format!("Invalid request: {}", e)),
+                };
+                let plugin = start.args.first().map(String::as_str).unwrap_or_default();
+                if !self
+                    .plugins
+                    .iter()
+                    .any(|installed| installed.name == plugin)
+                {
+                    return error(400, format!("No plugin named '{}' is installed", plugin));
+                }
+                match self.ask(Event::Start {
+                    id: String::new(),
+                    args: start.args.clone(),
+                }) {
+                    Some(Answer::Started { pid, log }) => {
+                        return (201, json!({ "pid": pid, "log": log }));
+                    }
+                    Some(Answer::Failed(message)) => return error(500, message),
+                    Some(_) | None => {}
+                }
+                match processes::start(&start.args) {
+                    Ok(started) => {
+                        println!("▶️  Started {} (pid {})", start.args.join(" "), started.pid);
+                        (201, json!({ "pid": started.pid, "log": started.log }))
+                    }
+                    Err(e) => error(500, e.to_string()),
+                }
+            }
+            ("DELETE", ["v1", "processes", pid]) => {
+                let Ok(pid) = pid.parse() else {
+                    return error(400, format!("Invalid pid '{}'", pid));
+                };
+                match self.ask(Event::Stop {
+                    id: String::new(),
+                    pid,
+                }) {
+                    Some(Answer::Stopped { plugin }) => {
+                        return (200, json!({ "plugin": plugin, "pid": pid }));
+                    }
+                    Some(Answer::Failed(message)) => return error(500, message),
+                    Some(_) | None => {}
+                }
+                match processes::stop(pid) {
+                    Ok(plugin) => {
+                        println!("⏹️  Stopped {} (pid {})", plugin, pid);
+                        (200, json!({ "plugin": plugin, "pid": pid }))
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => error(404, e.to_string()),
+                    Err(e) => error(500, e.to_string()),
+                }
+            }
+            ("GET", ["v1", "forwards"]) => (200, json!(forwards())),
+            ("GET", _) | ("POST", _) | ("DELETE", _) => error(404, format!("No route {}", path)),
+            _ => error(405, format!("Method {} not allowed", request.method)),
+        }
+    }
+
+    async fn respond(&self, mut stream: TcpStream) -> Result<()> {
+        let (status, body) = match read_request(&mut stream).await {
+            Ok(Some(request)) => self.route(&request),
+            Ok(None) => return Ok(()),
+            Err(e) => match e.downcast_ref::<BadRequest>() {
+                Some(bad) => (400, json!({ "error": bad.to_string() })),
+                None => return Err(e),
+            },
+        };
+        let reason = match status {
+            200 => "OK",
+            201 => "Created",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        };
+        let body = body.to_string();
+        let response = format!(
+            concat!(
+                "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\n",
+                "Content-Length: {}\r\nConnection: close\r\n\r\n{}"
+            ),
+            status,
+            reason,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// Serves the API until Ctrl-C
+pub fn serve(listen: &str, plugins: Vec<PluginInfo>) -> Result<()> {
+    serve_until(listen, plugins, None, async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+}
+
+/// Serves the API until `stop` completes, starting and stopping processes through
+/// `supervisor` when given
+pub fn serve_until(
+    listen: &str,
+    plugins: Vec<PluginInfo>,
+    supervisor: Option<Supervisor>,
+    stop: impl Future<Output = ()>,
+) -> Result<()> {
+    let endpoint =
+        endpoint_path().ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+    let control = Arc::new(Control {
+        plugins,
+        token: token()?,
+        supervisor,
+    });
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        tokio::pin!(stop);
+        let listener = TcpListener::bind(listen).await?;
+        write_endpoint(&endpoint, listen, &control.token)?;
+        println!("🎛️  Serving the control API on http://{}/v1/", listen);
+        println!("🔑 Address and token are in {}", endpoint.display());
+        loop {
+            let (stream, _) = tokio::select! {
+                accepted = listener.accept() => accepted?,
+                _ = &mut stop => break,
+            };
+            let control = control.clone();
+            tokio::spawn(async move {
+                if let Err(e) = control.respond(stream).await {
+                    eprintln!("⚠️  {}", e);
+                }
+            });
+        }
+        let _ = fs::remove_file(&endpoint);
+        println!("\n👋 Shutting down...");
+        Ok(())
+    })
+}
@@ -0,0 +1,901 @@
+// `proxy daemon` runs several long-running plugins at once, e.g. a few port forwards and
+// socks5_proxy, under one supervisor: each plugin command runs as its own `proxy` child,
+// so one crashing doesn't stop the others, and is restarted with a growing delay when it
+// fails. A plugin that exits successfully is done and stays stopped. The supervisor keeps
+// `daemon/status.json` in the state directory up to date for `proxy daemon status`, and
+// the plugins' output goes to logs next to it.
+//
+// The supervisor handles the start and stop requests on the event bus (plugin_api::events),
+// e.g. from the web_ui dashboard: a plugin started that way is supervised like the ones
+// given. It announces its plugins starting and exiting there too. With --control it also
+// serves the control API (`proxy serve-control`), whose start and stop requests it carries
+// out the same way.
+//
+// The supervisor also watches the plugin directory. Once a plugin's library or binary has
+// been replaced and stopped changing, it briefly loads the new one to see which plugin it
+// is, and restarts only that plugin's commands; everything else keeps running.
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::control;
+use crate::loader;
+use plugin_api::events::{self, Event};
+use plugin_api::processes;
+
+const STATUS_FILE: &str = "status.json";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How often the plugin directory is checked; a file is reloaded once it stayed the same
+/// between two checks, so one that is still being copied isn't
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+/// How long plugins get to exit after being interrupted
+const STOP_GRACE: Duration = Duration::from_secs(5);
+/// The first restart delay, doubled after every failure up to MAX_BACKOFF
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A plugin that ran this long before failing starts over with the initial delay
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+/// Failures in a row, each before STABLE_AFTER, after which a plugin is given up on
+const MAX_QUICK_FAILURES: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum State {
+    Running,
+    Restarting,
+    Failed,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PluginStatus {
+    command: String,
+    /// The version of the plugin it was last started with
+    #[serde(default)]
+    version: String,
+    pid: Option<u32>,
+    state: State,
+    restarts: u32,
+    /// When the plugin entered its state
+    since: String,
+    last_exit: Option<String>,
+    log: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Status {
+    pid: u32,
+    started: String,
+    plugins: Vec<PluginStatus>,
+}
+
+struct Supervised {
+    args: Vec<String>,
+    child: Option<Child>,
+    started: Instant,
+    restart_at: Option<Instant>,
+    backoff: Duration,
+    quick_failures: u32,
+    status: PluginStatus,
+}
+
+/// Size and modification time of a plugin file, which change when it is replaced
+type Fingerprint = (u64, Option<SystemTime>);
+
+/// Notices plugin files that were added or replaced in the plugin directory
+struct Watcher {
+    dir: PathBuf,
+    files: BTreeMap<PathBuf, Fingerprint>,
+    /// Files that changed, with how they looked at the last check
+    changed: BTreeMap<PathBuf, Fingerprint>,
+    checked: Instant,
+}
+
+impl Watcher {
+    fn new(dir: &Path) -> Self {
+        Watcher {
+            dir: dir.to_path_buf(),
+            files: Self::scan(dir),
+            changed: BTreeMap::new(),
+            checked: Instant::now(),
+        }
+    }
+
+    fn scan(dir: &Path) -> BTreeMap<PathBuf, Fingerprint> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return BTreeMap::new();
+        };
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| loader::is_plugin_file(path))
+            .filter_map(|path| {
+                let metadata = fs::metadata(&path).ok()?;
+                Some((path, (metadata.len(), metadata.modified().ok())))
+            })
+            .collect()
+    }
+
+    /// The plugin files added or replaced since the last check that have stopped changing
+    fn poll(&mut self) -> Vec<PathBuf> {
+        if self.checked.elapsed() < WATCH_INTERVAL {
+            return Vec::new();
+        }
+        self.checked = Instant::now();
+        let current = Self::scan(&self.dir);
+        let mut settled = Vec::new();
+        for (path, fingerprint) in &current {
+            if self.files.get(path) == Some(fingerprint) {
+                self.changed.remove(path);
+            } else if self.changed.get(path) == Some(fingerprint) {
+                self.changed.remove(path);
+                self.files.insert(path.clone(), *fingerprint);
+                settled.push(path.clone());
+            } else {
+                self.changed.insert(path.clone(), *fingerprint);
+            }
+        }
+        self.files.retain(|path, _| current.contains_key(path));
+        self.changed.retain(|path, _| current.contains_key(path));
+        settled
+    }
+}
+
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn request_stop(_signal: libc::c_int) {
+    STOPPING.store(true, Ordering::SeqCst);
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn request_stop(_event: u32) -> windows_sys::Win32::Foundation::BOOL {
+    STOPPING.store(true, Ordering::SeqCst);
+    1
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn daemon_dir() -> Result<PathBuf> {
+    plugin_api::plugin_state_dir("daemon")
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))
+}
+
+fn read_status(dir: &Path) -> Option<Status> {
+    fs::read(dir.join(STATUS_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+}
+
+fn write_status(dir: &Path, status: &Status) -> Result<()> {
+    let path = dir.join(STATUS_FILE);
+    let staged = dir.join(format!("{}.tmp", STATUS_FILE));
+    // Made again when it was removed while the daemon runs
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    fs::write(&staged, serde_json::to_string_pretty(status)?)
+        .with_context(|| format!("Failed to write {}", staged.display()))?;
+    fs::rename(&staged, &path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Splits each plugin command line into its arguments as a shell would, so quoted ones
+/// keep their spaces, and checks it names a plugin
+fn parse_commands(commands: &[String], installed: &[&str]) -> Result<Vec<Vec<String>>> {
+    commands
+        .iter()
+        .map(|line| {
+            let args = shlex::split(line)
+                .ok_or_else(|| anyhow!("Unbalanced quotes in plugin command: {}", line))?;
+            match args.first() {
+                Some(plugin) if installed.contains(&plugin.as_str()) => Ok(args),
+                Some(plugin) => Err(anyhow!(
+                    "{} is not an installed plugin, see proxy --list-plugins",
+                    plugin
+                )),
+                None => Err(anyhow!("Empty plugin command")),
+            }
+        })
+        .collect()
+}
+
+/// The commands given, or the project config's `up` list without any, quoted so that
+/// `parse_commands` gives back the same arguments
+fn commands(given: &[String]) -> Result<Vec<String>> {
+    if !given.is_empty() {
+        return Ok(given.to_vec());
+    }
+    let (path, commands) = plugin_api::project::up()?.ok_or_else(|| {
+        anyhow!(
+            "Name the plugin commands to run, or list them under `up` in {}",
+            plugin_api::project::FILE_NAME
+        )
+    })?;
+    if commands.is_empty() {
+        return Err(anyhow!("`up` in {} is empty", path.display()));
+    }
+    println!("📁 Plugins from {}", path.display());
+    commands
+        .iter()
+        .map(|args| {
+            shlex::try_join(args.iter().map(String::as_str))
+                .map_err(|e| anyhow!("Invalid `up` command in {}: {}", path.display(), e))
+        })
+        .collect()
+}
+
+fn spawn(args: &[String], log: &Path) -> Result<Child> {
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .with_context(|| format!("Failed to open {}", log.display()))?;
+    // Out of the terminal's process group: Ctrl-C reaches the supervisor, which stops them
+    Ok(processes::spawn(args, log)?)
+}
+
+/// Interrupts the plugins as Ctrl-C would, killing those that don't exit in time
+fn stop_children(plugins: &mut [&mut Supervised]) {
+    for plugin in plugins.iter() {
+        if let Some(child) = &plugin.child {
+            let _ = processes::interrupt(child.id());
+        }
+    }
+    let deadline = Instant::now() + STOP_GRACE;
+    for plugin in plugins.iter_mut() {
+        if let Some(mut child) = plugin.child.take() {
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = child.try_wait() {
+                    break;
+                }
+                std::thread::sleep(POLL_INTERVAL / 5);
+            }
+            if let Ok(None) = child.try_wait() {
+                if processes::kill(child.id()).is_err() {
+                    let _ = child.kill();
+                }
+            }
+            let _ = child.wait();
+            processes::exited(child.id());
+            events::announce(&Event::Exited {
+                plugin: plugin.args[0].clone(),
+                pid: child.id(),
+            });
+        }
+    }
+}
+
+fn stop_all(supervised: &mut [Supervised]) {
+    stop_children(&mut supervised.iter_mut().collect::<Vec<_>>());
+    for plugin in supervised.iter_mut() {
+        if matches!(plugin.status.state, State::Running | State::Restarting) {
+            plugin.status.state = State::Stopped;
+            plugin.status.pid = None;
+            plugin.status.since = now();
+        }
+    }
+}
+
+/// Starts a plugin's command, announced as answering the request `id` (empty for none)
+fn start_plugin(plugin: &mut Supervised, id: &str) {
+    match spawn(&plugin.args, &plugin.status.log) {
+        Ok(child) => {
+            println!("▶️  Started {} (pid {})", plugin.status.command, child.id());
+            events::announce(&Event::Started {
+                id: id.to_string(),
+                plugin: plugin.args[0].clone(),
+                pid: child.id(),
+            });
+            plugin.status.pid = Some(child.id());
+            plugin.status.state = State::Running;
+            plugin.child = Some(child);
+            plugin.started = Instant::now();
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to start {}: {}", plugin.status.command, e);
+            if !id.is_empty() {
+                events::announce(&Event::Failed {
+                    id: id.to_string(),
+                    message: e.to_string(),
+                });
+            }
+            plugin.status.state = State::Failed;
+            plugin.status.last_exit = Some(e.to_string());
+        }
+    }
+    plugin.status.since = now();
+}
+
+/// Decides what happens after a plugin exited: done, restarted later or given up on
+fn exited(plugin: &mut Supervised, status: ExitStatus) {
+    if let Some(child) = plugin.child.take() {
+        processes::exited(child.id());
+        events::announce(&Event::Exited {
+            plugin: plugin.args[0].clone(),
+            pid: child.id(),
+        });
+    }
+    plugin.status.pid = None;
+    plugin.status.last_exit = Some(status.to_string());
+    plugin.status.since = now();
+    if status.success() {
+        println!("⏹️  {} finished", plugin.status.command);
+        plugin.status.state = State::Stopped;
+        return;
+    }
+
+    if plugin.started.elapsed() >= STABLE_AFTER {
+        plugin.backoff = INITIAL_BACKOFF;
+        plugin.quick_failures = 0;
+    }
+    plugin.quick_failures += 1;
+    if plugin.quick_failures >= MAX_QUICK_FAILURES {
+        eprintln!(
+            "❌ {} failed {} times in a row ({}), giving up; see {}",
+            plugin.status.command,
+            plugin.quick_failures,
+            status,
+            plugin.status.log.display()
+        );
+        plugin.status.state = State::Failed;
+        return;
+    }
+    eprintln!(
+        "⚠️  {} exited ({}), restarting in {}s",
+        plugin.status.command,
+        status,
+        plugin.backoff.as_secs()
+    );
+    plugin.status.state = State::Restarting;
+    plugin.restart_at = Some(Instant::now() + plugin.backoff);
+    plugin.backoff = (plugin.backoff * 2).min(MAX_BACKOFF);
+}
+
+/// Restarts the commands of the plugin at `path`, which was just installed or updated
+fn reload(path: &Path, supervised: &mut [Supervised], known: &mut BTreeMap<String, String>) {
+    // Only to see which plugin it is; the tuple drops here, unloading it again
+    let (name, version) = match loader::load_file(path) {
+        Ok(Some(loaded)) => (loaded.0.name().to_string(), loaded.0.version().to_string()),
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("⚠️  Not reloading {}: {}", path.display(), e);
+            return;
+        }
+    };
+    if known.insert(name.clone(), version.clone()).is_none() {
+        println!("🧩 New plugin: {} {}", name, version);
+    }
+    // Commands that finished stay that way; failed ones get another chance
+    let mut affected: Vec<&mut Supervised> = supervised
+        .iter_mut()
+        .filter(|plugin| plugin.args[0] == name && plugin.status.state != State::Stopped)
+        .collect();
+    if affected.is_empty() {
+        return;
+    }
+    println!(
+        "🔄 Reloading {} {}, restarting {} commands",
+        name,
+        version,
+        affected.len()
+    );
+    stop_children(&mut affected);
+    for plugin in affected {
+        plugin.status.version = version.clone();
+        plugin.restart_at = None;
+        plugin.backoff = INITIAL_BACKOFF;
+        plugin.quick_failures = 0;
+        start_plugin(plugin, "");
+    }
+}
+
+fn supervise(
+    index: usize,
+    args: Vec<String>,
+    dir: &Path,
+    known: &BTreeMap<String, String>,
+) -> Supervised {
+    Supervised {
+        status: PluginStatus {
+            command: args.join(" "),
+            version: known.get(&args[0]).cloned().unwrap_or_default(),
+            pid: None,
+            state: State::Restarting,
+            restarts: 0,
+            since: now(),
+            last_exit: None,
+            log: dir.join(format!("{}-{}.log", index + 1, args[0])),
+        },
+        args,
+        child: None,
+        started: Instant::now(),
+        restart_at: None,
+        backoff: INITIAL_BACKOFF,
+        quick_failures: 0,
+    }
+}
+
+/// Carries out a start or stop request from the event bus or the control API. A plugin
+/// started is supervised from then on; a stop for a process this supervisor doesn't run
+/// is left to the caller.
+fn handle(
+    event: &Event,
+    supervised: &mut Vec<Supervised>,
+    known: &BTreeMap<String, String>,
+    dir: &Path,
+) -> control::Answer {
+    match event {
+        Event::Start { id, args } => {
+            let Some(name) = args.first().filter(|name| known.contains_key(*name)) else {
+                let message = format!(
+                    "{} is not an installed plugin",
+                    args.first().map(String::as_str).unwrap_or("\"\"")
+                );
+                events::announce(&Event::Failed {
+                    id: id.clone(),
+                    message: message.clone(),
+                });
+                return control::Answer::Failed(message);
+            };
+            println!("📨 Asked to start {}", name);
+            let mut plugin = supervise(supervised.len(), args.clone(), dir, known);
+            start_plugin(&mut plugin, id);
+            let answer = match plugin.status.pid {
+                Some(pid) => control::Answer::Started {
+                    pid,
+                    log: plugin.status.log.clone(),
+                },
+                None => {
+                    control::Answer::Failed(plugin.status.last_exit.clone().unwrap_or_default())
+                }
+            };
+            supervised.push(plugin);
+            answer
+        }
+        Event::Stop { id, pid } => {
+            let Some(plugin) = supervised
+                .iter_mut()
+                .find(|plugin| plugin.status.pid == Some(*pid))
+            else {
+                return control::Answer::NotSupervised;
+            };
+            println!("📨 Asked to stop {} (pid {})", plugin.status.command, pid);
+            stop_children(&mut [&mut *plugin]);
+            plugin.status.state = State::Stopped;
+            plugin.status.pid = None;
+            plugin.status.since = now();
+            events::announce(&Event::Stopped {
+                id: id.clone(),
+                plugin: plugin.args[0].clone(),
+                pid: *pid,
+            });
+            control::Answer::Stopped {
+                plugin: plugin.args[0].clone(),
+            }
+        }
+        _ => control::Answer::NotSupervised,
+    }
+}
+
+/// Runs the plugin commands (or the project's `up` list) under the supervisor until they
+/// are all done or it is stopped; `detach` runs the supervisor in the background.
+/// `installed` are the plugins' names and versions, `plugin_dir` is watched for updates.
+/// `control` is the address to serve the control API on, and the plugins it offers.
+pub fn start(
+    given: &[String],
+    installed: &[(String, String)],
+    plugin_dir: &Path,
+    detach: bool,
+    control: Option<(String, Vec<control::PluginInfo>)>,
+) -> Result<()> {
+    let dir = daemon_dir()?;
+    if let Some(status) = read_status(&dir).filter(|status| processes::alive(status.pid)) {
+        return Err(anyhow!(
+            "The daemon is already running (pid {}), see proxy daemon status",
+            status.pid
+        ));
+    }
+    let lines = commands(given)?;
+    let names: Vec<&str> = installed.iter().map(|(name, _)| name.as_str()).collect();
+    let commands = parse_commands(&lines, &names)?;
+    let mut known: BTreeMap<String, String> = installed.iter().cloned().collect();
+
+    if detach {
+        let mut args = vec!["daemon".to_string(), "start".to_string()];
+        if let Some((listen, _)) = &control {
+            args.push(format!("--control={}", listen));
+        }
+        args.extend(lines);
+        let started = processes::start(&args)?;
+        println!(
+            "🛡️  Daemon started in the background (pid {}), log: {}",
+            started.pid,
+            started.log.display()
+        );
+        println!("💡 Check on it with: proxy daemon status");
+        return Ok(());
+    }
+
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    #[cfg(unix)]
+    unsafe {
+        let handler = request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+    // Ctrl-C, and the Ctrl-Break `stop` sends
+    #[cfg(windows)]
+    unsafe {
+        windows_sys::Win32::System::Console::SetConsoleCtrlHandler(Some(request_stop), 1);
+    }
+
+    let mut supervised: Vec<Supervised> = commands
+        .into_iter()
+        .enumerate()
+        .map(|(index, args)| supervise(index, args, &dir, &known))
+        .collect();
+    let mut status = Status {
+        pid: std::process::id(),
+        started: now(),
+        plugins: Vec::new(),
+    };
+    println!(
+        "🛡️  Supervising {} plugins (pid {}), logs in {}",
+        supervised.len(),
+        status.pid,
+        dir.display()
+    );
+    for plugin in supervised.iter_mut() {
+        start_plugin(plugin, "");
+    }
+    // On a thread of its own, until the supervisor is done, handing it the requests to
+    // start and stop processes
+    let serving = Arc::new(AtomicBool::new(true));
+    let (supervisor, asked) = mpsc::channel();
+    let control = control.map(|(listen, plugins)| {
+        let serving = serving.clone();
+        std::thread::spawn(move || {
+            let done = async move {
+                while serving.load(Ordering::SeqCst) {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            };
+            if let Err(e) = control::serve_until(&listen, plugins, Some(supervisor), done) {
+                eprintln!("❌ Control API: {}", e);
+            }
+        })
+    });
+
+    // From a dashboard that had it, for plugins to be started under the supervisor
+    let handler = events::Handler::take_over();
+    let mut requests = events::Subscriber::new();
+    let mut watcher = Watcher::new(plugin_dir);
+    let mut status_failing = false;
+    while !STOPPING.load(Ordering::SeqCst) {
+        for path in watcher.poll() {
+            reload(&path, &mut supervised, &mut known);
+        }
+        let handling = handler.as_ref().is_some_and(events::Handler::held);
+        for received in requests.poll() {
+            let handled = handling.then(|| handle(&received.event, &mut supervised, &known, &dir));
+            if let Some(control::Answer::NotSupervised) = handled {
+                processes::handle(&received.event);
+            }
+        }
+        while let Ok((event, reply)) = asked.try_recv() {
+            let _ = reply.send(handle(&event, &mut supervised, &known, &dir));
+        }
+        for plugin in supervised.iter_mut() {
+            if let Some(child) = plugin.child.as_mut() {
+                if let Ok(Some(exit)) = child.try_wait() {
+                    exited(plugin, exit);
+                }
+            } else if plugin.status.state == State::Restarting
+                && plugin
+                    .restart_at
+                    .is_some_and(|restart_at| Instant::now() >= restart_at)
+            {
+                plugin.restart_at = None;
+                plugin.status.restarts += 1;
+                start_plugin(plugin, "");
+            }
+        }
+        status.plugins = supervised
+            .iter()
+            .map(|plugin| plugin.status.clone())
+            .collect();
+        // The plugins keep running when it can't be written; only the first failure of a
+        // run is reported, so a full disk doesn't flood the log
+        match write_status(&dir, &status) {
+            Ok(()) => status_failing = false,
+            Err(e) if !status_failing => {
+                eprintln!("⚠️  {:#}", e);
+                status_failing = true;
+            }
+            Err(_) => {}
+        }
+        if supervised
+            .iter()
+            .all(|plugin| matches!(plugin.status.state, State::Stopped | State::Failed))
+        {
+            break;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    if STOPPING.load(Ordering::SeqCst) {
+        println!("🛑 Stopping {} plugins", supervised.len());
+    }
+    stop_all(&mut supervised);
+    // Answers what the control API still waits on with an error
+    drop(asked);
+    serving.store(false, Ordering::SeqCst);
+    if let Some(control) = control {
+        let _ = control.join();
+    }
+    status.plugins = supervised.into_iter().map(|plugin| plugin.status).collect();
+    write_status(&dir, &status)?;
+    let failed = status
+        .plugins
+        .iter()
+        .filter(|plugin| plugin.state == State::Failed)
+        .count();
+    if failed > 0 {
+        return Err(anyhow!("{} of the plugins failed", failed));
+    }
+    Ok(())
+}
+
+fn format_since(since: &str) -> String {
+    DateTime::parse_from_rfc3339(since)
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|_| since.to_string())
+}
+
+/// Prints the supervised plugins and their state, as a table or as JSON
+pub fn status(json: bool) -> Result<()> {
+    let dir = daemon_dir()?;
+    let Some(status) = read_status(&dir) else {
+        if json {
+            println!("null");
+        } else {
+            println!("💤 The daemon has not run, start it with: proxy daemon start");
+        }
+        return Ok(());
+    };
+    let running = processes::alive(status.pid);
+    if json {
+        let mut value = serde_json::to_value(&status)?;
+        value["running"] = running.into();
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+
+    if running {
+        println!(
+            "🛡️  Daemon running (pid {}) since {}",
+            status.pid,
+            format_since(&status.started)
+        );
+    } else {
+        println!(
+            "💤 The daemon is not running; as it last was (pid {}, started {}):",
+            status.pid,
+            format_since(&status.started)
+        );
+    }
+    let width = status
+        .plugins
+        .iter()
+        .map(|plugin| plugin.command.len())
+        .max()
+        .unwrap_or(0)
+        .max("COMMAND".len());
+    println!();
+    println!(
+        "{:<width$} {:<10} {:<11} {:>8} {:>8}  {:<20} LAST EXIT",
+        "COMMAND",
+        "VERSION",
+        "STATE",
+        "PID",
+        "RESTARTS",
+        "SINCE",
+        width = width
+    );
+    for plugin in &status.plugins {
+        let state = match plugin.state {
+            State::Running => "running",
+            State::Restarting => "restarting",
+            State::Failed => "failed",
+            State::Stopped => "stopped",
+        };
+        println!(
+            "{:<width$} {:<10} {:<11} {:>8} {:>8}  {:<20} {}",
+            plugin.command,
+            plugin.version,
+            state,
+            plugin
+                .pid
+                .map(|pid| pid.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            plugin.restarts,
+            format_since(&plugin.since),
+            plugin.last_exit.as_deref().unwrap_or("-"),
+            width = width
+        );
+    }
+    println!();
+    println!("📂 Logs: {}", dir.display());
+    Ok(())
+}
+
+/// Stops the daemon, which stops its plugins first
+pub fn stop() -> Result<()> {
+    let dir = daemon_dir()?;
+    let Some(status) = read_status(&dir).filter(|status| processes::alive(status.pid)) else {
+        return Err(anyhow!("The daemon is not running"));
+    };
+    #[cfg(unix)]
+    if unsafe { libc::kill(status.pid as i32, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    // Ctrl-Break only reaches it from the console it runs on: otherwise end it along with
+    // its plugins
+    #[cfg(windows)]
+    if processes::interrupt(status.pid).is_err() {
+        processes::kill(status.pid)?;
+    }
+    // Plugins get STOP_GRACE to exit, and the supervisor a little longer
+    let deadline = Instant::now() + STOP_GRACE * 2;
+    while processes::alive(status.pid) {
+        if Instant::now() >= deadline {
+            // The Ctrl-Break may not have reached it
+            #[cfg(windows)]
+            if processes::kill(status.pid).is_ok() {
+                break;
+            }
+            return Err(anyhow!(
+                "The daemon (pid {}) is still running after {}s",
+                status.pid,
+                (STOP_GRACE * 2).as_secs()
+            ));
+        }
+        std::thread::sleep(POLL_INTERVAL / 5);
+    }
+    println!(
+        "🛑 Stopped the daemon (pid {}) and its {} plugins",
+        status.pid,
+        status.plugins.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_arguments_keep_their_spaces() {
+        let installed = ["k8s_exec", "socks5_proxy"];
+        let lines = vec![
+            "k8s_exec --pod api-0 -- sh -c 'ls -la /tmp'".to_string(),
+            r#"socks5_proxy --user "a b""#.to_string(),
+        ];
+        assert_eq!(
+            parse_commands(&lines, &installed).unwrap(),
+            vec![
+                vec![
+                    "k8s_exec",
+                    "--pod",
+                    "api-0",
+                    "--",
+                    "sh",
+                    "-c",
+                    "ls -la /tmp"
+                ],
+                vec!["socks5_proxy", "--user", "a b"],
+            ]
+        );
+        // As `commands` quotes the `up` list for a detached supervisor to split again
+        let args = ["k8s_exec", "--", "sh", "-c", "echo 'hi there'"];
+        let line = shlex::try_join(args).unwrap();
+        assert_eq!(parse_commands(&[line], &installed).unwrap(), vec![args]);
+
+        assert!(parse_commands(&["k8s_exec -- sh -c 'ls".to_string()], &installed).is_err());
+        assert!(parse_commands(&["vault login".to_string()], &installed).is_err());
+        assert!(parse_commands(&["  ".to_string()], &installed).is_err());
+    }
+
+    /// Polls as if WATCH_INTERVAL had passed since the last check
+    fn poll_now(watcher: &mut Watcher) -> Vec<PathBuf> {
+        watcher.checked = Instant::now()
+            .checked_sub(WATCH_INTERVAL)
+            .unwrap_or(watcher.checked);
+        watcher.poll()
+    }
+
+    #[test]
+    fn replaced_plugins_are_reloaded_once_they_stop_changing() {
+        let dir = std::env::temp_dir().join(format!("proxy-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let plugin = dir.join(crate::registry::library_file_name("k8s_exec"));
+        fs::write(&plugin, "v1").unwrap();
+        let mut watcher = Watcher::new(&dir);
+        assert!(poll_now(&mut watcher).is_empty());
+
+        fs::write(&plugin, "v2 still copying").unwrap();
+        assert!(poll_now(&mut watcher).is_empty());
+        fs::write(&plugin, "v2 copied in full").unwrap();
+        assert!(poll_now(&mut watcher).is_empty());
+        assert_eq!(poll_now(&mut watcher), vec![plugin.clone()]);
+        assert!(poll_now(&mut watcher).is_empty());
+
+        let added = dir.join(crate::registry::library_file_name("vault"));
+        fs::write(&added, "v1").unwrap();
+        fs::write(dir.join("notes.txt"), "not a plugin").unwrap();
+        assert!(poll_now(&mut watcher).is_empty());
+        assert_eq!(poll_now(&mut watcher), vec![added]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    fn exit_status(code: i32) -> ExitStatus {
+        std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+    }
+
+    #[cfg(windows)]
+    fn exit_status(code: i32) -> ExitStatus {
+        std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+    }
+
+    #[test]
+    fn plugins_failing_quickly_are_given_up_on() {
+        let known = BTreeMap::from([("k8s_exec".to_string(), "0.1.0".to_string())]);
+        let args = vec!["k8s_exec".to_string()];
+        let mut plugin = supervise(0, args, &std::env::temp_dir(), &known);
+        for failure in 1..MAX_QUICK_FAILURES {
+            plugin.started = Instant::now();
+            exited(&mut plugin, exit_status(1));
+            assert_eq!(plugin.status.state, State::Restarting);
+            assert_eq!(plugin.quick_failures, failure);
+        }
+        assert_eq!(
+            plugin.backoff,
+            INITIAL_BACKOFF * 2u32.pow(MAX_QUICK_FAILURES - 1)
+        );
+        plugin.started = Instant::now();
+        exited(&mut plugin, exit_status(1));
+        assert_eq!(plugin.status.state, State::Failed);
+
+        // A failure after a stable run starts over, and a clean exit is done
+        let mut plugin = supervise(
+            0,
+            vec!["k8s_exec".to_string()],
+            &std::env::temp_dir(),
+            &known,
+        );
+        plugin.quick_failures = MAX_QUICK_FAILURES - 1;
+        plugin.backoff = MAX_BACKOFF;
+        if let Some(started) = Instant::now().checked_sub(STABLE_AFTER) {
+            plugin.started = started;
+            exited(&mut plugin, exit_status(1));
+            assert_eq!(plugin.status.state, State::Restarting);
+            assert_eq!(plugin.quick_failures, 1);
+            assert_eq!(plugin.backoff, INITIAL_BACKOFF * 2);
+        }
+        exited(&mut plugin, exit_status(0));
+        assert_eq!(plugin.status.state, State::Stopped);
+    }
+}
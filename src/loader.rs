@@ -0,0 +1,273 @@
+// Loading plugin libraries. A plugin library exports `create_plugin`, which returns the
+// plugin, and `PLUGIN_API_VERSION`, the plugin API it was built against. The plugin is
+// only created when that is the API this host was built with: `create_plugin` hands
+// over a `Box<dyn Plugin>`, and a plugin built against another API lays it out
+// differently, which would crash on the first call. The library has to stay loaded for
+// as long as the plugin is used.
+//
+// Plugin binaries (plugin_api::subprocess) are loaded by asking them to describe
+// themselves, and run in a child process; a crash there is reported instead of ending
+// `proxy` with it.
+use anyhow::{anyhow, Result};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use libloading::{Library, Symbol};
+use plugin_api::config::ConfigError;
+use plugin_api::subprocess;
+use plugin_api::{Plugin, PLUGIN_API_VERSION};
+use std::env::consts::DLL_EXTENSION;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A plugin and the library it came from, none for plugin binaries. Tuple fields drop in
+/// order, so the plugin is dropped before its code is unloaded.
+pub type Loaded = (Box<dyn Plugin>, Option<Library>);
+
+/// How long a plugin binary gets to describe itself
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Loads the library at `path` and creates its plugin; None when it exports no plugin,
+/// an error when it can't be loaded or was built against another plugin API
+pub fn load(path: &Path) -> Result<Option<Loaded>> {
+    unsafe {
+        let lib = Library::new(path).map_err(|e| anyhow!("{}", e))?;
+        let plugin = {
+            let constructor: Result<Symbol<unsafe extern "C" fn() -> Box<dyn Plugin>>, _> =
+                lib.get(b"create_plugin");
+            let Ok(constructor) = constructor else {
+                return Ok(None);
+            };
+            let version: Option<Symbol<*const u32>> = lib.get(b"PLUGIN_API_VERSION").ok();
+            match version.map(|version| **version) {
+                Some(PLUGIN_API_VERSION) => {}
+                Some(version) => {
+                    return Err(anyhow!(
+                        "built against plugin API {}, this proxy uses {}; rebuild the plugin or install a version made for this proxy",
+                        version,
+                        PLUGIN_API_VERSION
+                    ))
+                }
+                None => {
+                    return Err(anyhow!(
+                        "built against a plugin API older than {}, which this proxy uses; rebuild the plugin",
+                        PLUGIN_API_VERSION
+                    ))
+                }
+            }
+            constructor()
+        };
+        Ok(Some((plugin, Some(lib))))
+    }
+}
+
+/// Whether `path` is a plugin: one of this platform's shared libraries (.so on Linux,
+/// .dylib on macOS, .dll on Windows) other than plugin_api's own, or a plugin binary
+pub fn is_plugin_file(path: &Path) -> bool {
+    if path.extension().and_then(|s| s.to_str()) == Some(DLL_EXTENSION) {
+        path.file_name().and_then(|s| s.to_str())
+            != Some(crate::registry::library_file_name("plugin_api").as_str())
+    } else {
+        subprocess::is_plugin_binary(path)
+    }
+}
+
+/// Loads the plugin library or plugin binary at `path`; None when it isn't a plugin
+pub fn load_file(path: &Path) -> Result<Option<Loaded>> {
+    if !is_plugin_file(path) {
+        Ok(None)
+    } else if subprocess::is_plugin_binary(path) {
+        load_binary(path).map(Some)
+    } else {
+        load(path)
+    }
+}
+
+/// A plugin binary, run in a child process
+struct ProcessPlugin {
+    path: PathBuf,
+    name: &'static str,
+    version: &'static str,
+    description: &'static str,
+    command: Command,
+    sample_config: Option<&'static str>,
+}
+
+/// Loads the plugin binary at `path` by asking it what it is
+pub fn load_binary(path: &Path) -> Result<Loaded> {
+    let description = subprocess::describe(path, DESCRIBE_TIMEOUT)?;
+    let leak = |text: String| -> &'static str { Box::leak(text.into_boxed_str()) };
+    let plugin = ProcessPlugin {
+        path: path.to_path_buf(),
+        command: description.command.to_command(),
+        name: leak(description.name),
+        version: leak(description.version),
+        description: leak(description.description),
+        sample_config: description.sample_config.map(leak),
+    };
+    Ok((Box::new(plugin), None))
+}
+
+#[cfg(unix)]
+fn signal_name(signal: i32) -> String {
+    let name = unsafe { libc::strsignal(signal) };
+    if name.is_null() {
+        return format!("signal {}", signal);
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name) };
+    format!("{} (signal {})", name.to_string_lossy(), signal)
+}
+
+/// The arguments `matches` was parsed from with `cmd`, in the order given, for the plugin
+/// binary to parse again. Only what was on the command line: defaults are the binary's.
+/// Global arguments are taken at the top only, as clap copies them into subcommands.
+fn command_line(cmd: &Command, matches: &ArgMatches, top: bool) -> Vec<OsString> {
+    let mut given: Vec<(usize, Vec<OsString>)> = Vec::new();
+    for arg in cmd.get_arguments() {
+        let id = arg.get_id().as_str();
+        if (arg.is_global_set() && !top)
+            || matches.value_source(id) != Some(ValueSource::CommandLine)
+        {
+            continue;
+        }
+        let indices: Vec<usize> = matches
+            .indices_of(id)
+            .map(|indices| indices.collect())
+            .unwrap_or_default();
+        let flag = match (arg.get_long(), arg.get_short()) {
+            (Some(long), _) => Some(format!("--{}", long)),
+            (None, Some(short)) => Some(format!("-{}", short)),
+            (None, None) => None,
+        };
+        if !arg.get_action().takes_values() {
+            // A flag, as often as it was given: -vv has one index for both
+            if let (Some(flag), Some(&index)) = (flag, indices.first()) {
+                let times = match arg.get_action() {
+                    ArgAction::Count => matches.get_count(id).into(),
+                    _ => indices.len(),
+                };
+                given.push((index, vec![OsString::from(flag); times]));
+            }
+            continue;
+        }
+        let Ok(Some(occurrences)) = matches.try_get_raw_occurrences(id) else {
+            continue;
+        };
+        // An index for each value, in order
+        let mut position = 0;
+        for values in occurrences {
+            let values: Vec<OsString> = values.map(OsString::from).collect();
+            let Some(&index) = indices.get(position) else {
+                break;
+            };
+            position += values.len();
+            let words = match &flag {
+                // With `=`, so a value starting with a dash isn't taken for a flag
+                Some(flag) if values.len() == 1 => {
+                    let mut word = OsString::from(format!("{}=", flag));
+                    word.push(&values[0]);
+                    vec![word]
+                }
+                Some(flag) => std::iter::once(OsString::from(flag))
+                    .chain(values)
+                    .collect(),
+                None if arg.is_trailing_var_arg_set() || arg.is_last_set() => {
+                    std::iter::once(OsString::from("--"))
+                        .chain(values)
+                        .collect()
+                }
+                None => values,
+            };
+            given.push((index, words));
+        }
+    }
+    given.sort_by_key(|(index, _)| *index);
+    let mut args: Vec<OsString> = given.into_iter().flat_map(|(_, words)| words).collect();
+
+    if let Some((name, sub_matches)) = matches.subcommand() {
+        if let Some(sub) = cmd.find_subcommand(name) {
+            args.push(sub.get_name().into());
+            args.extend(command_line(sub, sub_matches, false));
+        }
+    }
+    args
+}
+
+impl Plugin for ProcessPlugin {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn version(&self) -> &'static str {
+        self.version
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn subcommand(&self) -> Command {
+        self.command.clone()
+    }
+
+    fn sample_config(&self) -> Option<&'static str> {
+        self.sample_config
+    }
+
+    fn validate_config(&self, config: &str) -> Result<(), Vec<ConfigError>> {
+        subprocess::validate_config(&self.path, config, DESCRIBE_TIMEOUT).unwrap_or_else(|e| {
+            Err(vec![ConfigError {
+                path: String::new(),
+                message: format!("the plugin could not check it: {}", e),
+            }])
+        })
+    }
+
+    fn run(&self, matches: &ArgMatches) {
+        // The binary parses its command line itself, so it gets the arguments given
+        let mut command = std::process::Command::new(&self.path);
+        command.args(command_line(&self.command, matches, true));
+        // For the proxy commands the plugin runs, e.g. to read vault: secrets
+        if let Ok(exe) = plugin_api::proxy_exe() {
+            command.env(plugin_api::PROXY_EXE_ENV, exe);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.arg0(format!("proxy {}", self.name));
+        }
+        let status = match command.status() {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("❌ Failed to start {}: {}", self.path.display(), e);
+                std::process::exit(1);
+            }
+        };
+        if status.success() {
+            return;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                eprintln!(
+                    "❌ The {} plugin crashed: {}{}",
+                    self.name,
+                    signal_name(signal),
+                    if status.core_dumped() {
+                        ", core dumped"
+                    } else {
+                        ""
+                    }
+                );
+                std::process::exit(128 + signal);
+            }
+        }
+        let code = status.code().unwrap_or(1);
+        // Rust exits with 101 after a panic, which the plugin printed
+        if code == 101 {
+            eprintln!("❌ The {} plugin panicked", self.name);
+        }
+        std::process::exit(code);
+    }
+}
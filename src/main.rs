@@ -1,18 +1,43 @@
 use clap::{Arg, Command};
-use libloading::{Library, Symbol};
-use plugin_api::Plugin;
+use plugin_api::telemetry::{self, Span};
+use std::env::consts::DLL_EXTENSION;
 use std::fs;
 use std::path::PathBuf;
 
+mod audit;
+mod config;
+mod control;
+mod daemon;
+mod loader;
+mod man;
+mod metrics;
+mod notify;
+mod project;
+mod record;
+mod registry;
+mod scenario;
+mod usage;
+
 /// Proxy CLI
 fn main() {
+    run();
+    telemetry::shutdown();
+}
+
+/// Exits with `code` once the spans of the run are sent
+fn exit(code: i32) -> ! {
+    telemetry::shutdown();
+    std::process::exit(code)
+}
+
+fn run() {
     // Determine plugin directory from environment variable or default
     let plugin_dir = std::env::var_os("PROXY_PLUGIN_DIR")
         .map(PathBuf::from)
         .or_else(|| dirs::home_dir().map(|h| h.join(".cohandv/proxy/plugins")))
         .expect("Could not determine plugin directory");
 
-    println!("Loading plugins from: {}", plugin_dir.display());
+    eprintln!("Loading plugins from: {}", plugin_dir.display());
 
     let mut app = Command::new("proxy")
         .version("0.1.0")
@@ -22,33 +47,412 @@ fn main() {
                 .long("list-plugins")
                 .help("List all available plugins with their versions")
                 .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .help("Record the plugin's terminal output for `proxy replay`")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Play back a recorded plugin run, or list the recordings")
+                .arg(
+                    Arg::new("recording")
+                        .value_name("FILE")
+                        .help("Recording file, or its name in the recordings directory"),
+                )
+                .arg(
+                    Arg::new("speed")
+                        .long("speed")
+                        .short('s')
+                        .value_name("FACTOR")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("1")
+                        .help("Play faster (2) or slower (0.5)"),
+                )
+                .arg(
+                    Arg::new("idle-limit")
+                        .long("idle-limit")
+                        .short('i')
+                        .value_name("SECONDS")
+                        .value_parser(clap::value_parser!(f64))
+                        .help("Shorten pauses to at most this long"),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Export the audit log of privileged actions")
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .value_name("WHEN")
+                        .help("Only entries since this long ago (7d, 24h), date (2024-05-01) or RFC 3339 time"),
+                )
+                .arg(
+                    Arg::new("plugin")
+                        .long("plugin")
+                        .short('p')
+                        .value_name("PLUGIN")
+                        .help("Only entries of this plugin"),
+                )
+                .arg(
+                    Arg::new("action")
+                        .long("action")
+                        .short('a')
+                        .value_name("ACTION")
+                        .help("Only entries of this action, e.g. pod_exec"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["table", "json", "csv"])
+                        .default_value("table")
+                        .help("Output format; json writes one entry per line"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Write to this file instead of stdout"),
+                ),
+        )
+        .subcommand(
+            Command::new("usage")
+                .about("Report which targets the plugins tunneled to and how much data moved")
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .value_name("WHEN")
+                        .default_value("7d")
+                        .help("Targets used since this long ago (7d, 24h), date (2024-05-01) or RFC 3339 time"),
+                )
+                .arg(
+                    Arg::new("plugin")
+                        .long("plugin")
+                        .short('p')
+                        .value_name("PLUGIN")
+                        .help("Only the usage of this plugin"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("plugin")
+                .about("Find, install, update and remove plugins")
+                .subcommand_required(true)
+                .arg(
+                    Arg::new("index")
+                        .long("index")
+                        .value_name("URL")
+                        .global(true)
+                        .help("Plugin index URL or file (default: $PROXY_PLUGIN_INDEX or the community index)"),
+                )
+                .subcommand(
+                    Command::new("search")
+                        .about("List the plugins in the index matching a term")
+                        .arg(
+                            Arg::new("term")
+                                .value_name("TERM")
+                                .help("Part of a plugin name or description; all plugins when omitted"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("info")
+                        .about("Show a plugin's versions, platforms and downloads in the index")
+                        .arg(
+                            Arg::new("name")
+                                .value_name("PLUGIN")
+                                .required(true)
+                                .help("Plugin name as proxy plugin search lists it"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("install")
+                        .about("Install a plugin from the index, a URL or a file into the plugin directory")
+                        .arg(
+                            Arg::new("plugin")
+                                .value_name("PLUGIN|URL|PATH")
+                                .required(true)
+                                .help("Plugin name as proxy plugin search lists it, or a plugin library or .tar.gz"),
+                        )
+                        .arg(
+                            Arg::new("sha256")
+                                .long("sha256")
+                                .value_name("HEX")
+                                .help("Expected SHA-256 checksum of the download, required for URLs the index doesn't list"),
+                        )
+                        .arg(
+                            Arg::new("allow-http")
+                                .long("allow-http")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Allow downloads and indexes over plain http://"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("update")
+                        .about("Update plugins from where they were installed from")
+                        .arg(
+                            Arg::new("name")
+                                .value_name("PLUGIN")
+                                .help("Only this plugin; all installed with proxy plugin install when omitted"),
+                        )
+                        .arg(
+                            Arg::new("allow-http")
+                                .long("allow-http")
+                                .action(clap::ArgAction::SetTrue)
+                                .help("Allow downloads and indexes over plain http://"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Remove a plugin from the plugin directory")
+                        .arg(
+                            Arg::new("name")
+                                .value_name("PLUGIN")
+                                .required(true)
+                                .help("Plugin name, as proxy --list-plugins shows it"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("man")
+                .about("Write man pages for proxy and every subcommand, plugins included")
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("DIR")
+                        .default_value("man")
+                        .help("Directory to write the pages to"),
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run a scenario: ordered steps across plugins, with a final report")
+                .arg(
+                    Arg::new("scenario")
+                        .value_name("SCENARIO")
+                        .required(true)
+                        .help("Scenario file (TOML)"),
+                ),
+        )
+        .subcommand(Command::new("up").about(
+            "Start the plugins listed in the project config (.proxy.toml) in the background",
+        ))
+        .subcommand(
+            Command::new("project")
+                .about("Trust project configs (.proxy.toml), which are ignored until then")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("allow")
+                        .about("Trust a project config as it is now")
+                        .arg(
+                            Arg::new("path")
+                                .value_name("PATH")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .help("The project config; the one that applies here when omitted"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("deny")
+                        .about("Stop trusting a project config")
+                        .arg(
+                            Arg::new("path")
+                                .value_name("PATH")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .help("The project config; the one that applies here when omitted"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Manage plugin config files")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("init")
+                        .about("Create a plugin's config file from its sample config")
+                        .arg(Arg::new("plugin").required(true).help("Plugin name"))
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .help("Replace an existing config file")
+                                .action(clap::ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("validate")
+                        .about("Check plugin config files for unknown keys and wrong values")
+                        .arg(
+                            Arg::new("plugin")
+                                .help("Only check this plugin's config; all in plugins.d when omitted"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("daemon")
+                .about("Run several plugins at once, restarted when they fail")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("start")
+                        .about("Start the plugin commands under a supervisor")
+                        .arg(
+                            Arg::new("commands")
+                                .value_name("COMMAND")
+                                .num_args(1..)
+                                .help("Plugin command lines, each quoted and split as a shell would, e.g. \"k8s_exec --pod api-0 -- sh -c 'ls -la'\"; the project config's `up` list when omitted"),
+                        )
+                        .arg(
+                            Arg::new("detach")
+                                .long("detach")
+                                .short('d')
+                                .help("Run the supervisor in the background")
+                                .action(clap::ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("control")
+                                .long("control")
+                                .value_name("ADDRESS")
+                                .num_args(0..=1)
+                                .require_equals(true)
+                                .default_missing_value(control::DEFAULT_LISTEN)
+                                .help("Also serve the control API (see serve-control) from the supervisor"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("status")
+                        .about("Show the supervised plugins and their state")
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .value_parser(["table", "json"])
+                                .default_value("table")
+                                .help("Output format"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("stop").about("Stop the daemon and the plugins it runs"),
+                ),
+        )
+        .subcommand(
+            Command::new("notify")
+                .about("Send a notification through the channels in notifications.conf")
+                .arg(
+                    Arg::new("title")
+                        .long("title")
+                        .value_name("TITLE")
+                        .allow_hyphen_values(true)
+                        .default_value("proxy")
+                        .help("Notification title"),
+                )
+                .arg(
+                    Arg::new("body")
+                        .long("body")
+                        .value_name("TEXT")
+                        .allow_hyphen_values(true)
+                        .default_value("Test notification")
+                        .help("Notification text"),
+                )
+                .arg(
+                    Arg::new("event")
+                        .long("event")
+                        .short('e')
+                        .value_name("EVENT")
+                        .default_value("test")
+                        .help("Event name, matched against the configured events"),
+                )
+                .arg(
+                    Arg::new("plugin")
+                        .long("plugin")
+                        .short('p')
+                        .value_name("PLUGIN")
+                        .default_value("proxy")
+                        .help("Plugin the notification comes from"),
+                ),
+        )
+        .subcommand(
+            Command::new("serve-metrics")
+                .about("Serve the metrics of every running plugin on one Prometheus endpoint")
+                .arg(
+                    Arg::new("listen")
+                        .long("listen")
+                        .short('l')
+                        .value_name("ADDRESS")
+                        .default_value(metrics::DEFAULT_LISTEN)
+                        .help("Address to listen on"),
+                ),
+        )
+        .subcommand(
+            Command::new("serve-control")
+                .about("Serve a JSON API to list, start and stop plugin processes and forwards")
+                .arg(
+                    Arg::new("listen")
+                        .long("listen")
+                        .short('l')
+                        .value_name("ADDRESS")
+                        .default_value(control::DEFAULT_LISTEN)
+                        .help("Address to listen on"),
+                ),
         );
 
-    let mut plugins = Vec::new();
+    let root = Span::start("proxy");
+    let discovery = root.child("discover plugins");
+    discovery.set_attribute("proxy.plugin_dir", plugin_dir.display());
+    let mut plugins: Vec<loader::Loaded> = Vec::new();
+    // The built-in subcommands and clap's help, which a plugin can't take the name of
+    let reserved: Vec<String> = app
+        .get_subcommands()
+        .flat_map(|command| std::iter::once(command.get_name()).chain(command.get_all_aliases()))
+        .chain(["help"])
+        .map(str::to_string)
+        .collect();
+    // Where each plugin was loaded from, for `proxy plugin remove`
+    let mut loaded_from: Vec<(&'static str, PathBuf)> = Vec::new();
 
+    // Plugins are this platform's shared libraries: .so on Linux, .dylib on macOS and
+    // .dll on Windows, and plugin binaries (proxy-plugin-<name>) run in a child process
     if let Ok(entries) = fs::read_dir(&plugin_dir) {
         for entry in entries.flatten() {
             let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("dylib") {
-                // Optionally skip known non-plugin dylibs
-                if let Some(fname) = path.file_name().and_then(|s| s.to_str()) {
-                    if fname == "libplugin_api.dylib" {
+            // Kept as a tuple, whose fields drop in order: the plugin before its library
+            match loader::load_file(&path) {
+                Ok(Some(loaded)) => {
+                    let name = loaded.0.name();
+                    if plugins.iter().any(|(plugin, _)| plugin.name() == name) {
+                        eprintln!(
+                            "⚠️  Skipping {}: a {} plugin is already loaded",
+                            path.display(),
+                            name
+                        );
                         continue;
                     }
-                }
-                unsafe {
-                    let lib = Library::new(&path).unwrap();
-                    let constructor: Result<Symbol<unsafe extern "C" fn() -> Box<dyn Plugin>>, _> =
-                        lib.get(b"create_plugin");
-                    if let Ok(constructor) = constructor {
-                        let plugin = constructor();
-                        app = app.subcommand((*plugin).subcommand());
-                        plugins.push((lib, plugin)); // Keep lib alive!
+                    if reserved.iter().any(|command| command == name) {
+                        eprintln!(
+                            "⚠️  Skipping {}: {} is a built-in command",
+                            path.display(),
+                            name
+                        );
+                        continue;
                     }
+                    app = app.subcommand(loaded.0.subcommand());
+                    loaded_from.push((name, path));
+                    plugins.push(loaded);
                 }
+                Ok(None) => {}
+                Err(e) => eprintln!("⚠️  Skipping {}: {}", path.display(), e),
             }
         }
     }
+    discovery.set_int("proxy.plugins", plugins.len() as i64);
+    drop(discovery);
 
     let mut app_clone = app.clone();
     let matches = app.get_matches();
@@ -63,15 +467,16 @@ fn main() {
             println!("❌ No plugins found in: {}", plugin_dir.display());
             println!();
             println!("💡 To install plugins:");
-            println!("   1. Download plugin .dylib/.so/.dll files");
+            println!("   1. Download plugin .{} files", DLL_EXTENSION);
             println!("   2. Copy to: {}", plugin_dir.display());
             println!("   3. Run: proxy --list-plugins");
+            println!("   Or find them with: proxy plugin search <term>");
         } else {
             println!("┌──────────────────────┬────────────┬──────────────────────────────────┐");
             println!("│ Plugin Name          │ Version    │ Description                      │");
             println!("├──────────────────────┼────────────┼──────────────────────────────────┤");
 
-            for (_, plugin) in &plugins {
+            for (plugin, _) in &plugins {
                 let name = plugin.name();
                 let version = plugin.version();
                 let description = plugin.description();
@@ -100,10 +505,284 @@ fn main() {
         return;
     }
 
+    if let Some(sub_m) = matches.subcommand_matches("audit") {
+        let filter = audit::Filter {
+            since: sub_m.get_one::<String>("since").cloned(),
+            plugin: sub_m.get_one::<String>("plugin").cloned(),
+            action: sub_m.get_one::<String>("action").cloned(),
+        };
+        let format = sub_m.get_one::<String>("format").unwrap();
+        let output = sub_m.get_one::<String>("output").map(String::as_str);
+        if let Err(e) = audit::export(&filter, format, output) {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("usage") {
+        let filter = usage::Filter {
+            since: sub_m.get_one::<String>("since").unwrap().clone(),
+            plugin: sub_m.get_one::<String>("plugin").cloned(),
+        };
+        let json = sub_m.get_one::<String>("format").unwrap() == "json";
+        if let Err(e) = usage::report(&filter, json) {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("plugin") {
+        let index = registry::index_location(sub_m.get_one::<String>("index").map(String::as_str));
+        let result = match sub_m.subcommand() {
+            Some(("search", search_m)) => {
+                let installed: Vec<&str> =
+                    plugins.iter().map(|(plugin, _)| plugin.name()).collect();
+                registry::search(
+                    &index,
+                    search_m.get_one::<String>("term").map(String::as_str),
+                    &installed,
+                )
+            }
+            Some(("info", info_m)) => {
+                let name = info_m.get_one::<String>("name").unwrap();
+                let installed = plugins
+                    .iter()
+                    .find(|(plugin, _)| plugin.name() == name)
+                    .map(|(plugin, _)| plugin.version());
+                registry::info(&index, name, installed, &plugin_dir)
+            }
+            Some(("install", install_m)) => registry::install(
+                &index,
+                install_m.get_one::<String>("plugin").unwrap(),
+                install_m.get_one::<String>("sha256").map(String::as_str),
+                install_m.get_flag("allow-http"),
+                &plugin_dir,
+            ),
+            Some(("update", update_m)) => {
+                let installed: Vec<(&str, &str)> = plugins
+                    .iter()
+                    .map(|(plugin, _)| (plugin.name(), plugin.version()))
+                    .collect();
+                registry::update(
+                    &index,
+                    update_m.get_one::<String>("name").map(String::as_str),
+                    &installed,
+                    update_m.get_flag("allow-http"),
+                    &plugin_dir,
+                )
+            }
+            Some(("remove", remove_m)) => {
+                let name = remove_m.get_one::<String>("name").unwrap();
+                let loaded = loaded_from
+                    .iter()
+                    .find(|(plugin, _)| plugin == name)
+                    .map(|(_, path)| path.as_path());
+                registry::remove(name, loaded, &plugin_dir)
+            }
+            _ => unreachable!("a plugin subcommand is required"),
+        };
+        if let Err(e) = result {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("man") {
+        let output = PathBuf::from(sub_m.get_one::<String>("output").unwrap());
+        let names: Vec<&str> = plugins.iter().map(|(plugin, _)| plugin.name()).collect();
+        match man::generate_all(app_clone.clone(), &names, &output) {
+            Ok(written) => println!(
+                "✅ Wrote {} man pages to {}",
+                written.len(),
+                output.display()
+            ),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("run") {
+        let path = PathBuf::from(sub_m.get_one::<String>("scenario").unwrap());
+        if let Err(e) = scenario::run(&path) {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("config") {
+        let result = match sub_m.subcommand() {
+            Some(("init", init_m)) => {
+                let name = init_m.get_one::<String>("plugin").unwrap();
+                match plugins.iter().find(|(plugin, _)| plugin.name() == name) {
+                    Some((plugin, _)) => config::init(plugin.as_ref(), init_m.get_flag("force")),
+                    None => Err(anyhow::anyhow!(
+                        "{} is not an installed plugin, see proxy --list-plugins",
+                        name
+                    )),
+                }
+            }
+            Some(("validate", validate_m)) => {
+                let installed: Vec<&dyn plugin_api::Plugin> =
+                    plugins.iter().map(|(plugin, _)| plugin.as_ref()).collect();
+                config::validate(
+                    &installed,
+                    validate_m.get_one::<String>("plugin").map(String::as_str),
+                )
+            }
+            _ => unreachable!("a config subcommand is required"),
+        };
+        if let Err(e) = result {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("daemon") {
+        let result = match sub_m.subcommand() {
+            Some(("start", start_m)) => {
+                let commands: Vec<String> = start_m
+                    .get_many::<String>("commands")
+                    .map(|commands| commands.cloned().collect())
+                    .unwrap_or_default();
+                let installed: Vec<(String, String)> = plugins
+                    .iter()
+                    .map(|(plugin, _)| (plugin.name().to_string(), plugin.version().to_string()))
+                    .collect();
+                // The supervisor runs no plugin itself. Unloading them lets it load the
+                // updated libraries when reloading, where loading from the same path again
+                // would hand back the library already loaded; the commands go first, as
+                // they hold the plugins' argument parsers.
+                let control = start_m
+                    .get_one::<String>("control")
+                    .map(|listen| (listen.clone(), control::PluginInfo::of(&plugins)));
+                drop(app_clone);
+                drop(plugins);
+                daemon::start(
+                    &commands,
+                    &installed,
+                    &plugin_dir,
+                    start_m.get_flag("detach"),
+                    control,
+                )
+            }
+            Some(("status", status_m)) => {
+                daemon::status(status_m.get_one::<String>("format").unwrap() == "json")
+            }
+            Some(("stop", _)) => daemon::stop(),
+            _ => unreachable!("a daemon subcommand is required"),
+        };
+        if let Err(e) = result {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("project") {
+        let result = match sub_m.subcommand() {
+            Some(("allow", allow_m)) => {
+                project::allow(allow_m.get_one::<PathBuf>("path").map(PathBuf::as_path))
+            }
+            Some(("deny", deny_m)) => {
+                project::deny(deny_m.get_one::<PathBuf>("path").map(PathBuf::as_path))
+            }
+            _ => unreachable!("a project subcommand is required"),
+        };
+        if let Err(e) = result {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if matches.subcommand_matches("up").is_some() {
+        let installed: Vec<&str> = plugins.iter().map(|(plugin, _)| plugin.name()).collect();
+        if let Err(e) = project::up(&installed) {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("notify") {
+        let value = |name: &str| sub_m.get_one::<String>(name).unwrap().clone();
+        let notification = notify::Notification {
+            plugin: value("plugin"),
+            event: value("event"),
+            title: value("title"),
+            body: value("body"),
+        };
+        if let Err(e) = notify::send(&notification) {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("serve-metrics") {
+        let listen = sub_m.get_one::<String>("listen").unwrap();
+        if let Err(e) = metrics::serve(listen) {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("serve-control") {
+        let listen = sub_m.get_one::<String>("listen").unwrap();
+        if let Err(e) = control::serve(listen, control::PluginInfo::of(&plugins)) {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("replay") {
+        let result = match sub_m.get_one::<String>("recording") {
+            Some(recording) => record::replay(
+                recording,
+                *sub_m.get_one::<f64>("speed").unwrap(),
+                sub_m.get_one::<f64>("idle-limit").copied(),
+            ),
+            None => record::list(),
+        };
+        if let Err(e) = result {
+            eprintln!("❌ {}", e);
+            exit(1);
+        }
+        return;
+    }
+
     // Handle plugin subcommands
-    for (_, plugin) in plugins {
+    for (plugin, _) in &plugins {
         if let Some(sub_m) = matches.subcommand_matches(plugin.name()) {
+            let dispatch = root.child(&format!("run {}", plugin.name()));
+            dispatch.set_attribute("proxy.plugin", plugin.name());
+            dispatch.set_attribute("proxy.plugin_version", plugin.version());
+            // The plugin's spans go under this one
+            if let Some(traceparent) = dispatch.traceparent() {
+                std::env::set_var(telemetry::TRACEPARENT, traceparent);
+            }
+            if matches.get_flag("record") {
+                let args: Vec<String> = std::env::args()
+                    .skip(1)
+                    .filter(|arg| arg != "--record")
+                    .collect();
+                let title = format!("proxy {}", args.join(" "));
+                if let Err(e) = record::start(plugin.name(), &title) {
+                    eprintln!("⚠️  Not recording: {}", e);
+                }
+            }
             (*plugin).run(sub_m);
+            record::finish();
             return;
         }
     }
@@ -0,0 +1,191 @@
+// `proxy man` writes man pages for the host and for every subcommand, the plugins'
+// included, so packages can install them. Plugin pages also list the plugin's config
+// and state locations, and the host page the environment that moves them.
+use anyhow::{Context, Result};
+use clap::Command;
+use clap_mangen::roff::{bold, italic, roman, Inline, Roff};
+use clap_mangen::Man;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const ENVIRONMENT: [(&str, &str); 11] = [
+    ("PROXY_PLUGIN_DIR", "Directory plugins are loaded from"),
+    (
+        "PROXY_PLUGINS_CONFIG_DIR",
+        "Directory of the plugin config files",
+    ),
+    ("PROXY_STATE_DIR", "Directory plugins keep runtime state in"),
+    (
+        "PROXY_SECRETS_DIR",
+        "Directory secret:NAME config values are read from",
+    ),
+    (
+        "PROXY_AGE_KEY_FILE",
+        "age identity encrypted configs are decrypted with",
+    ),
+    ("PROXY_NOTIFICATIONS_CONFIG", "Notification channels config"),
+    (
+        "PROXY_PROJECT_CONFIG",
+        "Project config to use instead of the closest .proxy.toml, none when empty",
+    ),
+    (
+        "PROXY_PLUGIN_INDEX",
+        "Plugin index URL or file for proxy plugin",
+    ),
+    ("PROXY_AUDIT_LOG", "Audit log file"),
+    (
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        "OTLP/HTTP collector traces are sent to",
+    ),
+    (
+        "OTEL_SERVICE_NAME",
+        "Service name of the traces, proxy by default",
+    ),
+];
+
+fn host_files() -> Vec<(String, String)> {
+    [
+        ("~/.cohandv/proxy/plugins/", "Plugin libraries"),
+        ("~/.cohandv/proxy/config/plugins.d/", "Plugin config files"),
+        (
+            "~/.cohandv/proxy/config/notifications.conf",
+            "Notification channels",
+        ),
+        (
+            "~/.cohandv/proxy/config/age.key",
+            "age identity for encrypted configs",
+        ),
+        (
+            "~/.cohandv/proxy/secrets/",
+            "Secrets referenced from configs",
+        ),
+        (
+            "~/.cohandv/proxy/state/",
+            "Runtime state, logs, recordings, usage and the audit log",
+        ),
+        (
+            ".proxy.toml",
+            "Project config, in the current directory or a parent, once trusted",
+        ),
+        (
+            "~/.cohandv/proxy/config/trusted-projects",
+            "Project configs trusted with proxy project allow",
+        ),
+    ]
+    .iter()
+    .map(|(path, description)| (path.to_string(), description.to_string()))
+    .collect()
+}
+
+fn plugin_files(plugin: &str) -> Vec<(String, String)> {
+    vec![
+        (
+            format!("~/.cohandv/proxy/config/plugins.d/{}.conf", plugin),
+            "Config file (TOML), may be sops- or age-encrypted".to_string(),
+        ),
+        (
+            format!("~/.cohandv/proxy/config/plugins.d/{}.conf.d/", plugin),
+            "Config fragments, for plugins supporting split configs".to_string(),
+        ),
+        (
+            format!("~/.cohandv/proxy/state/{}/", plugin),
+            "Runtime state".to_string(),
+        ),
+        (
+            ".proxy.toml".to_string(),
+            format!(
+                "Project config; its [{}] table is merged over the config file",
+                plugin
+            ),
+        ),
+    ]
+}
+
+fn render_list(
+    roff: &mut Roff,
+    title: &str,
+    entries: &[(String, String)],
+    term: fn(String) -> Inline,
+) {
+    roff.control("SH", [title]);
+    for (name, description) in entries {
+        roff.control("TP", []);
+        roff.text([term(name.clone())]);
+        roff.text([roman(description.as_str())]);
+    }
+}
+
+fn render(cmd: &Command, plugin: Option<&str>, path: &Path) -> Result<()> {
+    let man = Man::new(cmd.clone()).source(format!("proxy {}", env!("CARGO_PKG_VERSION")));
+    let mut out =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    man.render_title(&mut out)?;
+    man.render_name_section(&mut out)?;
+    man.render_synopsis_section(&mut out)?;
+    man.render_description_section(&mut out)?;
+    if cmd.get_arguments().any(|arg| !arg.is_hide_set()) {
+        man.render_options_section(&mut out)?;
+    }
+    if cmd.get_subcommands().any(|sub| !sub.is_hide_set()) {
+        man.render_subcommands_section(&mut out)?;
+    }
+
+    let mut roff = Roff::new();
+    match plugin {
+        Some(plugin) => render_list(&mut roff, "FILES", &plugin_files(plugin), italic),
+        None => {
+            let environment: Vec<(String, String)> = ENVIRONMENT
+                .iter()
+                .map(|(name, description)| (name.to_string(), description.to_string()))
+                .collect();
+            render_list(&mut roff, "ENVIRONMENT", &environment, bold);
+            render_list(&mut roff, "FILES", &host_files(), italic);
+        }
+    }
+    roff.to_writer(&mut out)?;
+    if cmd.get_version().is_some() {
+        man.render_version_section(&mut out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes a page for `cmd` and each of its subcommands, named after the command line
+/// (proxy-plugin-search.1); `plugin` is the plugin the commands belong to
+fn generate(
+    cmd: &Command,
+    plugin: Option<&str>,
+    out_dir: &Path,
+    written: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let name = cmd.get_display_name().unwrap_or_else(|| cmd.get_name());
+    let path = out_dir.join(format!("{}.1", name));
+    render(cmd, plugin, &path)?;
+    written.push(path);
+    for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        generate(sub, plugin, out_dir, written)?;
+    }
+    Ok(())
+}
+
+/// Writes the pages of the host and its subcommands to `out_dir`; `plugins` names the
+/// subcommands that are plugins
+pub fn generate_all(app: Command, plugins: &[&str], out_dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(out_dir)
+        .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+    let mut app = app.disable_help_subcommand(true);
+    app.build();
+    let mut written = Vec::new();
+    let path = out_dir.join("proxy.1");
+    render(&app, None, &path)?;
+    written.push(path);
+    for sub in app.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        let plugin = plugins
+            .iter()
+            .find(|plugin| **plugin == sub.get_name())
+            .copied();
+        generate(sub, plugin, out_dir, &mut written)?;
+    }
+    Ok(written)
+}
@@ -0,0 +1,56 @@
+// `proxy serve-metrics`: one Prometheus endpoint for the metrics every running plugin
+// publishes through plugin_api::metrics, gathered fresh on each scrape.
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:9464";
+
+async fn respond(mut stream: TcpStream) -> Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < 8192 {
+        let n = stream.read(&mut buffer).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let (status, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", plugin_api::metrics::gather()),
+        ("GET", _) => ("404 Not Found", "Metrics are at /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+    let response = format!(
+        concat!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n",
+            "Content-Length: {}\r\nConnection: close\r\n\r\n{}"
+        ),
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+pub fn serve(listen: &str) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        let listener = TcpListener::bind(listen).await?;
+        println!("📈 Serving plugin metrics on http://{}/metrics", listen);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            tokio::spawn(async move {
+                if let Err(e) = respond(stream).await {
+                    eprintln!("⚠️  {}", e);
+                }
+            });
+        }
+    })
+}
@@ -0,0 +1,135 @@
+// `proxy notify` delivers a notification through the channels configured in
+// notifications.conf. Plugins run it with plugin_api::notify::send; it can also be run by
+// hand to try the configuration out.
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Deserialize)]
+struct NotifyConfig {
+    /// Show a desktop notification
+    desktop: Option<bool>,
+    /// Slack incoming webhook URL, or a secret reference to it
+    slack_webhook: Option<String>,
+    /// URL the notification is POSTed to as JSON, or a secret reference to it
+    webhook: Option<String>,
+    /// Shell command run with PROXY_NOTIFY_EVENT, _PLUGIN, _TITLE and _BODY set
+    command: Option<String>,
+    /// Only these events are sent; all of them when unset
+    events: Option<Vec<String>>,
+}
+
+pub struct Notification {
+    pub plugin: String,
+    pub event: String,
+    pub title: String,
+    pub body: String,
+}
+
+fn load_config() -> Result<NotifyConfig> {
+    let path = plugin_api::notify::config_path()
+        .ok_or_else(|| anyhow!("Could not determine the notifications config path"))?;
+    if !path.exists() {
+        return Err(anyhow!(
+            "No notification channels configured, create {}",
+            path.display()
+        ));
+    }
+    let content = plugin_api::read_config(&path)?;
+    toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn post(url: &str, payload: serde_json::Value) -> Result<()> {
+    let url = plugin_api::resolve_secret(url).map_err(|e| anyhow!(e))?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        reqwest::Client::new()
+            .post(&url)
+            .timeout(std::time::Duration::from_secs(10))
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    })
+}
+
+fn run_command(command: &str, notification: &Notification) -> Result<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("PROXY_NOTIFY_EVENT", &notification.event)
+        .env("PROXY_NOTIFY_PLUGIN", &notification.plugin)
+        .env("PROXY_NOTIFY_TITLE", &notification.title)
+        .env("PROXY_NOTIFY_BODY", &notification.body)
+        .stdin(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("exited with {}", status));
+    }
+    Ok(())
+}
+
+/// Sends the notification through every configured channel. A failing channel doesn't
+/// keep the others from being tried; the error lists the ones that failed.
+pub fn send(notification: &Notification) -> Result<()> {
+    let config = load_config()?;
+    if config
+        .events
+        .as_ref()
+        .is_some_and(|events| !events.contains(&notification.event))
+    {
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+    if config.desktop.unwrap_or(false) {
+        let shown = notify_rust::Notification::new()
+            .appname("proxy")
+            .summary(&notification.title)
+            .body(&notification.body)
+            .show();
+        if let Err(e) = shown {
+            eprintln!("⚠️  Desktop notification failed: {}", e);
+            failed.push("desktop");
+        }
+    }
+    if let Some(url) = &config.slack_webhook {
+        let text = format!("*{}*\n{}", notification.title, notification.body);
+        if let Err(e) = post(url, json!({ "text": text })) {
+            eprintln!("⚠️  Slack notification failed: {}", e);
+            failed.push("slack_webhook");
+        }
+    }
+    if let Some(url) = &config.webhook {
+        let payload = json!({
+            "event": notification.event,
+            "plugin": notification.plugin,
+            "title": notification.title,
+            "body": notification.body,
+            "time": chrono::Utc::now().to_rfc3339(),
+        });
+        if let Err(e) = post(url, payload) {
+            eprintln!("⚠️  Webhook notification failed: {}", e);
+            failed.push("webhook");
+        }
+    }
+    if let Some(command) = &config.command {
+        if let Err(e) = run_command(command, notification) {
+            eprintln!("⚠️  Notification command failed: {}", e);
+            failed.push("command");
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Notification not delivered by {}",
+            failed.join(", ")
+        ))
+    }
+}
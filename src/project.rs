@@ -0,0 +1,87 @@
+// `proxy up` starts the plugins the project config (.proxy.toml, see plugin_api::project)
+// lists under `up`, in the background. They run in the current directory, so they merge
+// the same project config over their own. `proxy project allow` trusts a project config,
+// which is ignored until then.
+use anyhow::{anyhow, Result};
+use plugin_api::processes;
+use std::path::{Path, PathBuf};
+
+/// `path`, or the project config that applies here
+fn given_or_found(path: Option<&Path>) -> Result<PathBuf> {
+    path.map(Path::to_path_buf)
+        .or_else(plugin_api::project::path)
+        .ok_or_else(|| {
+            anyhow!(
+                "No {} in this directory or its parents",
+                plugin_api::project::FILE_NAME
+            )
+        })
+}
+
+/// Trusts the project config at `path`, or the one that applies here, as it is now
+pub fn allow(path: Option<&Path>) -> Result<()> {
+    let path = plugin_api::project::allow(&given_or_found(path)?)?;
+    println!("✅ Trusted {}", path.display());
+    println!("💡 Plugins use it until it changes; allow it again after checking the changes");
+    Ok(())
+}
+
+/// Stops trusting the project config at `path`, or the one that applies here
+pub fn deny(path: Option<&Path>) -> Result<()> {
+    let path = given_or_found(path)?;
+    if plugin_api::project::deny(&path)? {
+        println!("🚫 No longer trusting {}", path.display());
+    } else {
+        println!("💡 {} was not trusted", path.display());
+    }
+    Ok(())
+}
+
+pub fn up(installed: &[&str]) -> Result<()> {
+    let (path, commands) = plugin_api::project::up()?.ok_or_else(|| {
+        anyhow!(
+            "No {} in this directory or its parents",
+            plugin_api::project::FILE_NAME
+        )
+    })?;
+    println!("📁 Project config: {}", path.display());
+    if commands.is_empty() {
+        println!("💡 List the plugins to start under `up`, e.g. up = [\"k8s_port_forward\"]");
+        return Ok(());
+    }
+    if let Some(unknown) = commands
+        .iter()
+        .map(|args| args[0].as_str())
+        .find(|plugin| !installed.contains(plugin))
+    {
+        return Err(anyhow!(
+            "`up` in {} names {}, which is not an installed plugin",
+            path.display(),
+            unknown
+        ));
+    }
+
+    let mut failed = 0;
+    for args in &commands {
+        match processes::start(args) {
+            Ok(started) => println!(
+                "▶️  Started {} (pid {}), log: {}",
+                args.join(" "),
+                started.pid,
+                started.log.display()
+            ),
+            Err(e) => {
+                eprintln!("❌ Failed to start {}: {}", args.join(" "), e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} plugins failed to start",
+            failed,
+            commands.len()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,317 @@
+// `proxy --record <plugin> ...` captures the plugin run's terminal output with its timing
+// in the asciinema v2 format, under `recordings/` in the state directory, and
+// `proxy replay` plays it back (so does `asciinema play`). The plugin runs in this
+// process, so its stdout and stderr are moved onto a pseudo terminal, or a pipe when the
+// output isn't a terminal, and a thread copies everything to the real output while
+// recording it.
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub fn recordings_dir() -> Option<PathBuf> {
+    plugin_api::plugin_state_dir("recordings")
+}
+
+#[cfg(unix)]
+mod capture {
+    use super::*;
+    use std::io::Read;
+    use std::os::fd::{FromRawFd, RawFd};
+    use std::sync::mpsc::{self, Receiver};
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    /// How long to wait for output still buffered when the run ends. Processes the plugin
+    /// started may hold the output open beyond that.
+    const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+    struct Recording {
+        path: PathBuf,
+        saved_stdout: RawFd,
+        saved_stderr: RawFd,
+        /// The write side stdout and stderr point at
+        output: RawFd,
+        done: Receiver<()>,
+    }
+
+    static ACTIVE: Mutex<Option<Recording>> = Mutex::new(None);
+
+    fn check(result: i32) -> io::Result<i32> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// How much of `pending` is text to record now: all of it but a UTF-8 sequence still
+    /// missing bytes. Invalid bytes are not waited for, they get replaced.
+    fn complete_len(pending: &[u8]) -> usize {
+        match std::str::from_utf8(pending) {
+            Ok(_) => pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => pending.len(),
+        }
+    }
+
+    /// Copies what the plugin writes to the terminal, recording it as output events
+    fn copy(mut input: File, mut terminal: File, mut cast: BufWriter<File>) {
+        let started = Instant::now();
+        let mut buffer = [0u8; 8192];
+        // A UTF-8 sequence split across reads waits for its remaining bytes
+        let mut pending: Vec<u8> = Vec::new();
+        loop {
+            // A closed pseudo terminal reads as an error, a closed pipe as end of file
+            let n = match input.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            let _ = terminal.write_all(&buffer[..n]);
+            pending.extend_from_slice(&buffer[..n]);
+            let complete = complete_len(&pending);
+            let text = String::from_utf8_lossy(&pending[..complete]).to_string();
+            pending.drain(..complete);
+            let event = json!([started.elapsed().as_secs_f64(), "o", text]);
+            let _ = writeln!(cast, "{}", event);
+            let _ = cast.flush();
+        }
+    }
+
+    /// The terminal size, the usual 80x24 when it isn't known
+    fn window_size() -> libc::winsize {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+        if !ok || size.ws_col == 0 {
+            size.ws_col = 80;
+            size.ws_row = 24;
+        }
+        size
+    }
+
+    pub fn start(plugin: &str, title: &str) -> Result<PathBuf> {
+        let dir =
+            recordings_dir().ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+        fs::create_dir_all(&dir)?;
+        let now = chrono::Local::now();
+        let path = dir.join(format!("{}-{}.cast", plugin, now.format("%Y%m%d-%H%M%S")));
+        let mut size = window_size();
+        let is_terminal = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
+        let mut cast = BufWriter::new(File::create(&path)?);
+        let header = json!({
+            "version": 2,
+            "width": size.ws_col,
+            "height": size.ws_row,
+            "timestamp": now.timestamp(),
+            "title": title,
+            "env": {
+                "SHELL": std::env::var("SHELL").unwrap_or_default(),
+                "TERM": std::env::var("TERM").unwrap_or_default(),
+            },
+        });
+        writeln!(cast, "{}", header)?;
+
+        // Reads on `input`, what the plugin writes goes to `output`
+        let (input, output) = unsafe {
+            let (mut input, mut output) = (0, 0);
+            if is_terminal {
+                check(libc::openpty(
+                    &mut input,
+                    &mut output,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::addr_of_mut!(size),
+                ))?;
+            } else {
+                let mut fds = [0; 2];
+                check(libc::pipe(fds.as_mut_ptr()))?;
+                (input, output) = (fds[0], fds[1]);
+            }
+            (input, output)
+        };
+
+        io::stdout().flush()?;
+        io::stderr().flush()?;
+        let (saved_stdout, saved_stderr, terminal) = unsafe {
+            let saved_stdout = check(libc::dup(libc::STDOUT_FILENO))?;
+            let saved_stderr = check(libc::dup(libc::STDERR_FILENO))?;
+            let terminal = File::from_raw_fd(check(libc::dup(saved_stdout))?);
+            check(libc::dup2(output, libc::STDOUT_FILENO))?;
+            check(libc::dup2(output, libc::STDERR_FILENO))?;
+            (saved_stdout, saved_stderr, terminal)
+        };
+        let input = unsafe { File::from_raw_fd(input) };
+        let (done_tx, done) = mpsc::channel();
+        std::thread::spawn(move || {
+            copy(input, terminal, cast);
+            let _ = done_tx.send(());
+        });
+
+        *ACTIVE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Recording {
+            path: path.clone(),
+            saved_stdout,
+            saved_stderr,
+            output,
+            done,
+        });
+        // Plugins often exit from their Ctrl-C handler instead of returning
+        unsafe {
+            libc::atexit(finish_at_exit);
+        }
+        Ok(path)
+    }
+
+    extern "C" fn finish_at_exit() {
+        finish();
+    }
+
+    /// Puts stdout and stderr back and waits for the rest of the output to be recorded
+    pub fn finish() {
+        let Some(recording) = ACTIVE.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        unsafe {
+            libc::dup2(recording.saved_stdout, libc::STDOUT_FILENO);
+            libc::dup2(recording.saved_stderr, libc::STDERR_FILENO);
+            libc::close(recording.saved_stdout);
+            libc::close(recording.saved_stderr);
+            libc::close(recording.output);
+        }
+        let _ = recording.done.recv_timeout(DRAIN_TIMEOUT);
+        eprintln!("🎬 Recorded to {}", recording.path.display());
+        eprintln!("💡 Replay: proxy replay {}", recording.path.display());
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn text_waits_for_the_rest_of_a_split_character() {
+            let text = "naïve".as_bytes();
+            assert_eq!(complete_len(text), text.len());
+            assert_eq!(complete_len(&text[..3]), 2);
+            assert_eq!(complete_len(&text[..4]), 4);
+            assert_eq!(complete_len(b"a\xffb"), 3);
+        }
+
+        #[test]
+        fn output_goes_to_the_terminal_and_into_the_cast() {
+            let dir = std::env::temp_dir().join(format!("proxy-record-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let (input, terminal, cast) = (dir.join("in"), dir.join("terminal"), dir.join("cast"));
+            fs::write(&input, "héllo\r\n").unwrap();
+            copy(
+                File::open(&input).unwrap(),
+                File::create(&terminal).unwrap(),
+                BufWriter::new(File::create(&cast).unwrap()),
+            );
+            assert_eq!(fs::read_to_string(&terminal).unwrap(), "héllo\r\n");
+            let cast = fs::read_to_string(&cast).unwrap();
+            let events: Vec<Value> = cast
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(events.len(), 1);
+            assert!(events[0][0].as_f64().is_some());
+            assert_eq!(events[0][1], "o");
+            assert_eq!(events[0][2], "héllo\r\n");
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use capture::{finish, start};
+
+#[cfg(not(unix))]
+pub fn start(_plugin: &str, _title: &str) -> Result<PathBuf> {
+    Err(anyhow!("Recording is only supported on Unix"))
+}
+
+#[cfg(not(unix))]
+pub fn finish() {}
+
+/// A recording by path, or by its file name in the recordings directory
+fn find(name: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(name);
+    if path.exists() {
+        return Ok(path);
+    }
+    let dir = recordings_dir().ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+    [dir.join(name), dir.join(format!("{}.cast", name))]
+        .into_iter()
+        .find(|path| path.exists())
+        .ok_or_else(|| anyhow!("No recording {}", name))
+}
+
+/// Prints the recordings in the state directory, newest first
+pub fn list() -> Result<()> {
+    let dir = recordings_dir().ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+    let mut recordings: Vec<(std::time::SystemTime, PathBuf)> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().extension().and_then(|s| s.to_str()) == Some("cast"))
+                .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+                .collect()
+        })
+        .unwrap_or_default();
+    if recordings.is_empty() {
+        println!("No recordings in {}", dir.display());
+        println!("💡 Record a run with: proxy --record <plugin> ...");
+        return Ok(());
+    }
+    recordings.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    println!("🎬 Recordings in {}:", dir.display());
+    for (_, path) in recordings {
+        let title = File::open(&path)
+            .ok()
+            .and_then(|file| BufReader::new(file).lines().next()?.ok())
+            .and_then(|header| serde_json::from_str::<Value>(&header).ok())
+            .and_then(|header| header["title"].as_str().map(str::to_string))
+            .unwrap_or_default();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        println!("  {:<48} {}", name, title);
+    }
+    Ok(())
+}
+
+/// Plays a recording back, `speed` times as fast, shortening pauses to `idle_limit`
+pub fn replay(name: &str, speed: f64, idle_limit: Option<f64>) -> Result<()> {
+    let path = find(name)?;
+    let file = File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut lines = BufReader::new(file).lines();
+    let header: Value = serde_json::from_str(&lines.next().transpose()?.unwrap_or_default())
+        .with_context(|| format!("{} is not an asciinema recording", path.display()))?;
+    if header["version"] != 2 {
+        return Err(anyhow!("Only asciinema v2 recordings can be replayed"));
+    }
+
+    let mut stdout = io::stdout();
+    let mut previous = 0.0;
+    for line in lines {
+        let line = line?;
+        let Ok(event) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        let (Some(time), Some("o"), Some(data)) =
+            (event[0].as_f64(), event[1].as_str(), event[2].as_str())
+        else {
+            continue;
+        };
+        let mut pause = (time - previous).max(0.0);
+        if let Some(limit) = idle_limit {
+            pause = pause.min(limit);
+        }
+        previous = time;
+        std::thread::sleep(Duration::from_secs_f64(pause / speed));
+        stdout.write_all(data.as_bytes())?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
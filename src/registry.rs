@@ -0,0 +1,864 @@
+// `proxy plugin search`, `info` and `install` work from a plugin index: a JSON or TOML
+// file listing plugins with their description, the platforms they run on and where to
+// get them, either a prebuilt library per platform or the repository to build them from.
+// The index is fetched from $PROXY_PLUGIN_INDEX or --index (a URL or a local file),
+// by default the community index.
+//
+// `install` also takes the URL or path of a library, or of a .tar.gz holding one. A library
+// is loaded before it is moved into the plugin directory, so only plugins get installed,
+// under the name the plugin gives itself. Where each plugin came from is kept in
+// .sources.json in the plugin directory, for `update` to go back to.
+use anyhow::{anyhow, Context, Result};
+use flate2::read::GzDecoder;
+use plugin_api::subprocess;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env::consts::{DLL_EXTENSION, DLL_PREFIX, EXE_SUFFIX};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::loader;
+
+pub const DEFAULT_INDEX: &str =
+    "https://raw.githubusercontent.com/cohandv/proxy-devops/main/plugins/index.json";
+
+#[derive(Debug, Deserialize)]
+struct Index {
+    plugins: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    name: String,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    description: String,
+    /// Platforms the plugin supports, as "<os>-<arch>"; any when empty
+    #[serde(default)]
+    platforms: Vec<String>,
+    /// Prebuilt libraries by platform
+    #[serde(default)]
+    libraries: BTreeMap<String, Library>,
+    /// Git repository to build the plugin from
+    repository: Option<String>,
+    /// Cargo package of the plugin in the repository, its name when unset
+    package: Option<String>,
+    homepage: Option<String>,
+    license: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Library {
+    url: String,
+    sha256: Option<String>,
+}
+
+/// The file in the plugin directory recording where the plugins were installed from
+const SOURCES_FILE: &str = ".sources.json";
+/// The `from` of plugins installed from the index
+const FROM_INDEX: &str = "index";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Source {
+    /// "index", or the URL or file the plugin was installed from
+    from: String,
+    /// Checksum of the installed library, to tell whether the source changed
+    sha256: String,
+}
+
+/// This machine's platform as the index names it, e.g. "linux-x86_64" or "macos-aarch64"
+fn platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// The file a plugin's library has on this platform: libNAME.so, libNAME.dylib or NAME.dll
+pub fn library_file_name(name: &str) -> String {
+    format!("{}{}.{}", DLL_PREFIX, name, DLL_EXTENSION)
+}
+
+/// The index location: --index, $PROXY_PLUGIN_INDEX or the community index
+pub fn index_location(index: Option<&str>) -> String {
+    index
+        .map(str::to_string)
+        .or_else(|| std::env::var("PROXY_PLUGIN_INDEX").ok())
+        .unwrap_or_else(|| DEFAULT_INDEX.to_string())
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let response = reqwest::get(url).await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    })
+}
+
+fn load_index(location: &str) -> Result<Index> {
+    let content = if is_url(location) {
+        fetch(location)
+            .map_err(|e| anyhow!("Failed to fetch the plugin index {}: {}", location, e))?
+    } else {
+        fs::read(location)
+            .map_err(|e| anyhow!("Failed to read the plugin index {}: {}", location, e))?
+    };
+    // A .toml index is TOML, as is one whose content doesn't start like a JSON object
+    let is_json = !location.ends_with(".toml")
+        && content
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .is_some_and(|byte| *byte == b'{');
+    if is_json {
+        serde_json::from_slice(&content)
+            .with_context(|| format!("Invalid plugin index {}", location))
+    } else {
+        let content = String::from_utf8(content)
+            .with_context(|| format!("Invalid plugin index {}", location))?;
+        toml::from_str(&content).with_context(|| format!("Invalid plugin index {}", location))
+    }
+}
+
+fn find<'a>(index: &'a Index, name: &str, location: &str) -> Result<&'a Entry> {
+    index
+        .plugins
+        .iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| {
+            anyhow!(
+                "No plugin named '{}' in {}, see proxy plugin search",
+                name,
+                location
+            )
+        })
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(width - 1).collect();
+        format!("{}…", cut)
+    }
+}
+
+/// Lists the plugins whose name or description contains `term`, every plugin without one
+pub fn search(location: &str, term: Option<&str>, installed: &[&str]) -> Result<()> {
+    let index = load_index(location)?;
+    let term = term.map(str::to_lowercase);
+    let found: Vec<&Entry> = index
+        .plugins
+        .iter()
+        .filter(|entry| {
+            term.as_deref().is_none_or(|term| {
+                entry.name.to_lowercase().contains(term)
+                    || entry.description.to_lowercase().contains(term)
+            })
+        })
+        .collect();
+    if found.is_empty() {
+        println!("❌ No plugins found in {}", location);
+        return Ok(());
+    }
+
+    let platform = platform();
+    println!(
+        "  {:<26} {:<9} {:<30} DESCRIPTION",
+        "NAME", "VERSION", "PLATFORMS"
+    );
+    for entry in &found {
+        let marker = if installed.contains(&entry.name.as_str()) {
+            "✓"
+        } else {
+            " "
+        };
+        let platforms = if entry.platforms.is_empty() {
+            "any".to_string()
+        } else {
+            entry.platforms.join(", ")
+        };
+        println!(
+            "{} {:<26} {:<9} {:<30} {}",
+            marker,
+            entry.name,
+            entry.version,
+            truncate(&platforms, 30),
+            entry.description
+        );
+    }
+    println!();
+    println!(
+        "📦 {} plugins (✓ installed), this platform is {}",
+        found.len(),
+        platform
+    );
+    println!(
+        "💡 Details with: proxy plugin info <name>, install with: proxy plugin install <name>"
+    );
+    Ok(())
+}
+
+/// Prints what the index knows about a plugin: its versions, platforms and downloads, and
+/// the version installed here. `installed` is the loaded plugin's version.
+pub fn info(location: &str, name: &str, installed: Option<&str>, plugin_dir: &Path) -> Result<()> {
+    let index = load_index(location)?;
+    let entry = find(&index, name, location)?;
+    let platform = platform();
+
+    println!("📦 {} {}", entry.name, entry.version);
+    if !entry.description.is_empty() {
+        println!("   {}", entry.description);
+    }
+    println!();
+    if let Some(homepage) = &entry.homepage {
+        println!("Homepage:    {}", homepage);
+    }
+    if let Some(license) = &entry.license {
+        println!("License:     {}", license);
+    }
+    match installed {
+        Some(version) => {
+            let from = match load_sources(plugin_dir).remove(name) {
+                Some(source) if source.from != FROM_INDEX => format!(" from {}", source.from),
+                _ => String::new(),
+            };
+            let status = if version == entry.version {
+                "up to date"
+            } else {
+                "update with proxy plugin update"
+            };
+            println!("Installed:   {}{} ({})", version, from, status);
+        }
+        None => println!("Installed:   no"),
+    }
+    let supported = entry.platforms.is_empty() || entry.platforms.contains(&platform);
+    println!(
+        "Platforms:   {} (this platform, {}, is {})",
+        if entry.platforms.is_empty() {
+            "any".to_string()
+        } else {
+            entry.platforms.join(", ")
+        },
+        platform,
+        if supported {
+            "supported"
+        } else {
+            "not supported"
+        }
+    );
+    if !entry.libraries.is_empty() {
+        println!("Downloads:");
+        for (library_platform, library) in &entry.libraries {
+            let marker = if *library_platform == platform {
+                "→"
+            } else {
+                " "
+            };
+            println!("  {} {:<16} {}", marker, library_platform, library.url);
+            if let Some(sha256) = &library.sha256 {
+                println!("    {:<16} sha256 {}", "", sha256);
+            }
+        }
+    }
+    if let Some(repository) = &entry.repository {
+        println!(
+            "Source:      {} (cargo package {})",
+            repository,
+            entry.package.as_deref().unwrap_or(&entry.name)
+        );
+    }
+
+    println!();
+    if !supported {
+        println!("❌ {} does not run on {}", entry.name, platform);
+    } else if entry.libraries.contains_key(&platform) {
+        println!("💡 Install with: proxy plugin install {}", entry.name);
+    } else if entry.repository.is_some() {
+        println!(
+            "💡 No prebuilt library for {}, build it from the source above",
+            platform
+        );
+    }
+    Ok(())
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn verify(what: &str, content: &[u8], expected: &str) -> Result<()> {
+    let actual = sha256_hex(content);
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            what,
+            expected,
+            actual
+        ));
+    }
+    Ok(())
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Refuses a plain http:// `url`, which anyone on the way can swap, unless `allow_http`
+fn refuse_http(url: &str, allow_http: bool) -> Result<()> {
+    if url.starts_with("http://") && !allow_http {
+        return Err(anyhow!(
+            "Refusing to fetch {} over plain http://, use https:// or pass --allow-http",
+            url
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `target` names a plugin in the index rather than a URL or a file
+fn is_index_name(target: &str) -> bool {
+    !(is_url(target)
+        || target.contains('/')
+        || target.contains(std::path::MAIN_SEPARATOR)
+        || Path::new(target).exists())
+}
+
+fn read_source(source: &str, allow_http: bool) -> Result<Vec<u8>> {
+    if is_url(source) {
+        refuse_http(source, allow_http)?;
+        fetch(source).map_err(|e| anyhow!("Failed to download {}: {}", source, e))
+    } else {
+        fs::read(source).map_err(|e| anyhow!("Failed to read {}: {}", source, e))
+    }
+}
+
+/// The plugin library in `content`: the content itself, or the library inside a gzipped
+/// tarball, which must hold exactly one
+fn library_from(source: &str, content: Vec<u8>) -> Result<Vec<u8>> {
+    if !content.starts_with(&[0x1f, 0x8b]) {
+        return Ok(content);
+    }
+    let plugin_api = library_file_name("plugin_api");
+    let mut archive = tar::Archive::new(GzDecoder::new(content.as_slice()));
+    let mut found = None;
+    for entry in archive
+        .entries()
+        .with_context(|| format!("Invalid archive {}", source))?
+    {
+        let mut entry = entry.with_context(|| format!("Invalid archive {}", source))?;
+        let path = entry.path()?.into_owned();
+        let is_plugin = path.extension().and_then(|s| s.to_str()) == Some(DLL_EXTENSION)
+            && path.file_name().and_then(|s| s.to_str()) != Some(plugin_api.as_str());
+        if !is_plugin {
+            continue;
+        }
+        if found.is_some() {
+            return Err(anyhow!(
+                "{} holds more than one .{} library",
+                source,
+                DLL_EXTENSION
+            ));
+        }
+        let mut library = Vec::new();
+        entry.read_to_end(&mut library)?;
+        found = Some(library);
+    }
+    found.ok_or_else(|| anyhow!("{} holds no .{} library", source, DLL_EXTENSION))
+}
+
+/// The plugin library from `source`, checked against every checksum in `expected` before
+/// anything loads it. A download needs at least one: from the index or --sha256.
+fn read_library(what: &str, source: &str, expected: &[&str], allow_http: bool) -> Result<Vec<u8>> {
+    if is_url(source) && expected.is_empty() {
+        return Err(anyhow!(
+            "No checksum for {}: pass --sha256 with the SHA-256 of {}",
+            what,
+            source
+        ));
+    }
+    let content = read_source(source, allow_http)?;
+    for expected in expected {
+        verify(what, &content, expected)?;
+    }
+    library_from(source, content)
+}
+
+/// The name and version of the plugin in the library at `path`, fetched from `source`
+fn check(path: &Path, source: &str, expected: Option<&str>) -> Result<(String, String)> {
+    let (plugin, lib) = loader::load(path)
+        .map_err(|e| anyhow!("{} cannot be loaded: {}", source, e))?
+        .ok_or_else(|| anyhow!("{} is not a proxy plugin: no create_plugin", source))?;
+    let name = plugin.name().to_string();
+    let version = plugin.version().to_string();
+    drop((plugin, lib));
+    if let Some(expected) = expected.filter(|expected| *expected != name) {
+        return Err(anyhow!(
+            "The library is the {} plugin, not {}",
+            name,
+            expected
+        ));
+    }
+    Ok((name, version))
+}
+
+/// Checks `library`, fetched from `source` and already verified, is a plugin (`expected`
+/// when given) by loading it and moves it into the plugin directory under the plugin's
+/// name. Returns the name.
+fn place(
+    plugin_dir: &Path,
+    source: &str,
+    library: &[u8],
+    expected: Option<&str>,
+) -> Result<String> {
+    // Staged where a running proxy doesn't look for plugins, so it keeps the library it
+    // loaded until the new one is renamed over it
+    let staging = plugin_dir.join(".staging");
+    fs::create_dir_all(&staging)
+        .with_context(|| format!("Failed to create {}", staging.display()))?;
+    let staged = staging.join(format!("{}.{}", std::process::id(), DLL_EXTENSION));
+    fs::write(&staged, library).with_context(|| format!("Failed to write {}", staged.display()))?;
+    let checked = check(&staged, source, expected);
+    if checked.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    let (name, version) = checked?;
+
+    let path = plugin_dir.join(library_file_name(&name));
+    let existed = path.exists();
+    fs::rename(&staged, &path).with_context(|| format!("Failed to install {}", path.display()))?;
+    let verb = if existed { "Updated" } else { "Installed" };
+    println!("✅ {} {} {} at {}", verb, name, version, path.display());
+    Ok(name)
+}
+
+fn load_sources(plugin_dir: &Path) -> BTreeMap<String, Source> {
+    fs::read(plugin_dir.join(SOURCES_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+fn record(plugin_dir: &Path, name: &str, from: &str, library: &[u8]) -> Result<()> {
+    let mut sources = load_sources(plugin_dir);
+    sources.insert(
+        name.to_string(),
+        Source {
+            from: from.to_string(),
+            sha256: sha256_hex(library),
+        },
+    );
+    let path = plugin_dir.join(SOURCES_FILE);
+    fs::write(&path, serde_json::to_string_pretty(&sources)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Downloads the library of an index entry for this platform into the plugin directory
+fn install_entry(
+    entry: &Entry,
+    plugin_dir: &Path,
+    sha256: Option<&str>,
+    allow_http: bool,
+) -> Result<()> {
+    let name = entry.name.as_str();
+    let platform = platform();
+    if !entry.platforms.is_empty() && !entry.platforms.contains(&platform) {
+        return Err(anyhow!(
+            "{} does not support {} (only {})",
+            name,
+            platform,
+            entry.platforms.join(", ")
+        ));
+    }
+
+    let Some(library) = entry.libraries.get(&platform) else {
+        return match &entry.repository {
+            Some(repository) => Err(anyhow!(
+                concat!(
+                    "No prebuilt {} for {}. Build it from {}:\n",
+                    "   cargo build --release -p {}\n",
+                    "   and copy the library from target/release/ to {}"
+                ),
+                name,
+                platform,
+                repository,
+                entry.package.as_deref().unwrap_or(name),
+                plugin_dir.join(library_file_name(name)).display()
+            )),
+            None => Err(anyhow!("No source of {} for {}", name, platform)),
+        };
+    };
+
+    println!(
+        "⬇️  Downloading {} {} from {}",
+        name, entry.version, library.url
+    );
+    let expected: Vec<&str> = library
+        .sha256
+        .as_deref()
+        .into_iter()
+        .chain(sha256)
+        .collect();
+    let content = read_library(name, &library.url, &expected, allow_http)?;
+    place(plugin_dir, &library.url, &content, Some(name))?;
+    record(plugin_dir, name, FROM_INDEX, &content)
+}
+
+/// Installs a plugin: `target` is a plugin in the index, or the URL or path of a plugin
+/// library or of a .tar.gz holding one. `sha256` is checked against the download, and
+/// a URL needs it unless the index gives one. Plain http:// is refused unless `allow_http`.
+pub fn install(
+    location: &str,
+    target: &str,
+    sha256: Option<&str>,
+    allow_http: bool,
+    plugin_dir: &Path,
+) -> Result<()> {
+    if is_index_name(target) {
+        refuse_http(location, allow_http)?;
+        let index = load_index(location)?;
+        let entry = find(&index, target, location)?;
+        return install_entry(entry, plugin_dir, sha256, allow_http);
+    }
+
+    println!("⬇️  Fetching {}", target);
+    let library = read_library(target, target, sha256.as_slice(), allow_http)?;
+    let name = place(plugin_dir, target, &library, None)?;
+    // A file by its full path, so `update` finds it from any directory
+    let from = if is_url(target) {
+        target.to_string()
+    } else {
+        fs::canonicalize(target)
+            .with_context(|| format!("Failed to resolve {}", target))?
+            .to_string_lossy()
+            .into_owned()
+    };
+    record(plugin_dir, &name, &from, &library)
+}
+
+/// Removes a plugin's library or binary from the plugin directory: the file it was
+/// `loaded` from, or, when it didn't load, the one named as `install` names them
+pub fn remove(name: &str, loaded: Option<&Path>, plugin_dir: &Path) -> Result<()> {
+    let path = match loaded {
+        Some(path) => path.to_path_buf(),
+        None => [
+            library_file_name(name),
+            format!("{}{}{}", subprocess::BINARY_PREFIX, name, EXE_SUFFIX),
+        ]
+        .iter()
+        .map(|file_name| plugin_dir.join(file_name))
+        .find(|path| path.exists())
+        .ok_or_else(|| anyhow!("{} is not installed in {}", name, plugin_dir.display()))?,
+    };
+    fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    let mut sources = load_sources(plugin_dir);
+    if sources.remove(name).is_some() {
+        let sources_path = plugin_dir.join(SOURCES_FILE);
+        fs::write(&sources_path, serde_json::to_string_pretty(&sources)?)
+            .with_context(|| format!("Failed to write {}", sources_path.display()))?;
+    }
+    println!("🗑️  Removed {} ({})", name, path.display());
+    Ok(())
+}
+
+/// Updates one installed plugin: from the file it was installed from when its library
+/// there changed, or from the index when the version there differs. A URL has no checksum
+/// for a changed library, so that is reported for reinstalling rather than loaded.
+/// Returns whether it was updated.
+fn update_one(
+    location: &str,
+    index: &mut Option<Index>,
+    source: Option<&Source>,
+    name: &str,
+    version: &str,
+    allow_http: bool,
+    plugin_dir: &Path,
+) -> Result<bool> {
+    if let Some(source) = source.filter(|source| source.from != FROM_INDEX) {
+        let content = read_source(&source.from, allow_http)?;
+        let library = library_from(&source.from, content)?;
+        if sha256_hex(&library) == source.sha256 {
+            return Ok(false);
+        }
+        if is_url(&source.from) {
+            return Err(anyhow!(
+                "{} changed, check it and reinstall with proxy plugin install {} --sha256 <SHA-256>",
+                source.from,
+                source.from
+            ));
+        }
+        place(plugin_dir, &source.from, &library, Some(name))?;
+        record(plugin_dir, name, &source.from, &library)?;
+        return Ok(true);
+    }
+
+    let index = match index {
+        Some(index) => index,
+        None => {
+            refuse_http(location, allow_http)?;
+            index.insert(load_index(location)?)
+        }
+    };
+    let entry = index
+        .plugins
+        .iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| anyhow!("{} is not in {}", name, location))?;
+    if entry.version == version {
+        return Ok(false);
+    }
+    install_entry(entry, plugin_dir, None, allow_http)?;
+    Ok(true)
+}
+
+/// Updates the plugins installed with `install`, or only `name`, which may also be one
+/// installed by hand that is in the index. `installed` holds the loaded plugins' names
+/// and versions. Plain http:// is refused unless `allow_http`.
+pub fn update(
+    location: &str,
+    name: Option<&str>,
+    installed: &[(&str, &str)],
+    allow_http: bool,
+    plugin_dir: &Path,
+) -> Result<()> {
+    let sources = load_sources(plugin_dir);
+    let plugins: Vec<(&str, &str)> = match name {
+        Some(name) => {
+            let plugin = installed
+                .iter()
+                .find(|(plugin, _)| *plugin == name)
+                .ok_or_else(|| anyhow!("{} is not installed, see proxy --list-plugins", name))?;
+            vec![*plugin]
+        }
+        None => installed
+            .iter()
+            .filter(|(plugin, _)| sources.contains_key(*plugin))
+            .copied()
+            .collect(),
+    };
+    if plugins.is_empty() {
+        println!("❌ No plugins installed with proxy plugin install");
+        return Ok(());
+    }
+
+    let mut index = None;
+    let (mut updated, mut failed) = (0, 0);
+    for (plugin, version) in plugins {
+        match update_one(
+            location,
+            &mut index,
+            sources.get(plugin),
+            plugin,
+            version,
+            allow_http,
+            plugin_dir,
+        ) {
+            Ok(true) => updated += 1,
+            Ok(false) => println!("✓ {} {} is up to date", plugin, version),
+            Err(e) => {
+                eprintln!("❌ {}: {}", plugin, e);
+                failed += 1;
+            }
+        }
+    }
+    println!();
+    println!("📦 {} plugin(s) updated", updated);
+    if failed > 0 {
+        return Err(anyhow!("{} plugin(s) could not be updated", failed));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes an index to a file of this test process and returns its path
+    fn index_file(name: &str, content: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("proxy-registry-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn json_index_entries_default_what_they_leave_out() {
+        let location = index_file(
+            "index.json",
+            r#"{"plugins": [
+                {"name": "k8s_exec", "version": "0.2.0", "description": "Shells in pods",
+                 "libraries": {"linux-x86_64": {"url": "https://example.com/libk8s_exec.so",
+                                                "sha256": "ab12"}}},
+                {"name": "vault", "repository": "https://github.com/example/vault"}
+            ]}"#,
+        );
+        let index = load_index(&location).unwrap();
+        assert_eq!(index.plugins.len(), 2);
+        let exec = &index.plugins[0];
+        assert_eq!(exec.version, "0.2.0");
+        assert!(exec.platforms.is_empty());
+        let library = &exec.libraries["linux-x86_64"];
+        assert_eq!(library.url, "https://example.com/libk8s_exec.so");
+        assert_eq!(library.sha256.as_deref(), Some("ab12"));
+        let vault = &index.plugins[1];
+        assert_eq!(vault.version, "");
+        assert!(vault.libraries.is_empty());
+        assert_eq!(vault.package, None);
+    }
+
+    #[test]
+    fn unreadable_indexes_are_reported() {
+        let missing = std::env::temp_dir().join("proxy-registry-absent.json");
+        let missing = missing.to_string_lossy();
+        assert!(load_index(&missing)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Failed to read the plugin index"));
+        let broken = index_file("broken.json", "{\"plugins\": [{}]}");
+        assert!(load_index(&broken)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid plugin index"));
+    }
+
+    #[test]
+    fn long_descriptions_are_cut_with_an_ellipsis() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate("Interactive shells", 10), "Interacti…");
+        assert_eq!(truncate("élégance and more", 4), "élé…");
+    }
+
+    /// A gzipped tarball holding the given files
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn plain_names_are_looked_up_in_the_index() {
+        assert!(is_index_name("k8s_exec"));
+        assert!(!is_index_name("https://example.com/libk8s_exec.so"));
+        assert!(!is_index_name("http://example.com/plugin.tar.gz"));
+        assert!(!is_index_name("./libk8s_exec.so"));
+        assert!(!is_index_name("target/release/libk8s_exec.so"));
+        // An existing file, here the package's manifest
+        assert!(!is_index_name("Cargo.toml"));
+    }
+
+    #[test]
+    fn libraries_pass_through_and_tarballs_give_up_theirs() {
+        let library = "\x7fELF plugin";
+        assert_eq!(
+            library_from("lib.so", library.into()).unwrap(),
+            library.as_bytes()
+        );
+
+        let plugin = format!("release/{}", library_file_name("k8s_exec"));
+        let plugin_api = format!("release/{}", library_file_name("plugin_api"));
+        let archive = tarball(&[
+            ("README.md", "docs"),
+            (plugin_api.as_str(), "api"),
+            (plugin.as_str(), library),
+        ]);
+        assert_eq!(
+            library_from("k8s_exec.tar.gz", archive).unwrap(),
+            library.as_bytes()
+        );
+    }
+
+    #[test]
+    fn tarballs_must_hold_exactly_one_library() {
+        let empty = tarball(&[("README.md", "docs")]);
+        assert!(library_from("empty.tar.gz", empty)
+            .unwrap_err()
+            .to_string()
+            .contains("holds no"));
+        let (one, two) = (library_file_name("one"), library_file_name("two"));
+        let both = tarball(&[(one.as_str(), "1"), (two.as_str(), "2")]);
+        assert!(library_from("both.tar.gz", both)
+            .unwrap_err()
+            .to_string()
+            .contains("more than one"));
+    }
+
+    #[test]
+    fn checksums_are_compared_ignoring_case() {
+        let digest = sha256_hex(b"plugin");
+        assert!(verify("k8s_exec", b"plugin", &digest.to_uppercase()).is_ok());
+        assert!(verify("k8s_exec", b"plugin!", &digest)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Checksum mismatch for k8s_exec"));
+    }
+
+    #[test]
+    fn downloads_need_a_checksum_and_https() {
+        let url = "https://example.com/libk8s_exec.so";
+        assert!(read_library("k8s_exec", url, &[], false)
+            .unwrap_err()
+            .to_string()
+            .starts_with("No checksum for k8s_exec"));
+        let digest = sha256_hex(b"plugin");
+        let plain = "http://example.com/libk8s_exec.so";
+        assert!(read_library("k8s_exec", plain, &[&digest], false)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Refusing to fetch http://example.com/libk8s_exec.so"));
+        assert!(refuse_http(plain, true).is_ok());
+        assert!(refuse_http(url, false).is_ok());
+
+        let path = std::env::temp_dir().join(format!("proxy-library-{}.so", std::process::id()));
+        fs::write(&path, b"plugin").unwrap();
+        let file = path.to_str().unwrap();
+        assert_eq!(
+            read_library("k8s_exec", file, &[], false).unwrap(),
+            b"plugin"
+        );
+        assert!(read_library("k8s_exec", file, &[&digest, "00"], false).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn toml_indexes_are_told_from_json_ones() {
+        let toml_index = r#"
+[[plugins]]
+name = "k8s_exec"
+version = "0.2.0"
+homepage = "https://example.com/k8s_exec"
+
+[plugins.libraries.linux-x86_64]
+url = "https://example.com/libk8s_exec.so"
+"#;
+        for name in ["index.toml", "index"] {
+            let index = load_index(&index_file(name, toml_index)).unwrap();
+            assert_eq!(index.plugins[0].name, "k8s_exec");
+            assert_eq!(
+                index.plugins[0].homepage.as_deref(),
+                Some("https://example.com/k8s_exec")
+            );
+            assert_eq!(
+                index.plugins[0].libraries["linux-x86_64"].url,
+                "https://example.com/libk8s_exec.so"
+            );
+        }
+        let json_index = "\n  {\"plugins\": [{\"name\": \"vault\", \"license\": \"MIT\"}]}";
+        let index = load_index(&index_file("index.txt", json_index)).unwrap();
+        assert_eq!(index.plugins[0].license.as_deref(), Some("MIT"));
+        // The extension wins over content that looks like JSON
+        assert!(load_index(&index_file("json.toml", json_index)).is_err());
+    }
+}
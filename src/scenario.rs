@@ -0,0 +1,507 @@
+// `proxy run <scenario.toml>` runs a scenario: ordered steps across plugins, e.g. start a
+// database forward, wait for it, run a load test and have ollama_chat summarize the
+// results. A step runs a plugin, a shell command or waits for an endpoint. A step runs once
+// the steps it `needs` succeeded and is skipped otherwise. Every step's output is saved in
+// the run directory, `scenarios/<name>-<time>/` in the state directory, along with the
+// final report. Background steps keep running until the scenario ends.
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long a wait_for step waits when the scenario doesn't say
+const DEFAULT_WAIT_SECS: u64 = 60;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long background steps get to exit after being interrupted
+const STOP_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct Scenario {
+    name: Option<String>,
+    #[serde(rename = "step", default)]
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Step {
+    id: String,
+    /// Runs `proxy <plugin> <args>`
+    plugin: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Runs a shell command
+    command: Option<String>,
+    /// Waits until tcp://host:port accepts connections or an http(s) URL answers 2xx/3xx
+    wait_for: Option<String>,
+    /// Steps that have to succeed first
+    #[serde(default)]
+    needs: Vec<String>,
+    /// Seconds before the step is stopped and failed
+    timeout: Option<u64>,
+    /// Keep the step running while the following ones run
+    #[serde(default)]
+    background: bool,
+    /// Step whose output is piped to this one's stdin
+    stdin: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+struct StepReport {
+    id: String,
+    status: Status,
+    seconds: f64,
+    detail: String,
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    scenario: String,
+    started: String,
+    succeeded: bool,
+    steps: Vec<StepReport>,
+}
+
+fn load(path: &Path) -> Result<Scenario> {
+    let content = plugin_api::read_config(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse(&content).map_err(|e| anyhow!("Invalid scenario {}: {}", path.display(), e))
+}
+
+/// Parses a scenario and checks its steps can run in order
+fn parse(content: &str) -> Result<Scenario> {
+    let scenario: Scenario = toml::from_str(content)?;
+    if scenario.steps.is_empty() {
+        return Err(anyhow!("No [[step]]"));
+    }
+    let mut seen: Vec<&str> = Vec::new();
+    for step in &scenario.steps {
+        if seen.contains(&step.id.as_str()) {
+            return Err(anyhow!("Step '{}' is defined twice", step.id));
+        }
+        let kinds = [
+            step.plugin.is_some(),
+            step.command.is_some(),
+            step.wait_for.is_some(),
+        ];
+        if kinds.iter().filter(|kind| **kind).count() != 1 {
+            return Err(anyhow!(
+                "Step '{}' needs exactly one of plugin, command or wait_for",
+                step.id
+            ));
+        }
+        if step.background && step.wait_for.is_some() {
+            return Err(anyhow!("Step '{}' can't wait in the background", step.id));
+        }
+        // Steps run in order, so only earlier ones can be depended on
+        for id in step.needs.iter().chain(&step.stdin) {
+            if !seen.contains(&id.as_str()) {
+                return Err(anyhow!(
+                    "Step '{}' refers to '{}', which is not an earlier step",
+                    step.id,
+                    id
+                ));
+            }
+        }
+        seen.push(&step.id);
+    }
+    Ok(scenario)
+}
+
+/// Replaces {run_dir} and {output.<id>} with the run directory and a step's output file
+fn expand(value: &str, run_dir: &Path) -> String {
+    let mut expanded = value.replace("{run_dir}", &run_dir.display().to_string());
+    while let Some(start) = expanded.find("{output.") {
+        let Some(end) = expanded[start..].find('}') else {
+            break;
+        };
+        let id = &expanded[start + "{output.".len()..start + end];
+        let path = output_path(run_dir, id).display().to_string();
+        expanded.replace_range(start..start + end + 1, &path);
+    }
+    expanded
+}
+
+fn output_path(run_dir: &Path, id: &str) -> PathBuf {
+    run_dir.join(format!("{}.log", id))
+}
+
+fn spawn(step: &Step, run_dir: &Path) -> Result<Child> {
+    let mut command = match (&step.plugin, &step.command) {
+        (Some(plugin), _) => {
+            let mut command = Command::new(std::env::current_exe()?);
+            command
+                .arg(plugin)
+                .args(step.args.iter().map(|arg| expand(arg, run_dir)));
+            command
+        }
+        (None, Some(line)) => {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(expand(line, run_dir));
+            command
+        }
+        (None, None) => unreachable!("checked when loading"),
+    };
+    let output = File::create(output_path(run_dir, &step.id))?;
+    let stdin = match &step.stdin {
+        Some(id) => Stdio::from(File::open(output_path(run_dir, id))?),
+        None => Stdio::null(),
+    };
+    command
+        .env("PROXY_SCENARIO_DIR", run_dir)
+        .stdin(stdin)
+        .stdout(output.try_clone()?)
+        .stderr(output);
+    Ok(command.spawn()?)
+}
+
+/// Interrupts a background step as Ctrl-C would, killing it if it doesn't exit in time
+fn stop(child: &mut Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(child.id() as i32, libc::SIGINT);
+    }
+    let deadline = Instant::now() + STOP_GRACE;
+    while Instant::now() < deadline {
+        if let Ok(Some(_)) = child.try_wait() {
+            return;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn reachable(target: &str) -> bool {
+    if let Some(address) = target.strip_prefix("tcp://") {
+        let Some(address) = address.to_socket_addrs().ok().and_then(|mut a| a.next()) else {
+            return false;
+        };
+        return TcpStream::connect_timeout(&address, POLL_INTERVAL * 4).is_ok();
+    }
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    else {
+        return false;
+    };
+    runtime.block_on(async {
+        let client = reqwest::Client::builder()
+            .timeout(POLL_INTERVAL * 4)
+            .build();
+        match client {
+            Ok(client) => client.get(target).send().await.is_ok_and(|response| {
+                response.status().is_success() || response.status().is_redirection()
+            }),
+            Err(_) => false,
+        }
+    })
+}
+
+fn wait_for(target: &str, timeout: Duration) -> Result<String> {
+    if !target.starts_with("tcp://")
+        && !target.starts_with("http://")
+        && !target.starts_with("https://")
+    {
+        return Err(anyhow!("wait_for takes tcp://host:port or an http(s) URL"));
+    }
+    let started = Instant::now();
+    loop {
+        if reachable(target) {
+            return Ok(format!("{} is up", target));
+        }
+        if started.elapsed() >= timeout {
+            return Err(anyhow!("{} not up after {}s", target, timeout.as_secs()));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Runs a foreground step to completion, stopping it at its timeout
+fn run_to_end(mut child: Child, timeout: Option<Duration>) -> Result<String> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return if status.success() {
+                Ok("exited successfully".to_string())
+            } else {
+                Err(anyhow!("exited with {}", status))
+            };
+        }
+        if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+            stop(&mut child);
+            return Err(anyhow!(
+                "timed out after {}s",
+                timeout.unwrap_or_default().as_secs()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Starts a background step; it fails if it exits right away
+fn run_in_background(
+    mut child: Child,
+    background: &mut Vec<(String, Child)>,
+    id: &str,
+) -> Result<String> {
+    std::thread::sleep(POLL_INTERVAL);
+    if let Some(status) = child.try_wait()? {
+        return Err(anyhow!("exited right away with {}", status));
+    }
+    let detail = format!("running in the background (pid {})", child.id());
+    background.push((id.to_string(), child));
+    Ok(detail)
+}
+
+fn run_step(step: &Step, run_dir: &Path, background: &mut Vec<(String, Child)>) -> Result<String> {
+    let timeout = step.timeout.map(Duration::from_secs);
+    if let Some(target) = &step.wait_for {
+        let timeout = timeout.unwrap_or(Duration::from_secs(DEFAULT_WAIT_SECS));
+        return wait_for(&expand(target, run_dir), timeout);
+    }
+    let child = spawn(step, run_dir)?;
+    if step.background {
+        run_in_background(child, background, &step.id)
+    } else {
+        run_to_end(child, timeout)
+    }
+}
+
+fn print_report(report: &Report, run_dir: &Path) {
+    println!();
+    println!("📋 Scenario {}", report.scenario);
+    println!("  {:<20} {:>8}  DETAIL", "STEP", "TIME");
+    for step in &report.steps {
+        let icon = match step.status {
+            Status::Succeeded => "✅",
+            Status::Failed => "❌",
+            Status::Skipped => "⏭️ ",
+        };
+        println!(
+            "{} {:<20} {:>7.1}s  {}",
+            icon, step.id, step.seconds, step.detail
+        );
+    }
+    println!();
+    println!("📂 Step output and report.json: {}", run_dir.display());
+}
+
+/// Runs the scenario's steps in order and reports on them; fails when a step failed
+pub fn run(path: &Path) -> Result<()> {
+    let scenario = load(path)?;
+    let name = scenario.name.clone().unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "scenario".to_string())
+    });
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let run_dir = plugin_api::plugin_state_dir("scenarios")
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))?
+        .join(format!("{}-{}", name, started_at));
+    fs::create_dir_all(&run_dir)
+        .with_context(|| format!("Failed to create {}", run_dir.display()))?;
+
+    let mut background = Vec::new();
+    let mut reports: Vec<StepReport> = Vec::new();
+    for step in &scenario.steps {
+        let unmet = step.needs.iter().find(|id| {
+            reports
+                .iter()
+                .any(|report| &report.id == *id && report.status != Status::Succeeded)
+        });
+        if let Some(id) = unmet {
+            println!("⏭️  {}: skipped, {} did not succeed", step.id, id);
+            reports.push(StepReport {
+                id: step.id.clone(),
+                status: Status::Skipped,
+                seconds: 0.0,
+                detail: format!("{} did not succeed", id),
+                output: None,
+            });
+            continue;
+        }
+
+        println!("▶️  {}", step.id);
+        let started = Instant::now();
+        let result = run_step(step, &run_dir, &mut background);
+        let output = Some(output_path(&run_dir, &step.id)).filter(|path| path.exists());
+        let (status, detail) = match result {
+            Ok(detail) => {
+                println!("✅ {}: {}", step.id, detail);
+                (Status::Succeeded, detail)
+            }
+            Err(e) => {
+                println!("❌ {}: {}", step.id, e);
+                (Status::Failed, e.to_string())
+            }
+        };
+        reports.push(StepReport {
+            id: step.id.clone(),
+            status,
+            seconds: started.elapsed().as_secs_f64(),
+            detail,
+            output,
+        });
+    }
+
+    for (id, mut child) in background.into_iter().rev() {
+        println!("⏹️  Stopping {}", id);
+        stop(&mut child);
+    }
+
+    let report = Report {
+        scenario: name,
+        started: chrono::DateTime::from_timestamp(started_at as i64, 0)
+            .unwrap_or_default()
+            .to_rfc3339(),
+        succeeded: reports
+            .iter()
+            .all(|report| report.status == Status::Succeeded),
+        steps: reports,
+    };
+    fs::write(
+        run_dir.join("report.json"),
+        serde_json::to_string_pretty(&report)?,
+    )?;
+    print_report(&report, &run_dir);
+    if report.succeeded {
+        Ok(())
+    } else {
+        let failed = report
+            .steps
+            .iter()
+            .filter(|step| step.status != Status::Succeeded)
+            .count();
+        Err(anyhow!(
+            "{} of {} steps did not succeed",
+            failed,
+            report.steps.len()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(content: &str) -> String {
+        parse(content).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn steps_parse_in_order_with_defaults() {
+        let scenario = parse(
+            r#"
+name = "smoke"
+
+[[step]]
+id = "db"
+plugin = "k8s_port_forward"
+args = ["db"]
+background = true
+
+[[step]]
+id = "up"
+wait_for = "tcp://localhost:5432"
+needs = ["db"]
+
+[[step]]
+id = "count"
+command = "psql -c 'select 1'"
+needs = ["up"]
+timeout = 30
+
+[[step]]
+id = "summary"
+plugin = "ollama_chat"
+stdin = "count"
+"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.name.as_deref(), Some("smoke"));
+        let ids: Vec<&str> = scenario.steps.iter().map(|step| step.id.as_str()).collect();
+        assert_eq!(ids, ["db", "up", "count", "summary"]);
+        assert!(scenario.steps[0].background);
+        assert_eq!(scenario.steps[0].args, ["db"]);
+        assert_eq!(scenario.steps[2].timeout, Some(30));
+        assert!(scenario.steps[3].needs.is_empty());
+        assert_eq!(scenario.steps[3].stdin.as_deref(), Some("count"));
+    }
+
+    #[test]
+    fn steps_need_exactly_one_kind_and_a_unique_id() {
+        assert_eq!(error("name = \"empty\""), "No [[step]]");
+        assert!(error("[[step]]\nid = \"a\"").contains("exactly one of"));
+        assert!(
+            error("[[step]]\nid = \"a\"\ncommand = \"true\"\nwait_for = \"tcp://x:1\"")
+                .contains("exactly one of")
+        );
+        assert_eq!(
+            error("[[step]]\nid = \"a\"\ncommand = \"true\"\n[[step]]\nid = \"a\"\ncommand = \"true\""),
+            "Step 'a' is defined twice"
+        );
+        assert_eq!(
+            error("[[step]]\nid = \"a\"\nwait_for = \"tcp://x:1\"\nbackground = true"),
+            "Step 'a' can't wait in the background"
+        );
+    }
+
+    #[test]
+    fn needs_and_stdin_refer_to_earlier_steps() {
+        let later = r#"
+[[step]]
+id = "a"
+command = "true"
+needs = ["b"]
+
+[[step]]
+id = "b"
+command = "true"
+"#;
+        assert_eq!(
+            error(later),
+            "Step 'a' refers to 'b', which is not an earlier step"
+        );
+        let itself = "[[step]]\nid = \"a\"\ncommand = \"cat\"\nstdin = \"a\"";
+        assert_eq!(
+            error(itself),
+            "Step 'a' refers to 'a', which is not an earlier step"
+        );
+        let unknown = "[[step]]\nid = \"a\"\ncommand = \"true\"\nneeds = [\"c\"]";
+        assert!(error(unknown).contains("'c'"));
+    }
+
+    #[test]
+    fn run_dir_and_step_outputs_are_expanded() {
+        let run_dir = Path::new("/tmp/run");
+        assert_eq!(
+            expand("{run_dir}/report.json", run_dir),
+            format!("{}/report.json", run_dir.display())
+        );
+        assert_eq!(
+            expand("--input {output.load} --baseline {output.base}", run_dir),
+            format!(
+                "--input {} --baseline {}",
+                run_dir.join("load.log").display(),
+                run_dir.join("base.log").display()
+            )
+        );
+        assert_eq!(expand("{output.open", run_dir), "{output.open");
+        assert_eq!(expand("{output}", run_dir), "{output}");
+    }
+}
@@ -0,0 +1,206 @@
+// `proxy usage` sums up the usage files the plugins keep (see plugin_api::usage): per
+// plugin and target, how many sessions were opened, how long the target was in use and
+// how much data moved, over the targets used in the given period.
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::audit::parse_since;
+
+#[derive(Debug, Deserialize)]
+struct UsageFile {
+    plugin: String,
+    targets: Vec<Target>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Target {
+    target: String,
+    #[serde(default)]
+    context: String,
+    last_used: String,
+    sessions: u64,
+    active_secs: f64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Total {
+    plugin: String,
+    target: String,
+    context: String,
+    sessions: u64,
+    active_secs: f64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    #[serde(serialize_with = "rfc3339")]
+    last_used: DateTime<Utc>,
+}
+
+fn rfc3339<S: serde::Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+pub struct Filter {
+    pub since: String,
+    pub plugin: Option<String>,
+}
+
+/// The targets used since the filter's time, summed over the plugin processes
+fn totals(filter: &Filter) -> Result<Vec<Total>> {
+    let since = parse_since(&filter.since)?;
+    let dir = plugin_api::usage::usage_dir()
+        .ok_or_else(|| anyhow!("Could not determine the state directory"))?;
+    let mut totals: BTreeMap<(String, String, String), Total> = BTreeMap::new();
+    for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(file) = fs::read(&path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<UsageFile>(&content).ok())
+        else {
+            continue;
+        };
+        if filter
+            .plugin
+            .as_deref()
+            .is_some_and(|plugin| plugin != file.plugin)
+        {
+            continue;
+        }
+        for target in file.targets {
+            let Ok(last_used) = DateTime::parse_from_rfc3339(&target.last_used) else {
+                continue;
+            };
+            let last_used = last_used.with_timezone(&Utc);
+            if last_used < since {
+                continue;
+            }
+            let key = (
+                file.plugin.clone(),
+                target.target.clone(),
+                target.context.clone(),
+            );
+            let total = totals.entry(key).or_insert_with(|| Total {
+                plugin: file.plugin.clone(),
+                target: target.target,
+                context: target.context,
+                sessions: 0,
+                active_secs: 0.0,
+                bytes_sent: 0,
+                bytes_received: 0,
+                last_used,
+            });
+            total.sessions += target.sessions;
+            total.active_secs += target.active_secs;
+            total.bytes_sent += target.bytes_sent;
+            total.bytes_received += target.bytes_received;
+            total.last_used = total.last_used.max(last_used);
+        }
+    }
+    let mut totals: Vec<Total> = totals.into_values().collect();
+    totals.sort_by(|a, b| {
+        (b.bytes_sent + b.bytes_received)
+            .cmp(&(a.bytes_sent + a.bytes_received))
+            .then(b.active_secs.total_cmp(&a.active_secs))
+    });
+    Ok(totals)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn format_duration(secs: f64) -> String {
+    let secs = secs as u64;
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Prints the usage since `filter.since` as a table, or as JSON
+pub fn report(filter: &Filter, json: bool) -> Result<()> {
+    let totals = totals(filter)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&totals)?);
+        return Ok(());
+    }
+    if totals.is_empty() {
+        println!("No usage recorded since {}", filter.since);
+        return Ok(());
+    }
+
+    println!(
+        "{:<24} {:<32} {:<20} {:>8} {:>9} {:>11} {:>11}  LAST USED",
+        "PLUGIN", "TARGET", "CONTEXT", "SESSIONS", "TIME", "SENT", "RECEIVED"
+    );
+    for total in &totals {
+        println!(
+            "{:<24} {:<32} {:<20} {:>8} {:>9} {:>11} {:>11}  {}",
+            total.plugin,
+            total.target,
+            total.context,
+            total.sessions,
+            format_duration(total.active_secs),
+            format_bytes(total.bytes_sent),
+            format_bytes(total.bytes_received),
+            total
+                .last_used
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+        );
+    }
+    let sent: u64 = totals.iter().map(|total| total.bytes_sent).sum();
+    let received: u64 = totals.iter().map(|total| total.bytes_received).sum();
+    println!();
+    println!(
+        "📊 {} targets since {}: {} sent, {} received",
+        totals.len(),
+        filter.since,
+        format_bytes(sent),
+        format_bytes(received)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_are_shown_in_binary_units() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+        assert_eq!(format_bytes(u64::MAX), "16777216.0 TiB");
+    }
+
+    #[test]
+    fn durations_are_shown_in_their_two_largest_units() {
+        assert_eq!(format_duration(59.9), "59s");
+        assert_eq!(format_duration(61.0), "1m01s");
+        assert_eq!(format_duration(3600.0), "1h00m");
+        assert_eq!(format_duration(90061.0), "25h01m");
+    }
+}